@@ -0,0 +1,98 @@
+//! Compares idle-poll recording while snapshot readers hammer the status.
+//!
+//! `locked` reproduces the previous single-`RwLock<StatusSnapshot>` design so
+//! the atomic counters in `SharedStatus` can be measured against it.
+
+use criterion::{Criterion, criterion_group, criterion_main};
+use musicbox::controller::{CardUid, ControllerAction, Track};
+use musicbox::telemetry::{SharedStatus, StatusSnapshot};
+use std::hint::black_box;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, RwLock};
+use std::thread;
+use std::time::SystemTime;
+
+const READERS: usize = 2;
+
+/// Snapshots carry the last action, so readers clone it as the display does.
+fn sample_action() -> ControllerAction {
+    ControllerAction::Started {
+        card: CardUid::new(vec![0x04, 0xa0, 0xb1, 0xc2]),
+        track: Track::new("/home/pi/music/album/track02.ogg".into()),
+    }
+}
+
+#[derive(Clone, Default)]
+struct LockedStatus {
+    inner: Arc<RwLock<StatusSnapshot>>,
+}
+
+impl LockedStatus {
+    fn record_idle(&self) {
+        let mut guard = self.inner.write().expect("status write lock");
+        guard.last_update = Some(SystemTime::now());
+        guard.idle_events += 1;
+    }
+
+    fn snapshot(&self) -> StatusSnapshot {
+        self.inner.read().expect("status read lock").clone()
+    }
+}
+
+/// Runs `record` on the bench thread while `READERS` threads take snapshots.
+fn with_readers<S, R>(c: &mut Criterion, name: &str, status: S, snapshot: fn(&S), record: R)
+where
+    S: Clone + Send + 'static,
+    R: Fn(&S),
+{
+    let stop = Arc::new(AtomicBool::new(false));
+    let readers: Vec<_> = (0..READERS)
+        .map(|_| {
+            let status = status.clone();
+            let stop = stop.clone();
+            thread::spawn(move || {
+                while !stop.load(Ordering::Relaxed) {
+                    snapshot(&status);
+                }
+            })
+        })
+        .collect();
+
+    c.bench_function(name, |b| b.iter(|| record(&status)));
+
+    stop.store(true, Ordering::Relaxed);
+    for reader in readers {
+        reader.join().expect("snapshot reader");
+    }
+}
+
+fn record_idle_under_contention(c: &mut Criterion) {
+    let locked = LockedStatus::default();
+    locked.inner.write().expect("status write lock").last_action = Some(sample_action());
+    let atomic = SharedStatus::default();
+    atomic.record_action(sample_action());
+
+    with_readers(
+        c,
+        "record_idle/locked",
+        locked,
+        |status| {
+            black_box(status.snapshot());
+        },
+        |status| status.record_idle(),
+    );
+    with_readers(
+        c,
+        "record_idle/atomic",
+        atomic,
+        |status| {
+            black_box(status.snapshot());
+        },
+        |status| {
+            black_box(status.record_idle());
+        },
+    );
+}
+
+criterion_group!(benches, record_idle_under_contention);
+criterion_main!(benches);
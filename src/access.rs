@@ -0,0 +1,234 @@
+//! Who may use the web API, and for what.
+//!
+//! With no `[[api_tokens]]` in the config the API stays open, as it always
+//! was on a trusted network. Once any token is listed, every `/api` request
+//! must present one, as a bearer token or as the password of HTTP basic
+//! auth with the token's name as the user, which browsers can prompt for.
+//! A guest token is meant for a babysitter's phone: it can start and pause
+//! cards and turn the volume up only as far as its `max_volume`, but cannot
+//! read or rewrite the config or download tracks.
+//!
+//! A read-only API, set with [`WebSettings::read_only`], answers reads and
+//! refuses every request that would change the box, whoever sends it.
+
+/// What a token may do.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TokenScope {
+    /// Everything the API offers.
+    Full,
+    /// Playback controls only.
+    #[default]
+    Guest,
+}
+
+/// One `[[api_tokens]]` entry.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ApiToken {
+    /// Shown in logs in place of the token itself.
+    pub name: String,
+    pub token: String,
+    pub scope: TokenScope,
+    /// Loudest volume, from 0.0 to 1.0, this token may set.
+    pub max_volume: Option<f32>,
+}
+
+impl ApiToken {
+    pub fn grant(&self) -> Grant {
+        Grant {
+            scope: self.scope,
+            max_volume: self.max_volume,
+        }
+    }
+}
+
+/// The tokens a box accepts.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ApiTokens {
+    tokens: Vec<ApiToken>,
+}
+
+impl ApiTokens {
+    pub fn new(tokens: Vec<ApiToken>) -> Self {
+        Self { tokens }
+    }
+
+    /// True when the API is open to anyone who can reach it.
+    pub fn is_empty(&self) -> bool {
+        self.tokens.is_empty()
+    }
+
+    /// The entry whose token is `presented`, compared in constant time so
+    /// response times do not give a token away byte by byte.
+    pub fn find(&self, presented: &str) -> Option<&ApiToken> {
+        self.tokens
+            .iter()
+            .find(|entry| constant_time_eq(entry.token.as_bytes(), presented.as_bytes()))
+    }
+
+    /// What a request with the given `Authorization` header may do, or
+    /// `None` if it must be turned away.
+    pub fn authorize(&self, header: Option<&str>) -> Option<(Grant, Option<&str>)> {
+        if self.is_empty() {
+            return Some((Grant::FULL, None));
+        }
+        let header = header?;
+        let entry = if let Some(presented) = header.strip_prefix("Bearer ") {
+            self.find(presented.trim())?
+        } else {
+            let encoded = header.strip_prefix("Basic ")?.trim();
+            let decoded = String::from_utf8(decode_base64(encoded)?).ok()?;
+            let (user, password) = decoded.split_once(':')?;
+            self.find(password).filter(|entry| entry.name == user)?
+        };
+        Some((entry.grant(), Some(entry.name.as_str())))
+    }
+}
+
+/// How large a file `POST /api/upload` takes unless `[web]` says
+/// otherwise: 200 MB, enough for a long audiobook chapter.
+pub const DEFAULT_MAX_UPLOAD_BYTES: u64 = 200 * 1024 * 1024;
+
+/// The optional `[web]` table.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WebSettings {
+    /// Refuses every API request that would change the box.
+    pub read_only: bool,
+    /// The largest file an upload may write; 0 turns uploads off.
+    pub max_upload_bytes: u64,
+}
+
+impl Default for WebSettings {
+    fn default() -> Self {
+        Self {
+            read_only: false,
+            max_upload_bytes: DEFAULT_MAX_UPLOAD_BYTES,
+        }
+    }
+}
+
+/// What one request may do.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Grant {
+    pub scope: TokenScope,
+    pub max_volume: Option<f32>,
+}
+
+impl Grant {
+    pub const FULL: Self = Self {
+        scope: TokenScope::Full,
+        max_volume: None,
+    };
+
+    pub fn is_full(&self) -> bool {
+        self.scope == TokenScope::Full
+    }
+
+    /// `volume` lowered to this grant's cap.
+    pub fn cap_volume(&self, volume: f32) -> f32 {
+        match self.max_volume {
+            Some(max) => volume.min(max),
+            None => volume,
+        }
+    }
+}
+
+/// Standard base64 with padding, as HTTP basic auth sends it.
+fn decode_base64(text: &str) -> Option<Vec<u8>> {
+    let text = text.trim_end_matches('=');
+    let mut bytes = Vec::with_capacity(text.len() * 3 / 4);
+    let mut buffer = 0u32;
+    let mut bits = 0;
+    for c in text.bytes() {
+        let value = match c {
+            b'A'..=b'Z' => c - b'A',
+            b'a'..=b'z' => c - b'a' + 26,
+            b'0'..=b'9' => c - b'0' + 52,
+            b'+' => 62,
+            b'/' => 63,
+            _ => return None,
+        };
+        buffer = buffer << 6 | u32::from(value);
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            bytes.push((buffer >> bits) as u8);
+        }
+    }
+    Some(bytes)
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    a.iter().zip(b).fold(0u8, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tokens() -> ApiTokens {
+        ApiTokens::new(vec![
+            ApiToken {
+                name: "parents".to_string(),
+                token: "full-secret".to_string(),
+                scope: TokenScope::Full,
+                max_volume: None,
+            },
+            ApiToken {
+                name: "babysitter".to_string(),
+                token: "guest-secret".to_string(),
+                scope: TokenScope::Guest,
+                max_volume: Some(0.4),
+            },
+        ])
+    }
+
+    #[test]
+    fn no_tokens_leaves_the_api_open() {
+        let open = ApiTokens::default();
+        assert_eq!(open.authorize(None), Some((Grant::FULL, None)));
+        assert_eq!(
+            open.authorize(Some("Bearer anything")),
+            Some((Grant::FULL, None))
+        );
+    }
+
+    #[test]
+    fn bearer_tokens_map_to_their_scope() {
+        let tokens = tokens();
+        let (grant, name) = tokens.authorize(Some("Bearer guest-secret")).unwrap();
+        assert!(!grant.is_full());
+        assert_eq!(name, Some("babysitter"));
+        assert_eq!(grant.cap_volume(0.9), 0.4);
+        assert_eq!(grant.cap_volume(0.2), 0.2);
+
+        let (grant, _) = tokens.authorize(Some("Bearer full-secret")).unwrap();
+        assert!(grant.is_full());
+        assert_eq!(grant.cap_volume(0.9), 0.9);
+
+        assert_eq!(tokens.authorize(None), None);
+        assert_eq!(tokens.authorize(Some("Bearer guest")), None);
+        assert_eq!(tokens.authorize(Some("guest-secret")), None);
+    }
+
+    #[test]
+    fn basic_auth_takes_the_token_name_and_token() {
+        let tokens = tokens();
+        // "parents:full-secret"
+        let (grant, name) = tokens
+            .authorize(Some("Basic cGFyZW50czpmdWxsLXNlY3JldA=="))
+            .unwrap();
+        assert!(grant.is_full());
+        assert_eq!(name, Some("parents"));
+        // "babysitter:full-secret": the right token under the wrong name.
+        assert_eq!(
+            tokens.authorize(Some("Basic YmFieXNpdHRlcjpmdWxsLXNlY3JldA==")),
+            None
+        );
+        assert_eq!(tokens.authorize(Some("Basic not base64!")), None);
+        assert_eq!(decode_base64("TWFu").as_deref(), Some(b"Man".as_slice()));
+        assert_eq!(decode_base64("TWE=").as_deref(), Some(b"Ma".as_slice()));
+    }
+}
@@ -1,9 +1,16 @@
 use crate::config::{ConfigError, MusicBoxConfig};
 use crate::controller::{AudioPlayer, ControllerAction, ControllerError, MusicBoxController};
-use crate::reader::{NfcReader, ReaderError, ReaderEvent};
+use crate::events::{EngineEvent, EventBus};
+use crate::input::ButtonSource;
+use crate::mirror;
+use crate::night;
+use crate::reader::{NfcReader, ReaderCapabilities, ReaderError, ReaderEvent};
+use crate::telemetry::{ReaderHealth, TapLatency};
 use std::fs::File;
 use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime};
 
 #[derive(Debug, thiserror::Error)]
 pub enum AppError {
@@ -17,21 +24,204 @@ pub enum AppError {
     Config(#[from] ConfigError),
 }
 
-/// Creates a `MusicBoxController` from a configuration file.
-pub fn controller_from_config_path<P: AudioPlayer>(
-    path: impl AsRef<Path>,
-    player: P,
-) -> Result<MusicBoxController<P>, AppError> {
+/// Loads and validates a configuration file.
+pub fn load_config(path: impl AsRef<Path>) -> Result<MusicBoxConfig, AppError> {
     let path_ref = path.as_ref();
     let file = File::open(path_ref).map_err(|source| AppError::OpenConfig {
         path: path_ref.into(),
         source,
     })?;
-    let config = MusicBoxConfig::from_reader(file)?;
-    let library = config.into_library();
+    let config_dir = path_ref.parent().unwrap_or(Path::new(""));
+    Ok(MusicBoxConfig::from_reader_in(file, config_dir)?)
+}
+
+/// Creates a `MusicBoxController` from a configuration file.
+pub fn controller_from_config_path<P: AudioPlayer>(
+    path: impl AsRef<Path>,
+    player: P,
+) -> Result<MusicBoxController<P>, AppError> {
+    let library = load_config(path)?.into_library();
     Ok(MusicBoxController::new(library, player))
 }
 
+/// Re-reads the config at `path` and swaps its cards into `controller`.
+/// A config that fails to load leaves the current cards in place.
+pub fn reload_library<P: AudioPlayer>(
+    controller: &Mutex<MusicBoxController<P>>,
+    path: impl AsRef<Path>,
+) -> Result<(), AppError> {
+    let library = load_config(&path)?.into_library();
+    // The file loaded, so it is a good copy to fall back on.
+    mirror::update(path.as_ref());
+    controller
+        .lock()
+        .expect("controller lock")
+        .replace_library(library);
+    Ok(())
+}
+
+/// How often [`ConfigWatcher::spawn`] looks at the config file.
+pub const CONFIG_WATCH_INTERVAL: Duration = Duration::from_secs(1);
+
+/// How long the config must stay unchanged before it is reloaded, so an
+/// editor's save or a copy in progress is read once, whole.
+pub const CONFIG_RELOAD_DEBOUNCE: Duration = Duration::from_millis(500);
+
+/// Notices when the config file changes on disk and reloads its cards, so
+/// edits over SSH or from another instance's debug UI apply without a
+/// restart.
+///
+/// The file's modification time and size are polled rather than watched
+/// with inotify, which keeps it working on any filesystem the config might
+/// live on, including network mounts.
+#[derive(Debug)]
+pub struct ConfigWatcher {
+    path: PathBuf,
+    debounce: Duration,
+    seen: Option<FileStamp>,
+    changed_at: Option<Instant>,
+}
+
+/// What a change to the config file is detected by.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct FileStamp {
+    modified: Option<SystemTime>,
+    len: u64,
+}
+
+impl FileStamp {
+    fn read(path: &Path) -> Option<Self> {
+        let metadata = std::fs::metadata(path).ok()?;
+        Some(Self {
+            modified: metadata.modified().ok(),
+            len: metadata.len(),
+        })
+    }
+}
+
+impl ConfigWatcher {
+    /// Starts watching `path`; its current contents count as already loaded.
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        let path = path.into();
+        let seen = FileStamp::read(&path);
+        Self {
+            path,
+            debounce: CONFIG_RELOAD_DEBOUNCE,
+            seen,
+            changed_at: None,
+        }
+    }
+
+    pub fn with_debounce(mut self, debounce: Duration) -> Self {
+        self.debounce = debounce;
+        self
+    }
+
+    /// Whether the file changed and has since been left alone for the
+    /// debounce period. A missing file, such as mid-way through an editor's
+    /// rename, is not a change.
+    pub fn poll(&mut self, now: Instant) -> bool {
+        let stamp = FileStamp::read(&self.path);
+        if stamp.is_some() && stamp != self.seen {
+            self.seen = stamp;
+            self.changed_at = Some(now);
+            return false;
+        }
+        match self.changed_at {
+            Some(changed) if now.duration_since(changed) >= self.debounce => {
+                self.changed_at = None;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Polls every `interval` on a background thread, reloading the cards
+    /// into `controller` after each change. A config that fails to load is
+    /// logged and the current cards stay in place.
+    pub fn spawn<P: AudioPlayer + Send + 'static>(
+        mut self,
+        controller: Arc<Mutex<MusicBoxController<P>>>,
+        interval: Duration,
+    ) -> std::thread::JoinHandle<()> {
+        std::thread::spawn(move || {
+            loop {
+                std::thread::sleep(interval);
+                if !self.poll(Instant::now()) {
+                    continue;
+                }
+                match reload_library(&controller, &self.path) {
+                    Ok(()) => tracing::info!(path = ?self.path, "config changed; cards reloaded"),
+                    Err(err) => {
+                        tracing::warn!(%err, "config changed but failed to load; keeping the current cards")
+                    }
+                }
+            }
+        })
+    }
+}
+
+/// Set by the SIGINT and SIGTERM handlers that
+/// [`ShutdownToken::from_signals`] installs.
+static SIGNALLED: AtomicBool = AtomicBool::new(false);
+
+/// Asks [`run_until_shutdown`] to return after the event it is handling,
+/// so the caller can stop playback and put the hardware to sleep.
+#[derive(Debug, Clone, Default)]
+pub struct ShutdownToken {
+    requested: Arc<AtomicBool>,
+    signals: bool,
+}
+
+impl ShutdownToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A token that SIGINT (Ctrl-C) and SIGTERM (`systemctl stop`) also
+    /// request. A second signal kills the process straight away, in case
+    /// shutting down hangs.
+    pub fn from_signals() -> Self {
+        install_signal_handlers();
+        Self {
+            signals: true,
+            ..Self::default()
+        }
+    }
+
+    pub fn request(&self) {
+        self.requested.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_requested(&self) -> bool {
+        self.requested.load(Ordering::SeqCst) || (self.signals && SIGNALLED.load(Ordering::SeqCst))
+    }
+}
+
+#[cfg(unix)]
+fn install_signal_handlers() {
+    extern "C" fn handle(signal: libc::c_int) {
+        SIGNALLED.store(true, Ordering::SeqCst);
+        // SAFETY: `signal` is async-signal-safe; restoring the default
+        // action lets a second signal end the process.
+        unsafe {
+            libc::signal(signal, libc::SIG_DFL);
+        }
+    }
+    for signal in [libc::SIGINT, libc::SIGTERM] {
+        // SAFETY: the handler only stores to an atomic and calls `signal`.
+        unsafe {
+            libc::signal(
+                signal,
+                handle as extern "C" fn(libc::c_int) as libc::sighandler_t,
+            );
+        }
+    }
+}
+
+#[cfg(not(unix))]
+fn install_signal_handlers() {}
+
 #[derive(Debug, thiserror::Error)]
 pub enum RunLoopError {
     #[error("reader error: {0}")]
@@ -57,51 +247,401 @@ where
     P: AudioPlayer,
 {
     let event = reader.next_event()?;
+    if let ReaderEvent::CardPresent { uid, .. } | ReaderEvent::CardRemoved { uid } = &event
+        && controller.is_bounce(uid, Instant::now())
+    {
+        tracing::debug!(%uid, "ignoring repeated reader event");
+        return Ok(ProcessOutcome::NoEvent);
+    }
     match event {
-        ReaderEvent::CardPresent { uid } => {
-            let action = controller.handle_card(&uid)?;
+        ReaderEvent::CardPresent {
+            uid,
+            ndef_text,
+            reader,
+            ..
+        } => {
+            let action = controller.handle_tap_at(&uid, ndef_text.as_deref(), reader.as_deref())?;
             Ok(ProcessOutcome::Action(action))
         }
+        ReaderEvent::CardRemoved { uid } => Ok(controller
+            .handle_removal(&uid)?
+            .map_or(ProcessOutcome::NoEvent, ProcessOutcome::Action)),
         ReaderEvent::Idle => Ok(ProcessOutcome::NoEvent),
         ReaderEvent::Shutdown => Ok(ProcessOutcome::Shutdown),
     }
 }
 
-/// Runs the main event loop until the reader requests a shutdown.
-pub fn run_until_shutdown<R, P, OnAction, OnIdle>(
+/// How long after a tap the run loop waits for the player's first sound
+/// before reporting the tap's latency without it.
+const FIRST_SOUND_WAIT: Duration = Duration::from_secs(5);
+
+/// A tap that started a track, waiting for the player to make a sound.
+struct PendingTap {
+    detected: Instant,
+    decided: Instant,
+}
+
+impl PendingTap {
+    /// The tap's latency, once the player has sounded since the tap or the
+    /// wait has run out.
+    fn finish(&self, first_sound: Option<Instant>, now: Instant) -> Option<TapLatency> {
+        let sound = first_sound.filter(|at| *at >= self.detected);
+        if sound.is_none() && now.duration_since(self.detected) < FIRST_SOUND_WAIT {
+            return None;
+        }
+        Some(TapLatency {
+            decision: self.decided - self.detected,
+            sound: sound.map(|at| at - self.detected),
+        })
+    }
+}
+
+/// Runs the main event loop until the reader requests a shutdown or the
+/// shutdown card is tapped, publishing what happens on `events`. The
+/// configured startup action runs first; if it fails, the loop starts
+/// anyway. Button presses are picked up after every reader event. Taps on
+/// expired or misplaced cards are published as [`EngineEvent::Rejected`]
+/// and the loop carries on. Each accepted tap's latency, from the reader's
+/// report to the first sound, is published as [`EngineEvent::Latency`]; a
+/// tap overtaken by the next one before it sounds is not reported. A tap
+/// that brings the tag's read counter also publishes
+/// [`EngineEvent::TagCounter`]. Every
+/// idle reader poll publishes [`EngineEvent::Idle`] and then waits
+/// `idle_pause`. [`EngineEvent::Stopped`] comes last, even when the loop
+/// fails.
+pub fn run_until_shutdown<R, B, P>(
     controller: Arc<Mutex<MusicBoxController<P>>>,
     reader: &mut R,
-    mut on_action: OnAction,
-    mut on_idle: OnIdle,
+    buttons: &mut B,
+    shutdown: &ShutdownToken,
+    events: &EventBus,
+    idle_pause: Duration,
 ) -> Result<(), RunLoopError>
 where
     R: NfcReader,
+    B: ButtonSource + ?Sized,
     P: AudioPlayer,
-    OnAction: FnMut(&ControllerAction),
-    OnIdle: FnMut(),
 {
+    let result = run_loop(controller, reader, buttons, shutdown, events, idle_pause);
+    events.publish(EngineEvent::Stopped);
+    result
+}
+
+fn run_loop<R, B, P>(
+    controller: Arc<Mutex<MusicBoxController<P>>>,
+    reader: &mut R,
+    buttons: &mut B,
+    shutdown: &ShutdownToken,
+    events: &EventBus,
+    idle_pause: Duration,
+) -> Result<(), RunLoopError>
+where
+    R: NfcReader,
+    B: ButtonSource + ?Sized,
+    P: AudioPlayer,
+{
+    let on_action = |action: &ControllerAction| events.publish(EngineEvent::Action(action.clone()));
+    let started = controller.lock().expect("controller lock").start_up();
+    match started {
+        Ok(Some(action)) => on_action(&action),
+        Ok(None) => {}
+        Err(err) => tracing::warn!(%err, "startup action failed"),
+    }
+
+    let mut buttons_working = true;
+    let mut pending_tap: Option<PendingTap> = None;
     loop {
-        match reader.next_event()? {
-            ReaderEvent::CardPresent { uid } => {
-                let action = {
+        if shutdown.is_requested() {
+            tracing::info!("shutdown requested");
+            break;
+        }
+
+        if let Some(pending) = &pending_tap {
+            let first_sound = controller.lock().expect("controller lock").first_sound_at();
+            if let Some(latency) = pending.finish(first_sound, Instant::now()) {
+                events.publish(EngineEvent::Latency(latency));
+                pending_tap = None;
+            }
+        }
+
+        if buttons_working {
+            match buttons.poll() {
+                Ok(presses) => {
+                    for button in presses {
+                        let action = {
+                            let mut guard = controller.lock().expect("controller lock");
+                            guard.handle_button(button)
+                        }?;
+                        if let Some(action) = action {
+                            on_action(&action);
+                        }
+                    }
+                }
+                Err(err) => {
+                    tracing::warn!(%err, "ignoring buttons until restart");
+                    buttons_working = false;
+                }
+            }
+        }
+
+        let event = reader.next_event()?;
+        let detected = Instant::now();
+        if let ReaderEvent::CardPresent {
+            uid,
+            tap_count: Some(count),
+            ..
+        } = &event
+        {
+            events.publish(EngineEvent::TagCounter {
+                card: uid.clone(),
+                count: *count,
+            });
+        }
+        if let ReaderEvent::CardPresent { uid, .. } | ReaderEvent::CardRemoved { uid } = &event {
+            let mut guard = controller.lock().expect("controller lock");
+            if guard.is_bounce(uid, Instant::now()) {
+                tracing::debug!(%uid, "ignoring repeated reader event");
+                continue;
+            }
+        }
+        match event {
+            ReaderEvent::CardPresent {
+                uid,
+                ndef_text,
+                reader,
+                ..
+            } => {
+                let result = {
                     let mut guard = controller.lock().expect("controller lock");
-                    guard.handle_card(&uid)
-                }?;
-                on_action(&action);
+                    guard.handle_tap_at(&uid, ndef_text.as_deref(), reader.as_deref())
+                };
+                match result {
+                    Ok(action) => {
+                        let decided = Instant::now();
+                        if matches!(
+                            action,
+                            ControllerAction::Started { .. } | ControllerAction::Switched { .. }
+                        ) {
+                            pending_tap = Some(PendingTap { detected, decided });
+                        } else {
+                            events.publish(EngineEvent::Latency(TapLatency {
+                                decision: decided - detected,
+                                sound: None,
+                            }));
+                        }
+                        on_action(&action);
+                        if matches!(action, ControllerAction::ShutdownRequested { .. }) {
+                            break;
+                        }
+                    }
+                    Err(
+                        err @ (ControllerError::TrackNotFound
+                        | ControllerError::CardExpired(_)
+                        | ControllerError::WrongReader { .. }),
+                    ) => {
+                        tracing::warn!(%uid, %err, "ignoring tap");
+                        events.publish(EngineEvent::Rejected {
+                            card: uid,
+                            reason: err.to_string(),
+                        });
+                    }
+                    Err(err) => return Err(err.into()),
+                }
+            }
+            ReaderEvent::CardRemoved { uid } => {
+                let removed = {
+                    let mut guard = controller.lock().expect("controller lock");
+                    guard.handle_removal(&uid)?
+                };
+                if let Some(action) = removed {
+                    on_action(&action);
+                }
+            }
+            ReaderEvent::Idle => {
+                let (advanced, recorded, night, output) = {
+                    let mut guard = controller.lock().expect("controller lock");
+                    let now = Instant::now();
+                    let advanced = guard.poll_playback_at(now)?;
+                    guard.tick(now)?;
+                    let minute = night::local_minute_of_day(SystemTime::now());
+                    let night = guard.apply_night_schedule(minute)?;
+                    let output = guard.poll_output(now);
+                    (advanced, guard.poll_recording(now), night, output)
+                };
+                for action in advanced
+                    .iter()
+                    .chain(&recorded)
+                    .chain(&night)
+                    .chain(&output)
+                {
+                    on_action(action);
+                }
+                events.publish(EngineEvent::Idle);
+                std::thread::sleep(idle_pause);
             }
-            ReaderEvent::Idle => on_idle(),
             ReaderEvent::Shutdown => break,
         }
     }
     Ok(())
 }
 
+/// What the run loop does once reader errors keep recurring.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ReaderErrorPolicy {
+    /// Surface the error and stop the run loop.
+    #[default]
+    Fail,
+    /// Keep retrying the reader, backing off between attempts.
+    RetryForever,
+    /// Stop polling the reader and idle as if the noop reader were selected.
+    FallbackNoop,
+}
+
+/// Tunables for [`PolicyReader`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ReaderErrorSettings {
+    pub policy: ReaderErrorPolicy,
+    /// Consecutive errors tolerated before the policy kicks in.
+    pub threshold: u32,
+    /// Delay before the first retry; doubles per consecutive error.
+    pub retry_delay: Duration,
+    pub max_retry_delay: Duration,
+}
+
+impl Default for ReaderErrorSettings {
+    fn default() -> Self {
+        Self {
+            policy: ReaderErrorPolicy::Fail,
+            threshold: 3,
+            retry_delay: Duration::from_millis(200),
+            max_retry_delay: Duration::from_secs(30),
+        }
+    }
+}
+
+impl ReaderErrorSettings {
+    fn backoff(&self, consecutive_errors: u32) -> Duration {
+        let shift = consecutive_errors.saturating_sub(1).min(16);
+        self.retry_delay
+            .saturating_mul(1 << shift)
+            .min(self.max_retry_delay)
+    }
+}
+
+/// Wraps a reader and applies a [`ReaderErrorPolicy`] to its errors.
+///
+/// Transient errors are retried inside `next_event` so the run loop only sees
+/// an error once the policy decides to fail. Every health change is reported
+/// through `on_health` so callers can mirror it into status or send alerts.
+pub struct PolicyReader<R, OnHealth> {
+    inner: R,
+    settings: ReaderErrorSettings,
+    on_health: OnHealth,
+    consecutive_errors: u32,
+    fallen_back: bool,
+}
+
+impl<R, OnHealth> PolicyReader<R, OnHealth>
+where
+    R: NfcReader,
+    OnHealth: FnMut(&ReaderHealth),
+{
+    pub fn new(inner: R, settings: ReaderErrorSettings, on_health: OnHealth) -> Self {
+        Self {
+            inner,
+            settings,
+            on_health,
+            consecutive_errors: 0,
+            fallen_back: false,
+        }
+    }
+
+    /// Returns true once the policy has abandoned the wrapped reader.
+    pub fn fallen_back(&self) -> bool {
+        self.fallen_back
+    }
+
+    fn handle_error(&mut self, err: ReaderError) -> Result<(), ReaderError> {
+        self.consecutive_errors = self.consecutive_errors.saturating_add(1);
+        let last_error = err.to_string();
+        let persistent = self.consecutive_errors >= self.settings.threshold.max(1);
+
+        let health = match (persistent, self.settings.policy) {
+            (true, ReaderErrorPolicy::Fail) => ReaderHealth::Failed { last_error },
+            (true, ReaderErrorPolicy::FallbackNoop) => ReaderHealth::FellBack { last_error },
+            _ => ReaderHealth::Retrying {
+                consecutive_errors: self.consecutive_errors,
+                last_error,
+            },
+        };
+        (self.on_health)(&health);
+
+        match health {
+            ReaderHealth::Failed { .. } => {
+                tracing::error!(%err, "reader error persisted; stopping");
+                Err(err)
+            }
+            ReaderHealth::FellBack { .. } => {
+                tracing::error!(%err, "reader error persisted; falling back to noop reader");
+                self.fallen_back = true;
+                Ok(())
+            }
+            _ => {
+                let delay = self.settings.backoff(self.consecutive_errors);
+                tracing::warn!(
+                    %err,
+                    consecutive_errors = self.consecutive_errors,
+                    ?delay,
+                    "reader error; retrying"
+                );
+                std::thread::sleep(delay);
+                Ok(())
+            }
+        }
+    }
+}
+
+impl<R, OnHealth> NfcReader for PolicyReader<R, OnHealth>
+where
+    R: NfcReader,
+    OnHealth: FnMut(&ReaderHealth),
+{
+    /// The wrapped reader's, until the policy falls back to idling.
+    fn capabilities(&self) -> ReaderCapabilities {
+        if self.fallen_back {
+            ReaderCapabilities::default()
+        } else {
+            self.inner.capabilities()
+        }
+    }
+
+    fn next_event(&mut self) -> Result<ReaderEvent, ReaderError> {
+        loop {
+            if self.fallen_back {
+                return Ok(ReaderEvent::Idle);
+            }
+            match self.inner.next_event() {
+                Ok(event) => {
+                    if self.consecutive_errors > 0 {
+                        self.consecutive_errors = 0;
+                        (self.on_health)(&ReaderHealth::Healthy);
+                    }
+                    return Ok(event);
+                }
+                Err(err) => self.handle_error(err)?,
+            }
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::controller::{
-        CardUid, ControllerAction, ControllerError, Library, MusicBoxController, Track,
+        CardUid, ControllerAction, ControllerError, Library, MusicBoxController, SystemCommand,
+        Track,
     };
+    use crate::input::{ButtonEvent, InputError, NoButtons};
     use crate::reader::{NfcReader, ReaderError, ReaderEvent};
     use std::cell::RefCell;
     use std::collections::{HashMap, VecDeque};
@@ -134,6 +674,13 @@ mod tests {
     }
 
     impl AudioPlayer for MockPlayer {
+        fn capabilities(&self) -> crate::controller::PlayerCapabilities {
+            crate::controller::PlayerCapabilities {
+                volume: true,
+                ..Default::default()
+            }
+        }
+
         fn play(&mut self, track: &Track) -> Result<(), crate::controller::PlayerError> {
             self.calls
                 .borrow_mut()
@@ -145,6 +692,14 @@ mod tests {
             self.calls.borrow_mut().push(Call::Stop);
             Ok(())
         }
+
+        fn pause(&mut self) -> Result<(), crate::controller::PlayerError> {
+            Ok(())
+        }
+
+        fn resume(&mut self) -> Result<(), crate::controller::PlayerError> {
+            Ok(())
+        }
     }
 
     #[derive(Clone)]
@@ -170,7 +725,7 @@ mod tests {
             self.events
                 .borrow_mut()
                 .pop_front()
-                .unwrap_or_else(|| Ok(ReaderEvent::Shutdown))
+                .unwrap_or(Ok(ReaderEvent::Shutdown))
         }
     }
 
@@ -236,6 +791,32 @@ music_dir = "/music"
         }
     }
 
+    #[test]
+    fn config_watcher_reloads_once_a_change_settles() {
+        let file = write_config("music_dir = \"/music\"\n[cards]\n");
+        let debounce = Duration::from_millis(500);
+        let mut watcher = ConfigWatcher::new(file.path()).with_debounce(debounce);
+        let start = Instant::now();
+        assert!(!watcher.poll(start));
+        assert!(!watcher.poll(start + debounce));
+
+        std::fs::write(
+            file.path(),
+            "music_dir = \"/music\"\n[cards]\n\"0102\" = \"song1.mp3\"\n",
+        )
+        .unwrap();
+        assert!(
+            !watcher.poll(start),
+            "a fresh change waits out the debounce"
+        );
+        assert!(!watcher.poll(start + debounce / 2));
+        assert!(watcher.poll(start + debounce));
+        assert!(
+            !watcher.poll(start + debounce * 2),
+            "each change reloads once"
+        );
+    }
+
     #[test]
     fn process_next_event_triggers_controller_on_card_present() {
         let player = MockPlayer::new();
@@ -243,6 +824,9 @@ music_dir = "/music"
             controller_with_tracks(vec![("0102", "/music/song1.mp3")], player.clone());
         let mut reader = ScriptedReader::from_events(vec![ReaderEvent::CardPresent {
             uid: CardUid::from_hex("0102").unwrap(),
+            ndef_text: None,
+            reader: None,
+            tap_count: None,
         }]);
 
         let outcome = process_next_event(&mut controller, &mut reader).unwrap();
@@ -288,9 +872,16 @@ music_dir = "/music"
 
     #[test]
     fn process_next_event_returns_controller_error() {
-        let mut controller = controller_with_tracks(vec![], MockPlayer::new());
+        let empty = tempfile::tempdir().unwrap();
+        let mut controller = controller_with_tracks(
+            vec![("0304", empty.path().to_str().unwrap())],
+            MockPlayer::new(),
+        );
         let mut reader = ScriptedReader::from_events(vec![ReaderEvent::CardPresent {
             uid: CardUid::from_hex("0304").unwrap(),
+            ndef_text: None,
+            reader: None,
+            tap_count: None,
         }]);
 
         let err = process_next_event(&mut controller, &mut reader).unwrap_err();
@@ -317,8 +908,121 @@ music_dir = "/music"
         ));
     }
 
+    fn failing_reader(errors: usize, then: Vec<ReaderEvent>) -> ScriptedReader {
+        let mut sequence: Vec<_> = (0..errors)
+            .map(|_| Err(ReaderError::backend("boom")))
+            .collect();
+        sequence.extend(then.into_iter().map(Ok));
+        ScriptedReader::new(sequence)
+    }
+
+    fn settings(policy: ReaderErrorPolicy, threshold: u32) -> ReaderErrorSettings {
+        ReaderErrorSettings {
+            policy,
+            threshold,
+            retry_delay: Duration::ZERO,
+            max_retry_delay: Duration::ZERO,
+        }
+    }
+
+    #[test]
+    fn policy_reader_fails_after_threshold() {
+        let mut reports = Vec::new();
+        let mut reader = PolicyReader::new(
+            failing_reader(5, vec![]),
+            settings(ReaderErrorPolicy::Fail, 2),
+            |health: &ReaderHealth| reports.push(health.label()),
+        );
+
+        let err = reader.next_event().unwrap_err();
+
+        assert!(matches!(err, ReaderError::Backend { .. }));
+        drop(reader);
+        assert_eq!(reports, vec!["retrying", "failed"]);
+    }
+
+    #[test]
+    fn policy_reader_retries_and_recovers() {
+        let mut reports = Vec::new();
+        let uid = CardUid::from_hex("0102").unwrap();
+        let mut reader = PolicyReader::new(
+            failing_reader(
+                4,
+                vec![ReaderEvent::CardPresent {
+                    uid: uid.clone(),
+                    ndef_text: None,
+                    reader: None,
+                    tap_count: None,
+                }],
+            ),
+            settings(ReaderErrorPolicy::RetryForever, 2),
+            |health: &ReaderHealth| reports.push(health.clone()),
+        );
+
+        let event = reader.next_event().unwrap();
+
+        assert_eq!(
+            event,
+            ReaderEvent::CardPresent {
+                uid,
+                ndef_text: None,
+                reader: None,
+                tap_count: None,
+            }
+        );
+        drop(reader);
+        assert_eq!(reports.len(), 5);
+        assert_eq!(
+            reports[3],
+            ReaderHealth::Retrying {
+                consecutive_errors: 4,
+                last_error: "reader backend error: boom".into(),
+            }
+        );
+        assert_eq!(reports[4], ReaderHealth::Healthy);
+    }
+
+    #[test]
+    fn policy_reader_falls_back_to_idle() {
+        let mut reports = Vec::new();
+        let uid = CardUid::from_hex("0102").unwrap();
+        let mut reader = PolicyReader::new(
+            failing_reader(
+                1,
+                vec![ReaderEvent::CardPresent {
+                    uid,
+                    ndef_text: None,
+                    reader: None,
+                    tap_count: None,
+                }],
+            ),
+            settings(ReaderErrorPolicy::FallbackNoop, 1),
+            |health: &ReaderHealth| reports.push(health.label()),
+        );
+
+        assert_eq!(reader.next_event().unwrap(), ReaderEvent::Idle);
+        assert_eq!(reader.next_event().unwrap(), ReaderEvent::Idle);
+        assert!(reader.fallen_back());
+        drop(reader);
+        assert_eq!(reports, vec!["fallback"]);
+    }
+
+    #[test]
+    fn retry_backoff_doubles_up_to_cap() {
+        let settings = ReaderErrorSettings {
+            retry_delay: Duration::from_millis(100),
+            max_retry_delay: Duration::from_millis(350),
+            ..ReaderErrorSettings::default()
+        };
+
+        assert_eq!(settings.backoff(1), Duration::from_millis(100));
+        assert_eq!(settings.backoff(2), Duration::from_millis(200));
+        assert_eq!(settings.backoff(3), Duration::from_millis(350));
+    }
+
     #[test]
-    fn run_until_shutdown_invokes_callbacks_until_shutdown() {
+    #[allow(clippy::arc_with_non_send_sync)]
+    fn run_until_shutdown_publishes_events_until_shutdown() {
         let player = MockPlayer::new();
         let controller = controller_with_tracks(
             vec![("0102", "/music/song1.mp3"), ("0304", "/music/song2.mp3")],
@@ -328,32 +1032,55 @@ music_dir = "/music"
         let mut reader = ScriptedReader::from_events(vec![
             ReaderEvent::CardPresent {
                 uid: CardUid::from_hex("0102").unwrap(),
+                ndef_text: None,
+                reader: None,
+                tap_count: None,
             },
             ReaderEvent::Idle,
+            ReaderEvent::CardPresent {
+                uid: CardUid::from_hex("0909").unwrap(),
+                ndef_text: None,
+                reader: None,
+                tap_count: Some(42),
+            },
             ReaderEvent::CardPresent {
                 uid: CardUid::from_hex("0304").unwrap(),
+                ndef_text: None,
+                reader: None,
+                tap_count: None,
             },
             ReaderEvent::Shutdown,
         ]);
 
-        let mut actions = Vec::new();
-        let mut idle_calls = 0usize;
-        run_until_shutdown(
-            controller.clone(),
-            &mut reader,
-            |action| actions.push(action.clone()),
-            || idle_calls += 1,
-        )
-        .unwrap();
+        let events = run_to_end(controller, &mut reader, &mut NoButtons);
 
-        assert_eq!(idle_calls, 1);
         assert_eq!(
-            actions,
+            events
+                .iter()
+                .filter(|event| **event == EngineEvent::Idle)
+                .count(),
+            1
+        );
+        assert!(
+            !events
+                .iter()
+                .any(|event| matches!(event, EngineEvent::Rejected { .. }))
+        );
+        assert!(events.contains(&EngineEvent::TagCounter {
+            card: CardUid::from_hex("0909").unwrap(),
+            count: 42,
+        }));
+        assert_eq!(events.last(), Some(&EngineEvent::Stopped));
+        assert_eq!(
+            actions(&events),
             vec![
                 ControllerAction::Started {
                     card: CardUid::from_hex("0102").unwrap(),
                     track: Track::new(PathBuf::from("/music/song1.mp3")),
                 },
+                ControllerAction::UnknownCard {
+                    card: CardUid::from_hex("0909").unwrap(),
+                },
                 ControllerAction::Switched {
                     from_card: CardUid::from_hex("0102").unwrap(),
                     from_track: Track::new(PathBuf::from("/music/song1.mp3")),
@@ -371,4 +1098,206 @@ music_dir = "/music"
             ]
         );
     }
+
+    /// Runs the loop until the reader runs dry and returns what it
+    /// published.
+    fn run_to_end<P: AudioPlayer>(
+        controller: Arc<Mutex<MusicBoxController<P>>>,
+        reader: &mut impl NfcReader,
+        buttons: &mut impl ButtonSource,
+    ) -> Vec<EngineEvent> {
+        let events = EventBus::new();
+        let published = events.subscribe();
+        run_until_shutdown(
+            controller,
+            reader,
+            buttons,
+            &ShutdownToken::new(),
+            &events,
+            Duration::ZERO,
+        )
+        .unwrap();
+        published
+            .try_iter()
+            .map(|published| published.event)
+            .collect()
+    }
+
+    fn actions(events: &[EngineEvent]) -> Vec<ControllerAction> {
+        events
+            .iter()
+            .filter_map(|event| match event {
+                EngineEvent::Action(action) => Some(action.clone()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn pending_taps_wait_for_the_first_sound_after_them() {
+        let detected = Instant::now();
+        let ms = |ms| Duration::from_millis(ms);
+        let pending = PendingTap {
+            detected,
+            decided: detected + ms(3),
+        };
+
+        // A sound from the previous track does not count.
+        assert_eq!(pending.finish(None, detected + ms(10)), None);
+        assert_eq!(
+            pending.finish(detected.checked_sub(ms(1)), detected + ms(10)),
+            None
+        );
+        assert_eq!(
+            pending.finish(Some(detected + ms(40)), detected + ms(50)),
+            Some(TapLatency {
+                decision: ms(3),
+                sound: Some(ms(40)),
+            })
+        );
+        assert_eq!(
+            pending.finish(None, detected + FIRST_SOUND_WAIT),
+            Some(TapLatency {
+                decision: ms(3),
+                sound: None,
+            })
+        );
+    }
+
+    #[test]
+    #[allow(clippy::arc_with_non_send_sync)]
+    fn shutdown_card_ends_the_run_loop() {
+        let player = MockPlayer::new();
+        let library = controller_with_tracks(vec![("0102", "/music/song1.mp3")], player.clone())
+            .library()
+            .clone()
+            .with_system_commands(HashMap::from([(
+                CardUid::from_hex("ff").unwrap(),
+                SystemCommand::Shutdown,
+            )]));
+        let controller = Arc::new(Mutex::new(MusicBoxController::new(library, player.clone())));
+        let tap = |hex| ReaderEvent::CardPresent {
+            uid: CardUid::from_hex(hex).unwrap(),
+            ndef_text: None,
+            reader: None,
+            tap_count: None,
+        };
+        let mut reader = ScriptedReader::from_events(vec![tap("ff"), tap("0102")]);
+
+        let events = run_to_end(controller, &mut reader, &mut NoButtons);
+
+        assert_eq!(
+            actions(&events),
+            vec![ControllerAction::ShutdownRequested {
+                card: CardUid::from_hex("ff").unwrap(),
+            }]
+        );
+        assert!(player.calls().is_empty());
+    }
+
+    #[test]
+    #[allow(clippy::arc_with_non_send_sync)]
+    fn shutdown_token_stops_the_run_loop_between_events() {
+        let player = MockPlayer::new();
+        let controller = Arc::new(Mutex::new(controller_with_tracks(
+            vec![("0102", "/music/song1.mp3"), ("0304", "/music/song2.mp3")],
+            player.clone(),
+        )));
+        let tap = |hex| ReaderEvent::CardPresent {
+            uid: CardUid::from_hex(hex).unwrap(),
+            ndef_text: None,
+            reader: None,
+            tap_count: None,
+        };
+        let mut reader =
+            ScriptedReader::from_events(vec![tap("0102"), ReaderEvent::Idle, tap("0304")]);
+        let shutdown = ShutdownToken::new();
+
+        let events = EventBus::new();
+        let published = events.subscribe();
+        events.on({
+            let shutdown = shutdown.clone();
+            move |published| {
+                if let EngineEvent::Action(_) = published.event {
+                    shutdown.request();
+                }
+            }
+        });
+        run_until_shutdown(
+            controller,
+            &mut reader,
+            &mut NoButtons,
+            &shutdown,
+            &events,
+            Duration::ZERO,
+        )
+        .unwrap();
+
+        let published: Vec<EngineEvent> = published.try_iter().map(|p| p.event).collect();
+        assert_eq!(actions(&published).len(), 1);
+        assert!(shutdown.is_requested());
+        assert!(!ShutdownToken::new().is_requested());
+    }
+
+    /// Hands out one batch of presses per poll.
+    struct ScriptedButtons(VecDeque<Vec<ButtonEvent>>);
+
+    impl ButtonSource for ScriptedButtons {
+        fn poll(&mut self) -> Result<Vec<ButtonEvent>, InputError> {
+            Ok(self.0.pop_front().unwrap_or_default())
+        }
+    }
+
+    #[test]
+    #[allow(clippy::arc_with_non_send_sync)]
+    fn run_until_shutdown_applies_button_presses() {
+        let player = MockPlayer::new();
+        let library = Library::from_playlists(HashMap::from([(
+            CardUid::from_hex("0102").unwrap(),
+            vec![
+                Track::new(PathBuf::from("/music/01.mp3")),
+                Track::new(PathBuf::from("/music/02.mp3")),
+            ],
+        )]));
+        let controller = MusicBoxController::new(library, player.clone());
+        let controller = Arc::new(Mutex::new(controller));
+        let mut reader = ScriptedReader::from_events(vec![
+            ReaderEvent::CardPresent {
+                uid: CardUid::from_hex("0102").unwrap(),
+                ndef_text: None,
+                reader: None,
+                tap_count: None,
+            },
+            ReaderEvent::Shutdown,
+        ]);
+        let mut buttons = ScriptedButtons(VecDeque::from([
+            vec![ButtonEvent::Next],
+            vec![ButtonEvent::Next, ButtonEvent::VolumeDown],
+        ]));
+
+        let events = run_to_end(controller.clone(), &mut reader, &mut buttons);
+
+        // The first Next arrives before any card plays and does nothing.
+        assert_eq!(
+            actions(&events),
+            vec![
+                ControllerAction::Started {
+                    card: CardUid::from_hex("0102").unwrap(),
+                    track: Track::new(PathBuf::from("/music/01.mp3")),
+                },
+                ControllerAction::Skipped {
+                    card: CardUid::from_hex("0102").unwrap(),
+                    track: Track::new(PathBuf::from("/music/02.mp3")),
+                },
+            ]
+        );
+        assert_eq!(controller.lock().unwrap().volume(), 0.95);
+        assert_eq!(
+            player.calls(),
+            vec![
+                Call::Play(PathBuf::from("/music/01.mp3")),
+                Call::Play(PathBuf::from("/music/02.mp3")),
+            ]
+        );
+    }
 }
@@ -1,4 +1,87 @@
-use crate::controller::{AudioPlayer, PlayerError, Track};
+use crate::controller::{AudioPlayer, PlayerCapabilities, PlayerError, Track};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// How tracks fade in and out, so switching cards does not cut off abruptly.
+/// The default plays and stops tracks without any fade.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FadeSettings {
+    /// How long a track takes to fade out when it stops or is replaced.
+    pub fade_out: Duration,
+    /// How long each new track takes to fade in.
+    pub fade_in: Duration,
+    /// Start the new track while the old one fades out, instead of after.
+    pub crossfade: bool,
+}
+
+/// Time between volume updates while fading out.
+#[cfg(any(feature = "audio-rodio", test))]
+const FADE_STEP: Duration = Duration::from_millis(20);
+
+/// Volumes to apply, one per [`FADE_STEP`], to fade from `start` to silence
+/// over `over`. The last level is always 0.0.
+#[cfg(any(feature = "audio-rodio", test))]
+fn fade_out_levels(start: f32, over: Duration) -> impl Iterator<Item = f32> {
+    let steps = (over.as_millis() / FADE_STEP.as_millis()).max(1) as u32;
+    (0..steps)
+        .rev()
+        .map(move |remaining| start * remaining as f32 / steps as f32)
+}
+
+/// Level above which night mode compresses, about -12 dBFS.
+#[cfg(any(feature = "audio-rodio", test))]
+const NIGHT_THRESHOLD: f32 = 0.25;
+/// How strongly night mode squeezes levels above the threshold.
+#[cfg(any(feature = "audio-rodio", test))]
+const NIGHT_RATIO: f32 = 4.0;
+/// Gain after compression, so quiet passages such as dialogue stay audible.
+#[cfg(any(feature = "audio-rodio", test))]
+const NIGHT_MAKEUP: f32 = 1.5;
+/// Peak level the limiter never lets through, about -6 dBFS.
+#[cfg(any(feature = "audio-rodio", test))]
+const NIGHT_CEILING: f32 = 0.5;
+
+/// The DSP stage behind night mode: a feed-forward compressor that follows
+/// the signal's envelope, then a hard limiter. It works on interleaved
+/// samples, so all channels share one envelope.
+#[cfg(any(feature = "audio-rodio", test))]
+#[derive(Debug, Clone)]
+struct Compressor {
+    envelope: f32,
+    attack: f32,
+    release: f32,
+}
+
+#[cfg(any(feature = "audio-rodio", test))]
+impl Compressor {
+    fn new(sample_rate: u32, channels: u16) -> Self {
+        let samples_per_sec = (sample_rate * u32::from(channels.max(1))).max(1) as f32;
+        let coefficient = |time: Duration| (-1.0 / (time.as_secs_f32() * samples_per_sec)).exp();
+        Self {
+            envelope: 0.0,
+            attack: coefficient(Duration::from_millis(5)),
+            release: coefficient(Duration::from_millis(250)),
+        }
+    }
+
+    fn process(&mut self, sample: f32) -> f32 {
+        let level = sample.abs();
+        let coefficient = if level > self.envelope {
+            self.attack
+        } else {
+            self.release
+        };
+        self.envelope = level + coefficient * (self.envelope - level);
+        let gain = if self.envelope > NIGHT_THRESHOLD {
+            (NIGHT_THRESHOLD + (self.envelope - NIGHT_THRESHOLD) / NIGHT_RATIO) / self.envelope
+        } else {
+            1.0
+        };
+        (sample * gain * NIGHT_MAKEUP).clamp(-NIGHT_CEILING, NIGHT_CEILING)
+    }
+}
 
 // An audio backend that uses the `rodio` library for audio playback.
 //
@@ -6,13 +89,105 @@ use crate::controller::{AudioPlayer, PlayerError, Track};
 #[cfg(feature = "audio-rodio")]
 mod rodio_backend {
     use super::*;
-    use rodio::{OutputStream, OutputStreamBuilder, Sink};
+    use crate::controller::{Gain, PlayerCapabilities, TrackSource};
+    use crate::stream::HttpStream;
+    use rodio::cpal::traits::{DeviceTrait, HostTrait};
+    use rodio::source::SeekError;
+    use rodio::{ChannelCount, OutputStream, OutputStreamBuilder, SampleRate, Sink, Source};
     use std::fs::File;
     use std::path::Path;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::{Arc, OnceLock};
+    use std::thread;
+    use std::time::Instant;
 
     pub struct RodioPlayer {
         stream: OutputStream,
-        sink: Sink,
+        /// Shared with fade threads, which ramp its volume or start it late.
+        sink: Arc<Sink>,
+        /// Mixes sound effects over `sink` without interrupting it.
+        effects: Sink,
+        /// Reapplied to each fresh sink, which starts at full volume.
+        volume: f32,
+        /// Holds every sink at silence without forgetting `volume`.
+        muted: bool,
+        fades: FadeSettings,
+        /// Set when the latest fade-out finishes; `None` if nothing faded yet.
+        fade_done: Option<Arc<AtomicBool>>,
+        /// Set while the listener has paused the current sink, so a start
+        /// delayed behind a fade-out does not unpause it.
+        held: Arc<AtomicBool>,
+        /// Read by every queued source's [`OutputStage`], so switching night
+        /// mode also reaches the track already playing.
+        night: Arc<AtomicBool>,
+        /// Filled by the latest track's [`OutputStage`] when the output first
+        /// pulls a sample from it.
+        first_sound: Arc<OnceLock<Instant>>,
+    }
+
+    /// The last stage before a track reaches the sink: it notes when the
+    /// output first pulls a sample, and runs samples through the night-mode
+    /// [`Compressor`] while `night` is set.
+    struct OutputStage<S> {
+        inner: S,
+        night: Arc<AtomicBool>,
+        compressor: Compressor,
+        /// Taken on the first sample.
+        first_sound: Option<Arc<OnceLock<Instant>>>,
+    }
+
+    impl<S: Source> OutputStage<S> {
+        fn new(inner: S, night: Arc<AtomicBool>, first_sound: Arc<OnceLock<Instant>>) -> Self {
+            let compressor = Compressor::new(inner.sample_rate(), inner.channels());
+            Self {
+                inner,
+                night,
+                compressor,
+                first_sound: Some(first_sound),
+            }
+        }
+    }
+
+    impl<S: Source> Iterator for OutputStage<S> {
+        type Item = rodio::Sample;
+
+        fn next(&mut self) -> Option<Self::Item> {
+            let sample = self.inner.next()?;
+            if let Some(first_sound) = self.first_sound.take() {
+                let _ = first_sound.set(Instant::now());
+            }
+            if self.night.load(Ordering::Relaxed) {
+                Some(self.compressor.process(sample))
+            } else {
+                Some(sample)
+            }
+        }
+
+        fn size_hint(&self) -> (usize, Option<usize>) {
+            self.inner.size_hint()
+        }
+    }
+
+    impl<S: Source> Source for OutputStage<S> {
+        fn current_span_len(&self) -> Option<usize> {
+            self.inner.current_span_len()
+        }
+
+        fn channels(&self) -> ChannelCount {
+            self.inner.channels()
+        }
+
+        fn sample_rate(&self) -> SampleRate {
+            self.inner.sample_rate()
+        }
+
+        fn total_duration(&self) -> Option<Duration> {
+            self.inner.total_duration()
+        }
+
+        fn try_seek(&mut self, pos: Duration) -> Result<(), SeekError> {
+            self.inner.try_seek(pos)
+        }
     }
 
     impl RodioPlayer {
@@ -22,8 +197,49 @@ mod rodio_backend {
                 OutputStreamBuilder::open_default_stream().map_err(|err| PlayerError::Backend {
                     message: format!("failed to open output stream: {err}"),
                 })?;
-            let sink = Sink::connect_new(stream.mixer());
-            Ok(Self { stream, sink })
+            Ok(Self::with_stream(stream))
+        }
+
+        /// Creates a `RodioPlayer` on the output device called `name`, as
+        /// listed by [`output_devices`].
+        pub fn open_device(name: &str) -> Result<Self, PlayerError> {
+            let backend = |message: String| PlayerError::Backend { message };
+            let device = rodio::cpal::default_host()
+                .output_devices()
+                .map_err(|err| backend(format!("failed to list output devices: {err}")))?
+                .find(|device| device.name().is_ok_and(|found| found == name))
+                .ok_or_else(|| backend(format!("no output device named {name:?}")))?;
+            let stream = OutputStreamBuilder::from_device(device)
+                .and_then(|builder| builder.open_stream_or_fallback())
+                .map_err(|err| backend(format!("failed to open {name:?}: {err}")))?;
+            Ok(Self::with_stream(stream))
+        }
+
+        fn with_stream(stream: OutputStream) -> Self {
+            let sink = Arc::new(Sink::connect_new(stream.mixer()));
+            let effects = Sink::connect_new(stream.mixer());
+            Self {
+                stream,
+                sink,
+                effects,
+                volume: 1.0,
+                muted: false,
+                fades: FadeSettings::default(),
+                fade_done: None,
+                held: Arc::new(AtomicBool::new(false)),
+                night: Arc::new(AtomicBool::new(false)),
+                first_sound: Arc::new(OnceLock::new()),
+            }
+        }
+
+        pub fn with_fades(mut self, fades: FadeSettings) -> Self {
+            self.fades = fades;
+            self
+        }
+
+        /// The volume sinks actually play at.
+        fn output_volume(&self) -> f32 {
+            if self.muted { 0.0 } else { self.volume }
         }
 
         /// Loads a track from a file and returns a `rodio` decoder.
@@ -39,43 +255,253 @@ mod rodio_backend {
             Ok(decoder)
         }
 
+        /// Opens a network stream and returns a `rodio` decoder reading it
+        /// in order, without seeking.
+        fn load_stream(url: &str) -> Result<rodio::Decoder<HttpStream>, PlayerError> {
+            let stream = HttpStream::open(url).map_err(|err| PlayerError::Backend {
+                message: format!("failed to open stream {url}: {err}"),
+            })?;
+            let mut builder = rodio::Decoder::builder().with_seekable(false);
+            if let Some(content_type) = stream.content_type() {
+                builder = builder.with_mime_type(content_type);
+            }
+            builder
+                .with_data(stream)
+                .build()
+                .map_err(|err| PlayerError::Backend {
+                    message: format!("failed to decode stream {url}: {err}"),
+                })
+        }
+
+        /// Queues `source` on the current sink behind the output stage,
+        /// scaled by the card's `gain` and faded in if configured.
+        fn append(&mut self, source: impl Source + Send + 'static, gain: Gain) {
+            self.first_sound = Arc::new(OnceLock::new());
+            let source = OutputStage::new(
+                source.amplify(gain.factor()),
+                Arc::clone(&self.night),
+                Arc::clone(&self.first_sound),
+            );
+            if self.fades.fade_in.is_zero() {
+                self.sink.append(source);
+            } else {
+                self.sink.append(source.fade_in(self.fades.fade_in));
+            }
+        }
+
         /// Resets the `rodio` sink.
         ///
-        /// This is useful for clearing the audio buffer.
+        /// This is useful for clearing the audio buffer. A sink that is
+        /// still audible fades out on a background thread when a fade-out
+        /// is configured, and stops at once otherwise.
         fn reset_sink(&mut self) {
-            self.sink = Sink::connect_new(self.stream.mixer());
+            let old = std::mem::replace(
+                &mut self.sink,
+                Arc::new(Sink::connect_new(self.stream.mixer())),
+            );
+            self.sink.set_volume(self.output_volume());
+            self.held = Arc::new(AtomicBool::new(false));
+
+            let fade_out = self.fades.fade_out;
+            if old.empty() || old.is_paused() || fade_out.is_zero() {
+                old.stop();
+                return;
+            }
+            let done = Arc::new(AtomicBool::new(false));
+            self.fade_done = Some(Arc::clone(&done));
+            thread::spawn(move || {
+                for level in fade_out_levels(old.volume(), fade_out) {
+                    old.set_volume(level);
+                    thread::sleep(FADE_STEP);
+                }
+                old.stop();
+                done.store(true, Ordering::SeqCst);
+            });
+        }
+
+        /// Without crossfade, holds the current sink until the last fade-out
+        /// finishes and then starts it from a background thread. Returns
+        /// whether the start was delayed.
+        fn start_after_fade_out(&mut self) -> bool {
+            let Some(done) = self
+                .fade_done
+                .as_ref()
+                .filter(|done| !self.fades.crossfade && !done.load(Ordering::SeqCst))
+                .cloned()
+            else {
+                return false;
+            };
+            self.sink.pause();
+            let sink = Arc::clone(&self.sink);
+            let held = Arc::clone(&self.held);
+            thread::spawn(move || {
+                while !done.load(Ordering::SeqCst) {
+                    thread::sleep(FADE_STEP);
+                }
+                if !held.load(Ordering::SeqCst) {
+                    sink.play();
+                }
+            });
+            true
         }
     }
 
     impl AudioPlayer for RodioPlayer {
+        fn capabilities(&self) -> PlayerCapabilities {
+            PlayerCapabilities {
+                seek: true,
+                volume: true,
+                track_end: true,
+                streams: true,
+            }
+        }
+
         /// Plays the given track.
         ///
         /// This replaces the currently playing track, if any.
         fn play(&mut self, track: &Track) -> Result<(), PlayerError> {
             self.reset_sink();
-            let source = Self::load_track(track.path())?;
-            self.sink.append(source);
-            self.sink.play();
+            let delayed = match track.source() {
+                TrackSource::File(path) => {
+                    let source = Self::load_track(path)?;
+                    let delayed = self.start_after_fade_out();
+                    self.append(source, track.gain());
+                    delayed
+                }
+                TrackSource::Url(url) => {
+                    let source = Self::load_stream(url)?;
+                    let delayed = self.start_after_fade_out();
+                    self.append(source, track.gain());
+                    delayed
+                }
+            };
+            if !delayed {
+                self.sink.play();
+            }
             Ok(())
         }
 
-        /// Stops the currently playing track.
+        /// Stops the currently playing track, fading it out if configured.
         fn stop(&mut self) -> Result<(), PlayerError> {
             if !self.sink.empty() {
-                self.sink.stop();
                 self.reset_sink();
             }
             Ok(())
         }
 
-        /// Waits for the currently playing track to finish.
+        fn pause(&mut self) -> Result<(), PlayerError> {
+            self.held.store(true, Ordering::SeqCst);
+            self.sink.pause();
+            Ok(())
+        }
+
+        fn resume(&mut self) -> Result<(), PlayerError> {
+            self.held.store(false, Ordering::SeqCst);
+            self.sink.play();
+            Ok(())
+        }
+
+        /// Waits for the currently playing track, and any sound effect, to
+        /// finish.
         fn wait_until_done(&mut self) -> Result<(), PlayerError> {
             self.sink.sleep_until_end();
+            self.effects.sleep_until_end();
+            self.reset_sink();
+            Ok(())
+        }
+
+        /// The sink drains its queue as playback progresses, so an empty sink
+        /// means the track ended (or nothing was started).
+        fn is_finished(&self) -> bool {
+            self.sink.empty()
+        }
+
+        fn set_volume(&mut self, volume: f32) -> Result<(), PlayerError> {
+            self.volume = volume.clamp(0.0, 1.0);
+            self.sink.set_volume(self.output_volume());
+            self.effects.set_volume(self.output_volume());
+            Ok(())
+        }
+
+        fn volume(&self) -> f32 {
+            self.volume
+        }
+
+        fn set_muted(&mut self, muted: bool) -> Result<(), PlayerError> {
+            self.muted = muted;
+            self.sink.set_volume(self.output_volume());
+            self.effects.set_volume(self.output_volume());
+            Ok(())
+        }
+
+        fn set_night_mode(&mut self, on: bool) -> Result<(), PlayerError> {
+            self.night.store(on, Ordering::Relaxed);
+            Ok(())
+        }
+
+        fn position(&self) -> Option<Duration> {
+            Some(self.sink.get_pos())
+        }
+
+        fn seek(&mut self, offset: Duration) -> Result<(), PlayerError> {
+            self.sink
+                .try_seek(offset)
+                .map_err(|err| PlayerError::Backend {
+                    message: format!("failed to seek to {offset:?}: {err}"),
+                })
+        }
+
+        fn first_sound_at(&self) -> Option<Instant> {
+            self.first_sound.get().copied()
+        }
+
+        /// Fades out over `over` in place of the configured fade-out.
+        fn fade_out(&mut self, over: Duration) -> Result<(), PlayerError> {
+            if self.sink.empty() {
+                return Ok(());
+            }
+            let configured = std::mem::replace(&mut self.fades.fade_out, over);
             self.reset_sink();
+            self.fades.fade_out = configured;
+            Ok(())
+        }
+
+        /// Queues `effect` on the effects sink, behind any effect still
+        /// playing, while the track sink carries on.
+        fn play_effect(&mut self, effect: &Track) -> Result<(), PlayerError> {
+            let TrackSource::File(path) = effect.source() else {
+                return Err(PlayerError::Backend {
+                    message: "sound effects must be local files".into(),
+                });
+            };
+            self.effects.append(Self::load_track(path)?);
+            self.effects.play();
             Ok(())
         }
     }
 
+    /// Names of the output devices the audio host offers, for the
+    /// `[audio]` table's `device` key. Devices whose name cannot be read
+    /// are left out.
+    pub fn output_devices() -> Vec<String> {
+        rodio::cpal::default_host()
+            .output_devices()
+            .map(|devices| devices.filter_map(|device| device.name().ok()).collect())
+            .unwrap_or_default()
+    }
+
+    /// The device playback would open right now: `wanted` if the host
+    /// lists it, or else the host's default output. `None` when there is
+    /// no such device, as when the USB speaker is not plugged in yet.
+    pub fn current_output(wanted: Option<&str>) -> Option<String> {
+        match wanted {
+            Some(name) => output_devices().into_iter().find(|found| found == name),
+            None => rodio::cpal::default_host()
+                .default_output_device()
+                .and_then(|device| device.name().ok()),
+        }
+    }
+
     #[cfg(test)]
     mod tests {
         use super::*;
@@ -105,6 +531,14 @@ mod rodio_backend {
                     .into(),
             })
         }
+
+        pub fn open_device(_name: &str) -> Result<Self, PlayerError> {
+            Self::new()
+        }
+
+        pub fn with_fades(self, _fades: FadeSettings) -> Self {
+            self
+        }
     }
 
     impl AudioPlayer for RodioPlayer {
@@ -117,6 +551,24 @@ mod rodio_backend {
         fn stop(&mut self) -> Result<(), PlayerError> {
             Ok(())
         }
+
+        fn pause(&mut self) -> Result<(), PlayerError> {
+            Ok(())
+        }
+
+        fn resume(&mut self) -> Result<(), PlayerError> {
+            Ok(())
+        }
+    }
+
+    /// Without the rodio backend there are no output devices to choose.
+    pub fn output_devices() -> Vec<String> {
+        Vec::new()
+    }
+
+    /// Without the rodio backend no device ever turns up.
+    pub fn current_output(_wanted: Option<&str>) -> Option<String> {
+        None
     }
 
     #[cfg(test)]
@@ -133,10 +585,338 @@ mod rodio_backend {
 
         #[test]
         fn stop_is_noop() {
-            let mut player = RodioPlayer::default();
+            let mut player = RodioPlayer;
             player.stop().expect("stop should succeed");
         }
     }
 }
 
-pub use rodio_backend::RodioPlayer;
+pub use rodio_backend::{RodioPlayer, current_output, output_devices};
+
+/// How long [`SimulatedPlayer`] lets a track play when its file does not
+/// say how long it is.
+pub const SIMULATED_TRACK_LENGTH: Duration = Duration::from_secs(180);
+
+/// A clock that only moves when told to, so a [`SimulatedPlayer`] can play
+/// an hour of music in a test that takes milliseconds. Clones share the
+/// same time.
+#[derive(Debug, Clone)]
+pub struct VirtualClock {
+    now: Arc<Mutex<Instant>>,
+}
+
+impl VirtualClock {
+    /// A clock that starts at the real current time.
+    pub fn new() -> Self {
+        Self {
+            now: Arc::new(Mutex::new(Instant::now())),
+        }
+    }
+
+    pub fn now(&self) -> Instant {
+        *self.now.lock().expect("clock lock")
+    }
+
+    pub fn advance(&self, by: Duration) {
+        *self.now.lock().expect("clock lock") += by;
+    }
+}
+
+impl Default for VirtualClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A track the simulated player is going through.
+#[derive(Debug, Clone)]
+struct SimulatedTrack {
+    path: PathBuf,
+    length: Duration,
+    /// Position at `since`.
+    offset: Duration,
+    since: Instant,
+    paused: bool,
+}
+
+impl SimulatedTrack {
+    fn position(&self, now: Instant) -> Duration {
+        let played = if self.paused {
+            Duration::ZERO
+        } else {
+            now.saturating_duration_since(self.since)
+        };
+        (self.offset + played).min(self.length)
+    }
+}
+
+/// A player that makes no sound but keeps time as if it did: each track
+/// lasts as long as its file says, and [`AudioPlayer::is_finished`] turns
+/// true when it would have ended. Playlists, gaps, and countdowns then run
+/// on a box without a sound card, or against a [`VirtualClock`] in tests.
+#[derive(Debug)]
+pub struct SimulatedPlayer {
+    clock: Option<VirtualClock>,
+    lengths: HashMap<PathBuf, Duration>,
+    current: Option<SimulatedTrack>,
+    volume: f32,
+    muted: bool,
+}
+
+impl SimulatedPlayer {
+    /// A player that keeps real time.
+    pub fn new() -> Self {
+        Self {
+            clock: None,
+            lengths: HashMap::new(),
+            current: None,
+            volume: 1.0,
+            muted: false,
+        }
+    }
+
+    /// Keeps time by `clock` instead of the real one.
+    pub fn with_clock(mut self, clock: VirtualClock) -> Self {
+        self.clock = Some(clock);
+        self
+    }
+
+    /// Plays the tracks at these paths for the given lengths instead of
+    /// reading them from the files.
+    pub fn with_lengths(mut self, lengths: HashMap<PathBuf, Duration>) -> Self {
+        self.lengths.extend(lengths);
+        self
+    }
+
+    fn now(&self) -> Instant {
+        self.clock
+            .as_ref()
+            .map_or_else(Instant::now, VirtualClock::now)
+    }
+
+    /// The track's length: given, read from the file once, or else
+    /// [`SIMULATED_TRACK_LENGTH`].
+    fn length_of(&mut self, track: &Track) -> Duration {
+        let path = track.path();
+        if let Some(length) = self.lengths.get(path) {
+            return *length;
+        }
+        let length = crate::metadata::duration(path).unwrap_or_else(|err| {
+            tracing::debug!(%err, "simulating a track of the default length");
+            SIMULATED_TRACK_LENGTH
+        });
+        self.lengths.insert(path.to_path_buf(), length);
+        length
+    }
+}
+
+impl Default for SimulatedPlayer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl AudioPlayer for SimulatedPlayer {
+    fn capabilities(&self) -> PlayerCapabilities {
+        PlayerCapabilities {
+            seek: true,
+            volume: true,
+            track_end: true,
+            streams: true,
+        }
+    }
+
+    fn play(&mut self, track: &Track) -> Result<(), PlayerError> {
+        let length = self.length_of(track);
+        tracing::info!(
+            track = %track.path().display(),
+            ?length,
+            gain = track.gain().factor(),
+            "[simulated] playing"
+        );
+        let now = self.now();
+        self.current = Some(SimulatedTrack {
+            path: track.path().to_path_buf(),
+            length,
+            offset: Duration::ZERO,
+            since: now,
+            paused: false,
+        });
+        Ok(())
+    }
+
+    fn stop(&mut self) -> Result<(), PlayerError> {
+        let now = self.now();
+        if let Some(current) = self.current.take() {
+            tracing::info!(
+                track = %current.path.display(),
+                position = ?current.position(now),
+                "[simulated] stopped"
+            );
+        }
+        Ok(())
+    }
+
+    fn pause(&mut self) -> Result<(), PlayerError> {
+        let now = self.now();
+        if let Some(current) = self.current.as_mut().filter(|current| !current.paused) {
+            current.offset = current.position(now);
+            current.since = now;
+            current.paused = true;
+            tracing::info!(position = ?current.offset, "[simulated] paused");
+        }
+        Ok(())
+    }
+
+    fn resume(&mut self) -> Result<(), PlayerError> {
+        let now = self.now();
+        if let Some(current) = self.current.as_mut().filter(|current| current.paused) {
+            current.since = now;
+            current.paused = false;
+            tracing::info!(position = ?current.offset, "[simulated] resumed");
+        }
+        Ok(())
+    }
+
+    fn is_finished(&self) -> bool {
+        self.current
+            .as_ref()
+            .is_some_and(|current| current.position(self.now()) >= current.length)
+    }
+
+    fn set_volume(&mut self, volume: f32) -> Result<(), PlayerError> {
+        self.volume = volume;
+        Ok(())
+    }
+
+    fn volume(&self) -> f32 {
+        self.volume
+    }
+
+    fn set_muted(&mut self, muted: bool) -> Result<(), PlayerError> {
+        self.muted = muted;
+        Ok(())
+    }
+
+    fn position(&self) -> Option<Duration> {
+        self.current
+            .as_ref()
+            .map(|current| current.position(self.now()))
+    }
+
+    fn seek(&mut self, offset: Duration) -> Result<(), PlayerError> {
+        let now = self.now();
+        if let Some(current) = self.current.as_mut() {
+            current.offset = offset.min(current.length);
+            current.since = now;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn night_compressor_squeezes_loud_passages_under_the_ceiling() {
+        let mut compressor = Compressor::new(8_000, 1);
+        let quiet: Vec<f32> = (0..800).map(|_| compressor.process(0.1)).collect();
+        assert!((quiet[799] - 0.1 * NIGHT_MAKEUP).abs() < 1e-3);
+
+        let loud: Vec<f32> = (0..800).map(|_| compressor.process(0.9)).collect();
+        assert!(loud.iter().all(|sample| sample.abs() <= NIGHT_CEILING));
+        assert_eq!(loud[799], NIGHT_CEILING);
+
+        // Once the envelope settles, levels above the threshold grow at a
+        // quarter of the rate.
+        let medium = (0..20_000).map(|_| compressor.process(0.4)).last();
+        let settled = NIGHT_THRESHOLD + (0.4 - NIGHT_THRESHOLD) / NIGHT_RATIO;
+        assert!((medium.unwrap() - settled * NIGHT_MAKEUP).abs() < 1e-3);
+        assert!(compressor.process(-0.9) >= -NIGHT_CEILING);
+    }
+
+    #[test]
+    fn fade_out_levels_ramp_linearly_to_silence() {
+        let levels: Vec<f32> = fade_out_levels(0.8, Duration::from_millis(80)).collect();
+        assert_eq!(levels, vec![0.6, 0.4, 0.2, 0.0]);
+        let instant: Vec<f32> = fade_out_levels(0.8, Duration::ZERO).collect();
+        assert_eq!(instant, vec![0.0]);
+    }
+
+    #[test]
+    fn simulated_playlists_advance_when_their_tracks_would_end() {
+        use crate::controller::{
+            CardUid, ControllerAction, Library, MusicBoxController, PlaybackMode,
+        };
+
+        let clock = VirtualClock::new();
+        let card = CardUid::new(vec![1]);
+        let tracks = [PathBuf::from("one.mp3"), PathBuf::from("two.mp3")];
+        let player = SimulatedPlayer::new()
+            .with_clock(clock.clone())
+            .with_lengths(HashMap::from([
+                (tracks[0].clone(), Duration::from_secs(60)),
+                (tracks[1].clone(), Duration::from_secs(30)),
+            ]));
+        let library = Library::from_playlists(HashMap::from([(
+            card.clone(),
+            tracks.iter().cloned().map(Track::new).collect(),
+        )]))
+        .with_playback_mode(PlaybackMode::PauseResume);
+        let mut controller = MusicBoxController::new(library, player);
+        controller.handle_card(&card).unwrap();
+
+        clock.advance(Duration::from_secs(59));
+        assert_eq!(controller.poll_playback_at(clock.now()).unwrap(), None);
+        clock.advance(Duration::from_secs(1));
+        assert!(matches!(
+            controller.poll_playback_at(clock.now()).unwrap(),
+            Some(ControllerAction::Advanced { track, .. }) if track.path() == tracks[1]
+        ));
+
+        // Paused time does not count towards the track's length.
+        controller.handle_card(&card).unwrap();
+        clock.advance(Duration::from_secs(600));
+        assert!(matches!(
+            controller.handle_card(&card).unwrap(),
+            ControllerAction::Resumed { .. }
+        ));
+        clock.advance(Duration::from_secs(29));
+        assert_eq!(controller.poll_playback_at(clock.now()).unwrap(), None);
+        clock.advance(Duration::from_secs(1));
+        assert!(matches!(
+            controller.poll_playback_at(clock.now()).unwrap(),
+            Some(ControllerAction::Stopped { .. })
+        ));
+    }
+
+    #[test]
+    fn simulated_tracks_take_their_length_from_the_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("tone.wav");
+        crate::memo::Clip {
+            sample_rate: 8_000,
+            channels: 1,
+            samples: vec![0; 16_000],
+        }
+        .write_wav(&path)
+        .unwrap();
+        let clock = VirtualClock::new();
+        let mut player = SimulatedPlayer::new().with_clock(clock.clone());
+
+        player.play(&Track::new(path)).unwrap();
+        player.seek(Duration::from_secs(1)).unwrap();
+        clock.advance(Duration::from_millis(999));
+        assert!(!player.is_finished());
+        clock.advance(Duration::from_millis(1));
+        assert!(player.is_finished());
+        assert_eq!(player.position(), Some(Duration::from_secs(2)));
+
+        player
+            .play(&Track::new(dir.path().join("missing.mp3")))
+            .unwrap();
+        clock.advance(SIMULATED_TRACK_LENGTH - Duration::from_secs(1));
+        assert!(!player.is_finished());
+    }
+}
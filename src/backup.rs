@@ -0,0 +1,446 @@
+//! Single-file backups of everything needed to rebuild a box.
+//!
+//! A backup is a zstd-compressed tarball. The first entry, `manifest.json`,
+//! records where each file came from, so `restore` can put everything back
+//! in place on a freshly flashed SD card:
+//!
+//! - `config/<name>`: the config file;
+//! - `state/<n>-<name>`: state files such as the history log, restored to
+//!   the state file of the same name the restoring run points at;
+//! - `music/...`: the music directory, when requested.
+
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File};
+use std::io::Read;
+use std::path::{Component, Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const MANIFEST: &str = "manifest.json";
+const FORMAT_VERSION: u32 = 1;
+
+#[derive(Debug, thiserror::Error)]
+pub enum BackupError {
+    #[error("{path:?}: {source}")]
+    Io {
+        path: PathBuf,
+        #[source]
+        source: std::io::Error,
+    },
+    #[error("invalid backup manifest: {0}")]
+    Manifest(#[from] serde_json::Error),
+    #[error("archive has no {MANIFEST}; is it a musicbox backup?")]
+    MissingManifest,
+    #[error("backup format {0} is newer than this musicbox understands")]
+    UnsupportedFormat(u32),
+    #[error("archive entry {0:?} would escape its target directory")]
+    UnsafePath(PathBuf),
+    #[error("archive entry {0:?} is not a regular file or directory")]
+    UnsupportedEntry(PathBuf),
+    #[error("{0:?} already exists; pass --force to overwrite")]
+    Exists(PathBuf),
+}
+
+/// What to put in a backup.
+#[derive(Debug, Clone, Default)]
+pub struct BackupSources {
+    pub config: PathBuf,
+    /// State files such as the history log. Missing files are skipped.
+    pub state_files: Vec<PathBuf>,
+    /// The music directory, when it should be included.
+    pub music_dir: Option<PathBuf>,
+}
+
+/// Where to restore a backup; `None` means the original location.
+#[derive(Debug, Clone, Default)]
+pub struct RestoreOptions {
+    pub config: Option<PathBuf>,
+    pub music_dir: Option<PathBuf>,
+    /// Where state files go. Each archived state file replaces the one here
+    /// with the same file name and is skipped when there is none, so an
+    /// archive cannot write state anywhere the box does not use.
+    pub state_files: Vec<PathBuf>,
+    /// Replace files that already exist instead of refusing.
+    pub overwrite: bool,
+}
+
+/// Counts of what a backup or restore touched.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BundleSummary {
+    /// The config and state files.
+    pub files: usize,
+    pub music_files: usize,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Manifest {
+    format: u32,
+    /// Seconds since the Unix epoch.
+    created: u64,
+    config: PathBuf,
+    state_files: Vec<StateEntry>,
+    music_dir: Option<PathBuf>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct StateEntry {
+    archive_name: String,
+    path: PathBuf,
+}
+
+fn io_err(path: &Path) -> impl FnOnce(std::io::Error) -> BackupError + '_ {
+    move |source| BackupError::Io {
+        path: path.to_path_buf(),
+        source,
+    }
+}
+
+/// Writes a backup of `sources` to `archive`.
+pub fn create_backup(
+    archive: &Path,
+    sources: &BackupSources,
+) -> Result<BundleSummary, BackupError> {
+    let state_files: Vec<StateEntry> = sources
+        .state_files
+        .iter()
+        .filter(|path| {
+            let exists = path.is_file();
+            if !exists {
+                tracing::warn!(path = %path.display(), "state file missing; leaving it out of the backup");
+            }
+            exists
+        })
+        .enumerate()
+        .map(|(index, path)| StateEntry {
+            archive_name: format!("{index}-{}", file_name(path)),
+            path: absolute(path),
+        })
+        .collect();
+    let manifest = Manifest {
+        format: FORMAT_VERSION,
+        created: SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|elapsed| elapsed.as_secs())
+            .unwrap_or(0),
+        config: absolute(&sources.config),
+        state_files,
+        music_dir: sources.music_dir.as_deref().map(absolute),
+    };
+
+    let file = File::create(archive).map_err(io_err(archive))?;
+    let encoder = zstd::Encoder::new(file, 0).map_err(io_err(archive))?;
+    let mut builder = tar::Builder::new(encoder);
+    let mut summary = BundleSummary::default();
+
+    let manifest_bytes = serde_json::to_vec_pretty(&manifest)?;
+    let mut header = tar::Header::new_gnu();
+    header.set_size(manifest_bytes.len() as u64);
+    header.set_mode(0o644);
+    header.set_mtime(manifest.created);
+    header.set_cksum();
+    builder
+        .append_data(&mut header, MANIFEST, manifest_bytes.as_slice())
+        .map_err(io_err(archive))?;
+
+    builder
+        .append_path_with_name(
+            &sources.config,
+            Path::new("config").join(file_name(&sources.config)),
+        )
+        .map_err(io_err(&sources.config))?;
+    summary.files += 1;
+    for entry in &manifest.state_files {
+        builder
+            .append_path_with_name(&entry.path, Path::new("state").join(&entry.archive_name))
+            .map_err(io_err(&entry.path))?;
+        summary.files += 1;
+    }
+    if let Some(music_dir) = &sources.music_dir {
+        builder
+            .append_dir_all("music", music_dir)
+            .map_err(io_err(music_dir))?;
+        summary.music_files = count_files(music_dir).map_err(io_err(music_dir))?;
+    }
+
+    builder
+        .into_inner()
+        .and_then(|encoder| encoder.finish())
+        .map_err(io_err(archive))?;
+    Ok(summary)
+}
+
+/// Unpacks a backup made by [`create_backup`].
+pub fn restore_backup(
+    archive: &Path,
+    options: &RestoreOptions,
+) -> Result<BundleSummary, BackupError> {
+    let file = File::open(archive).map_err(io_err(archive))?;
+    let decoder = zstd::Decoder::new(file).map_err(io_err(archive))?;
+    let mut tarball = tar::Archive::new(decoder);
+    let mut entries = tarball.entries().map_err(io_err(archive))?;
+
+    let manifest: Manifest = {
+        let mut first = entries
+            .next()
+            .ok_or(BackupError::MissingManifest)?
+            .map_err(io_err(archive))?;
+        if first.path().map_err(io_err(archive))?.as_ref() != Path::new(MANIFEST) {
+            return Err(BackupError::MissingManifest);
+        }
+        let mut bytes = Vec::new();
+        first.read_to_end(&mut bytes).map_err(io_err(archive))?;
+        serde_json::from_slice(&bytes)?
+    };
+    if manifest.format > FORMAT_VERSION {
+        return Err(BackupError::UnsupportedFormat(manifest.format));
+    }
+    let config_target = options.config.clone().unwrap_or(manifest.config.clone());
+    let music_target = options.music_dir.clone().or(manifest.music_dir.clone());
+
+    let mut summary = BundleSummary::default();
+    for entry in entries {
+        let mut entry = entry.map_err(io_err(archive))?;
+        let path = entry.path().map_err(io_err(archive))?.into_owned();
+        let kind = entry.header().entry_type();
+        if !kind.is_file() && !kind.is_dir() {
+            return Err(BackupError::UnsupportedEntry(path));
+        }
+        let mut components = path.components();
+        let section = components.next();
+        let rest = components.as_path();
+        if !rest
+            .components()
+            .all(|part| matches!(part, Component::Normal(_)))
+        {
+            return Err(BackupError::UnsafePath(path));
+        }
+        let (target, is_music) = match section.and_then(|part| part.as_os_str().to_str()) {
+            Some("config") => (config_target.clone(), false),
+            Some("state") => {
+                let Some(state) = manifest
+                    .state_files
+                    .iter()
+                    .find(|state| Path::new(&state.archive_name) == rest)
+                else {
+                    continue;
+                };
+                let name = file_name(&state.path);
+                let Some(target) = options
+                    .state_files
+                    .iter()
+                    .find(|path| file_name(path) == name)
+                else {
+                    tracing::warn!(
+                        path = %state.path.display(),
+                        "no state file of that name to restore to; skipping it"
+                    );
+                    continue;
+                };
+                (target.clone(), false)
+            }
+            Some("music") => match &music_target {
+                Some(music_dir) => (music_dir.join(rest), true),
+                None => continue,
+            },
+            _ => continue,
+        };
+
+        if entry.header().entry_type().is_dir() {
+            fs::create_dir_all(&target).map_err(io_err(&target))?;
+            continue;
+        }
+        if target.exists() && !options.overwrite {
+            return Err(BackupError::Exists(target));
+        }
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent).map_err(io_err(parent))?;
+        }
+        entry.unpack(&target).map_err(io_err(&target))?;
+        if is_music {
+            summary.music_files += 1;
+        } else {
+            summary.files += 1;
+        }
+    }
+    Ok(summary)
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "file".to_string())
+}
+
+/// Makes `path` absolute so a restore run from another directory lands in
+/// the same place.
+fn absolute(path: &Path) -> PathBuf {
+    std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf())
+}
+
+fn count_files(dir: &Path) -> Result<usize, std::io::Error> {
+    let mut count = 0;
+    let mut pending = vec![dir.to_path_buf()];
+    while let Some(current) = pending.pop() {
+        for entry in fs::read_dir(&current)? {
+            let path = entry?.path();
+            if path.is_dir() {
+                pending.push(path);
+            } else {
+                count += 1;
+            }
+        }
+    }
+    Ok(count)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    fn sample_box(root: &Path) -> BackupSources {
+        let music_dir = root.join("music");
+        fs::create_dir_all(music_dir.join("album")).unwrap();
+        fs::write(music_dir.join("album/01.mp3"), b"ID3").unwrap();
+        fs::write(
+            root.join("musicbox.toml"),
+            "music_dir = \"music\"\n[cards]\n",
+        )
+        .unwrap();
+        fs::write(root.join("history.jsonl"), "{}\n").unwrap();
+        BackupSources {
+            config: root.join("musicbox.toml"),
+            state_files: vec![root.join("history.jsonl"), root.join("never-written.jsonl")],
+            music_dir: Some(music_dir),
+        }
+    }
+
+    #[test]
+    fn backup_round_trips_to_new_locations() {
+        let tmp = tempdir().unwrap();
+        let sources = sample_box(&tmp.path().join("old"));
+        let archive = tmp.path().join("box.tar.zst");
+
+        let created = create_backup(&archive, &sources).unwrap();
+        assert_eq!(
+            created,
+            BundleSummary {
+                files: 2,
+                music_files: 1
+            }
+        );
+
+        fs::remove_dir_all(tmp.path().join("old")).unwrap();
+        let new = tmp.path().join("new");
+        let restored = restore_backup(
+            &archive,
+            &RestoreOptions {
+                config: Some(new.join("musicbox.toml")),
+                music_dir: Some(new.join("music")),
+                state_files: vec![new.join("state/history.jsonl")],
+                overwrite: false,
+            },
+        )
+        .unwrap();
+
+        assert_eq!(restored, created);
+        assert_eq!(
+            fs::read_to_string(new.join("musicbox.toml")).unwrap(),
+            "music_dir = \"music\"\n[cards]\n"
+        );
+        assert_eq!(fs::read(new.join("music/album/01.mp3")).unwrap(), b"ID3");
+        assert_eq!(
+            fs::read_to_string(new.join("state/history.jsonl")).unwrap(),
+            "{}\n"
+        );
+        assert!(!tmp.path().join("old").exists());
+    }
+
+    #[test]
+    fn state_files_only_go_where_the_restoring_run_points() {
+        let tmp = tempdir().unwrap();
+        let sources = BackupSources {
+            music_dir: None,
+            ..sample_box(tmp.path())
+        };
+        let archive = tmp.path().join("box.tar.zst");
+        create_backup(&archive, &sources).unwrap();
+        fs::remove_file(tmp.path().join("history.jsonl")).unwrap();
+
+        let restored = restore_backup(
+            &archive,
+            &RestoreOptions {
+                config: Some(tmp.path().join("restored.toml")),
+                ..RestoreOptions::default()
+            },
+        )
+        .unwrap();
+        assert_eq!(restored.files, 1);
+        assert!(!tmp.path().join("history.jsonl").exists());
+    }
+
+    #[test]
+    fn restore_rejects_links() {
+        let tmp = tempdir().unwrap();
+        let archive = tmp.path().join("box.tar.zst");
+        let manifest = Manifest {
+            format: FORMAT_VERSION,
+            created: 0,
+            config: tmp.path().join("musicbox.toml"),
+            state_files: Vec::new(),
+            music_dir: Some(tmp.path().join("music")),
+        };
+        let manifest = serde_json::to_vec(&manifest).unwrap();
+        let encoder = zstd::Encoder::new(File::create(&archive).unwrap(), 0).unwrap();
+        let mut builder = tar::Builder::new(encoder);
+        let mut header = tar::Header::new_gnu();
+        header.set_size(manifest.len() as u64);
+        header.set_cksum();
+        builder
+            .append_data(&mut header, MANIFEST, manifest.as_slice())
+            .unwrap();
+        let mut header = tar::Header::new_gnu();
+        header.set_entry_type(tar::EntryType::Symlink);
+        header.set_size(0);
+        builder
+            .append_link(&mut header, "music/escape", "/etc")
+            .unwrap();
+        builder.into_inner().unwrap().finish().unwrap();
+
+        let err = restore_backup(&archive, &RestoreOptions::default()).unwrap_err();
+        assert!(
+            matches!(err, BackupError::UnsupportedEntry(path) if path == Path::new("music/escape"))
+        );
+        assert!(!tmp.path().join("music/escape").exists());
+    }
+
+    #[test]
+    fn restore_refuses_to_overwrite_without_permission() {
+        let tmp = tempdir().unwrap();
+        let sources = BackupSources {
+            music_dir: None,
+            ..sample_box(tmp.path())
+        };
+        let archive = tmp.path().join("box.tar.zst");
+        create_backup(&archive, &sources).unwrap();
+        fs::write(&sources.config, "changed").unwrap();
+
+        let err = restore_backup(&archive, &RestoreOptions::default()).unwrap_err();
+        assert!(matches!(err, BackupError::Exists(path) if path == absolute(&sources.config)));
+
+        let overwrite = RestoreOptions {
+            state_files: sources.state_files.clone(),
+            overwrite: true,
+            ..RestoreOptions::default()
+        };
+        restore_backup(&archive, &overwrite).unwrap();
+        assert!(
+            fs::read_to_string(&sources.config)
+                .unwrap()
+                .contains("[cards]")
+        );
+        assert_eq!(
+            fs::read_to_string(tmp.path().join("history.jsonl")).unwrap(),
+            "{}\n"
+        );
+    }
+}
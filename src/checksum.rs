@@ -0,0 +1,280 @@
+//! SHA-256 checksums for the music library, to catch bit rot on aging SD
+//! cards before it shows up as a decode error halfway through a song.
+//!
+//! Checksums live in [`MANIFEST_NAME`] at the top of `music_dir`, in the
+//! format `sha256sum` writes, so `sha256sum -c checksums.sha256` run from
+//! `music_dir` checks the same files.
+
+use crate::config::audio_files_under;
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+
+pub const MANIFEST_NAME: &str = "checksums.sha256";
+
+#[derive(Debug, thiserror::Error)]
+pub enum ChecksumError {
+    #[error("{path:?}: {source}")]
+    Io {
+        path: PathBuf,
+        #[source]
+        source: io::Error,
+    },
+    #[error("{path:?} line {line}: expected `<sha256>  <path>`")]
+    Malformed { path: PathBuf, line: usize },
+    #[error("no checksums recorded yet; run `musicbox verify --update` first")]
+    MissingManifest,
+}
+
+/// What verification found for one track.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TrackStatus {
+    Ok,
+    /// The file's contents no longer match the recorded checksum.
+    Mismatch,
+    /// The checksum is recorded but the file is gone.
+    Missing,
+    /// The file has no recorded checksum.
+    Unrecorded,
+}
+
+/// One track's verification result, with its path relative to `music_dir`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TrackCheck {
+    pub path: PathBuf,
+    pub status: TrackStatus,
+}
+
+/// The file about to be hashed, for progress reports.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TrackProgress<'a> {
+    /// How many files were hashed before this one.
+    pub index: usize,
+    pub total: usize,
+    /// Relative to `music_dir`.
+    pub path: &'a Path,
+}
+
+/// Recorded checksums, keyed by path relative to `music_dir`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ChecksumManifest {
+    entries: BTreeMap<PathBuf, String>,
+}
+
+impl ChecksumManifest {
+    /// Reads the manifest in `music_dir`.
+    pub fn load(music_dir: &Path) -> Result<Self, ChecksumError> {
+        let path = music_dir.join(MANIFEST_NAME);
+        let contents = match fs::read_to_string(&path) {
+            Ok(contents) => contents,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => {
+                return Err(ChecksumError::MissingManifest);
+            }
+            Err(source) => return Err(ChecksumError::Io { path, source }),
+        };
+        let mut entries = BTreeMap::new();
+        for (index, line) in contents.lines().enumerate() {
+            if line.trim().is_empty() {
+                continue;
+            }
+            // `sha256sum` marks binary-mode entries with `*` instead of a space.
+            let parsed = line
+                .split_once("  ")
+                .or_else(|| line.split_once(" *"))
+                .filter(|(digest, _)| {
+                    digest.len() == 64 && digest.chars().all(|c| c.is_ascii_hexdigit())
+                });
+            let Some((digest, track)) = parsed else {
+                return Err(ChecksumError::Malformed {
+                    path,
+                    line: index + 1,
+                });
+            };
+            entries.insert(PathBuf::from(track), digest.to_ascii_lowercase());
+        }
+        Ok(Self { entries })
+    }
+
+    /// Checksums every audio file under `music_dir`.
+    pub fn compute(music_dir: &Path) -> Result<Self, ChecksumError> {
+        Self::compute_with(music_dir, |_| {})
+    }
+
+    /// Like [`Self::compute`], calling `on_track` before hashing each file.
+    pub fn compute_with(
+        music_dir: &Path,
+        mut on_track: impl FnMut(TrackProgress<'_>),
+    ) -> Result<Self, ChecksumError> {
+        let io_err = |path: &Path| {
+            let path = path.to_path_buf();
+            move |source| ChecksumError::Io { path, source }
+        };
+        let mut entries = BTreeMap::new();
+        let files = audio_files_under(music_dir).map_err(io_err(music_dir))?;
+        for (index, path) in files.iter().enumerate() {
+            let relative = relative_to(music_dir, path);
+            on_track(TrackProgress {
+                index,
+                total: files.len(),
+                path: &relative,
+            });
+            let digest = sha256_file(path).map_err(io_err(path))?;
+            entries.insert(relative, digest);
+        }
+        Ok(Self { entries })
+    }
+
+    /// Writes the manifest into `music_dir`, replacing any previous one.
+    pub fn save(&self, music_dir: &Path) -> Result<(), ChecksumError> {
+        let path = music_dir.join(MANIFEST_NAME);
+        let contents: String = self
+            .entries
+            .iter()
+            .map(|(track, digest)| format!("{digest}  {}\n", track.display()))
+            .collect();
+        fs::write(&path, contents).map_err(|source| ChecksumError::Io { path, source })
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Re-hashes the library and compares it to the recorded checksums.
+    /// Tracks that are fine are included, so callers can report progress.
+    pub fn verify(&self, music_dir: &Path) -> Result<Vec<TrackCheck>, ChecksumError> {
+        self.verify_with(music_dir, |_| {})
+    }
+
+    /// Like [`Self::verify`], calling `on_track` before hashing each file.
+    pub fn verify_with(
+        &self,
+        music_dir: &Path,
+        on_track: impl FnMut(TrackProgress<'_>),
+    ) -> Result<Vec<TrackCheck>, ChecksumError> {
+        let current = Self::compute_with(music_dir, on_track)?;
+        let mut checks: Vec<TrackCheck> = current
+            .entries
+            .iter()
+            .map(|(path, digest)| TrackCheck {
+                path: path.clone(),
+                status: match self.entries.get(path) {
+                    Some(recorded) if recorded == digest => TrackStatus::Ok,
+                    Some(_) => TrackStatus::Mismatch,
+                    None => TrackStatus::Unrecorded,
+                },
+            })
+            .collect();
+        checks.extend(
+            self.entries
+                .keys()
+                .filter(|path| !current.entries.contains_key(*path))
+                .map(|path| TrackCheck {
+                    path: path.clone(),
+                    status: TrackStatus::Missing,
+                }),
+        );
+        checks.sort_by(|a, b| a.path.cmp(&b.path));
+        Ok(checks)
+    }
+}
+
+pub(crate) fn sha256_file(path: &Path) -> Result<String, io::Error> {
+    let mut file = File::open(path)?;
+    let mut hasher = Sha256::new();
+    let mut buffer = [0u8; 64 * 1024];
+    loop {
+        let read = file.read(&mut buffer)?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+    }
+    Ok(hasher
+        .finalize()
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect())
+}
+
+fn relative_to(music_dir: &Path, path: &Path) -> PathBuf {
+    path.strip_prefix(music_dir)
+        .map(Path::to_path_buf)
+        .unwrap_or_else(|_| path.to_path_buf())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn sha256_matches_known_digest() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("abc.mp3");
+        fs::write(&path, b"abc").unwrap();
+        assert_eq!(
+            sha256_file(&path).unwrap(),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+    }
+
+    #[test]
+    fn verify_flags_changed_missing_and_new_tracks() {
+        let dir = tempdir().unwrap();
+        fs::create_dir(dir.path().join("album")).unwrap();
+        fs::write(dir.path().join("album/01.mp3"), b"one").unwrap();
+        fs::write(dir.path().join("album/02.mp3"), b"two").unwrap();
+        fs::write(dir.path().join("gone.ogg"), b"gone").unwrap();
+        ChecksumManifest::compute(dir.path())
+            .unwrap()
+            .save(dir.path())
+            .unwrap();
+
+        fs::write(dir.path().join("album/02.mp3"), b"tw0").unwrap();
+        fs::remove_file(dir.path().join("gone.ogg")).unwrap();
+        fs::write(dir.path().join("new.flac"), b"new").unwrap();
+
+        let manifest = ChecksumManifest::load(dir.path()).unwrap();
+        assert_eq!(manifest.len(), 3);
+        let mut progress = Vec::new();
+        let statuses: Vec<(PathBuf, TrackStatus)> = manifest
+            .verify_with(dir.path(), |track| {
+                progress.push((track.index, track.total))
+            })
+            .unwrap()
+            .into_iter()
+            .map(|check| (check.path, check.status))
+            .collect();
+        assert_eq!(
+            statuses,
+            vec![
+                (PathBuf::from("album/01.mp3"), TrackStatus::Ok),
+                (PathBuf::from("album/02.mp3"), TrackStatus::Mismatch),
+                (PathBuf::from("gone.ogg"), TrackStatus::Missing),
+                (PathBuf::from("new.flac"), TrackStatus::Unrecorded),
+            ]
+        );
+        assert_eq!(progress, vec![(0, 3), (1, 3), (2, 3)]);
+    }
+
+    #[test]
+    fn load_reports_missing_and_malformed_manifests() {
+        let dir = tempdir().unwrap();
+        assert!(matches!(
+            ChecksumManifest::load(dir.path()),
+            Err(ChecksumError::MissingManifest)
+        ));
+
+        fs::write(dir.path().join(MANIFEST_NAME), "not a checksum line\n").unwrap();
+        assert!(matches!(
+            ChecksumManifest::load(dir.path()),
+            Err(ChecksumError::Malformed { line: 1, .. })
+        ));
+    }
+}
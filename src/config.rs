@@ -1,12 +1,41 @@
-use crate::controller::{CardUid, CardUidParseError, Library, Track};
-use serde::Deserialize;
-use std::collections::HashMap;
+use crate::access::{ApiToken, ApiTokens, DEFAULT_MAX_UPLOAD_BYTES, TokenScope, WebSettings};
+use crate::audio::FadeSettings;
+use crate::controller::{
+    AmbientPolicy, CardUid, CardUidParseError, Combo, Countdown, DEFAULT_COMBO_WINDOW,
+    DEFAULT_VOLUME_STEP, Effects, Gain, GroupAction, GroupCommand, Library, PlaybackMode,
+    PlaylistEnd, PlaylistMode, SleepTimer, SoundCues, StartupAction, SystemCommand, Track,
+};
+use crate::display::remote::{self, RemoteDisplaySettings, RemoteTarget};
+use crate::expiry;
+use crate::greeting::{Greeting, GreetingPeriod, Greetings};
+use crate::input::{ButtonConfig, ButtonEvent};
+use crate::led::{ColorParseError, Rgb};
+use crate::locale::Locale;
+use crate::loudness::{self, LoudnessSettings};
+use crate::memo::MemoSettings;
+use crate::mirror;
+use crate::mqtt::{self, MqttSettings, MqttTls};
+use crate::night::{NightSchedule, NightSettings};
+use crate::notify::{self, BatteryWatch, Notifier, NotifyEvents, PushService};
+use crate::pn532::{self, Pn532Settings};
+use crate::podcast::{self, EpisodeOrder, Podcasts};
+use crate::reader::{PcscSettings, ReaderZone};
+use crate::sandbox;
+use crate::secrets::{Secrets, SecretsError};
+use crate::speech::{self, Speech};
+use crate::spotify::{self, SpotifySettings};
+use crate::stream;
+use crate::subsonic::{self, SubsonicCache, SubsonicServer};
+use crate::webhook::WebhookUrl;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::fs;
 use std::io::Read;
 use std::path::PathBuf;
 
 use std::path::Path;
-use toml_edit::{DocumentMut, table, value};
+use std::time::{Duration, SystemTime};
+use toml_edit::{DocumentMut, Item, table, value};
 
 #[derive(Debug, thiserror::Error)]
 pub enum ConfigError {
@@ -16,10 +45,114 @@ pub enum ConfigError {
     ParseToml(#[from] toml::de::Error),
     #[error("invalid card uid: {0}")]
     CardUid(#[from] CardUidParseError),
-    #[error("duplicate mapping for card {0:?}")]
-    DuplicateCard(CardUid),
+    #[error("cards mapped more than once: {}", format_duplicates(.0))]
+    DuplicateCards(Vec<DuplicateCard>),
+    #[error("music_dir is not set in the config")]
+    MissingMusicDir,
+    #[error("card name {0:?} is used by more than one card")]
+    DuplicateName(String),
+    #[error("{entry}: {source}")]
+    Color {
+        entry: String,
+        #[source]
+        source: ColorParseError,
+    },
+    #[error("card {card} needs exactly one of `track` or a non-empty `tracks` list")]
+    TrackSpec { card: String },
+    #[error("card {card} refers to group {group:?}, which has no [groups.{group}] table")]
+    UnknownGroup { card: String, group: String },
+    #[error("card {card} refers to reader {reader:?}, which is not in the [readers] table")]
+    UnknownReader { card: String, reader: String },
+    #[error("invalid [ambient] table: {0}")]
+    Ambient(&'static str),
+    #[error("invalid [fade] table: {0}")]
+    Fade(&'static str),
+    #[error("invalid [buttons] table: {0}")]
+    Buttons(String),
+    #[error("invalid [notify] table: {0}")]
+    Notify(String),
+    #[error("invalid subsonic setup: {0}")]
+    Subsonic(String),
+    #[error("invalid spotify setup: {0}")]
+    Spotify(String),
+    #[error("invalid [pn532] table: {0}")]
+    Pn532(String),
+    #[error("invalid [pcsc] table: {0}")]
+    Pcsc(String),
+    #[error("invalid [night] table: {0}")]
+    Night(String),
+    #[error("invalid [greetings] table: {0}")]
+    Greetings(String),
+    #[error("invalid [remote_display] table: {0}")]
+    RemoteDisplay(String),
+    #[error("invalid [mqtt] table: {0}")]
+    Mqtt(String),
+    #[error("invalid [loudness] table: {0}")]
+    Loudness(String),
+    #[error("invalid [effects] table: {0}")]
+    Effects(String),
+    #[error("invalid [startup] table: {0}")]
+    Startup(&'static str),
+    #[error("invalid api token {index}: {reason}")]
+    ApiToken { index: usize, reason: String },
+    #[error("invalid podcast entry: {0}")]
+    Podcast(String),
+    #[error("invalid stream entry: {0}")]
+    Stream(String),
+    #[error("invalid [recording] table: {0}")]
+    Recording(String),
+    #[error("invalid [speech] table: {0}")]
+    Speech(String),
+    #[error(
+        "card {card}: `expires` must be a date and time with an offset, like 2026-10-25T18:00:00Z"
+    )]
+    Expiry { card: String },
+    #[error("card {card}: invalid `{key}`: {reason}")]
+    OnEnd {
+        card: String,
+        /// `on_end` or `on_finish`, whichever the card set.
+        key: &'static str,
+        reason: String,
+    },
+    #[error("card {card}: invalid countdown: {reason}")]
+    Countdown { card: String, reason: &'static str },
+    #[error("card {card}: invalid loudness: {reason}")]
+    Gain { card: String, reason: &'static str },
+    #[error("invalid combo {index}: {reason}")]
+    Combo { index: usize, reason: String },
+    #[error(transparent)]
+    Secrets(#[from] SecretsError),
+    #[error("secret {0:?} is not in the secrets file")]
+    UnknownSecret(String),
+    #[error("secret {0:?} is referenced but no `secrets` file is set")]
+    NoSecretsFile(String),
+}
+
+/// Config keys that normalize to the same card UID.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DuplicateCard {
+    pub uid: CardUid,
+    pub keys: Vec<String>,
+}
+
+fn format_duplicates(duplicates: &[DuplicateCard]) -> String {
+    duplicates
+        .iter()
+        .map(|duplicate| {
+            let keys: Vec<String> = duplicate
+                .keys
+                .iter()
+                .map(|key| format!("{key:?}"))
+                .collect();
+            format!("{} (keys {})", duplicate.uid, keys.join(", "))
+        })
+        .collect::<Vec<_>>()
+        .join("; ")
 }
 
+/// File extensions treated as playable audio when scanning `music_dir`.
+pub const AUDIO_EXTENSIONS: &[&str] = &["mp3", "ogg", "oga", "flac", "wav", "m4a", "aac", "opus"];
+
 #[derive(Debug, thiserror::Error)]
 pub enum ConfigEditError {
     #[error("failed to read config {path:?}: {source}")]
@@ -38,205 +171,4985 @@ pub enum ConfigEditError {
     Parse(#[from] toml_edit::TomlError),
     #[error("config missing [cards] table")]
     MissingCards,
-    #[error("card {0:?} already mapped in config")]
+    #[error("card {0} already mapped in config")]
     Duplicate(CardUid),
+    #[error("card {0} is not mapped in config")]
+    NotMapped(CardUid),
+    #[error("card {0} plays a playlist or runs a command, not a single track")]
+    NotSingleTrack(CardUid),
+    #[error("failed to decode config: {0}")]
+    Decode(#[from] toml::de::Error),
+    #[error("invalid card uid: {0}")]
+    CardUid(#[from] CardUidParseError),
+}
+
+/// What [`merge_remote_config`] changed.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct SyncReport {
+    /// Cards copied from the remote config.
+    pub added: Vec<CardUid>,
+    /// Cards both configs already map the same way.
+    pub unchanged: Vec<CardUid>,
+    /// Entries mapped differently here; the local entry was kept.
+    pub conflicts: Vec<SyncConflict>,
+    /// `[groups]` tables copied from the remote config.
+    pub groups_added: Vec<String>,
+    /// Track entries (as written in the remote config) of every card now
+    /// mapped the same way on both boxes.
+    pub tracks: Vec<String>,
+}
+
+/// A card or group that the local and remote configs define differently.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SyncConflict {
+    /// `card <uid>` or `group <name>`.
+    pub entry: String,
+    pub local: String,
+    pub remote: String,
 }
 
 /// Represents the configuration for the music box.
 #[derive(Debug, Clone)]
 pub struct MusicBoxConfig {
     music_dir: PathBuf,
-    cards: HashMap<CardUid, PathBuf>,
+    allow_external_paths: bool,
+    cards: HashMap<CardUid, Vec<PathBuf>>,
+    names: HashMap<CardUid, String>,
+    colors: HashMap<CardUid, Rgb>,
+    groups: HashMap<CardUid, String>,
+    commands: HashMap<CardUid, GroupCommand>,
+    system_commands: HashMap<CardUid, SystemCommand>,
+    resumable: HashSet<CardUid>,
+    locked: HashSet<CardUid>,
+    expiries: HashMap<CardUid, SystemTime>,
+    modes: HashMap<CardUid, PlaylistMode>,
+    ends: HashMap<CardUid, PlaylistEnd>,
+    readers: HashMap<CardUid, String>,
+    gaps: HashMap<CardUid, Duration>,
+    gains: HashMap<CardUid, Gain>,
+    countdowns: HashMap<CardUid, Countdown>,
+    zones: Vec<ReaderZone>,
+    pn532: Option<Pn532Settings>,
+    pcsc: PcscSettings,
+    remote_display: Option<RemoteDisplaySettings>,
+    mqtt: Option<MqttSettings>,
+    loudness: Option<LoudnessSettings>,
+    audio_device: Option<String>,
+    audio_release_after: Option<Duration>,
+    night: NightSettings,
+    greetings: Greetings,
+    sounds: SoundCues,
+    effects: Effects,
+    startup: StartupAction,
+    ambient: Option<AmbientPolicy>,
+    playback_mode: PlaybackMode,
+    debounce: Duration,
+    combos: Vec<Combo>,
+    combo_window: Duration,
+    max_play: Option<Duration>,
+    max_plays: HashMap<CardUid, Option<Duration>>,
+    sleep_timer: SleepTimer,
+    api_tokens: ApiTokens,
+    web: WebSettings,
+    buttons: Option<ButtonConfig>,
+    volume_step: f32,
+    fades: FadeSettings,
+    notifier: Option<Notifier>,
+    subsonic: Option<SubsonicCache>,
+    spotify: Option<SpotifySettings>,
+    podcasts: Podcasts,
+    recording: Option<MemoSettings>,
+    speech: Option<Speech>,
+    locale: Locale,
 }
 
 #[derive(Debug, Deserialize)]
 struct RawConfig {
     music_dir: PathBuf,
-    cards: HashMap<String, String>,
+    /// Lets tracks, and symlinks inside `music_dir`, point outside it.
+    #[serde(default)]
+    allow_external_paths: bool,
+    #[serde(default)]
+    locale: Locale,
+    #[serde(default)]
+    playback_mode: RawPlaybackMode,
+    /// Milliseconds after acting on a card during which its events are
+    /// ignored.
+    #[serde(default)]
+    debounce_ms: u64,
+    /// Milliseconds within which the second card of a combo must follow
+    /// the first.
+    #[serde(default)]
+    combo_window_ms: Option<u64>,
+    /// Minutes any card plays before fading out, for bedtime; 0 or unset
+    /// lets cards play on.
+    #[serde(default)]
+    max_play_minutes: Option<u64>,
+    /// Seconds over which the sleep timer fades a card out, ending as its
+    /// time is up.
+    #[serde(default)]
+    sleep_fade_seconds: Option<u64>,
+    /// Seconds before the sleep timer runs out that `[effects]
+    /// sleep_warning` plays.
+    #[serde(default)]
+    sleep_warning_seconds: Option<u64>,
+    /// File of credentials that `{ secret = "..." }` values refer to;
+    /// relative paths resolve against the config's directory.
+    #[serde(default)]
+    secrets: Option<PathBuf>,
+    cards: HashMap<String, RawCard>,
+    #[serde(default)]
+    groups: HashMap<String, RawGroup>,
+    #[serde(default)]
+    combos: Vec<RawCombo>,
+    #[serde(default)]
+    sounds: RawSounds,
+    #[serde(default)]
+    effects: RawEffects,
+    #[serde(default)]
+    startup: Option<RawStartup>,
+    #[serde(default)]
+    ambient: Option<RawAmbient>,
+    #[serde(default)]
+    buttons: Option<RawButtons>,
+    #[serde(default)]
+    fade: RawFade,
+    #[serde(default)]
+    notify: Option<RawNotify>,
+    #[serde(default)]
+    subsonic: Option<RawSubsonic>,
+    #[serde(default)]
+    spotify: Option<RawSpotify>,
+    #[serde(default)]
+    podcasts: RawPodcasts,
+    #[serde(default)]
+    recording: Option<RawRecording>,
+    #[serde(default)]
+    speech: Option<RawSpeech>,
+    /// Reader labels mapped to part of the PC/SC device name.
+    #[serde(default)]
+    readers: BTreeMap<String, String>,
+    #[serde(default)]
+    pn532: Option<RawPn532>,
+    #[serde(default)]
+    pcsc: Option<RawPcsc>,
+    #[serde(default)]
+    audio: RawAudio,
+    #[serde(default)]
+    night: Option<RawNight>,
+    #[serde(default)]
+    greetings: RawGreetings,
+    #[serde(default)]
+    remote_display: Option<RawRemoteDisplay>,
+    #[serde(default)]
+    mqtt: Option<RawMqtt>,
+    #[serde(default)]
+    loudness: Option<RawLoudness>,
+    #[serde(default)]
+    api_tokens: Vec<RawApiToken>,
+    #[serde(default)]
+    web: RawWeb,
 }
 
-impl MusicBoxConfig {
-    pub fn from_reader<R: Read>(mut reader: R) -> Result<Self, ConfigError> {
-        let mut buffer = String::new();
-        reader.read_to_string(&mut buffer)?;
-        let raw: RawConfig = toml::from_str(&buffer)?;
-        Self::from_raw(raw)
-    }
+/// The optional `[web]` table: how the debug HTTP API may be used.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawWeb {
+    #[serde(default)]
+    read_only: bool,
+    /// The largest file `POST /api/upload` takes, in megabytes.
+    #[serde(default)]
+    max_upload_mb: Option<u64>,
+}
 
-    pub fn music_dir(&self) -> &Path {
-        &self.music_dir
-    }
+/// A `[groups.<name>]` table; its settings are defaults for member cards.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawGroup {
+    #[serde(default)]
+    color: Option<String>,
+    #[serde(default)]
+    mode: Option<RawPlaylistMode>,
+    #[serde(default)]
+    resume: Option<bool>,
+    #[serde(default)]
+    gap_ms: Option<u64>,
+    #[serde(default)]
+    volume: Option<f32>,
+    #[serde(default)]
+    gain_db: Option<f32>,
+    #[serde(default)]
+    max_play_minutes: Option<u64>,
+}
 
-    fn from_raw(raw: RawConfig) -> Result<Self, ConfigError> {
-        let RawConfig { music_dir, cards } = raw;
-        let music_dir = music_dir;
-        let mut parsed = HashMap::with_capacity(cards.len());
-        for (card_hex, relative_path) in cards {
-            let uid = CardUid::from_hex(card_hex.trim())?;
-            let track_path = resolve_track_path(&music_dir, relative_path.trim());
-            if parsed.insert(uid.clone(), track_path).is_some() {
-                return Err(ConfigError::DuplicateCard(uid));
+/// A `[[combos]]` entry: two cards, by name or UID, and what they play
+/// when tapped one after the other.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawCombo {
+    cards: Vec<String>,
+    #[serde(default)]
+    track: Option<String>,
+    #[serde(default)]
+    tracks: Option<Vec<String>>,
+}
+
+/// The optional `[sounds]` table of cue files, relative to `music_dir`.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawSounds {
+    goodnight: Option<String>,
+    /// Played when a countdown card's time is up.
+    chime: Option<String>,
+}
+
+/// The optional `[effects]` table of sound effects, relative to
+/// `music_dir`, mixed over whatever plays.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawEffects {
+    ready: Option<String>,
+    unknown_card: Option<String>,
+    shutdown: Option<String>,
+    sleep_warning: Option<String>,
+}
+
+/// The optional `[startup]` table: what happens once the box has booted.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawStartup {
+    action: RawStartupAction,
+    /// Cue for `action = "chime"`, relative to `music_dir`.
+    #[serde(default)]
+    chime: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+enum RawStartupAction {
+    Silent,
+    Chime,
+    Resume,
+    Ambient,
+}
+
+/// The optional `[ambient]` table: a quiet playlist for idle stretches.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawAmbient {
+    /// Tracks or folders, relative to `music_dir`.
+    tracks: Vec<String>,
+    /// Minutes of silence before the playlist starts.
+    after_minutes: u64,
+    #[serde(default = "default_ambient_volume")]
+    volume: f32,
+}
+
+fn default_ambient_volume() -> f32 {
+    0.3
+}
+
+/// The optional `[fade]` table: how tracks fade when switching or stopping.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawFade {
+    #[serde(default)]
+    out_ms: u64,
+    #[serde(default)]
+    in_ms: u64,
+    #[serde(default)]
+    crossfade: bool,
+}
+
+/// The optional `[notify]` table: push notifications for a parent's phone.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawNotify {
+    service: RawPushService,
+    /// Endpoint to post to; ntfy defaults to the public ntfy.sh server, and
+    /// Pushover always uses its own API.
+    #[serde(default)]
+    url: Option<String>,
+    /// ntfy topic; on a public server, anyone who knows it can read along.
+    #[serde(default)]
+    topic: Option<RawSecret>,
+    /// Pushover application token.
+    #[serde(default)]
+    token: Option<RawSecret>,
+    /// Pushover user key.
+    #[serde(default)]
+    user: Option<RawSecret>,
+    #[serde(default = "default_true")]
+    unknown_card: bool,
+    #[serde(default = "default_true")]
+    reader_offline: bool,
+    #[serde(default = "default_true")]
+    low_battery: bool,
+    /// File holding the battery level in percent, such as
+    /// `/sys/class/power_supply/BAT0/capacity`.
+    #[serde(default)]
+    battery: Option<PathBuf>,
+    #[serde(default)]
+    low_battery_percent: Option<u8>,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+enum RawPushService {
+    Ntfy,
+    Pushover,
+    Webhook,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+/// The optional `[subsonic]` table: a server that `subsonic:<id>` entries
+/// stream from.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawSubsonic {
+    url: String,
+    user: RawSecret,
+    password: RawSecret,
+    /// Where downloaded songs are kept; relative paths resolve against
+    /// `music_dir`.
+    #[serde(default = "default_subsonic_cache")]
+    cache_dir: String,
+}
+
+/// A credential, written inline or as `{ secret = "<key>" }` naming an
+/// entry in the `secrets` file.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum RawSecret {
+    Inline(String),
+    Reference(RawSecretRef),
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawSecretRef {
+    secret: String,
+}
+
+impl RawSecret {
+    fn resolve(self, secrets: Option<&Secrets>) -> Result<String, ConfigError> {
+        match self {
+            Self::Inline(value) => Ok(value),
+            Self::Reference(RawSecretRef { secret }) => {
+                let Some(secrets) = secrets else {
+                    return Err(ConfigError::NoSecretsFile(secret));
+                };
+                secrets
+                    .get(&secret)
+                    .map(str::to_string)
+                    .ok_or(ConfigError::UnknownSecret(secret))
             }
         }
-        Ok(Self {
-            music_dir,
-            cards: parsed,
-        })
     }
+}
 
-    pub fn into_library(self) -> Library {
-        let tracks = self
-            .cards
-            .into_iter()
-            .map(|(uid, path)| (uid, Track::new(path)))
-            .collect();
-        Library::new(tracks)
-    }
+fn default_subsonic_cache() -> String {
+    ".subsonic-cache".to_string()
 }
 
-/// Adds a new card to the configuration file.
-pub fn add_card_to_config(path: &Path, uid: &CardUid, track: &str) -> Result<(), ConfigEditError> {
-    let mut doc = if path.exists() {
-        let contents = fs::read_to_string(path).map_err(|source| ConfigEditError::Read {
-            path: path.to_path_buf(),
-            source,
-        })?;
-        contents.parse::<DocumentMut>()?
-    } else {
-        let mut doc = DocumentMut::new();
-        doc["music_dir"] = value("");
-        doc["cards"] = table();
-        doc
-    };
+/// The optional `[spotify]` table: the local Spotify Connect daemon that
+/// `spotify:` entries play on. Credentials and the audio cache are the
+/// daemon's own settings, so the daemon must be logged in already.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawSpotify {
+    #[serde(default = "default_spotify_url")]
+    url: String,
+}
 
-    if !doc.as_table().contains_key("cards") {
-        doc["cards"] = table();
+fn default_spotify_url() -> String {
+    "http://127.0.0.1:3678".to_string()
+}
+
+/// The optional `[night]` table: night mode's volume cap and schedule.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawNight {
+    #[serde(default = "default_night_volume")]
+    max_volume: f32,
+    /// Local time night mode switches on, as `HH:MM`.
+    #[serde(default)]
+    from: Option<String>,
+    /// Local time night mode switches off, as `HH:MM`.
+    #[serde(default)]
+    until: Option<String>,
+}
+
+fn default_night_volume() -> f32 {
+    NightSettings::default().max_volume
+}
+
+/// The optional `[greetings]` table of clips played before the first card
+/// of the morning and of the evening.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawGreetings {
+    morning: Option<RawGreeting>,
+    evening: Option<RawGreeting>,
+}
+
+/// A `[greetings.morning]` or `[greetings.evening]` table.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawGreeting {
+    track: String,
+    /// Local time the greeting window opens, as `HH:MM`.
+    #[serde(default)]
+    from: Option<String>,
+    /// Local time the greeting window closes, as `HH:MM`.
+    #[serde(default)]
+    until: Option<String>,
+}
+
+fn greeting(
+    period: GreetingPeriod,
+    raw: RawGreeting,
+    music_dir: &Path,
+    locale: Locale,
+) -> Result<Greeting, ConfigError> {
+    let name = match period {
+        GreetingPeriod::Morning => "morning",
+        GreetingPeriod::Evening => "evening",
+    };
+    let default = period.default_window();
+    let time = |text: Option<&str>, minute: u16| match text {
+        Some(text) => parse_time_of_day(text),
+        None => Some(((minute / 60) as u8, (minute % 60) as u8)),
+    };
+    let window = time(raw.from.as_deref(), default.from)
+        .zip(time(raw.until.as_deref(), default.until))
+        .and_then(|(from, until)| NightSchedule::from_times(from, until))
+        .ok_or_else(|| {
+            ConfigError::Greetings(format!(
+                "{name}: from and until must be different HH:MM times"
+            ))
+        })?;
+    if raw.track.trim().is_empty() {
+        return Err(ConfigError::Greetings(format!("{name}: track is empty")));
     }
+    Ok(Greeting {
+        track: cue_track(music_dir, &raw.track, locale),
+        window,
+    })
+}
 
-    let cards = doc["cards"]
-        .as_table_mut()
-        .ok_or(ConfigEditError::MissingCards)?;
-    let uid_hex = uid.to_hex_lowercase();
+/// A sound cue under `music_dir`, or its variant for `locale` when one
+/// sits next to it.
+fn cue_track(music_dir: &Path, path: &str, locale: Locale) -> Track {
+    Track::new(locale.localized(&resolve_track_path(music_dir, path.trim())))
+}
 
-    if cards.contains_key(&uid_hex) {
-        return Err(ConfigEditError::Duplicate(uid.clone()));
+/// Parses a `HH:MM` time of day.
+fn parse_time_of_day(text: &str) -> Option<(u8, u8)> {
+    let (hour, minute) = text.trim().split_once(':')?;
+    if minute.len() != 2 {
+        return None;
     }
+    Some((hour.parse().ok()?, minute.parse().ok()?))
+}
 
-    cards.insert(&uid_hex, value(track));
+fn night_settings(raw: RawNight) -> Result<NightSettings, ConfigError> {
+    if !(0.0..=1.0).contains(&raw.max_volume) {
+        return Err(ConfigError::Night(
+            "max_volume must be between 0.0 and 1.0".to_string(),
+        ));
+    }
+    let schedule = match (raw.from, raw.until) {
+        (None, None) => None,
+        (Some(from), Some(until)) => {
+            let times = parse_time_of_day(&from).zip(parse_time_of_day(&until));
+            let schedule = times.and_then(|(from, until)| NightSchedule::from_times(from, until));
+            Some(schedule.ok_or_else(|| {
+                ConfigError::Night(format!(
+                    "from {from:?} and until {until:?} must be different HH:MM times"
+                ))
+            })?)
+        }
+        _ => {
+            return Err(ConfigError::Night(
+                "from and until must be set together".to_string(),
+            ));
+        }
+    };
+    Ok(NightSettings {
+        max_volume: raw.max_volume,
+        schedule,
+    })
+}
 
-    fs::write(path, doc.to_string()).map_err(|source| ConfigEditError::Write {
-        path: path.to_path_buf(),
-        source,
-    })?;
+/// The optional `[pn532]` table: a PN532 board on a serial port.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawPn532 {
+    device: PathBuf,
+    #[serde(default = "default_pn532_baud")]
+    baud: u32,
+    #[serde(default)]
+    poll_interval_ms: Option<u64>,
+    #[serde(default)]
+    read_counter: bool,
+}
 
-    Ok(())
+fn default_pn532_baud() -> u32 {
+    pn532::DEFAULT_BAUD
 }
 
-/// Resolves the absolute path to a track.
-fn resolve_track_path(music_dir: &Path, entry: &str) -> PathBuf {
-    let path = PathBuf::from(entry);
-    if path.is_absolute() || music_dir.as_os_str().is_empty() {
-        path
-    } else {
-        normalize_join(music_dir, path)
-    }
+/// The optional `[pcsc]` table: how USB readers behind `pcscd` are polled.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawPcsc {
+    #[serde(default)]
+    poll_interval_ms: Option<u64>,
+    #[serde(default)]
+    max_backoff_ms: Option<u64>,
 }
 
-fn normalize_join(base: &Path, relative: PathBuf) -> PathBuf {
-    base.join(relative)
+/// The optional `[remote_display]` table: a screen elsewhere that mirrors
+/// the status.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawRemoteDisplay {
+    /// `http://HOST/PATH` to POST to, or `udp://HOST:PORT`.
+    url: String,
+    /// Seconds after which an unchanged status is sent again.
+    #[serde(default)]
+    refresh_secs: Option<u64>,
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use std::path::Path;
-    use tempfile::tempdir;
-    use toml_edit::DocumentMut;
+/// The optional `[mqtt]` table: a broker through which a home automation
+/// hub watches and controls the box.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawMqtt {
+    host: String,
+    /// 1883, or 8883 with `tls`, when unset.
+    #[serde(default)]
+    port: Option<u16>,
+    #[serde(default)]
+    client_id: Option<String>,
+    #[serde(default)]
+    topic_prefix: Option<String>,
+    #[serde(default)]
+    user: Option<RawSecret>,
+    #[serde(default)]
+    password: Option<RawSecret>,
+    #[serde(default)]
+    tls: bool,
+    /// PEM certificates to trust instead of the system's.
+    #[serde(default)]
+    ca_file: Option<PathBuf>,
+    #[serde(default)]
+    client_cert: Option<PathBuf>,
+    #[serde(default)]
+    client_key: Option<PathBuf>,
+}
 
-    #[test]
-    fn builds_library_from_config() {
-        let toml = r#"
-music_dir = "/music"
+/// The optional `[loudness]` table: play every analyzed track at the same
+/// level.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawLoudness {
+    #[serde(default)]
+    target_lufs: Option<f32>,
+}
 
-[cards]
-"0a0b" = "song1.mp3"
-"0c0d" = "/absolute/song2.mp3"
-"0e0f" = "nested/song3.ogg"
-"#;
+/// An `[[api_tokens]]` entry: a bearer token for the web API.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawApiToken {
+    name: String,
+    token: RawSecret,
+    #[serde(default)]
+    scope: RawTokenScope,
+    /// Loudest volume the token may set, from 0.0 to 1.0.
+    #[serde(default)]
+    max_volume: Option<f32>,
+}
 
-        let config = MusicBoxConfig::from_reader(toml.as_bytes()).unwrap();
-        assert_eq!(config.music_dir(), Path::new("/music"));
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+enum RawTokenScope {
+    Full,
+    #[default]
+    Guest,
+}
 
-        let library = config.into_library();
+/// Shortest token accepted, so a guessable one is not left on the network.
+const MIN_TOKEN_LEN: usize = 16;
 
-        assert_eq!(
-            library
-                .lookup(&CardUid::from_hex("0a0b").unwrap())
-                .unwrap()
-                .path(),
-            Path::new("/music/song1.mp3")
-        );
-        assert_eq!(
-            library
-                .lookup(&CardUid::from_hex("0c0d").unwrap())
-                .unwrap()
-                .path(),
-            Path::new("/absolute/song2.mp3")
-        );
-        assert_eq!(
-            library
-                .lookup(&CardUid::from_hex("0e0f").unwrap())
-                .unwrap()
-                .path(),
-            Path::new("/music/nested/song3.ogg")
-        );
+/// Quietest and loudest `target_lufs` accepted.
+const TARGET_LUFS_RANGE: std::ops::RangeInclusive<f32> = -40.0..=-5.0;
+
+/// A positive `poll_interval_ms`, as a duration.
+fn poll_interval(ms: Option<u64>) -> Result<Option<Duration>, String> {
+    match ms {
+        Some(0) => Err("poll_interval_ms must be greater than zero".to_string()),
+        ms => Ok(ms.map(Duration::from_millis)),
     }
+}
 
-    #[test]
-    fn invalid_card_uid_returns_error() {
-        let toml = r#"
-music_dir = "/music"
+/// The optional `[audio]` table: where sound goes.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawAudio {
+    /// Output device name; the system default when unset.
+    #[serde(default)]
+    device: Option<String>,
+    /// Opens the device on the first play instead of at startup.
+    #[serde(default)]
+    lazy: bool,
+    /// With `lazy`, closes the device after this long without playing.
+    #[serde(default = "default_release_after_seconds")]
+    release_after_seconds: u64,
+}
 
-[cards]
-"zz" = "song.mp3"
-"#;
+impl Default for RawAudio {
+    fn default() -> Self {
+        Self {
+            device: None,
+            lazy: false,
+            release_after_seconds: default_release_after_seconds(),
+        }
+    }
+}
 
-        let err = MusicBoxConfig::from_reader(toml.as_bytes()).unwrap_err();
-        assert!(matches!(err, ConfigError::CardUid(_)));
+fn default_release_after_seconds() -> u64 {
+    300
+}
+
+/// The optional `[podcasts]` table: how `podcast:<feed url>` entries play.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawPodcasts {
+    /// Where downloaded episodes are kept; relative paths resolve against
+    /// `music_dir`.
+    #[serde(default = "default_podcast_cache")]
+    cache_dir: String,
+    #[serde(default)]
+    episode: RawEpisodeOrder,
+}
+
+impl Default for RawPodcasts {
+    fn default() -> Self {
+        Self {
+            cache_dir: default_podcast_cache(),
+            episode: RawEpisodeOrder::default(),
+        }
     }
+}
 
-    #[test]
-    fn add_card_to_config_creates_or_updates_file() {
-        let dir = tempdir().unwrap();
-        let path = dir.path().join("musicbox.toml");
-        let uid = CardUid::from_hex("0a0b").unwrap();
+fn default_podcast_cache() -> String {
+    ".podcast-cache".to_string()
+}
 
-        add_card_to_config(&path, &uid, "songs/track.mp3").unwrap();
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+enum RawEpisodeOrder {
+    #[default]
+    Newest,
+    NextUnplayed,
+}
 
-        let contents = std::fs::read_to_string(&path).unwrap();
-        let doc = contents.parse::<DocumentMut>().unwrap();
-        assert_eq!(doc["music_dir"].as_str(), Some(""));
-        assert_eq!(doc["cards"]["0a0b"].as_str(), Some("songs/track.mp3"));
+impl From<RawEpisodeOrder> for EpisodeOrder {
+    fn from(value: RawEpisodeOrder) -> Self {
+        match value {
+            RawEpisodeOrder::Newest => EpisodeOrder::Newest,
+            RawEpisodeOrder::NextUnplayed => EpisodeOrder::NextUnplayed,
+        }
     }
+}
 
-    #[test]
-    fn add_card_to_config_rejects_duplicate_cards() {
-        let dir = tempdir().unwrap();
-        let path = dir.path().join("musicbox.toml");
-        std::fs::write(
-            &path,
-            r#"
-music_dir = "/music"
+/// The optional `[recording]` table: a command card that records voice
+/// memos onto other cards.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawRecording {
+    card: String,
+    /// Where memos are saved; relative paths resolve against `music_dir`.
+    #[serde(default = "default_memo_dir")]
+    dir: String,
+    #[serde(default = "default_memo_seconds")]
+    max_seconds: u64,
+}
 
-[cards]
-"0c0d" = "other.mp3"
-"#
-            .trim(),
-        )
-        .unwrap();
+/// The optional `[speech]` table: a text-to-speech command that voices
+/// the effects without a file.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawSpeech {
+    command: Vec<String>,
+    /// Where rendered phrases are kept; defaults to a directory under the
+    /// system's temporary directory.
+    #[serde(default)]
+    cache_dir: Option<PathBuf>,
+}
 
-        let uid = CardUid::from_hex("0c0d").unwrap();
-        let err = add_card_to_config(&path, &uid, "songs/new.mp3").unwrap_err();
-        assert!(matches!(err, ConfigEditError::Duplicate(_)));
+fn default_memo_dir() -> String {
+    "memos".to_string()
+}
+
+fn default_memo_seconds() -> u64 {
+    60
+}
+
+/// Longest accepted voice memo.
+const MAX_MEMO_SECONDS: u64 = 300;
+
+/// Longest accepted fade, so a typo cannot hold back the next card.
+const MAX_FADE_MS: u64 = 10_000;
+
+/// The optional `[buttons]` table: GPIO line numbers of physical buttons.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawButtons {
+    #[serde(default = "default_gpio_chip")]
+    gpio_chip: PathBuf,
+    next: Option<u32>,
+    previous: Option<u32>,
+    play_pause: Option<u32>,
+    volume_up: Option<u32>,
+    volume_down: Option<u32>,
+    mute: Option<u32>,
+    #[serde(default = "default_volume_step")]
+    volume_step: f32,
+}
+
+fn default_gpio_chip() -> PathBuf {
+    PathBuf::from("/dev/gpiochip0")
+}
+
+fn default_volume_step() -> f32 {
+    DEFAULT_VOLUME_STEP
+}
+
+/// A `[cards]` value: a bare track path, an array of paths (a playlist), a
+/// table with metadata, a group command card, or a system command card.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum RawCard {
+    Track(String),
+    Playlist(Vec<String>),
+    Detailed(Box<RawCardDetails>),
+    Command(RawCommandCard),
+    System(RawSystemCard),
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawSystemCard {
+    action: RawSystemAction,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+enum RawSystemAction {
+    Mute,
+    NightMode,
+    VolumeUp,
+    VolumeDown,
+    Next,
+    Previous,
+    PlayPause,
+    Shutdown,
+    ReloadConfig,
+    Unlock,
+}
+
+impl From<RawSystemAction> for SystemCommand {
+    fn from(value: RawSystemAction) -> Self {
+        match value {
+            RawSystemAction::Mute => SystemCommand::ToggleMute,
+            RawSystemAction::NightMode => SystemCommand::ToggleNightMode,
+            RawSystemAction::VolumeUp => SystemCommand::VolumeUp,
+            RawSystemAction::VolumeDown => SystemCommand::VolumeDown,
+            RawSystemAction::Next => SystemCommand::Next,
+            RawSystemAction::Previous => SystemCommand::Previous,
+            RawSystemAction::PlayPause => SystemCommand::PlayPause,
+            RawSystemAction::Shutdown => SystemCommand::Shutdown,
+            RawSystemAction::ReloadConfig => SystemCommand::ReloadConfig,
+            RawSystemAction::Unlock => SystemCommand::Unlock,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawCommandCard {
+    action: RawGroupAction,
+    group: String,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+enum RawGroupAction {
+    StopGroup,
+    ShuffleGroup,
+}
+
+impl From<RawGroupAction> for GroupAction {
+    fn from(value: RawGroupAction) -> Self {
+        match value {
+            RawGroupAction::StopGroup => GroupAction::Stop,
+            RawGroupAction::ShuffleGroup => GroupAction::Shuffle,
+        }
+    }
+}
+
+/// The top-level `playback_mode`: what re-tapping the playing card does.
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+enum RawPlaybackMode {
+    #[default]
+    Stop,
+    PauseResume,
+    LiftToStop,
+}
+
+impl From<RawPlaybackMode> for PlaybackMode {
+    fn from(value: RawPlaybackMode) -> Self {
+        match value {
+            RawPlaybackMode::Stop => PlaybackMode::StopOnRetap,
+            RawPlaybackMode::PauseResume => PlaybackMode::PauseResume,
+            RawPlaybackMode::LiftToStop => PlaybackMode::LiftToStop,
+        }
+    }
+}
+
+/// A card's `mode`: the order its playlist plays in.
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+enum RawPlaylistMode {
+    #[default]
+    Sequential,
+    Shuffle,
+    Repeat,
+    RepeatOne,
+    Alternate,
+}
+
+/// A card's `on_end`: what happens after its last track. Continuing names
+/// another card by nickname or UID.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum RawPlaylistEnd {
+    Action(RawEndAction),
+    Continue(RawContinue),
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+enum RawEndAction {
+    Stop,
+    Repeat,
+    ShuffleRepeat,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawContinue {
+    #[serde(rename = "continue")]
+    card: String,
+}
+
+/// Reads an `on_finish` hook as the `on_end` it stands for.
+fn parse_on_finish(hook: &str) -> Option<RawPlaylistEnd> {
+    let hook = hook.trim();
+    if hook == "stop" {
+        return Some(RawPlaylistEnd::Action(RawEndAction::Stop));
+    }
+    let card = hook.strip_prefix("card:")?.trim();
+    (!card.is_empty()).then(|| {
+        RawPlaylistEnd::Continue(RawContinue {
+            card: card.to_string(),
+        })
+    })
+}
+
+impl From<RawPlaylistMode> for PlaylistMode {
+    fn from(value: RawPlaylistMode) -> Self {
+        match value {
+            RawPlaylistMode::Sequential => PlaylistMode::Sequential,
+            RawPlaylistMode::Shuffle => PlaylistMode::Shuffle,
+            RawPlaylistMode::Repeat => PlaylistMode::Repeat,
+            RawPlaylistMode::RepeatOne => PlaylistMode::RepeatOne,
+            RawPlaylistMode::Alternate => PlaylistMode::Alternate,
+        }
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawCardDetails {
+    #[serde(default)]
+    track: Option<String>,
+    /// An ordered playlist, as an alternative to `track`.
+    #[serde(default)]
+    tracks: Option<Vec<String>>,
+    #[serde(default)]
+    name: Option<String>,
+    /// LED color hint shown while the card plays, as `#rrggbb`.
+    #[serde(default)]
+    color: Option<String>,
+    /// Name of a `[groups]` entry whose defaults and commands apply.
+    #[serde(default)]
+    group: Option<String>,
+    /// Remember the playback position so the next tap picks up there.
+    #[serde(default)]
+    resume: Option<bool>,
+    /// Ignore skip and stop commands while the card plays, until the
+    /// parent card unlocks it.
+    #[serde(default)]
+    locked: bool,
+    /// When a guest card stops working.
+    #[serde(default)]
+    expires: Option<toml::value::Datetime>,
+    #[serde(default)]
+    mode: Option<RawPlaylistMode>,
+    #[serde(default)]
+    on_end: Option<RawPlaylistEnd>,
+    /// `"stop"` or `"card:<uid or name>"`, a shorter way to write `on_end`
+    /// for chaining cards.
+    #[serde(default)]
+    on_finish: Option<String>,
+    /// Label of the only reader this card answers on.
+    #[serde(default)]
+    reader: Option<String>,
+    /// Silence between tracks, so audiobook chapters stand apart.
+    #[serde(default)]
+    gap_ms: Option<u64>,
+    /// Seconds the card plays for before stopping with a chime, looping
+    /// its tracks until then.
+    #[serde(default)]
+    countdown_secs: u64,
+    /// This card's own chime, in place of `[sounds] chime`.
+    #[serde(default)]
+    chime: Option<String>,
+    /// Loudness of the card's tracks as a factor, such as 0.5 for half.
+    #[serde(default)]
+    volume: Option<f32>,
+    /// Loudness of the card's tracks in decibels, as an alternative to
+    /// `volume`.
+    #[serde(default)]
+    gain_db: Option<f32>,
+    /// Minutes the card plays before fading out, in place of the top-level
+    /// `max_play_minutes`; 0 lets it play on.
+    #[serde(default)]
+    max_play_minutes: Option<u64>,
+}
+
+impl RawCardDetails {
+    /// Fills in what the card leaves unset from its group's defaults. A
+    /// card's `volume` or `gain_db` replaces the group's loudness whichever
+    /// of the two the group uses.
+    fn inherit(&mut self, group: &RawGroup) {
+        self.color = self.color.take().or_else(|| group.color.clone());
+        self.mode = self.mode.or(group.mode);
+        self.resume = self.resume.or(group.resume);
+        self.gap_ms = self.gap_ms.or(group.gap_ms);
+        if self.volume.is_none() && self.gain_db.is_none() {
+            self.volume = group.volume;
+            self.gain_db = group.gain_db;
+        }
+        self.max_play_minutes = self.max_play_minutes.or(group.max_play_minutes);
+    }
+}
+
+impl MusicBoxConfig {
+    /// Parses a config, resolving a relative `secrets` path against the
+    /// working directory.
+    pub fn from_reader<R: Read>(reader: R) -> Result<Self, ConfigError> {
+        Self::from_reader_in(reader, Path::new(""))
+    }
+
+    /// Parses a config read from a file in `config_dir`, which a relative
+    /// `secrets` path resolves against.
+    pub fn from_reader_in<R: Read>(mut reader: R, config_dir: &Path) -> Result<Self, ConfigError> {
+        let mut buffer = String::new();
+        reader.read_to_string(&mut buffer)?;
+        let raw: RawConfig = toml::from_str(&buffer)?;
+        let secrets = raw
+            .secrets
+            .as_ref()
+            .map(|path| Secrets::load(&config_dir.join(path)))
+            .transpose()?;
+        Self::from_raw(raw, secrets.as_ref())
+    }
+
+    /// Language for on-device text.
+    pub fn locale(&self) -> Locale {
+        self.locale
+    }
+
+    pub fn music_dir(&self) -> &Path {
+        &self.music_dir
+    }
+
+    /// How the audio backend fades tracks in and out.
+    pub fn fades(&self) -> FadeSettings {
+        self.fades
+    }
+
+    /// The download cache for `subsonic:` tracks, if a server is configured.
+    pub fn subsonic(&self) -> Option<&SubsonicCache> {
+        self.subsonic.as_ref()
+    }
+
+    /// The Spotify Connect daemon for `spotify:` tracks, if configured.
+    pub fn spotify(&self) -> Option<&SpotifySettings> {
+        self.spotify.as_ref()
+    }
+
+    /// Where `podcast:` episodes are cached and which one a tap plays.
+    pub fn podcasts(&self) -> &Podcasts {
+        &self.podcasts
+    }
+
+    /// Voice memo settings from the `[recording]` table, if configured.
+    pub fn recording(&self) -> Option<&MemoSettings> {
+        self.recording.as_ref()
+    }
+
+    /// Push notifications from the `[notify]` table, if configured.
+    pub fn notifier(&self) -> Option<&Notifier> {
+        self.notifier.as_ref()
+    }
+
+    /// The `[readers]` table, in label order. Empty means every attached
+    /// reader is polled.
+    pub fn reader_zones(&self) -> &[ReaderZone] {
+        &self.zones
+    }
+
+    /// The `[pn532]` table, if a PN532 board is wired up.
+    pub fn pn532(&self) -> Option<&Pn532Settings> {
+        self.pn532.as_ref()
+    }
+
+    /// The `[pcsc]` table, or its defaults when absent.
+    pub fn pcsc(&self) -> PcscSettings {
+        self.pcsc
+    }
+
+    /// The `[remote_display]` table, if a screen elsewhere mirrors the
+    /// status.
+    pub fn remote_display(&self) -> Option<&RemoteDisplaySettings> {
+        self.remote_display.as_ref()
+    }
+
+    /// The `[mqtt]` table, if a home automation hub watches the box.
+    pub fn mqtt(&self) -> Option<&MqttSettings> {
+        self.mqtt.as_ref()
+    }
+
+    /// The `[loudness]` table, if tracks are normalized to one level.
+    pub fn loudness(&self) -> Option<LoudnessSettings> {
+        self.loudness
+    }
+
+    /// The output device from the `[audio]` table; `None` means the
+    /// system default.
+    pub fn audio_device(&self) -> Option<&str> {
+        self.audio_device.as_deref()
+    }
+
+    /// With `[audio]` `lazy = true`, how long the output device stays open
+    /// without playing; `None` opens it at startup and keeps it open.
+    pub fn audio_release_after(&self) -> Option<Duration> {
+        self.audio_release_after
+    }
+
+    /// The `[buttons]` table, if physical buttons are configured.
+    pub fn buttons(&self) -> Option<&ButtonConfig> {
+        self.buttons.as_ref()
+    }
+
+    fn from_raw(raw: RawConfig, secrets: Option<&Secrets>) -> Result<Self, ConfigError> {
+        let RawConfig {
+            music_dir,
+            allow_external_paths,
+            locale,
+            playback_mode,
+            debounce_ms,
+            combo_window_ms,
+            max_play_minutes,
+            sleep_fade_seconds,
+            sleep_warning_seconds,
+            secrets: _,
+            cards,
+            groups,
+            combos,
+            sounds,
+            effects,
+            startup,
+            ambient,
+            buttons,
+            fade,
+            notify,
+            subsonic,
+            spotify,
+            podcasts,
+            recording,
+            speech,
+            readers: zones,
+            pn532,
+            pcsc,
+            audio,
+            night,
+            greetings,
+            remote_display,
+            mqtt,
+            loudness,
+            api_tokens,
+            web,
+        } = raw;
+        for (group, settings) in &groups {
+            if let Some(color) = &settings.color {
+                Rgb::parse(color).map_err(|source| ConfigError::Color {
+                    entry: format!("group {group}"),
+                    source,
+                })?;
+            }
+        }
+        let check_group = |uid: &CardUid, group: &str| {
+            if groups.contains_key(group) {
+                Ok(())
+            } else {
+                Err(ConfigError::UnknownGroup {
+                    card: uid.to_string(),
+                    group: group.to_string(),
+                })
+            }
+        };
+        let mut parsed = HashMap::with_capacity(cards.len());
+        let mut names: HashMap<CardUid, String> = HashMap::new();
+        let mut colors: HashMap<CardUid, Rgb> = HashMap::new();
+        let mut memberships: HashMap<CardUid, String> = HashMap::new();
+        let mut commands: HashMap<CardUid, GroupCommand> = HashMap::new();
+        let mut system_commands: HashMap<CardUid, SystemCommand> = HashMap::new();
+        let mut resumable: HashSet<CardUid> = HashSet::new();
+        let mut locked: HashSet<CardUid> = HashSet::new();
+        let mut expiries: HashMap<CardUid, SystemTime> = HashMap::new();
+        let mut modes: HashMap<CardUid, PlaylistMode> = HashMap::new();
+        let mut ends: HashMap<CardUid, PlaylistEnd> = HashMap::new();
+        let mut continuations: Vec<(CardUid, &'static str, String)> = Vec::new();
+        let mut readers: HashMap<CardUid, String> = HashMap::new();
+        let mut gaps: HashMap<CardUid, Duration> = HashMap::new();
+        let mut max_plays: HashMap<CardUid, Option<Duration>> = HashMap::new();
+        let mut gains: HashMap<CardUid, Gain> = HashMap::new();
+        let mut countdowns: HashMap<CardUid, Countdown> = HashMap::new();
+        let mut keys_by_uid: HashMap<CardUid, Vec<String>> = HashMap::new();
+        for (card_hex, card) in cards {
+            let uid = CardUid::parse(&card_hex)?;
+            keys_by_uid.entry(uid.clone()).or_default().push(card_hex);
+            let mut details = match card {
+                RawCard::Track(track) => RawCardDetails {
+                    track: Some(track),
+                    ..RawCardDetails::default()
+                },
+                RawCard::Playlist(tracks) => RawCardDetails {
+                    tracks: Some(tracks),
+                    ..RawCardDetails::default()
+                },
+                RawCard::Detailed(details) => *details,
+                RawCard::Command(command) => {
+                    check_group(&uid, &command.group)?;
+                    commands.insert(
+                        uid,
+                        GroupCommand {
+                            action: command.action.into(),
+                            group: command.group,
+                        },
+                    );
+                    continue;
+                }
+                RawCard::System(system) => {
+                    system_commands.insert(uid, system.action.into());
+                    continue;
+                }
+            };
+            if let Some(group) = &details.group {
+                check_group(&uid, group)?;
+                details.inherit(&groups[group]);
+            }
+            let entries = match (details.track, details.tracks) {
+                (Some(track), None) => vec![track],
+                (None, Some(tracks)) if !tracks.is_empty() => tracks,
+                _ => {
+                    return Err(ConfigError::TrackSpec {
+                        card: uid.to_string(),
+                    });
+                }
+            };
+            let track_paths: Vec<PathBuf> = entries
+                .iter()
+                .map(|entry| resolve_track_path(&music_dir, entry.trim()))
+                .collect();
+            if let Some(name) = details.name.map(|name| name.trim().to_string())
+                && !name.is_empty()
+            {
+                if names
+                    .values()
+                    .any(|existing| existing.eq_ignore_ascii_case(&name))
+                {
+                    return Err(ConfigError::DuplicateName(name));
+                }
+                names.insert(uid.clone(), name);
+            }
+            if let Some(color) = details.color {
+                let color = Rgb::parse(&color).map_err(|source| ConfigError::Color {
+                    entry: format!("card {uid}"),
+                    source,
+                })?;
+                colors.insert(uid.clone(), color);
+            }
+            if let Some(group) = details.group {
+                memberships.insert(uid.clone(), group);
+            }
+            if details.resume == Some(true) {
+                resumable.insert(uid.clone());
+            }
+            if details.locked {
+                locked.insert(uid.clone());
+            }
+            if let Some(expires) = details.expires {
+                let expires =
+                    expiry::to_system_time(&expires).ok_or_else(|| ConfigError::Expiry {
+                        card: uid.to_string(),
+                    })?;
+                expiries.insert(uid.clone(), expires);
+            }
+            if let Some(reader) = details.reader {
+                if !zones.contains_key(&reader) {
+                    return Err(ConfigError::UnknownReader {
+                        card: uid.to_string(),
+                        reader,
+                    });
+                }
+                readers.insert(uid.clone(), reader);
+            }
+            if let Some(gap_ms) = details.gap_ms.filter(|&gap_ms| gap_ms > 0) {
+                gaps.insert(uid.clone(), Duration::from_millis(gap_ms));
+            }
+            if let Some(minutes) = details.max_play_minutes {
+                max_plays.insert(uid.clone(), play_limit(minutes));
+            }
+            if let Some(gain) = card_gain(&uid, details.volume, details.gain_db)? {
+                gains.insert(uid.clone(), gain);
+            }
+            let (end_key, on_end) = match (details.on_end, details.on_finish) {
+                (Some(_), Some(_)) => {
+                    return Err(ConfigError::OnEnd {
+                        card: uid.to_string(),
+                        key: "on_finish",
+                        reason: "set `on_end` or `on_finish`, not both".to_string(),
+                    });
+                }
+                (None, Some(hook)) => {
+                    let end = parse_on_finish(&hook).ok_or_else(|| ConfigError::OnEnd {
+                        card: uid.to_string(),
+                        key: "on_finish",
+                        reason: format!("expected \"stop\" or \"card:<uid>\", not {hook:?}"),
+                    })?;
+                    ("on_finish", Some(end))
+                }
+                (on_end, None) => ("on_end", on_end),
+            };
+            match (details.countdown_secs, details.chime) {
+                (0, None) => {}
+                (0, Some(_)) => {
+                    return Err(ConfigError::Countdown {
+                        card: uid.to_string(),
+                        reason: "`chime` needs `countdown_secs`",
+                    });
+                }
+                (_, _) if on_end.is_some() => {
+                    return Err(ConfigError::Countdown {
+                        card: uid.to_string(),
+                        reason: "countdowns loop until their time is up, so `on_end` and `on_finish` never apply",
+                    });
+                }
+                (secs, chime) => {
+                    countdowns.insert(
+                        uid.clone(),
+                        Countdown {
+                            duration: Duration::from_secs(secs),
+                            chime: chime.map(|path| cue_track(&music_dir, &path, locale)),
+                        },
+                    );
+                }
+            }
+            let mode = PlaylistMode::from(details.mode.unwrap_or_default());
+            if mode != PlaylistMode::Sequential {
+                modes.insert(uid.clone(), mode);
+            }
+            match on_end {
+                Some(_) if matches!(mode, PlaylistMode::Repeat | PlaylistMode::RepeatOne) => {
+                    return Err(ConfigError::OnEnd {
+                        card: uid.to_string(),
+                        key: end_key,
+                        reason: "repeating cards never reach their end".to_string(),
+                    });
+                }
+                Some(_) if mode == PlaylistMode::Alternate => {
+                    return Err(ConfigError::OnEnd {
+                        card: uid.to_string(),
+                        key: end_key,
+                        reason: "alternating cards stop after every track".to_string(),
+                    });
+                }
+                None | Some(RawPlaylistEnd::Action(RawEndAction::Stop)) => {}
+                Some(RawPlaylistEnd::Action(RawEndAction::Repeat)) => {
+                    ends.insert(uid.clone(), PlaylistEnd::Repeat);
+                }
+                Some(RawPlaylistEnd::Action(RawEndAction::ShuffleRepeat)) => {
+                    ends.insert(uid.clone(), PlaylistEnd::ShuffleRepeat);
+                }
+                Some(RawPlaylistEnd::Continue(next)) => {
+                    continuations.push((uid.clone(), end_key, next.card));
+                }
+            }
+            parsed.insert(uid, track_paths);
+        }
+
+        for (uid, key, next) in continuations {
+            let target = names
+                .iter()
+                .find(|(_, name)| name.eq_ignore_ascii_case(next.trim()))
+                .map(|(card, _)| card.clone())
+                .or_else(|| CardUid::parse(&next).ok())
+                .filter(|card| parsed.contains_key(card))
+                .ok_or_else(|| ConfigError::OnEnd {
+                    card: uid.to_string(),
+                    key,
+                    reason: format!("no card named {next:?}"),
+                })?;
+            ends.insert(uid, PlaylistEnd::Continue(target));
+        }
+
+        let combos = combos
+            .into_iter()
+            .enumerate()
+            .map(|(index, raw)| combo(index + 1, raw, &names, &music_dir))
+            .collect::<Result<Vec<_>, _>>()?;
+        for (index, combo) in combos.iter().enumerate() {
+            let [a, b] = &combo.cards;
+            if combos[..index]
+                .iter()
+                .any(|earlier| earlier.cards.contains(a) && earlier.cards.contains(b))
+            {
+                return Err(ConfigError::Combo {
+                    index: index + 1,
+                    reason: "the same two cards already form a combo".into(),
+                });
+            }
+        }
+
+        let mut duplicates: Vec<DuplicateCard> = keys_by_uid
+            .into_iter()
+            .filter(|(_, keys)| keys.len() > 1)
+            .map(|(uid, mut keys)| {
+                keys.sort();
+                DuplicateCard { uid, keys }
+            })
+            .collect();
+        if !duplicates.is_empty() {
+            duplicates.sort_by(|a, b| a.uid.as_bytes().cmp(b.uid.as_bytes()));
+            return Err(ConfigError::DuplicateCards(duplicates));
+        }
+
+        let cue = |path: &str| cue_track(&music_dir, path, locale);
+        let sounds = SoundCues {
+            goodnight: sounds.goodnight.as_deref().map(cue),
+            chime: sounds.chime.as_deref().map(cue),
+            startup: startup
+                .as_ref()
+                .and_then(|startup| startup.chime.as_deref())
+                .map(cue),
+        };
+        let effects = Effects {
+            ready: effects.ready.as_deref().map(cue),
+            unknown_card: effects.unknown_card.as_deref().map(cue),
+            shutdown: effects.shutdown.as_deref().map(cue),
+            sleep_warning: effects.sleep_warning.as_deref().map(cue),
+        };
+        if let Some(track) = [
+            &effects.ready,
+            &effects.unknown_card,
+            &effects.shutdown,
+            &effects.sleep_warning,
+        ]
+        .into_iter()
+        .flatten()
+        .find(|track| track.is_remote())
+        {
+            return Err(ConfigError::Effects(format!(
+                "{} is not a local file",
+                track.path().display()
+            )));
+        }
+
+        let ambient = ambient
+            .map(|ambient| {
+                if ambient.tracks.is_empty() {
+                    return Err(ConfigError::Ambient("`tracks` must not be empty"));
+                }
+                if ambient.after_minutes == 0 {
+                    return Err(ConfigError::Ambient("`after_minutes` must be at least 1"));
+                }
+                if !(0.0..=1.0).contains(&ambient.volume) {
+                    return Err(ConfigError::Ambient("`volume` must be between 0.0 and 1.0"));
+                }
+                Ok(AmbientPolicy {
+                    tracks: ambient
+                        .tracks
+                        .iter()
+                        .map(|path| Track::new(resolve_track_path(&music_dir, path.trim())))
+                        .collect(),
+                    idle_after: Duration::from_secs(ambient.after_minutes * 60),
+                    volume: ambient.volume,
+                })
+            })
+            .transpose()?;
+
+        let startup = match startup {
+            None => StartupAction::Silent,
+            Some(raw) => match (raw.action, raw.chime.is_some()) {
+                (RawStartupAction::Chime, false) => {
+                    return Err(ConfigError::Startup("`action = \"chime\"` needs a `chime`"));
+                }
+                (RawStartupAction::Chime, true) => StartupAction::Chime,
+                (_, true) => {
+                    return Err(ConfigError::Startup(
+                        "`chime` is only used with `action = \"chime\"`",
+                    ));
+                }
+                (RawStartupAction::Silent, false) => StartupAction::Silent,
+                (RawStartupAction::Resume, false) => StartupAction::Resume,
+                (RawStartupAction::Ambient, false) if ambient.is_none() => {
+                    return Err(ConfigError::Startup(
+                        "`action = \"ambient\"` needs an [ambient] table",
+                    ));
+                }
+                (RawStartupAction::Ambient, false) => StartupAction::Ambient,
+            },
+        };
+
+        if fade.out_ms > MAX_FADE_MS || fade.in_ms > MAX_FADE_MS {
+            return Err(ConfigError::Fade("fades must be at most 10000 ms"));
+        }
+        if fade.crossfade && fade.out_ms == 0 {
+            return Err(ConfigError::Fade("`crossfade` needs a non-zero `out_ms`"));
+        }
+        let fades = FadeSettings {
+            fade_out: Duration::from_millis(fade.out_ms),
+            fade_in: Duration::from_millis(fade.in_ms),
+            crossfade: fade.crossfade,
+        };
+
+        let notifier = notify.map(|raw| notifier(raw, secrets)).transpose()?;
+        let mqtt = mqtt.map(|raw| mqtt_settings(raw, secrets)).transpose()?;
+
+        let subsonic = subsonic
+            .map(|raw| {
+                let url = WebhookUrl::parse(&raw.url)
+                    .map_err(|err| ConfigError::Subsonic(err.to_string()))?;
+                Ok::<_, ConfigError>(SubsonicCache::new(
+                    SubsonicServer::new(
+                        url,
+                        raw.user.resolve(secrets)?,
+                        raw.password.resolve(secrets)?,
+                    ),
+                    resolve_track_path(&music_dir, &raw.cache_dir),
+                ))
+            })
+            .transpose()?;
+        let ambient_tracks = ambient.iter().flat_map(|ambient| &ambient.tracks);
+        let remote_entries = parsed
+            .values()
+            .flatten()
+            .chain(ambient_tracks.map(|track| &track.path))
+            .filter_map(|path| path.to_str())
+            .filter(|entry| entry.starts_with(subsonic::SCHEME));
+        for entry in remote_entries {
+            if subsonic::song_id(entry).is_none() {
+                return Err(ConfigError::Subsonic(format!(
+                    "{entry:?} is not a valid song id"
+                )));
+            }
+            if subsonic.is_none() {
+                return Err(ConfigError::Subsonic(format!(
+                    "{entry:?} needs a [subsonic] table"
+                )));
+            }
+        }
+
+        let spotify = spotify
+            .map(|raw| {
+                let api = WebhookUrl::parse(&raw.url)
+                    .map_err(|err| ConfigError::Spotify(err.to_string()))?;
+                Ok::<_, ConfigError>(SpotifySettings { api })
+            })
+            .transpose()?;
+        let spotify_entries = parsed
+            .values()
+            .flatten()
+            .filter_map(|path| path.to_str())
+            .filter(|entry| entry.starts_with(spotify::SCHEME));
+        for entry in spotify_entries {
+            if spotify::spotify_uri(entry).is_none() {
+                return Err(ConfigError::Spotify(format!(
+                    "{entry:?} is not a Spotify track, album, playlist, artist, episode, or show URI"
+                )));
+            }
+            if spotify.is_none() {
+                return Err(ConfigError::Spotify(format!(
+                    "{entry:?} needs a [spotify] table"
+                )));
+            }
+        }
+
+        let feeds = parsed
+            .values()
+            .flatten()
+            .filter_map(|path| path.to_str())
+            .filter_map(podcast::feed_url);
+        for feed in feeds {
+            if let Err(err) = WebhookUrl::parse(feed) {
+                return Err(ConfigError::Podcast(err.to_string()));
+            }
+        }
+        let streams = parsed
+            .values()
+            .flatten()
+            .chain(
+                ambient
+                    .iter()
+                    .flat_map(|ambient| &ambient.tracks)
+                    .map(|track| &track.path),
+            )
+            .filter_map(|path| path.to_str())
+            .filter(|entry| stream::is_stream_url(entry));
+        for url in streams {
+            if let Err(err) = WebhookUrl::parse(url) {
+                return Err(ConfigError::Stream(err.to_string()));
+            }
+        }
+        let podcasts = Podcasts::new(
+            resolve_track_path(&music_dir, &podcasts.cache_dir),
+            podcasts.episode.into(),
+        );
+
+        let speech = speech
+            .map(|raw| {
+                if raw
+                    .command
+                    .first()
+                    .is_none_or(|program| program.trim().is_empty())
+                {
+                    return Err(ConfigError::Speech("`command` needs a program".into()));
+                }
+                if !raw.command.iter().any(|arg| arg.contains(speech::OUTPUT)) {
+                    return Err(ConfigError::Speech(format!(
+                        "`command` must write to {}",
+                        speech::OUTPUT
+                    )));
+                }
+                Ok(Speech {
+                    command: raw.command,
+                    cache_dir: raw.cache_dir.unwrap_or_else(Speech::default_cache_dir),
+                })
+            })
+            .transpose()?;
+        let recording = recording
+            .map(|raw| {
+                let card = CardUid::parse(&raw.card)?;
+                if parsed.contains_key(&card)
+                    || commands.contains_key(&card)
+                    || system_commands.contains_key(&card)
+                {
+                    return Err(ConfigError::Recording(format!(
+                        "card {card} is also in [cards]"
+                    )));
+                }
+                if !(1..=MAX_MEMO_SECONDS).contains(&raw.max_seconds) {
+                    return Err(ConfigError::Recording(format!(
+                        "max_seconds must be between 1 and {MAX_MEMO_SECONDS}"
+                    )));
+                }
+                Ok(MemoSettings {
+                    card,
+                    dir: resolve_track_path(&music_dir, &raw.dir),
+                    max_length: Duration::from_secs(raw.max_seconds),
+                })
+            })
+            .transpose()?;
+
+        let pn532 = pn532
+            .map(|raw| {
+                if !pn532::SUPPORTED_BAUDS.contains(&raw.baud) {
+                    return Err(ConfigError::Pn532(format!(
+                        "baud must be one of {:?}",
+                        pn532::SUPPORTED_BAUDS
+                    )));
+                }
+                Ok(Pn532Settings {
+                    device: raw.device,
+                    baud: raw.baud,
+                    poll_interval: poll_interval(raw.poll_interval_ms)
+                        .map_err(ConfigError::Pn532)?,
+                    read_counter: raw.read_counter,
+                })
+            })
+            .transpose()?;
+
+        let pcsc = match pcsc {
+            Some(raw) => {
+                let defaults = PcscSettings::default();
+                let settings = PcscSettings {
+                    poll_interval: poll_interval(raw.poll_interval_ms)
+                        .map_err(ConfigError::Pcsc)?,
+                    max_backoff: raw
+                        .max_backoff_ms
+                        .map_or(defaults.max_backoff, Duration::from_millis),
+                };
+                if settings
+                    .poll_interval
+                    .is_some_and(|poll| poll > settings.max_backoff)
+                {
+                    return Err(ConfigError::Pcsc(
+                        "max_backoff_ms must not be shorter than poll_interval_ms".to_string(),
+                    ));
+                }
+                settings
+            }
+            None => PcscSettings::default(),
+        };
+
+        let remote_display = remote_display
+            .map(|raw| {
+                let target = RemoteTarget::parse(raw.url.trim())
+                    .map_err(|err| ConfigError::RemoteDisplay(err.to_string()))?;
+                let refresh = match raw.refresh_secs {
+                    Some(0) => {
+                        return Err(ConfigError::RemoteDisplay(
+                            "refresh_secs must be greater than zero".to_string(),
+                        ));
+                    }
+                    secs => secs.map_or(remote::DEFAULT_REFRESH, Duration::from_secs),
+                };
+                Ok(RemoteDisplaySettings { target, refresh })
+            })
+            .transpose()?;
+
+        let night = night.map(night_settings).transpose()?.unwrap_or_default();
+        let greetings = Greetings {
+            morning: greetings
+                .morning
+                .map(|raw| greeting(GreetingPeriod::Morning, raw, &music_dir, locale))
+                .transpose()?,
+            evening: greetings
+                .evening
+                .map(|raw| greeting(GreetingPeriod::Evening, raw, &music_dir, locale))
+                .transpose()?,
+        };
+
+        let loudness = loudness
+            .map(|raw| match raw.target_lufs {
+                Some(target) if !TARGET_LUFS_RANGE.contains(&target) => {
+                    Err(ConfigError::Loudness(format!(
+                        "target_lufs must be between {} and {}",
+                        TARGET_LUFS_RANGE.start(),
+                        TARGET_LUFS_RANGE.end()
+                    )))
+                }
+                target => Ok(LoudnessSettings {
+                    target_lufs: target.unwrap_or(loudness::DEFAULT_TARGET_LUFS),
+                }),
+            })
+            .transpose()?;
+
+        let api_tokens = api_tokens
+            .into_iter()
+            .enumerate()
+            .map(|(index, raw)| api_token(index + 1, raw, secrets))
+            .collect::<Result<Vec<_>, _>>()?;
+        for (index, entry) in api_tokens.iter().enumerate() {
+            if api_tokens[..index]
+                .iter()
+                .any(|earlier| earlier.token == entry.token || earlier.name == entry.name)
+            {
+                return Err(ConfigError::ApiToken {
+                    index: index + 1,
+                    reason: "names and tokens must be unique".to_string(),
+                });
+            }
+        }
+        let api_tokens = ApiTokens::new(api_tokens);
+
+        let volume_step = buttons
+            .as_ref()
+            .map_or(DEFAULT_VOLUME_STEP, |buttons| buttons.volume_step);
+        let buttons = buttons.map(button_config).transpose()?;
+
+        let config = Self {
+            music_dir,
+            cards: parsed,
+            names,
+            colors,
+            groups: memberships,
+            commands,
+            system_commands,
+            resumable,
+            locked,
+            expiries,
+            modes,
+            ends,
+            readers,
+            gaps,
+            gains,
+            countdowns,
+            pn532,
+            pcsc,
+            remote_display,
+            mqtt,
+            loudness,
+            audio_device: audio.device.filter(|device| !device.trim().is_empty()),
+            audio_release_after: audio
+                .lazy
+                .then(|| Duration::from_secs(audio.release_after_seconds)),
+            night,
+            greetings,
+            zones: zones
+                .into_iter()
+                .map(|(label, device)| ReaderZone { label, device })
+                .collect(),
+            sounds,
+            effects,
+            startup,
+            ambient,
+            playback_mode: playback_mode.into(),
+            debounce: Duration::from_millis(debounce_ms),
+            combos,
+            combo_window: combo_window_ms.map_or(DEFAULT_COMBO_WINDOW, Duration::from_millis),
+            max_play: max_play_minutes.and_then(play_limit),
+            max_plays,
+            sleep_timer: SleepTimer {
+                fade: sleep_fade_seconds.map_or(SleepTimer::default().fade, Duration::from_secs),
+                warning: sleep_warning_seconds
+                    .map_or(SleepTimer::default().warning, Duration::from_secs),
+            },
+            api_tokens,
+            web: WebSettings {
+                read_only: web.read_only,
+                max_upload_bytes: web.max_upload_mb.map_or(DEFAULT_MAX_UPLOAD_BYTES, |mb| {
+                    mb.saturating_mul(1024 * 1024)
+                }),
+            },
+            buttons,
+            volume_step,
+            fades,
+            notifier,
+            subsonic,
+            spotify,
+            podcasts,
+            recording,
+            speech,
+            locale,
+            allow_external_paths,
+        };
+        if !config.allow_external_paths {
+            for path in config.external_tracks() {
+                tracing::warn!(
+                    path = %path.display(),
+                    "track is outside music_dir; set allow_external_paths = true if that is intended"
+                );
+            }
+        }
+        Ok(config)
+    }
+
+    /// Whether tracks may live outside `music_dir`.
+    pub fn allow_external_paths(&self) -> bool {
+        self.allow_external_paths
+    }
+
+    /// Local tracks, including folders, cues, and ambient and combo tracks,
+    /// that resolve outside `music_dir` through `..`, an absolute path, or
+    /// a symlink. Sorted, each listed once.
+    pub fn external_tracks(&self) -> Vec<PathBuf> {
+        if self.music_dir.as_os_str().is_empty() {
+            return Vec::new();
+        }
+        let chimes = self
+            .countdowns
+            .values()
+            .filter_map(|countdown| countdown.chime.as_ref());
+        let tracks = [
+            &self.sounds.goodnight,
+            &self.sounds.chime,
+            &self.sounds.startup,
+            &self.effects.ready,
+            &self.effects.unknown_card,
+            &self.effects.shutdown,
+            &self.effects.sleep_warning,
+        ]
+        .into_iter()
+        .flatten()
+        .chain(chimes)
+        .chain(self.greetings.tracks())
+        .chain(self.ambient.iter().flat_map(|ambient| &ambient.tracks))
+        .chain(self.combos.iter().flat_map(|combo| &combo.tracks))
+        .map(|track| &track.path);
+        let mut external: Vec<PathBuf> = self
+            .cards
+            .values()
+            .flatten()
+            .chain(tracks)
+            .filter(|path| {
+                !Track::new(path.to_path_buf()).is_remote()
+                    && !sandbox::is_inside(&self.music_dir, path)
+            })
+            .cloned()
+            .collect();
+        external.sort();
+        external.dedup();
+        external
+    }
+
+    /// Lists audio files under `music_dir` that no card references, relative
+    /// to `music_dir` and sorted for stable output.
+    pub fn orphaned_tracks(&self) -> Result<Vec<PathBuf>, ConfigError> {
+        if self.music_dir.as_os_str().is_empty() {
+            return Err(ConfigError::MissingMusicDir);
+        }
+        let ambient = self.ambient.iter().flat_map(|ambient| &ambient.tracks);
+        let combos = self.combos.iter().flat_map(|combo| &combo.tracks);
+        let referenced: HashSet<&PathBuf> = self
+            .cards
+            .values()
+            .flatten()
+            .chain(ambient.chain(combos).map(|track| &track.path))
+            .collect();
+        // Folder-backed cards play the audio files directly inside them.
+        let referenced_dirs: HashSet<&Path> = referenced
+            .iter()
+            .filter(|path| path.is_dir())
+            .map(|path| path.as_path())
+            .collect();
+        let mut orphans = Vec::new();
+        for path in audio_files_under(&self.music_dir)? {
+            let in_folder_card = path
+                .parent()
+                .is_some_and(|parent| referenced_dirs.contains(parent));
+            if !referenced.contains(&path) && !in_folder_card {
+                let relative = path
+                    .strip_prefix(&self.music_dir)
+                    .map(Path::to_path_buf)
+                    .unwrap_or(path);
+                orphans.push(relative);
+            }
+        }
+        orphans.sort();
+        Ok(orphans)
+    }
+
+    /// Checks everything the config points at: that each track exists and
+    /// can be decoded, that folder cards hold audio, and that `music_dir`
+    /// exists. Tracks shared by several cards and files no card plays are
+    /// reported as warnings. Remote tracks are not checked.
+    pub fn validate(&self) -> ValidationReport {
+        let mut report = ValidationReport::default();
+        if self.music_dir.as_os_str().is_empty() || !self.music_dir.is_dir() {
+            report.push(
+                Severity::Error,
+                None,
+                Some(&self.music_dir),
+                "music_dir is not a directory",
+            );
+        }
+
+        let mut cards: Vec<(&CardUid, &Vec<PathBuf>)> = self.cards.iter().collect();
+        cards.sort_by_key(|(uid, _)| uid.to_hex_lowercase());
+        let mut players: HashMap<&PathBuf, Vec<&CardUid>> = HashMap::new();
+        for (uid, paths) in &cards {
+            for path in paths.iter() {
+                check_track(&mut report, Some(uid), path);
+                let cards = players.entry(path).or_default();
+                if !cards.contains(uid) {
+                    cards.push(uid);
+                }
+            }
+            if let Some(chime) = self.countdowns.get(uid).and_then(|c| c.chime.as_ref()) {
+                check_track(&mut report, Some(uid), &chime.path);
+            }
+        }
+        let cues = [
+            &self.sounds.goodnight,
+            &self.sounds.chime,
+            &self.sounds.startup,
+            &self.effects.ready,
+            &self.effects.unknown_card,
+            &self.effects.shutdown,
+            &self.effects.sleep_warning,
+        ];
+        let ambient = self.ambient.iter().flat_map(|ambient| &ambient.tracks);
+        let combos = self.combos.iter().flat_map(|combo| &combo.tracks);
+        let greetings = self.greetings.tracks();
+        for track in cues
+            .into_iter()
+            .flatten()
+            .chain(greetings)
+            .chain(ambient)
+            .chain(combos)
+        {
+            check_track(&mut report, None, &track.path);
+        }
+
+        let mut shared: Vec<_> = players
+            .into_iter()
+            .filter(|(_, cards)| cards.len() > 1)
+            .collect();
+        shared.sort_by_key(|(path, _)| *path);
+        for (path, cards) in shared {
+            let cards: Vec<String> = cards.iter().map(|uid| uid.to_hex_lowercase()).collect();
+            report.push(
+                Severity::Warning,
+                None,
+                Some(path),
+                format!("played by several cards: {}", cards.join(", ")),
+            );
+        }
+        if !self.allow_external_paths {
+            for path in self.external_tracks() {
+                report.push(
+                    Severity::Warning,
+                    None,
+                    Some(&path),
+                    "outside music_dir; set allow_external_paths = true if that is intended",
+                );
+            }
+        }
+        if let Ok(orphans) = self.orphaned_tracks() {
+            for orphan in orphans {
+                report.push(
+                    Severity::Warning,
+                    None,
+                    Some(&orphan),
+                    "no card plays this file",
+                );
+            }
+        }
+        report
+    }
+
+    pub fn into_library(self) -> Library {
+        let playlists = self
+            .cards
+            .into_iter()
+            .map(|(uid, paths)| {
+                let gain = self.gains.get(&uid).copied().unwrap_or_default();
+                let tracks = paths
+                    .into_iter()
+                    .map(|path| Track::new(path).with_gain(gain))
+                    .collect();
+                (uid, tracks)
+            })
+            .collect();
+        Library::from_playlists(playlists)
+            .with_names(self.names)
+            .with_colors(self.colors)
+            .with_groups(self.groups)
+            .with_commands(self.commands)
+            .with_system_commands(self.system_commands)
+            .with_resumable(self.resumable)
+            .with_locked(self.locked)
+            .with_expiries(self.expiries)
+            .with_modes(self.modes)
+            .with_ends(self.ends)
+            .with_readers(self.readers)
+            .with_gaps(self.gaps)
+            .with_countdowns(self.countdowns)
+            .with_sounds(self.sounds)
+            .with_effects(match &self.speech {
+                Some(speech) => speech.announce(self.effects, self.locale),
+                None => self.effects,
+            })
+            .with_startup(self.startup)
+            .with_ambient(self.ambient)
+            .with_night(self.night)
+            .with_greetings(self.greetings)
+            .with_music_dir(self.music_dir)
+            .with_external_paths(self.allow_external_paths)
+            .with_playback_mode(self.playback_mode)
+            .with_debounce(self.debounce)
+            .with_combos(self.combos)
+            .with_combo_window(self.combo_window)
+            .with_max_play(self.max_play)
+            .with_max_plays(self.max_plays)
+            .with_sleep_timer(self.sleep_timer)
+            .with_api_tokens(self.api_tokens)
+            .with_web_settings(self.web)
+            .with_volume_step(self.volume_step)
+    }
+}
+
+/// Validates an `[[api_tokens]]` entry, numbered from 1 for error messages.
+fn api_token(
+    index: usize,
+    raw: RawApiToken,
+    secrets: Option<&Secrets>,
+) -> Result<ApiToken, ConfigError> {
+    let invalid = |reason: &str| ConfigError::ApiToken {
+        index,
+        reason: reason.to_string(),
+    };
+    let name = raw.name.trim().to_string();
+    if name.is_empty() {
+        return Err(invalid("name must not be empty"));
+    }
+    let token = raw.token.resolve(secrets)?;
+    if token.len() < MIN_TOKEN_LEN || token.chars().any(char::is_whitespace) {
+        return Err(invalid(&format!(
+            "token must be at least {MIN_TOKEN_LEN} characters without spaces"
+        )));
+    }
+    if raw
+        .max_volume
+        .is_some_and(|max| !(0.0..=1.0).contains(&max))
+    {
+        return Err(invalid("max_volume must be between 0.0 and 1.0"));
+    }
+    let scope = match raw.scope {
+        RawTokenScope::Full => TokenScope::Full,
+        RawTokenScope::Guest => TokenScope::Guest,
+    };
+    Ok(ApiToken {
+        name,
+        token,
+        scope,
+        max_volume: raw.max_volume,
+    })
+}
+
+/// Validates a `[[combos]]` entry, numbered from 1 for error messages.
+/// Cards are named by their `name` or UID, and need not be mapped
+/// themselves.
+fn combo(
+    index: usize,
+    raw: RawCombo,
+    names: &HashMap<CardUid, String>,
+    music_dir: &Path,
+) -> Result<Combo, ConfigError> {
+    let invalid = |reason: String| ConfigError::Combo { index, reason };
+    let cards: Vec<CardUid> = raw
+        .cards
+        .iter()
+        .map(|card| {
+            names
+                .iter()
+                .find(|(_, name)| name.eq_ignore_ascii_case(card.trim()))
+                .map(|(uid, _)| uid.clone())
+                .or_else(|| CardUid::parse(card).ok())
+                .ok_or_else(|| invalid(format!("no card named {card:?}")))
+        })
+        .collect::<Result<_, _>>()?;
+    let [first, second]: [CardUid; 2] = cards
+        .try_into()
+        .map_err(|_| invalid("`cards` must list exactly two cards".into()))?;
+    if first == second {
+        return Err(invalid("the two cards must differ".into()));
+    }
+    let entries = match (raw.track, raw.tracks) {
+        (Some(track), None) => vec![track],
+        (None, Some(tracks)) if !tracks.is_empty() => tracks,
+        _ => {
+            return Err(invalid(
+                "needs exactly one of `track` or a non-empty `tracks` list".into(),
+            ));
+        }
+    };
+    Ok(Combo {
+        cards: [first, second],
+        tracks: entries
+            .iter()
+            .map(|entry| Track::new(resolve_track_path(music_dir, entry.trim())))
+            .collect(),
+    })
+}
+
+/// Validates the `[notify]` table: each service needs its own keys.
+fn notifier(raw: RawNotify, secrets: Option<&Secrets>) -> Result<Notifier, ConfigError> {
+    let required = |value: Option<String>, key: &str| {
+        value
+            .filter(|value| !value.trim().is_empty())
+            .ok_or_else(|| ConfigError::Notify(format!("`{key}` is required for this service")))
+    };
+    let secret = |value: Option<RawSecret>| value.map(|value| value.resolve(secrets)).transpose();
+    let parse_url =
+        |url: &str| WebhookUrl::parse(url).map_err(|err| ConfigError::Notify(err.to_string()));
+    let service = match raw.service {
+        RawPushService::Ntfy => PushService::Ntfy {
+            server: parse_url(raw.url.as_deref().unwrap_or("http://ntfy.sh"))?,
+            topic: required(secret(raw.topic)?, "topic")?,
+        },
+        RawPushService::Pushover => {
+            if raw.url.is_some() {
+                return Err(ConfigError::Notify(
+                    "`url` is not used by pushover, which posts to its own API".into(),
+                ));
+            }
+            let service = PushService::Pushover {
+                token: required(secret(raw.token)?, "token")?,
+                user: required(secret(raw.user)?, "user")?,
+            };
+            parse_url(notify::PUSHOVER_API)?;
+            service
+        }
+        RawPushService::Webhook => PushService::Webhook {
+            url: parse_url(&required(raw.url, "url")?)?,
+        },
+    };
+    let low_percent = raw
+        .low_battery_percent
+        .unwrap_or(notify::DEFAULT_LOW_BATTERY_PERCENT);
+    if !(1..100).contains(&low_percent) {
+        return Err(ConfigError::Notify(
+            "`low_battery_percent` must be between 1 and 99".into(),
+        ));
+    }
+    if raw.low_battery_percent.is_some() && raw.battery.is_none() {
+        return Err(ConfigError::Notify(
+            "`low_battery_percent` needs a `battery` file to read".into(),
+        ));
+    }
+    let notifier = Notifier::new(
+        service,
+        NotifyEvents {
+            unknown_card: raw.unknown_card,
+            reader_offline: raw.reader_offline,
+            low_battery: raw.low_battery,
+        },
+    );
+    Ok(match raw.battery {
+        Some(path) => notifier.with_battery(BatteryWatch { path, low_percent }),
+        None => notifier,
+    })
+}
+
+/// Validates the `[mqtt]` table.
+fn mqtt_settings(raw: RawMqtt, secrets: Option<&Secrets>) -> Result<MqttSettings, ConfigError> {
+    let invalid = |reason: &str| ConfigError::Mqtt(reason.to_string());
+    let host = raw.host.trim().to_string();
+    if host.is_empty() {
+        return Err(invalid("`host` must not be empty"));
+    }
+    let prefix = raw
+        .topic_prefix
+        .as_deref()
+        .unwrap_or(mqtt::DEFAULT_PREFIX)
+        .trim()
+        .trim_end_matches('/')
+        .to_string();
+    if prefix.is_empty() || prefix.contains(['+', '#']) {
+        return Err(invalid(
+            "`topic_prefix` must be a topic name without + or # wildcards",
+        ));
+    }
+    let client_id = raw
+        .client_id
+        .map(|id| id.trim().to_string())
+        .unwrap_or_else(|| mqtt::DEFAULT_CLIENT_ID.to_string());
+    if client_id.is_empty() {
+        return Err(invalid("`client_id` must not be empty"));
+    }
+    let credentials = match (raw.user, raw.password) {
+        (Some(user), password) => Some((
+            user.resolve(secrets)?,
+            password
+                .map(|password| password.resolve(secrets))
+                .transpose()?
+                .unwrap_or_default(),
+        )),
+        (None, Some(_)) => return Err(invalid("`password` needs a `user`")),
+        (None, None) => None,
+    };
+    let client_auth = match (raw.client_cert, raw.client_key) {
+        (Some(cert), Some(key)) => Some((cert, key)),
+        (None, None) => None,
+        _ => {
+            return Err(invalid(
+                "`client_cert` and `client_key` must be given together",
+            ));
+        }
+    };
+    let tls = if raw.tls {
+        Some(MqttTls {
+            ca_file: raw.ca_file,
+            client_auth,
+        })
+    } else {
+        if raw.ca_file.is_some() || client_auth.is_some() {
+            return Err(invalid(
+                "`ca_file`, `client_cert`, and `client_key` need `tls = true`",
+            ));
+        }
+        None
+    };
+    let port = raw.port.unwrap_or(if tls.is_some() {
+        mqtt::DEFAULT_TLS_PORT
+    } else {
+        mqtt::DEFAULT_PORT
+    });
+    Ok(MqttSettings {
+        host,
+        port,
+        client_id,
+        prefix,
+        credentials,
+        tls,
+    })
+}
+
+/// Validates the `[buttons]` table: each GPIO line drives one button.
+fn button_config(raw: RawButtons) -> Result<ButtonConfig, ConfigError> {
+    if !(raw.volume_step > 0.0 && raw.volume_step <= 1.0) {
+        return Err(ConfigError::Buttons(
+            "`volume_step` must be above 0.0 and at most 1.0".into(),
+        ));
+    }
+    let pins: Vec<(u32, ButtonEvent)> = [
+        (raw.next, ButtonEvent::Next),
+        (raw.previous, ButtonEvent::Previous),
+        (raw.play_pause, ButtonEvent::PlayPause),
+        (raw.volume_up, ButtonEvent::VolumeUp),
+        (raw.volume_down, ButtonEvent::VolumeDown),
+        (raw.mute, ButtonEvent::Mute),
+    ]
+    .into_iter()
+    .filter_map(|(pin, button)| pin.map(|pin| (pin, button)))
+    .collect();
+    let mut seen = HashSet::new();
+    if let Some((pin, _)) = pins.iter().find(|(pin, _)| !seen.insert(*pin)) {
+        return Err(ConfigError::Buttons(format!(
+            "GPIO {pin} is assigned to more than one button"
+        )));
+    }
+    Ok(ButtonConfig {
+        gpio_chip: raw.gpio_chip,
+        pins,
+    })
+}
+
+/// Writes an edited config to `path` and copies it to the mirror, if one
+/// is installed.
+fn write_config(path: &Path, contents: &str) -> Result<(), ConfigEditError> {
+    fs::write(path, contents).map_err(|source| ConfigEditError::Write {
+        path: path.to_path_buf(),
+        source,
+    })?;
+    mirror::update(path);
+    Ok(())
+}
+
+/// Adds a new card to the configuration file. Guest cards get an `expires`
+/// time, after which they stop playing.
+pub fn add_card_to_config(
+    path: &Path,
+    uid: &CardUid,
+    track: &str,
+    expires: Option<SystemTime>,
+) -> Result<(), ConfigEditError> {
+    let mut doc = if path.exists() {
+        let contents = fs::read_to_string(path).map_err(|source| ConfigEditError::Read {
+            path: path.to_path_buf(),
+            source,
+        })?;
+        contents.parse::<DocumentMut>()?
+    } else {
+        let mut doc = DocumentMut::new();
+        doc["music_dir"] = value("");
+        doc["cards"] = table();
+        doc
+    };
+
+    if !doc.as_table().contains_key("cards") {
+        doc["cards"] = table();
+    }
+
+    let cards = doc["cards"]
+        .as_table_mut()
+        .ok_or(ConfigEditError::MissingCards)?;
+    let uid_hex = uid.to_hex_lowercase();
+
+    let already_mapped = cards
+        .iter()
+        .any(|(key, _)| CardUid::parse(key).is_ok_and(|existing| &existing == uid));
+    if already_mapped {
+        return Err(ConfigEditError::Duplicate(uid.clone()));
+    }
+
+    match expires {
+        Some(expires) => {
+            let mut details = toml_edit::InlineTable::new();
+            details.insert("track", track.into());
+            details.insert("expires", expiry::to_datetime(expires).into());
+            cards.insert(&uid_hex, value(details));
+        }
+        None => {
+            cards.insert(&uid_hex, value(track));
+        }
+    }
+
+    write_config(path, &doc.to_string())?;
+
+    Ok(())
+}
+
+/// Removes the entry for `uid` from the config at `path`, however its key
+/// is spelled. Comments and formatting elsewhere are kept.
+pub fn remove_card_from_config(path: &Path, uid: &CardUid) -> Result<(), ConfigEditError> {
+    let contents = fs::read_to_string(path).map_err(|source| ConfigEditError::Read {
+        path: path.to_path_buf(),
+        source,
+    })?;
+    let mut doc = contents.parse::<DocumentMut>()?;
+    let cards = doc
+        .get_mut("cards")
+        .and_then(|cards| cards.as_table_like_mut())
+        .ok_or(ConfigEditError::MissingCards)?;
+    let keys: Vec<String> = cards
+        .iter()
+        .filter(|(key, _)| CardUid::parse(key).is_ok_and(|existing| &existing == uid))
+        .map(|(key, _)| key.to_string())
+        .collect();
+    if keys.is_empty() {
+        return Err(ConfigEditError::NotMapped(uid.clone()));
+    }
+    for key in keys {
+        cards.remove(&key);
+    }
+
+    write_config(path, &doc.to_string())
+}
+
+/// Points the existing entry for `uid` at `track`. A card set up as a
+/// table keeps its other settings, such as its name or volume. Playlists
+/// and command cards are only replaced, as a whole, when `force` is set.
+pub fn update_card_in_config(
+    path: &Path,
+    uid: &CardUid,
+    track: &str,
+    force: bool,
+) -> Result<(), ConfigEditError> {
+    let contents = fs::read_to_string(path).map_err(|source| ConfigEditError::Read {
+        path: path.to_path_buf(),
+        source,
+    })?;
+    let mut doc = contents.parse::<DocumentMut>()?;
+    let cards = doc
+        .get_mut("cards")
+        .and_then(|cards| cards.as_table_like_mut())
+        .ok_or(ConfigEditError::MissingCards)?;
+    let key = cards
+        .iter()
+        .find(|(key, _)| CardUid::parse(key).is_ok_and(|existing| &existing == uid))
+        .map(|(key, _)| key.to_string())
+        .ok_or_else(|| ConfigEditError::NotMapped(uid.clone()))?;
+    let entry = cards.get_mut(&key).expect("key was just found");
+
+    let single_track = entry.is_str()
+        || entry.as_table_like().is_some_and(|details| {
+            !details.contains_key("tracks") && !details.contains_key("action")
+        });
+    if !single_track {
+        if !force {
+            return Err(ConfigEditError::NotSingleTrack(uid.clone()));
+        }
+        *entry = value(track);
+    } else if let Some(details) = entry.as_table_like_mut() {
+        match details.get_mut("track") {
+            Some(existing) => replace_keeping_decor(existing, track),
+            None => {
+                details.insert("track", value(track));
+            }
+        }
+    } else {
+        replace_keeping_decor(entry, track);
+    }
+
+    write_config(path, &doc.to_string())
+}
+
+/// Swaps a string value in place, keeping the spacing and trailing comment
+/// around it.
+fn replace_keeping_decor(item: &mut Item, text: &str) {
+    match item.as_value_mut() {
+        Some(existing) => {
+            let decor = existing.decor().clone();
+            *existing = text.into();
+            *existing.decor_mut() = decor;
+        }
+        None => *item = value(text),
+    }
+}
+
+/// Stands in for the card UID in a placeholder entry.
+pub const PLACEHOLDER_UID: &str = "<uid>";
+
+/// A commented `[cards]` entry for `track`, such as
+/// `# "<uid>" = "album/01.mp3"`. Filling in the UID and uncommenting it
+/// maps a card.
+pub fn placeholder_entry(track: &str) -> String {
+    let escaped = track.replace('\\', "\\\\").replace('"', "\\\"");
+    format!("# \"{PLACEHOLDER_UID}\" = \"{escaped}\"")
+}
+
+/// The track of a placeholder entry line, if `line` is one.
+fn placeholder_track(line: &str) -> Option<String> {
+    let entry = line.trim().strip_prefix('#')?.trim();
+    if !entry.starts_with(&format!("\"{PLACEHOLDER_UID}\"")) {
+        return None;
+    }
+    let doc = entry.parse::<DocumentMut>().ok()?;
+    doc.get(PLACEHOLDER_UID)?.as_str().map(str::to_string)
+}
+
+/// Tracks that already have a placeholder entry in the config at `path`.
+pub fn list_placeholders(path: &Path) -> Result<Vec<String>, ConfigEditError> {
+    let contents = fs::read_to_string(path).map_err(|source| ConfigEditError::Read {
+        path: path.to_path_buf(),
+        source,
+    })?;
+    Ok(contents.lines().filter_map(placeholder_track).collect())
+}
+
+/// Adds a placeholder entry to the end of `[cards]` in the config at `path`
+/// for each of `tracks` that has none yet, and returns the tracks added.
+/// New entries go after earlier placeholders; everything else in the file
+/// is kept as written.
+pub fn add_placeholders_to_config(
+    path: &Path,
+    tracks: &[String],
+) -> Result<Vec<String>, ConfigEditError> {
+    let contents = fs::read_to_string(path).map_err(|source| ConfigEditError::Read {
+        path: path.to_path_buf(),
+        source,
+    })?;
+    let existing: HashSet<String> = contents.lines().filter_map(placeholder_track).collect();
+    let mut added: Vec<String> = Vec::new();
+    for track in tracks {
+        if !existing.contains(track) && !added.contains(track) {
+            added.push(track.clone());
+        }
+    }
+    if added.is_empty() {
+        return Ok(added);
+    }
+    let block: String = added
+        .iter()
+        .map(|track| placeholder_entry(track) + "\n")
+        .collect();
+
+    let mut doc = contents.parse::<DocumentMut>()?;
+    if !doc.as_table().contains_key("cards") {
+        doc["cards"] = table();
+    }
+    let cards = doc["cards"]
+        .as_table_mut()
+        .ok_or(ConfigEditError::MissingCards)?;
+    // Lines after the last entry of `[cards]` belong to the header of the
+    // table that comes next in the file, or to the end of the document. When
+    // only `[cards.<uid>]` tables exist, a `[cards]` header is written above
+    // them and the entries go straight after it.
+    let first_after = |table: &mut toml_edit::Table, after: Option<usize>| {
+        let mut next = None;
+        visit_tables(table, &mut |table| {
+            if let Some(position) = table.position()
+                && after.is_none_or(|after| position > after)
+                && !table.is_implicit()
+            {
+                next = Some(next.map_or(position, |next: usize| next.min(position)));
+            }
+        });
+        next
+    };
+    let next = match cards.position() {
+        Some(position) => first_after(doc.as_table_mut(), Some(position)),
+        None if cards.is_implicit() => {
+            cards.set_implicit(false);
+            first_after(cards, None)
+        }
+        None => None,
+    };
+    match next {
+        Some(next) => visit_tables(doc.as_table_mut(), &mut |table| {
+            if table.position() == Some(next) && !table.is_implicit() {
+                let prefix = table
+                    .decor()
+                    .prefix()
+                    .and_then(|prefix| prefix.as_str())
+                    .unwrap_or("")
+                    .to_string();
+                let at = after_placeholders(&prefix).unwrap_or(0);
+                table
+                    .decor_mut()
+                    .set_prefix(format!("{}{block}{}", &prefix[..at], &prefix[at..]));
+            }
+        }),
+        None => {
+            let mut trailing = doc.trailing().as_str().unwrap_or("").to_string();
+            if trailing.is_empty() && !doc.to_string().ends_with('\n')
+                || !trailing.is_empty() && !trailing.ends_with('\n')
+            {
+                trailing.push('\n');
+            }
+            let at = after_placeholders(&trailing).unwrap_or(trailing.len());
+            trailing.insert_str(at, &block);
+            doc.set_trailing(trailing);
+        }
+    }
+
+    write_config(path, &doc.to_string())?;
+    Ok(added)
+}
+
+/// The byte offset just past the last placeholder line in `text`.
+fn after_placeholders(text: &str) -> Option<usize> {
+    let mut offset = 0;
+    let mut after = None;
+    for line in text.split_inclusive('\n') {
+        offset += line.len();
+        if placeholder_track(line).is_some() {
+            after = Some(offset);
+        }
+    }
+    after
+}
+
+/// Calls `visit` on every table below `table`, including those in arrays
+/// of tables.
+fn visit_tables(table: &mut toml_edit::Table, visit: &mut dyn FnMut(&mut toml_edit::Table)) {
+    for (_, item) in table.iter_mut() {
+        match item {
+            Item::Table(child) => {
+                visit(child);
+                visit_tables(child, visit);
+            }
+            Item::ArrayOfTables(array) => {
+                for child in array.iter_mut() {
+                    visit(child);
+                    visit_tables(child, visit);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// How serious a [`Diagnostic`] is. Only errors make a config invalid.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+/// One problem found by [`MusicBoxConfig::validate`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    /// Hex UID of the card it concerns, if any.
+    pub card: Option<String>,
+    pub path: Option<PathBuf>,
+    pub message: String,
+}
+
+/// What [`MusicBoxConfig::validate`] found, in the order it checked.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct ValidationReport {
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+impl ValidationReport {
+    fn push(
+        &mut self,
+        severity: Severity,
+        card: Option<&CardUid>,
+        path: Option<&Path>,
+        message: impl Into<String>,
+    ) {
+        self.diagnostics.push(Diagnostic {
+            severity,
+            card: card.map(CardUid::to_hex_lowercase),
+            path: path.map(Path::to_path_buf),
+            message: message.into(),
+        });
+    }
+
+    pub fn errors(&self) -> usize {
+        self.count(Severity::Error)
+    }
+
+    pub fn warnings(&self) -> usize {
+        self.count(Severity::Warning)
+    }
+
+    fn count(&self, severity: Severity) -> usize {
+        self.diagnostics
+            .iter()
+            .filter(|diagnostic| diagnostic.severity == severity)
+            .count()
+    }
+}
+
+/// A `[cards]` entry as written in the config, for `musicbox tag list`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct CardEntry {
+    /// The card's UID in lowercase hex.
+    pub uid: String,
+    pub name: Option<String>,
+    /// Tracks as written, relative to `music_dir`; empty for command cards.
+    pub tracks: Vec<String>,
+    /// The `action` of a command card.
+    pub action: Option<String>,
+}
+
+/// Lists the cards of the config at `path`, sorted by UID. Entries are read
+/// as written, without resolving tracks or checking the rest of the config.
+pub fn list_cards(path: &Path) -> Result<Vec<CardEntry>, ConfigEditError> {
+    let contents = fs::read_to_string(path).map_err(|source| ConfigEditError::Read {
+        path: path.to_path_buf(),
+        source,
+    })?;
+    let values: toml::Table = toml::from_str(&contents)?;
+    let cards = values
+        .get("cards")
+        .and_then(toml::Value::as_table)
+        .ok_or(ConfigEditError::MissingCards)?;
+    let mut entries = cards
+        .iter()
+        .map(|(key, value)| {
+            let text = |field: &str| {
+                value
+                    .get(field)
+                    .and_then(toml::Value::as_str)
+                    .map(str::to_string)
+            };
+            Ok(CardEntry {
+                uid: CardUid::parse(key)?.to_hex_lowercase(),
+                name: text("name"),
+                tracks: card_track_entries(value),
+                action: text("action"),
+            })
+        })
+        .collect::<Result<Vec<_>, CardUidParseError>>()?;
+    entries.sort_by(|a, b| a.uid.cmp(&b.uid));
+    Ok(entries)
+}
+
+/// Removes cards whose `expires` time is at or before `now` from the config
+/// at `path`, returning their UIDs. Cards without an expiry are kept.
+pub fn remove_expired_cards(path: &Path, now: SystemTime) -> Result<Vec<CardUid>, ConfigEditError> {
+    let contents = fs::read_to_string(path).map_err(|source| ConfigEditError::Read {
+        path: path.to_path_buf(),
+        source,
+    })?;
+    let mut doc = contents.parse::<DocumentMut>()?;
+    let cards = doc["cards"]
+        .as_table_like_mut()
+        .ok_or(ConfigEditError::MissingCards)?;
+
+    let expired_keys: Vec<String> = cards
+        .iter()
+        .filter(|(_, card)| {
+            card.as_table_like()
+                .and_then(|details| details.get("expires"))
+                .and_then(|expires| expires.as_datetime())
+                .and_then(expiry::to_system_time)
+                .is_some_and(|expires| expires <= now)
+        })
+        .map(|(key, _)| key.to_string())
+        .collect();
+    if expired_keys.is_empty() {
+        return Ok(Vec::new());
+    }
+    let mut removed = Vec::with_capacity(expired_keys.len());
+    for key in expired_keys {
+        cards.remove(&key);
+        removed.push(CardUid::parse(&key)?);
+    }
+
+    write_config(path, &doc.to_string())?;
+    removed.sort_by(|a, b| a.as_bytes().cmp(b.as_bytes()));
+    Ok(removed)
+}
+
+/// Merges the cards and groups of another box's config (`remote`, as TOML
+/// text) into the config at `path`. Entries only the remote has are copied
+/// verbatim, local entries always win over differing remote ones, and a
+/// missing local config starts as a copy of the remote one.
+pub fn merge_remote_config(path: &Path, remote: &str) -> Result<SyncReport, ConfigEditError> {
+    let remote_doc = remote.parse::<DocumentMut>()?;
+    let remote_values: toml::Table = toml::from_str(remote)?;
+    let (mut doc, local_values) = if path.exists() {
+        let contents = fs::read_to_string(path).map_err(|source| ConfigEditError::Read {
+            path: path.to_path_buf(),
+            source,
+        })?;
+        let values: toml::Table = toml::from_str(&contents)?;
+        (contents.parse::<DocumentMut>()?, values)
+    } else {
+        let mut doc = remote_doc.clone();
+        doc.remove("cards");
+        doc.remove("groups");
+        (doc, toml::Table::new())
+    };
+    let section = |values: &toml::Table, name: &str| {
+        values
+            .get(name)
+            .and_then(toml::Value::as_table)
+            .cloned()
+            .unwrap_or_default()
+    };
+
+    let mut report = SyncReport::default();
+    let local_cards: HashMap<CardUid, toml::Value> = section(&local_values, "cards")
+        .into_iter()
+        .map(|(key, value)| Ok((CardUid::parse(&key)?, value)))
+        .collect::<Result<_, CardUidParseError>>()?;
+    if !doc.as_table().contains_key("cards") {
+        doc["cards"] = table();
+    }
+    for (key, remote_value) in section(&remote_values, "cards") {
+        let uid = CardUid::parse(&key)?;
+        match local_cards.get(&uid) {
+            Some(local) if local != &remote_value => {
+                report.conflicts.push(SyncConflict {
+                    entry: format!("card {uid}"),
+                    local: local.to_string(),
+                    remote: remote_value.to_string(),
+                });
+                continue;
+            }
+            Some(_) => report.unchanged.push(uid),
+            None => {
+                copy_entry(&mut doc, &remote_doc, "cards", &key);
+                report.added.push(uid);
+            }
+        }
+        report.tracks.extend(card_track_entries(&remote_value));
+    }
+
+    let local_groups = section(&local_values, "groups");
+    for (name, remote_value) in section(&remote_values, "groups") {
+        match local_groups.get(&name) {
+            Some(local) if local != &remote_value => report.conflicts.push(SyncConflict {
+                entry: format!("group {name}"),
+                local: local.to_string(),
+                remote: remote_value.to_string(),
+            }),
+            Some(_) => {}
+            None => {
+                if !doc.as_table().contains_key("groups") {
+                    let mut groups = toml_edit::Table::new();
+                    groups.set_implicit(true);
+                    doc["groups"] = toml_edit::Item::Table(groups);
+                }
+                copy_entry(&mut doc, &remote_doc, "groups", &name);
+                report.groups_added.push(name);
+            }
+        }
+    }
+
+    write_config(path, &doc.to_string())?;
+
+    report.added.sort_by(|a, b| a.as_bytes().cmp(b.as_bytes()));
+    report
+        .unchanged
+        .sort_by(|a, b| a.as_bytes().cmp(b.as_bytes()));
+    report.conflicts.sort_by(|a, b| a.entry.cmp(&b.entry));
+    report.groups_added.sort();
+    report.tracks.sort();
+    report.tracks.dedup();
+    Ok(report)
+}
+
+/// Copies `section.key` from `from`, keeping the key's original quoting.
+fn copy_entry(doc: &mut DocumentMut, from: &DocumentMut, section: &str, key: &str) {
+    let Some((key, item)) = from[section]
+        .as_table_like()
+        .and_then(|table| table.get_key_value(key))
+    else {
+        return;
+    };
+    if let Some(target) = doc[section].as_table_mut() {
+        target.insert_formatted(key, item.clone());
+    } else if let Some(target) = doc[section].as_table_like_mut() {
+        target.insert(key.get(), item.clone());
+    }
+}
+
+/// Track paths named by a `[cards]` value in any of its accepted forms.
+fn card_track_entries(value: &toml::Value) -> Vec<String> {
+    let strings = |value: &toml::Value| -> Vec<String> {
+        match value {
+            toml::Value::String(track) => vec![track.clone()],
+            toml::Value::Array(tracks) => tracks
+                .iter()
+                .filter_map(|track| track.as_str().map(str::to_string))
+                .collect(),
+            _ => Vec::new(),
+        }
+    };
+    match value {
+        toml::Value::Table(details) => ["track", "tracks"]
+            .iter()
+            .filter_map(|key| details.get(*key))
+            .flat_map(strings)
+            .collect(),
+        other => strings(other),
+    }
+}
+
+/// Checks one track, file or folder, for [`MusicBoxConfig::validate`].
+fn check_track(report: &mut ValidationReport, card: Option<&CardUid>, path: &Path) {
+    let track = Track::new(path.to_path_buf());
+    if track.is_remote() {
+        return;
+    }
+    if path.is_dir() {
+        let files = track.expand();
+        if files.is_empty() {
+            report.push(
+                Severity::Error,
+                card,
+                Some(path),
+                "folder has no audio files",
+            );
+        }
+        for file in files {
+            if let Err(err) = crate::metadata::probe(file.path()) {
+                report.push(Severity::Error, card, Some(file.path()), err.to_string());
+            }
+        }
+        return;
+    }
+    let located = track.locate();
+    if !located.path().exists() {
+        report.push(Severity::Error, card, Some(path), "track does not exist");
+        return;
+    }
+    if located.path() != path {
+        report.push(
+            Severity::Warning,
+            card,
+            Some(path),
+            format!(
+                "track is missing; {} plays instead",
+                located.path().display()
+            ),
+        );
+    }
+    if let Err(err) = crate::metadata::probe(located.path()) {
+        report.push(Severity::Error, card, Some(located.path()), err.to_string());
+    }
+}
+
+/// Recursively collects files with an [`AUDIO_EXTENSIONS`] extension.
+pub(crate) fn audio_files_under(dir: &Path) -> Result<Vec<PathBuf>, std::io::Error> {
+    let mut files = Vec::new();
+    let mut pending = vec![dir.to_path_buf()];
+    while let Some(current) = pending.pop() {
+        for entry in fs::read_dir(&current)? {
+            let path = entry?.path();
+            if path.is_dir() {
+                pending.push(path);
+            } else if is_audio_file(&path) {
+                files.push(path);
+            }
+        }
+    }
+    Ok(files)
+}
+
+pub(crate) fn is_audio_file(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .map(|ext| {
+            AUDIO_EXTENSIONS
+                .iter()
+                .any(|known| known.eq_ignore_ascii_case(ext))
+        })
+        .unwrap_or(false)
+}
+
+/// A `max_play_minutes` value as a sleep timer; 0 means none.
+fn play_limit(minutes: u64) -> Option<Duration> {
+    (minutes > 0).then(|| Duration::from_secs(minutes * 60))
+}
+
+/// Loudest a card's `volume` may boost its tracks, about +12 dB.
+const MAX_CARD_VOLUME: f32 = 4.0;
+
+/// A card's loudness from its `volume` factor or its `gain_db`, which are
+/// alternatives.
+fn card_gain(
+    uid: &CardUid,
+    volume: Option<f32>,
+    gain_db: Option<f32>,
+) -> Result<Option<Gain>, ConfigError> {
+    let invalid = |reason| ConfigError::Gain {
+        card: uid.to_string(),
+        reason,
+    };
+    let factor = match (volume, gain_db) {
+        (None, None) => return Ok(None),
+        (Some(_), Some(_)) => return Err(invalid("set `volume` or `gain_db`, not both")),
+        (Some(volume), None) => volume,
+        (None, Some(db)) => Gain::from_db(db)
+            .ok_or_else(|| invalid("`gain_db` must be a number"))?
+            .factor(),
+    };
+    match Gain::from_factor(factor) {
+        None => Err(invalid("`volume` must be a number from 0.0")),
+        Some(gain) if gain.factor() > MAX_CARD_VOLUME => Err(invalid(
+            "tracks may play at most 4 times louder (`volume = 4.0`, `gain_db = 12`)",
+        )),
+        gain => Ok(gain),
+    }
+}
+
+/// Resolves the absolute path to a track.
+fn resolve_track_path(music_dir: &Path, entry: &str) -> PathBuf {
+    let path = PathBuf::from(entry);
+    let remote = entry.starts_with(subsonic::SCHEME)
+        || entry.starts_with(spotify::SCHEME)
+        || entry.starts_with(podcast::SCHEME)
+        || stream::is_stream_url(entry);
+    if path.is_absolute() || music_dir.as_os_str().is_empty() || remote {
+        path
+    } else {
+        normalize_join(music_dir, path)
+    }
+}
+
+fn normalize_join(base: &Path, relative: PathBuf) -> PathBuf {
+    base.join(relative)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+    use tempfile::tempdir;
+    use toml_edit::DocumentMut;
+
+    #[test]
+    fn builds_library_from_config() {
+        let toml = r#"
+music_dir = "/music"
+
+[cards]
+"0a0b" = "song1.mp3"
+"0c0d" = "/absolute/song2.mp3"
+"0e0f" = "nested/song3.ogg"
+"#;
+
+        let config = MusicBoxConfig::from_reader(toml.as_bytes()).unwrap();
+        assert_eq!(config.music_dir(), Path::new("/music"));
+
+        let library = config.into_library();
+
+        assert_eq!(
+            library
+                .lookup(&CardUid::from_hex("0a0b").unwrap())
+                .unwrap()
+                .path(),
+            Path::new("/music/song1.mp3")
+        );
+        assert_eq!(
+            library
+                .lookup(&CardUid::from_hex("0c0d").unwrap())
+                .unwrap()
+                .path(),
+            Path::new("/absolute/song2.mp3")
+        );
+        assert_eq!(
+            library
+                .lookup(&CardUid::from_hex("0e0f").unwrap())
+                .unwrap()
+                .path(),
+            Path::new("/music/nested/song3.ogg")
+        );
+    }
+
+    #[test]
+    fn invalid_card_uid_returns_error() {
+        let toml = r#"
+music_dir = "/music"
+
+[cards]
+"zz" = "song.mp3"
+"#;
+
+        let err = MusicBoxConfig::from_reader(toml.as_bytes()).unwrap_err();
+        assert!(matches!(err, ConfigError::CardUid(_)));
+    }
+
+    #[test]
+    fn card_keys_are_normalized() {
+        let toml = r#"
+music_dir = "/music"
+
+[cards]
+"AB CD" = "song1.mp3"
+"01:02" = "song2.mp3"
+"#;
+
+        let library = MusicBoxConfig::from_reader(toml.as_bytes())
+            .unwrap()
+            .into_library();
+
+        assert_eq!(
+            library
+                .lookup(&CardUid::from_hex("abcd").unwrap())
+                .unwrap()
+                .path(),
+            Path::new("/music/song1.mp3")
+        );
+        assert!(
+            library
+                .lookup(&CardUid::from_hex("0102").unwrap())
+                .is_some()
+        );
+    }
+
+    #[test]
+    fn card_tables_carry_names() {
+        let toml = r#"
+music_dir = "/music"
+
+[cards]
+"0a0b" = { track = "lullaby.mp3", name = "Lullaby" }
+"0c0d" = "plain.mp3"
+"#;
+
+        let library = MusicBoxConfig::from_reader(toml.as_bytes())
+            .unwrap()
+            .into_library();
+        let uid = CardUid::from_hex("0a0b").unwrap();
+
+        assert_eq!(
+            library.lookup(&uid).unwrap().path(),
+            Path::new("/music/lullaby.mp3")
+        );
+        assert_eq!(library.name(&uid), Some("Lullaby"));
+        assert_eq!(library.resolve_card("lullaby").unwrap(), uid);
+    }
+
+    #[test]
+    fn card_tables_carry_colors() {
+        let toml = r##"
+music_dir = "/music"
+
+[cards]
+"0a0b" = { track = "lullaby.mp3", color = "#3366FF" }
+"0c0d" = "plain.mp3"
+"##;
+
+        let library = MusicBoxConfig::from_reader(toml.as_bytes())
+            .unwrap()
+            .into_library();
+
+        assert_eq!(
+            library.color(&CardUid::from_hex("0a0b").unwrap()),
+            Some(Rgb::new(0x33, 0x66, 0xff))
+        );
+        assert_eq!(library.color(&CardUid::from_hex("0c0d").unwrap()), None);
+
+        let err = MusicBoxConfig::from_reader(
+            "music_dir = \"/m\"\n[cards]\n\"0a0b\" = { track = \"a.mp3\", color = \"blue\" }\n"
+                .as_bytes(),
+        )
+        .unwrap_err();
+        assert!(matches!(err, ConfigError::Color { ref entry, .. } if entry == "card 0a0b"));
+    }
+
+    #[test]
+    fn groups_apply_defaults_and_define_command_cards() {
+        let toml = r##"
+music_dir = "/music"
+
+[groups.audiobooks]
+color = "#0000ff"
+
+[groups.music]
+
+[cards]
+"01" = { track = "book1.mp3", group = "audiobooks" }
+"02" = { track = "book2.mp3", group = "audiobooks", color = "#ff0000" }
+"03" = { track = "song.mp3", group = "music" }
+"a0" = { action = "stop-group", group = "audiobooks" }
+"a1" = { action = "shuffle-group", group = "music" }
+"##;
+
+        let library = MusicBoxConfig::from_reader(toml.as_bytes())
+            .unwrap()
+            .into_library();
+        let uid = |hex| CardUid::from_hex(hex).unwrap();
+
+        assert_eq!(library.group(&uid("01")), Some("audiobooks"));
+        assert_eq!(library.color(&uid("01")), Some(Rgb::new(0, 0, 0xff)));
+        assert_eq!(library.color(&uid("02")), Some(Rgb::new(0xff, 0, 0)));
+        assert_eq!(library.color(&uid("03")), None);
+        assert_eq!(
+            library.group_members("audiobooks"),
+            vec![uid("01"), uid("02")]
+        );
+        assert_eq!(
+            library.command(&uid("a1")),
+            Some(&GroupCommand {
+                action: GroupAction::Shuffle,
+                group: "music".into()
+            })
+        );
+        assert!(library.lookup(&uid("a0")).is_none());
+    }
+
+    #[test]
+    fn groups_give_members_their_playback_defaults() {
+        let toml = r#"
+music_dir = "/music"
+
+[groups.audiobooks]
+mode = "shuffle"
+resume = true
+gap_ms = 1500
+volume = 0.5
+max_play_minutes = 30
+
+[cards]
+"01" = { track = "book1.mp3", group = "audiobooks" }
+"02" = { track = "book2.mp3", group = "audiobooks", mode = "sequential", resume = false, gap_ms = 0, gain_db = 0.0, max_play_minutes = 0 }
+"#;
+
+        let library = MusicBoxConfig::from_reader(toml.as_bytes())
+            .unwrap()
+            .into_library();
+        let uid = |hex| CardUid::from_hex(hex).unwrap();
+        let gain = |hex| library.lookup(&uid(hex)).unwrap().gain().factor();
+
+        assert_eq!(library.mode(&uid("01")), PlaylistMode::Shuffle);
+        assert!(library.resumes(&uid("01")));
+        assert_eq!(library.gap(&uid("01")), Duration::from_millis(1500));
+        assert_eq!(gain("01"), 0.5);
+        assert_eq!(
+            library.max_play(&uid("01")),
+            Some(Duration::from_secs(30 * 60))
+        );
+
+        assert_eq!(library.mode(&uid("02")), PlaylistMode::Sequential);
+        assert!(!library.resumes(&uid("02")));
+        assert_eq!(library.gap(&uid("02")), Duration::ZERO);
+        assert_eq!(gain("02"), 1.0);
+        assert_eq!(library.max_play(&uid("02")), None);
+    }
+
+    #[test]
+    fn undeclared_groups_are_rejected() {
+        let toml = r#"
+music_dir = "/music"
+
+[cards]
+"01" = { track = "book1.mp3", group = "audiobookz" }
+"#;
+
+        let err = MusicBoxConfig::from_reader(toml.as_bytes()).unwrap_err();
+
+        assert!(
+            matches!(err, ConfigError::UnknownGroup { ref group, .. } if group == "audiobookz")
+        );
+    }
+
+    #[test]
+    fn array_values_define_playlists() {
+        let toml = r#"
+music_dir = "/music"
+
+[cards]
+"0a0b" = ["book/01.mp3", "book/02.mp3"]
+"0c0d" = { tracks = ["a.mp3", "b.mp3"], name = "Mix" }
+"#;
+
+        let library = MusicBoxConfig::from_reader(toml.as_bytes())
+            .unwrap()
+            .into_library();
+        let paths = |hex| {
+            library
+                .playlist(&CardUid::from_hex(hex).unwrap())
+                .unwrap()
+                .iter()
+                .map(|track| track.path().to_path_buf())
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            paths("0a0b"),
+            vec![
+                PathBuf::from("/music/book/01.mp3"),
+                PathBuf::from("/music/book/02.mp3")
+            ]
+        );
+        assert_eq!(paths("0c0d").len(), 2);
+    }
+
+    #[test]
+    fn card_tables_need_exactly_one_track_source() {
+        for card in [
+            r#"{ name = "Nothing" }"#,
+            r#"{ track = "a.mp3", tracks = ["b.mp3"] }"#,
+            "[]",
+        ] {
+            let toml = format!("music_dir = \"/m\"\n[cards]\n\"0a0b\" = {card}\n");
+            let err = MusicBoxConfig::from_reader(toml.as_bytes()).unwrap_err();
+            assert!(
+                matches!(err, ConfigError::TrackSpec { .. }),
+                "{card}: {err}"
+            );
+        }
+    }
+
+    #[test]
+    fn locale_defaults_to_english_and_parses_codes() {
+        let english =
+            MusicBoxConfig::from_reader("music_dir = \"/m\"\n[cards]\n".as_bytes()).unwrap();
+        assert_eq!(english.locale(), Locale::En);
+
+        let german = MusicBoxConfig::from_reader(
+            "music_dir = \"/m\"\nlocale = \"de\"\n[cards]\n".as_bytes(),
+        )
+        .unwrap();
+        assert_eq!(german.locale(), Locale::De);
+
+        let err = MusicBoxConfig::from_reader(
+            "music_dir = \"/m\"\nlocale = \"xx\"\n[cards]\n".as_bytes(),
+        )
+        .unwrap_err();
+        assert!(matches!(err, ConfigError::ParseToml(_)));
+    }
+
+    #[test]
+    fn the_box_speaks_and_plays_cues_in_its_locale() {
+        use crate::controller::{AudioPlayer, MusicBoxController, PlayerError};
+        use std::sync::{Arc, Mutex};
+
+        struct Recorder(Arc<Mutex<Vec<PathBuf>>>);
+        impl AudioPlayer for Recorder {
+            fn play(&mut self, track: &Track) -> Result<(), PlayerError> {
+                self.0.lock().unwrap().push(track.path().to_path_buf());
+                Ok(())
+            }
+            fn stop(&mut self) -> Result<(), PlayerError> {
+                Ok(())
+            }
+            fn pause(&mut self) -> Result<(), PlayerError> {
+                Ok(())
+            }
+            fn resume(&mut self) -> Result<(), PlayerError> {
+                Ok(())
+            }
+            fn play_effect(&mut self, effect: &Track) -> Result<(), PlayerError> {
+                self.play(effect)
+            }
+        }
+
+        let dir = tempdir().unwrap();
+        let music = dir.path().join("music");
+        fs::create_dir_all(music.join("cues")).unwrap();
+        fs::write(music.join("cues/goodnight.mp3"), b"").unwrap();
+        fs::write(music.join("cues/goodnight.de.mp3"), b"").unwrap();
+        let toml = format!(
+            r#"
+music_dir = {music:?}
+locale = "de"
+
+[cards]
+
+[sounds]
+goodnight = "cues/goodnight.mp3"
+
+[speech]
+command = ["sh", "-c", "printf %s \"$1\" > \"$2\"", "sh", "{{text}}", "{{output}}"]
+cache_dir = {cache:?}
+"#,
+            cache = dir.path().join("speech"),
+        );
+        let library = MusicBoxConfig::from_reader(toml.as_bytes())
+            .unwrap()
+            .into_library();
+        let played = Arc::new(Mutex::new(Vec::new()));
+        let mut controller = MusicBoxController::new(library, Recorder(played.clone()));
+
+        controller.start_up().unwrap();
+        controller.shutdown().unwrap();
+
+        let played = played.lock().unwrap();
+        let heard: Vec<String> = played[..2]
+            .iter()
+            .map(|path| fs::read_to_string(path).unwrap())
+            .collect();
+        assert_eq!(heard, ["Bereit zum Spielen.", "Gute Nacht!"]);
+        assert_eq!(played[2], music.join("cues/goodnight.de.mp3"));
+        assert_eq!(played.len(), 3);
+    }
+
+    #[test]
+    fn speech_command_must_name_a_program_and_an_output() {
+        for command in [r#"[]"#, r#"["espeak-ng", "{text}"]"#] {
+            let err = MusicBoxConfig::from_reader(
+                format!("music_dir = \"/m\"\n[cards]\n[speech]\ncommand = {command}\n").as_bytes(),
+            )
+            .unwrap_err();
+            assert!(matches!(err, ConfigError::Speech(_)), "{command}: {err}");
+        }
+    }
+
+    #[test]
+    fn sounds_table_resolves_goodnight_cue() {
+        let toml = r#"
+music_dir = "/music"
+
+[cards]
+
+[sounds]
+goodnight = "cues/goodnight.ogg"
+"#;
+
+        let library = MusicBoxConfig::from_reader(toml.as_bytes())
+            .unwrap()
+            .into_library();
+
+        assert_eq!(
+            library.sounds().goodnight,
+            Some(Track::new(PathBuf::from("/music/cues/goodnight.ogg")))
+        );
+    }
+
+    #[test]
+    fn effects_table_resolves_local_files() {
+        let base = "music_dir = \"/music\"\n[cards]\n";
+        let library = MusicBoxConfig::from_reader(
+            format!("{base}[effects]\nready = \"fx/ready.ogg\"\nunknown_card = \"fx/bonk.ogg\"\n")
+                .as_bytes(),
+        )
+        .unwrap()
+        .into_library();
+        assert_eq!(
+            library.effects(),
+            &Effects {
+                ready: Some(Track::new(PathBuf::from("/music/fx/ready.ogg"))),
+                unknown_card: Some(Track::new(PathBuf::from("/music/fx/bonk.ogg"))),
+                shutdown: None,
+                sleep_warning: None,
+            }
+        );
+        assert!(matches!(
+            MusicBoxConfig::from_reader(
+                format!("{base}[effects]\nshutdown = \"http://radio.example/bye\"\n").as_bytes()
+            ),
+            Err(ConfigError::Effects(_))
+        ));
+    }
+
+    #[test]
+    fn startup_table_picks_what_happens_at_boot() {
+        let base = "music_dir = \"/music\"\n[cards]\n";
+        let library = |extra: &str| {
+            MusicBoxConfig::from_reader(format!("{base}{extra}").as_bytes())
+                .map(MusicBoxConfig::into_library)
+        };
+        assert_eq!(library("").unwrap().startup(), StartupAction::Silent);
+        assert_eq!(
+            library("[startup]\naction = \"resume\"\n")
+                .unwrap()
+                .startup(),
+            StartupAction::Resume
+        );
+
+        let chime = library("[startup]\naction = \"chime\"\nchime = \"cues/hello.ogg\"\n").unwrap();
+        assert_eq!(chime.startup(), StartupAction::Chime);
+        assert_eq!(
+            chime.sounds().startup,
+            Some(Track::new(PathBuf::from("/music/cues/hello.ogg")))
+        );
+
+        let ambient = "[ambient]\ntracks = [\"rain.ogg\"]\nafter_minutes = 10\n[startup]\naction = \"ambient\"\n";
+        assert_eq!(library(ambient).unwrap().startup(), StartupAction::Ambient);
+
+        for bad in [
+            "[startup]\naction = \"chime\"\n",
+            "[startup]\naction = \"resume\"\nchime = \"hello.ogg\"\n",
+            "[startup]\naction = \"ambient\"\n",
+        ] {
+            assert!(
+                matches!(library(bad), Err(ConfigError::Startup(_))),
+                "{bad}"
+            );
+        }
+    }
+
+    #[test]
+    fn playback_mode_defaults_to_stop_and_accepts_pause_resume() {
+        let library = |extra: &str| {
+            MusicBoxConfig::from_reader(
+                format!("music_dir = \"/m\"\n{extra}\n[cards]\n").as_bytes(),
+            )
+            .unwrap()
+            .into_library()
+        };
+
+        assert_eq!(library("").playback_mode(), PlaybackMode::StopOnRetap);
+        assert_eq!(
+            library("playback_mode = \"pause-resume\"").playback_mode(),
+            PlaybackMode::PauseResume
+        );
+        assert_eq!(
+            library("playback_mode = \"lift-to-stop\"").playback_mode(),
+            PlaybackMode::LiftToStop
+        );
+        assert_eq!(library("").debounce(), Duration::ZERO);
+        assert_eq!(
+            library("debounce_ms = 400").debounce(),
+            Duration::from_millis(400)
+        );
+    }
+
+    #[test]
+    fn resume_marks_only_opted_in_cards() {
+        let toml = r#"
+music_dir = "/music"
+
+[cards]
+"01" = { track = "books/hobbit", resume = true }
+"02" = { track = "songs/song.mp3" }
+"03" = "songs/other.mp3"
+"#;
+        let library = MusicBoxConfig::from_reader(toml.as_bytes())
+            .unwrap()
+            .into_library();
+
+        assert!(library.resumes(&CardUid::parse("01").unwrap()));
+        assert!(!library.resumes(&CardUid::parse("02").unwrap()));
+        assert!(!library.resumes(&CardUid::parse("03").unwrap()));
+    }
+
+    #[test]
+    fn guest_cards_carry_expiry_times() {
+        let toml = r#"
+music_dir = "/music"
+
+[cards]
+"01" = { track = "party.mp3", expires = 2026-10-25T18:30:05Z }
+"02" = "songs/song.mp3"
+"#;
+        let library = MusicBoxConfig::from_reader(toml.as_bytes())
+            .unwrap()
+            .into_library();
+
+        let expires = std::time::UNIX_EPOCH + Duration::from_secs(1_792_953_005);
+        assert_eq!(
+            library.expires_at(&CardUid::parse("01").unwrap()),
+            Some(expires)
+        );
+        assert_eq!(library.expires_at(&CardUid::parse("02").unwrap()), None);
+
+        let local = "music_dir = \"/music\"\n[cards]\n\"01\" = { track = \"a.mp3\", expires = 2026-10-25T18:30:05 }\n";
+        assert!(matches!(
+            MusicBoxConfig::from_reader(local.as_bytes()),
+            Err(ConfigError::Expiry { .. })
+        ));
+    }
+
+    #[test]
+    fn cards_choose_their_playlist_mode() {
+        let toml = r#"
+music_dir = "/music"
+
+[cards]
+"01" = { tracks = ["a.mp3", "b.mp3"], mode = "shuffle" }
+"02" = { track = "lullaby.mp3", mode = "repeat-one" }
+"03" = "songs/song.mp3"
+"04" = { tracks = ["cow-en.mp3", "cow-de.mp3"], mode = "alternate" }
+"#;
+        let library = MusicBoxConfig::from_reader(toml.as_bytes())
+            .unwrap()
+            .into_library();
+        let mode = |hex| library.mode(&CardUid::parse(hex).unwrap());
+        assert_eq!(mode("04"), PlaylistMode::Alternate);
+        assert_eq!(mode("01"), PlaylistMode::Shuffle);
+        assert_eq!(mode("02"), PlaylistMode::RepeatOne);
+        assert_eq!(mode("03"), PlaylistMode::Sequential);
+
+        let unknown =
+            "music_dir = \"/music\"\n[cards]\n\"01\" = { track = \"a.mp3\", mode = \"random\" }\n";
+        assert!(MusicBoxConfig::from_reader(unknown.as_bytes()).is_err());
+    }
+
+    #[test]
+    fn mute_cards_and_buttons_toggle_mute() {
+        let toml = r#"
+music_dir = "/music"
+
+[cards]
+"01" = "a.mp3"
+"0b02" = { action = "mute" }
+
+[buttons]
+mute = 22
+"#;
+        let config = MusicBoxConfig::from_reader(toml.as_bytes()).unwrap();
+        let buttons = config.buttons().unwrap();
+        assert!(buttons.pins.contains(&(22, ButtonEvent::Mute)));
+        let library = config.into_library();
+        let mute = CardUid::parse("0b02").unwrap();
+        assert_eq!(
+            library.system_command(&mute),
+            Some(SystemCommand::ToggleMute)
+        );
+        assert!(library.playlist(&mute).is_none());
+    }
+
+    #[test]
+    fn command_cards_map_to_system_actions() {
+        let toml = r#"
+music_dir = "/music"
+
+[cards]
+"01" = { action = "volume-up" }
+"02" = { action = "volume-down" }
+"03" = { action = "next" }
+"04" = { action = "previous" }
+"05" = { action = "play-pause" }
+"06" = { action = "shutdown" }
+"07" = { action = "reload-config" }
+"08" = { action = "unlock" }
+"09" = { track = "book.mp3", locked = true }
+"#;
+        let library = MusicBoxConfig::from_reader(toml.as_bytes())
+            .unwrap()
+            .into_library();
+        let command = |hex| library.system_command(&CardUid::parse(hex).unwrap());
+        assert_eq!(command("01"), Some(SystemCommand::VolumeUp));
+        assert_eq!(command("02"), Some(SystemCommand::VolumeDown));
+        assert_eq!(command("03"), Some(SystemCommand::Next));
+        assert_eq!(command("04"), Some(SystemCommand::Previous));
+        assert_eq!(command("05"), Some(SystemCommand::PlayPause));
+        assert_eq!(command("06"), Some(SystemCommand::Shutdown));
+        assert_eq!(command("07"), Some(SystemCommand::ReloadConfig));
+        assert_eq!(command("08"), Some(SystemCommand::Unlock));
+        assert!(library.is_locked(&CardUid::parse("09").unwrap()));
+        assert!(!library.is_locked(&CardUid::parse("08").unwrap()));
+
+        let unknown = "music_dir = \"/music\"\n[cards]\n\"01\" = { action = \"reboot\" }\n";
+        assert!(MusicBoxConfig::from_reader(unknown.as_bytes()).is_err());
+    }
+
+    #[test]
+    fn combos_pair_cards_by_name_or_uid() {
+        let toml = r#"
+music_dir = "/music"
+combo_window_ms = 5000
+
+[cards]
+"01" = { track = "lion.mp3", name = "Lion" }
+
+[[combos]]
+cards = ["lion", "0f"]
+track = "animals/lion-de.mp3"
+"#;
+        let library = MusicBoxConfig::from_reader(toml.as_bytes())
+            .unwrap()
+            .into_library();
+        let uid = |hex| CardUid::parse(hex).unwrap();
+        assert_eq!(library.combo_window(), Duration::from_secs(5));
+        assert_eq!(
+            library
+                .combo(&uid("0f"), &uid("01"))
+                .map(|combo| &combo.tracks),
+            Some(&vec![Track::new(PathBuf::from(
+                "/music/animals/lion-de.mp3"
+            ))])
+        );
+        assert!(library.in_combo(&uid("0f")));
+        assert!(!library.in_combo(&uid("02")));
+
+        for (bad, reason) in [
+            (toml.replace("\"0f\"]", "\"0f\", \"02\"]"), "exactly two"),
+            (toml.replace("\"0f\"]", "\"01\"]"), "must differ"),
+            (toml.replace("\"lion\"", "\"tiger\""), "no card named"),
+            (
+                toml.replace("track = \"animals", "tracks = []\n#"),
+                "`track`",
+            ),
+            (
+                format!("{toml}[[combos]]\ncards = [\"0f\", \"01\"]\ntrack = \"x.mp3\"\n"),
+                "already form",
+            ),
+        ] {
+            match MusicBoxConfig::from_reader(bad.as_bytes()) {
+                Err(ConfigError::Combo {
+                    reason: message, ..
+                }) => {
+                    assert!(message.contains(reason), "{message}")
+                }
+                other => panic!("expected a combo error for {reason}, got {other:?}"),
+            }
+        }
+    }
+
+    #[test]
+    fn cards_choose_what_happens_after_their_last_track() {
+        let toml = r#"
+music_dir = "/music"
+
+[cards]
+"01" = { tracks = ["a.mp3", "b.mp3"], on_end = { continue = "lullabies" } }
+"02" = { track = "night.mp3", name = "Lullabies", on_end = "shuffle-repeat" }
+"03" = { track = "c.mp3", on_end = "stop" }
+"04" = { track = "part1.mp3", on_finish = "card:05" }
+"05" = { track = "part2.mp3", on_finish = "card: lullabies" }
+"06" = { track = "d.mp3", on_finish = "stop" }
+"#;
+        let library = MusicBoxConfig::from_reader(toml.as_bytes())
+            .unwrap()
+            .into_library();
+        let end = |hex| library.end(&CardUid::parse(hex).unwrap());
+        assert_eq!(
+            end("01"),
+            PlaylistEnd::Continue(CardUid::parse("02").unwrap())
+        );
+        assert_eq!(end("02"), PlaylistEnd::ShuffleRepeat);
+        assert_eq!(end("03"), PlaylistEnd::Stop);
+        assert_eq!(
+            end("04"),
+            PlaylistEnd::Continue(CardUid::parse("05").unwrap())
+        );
+        assert_eq!(
+            end("05"),
+            PlaylistEnd::Continue(CardUid::parse("02").unwrap())
+        );
+        assert_eq!(end("06"), PlaylistEnd::Stop);
+
+        for bad in [
+            r#""01" = { track = "a.mp3", on_end = { continue = "missing" } }"#,
+            r#""01" = { track = "a.mp3", mode = "repeat", on_end = "stop" }"#,
+            r#""01" = { track = "a.mp3", on_finish = "card:missing" }"#,
+            r#""01" = { track = "a.mp3", on_finish = "repeat" }"#,
+            r#""01" = { track = "a.mp3", on_finish = "stop", on_end = "stop" }"#,
+        ] {
+            let toml = format!("music_dir = \"/music\"\n[cards]\n{bad}\n");
+            assert!(matches!(
+                MusicBoxConfig::from_reader(toml.as_bytes()),
+                Err(ConfigError::OnEnd { .. })
+            ));
+        }
+    }
+
+    #[test]
+    fn max_play_minutes_sets_a_sleep_timer_that_cards_can_override() {
+        let toml = r#"
+music_dir = "/music"
+max_play_minutes = 30
+
+[cards]
+"01" = "lullaby.mp3"
+"02" = { track = "story.mp3", max_play_minutes = 10 }
+"03" = { track = "book", max_play_minutes = 0 }
+"#;
+        let library = MusicBoxConfig::from_reader(toml.as_bytes())
+            .unwrap()
+            .into_library();
+        let limit = |hex| library.max_play(&CardUid::parse(hex).unwrap());
+        assert_eq!(limit("01"), Some(Duration::from_secs(30 * 60)));
+        assert_eq!(limit("02"), Some(Duration::from_secs(10 * 60)));
+        assert_eq!(limit("03"), None);
+        assert_eq!(library.sleep_timer(), SleepTimer::default());
+
+        let library = MusicBoxConfig::from_reader(
+            format!("sleep_fade_seconds = 30\nsleep_warning_seconds = 120\n{toml}").as_bytes(),
+        )
+        .unwrap()
+        .into_library();
+        assert_eq!(
+            library.sleep_timer(),
+            SleepTimer {
+                fade: Duration::from_secs(30),
+                warning: Duration::from_secs(120),
+            }
+        );
+    }
+
+    #[test]
+    fn gap_ms_sets_the_silence_between_tracks() {
+        let toml = r#"
+music_dir = "/music"
+
+[cards]
+"01" = { tracks = ["ch1.mp3", "ch2.mp3"], gap_ms = 1500 }
+"02" = "song.mp3"
+"#;
+        let library = MusicBoxConfig::from_reader(toml.as_bytes())
+            .unwrap()
+            .into_library();
+        assert_eq!(
+            library.gap(&CardUid::parse("01").unwrap()),
+            Duration::from_millis(1500)
+        );
+        assert_eq!(library.gap(&CardUid::parse("02").unwrap()), Duration::ZERO);
+    }
+
+    #[test]
+    fn volume_and_gain_db_set_a_cards_loudness() {
+        let toml = r#"
+music_dir = "/music"
+
+[cards]
+"01" = { tracks = ["ch1.mp3", "ch2.mp3"], volume = 0.5 }
+"02" = { track = "loud.mp3", gain_db = -6.0 }
+"03" = "song.mp3"
+"#;
+        let library = MusicBoxConfig::from_reader(toml.as_bytes())
+            .unwrap()
+            .into_library();
+        let gains = |card: &str| -> Vec<f32> {
+            library
+                .playlist(&CardUid::parse(card).unwrap())
+                .unwrap()
+                .iter()
+                .map(|track| track.gain().factor())
+                .collect()
+        };
+        assert_eq!(gains("01"), vec![0.5, 0.5]);
+        assert!((gains("02")[0] - 0.501).abs() < 0.001);
+        assert_eq!(gains("03"), vec![1.0]);
+
+        for card in [
+            r#"{ track = "a.mp3", volume = 0.5, gain_db = 3.0 }"#,
+            r#"{ track = "a.mp3", volume = -0.5 }"#,
+            r#"{ track = "a.mp3", gain_db = 20.0 }"#,
+        ] {
+            let toml = format!("music_dir = \"/music\"\n[cards]\n\"01\" = {card}\n");
+            assert!(matches!(
+                MusicBoxConfig::from_reader(toml.as_bytes()),
+                Err(ConfigError::Gain { .. })
+            ));
+        }
+    }
+
+    #[test]
+    fn countdown_cards_take_a_duration_and_an_optional_chime() {
+        let toml = r#"
+music_dir = "/music"
+
+[sounds]
+chime = "cues/ding.mp3"
+
+[cards]
+"01" = { name = "Toothbrushing", track = "brush.mp3", countdown_secs = 120 }
+"02" = { track = "tidy.mp3", countdown_secs = 300, chime = "cues/gong.mp3" }
+"03" = "song.mp3"
+"#;
+        let library = MusicBoxConfig::from_reader(toml.as_bytes())
+            .unwrap()
+            .into_library();
+        assert_eq!(
+            library.sounds().chime,
+            Some(Track::new(PathBuf::from("/music/cues/ding.mp3")))
+        );
+        assert_eq!(
+            library.countdown(&CardUid::parse("01").unwrap()),
+            Some(&Countdown {
+                duration: Duration::from_secs(120),
+                chime: None,
+            })
+        );
+        assert_eq!(
+            library
+                .countdown(&CardUid::parse("02").unwrap())
+                .and_then(|countdown| countdown.chime.clone()),
+            Some(Track::new(PathBuf::from("/music/cues/gong.mp3")))
+        );
+        assert_eq!(library.countdown(&CardUid::parse("03").unwrap()), None);
+
+        let chime_alone =
+            "music_dir = \"/music\"\n[cards]\n\"01\" = { track = \"a.mp3\", chime = \"b.mp3\" }\n";
+        assert!(matches!(
+            MusicBoxConfig::from_reader(chime_alone.as_bytes()),
+            Err(ConfigError::Countdown { .. })
+        ));
+    }
+
+    #[test]
+    fn night_table_sets_the_cap_and_schedule() {
+        let toml = r#"
+music_dir = "/music"
+
+[night]
+max_volume = 0.3
+from = "19:30"
+until = "07:00"
+
+[cards]
+"ee" = { action = "night-mode" }
+"#;
+        let library = MusicBoxConfig::from_reader(toml.as_bytes())
+            .unwrap()
+            .into_library();
+        assert_eq!(
+            *library.night(),
+            NightSettings {
+                max_volume: 0.3,
+                schedule: NightSchedule::from_times((19, 30), (7, 0)),
+            }
+        );
+        assert_eq!(
+            library.system_command(&CardUid::parse("ee").unwrap()),
+            Some(SystemCommand::ToggleNightMode)
+        );
+
+        for bad in [
+            "from = \"19:30\"",
+            "from = \"25:00\"\nuntil = \"07:00\"",
+            "max_volume = 1.5",
+        ] {
+            let toml = format!("music_dir = \"/music\"\n[cards]\n[night]\n{bad}\n");
+            assert!(matches!(
+                MusicBoxConfig::from_reader(toml.as_bytes()),
+                Err(ConfigError::Night(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn greetings_table_sets_clips_and_windows() {
+        let toml = r#"
+music_dir = "/music"
+
+[greetings.morning]
+track = "cues/good-morning.mp3"
+
+[greetings.evening]
+track = "cues/good-evening.mp3"
+from = "18:30"
+
+[cards]
+"#;
+        let library = MusicBoxConfig::from_reader(toml.as_bytes())
+            .unwrap()
+            .into_library();
+        let greetings = library.greetings();
+        assert_eq!(
+            greetings.morning,
+            Some(Greeting {
+                track: Track::new(PathBuf::from("/music/cues/good-morning.mp3")),
+                window: GreetingPeriod::Morning.default_window(),
+            })
+        );
+        assert_eq!(
+            greetings.evening.as_ref().unwrap().window,
+            NightSchedule::from_times((18, 30), (22, 0)).unwrap()
+        );
+
+        for bad in [
+            "[greetings.morning]\ntrack = \"a.mp3\"\nfrom = \"7\"",
+            "[greetings.evening]\ntrack = \"a.mp3\"\nfrom = \"22:00\"",
+            "[greetings.evening]\ntrack = \" \"",
+        ] {
+            let toml = format!("music_dir = \"/music\"\n[cards]\n{bad}\n");
+            assert!(matches!(
+                MusicBoxConfig::from_reader(toml.as_bytes()),
+                Err(ConfigError::Greetings(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn pn532_table_names_the_serial_port() {
+        let base = "music_dir = \"/music\"\n[cards]\n";
+        let config = MusicBoxConfig::from_reader(
+            format!("{base}[pn532]\ndevice = \"/dev/serial0\"\n").as_bytes(),
+        )
+        .unwrap();
+        assert_eq!(
+            config.pn532(),
+            Some(&Pn532Settings {
+                device: PathBuf::from("/dev/serial0"),
+                baud: 115_200,
+                poll_interval: None,
+                read_counter: false,
+            })
+        );
+        let odd = format!("{base}[pn532]\ndevice = \"/dev/ttyUSB0\"\nbaud = 12345\n");
+        assert!(matches!(
+            MusicBoxConfig::from_reader(odd.as_bytes()),
+            Err(ConfigError::Pn532(_))
+        ));
+    }
+
+    #[test]
+    fn poll_intervals_are_set_per_reader_backend() {
+        let base = "music_dir = \"/music\"\n[cards]\n";
+        let config = MusicBoxConfig::from_reader(base.as_bytes()).unwrap();
+        assert_eq!(config.pcsc(), PcscSettings::default());
+
+        let toml = format!(
+            "{base}[pcsc]\npoll_interval_ms = 500\nmax_backoff_ms = 10000\n\
+             [pn532]\ndevice = \"/dev/serial0\"\npoll_interval_ms = 50\n"
+        );
+        let config = MusicBoxConfig::from_reader(toml.as_bytes()).unwrap();
+        assert_eq!(
+            config.pcsc(),
+            PcscSettings {
+                poll_interval: Some(Duration::from_millis(500)),
+                max_backoff: Duration::from_secs(10),
+            }
+        );
+        assert_eq!(
+            config.pn532().unwrap().poll_interval,
+            Some(Duration::from_millis(50))
+        );
+
+        for bad in [
+            "[pcsc]\npoll_interval_ms = 0\n",
+            "[pcsc]\npoll_interval_ms = 500\nmax_backoff_ms = 100\n",
+            "[pcsc]\nbackoff = 1\n",
+        ] {
+            assert!(
+                MusicBoxConfig::from_reader(format!("{base}{bad}").as_bytes()).is_err(),
+                "{bad}"
+            );
+        }
+        assert!(matches!(
+            MusicBoxConfig::from_reader(
+                format!("{base}[pn532]\ndevice = \"/dev/serial0\"\npoll_interval_ms = 0\n")
+                    .as_bytes()
+            ),
+            Err(ConfigError::Pn532(_))
+        ));
+    }
+
+    #[test]
+    fn remote_display_takes_an_http_or_udp_url() {
+        let base = "music_dir = \"/music\"\n[cards]\n";
+        let config = MusicBoxConfig::from_reader(base.as_bytes()).unwrap();
+        assert_eq!(config.remote_display(), None);
+
+        let toml = format!("{base}[remote_display]\nurl = \"udp://10.0.0.5:4210\"\n");
+        let config = MusicBoxConfig::from_reader(toml.as_bytes()).unwrap();
+        assert_eq!(
+            config.remote_display(),
+            Some(&RemoteDisplaySettings {
+                target: RemoteTarget::Udp("10.0.0.5:4210".into()),
+                refresh: remote::DEFAULT_REFRESH,
+            })
+        );
+        let toml = format!(
+            "{base}[remote_display]\nurl = \"http://esp32.local/status\"\nrefresh_secs = 5\n"
+        );
+        let config = MusicBoxConfig::from_reader(toml.as_bytes()).unwrap();
+        assert_eq!(
+            config.remote_display().unwrap().refresh,
+            Duration::from_secs(5)
+        );
+
+        for bad in [
+            "url = \"ftp://esp32.local/\"\n",
+            "url = \"udp://10.0.0.5\"\n",
+            "url = \"udp://10.0.0.5:4210\"\nrefresh_secs = 0\n",
+        ] {
+            assert!(matches!(
+                MusicBoxConfig::from_reader(format!("{base}[remote_display]\n{bad}").as_bytes()),
+                Err(ConfigError::RemoteDisplay(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn loudness_table_turns_on_normalization() {
+        let base = "music_dir = \"/music\"\n[cards]\n";
+        let config = MusicBoxConfig::from_reader(base.as_bytes()).unwrap();
+        assert_eq!(config.loudness(), None);
+
+        let config = MusicBoxConfig::from_reader(format!("{base}[loudness]\n").as_bytes()).unwrap();
+        assert_eq!(config.loudness(), Some(LoudnessSettings::default()));
+        let config = MusicBoxConfig::from_reader(
+            format!("{base}[loudness]\ntarget_lufs = -23\n").as_bytes(),
+        )
+        .unwrap();
+        assert_eq!(config.loudness().unwrap().target_lufs, -23.0);
+        assert!(matches!(
+            MusicBoxConfig::from_reader(format!("{base}[loudness]\ntarget_lufs = 3\n").as_bytes()),
+            Err(ConfigError::Loudness(_))
+        ));
+    }
+
+    #[test]
+    fn api_tokens_default_to_guest_scope() {
+        let base = "music_dir = \"/music\"\n[cards]\n";
+        let library = MusicBoxConfig::from_reader(base.as_bytes())
+            .unwrap()
+            .into_library();
+        assert!(library.api_tokens().is_empty());
+
+        let toml = format!(
+            "{base}[[api_tokens]]\nname = \"parents\"\ntoken = \"0123456789abcdef\"\nscope = \"full\"\n\
+             [[api_tokens]]\nname = \"babysitter\"\ntoken = \"fedcba9876543210\"\nmax_volume = 0.4\n"
+        );
+        let library = MusicBoxConfig::from_reader(toml.as_bytes())
+            .unwrap()
+            .into_library();
+        let tokens = library.api_tokens();
+        assert!(tokens.find("0123456789abcdef").unwrap().grant().is_full());
+        let guest = tokens.find("fedcba9876543210").unwrap();
+        assert_eq!(guest.scope, TokenScope::Guest);
+        assert_eq!(guest.max_volume, Some(0.4));
+
+        for bad in [
+            "name = \"short\"\ntoken = \"abc\"\n",
+            "name = \"loud\"\ntoken = \"0123456789abcdef\"\nmax_volume = 1.5\n",
+            "name = \"\"\ntoken = \"0123456789abcdef\"\n",
+        ] {
+            assert!(matches!(
+                MusicBoxConfig::from_reader(format!("{base}[[api_tokens]]\n{bad}").as_bytes()),
+                Err(ConfigError::ApiToken { index: 1, .. })
+            ));
+        }
+        let twice = format!(
+            "{base}[[api_tokens]]\nname = \"a\"\ntoken = \"0123456789abcdef\"\n\
+             [[api_tokens]]\nname = \"b\"\ntoken = \"0123456789abcdef\"\n"
+        );
+        assert!(matches!(
+            MusicBoxConfig::from_reader(twice.as_bytes()),
+            Err(ConfigError::ApiToken { index: 2, .. })
+        ));
+    }
+
+    #[test]
+    fn web_table_can_make_the_api_read_only() {
+        let base = "music_dir = \"/music\"\n[cards]\n";
+        let library = MusicBoxConfig::from_reader(base.as_bytes())
+            .unwrap()
+            .into_library();
+        assert!(!library.web_settings().read_only);
+
+        let toml = format!("{base}[web]\nread_only = true\n");
+        let library = MusicBoxConfig::from_reader(toml.as_bytes())
+            .unwrap()
+            .into_library();
+        assert!(library.web_settings().read_only);
+        assert_eq!(
+            library.web_settings().max_upload_bytes,
+            DEFAULT_MAX_UPLOAD_BYTES
+        );
+        let toml = format!("{base}[web]\nmax_upload_mb = 5\n");
+        let library = MusicBoxConfig::from_reader(toml.as_bytes())
+            .unwrap()
+            .into_library();
+        assert_eq!(library.web_settings().max_upload_bytes, 5 * 1024 * 1024);
+        let typo = format!("{base}[web]\nreadonly = true\n");
+        assert!(MusicBoxConfig::from_reader(typo.as_bytes()).is_err());
+    }
+
+    #[test]
+    fn cards_can_be_tied_to_a_labelled_reader() {
+        let toml = r#"
+music_dir = "/music"
+
+[readers]
+front = "ACR122U 00"
+back = "ACR122U 01"
+
+[cards]
+"01" = { track = "a.mp3", reader = "back" }
+"02" = "b.mp3"
+"#;
+        let config = MusicBoxConfig::from_reader(toml.as_bytes()).unwrap();
+        assert_eq!(
+            config.reader_zones(),
+            [
+                ReaderZone {
+                    label: "back".into(),
+                    device: "ACR122U 01".into()
+                },
+                ReaderZone {
+                    label: "front".into(),
+                    device: "ACR122U 00".into()
+                },
+            ]
+        );
+        let library = config.into_library();
+        assert_eq!(library.reader(&CardUid::parse("01").unwrap()), Some("back"));
+        assert_eq!(library.reader(&CardUid::parse("02").unwrap()), None);
+
+        let unknown =
+            "music_dir = \"/music\"\n[cards]\n\"01\" = { track = \"a.mp3\", reader = \"side\" }\n";
+        assert!(matches!(
+            MusicBoxConfig::from_reader(unknown.as_bytes()),
+            Err(ConfigError::UnknownReader { .. })
+        ));
+    }
+
+    #[test]
+    fn remove_expired_cards_keeps_current_and_permanent_cards() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("musicbox.toml");
+        let uid = |hex| CardUid::from_hex(hex).unwrap();
+        let now = SystemTime::now();
+        add_card_to_config(
+            &path,
+            &uid("01"),
+            "a.mp3",
+            Some(now - Duration::from_secs(5)),
+        )
+        .unwrap();
+        add_card_to_config(
+            &path,
+            &uid("02"),
+            "b.mp3",
+            Some(now + Duration::from_secs(3_600)),
+        )
+        .unwrap();
+        add_card_to_config(&path, &uid("03"), "c.mp3", None).unwrap();
+
+        assert_eq!(remove_expired_cards(&path, now).unwrap(), vec![uid("01")]);
+        assert!(remove_expired_cards(&path, now).unwrap().is_empty());
+
+        let library = MusicBoxConfig::from_reader(std::fs::read(&path).unwrap().as_slice())
+            .unwrap()
+            .into_library();
+        assert_eq!(library.lookup(&uid("01")), None);
+        assert!(library.expires_at(&uid("02")).is_some());
+        assert!(library.lookup(&uid("03")).is_some());
+    }
+
+    #[test]
+    fn fade_table_sets_fade_durations() {
+        let base = "music_dir = \"/music\"\n[cards]\n";
+        let config = MusicBoxConfig::from_reader(base.as_bytes()).unwrap();
+        assert_eq!(config.fades(), FadeSettings::default());
+
+        let toml = format!("{base}[fade]\nout_ms = 400\nin_ms = 250\ncrossfade = true\n");
+        let config = MusicBoxConfig::from_reader(toml.as_bytes()).unwrap();
+        assert_eq!(
+            config.fades(),
+            FadeSettings {
+                fade_out: Duration::from_millis(400),
+                fade_in: Duration::from_millis(250),
+                crossfade: true,
+            }
+        );
+
+        let toml = format!("{base}[fade]\ncrossfade = true\n");
+        assert!(matches!(
+            MusicBoxConfig::from_reader(toml.as_bytes()),
+            Err(ConfigError::Fade(_))
+        ));
+    }
+
+    #[test]
+    fn notify_table_builds_push_service() {
+        let base = "music_dir = \"/music\"\n[cards]\n";
+        let toml = format!(
+            "{base}[notify]\nservice = \"ntfy\"\ntopic = \"kids-box\"\nreader_offline = false\n\
+             battery = \"/sys/class/power_supply/BAT0/capacity\"\n"
+        );
+        let config = MusicBoxConfig::from_reader(toml.as_bytes()).unwrap();
+        let notifier = config.notifier().unwrap();
+        assert_eq!(
+            notifier.service(),
+            &PushService::Ntfy {
+                server: WebhookUrl::parse("http://ntfy.sh").unwrap(),
+                topic: "kids-box".into(),
+            }
+        );
+        assert_eq!(
+            notifier.events(),
+            NotifyEvents {
+                unknown_card: true,
+                reader_offline: false,
+                low_battery: true,
+            }
+        );
+        assert_eq!(
+            notifier.battery(),
+            Some(&BatteryWatch {
+                path: "/sys/class/power_supply/BAT0/capacity".into(),
+                low_percent: 15,
+            })
+        );
+
+        let toml = format!("{base}[notify]\nservice = \"pushover\"\ntoken = \"abc\"\n");
+        let err = MusicBoxConfig::from_reader(toml.as_bytes()).unwrap_err();
+        assert!(matches!(err, ConfigError::Notify(message) if message.contains("`user`")));
+
+        for bad in [
+            "service = \"pushover\"\ntoken = \"abc\"\nuser = \"def\"\nurl = \"http://relay.local/push\"\n",
+            "service = \"webhook\"\nurl = \"http://hass.local/hook\"\nlow_battery_percent = 20\n",
+            "service = \"webhook\"\nurl = \"http://hass.local/hook\"\nbattery = \"capacity\"\nlow_battery_percent = 0\n",
+        ] {
+            assert!(
+                matches!(
+                    MusicBoxConfig::from_reader(format!("{base}[notify]\n{bad}").as_bytes()),
+                    Err(ConfigError::Notify(_))
+                ),
+                "{bad}"
+            );
+        }
+    }
+
+    #[test]
+    fn mqtt_table_defaults_ports_and_topics() {
+        let base = "music_dir = \"/music\"\n[cards]\n";
+        let config = MusicBoxConfig::from_reader(base.as_bytes()).unwrap();
+        assert_eq!(config.mqtt(), None);
+
+        let toml = format!("{base}[mqtt]\nhost = \"homeassistant.local\"\n");
+        let config = MusicBoxConfig::from_reader(toml.as_bytes()).unwrap();
+        assert_eq!(
+            config.mqtt(),
+            Some(&MqttSettings {
+                host: "homeassistant.local".into(),
+                port: 1883,
+                client_id: "musicbox".into(),
+                prefix: "musicbox".into(),
+                credentials: None,
+                tls: None,
+            })
+        );
+
+        let toml = format!(
+            "{base}[mqtt]\nhost = \"broker\"\ntopic_prefix = \"nursery/box/\"\nuser = \"box\"\n\
+             password = \"hunter2\"\ntls = true\nca_file = \"/etc/mqtt/ca.pem\"\n"
+        );
+        let mqtt = MusicBoxConfig::from_reader(toml.as_bytes())
+            .unwrap()
+            .mqtt()
+            .cloned()
+            .unwrap();
+        assert_eq!(mqtt.port, 8883);
+        assert_eq!(mqtt.topic("state"), "nursery/box/state");
+        assert_eq!(mqtt.credentials, Some(("box".into(), "hunter2".into())));
+        assert_eq!(
+            mqtt.tls,
+            Some(MqttTls {
+                ca_file: Some("/etc/mqtt/ca.pem".into()),
+                client_auth: None,
+            })
+        );
+
+        let toml = format!(
+            "{base}[mqtt]\nhost = \"broker\"\ntls = true\nclient_cert = \"box.pem\"\n\
+             client_key = \"box.key\"\n"
+        );
+        let mqtt = MusicBoxConfig::from_reader(toml.as_bytes())
+            .unwrap()
+            .mqtt()
+            .cloned()
+            .unwrap();
+        assert_eq!(
+            mqtt.tls,
+            Some(MqttTls {
+                ca_file: None,
+                client_auth: Some(("box.pem".into(), "box.key".into())),
+            })
+        );
+
+        for bad in [
+            "host = \" \"\n",
+            "host = \"broker\"\ntopic_prefix = \"box/#\"\n",
+            "host = \"broker\"\npassword = \"hunter2\"\n",
+            "host = \"broker\"\nca_file = \"/etc/mqtt/ca.pem\"\n",
+            "host = \"broker\"\ntls = true\nclient_cert = \"box.pem\"\n",
+        ] {
+            assert!(
+                matches!(
+                    MusicBoxConfig::from_reader(format!("{base}[mqtt]\n{bad}").as_bytes()),
+                    Err(ConfigError::Mqtt(_))
+                ),
+                "{bad}"
+            );
+        }
+    }
+
+    #[cfg(feature = "https")]
+    #[test]
+    fn credentials_can_come_from_a_secrets_file() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(
+            dir.path().join("secrets.toml"),
+            "pushover-token = \"abc\"\npushover-user = \"def\"\n",
+        )
+        .unwrap();
+        let toml = "music_dir = \"/music\"\nsecrets = \"secrets.toml\"\n[cards]\n\
+                    [notify]\nservice = \"pushover\"\n\
+                    token = { secret = \"pushover-token\" }\nuser = { secret = \"pushover-user\" }\n";
+        let config = MusicBoxConfig::from_reader_in(toml.as_bytes(), dir.path()).unwrap();
+        assert_eq!(
+            config.notifier().unwrap().service(),
+            &PushService::Pushover {
+                token: "abc".into(),
+                user: "def".into(),
+            }
+        );
+
+        let unknown = toml.replace("\"pushover-user\" }", "\"missing\" }");
+        assert!(matches!(
+            MusicBoxConfig::from_reader_in(unknown.as_bytes(), dir.path()),
+            Err(ConfigError::UnknownSecret(key)) if key == "missing"
+        ));
+        let no_file = toml.replace("secrets = \"secrets.toml\"\n", "");
+        assert!(matches!(
+            MusicBoxConfig::from_reader_in(no_file.as_bytes(), dir.path()),
+            Err(ConfigError::NoSecretsFile(_))
+        ));
+        assert!(matches!(
+            MusicBoxConfig::from_reader(toml.as_bytes()),
+            Err(ConfigError::Secrets(_))
+        ));
+    }
+
+    #[test]
+    fn subsonic_entries_need_a_server() {
+        let cards = "music_dir = \"/music\"\n[cards]\n\"01\" = \"subsonic:abc123\"\n";
+        assert!(matches!(
+            MusicBoxConfig::from_reader(cards.as_bytes()),
+            Err(ConfigError::Subsonic(_))
+        ));
+
+        let toml = format!(
+            "{cards}[subsonic]\nurl = \"http://navidrome.local:4533\"\nuser = \"kid\"\npassword = \"pw\"\n"
+        );
+        let config = MusicBoxConfig::from_reader(toml.as_bytes()).unwrap();
+        assert_eq!(
+            config.subsonic().unwrap().dir(),
+            Path::new("/music/.subsonic-cache")
+        );
+        let library = config.into_library();
+        assert_eq!(
+            library.lookup(&CardUid::parse("01").unwrap()),
+            Some(&Track::new(PathBuf::from("subsonic:abc123")))
+        );
+
+        let bad = toml.replace("subsonic:abc123", "subsonic:../x");
+        assert!(matches!(
+            MusicBoxConfig::from_reader(bad.as_bytes()),
+            Err(ConfigError::Subsonic(_))
+        ));
+    }
+
+    #[test]
+    fn spotify_entries_need_a_daemon() {
+        let cards =
+            "music_dir = \"/music\"\n[cards]\n\"01\" = \"spotify:album:4aawyAB9vmqN3uQ7FjRGTy\"\n";
+        assert!(matches!(
+            MusicBoxConfig::from_reader(cards.as_bytes()),
+            Err(ConfigError::Spotify(_))
+        ));
+
+        let toml = format!("{cards}[spotify]\n");
+        let config = MusicBoxConfig::from_reader(toml.as_bytes()).unwrap();
+        assert_eq!(
+            config.spotify().unwrap().api,
+            WebhookUrl::parse("http://127.0.0.1:3678").unwrap()
+        );
+        assert_eq!(
+            config.into_library().lookup(&CardUid::parse("01").unwrap()),
+            Some(&Track::new(PathBuf::from(
+                "spotify:album:4aawyAB9vmqN3uQ7FjRGTy"
+            )))
+        );
+
+        let bad = toml.replace("spotify:album:", "spotify:user:");
+        assert!(matches!(
+            MusicBoxConfig::from_reader(bad.as_bytes()),
+            Err(ConfigError::Spotify(_))
+        ));
+    }
+
+    #[test]
+    fn podcast_entries_name_http_feeds() {
+        let toml = r#"
+music_dir = "/music"
+
+[cards]
+"01" = "podcast:http://feeds.local/stories.xml"
+
+[podcasts]
+episode = "next-unplayed"
+"#;
+        let config = MusicBoxConfig::from_reader(toml.as_bytes()).unwrap();
+        assert_eq!(config.podcasts().dir(), Path::new("/music/.podcast-cache"));
+        assert_eq!(config.podcasts().order(), EpisodeOrder::NextUnplayed);
+        let library = config.into_library();
+        assert_eq!(
+            library.lookup(&CardUid::parse("01").unwrap()),
+            Some(&Track::new(PathBuf::from(
+                "podcast:http://feeds.local/stories.xml"
+            )))
+        );
+
+        let https = toml.replace("http://", "https://");
+        assert_eq!(
+            MusicBoxConfig::from_reader(https.as_bytes()).is_ok(),
+            cfg!(feature = "https")
+        );
+        let ftp = toml.replace("http://", "ftp://");
+        assert!(matches!(
+            MusicBoxConfig::from_reader(ftp.as_bytes()),
+            Err(ConfigError::Podcast(_))
+        ));
+    }
+
+    #[test]
+    fn stream_entries_load_when_the_build_can_fetch_them() {
+        let toml = "music_dir = \"/music\"\n[cards]\n\"0a\" = \"https://radio.example/live\"\n";
+        let loaded = MusicBoxConfig::from_reader(toml.as_bytes());
+        if cfg!(feature = "https") {
+            assert!(loaded.is_ok());
+        } else {
+            assert!(
+                matches!(loaded, Err(ConfigError::Stream(message)) if message.contains("https feature"))
+            );
+        }
+    }
+
+    #[test]
+    fn recording_table_sets_up_voice_memos() {
+        let toml = r#"
+music_dir = "/music"
+
+[cards]
+"01" = "song.mp3"
+
+[recording]
+card = "0b:01"
+max_seconds = 20
+"#;
+        let config = MusicBoxConfig::from_reader(toml.as_bytes()).unwrap();
+        assert_eq!(
+            config.recording(),
+            Some(&MemoSettings {
+                card: CardUid::parse("0b01").unwrap(),
+                dir: PathBuf::from("/music/memos"),
+                max_length: Duration::from_secs(20),
+            })
+        );
+
+        let taken = toml.replace("0b:01", "01");
+        assert!(matches!(
+            MusicBoxConfig::from_reader(taken.as_bytes()),
+            Err(ConfigError::Recording(_))
+        ));
+    }
+
+    #[test]
+    fn ambient_table_builds_idle_policy() {
+        let toml = r#"
+music_dir = "/music"
+
+[cards]
+
+[ambient]
+tracks = ["ambient"]
+after_minutes = 10
+"#;
+
+        let library = MusicBoxConfig::from_reader(toml.as_bytes())
+            .unwrap()
+            .into_library();
+
+        assert_eq!(
+            library.ambient(),
+            Some(&AmbientPolicy {
+                tracks: vec![Track::new(PathBuf::from("/music/ambient"))],
+                idle_after: Duration::from_secs(600),
+                volume: 0.3,
+            })
+        );
+    }
+
+    #[test]
+    fn ambient_volume_out_of_range_is_rejected() {
+        let toml = r#"
+music_dir = "/music"
+
+[cards]
+
+[ambient]
+tracks = ["rain.ogg"]
+after_minutes = 5
+volume = 1.5
+"#;
+
+        let err = MusicBoxConfig::from_reader(toml.as_bytes()).unwrap_err();
+        assert!(matches!(err, ConfigError::Ambient(_)));
+    }
+
+    #[test]
+    fn duplicate_card_names_are_rejected() {
+        let toml = r#"
+music_dir = "/music"
+
+[cards]
+"0a0b" = { track = "a.mp3", name = "Bedtime" }
+"0c0d" = { track = "b.mp3", name = "bedtime" }
+"#;
+
+        let err = MusicBoxConfig::from_reader(toml.as_bytes()).unwrap_err();
+        assert!(matches!(err, ConfigError::DuplicateName(_)));
+    }
+
+    #[test]
+    fn keys_differing_only_in_case_or_spacing_conflict() {
+        let toml = r#"
+music_dir = "/music"
+
+[cards]
+"abcd" = "song1.mp3"
+"AB CD" = "song2.mp3"
+"ABCD" = "song3.mp3"
+"0102" = "song4.mp3"
+"#;
+
+        let err = MusicBoxConfig::from_reader(toml.as_bytes()).unwrap_err();
+
+        match &err {
+            ConfigError::DuplicateCards(duplicates) => {
+                assert_eq!(
+                    duplicates,
+                    &vec![DuplicateCard {
+                        uid: CardUid::from_hex("abcd").unwrap(),
+                        keys: vec!["AB CD".into(), "ABCD".into(), "abcd".into()],
+                    }]
+                );
+            }
+            other => panic!("unexpected error: {other:?}"),
+        }
+        assert_eq!(
+            err.to_string(),
+            r#"cards mapped more than once: abcd (keys "AB CD", "ABCD", "abcd")"#
+        );
+    }
+
+    #[test]
+    fn orphaned_tracks_lists_unreferenced_audio() {
+        let dir = tempdir().unwrap();
+        let music = dir.path();
+        std::fs::create_dir_all(music.join("album")).unwrap();
+        for file in [
+            "mapped.mp3",
+            "loose.ogg",
+            "album/b.flac",
+            "album/a.MP3",
+            "notes.txt",
+        ] {
+            std::fs::write(music.join(file), b"").unwrap();
+        }
+        let toml = format!(
+            "music_dir = {:?}\n[cards]\n\"0a0b\" = \"mapped.mp3\"\n",
+            music.display().to_string()
+        );
+
+        let config = MusicBoxConfig::from_reader(toml.as_bytes()).unwrap();
+        let orphans = config.orphaned_tracks().unwrap();
+
+        assert_eq!(
+            orphans,
+            vec![
+                PathBuf::from("album/a.MP3"),
+                PathBuf::from("album/b.flac"),
+                PathBuf::from("loose.ogg"),
+            ]
+        );
+    }
+
+    #[test]
+    fn orphaned_tracks_requires_music_dir() {
+        let config = MusicBoxConfig::from_reader("music_dir = \"\"\n[cards]\n".as_bytes()).unwrap();
+        assert!(matches!(
+            config.orphaned_tracks(),
+            Err(ConfigError::MissingMusicDir)
+        ));
+    }
+
+    #[test]
+    fn add_card_to_config_creates_or_updates_file() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("musicbox.toml");
+        let uid = CardUid::from_hex("0a0b").unwrap();
+
+        add_card_to_config(&path, &uid, "songs/track.mp3", None).unwrap();
+
+        let contents = std::fs::read_to_string(&path).unwrap();
+        let doc = contents.parse::<DocumentMut>().unwrap();
+        assert_eq!(doc["music_dir"].as_str(), Some(""));
+        assert_eq!(doc["cards"]["0a0b"].as_str(), Some("songs/track.mp3"));
+    }
+
+    #[test]
+    fn merge_remote_config_adds_new_entries_and_reports_conflicts() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("musicbox.toml");
+        std::fs::write(
+            &path,
+            r#"
+music_dir = "/home/pi/music"
+
+[cards]
+"0a0b" = "same.mp3"
+"0c0d" = "mine.mp3"
+"#,
+        )
+        .unwrap();
+        let remote = r##"
+music_dir = "/srv/music"
+
+[cards]
+"0A0B" = "same.mp3"
+"0c0d" = "theirs.mp3"
+"0e0f" = { tracks = ["book/01.mp3", "book/02.mp3"], group = "stories" }
+"1011" = { action = "stop-group", group = "stories" }
+
+[groups.stories]
+color = "#3366ff"
+"##;
+
+        let report = merge_remote_config(&path, remote).unwrap();
+
+        let uid = |hex| CardUid::from_hex(hex).unwrap();
+        assert_eq!(report.added, vec![uid("0e0f"), uid("1011")]);
+        assert_eq!(report.unchanged, vec![uid("0a0b")]);
+        assert_eq!(report.conflicts.len(), 1);
+        assert_eq!(report.conflicts[0].entry, "card 0c0d");
+        assert_eq!(report.groups_added, vec!["stories".to_string()]);
+        assert_eq!(
+            report.tracks,
+            vec!["book/01.mp3", "book/02.mp3", "same.mp3"]
+        );
+
+        let merged = MusicBoxConfig::from_reader(std::fs::read(&path).unwrap().as_slice())
+            .unwrap()
+            .into_library();
+        assert_eq!(merged.group(&uid("0e0f")), Some("stories"));
+        assert_eq!(
+            merged.lookup(&uid("0c0d")),
+            Some(&Track::new(PathBuf::from("/home/pi/music/mine.mp3")))
+        );
+    }
+
+    #[test]
+    fn merge_remote_config_clones_into_missing_config() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("musicbox.toml");
+        let remote =
+            "music_dir = \"/srv/music\"\nlocale = \"de\"\n\n[cards]\n\"0a0b\" = \"a.mp3\"\n";
+
+        let report = merge_remote_config(&path, remote).unwrap();
+
+        assert_eq!(report.added, vec![CardUid::from_hex("0a0b").unwrap()]);
+        let config = MusicBoxConfig::from_reader(std::fs::read(&path).unwrap().as_slice()).unwrap();
+        assert_eq!(config.music_dir(), Path::new("/srv/music"));
+        assert_eq!(config.locale(), Locale::De);
+    }
+
+    #[test]
+    fn add_card_to_config_rejects_duplicate_cards() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("musicbox.toml");
+        std::fs::write(
+            &path,
+            r#"
+music_dir = "/music"
+
+[cards]
+"0c0d" = "other.mp3"
+"#
+            .trim(),
+        )
+        .unwrap();
+
+        let uid = CardUid::from_hex("0c0d").unwrap();
+        let err = add_card_to_config(&path, &uid, "songs/new.mp3", None).unwrap_err();
+        assert!(matches!(err, ConfigEditError::Duplicate(_)));
+
+        std::fs::write(
+            &path,
+            "music_dir = \"/music\"\n[cards]\n\"0C 0D\" = \"other.mp3\"\n",
+        )
+        .unwrap();
+        let err = add_card_to_config(&path, &uid, "songs/new.mp3", None).unwrap_err();
+        assert!(matches!(err, ConfigEditError::Duplicate(_)));
+    }
+
+    #[test]
+    fn cards_can_be_listed_and_removed_keeping_comments() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("musicbox.toml");
+        std::fs::write(
+            &path,
+            r#"music_dir = "/music"
+
+[cards]
+"0C 0D" = { track = "lullaby.mp3", name = "Lullaby" }
+# Bedtime
+"0a" = ["a.mp3", "b.mp3"]
+"0b" = { action = "next" }
+"#,
+        )
+        .unwrap();
+
+        let entries = list_cards(&path).unwrap();
+        assert_eq!(
+            entries,
+            vec![
+                CardEntry {
+                    uid: "0a".into(),
+                    name: None,
+                    tracks: vec!["a.mp3".into(), "b.mp3".into()],
+                    action: None,
+                },
+                CardEntry {
+                    uid: "0b".into(),
+                    name: None,
+                    tracks: Vec::new(),
+                    action: Some("next".into()),
+                },
+                CardEntry {
+                    uid: "0c0d".into(),
+                    name: Some("Lullaby".into()),
+                    tracks: vec!["lullaby.mp3".into()],
+                    action: None,
+                },
+            ]
+        );
+
+        remove_card_from_config(&path, &CardUid::from_hex("0c0d").unwrap()).unwrap();
+        let contents = std::fs::read_to_string(&path).unwrap();
+        assert!(!contents.contains("lullaby"));
+        assert!(contents.contains("# Bedtime"));
+        assert_eq!(list_cards(&path).unwrap().len(), 2);
+        assert!(matches!(
+            remove_card_from_config(&path, &CardUid::from_hex("0c0d").unwrap()),
+            Err(ConfigEditError::NotMapped(_))
+        ));
+    }
+
+    #[test]
+    fn validation_reports_missing_undecodable_shared_and_orphaned_tracks() {
+        let dir = tempdir().unwrap();
+        let music = dir.path();
+        let clip = crate::memo::Clip {
+            sample_rate: 8_000,
+            channels: 1,
+            samples: vec![0; 800],
+        };
+        clip.write_wav(&music.join("good.wav")).unwrap();
+        clip.write_wav(&music.join("orphan.wav")).unwrap();
+        std::fs::write(music.join("bad.mp3"), b"not audio").unwrap();
+        std::fs::create_dir(music.join("empty")).unwrap();
+        let config = MusicBoxConfig::from_reader(
+            format!(
+                r#"music_dir = {:?}
+
+[cards]
+"01" = "good.wav"
+"02" = ["good.wav", "missing.mp3"]
+"03" = "bad.mp3"
+"04" = "empty"
+"#,
+                music.display().to_string()
+            )
+            .as_bytes(),
+        )
+        .unwrap();
+
+        let report = config.validate();
+        let found: Vec<(Severity, Option<&str>, &str)> = report
+            .diagnostics
+            .iter()
+            .map(|diagnostic| {
+                (
+                    diagnostic.severity,
+                    diagnostic.card.as_deref(),
+                    diagnostic.message.as_str(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            found[0],
+            (Severity::Error, Some("02"), "track does not exist")
+        );
+        assert_eq!(found[1].0, Severity::Error);
+        assert_eq!(found[1].1, Some("03"));
+        assert_eq!(
+            found[2],
+            (Severity::Error, Some("04"), "folder has no audio files")
+        );
+        assert_eq!(
+            found[3],
+            (Severity::Warning, None, "played by several cards: 01, 02")
+        );
+        assert_eq!(
+            found[4],
+            (Severity::Warning, None, "no card plays this file")
+        );
+        assert_eq!(
+            report.diagnostics[4].path,
+            Some(PathBuf::from("orphan.wav"))
+        );
+        assert_eq!((report.errors(), report.warnings()), (3, 2));
+    }
+
+    #[test]
+    fn updating_a_card_keeps_its_settings_and_guards_playlists() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("musicbox.toml");
+        std::fs::write(
+            &path,
+            r#"music_dir = "/music"
+
+[cards]
+"0C 0D" = { track = "lullaby.mp3", name = "Lullaby" }
+"0e" = "old.mp3" # kitchen
+"0a" = ["a.mp3", "b.mp3"]
+"#,
+        )
+        .unwrap();
+        let card = |hex| CardUid::from_hex(hex).unwrap();
+
+        update_card_in_config(&path, &card("0c0d"), "waltz.mp3", false).unwrap();
+        update_card_in_config(&path, &card("0e"), "new.mp3", false).unwrap();
+        let contents = std::fs::read_to_string(&path).unwrap();
+        assert!(contents.contains(r#""0C 0D" = { track = "waltz.mp3", name = "Lullaby" }"#));
+        assert!(contents.contains(r#""0e" = "new.mp3" # kitchen"#));
+
+        assert!(matches!(
+            update_card_in_config(&path, &card("0a"), "c.mp3", false),
+            Err(ConfigEditError::NotSingleTrack(_))
+        ));
+        update_card_in_config(&path, &card("0a"), "c.mp3", true).unwrap();
+        let entries = list_cards(&path).unwrap();
+        assert_eq!(entries[0].tracks, vec!["c.mp3".to_string()]);
+        assert!(matches!(
+            update_card_in_config(&path, &card("ff"), "c.mp3", true),
+            Err(ConfigEditError::NotMapped(_))
+        ));
+    }
+
+    #[test]
+    fn placeholders_go_at_the_end_of_cards_once_each() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("musicbox.toml");
+        std::fs::write(
+            &path,
+            r#"music_dir = "/music" # where the SD card is mounted
+
+[cards]
+"0a" = "lullaby.mp3"
+
+# Bedtime cards.
+[groups.bedtime]
+color = "blue"
+"#,
+        )
+        .unwrap();
+        let tracks = |names: &[&str]| {
+            names
+                .iter()
+                .map(|name| name.to_string())
+                .collect::<Vec<_>>()
+        };
+
+        let added =
+            add_placeholders_to_config(&path, &tracks(&["new/01.mp3", "new/\"2\".mp3"])).unwrap();
+        assert_eq!(added.len(), 2);
+        let added =
+            add_placeholders_to_config(&path, &tracks(&["new/01.mp3", "new/03.mp3"])).unwrap();
+        assert_eq!(added, tracks(&["new/03.mp3"]));
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            r#"music_dir = "/music" # where the SD card is mounted
+
+[cards]
+"0a" = "lullaby.mp3"
+# "<uid>" = "new/01.mp3"
+# "<uid>" = "new/\"2\".mp3"
+# "<uid>" = "new/03.mp3"
+
+# Bedtime cards.
+[groups.bedtime]
+color = "blue"
+"#
+        );
+        assert_eq!(
+            list_placeholders(&path).unwrap(),
+            tracks(&["new/01.mp3", "new/\"2\".mp3", "new/03.mp3"])
+        );
+        assert_eq!(list_cards(&path).unwrap().len(), 1);
+
+        std::fs::write(&path, "music_dir = \"/music\"\n[cards]\n\"0a\" = \"a.mp3\"").unwrap();
+        add_placeholders_to_config(&path, &tracks(&["b.mp3"])).unwrap();
+        add_placeholders_to_config(&path, &tracks(&["c.mp3"])).unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            "music_dir = \"/music\"\n[cards]\n\"0a\" = \"a.mp3\"\n# \"<uid>\" = \"b.mp3\"\n# \"<uid>\" = \"c.mp3\"\n"
+        );
+    }
+
+    #[test]
+    fn tracks_outside_music_dir_are_flagged_unless_allowed() {
+        let toml = r#"
+music_dir = "/music"
+
+[cards]
+"0a" = "inside/song.mp3"
+"0b" = "../elsewhere/song.mp3"
+"0c" = "/srv/share/song.mp3"
+"0d" = "http://radio.example/stream"
+
+[sounds]
+goodnight = "/srv/share/goodnight.mp3"
+"#;
+        let config = MusicBoxConfig::from_reader(toml.as_bytes()).unwrap();
+        assert!(!config.allow_external_paths());
+        assert_eq!(
+            config.external_tracks(),
+            vec![
+                PathBuf::from("/music/../elsewhere/song.mp3"),
+                PathBuf::from("/srv/share/goodnight.mp3"),
+                PathBuf::from("/srv/share/song.mp3"),
+            ]
+        );
+        let outside = |report: &ValidationReport| {
+            report
+                .diagnostics
+                .iter()
+                .filter(|diagnostic| diagnostic.message.contains("outside music_dir"))
+                .count()
+        };
+        assert_eq!(outside(&config.validate()), 3);
+
+        let allowed = format!("allow_external_paths = true\n{toml}");
+        let config = MusicBoxConfig::from_reader(allowed.as_bytes()).unwrap();
+        assert_eq!(outside(&config.validate()), 0);
+        assert!(config.into_library().allows_external_paths());
     }
 }
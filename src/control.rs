@@ -0,0 +1,484 @@
+//! The control socket: a Unix domain socket through which CLI commands
+//! drive the running musicbox.
+//!
+//! Only the long-running player owns the controller and the sound card.
+//! Commands such as `musicbox ctl play` connect to its socket instead of
+//! building a second controller, which would fight the service over ALSA.
+//!
+//! A connection carries newline-delimited JSON: each [`ControlRequest`] line
+//! is answered by one [`ControlResponse`] line.
+
+use crate::app::reload_library;
+use crate::controller::{AudioPlayer, CardUid, CardUidParseError, MusicBoxController};
+use crate::events::EventBus;
+use serde::{Deserialize, Serialize};
+use std::io::{self, BufRead, BufReader, Write};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// How long the player waits on a client that connected but went quiet.
+const CLIENT_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Debug, thiserror::Error)]
+pub enum ControlError {
+    #[error("no musicbox instance is listening on {path:?}: {source}")]
+    Unreachable {
+        path: PathBuf,
+        #[source]
+        source: io::Error,
+    },
+    #[error("another musicbox instance is already listening on {0:?}")]
+    InUse(PathBuf),
+    #[error("control socket error: {0}")]
+    Io(#[from] io::Error),
+    #[error("unexpected control message: {0}")]
+    Decode(#[from] serde_json::Error),
+    #[error("running instance rejected the request: {0}")]
+    Rejected(String),
+}
+
+/// Where the player listens unless told otherwise: the user's runtime
+/// directory, or the temporary directory when there is none.
+pub fn default_socket_path() -> PathBuf {
+    std::env::var_os("XDG_RUNTIME_DIR")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .unwrap_or_else(std::env::temp_dir)
+        .join("musicbox.sock")
+}
+
+/// A command for the running player.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "command", rename_all = "kebab-case")]
+pub enum ControlRequest {
+    /// Handle a card, by hex UID or name, as if it had been tapped.
+    Play {
+        card: String,
+    },
+    Pause,
+    Status,
+    /// Re-read the config file and swap in its cards.
+    Reload,
+    /// List the playing card's queue.
+    Queue,
+    /// Drop an entry, by index, from the playing card's queue.
+    Dequeue {
+        index: usize,
+    },
+}
+
+/// The player's answer to a [`ControlRequest`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ControlResponse {
+    pub ok: bool,
+    /// What the request did, or why it failed.
+    pub message: String,
+    /// What the player is doing after handling the request.
+    #[serde(default)]
+    pub status: Option<ControlStatus>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ControlStatus {
+    /// Hex UID of the card playing, if any.
+    pub card: Option<String>,
+    pub name: Option<String>,
+    pub track: Option<String>,
+    pub volume: f32,
+    pub muted: bool,
+    /// Whether the player notes unknown cards.
+    #[serde(default)]
+    pub learning: bool,
+    /// Hex UIDs of unknown cards noted while learning, most recent first.
+    #[serde(default)]
+    pub learned: Vec<String>,
+    /// The playing card's tracks, folders expanded.
+    #[serde(default)]
+    pub queue: Vec<String>,
+    /// Index in `queue` of the track playing.
+    #[serde(default)]
+    pub queue_position: Option<usize>,
+}
+
+/// What the socket needs from the running player.
+pub struct ControlState<P: AudioPlayer + Send + 'static> {
+    pub controller: Arc<Mutex<MusicBoxController<P>>>,
+    pub config_path: PathBuf,
+    /// Where plays and pauses from clients are published.
+    pub events: EventBus,
+}
+
+/// A bound control socket, ready to [`serve`](Self::serve).
+#[derive(Debug)]
+pub struct ControlServer {
+    listener: UnixListener,
+}
+
+impl ControlServer {
+    /// Binds `path`, replacing a socket left behind by an instance that
+    /// did not shut down cleanly. Fails if another instance still answers
+    /// on it.
+    pub fn bind(path: &Path) -> Result<Self, ControlError> {
+        if path.exists() {
+            if UnixStream::connect(path).is_ok() {
+                return Err(ControlError::InUse(path.to_path_buf()));
+            }
+            std::fs::remove_file(path)?;
+        }
+        Ok(Self {
+            listener: UnixListener::bind(path)?,
+        })
+    }
+
+    /// Answers clients one at a time until the listener fails.
+    pub fn serve<P: AudioPlayer + Send + 'static>(
+        self,
+        state: ControlState<P>,
+    ) -> Result<(), ControlError> {
+        for stream in self.listener.incoming() {
+            let stream = stream?;
+            if let Err(err) = serve_client(&state, stream) {
+                tracing::warn!(%err, "control client failed");
+            }
+        }
+        Ok(())
+    }
+}
+
+fn serve_client<P: AudioPlayer + Send + 'static>(
+    state: &ControlState<P>,
+    stream: UnixStream,
+) -> Result<(), ControlError> {
+    stream.set_read_timeout(Some(CLIENT_TIMEOUT))?;
+    let mut writer = stream.try_clone()?;
+    for line in BufReader::new(stream).lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let response = match serde_json::from_str::<ControlRequest>(&line) {
+            Ok(request) => handle(state, request),
+            Err(err) => failure(format!("invalid request: {err}")),
+        };
+        serde_json::to_writer(&mut writer, &response)?;
+        writer.write_all(b"\n")?;
+    }
+    Ok(())
+}
+
+fn handle<P: AudioPlayer + Send + 'static>(
+    state: &ControlState<P>,
+    request: ControlRequest,
+) -> ControlResponse {
+    let message = match request {
+        ControlRequest::Play { card } => {
+            let uid = match find_card(state, &card) {
+                Ok(uid) => uid,
+                Err(err) => return failure(err.to_string()),
+            };
+            let played = state
+                .events
+                .apply_and_publish(&state.controller, |controller| controller.handle_card(&uid));
+            match played {
+                Ok(action) => format!("{action:?}"),
+                Err(err) => return failure(err.to_string()),
+            }
+        }
+        ControlRequest::Pause => {
+            let paused = state
+                .events
+                .apply_and_publish(&state.controller, |controller| controller.pause_playback());
+            match paused {
+                Ok(Some(action)) => format!("{action:?}"),
+                Ok(None) => "No active playback to pause".to_string(),
+                Err(err) => return failure(err.to_string()),
+            }
+        }
+        ControlRequest::Status | ControlRequest::Queue => "OK".to_string(),
+        ControlRequest::Reload => match reload_library(&state.controller, &state.config_path) {
+            Ok(()) => format!("Reloaded {}", state.config_path.display()),
+            Err(err) => return failure(err.to_string()),
+        },
+        ControlRequest::Dequeue { index } => {
+            let removed = state
+                .controller
+                .lock()
+                .expect("controller lock")
+                .remove_queued(index);
+            match removed {
+                Ok(track) => format!("Removed {} from the queue", track.path().display()),
+                Err(err) => return failure(err.to_string()),
+            }
+        }
+    };
+    ControlResponse {
+        ok: true,
+        message,
+        status: Some(status(&state.controller.lock().expect("controller lock"))),
+    }
+}
+
+/// Resolves `input` to a card by name or hex UID. Input that names no
+/// mapped card is searched for, so a title or file name plays the card
+/// that has it.
+fn find_card<P: AudioPlayer + Send + 'static>(
+    state: &ControlState<P>,
+    input: &str,
+) -> Result<CardUid, CardUidParseError> {
+    // The first search reads every file's tags; do not hold up playback.
+    let library = state
+        .controller
+        .lock()
+        .expect("controller lock")
+        .library()
+        .clone();
+    let resolved = library.resolve_card(input);
+    if matches!(&resolved, Ok(uid) if library.playlist(uid).is_some()) {
+        return resolved;
+    }
+    match library.search_index().best_card(input) {
+        Some(uid) => Ok(uid),
+        None => resolved,
+    }
+}
+
+fn status<P: AudioPlayer>(controller: &MusicBoxController<P>) -> ControlStatus {
+    let active = controller.active();
+    let (queue, queue_position) = controller.queue().unzip();
+    ControlStatus {
+        card: active.as_ref().map(|(card, _)| card.to_hex_lowercase()),
+        name: active
+            .as_ref()
+            .and_then(|(card, _)| controller.library().name(card))
+            .map(str::to_string),
+        track: active.map(|(_, track)| track.path().display().to_string()),
+        volume: controller.volume(),
+        muted: controller.is_muted(),
+        learning: controller.is_learning(),
+        learned: controller
+            .learned_cards()
+            .iter()
+            .map(CardUid::to_hex_lowercase)
+            .collect(),
+        queue: queue
+            .unwrap_or_default()
+            .iter()
+            .map(|track| track.path().display().to_string())
+            .collect(),
+        queue_position,
+    }
+}
+
+fn failure(message: String) -> ControlResponse {
+    ControlResponse {
+        ok: false,
+        message,
+        status: None,
+    }
+}
+
+/// Sends requests to the player listening on a control socket.
+#[derive(Debug, Clone)]
+pub struct ControlClient {
+    path: PathBuf,
+}
+
+impl ControlClient {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Sends `request` and waits for the answer. A request the player could
+    /// not carry out is returned as [`ControlError::Rejected`].
+    pub fn send(&self, request: &ControlRequest) -> Result<ControlResponse, ControlError> {
+        let mut stream =
+            UnixStream::connect(&self.path).map_err(|source| ControlError::Unreachable {
+                path: self.path.clone(),
+                source,
+            })?;
+        let mut line = serde_json::to_vec(request)?;
+        line.push(b'\n');
+        stream.write_all(&line)?;
+        let mut answer = String::new();
+        BufReader::new(stream).read_line(&mut answer)?;
+        let response: ControlResponse = serde_json::from_str(&answer)?;
+        if !response.ok {
+            return Err(ControlError::Rejected(response.message));
+        }
+        Ok(response)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::controller::{Library, PlayerError, Track};
+    use crate::events::EngineEvent;
+    use std::collections::HashMap;
+
+    struct SilentPlayer;
+
+    impl AudioPlayer for SilentPlayer {
+        fn play(&mut self, _track: &Track) -> Result<(), PlayerError> {
+            Ok(())
+        }
+
+        fn stop(&mut self) -> Result<(), PlayerError> {
+            Ok(())
+        }
+
+        fn pause(&mut self) -> Result<(), PlayerError> {
+            Ok(())
+        }
+
+        fn resume(&mut self) -> Result<(), PlayerError> {
+            Ok(())
+        }
+
+        fn is_finished(&self) -> bool {
+            false
+        }
+    }
+
+    #[test]
+    fn clients_drive_the_running_controller() {
+        let dir = tempfile::tempdir().unwrap();
+        let socket = dir.path().join("musicbox.sock");
+        let config_path = dir.path().join("musicbox.toml");
+        std::fs::write(
+            &config_path,
+            "music_dir = \"/music\"\n[cards]\n\"0b\" = \"other.mp3\"\n",
+        )
+        .unwrap();
+        let card = CardUid::new(vec![0x0a]);
+        let library = Library::new(HashMap::from([(
+            card.clone(),
+            Track::new(PathBuf::from("song.mp3")),
+        )]))
+        .with_names(HashMap::from([(card, "Lullaby".to_string())]));
+        let events = EventBus::new();
+        let published = events.subscribe();
+        let state = ControlState {
+            controller: Arc::new(Mutex::new(MusicBoxController::new(library, SilentPlayer))),
+            config_path,
+            events,
+        };
+        let server = ControlServer::bind(&socket).unwrap();
+        std::thread::spawn(move || server.serve(state));
+        let client = ControlClient::new(&socket);
+
+        let played = client
+            .send(&ControlRequest::Play {
+                card: "lullaby".into(),
+            })
+            .unwrap();
+        assert!(played.message.starts_with("Started"));
+        let status = played.status.unwrap();
+        assert_eq!(status.card.as_deref(), Some("0a"));
+        assert_eq!(status.name.as_deref(), Some("Lullaby"));
+        assert_eq!(status.track.as_deref(), Some("song.mp3"));
+
+        assert_eq!(status.queue, ["song.mp3"]);
+        assert_eq!(status.queue_position, Some(0));
+        assert!(matches!(
+            client.send(&ControlRequest::Dequeue { index: 0 }),
+            Err(ControlError::Rejected(message)) if message.contains("playing")
+        ));
+
+        let paused = client.send(&ControlRequest::Pause).unwrap();
+        assert_eq!(paused.status.unwrap().card, None);
+        let actions: Vec<_> = published
+            .try_iter()
+            .map(|published| match published.event {
+                EngineEvent::Action(action) => format!("{action:?}"),
+                other => panic!("unexpected event {other:?}"),
+            })
+            .collect();
+        assert_eq!(actions, [played.message, paused.message]);
+        let searched = client
+            .send(&ControlRequest::Play {
+                card: "Song".into(),
+            })
+            .unwrap();
+        assert_eq!(searched.status.unwrap().card.as_deref(), Some("0a"));
+        assert!(matches!(
+            client.send(&ControlRequest::Play { card: "ff".into() }),
+            Err(ControlError::Rejected(_))
+        ));
+        client.send(&ControlRequest::Reload).unwrap();
+        assert!(
+            client
+                .send(&ControlRequest::Play { card: "0b".into() })
+                .is_ok()
+        );
+
+        assert!(matches!(
+            ControlServer::bind(&socket),
+            Err(ControlError::InUse(_))
+        ));
+    }
+
+    #[test]
+    fn status_lists_the_cards_learned_most_recent_first() {
+        let dir = tempfile::tempdir().unwrap();
+        let socket = dir.path().join("musicbox.sock");
+        let mut controller = MusicBoxController::new(Library::new(HashMap::new()), SilentPlayer);
+        controller.set_learning(true);
+        for card in [[0x07], [0x08]] {
+            controller
+                .handle_tap(&CardUid::new(card.to_vec()), None)
+                .unwrap();
+        }
+        let state = ControlState {
+            controller: Arc::new(Mutex::new(controller)),
+            config_path: dir.path().join("musicbox.toml"),
+            events: EventBus::new(),
+        };
+        let server = ControlServer::bind(&socket).unwrap();
+        std::thread::spawn(move || server.serve(state));
+
+        let status = ControlClient::new(&socket)
+            .send(&ControlRequest::Status)
+            .unwrap()
+            .status
+            .unwrap();
+        assert!(status.learning);
+        assert_eq!(status.learned, ["08", "07"]);
+    }
+
+    #[test]
+    fn missing_players_are_unreachable_and_stale_sockets_are_replaced() {
+        let dir = tempfile::tempdir().unwrap();
+        let socket = dir.path().join("musicbox.sock");
+        assert!(matches!(
+            ControlClient::new(&socket).send(&ControlRequest::Status),
+            Err(ControlError::Unreachable { .. })
+        ));
+
+        drop(UnixListener::bind(&socket).unwrap());
+        assert!(socket.exists());
+        assert!(ControlServer::bind(&socket).is_ok());
+    }
+
+    #[test]
+    fn requests_are_tagged_by_command() {
+        assert_eq!(
+            serde_json::to_string(&ControlRequest::Play { card: "0a".into() }).unwrap(),
+            r#"{"command":"play","card":"0a"}"#
+        );
+        assert_eq!(
+            serde_json::from_str::<ControlRequest>(r#"{"command":"reload"}"#).unwrap(),
+            ControlRequest::Reload
+        );
+        assert_eq!(
+            serde_json::to_string(&ControlRequest::Dequeue { index: 2 }).unwrap(),
+            r#"{"command":"dequeue","index":2}"#
+        );
+    }
+}
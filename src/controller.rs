@@ -1,6 +1,17 @@
-use std::collections::HashMap;
+use crate::access::{ApiTokens, WebSettings};
+use crate::greeting::{GreetingPeriod, Greetings};
+use crate::input::ButtonEvent;
+use crate::led::Rgb;
+use crate::memo::{ARM_TIMEOUT, MemoSettings, RecordError, Recorder};
+use crate::night::{self, NightSettings};
+use crate::sandbox;
+use crate::search::SearchIndex;
+use crate::state::{ResumePoint, ResumeStore};
+use std::collections::{HashMap, HashSet};
 use std::fmt;
 use std::path::{Path, PathBuf};
+use std::sync::{Arc, OnceLock};
+use std::time::{Duration, Instant, SystemTime};
 
 /// The unique identifier of a card.
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
@@ -12,7 +23,7 @@ impl CardUid {
     }
 
     pub fn from_hex(hex: &str) -> Result<Self, CardUidParseError> {
-        if hex.len() % 2 != 0 {
+        if !hex.len().is_multiple_of(2) {
             return Err(CardUidParseError::OddLength);
         }
 
@@ -27,6 +38,30 @@ impl CardUid {
         Ok(Self(bytes))
     }
 
+    /// Parses a UID as people tend to write it: any case, with optional
+    /// whitespace or `:`/`-` separators between bytes (`AB CD`, `ab:cd`).
+    pub fn parse(input: &str) -> Result<Self, CardUidParseError> {
+        let compact: String = input
+            .chars()
+            .filter(|c| !c.is_whitespace() && *c != ':' && *c != '-')
+            .collect();
+        Self::from_hex(&compact)
+    }
+
+    /// Resolves user input to one of `names`' cards: a nickname
+    /// (case-insensitive) wins, otherwise the input is parsed as a hex UID.
+    pub fn resolve<'a>(
+        input: &str,
+        names: impl IntoIterator<Item = (&'a CardUid, &'a str)>,
+    ) -> Result<Self, CardUidParseError> {
+        let wanted = input.trim();
+        names
+            .into_iter()
+            .find(|(_, name)| name.eq_ignore_ascii_case(wanted))
+            .map(|(uid, _)| Ok(uid.clone()))
+            .unwrap_or_else(|| Self::parse(wanted))
+    }
+
     pub fn to_hex_lowercase(&self) -> String {
         let mut hex = String::with_capacity(self.0.len() * 2);
         for byte in &self.0 {
@@ -53,6 +88,39 @@ impl fmt::Display for CardUid {
     }
 }
 
+/// How to invent a UID for a card that cannot be scanned (e.g. `add` with the
+/// noop reader).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SyntheticUid {
+    /// Seven random bytes, the length of an NTAG / Ultralight UID.
+    #[default]
+    Random,
+    /// Sixteen ULID-style bytes: a 48-bit millisecond timestamp followed by 80
+    /// random bits, so UIDs sort by creation time.
+    Ulid,
+}
+
+impl CardUid {
+    /// Generates a fresh UID. Both strategies draw fresh random bits on every
+    /// call, so UIDs generated in a tight loop do not collide.
+    pub fn synthetic(strategy: SyntheticUid) -> Self {
+        match strategy {
+            SyntheticUid::Random => Self::new(random_u64().to_be_bytes()[..7].to_vec()),
+            SyntheticUid::Ulid => {
+                let millis = std::time::SystemTime::now()
+                    .duration_since(std::time::UNIX_EPOCH)
+                    .map(|elapsed| elapsed.as_millis() as u64)
+                    .unwrap_or(0);
+                let mut bytes = Vec::with_capacity(16);
+                bytes.extend_from_slice(&millis.to_be_bytes()[2..]);
+                bytes.extend_from_slice(&random_u64().to_be_bytes());
+                bytes.extend_from_slice(&random_u64().to_be_bytes()[..2]);
+                Self::new(bytes)
+            }
+        }
+    }
+}
+
 #[derive(Debug, thiserror::Error, PartialEq, Eq)]
 pub enum CardUidParseError {
     #[error("hex string must have an even number of characters")]
@@ -65,379 +133,4624 @@ pub enum CardUidParseError {
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Track {
     pub path: PathBuf,
+    gain: Gain,
+}
+
+/// How much louder or quieter a track plays than it was recorded, as a
+/// factor on its samples, so cards ripped from different sources sound
+/// alike. It stacks on top of the player's volume.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Gain(f32);
+
+// Gains are built only from finite factors, so they are never NaN.
+impl Eq for Gain {}
+
+impl Gain {
+    /// Plays tracks as recorded.
+    pub const UNITY: Gain = Gain(1.0);
+
+    /// A gain that multiplies samples by `factor`. `None` unless it is
+    /// finite and not negative.
+    pub fn from_factor(factor: f32) -> Option<Self> {
+        (factor.is_finite() && factor >= 0.0).then_some(Self(factor))
+    }
+
+    /// A gain of `db` decibels; negative values make tracks quieter.
+    pub fn from_db(db: f32) -> Option<Self> {
+        Self::from_factor(10f32.powf(db / 20.0))
+    }
+
+    pub fn factor(self) -> f32 {
+        self.0
+    }
+}
+
+impl Default for Gain {
+    fn default() -> Self {
+        Self::UNITY
+    }
+}
+
+/// Where a backend reads a track from.
+///
+/// Scheme entries like `subsonic:` and `podcast:` are resolved to files
+/// before they reach a backend, so only plain files and stream URLs remain.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TrackSource<'a> {
+    File(&'a Path),
+    Url(&'a str),
 }
 
 impl Track {
     pub fn new(path: PathBuf) -> Self {
-        Self { path }
+        Self {
+            path,
+            gain: Gain::UNITY,
+        }
+    }
+
+    /// Plays this track with `gain` applied.
+    pub fn with_gain(mut self, gain: Gain) -> Self {
+        self.gain = gain;
+        self
     }
 
     pub fn path(&self) -> &Path {
         &self.path
     }
-}
 
-/// Holds the mapping from card UIDs to tracks.
-#[derive(Debug, Default, Clone)]
-pub struct Library {
-    tracks: HashMap<CardUid, Track>,
-}
+    /// The card's loudness adjustment, which [`AudioPlayer::play`] applies.
+    pub fn gain(&self) -> Gain {
+        self.gain
+    }
 
-impl Library {
-    pub fn new(entries: HashMap<CardUid, Track>) -> Self {
-        Self { tracks: entries }
+    pub fn source(&self) -> TrackSource<'_> {
+        match self.path.to_str() {
+            Some(url) if crate::stream::is_stream_url(url) => TrackSource::Url(url),
+            _ => TrackSource::File(&self.path),
+        }
     }
 
-    pub fn lookup(&self, uid: &CardUid) -> Option<&Track> {
-        self.tracks.get(uid)
+    /// Whether this names a song on a server, a podcast feed, a Spotify URI,
+    /// or a network stream rather than a local file.
+    pub fn is_remote(&self) -> bool {
+        self.path.to_str().is_some_and(|path| {
+            path.starts_with(crate::subsonic::SCHEME)
+                || path.starts_with(crate::podcast::SCHEME)
+                || path.starts_with(crate::spotify::SCHEME)
+                || crate::stream::is_stream_url(path)
+        })
     }
 
-    pub fn entries(&self) -> Vec<(CardUid, Track)> {
-        self.tracks
-            .iter()
-            .map(|(uid, track)| (uid.clone(), track.clone()))
-            .collect()
+    /// Returns this track if its file exists, otherwise the closest sibling
+    /// with the same stem: first a case-insensitive spelling of the same
+    /// file, then one re-encoded with a [`FALLBACK_EXTENSIONS`] extension.
+    /// Falls back to `self` when nothing matches so the player can report the
+    /// missing file.
+    pub fn locate(&self) -> Track {
+        if self.path.exists() || self.is_remote() {
+            return self.clone();
+        }
+        match find_substitute(&self.path) {
+            Some(path) => {
+                tracing::info!(
+                    configured = %self.path.display(),
+                    substitute = %path.display(),
+                    "configured track missing; playing substitute"
+                );
+                Track::new(path).with_gain(self.gain)
+            }
+            None => self.clone(),
+        }
     }
 }
 
-#[derive(Debug, thiserror::Error)]
-pub enum ControllerError {
-    #[error("track not found for card")]
-    TrackNotFound,
-    #[error("audio player error: {0}")]
-    Audio(#[from] PlayerError),
+impl Track {
+    /// Expands a folder-backed entry into its audio files (not recursing),
+    /// sorted by name with digit runs compared numerically so `2.mp3` comes
+    /// before `10.mp3`. File entries resolve through [`Track::locate`].
+    pub fn expand(&self) -> Vec<Track> {
+        if !self.path.is_dir() {
+            return vec![self.locate()];
+        }
+        let mut files: Vec<PathBuf> = match std::fs::read_dir(&self.path) {
+            Ok(entries) => entries
+                .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+                .filter(|path| path.is_file() && crate::config::is_audio_file(path))
+                .collect(),
+            Err(err) => {
+                tracing::warn!(path = %self.path.display(), ?err, "failed to list track folder");
+                Vec::new()
+            }
+        };
+        files.sort_by(|a, b| natural_cmp(&a.to_string_lossy(), &b.to_string_lossy()));
+        files
+            .into_iter()
+            .map(|path| Track::new(path).with_gain(self.gain))
+            .collect()
+    }
 }
 
-#[derive(Debug, thiserror::Error)]
-pub enum PlayerError {
-    #[error("audio backend failure: {message}")]
-    Backend { message: String },
+/// Compares strings case-insensitively, treating runs of digits as numbers.
+fn natural_cmp(a: &str, b: &str) -> std::cmp::Ordering {
+    let mut a = a.chars().peekable();
+    let mut b = b.chars().peekable();
+    loop {
+        match (a.peek().copied(), b.peek().copied()) {
+            (None, None) => return std::cmp::Ordering::Equal,
+            (None, Some(_)) => return std::cmp::Ordering::Less,
+            (Some(_), None) => return std::cmp::Ordering::Greater,
+            (Some(x), Some(y)) if x.is_ascii_digit() && y.is_ascii_digit() => {
+                let take_number = |chars: &mut std::iter::Peekable<std::str::Chars<'_>>| {
+                    let mut digits = String::new();
+                    while let Some(c) = chars.peek().copied().filter(char::is_ascii_digit) {
+                        digits.push(c);
+                        chars.next();
+                    }
+                    digits
+                };
+                let (x, y) = (take_number(&mut a), take_number(&mut b));
+                let (x_trim, y_trim) = (x.trim_start_matches('0'), y.trim_start_matches('0'));
+                let ordering = x_trim
+                    .len()
+                    .cmp(&y_trim.len())
+                    .then_with(|| x_trim.cmp(y_trim))
+                    .then_with(|| x.len().cmp(&y.len()));
+                if ordering.is_ne() {
+                    return ordering;
+                }
+            }
+            (Some(x), Some(y)) => {
+                let ordering = x.to_lowercase().cmp(y.to_lowercase());
+                if ordering.is_ne() {
+                    return ordering;
+                }
+                a.next();
+                b.next();
+            }
+        }
+    }
 }
 
-/// An interface for audio playback.
-pub trait AudioPlayer {
-    fn play(&mut self, track: &Track) -> Result<(), PlayerError>;
-    fn stop(&mut self) -> Result<(), PlayerError>;
-    fn wait_until_done(&mut self) -> Result<(), PlayerError> {
-        Ok(())
-    }
+/// Extensions tried, in order, when a configured track has been re-encoded.
+pub const FALLBACK_EXTENSIONS: &[&str] = &["mp3", "ogg", "flac"];
+
+fn find_substitute(path: &Path) -> Option<PathBuf> {
+    let stem = path.file_stem()?.to_str()?;
+    let extension = path.extension().and_then(|ext| ext.to_str()).unwrap_or("");
+    let parent = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+
+    let candidates: Vec<PathBuf> = std::fs::read_dir(parent)
+        .ok()?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|candidate| {
+            candidate.is_file()
+                && candidate
+                    .file_stem()
+                    .and_then(|s| s.to_str())
+                    .is_some_and(|s| s.eq_ignore_ascii_case(stem))
+        })
+        .collect();
+
+    std::iter::once(extension)
+        .chain(FALLBACK_EXTENSIONS.iter().copied())
+        .find_map(|wanted| {
+            candidates
+                .iter()
+                .find(|candidate| {
+                    candidate
+                        .extension()
+                        .and_then(|ext| ext.to_str())
+                        .unwrap_or("")
+                        .eq_ignore_ascii_case(wanted)
+                })
+                .cloned()
+        })
 }
 
-/// Represents the actions that can be taken by the `MusicBoxController`.
-#[derive(Debug, Clone, PartialEq, Eq)]
-pub enum ControllerAction {
-    Started {
-        card: CardUid,
-        track: Track,
-    },
-    Stopped {
-        card: CardUid,
-        track: Track,
-    },
-    Switched {
-        from_card: CardUid,
-        from_track: Track,
-        to_card: CardUid,
-        to_track: Track,
-    },
+/// Short cues played around playback, outside any card mapping.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct SoundCues {
+    /// Played once on graceful shutdown before audio is torn down.
+    pub goodnight: Option<Track>,
+    /// Played when a countdown card's time is up, unless the card has its
+    /// own chime.
+    pub chime: Option<Track>,
+    /// Played once at boot when the startup action is
+    /// [`StartupAction::Chime`].
+    pub startup: Option<Track>,
 }
 
-struct ActiveTrack {
-    card: CardUid,
-    track: Track,
+/// Short sounds mixed over whatever plays, so listeners hear what the box
+/// is doing without a screen.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Effects {
+    /// Played once the box is ready for cards.
+    pub ready: Option<Track>,
+    /// Played when a card that is not in the library is tapped.
+    pub unknown_card: Option<Track>,
+    /// Played when shutdown begins, before playback stops.
+    pub shutdown: Option<Track>,
+    /// Played shortly before the sleep timer fades a card out.
+    pub sleep_warning: Option<Track>,
 }
 
-/// The main controller for the music box.
-pub struct MusicBoxController<P: AudioPlayer> {
-    library: Library,
-    player: P,
-    active: Option<ActiveTrack>,
+/// How the sleep timer ends a card that has played for its `max_play`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SleepTimer {
+    /// How long playback fades away before the time is up.
+    pub fade: Duration,
+    /// How long before the time is up [`Effects::sleep_warning`] plays.
+    pub warning: Duration,
 }
 
-impl<P: AudioPlayer> MusicBoxController<P> {
-    pub fn new(library: Library, player: P) -> Self {
+impl Default for SleepTimer {
+    fn default() -> Self {
         Self {
-            library,
-            player,
-            active: None,
+            fade: SLEEP_TIMER_FADE,
+            warning: SLEEP_TIMER_WARNING,
         }
     }
+}
 
-    pub fn wait_for_player(&mut self) -> Result<(), PlayerError> {
-        self.player.wait_until_done()
+/// What the box does once it is up, before the first tap.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum StartupAction {
+    /// Wait quietly for a card.
+    #[default]
+    Silent,
+    /// Play the startup cue from [`SoundCues::startup`].
+    Chime,
+    /// Pick up the resumable card that played last where it left off.
+    Resume,
+    /// Start the ambient playlist without waiting for it to be due.
+    Ambient,
+}
+
+/// A card that plays for a fixed time, such as two minutes of
+/// toothbrushing, looping its playlist until the time is up and then
+/// playing a chime.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Countdown {
+    pub duration: Duration,
+    /// Overrides [`SoundCues::chime`] for this card.
+    pub chime: Option<Track>,
+}
+
+/// Two cards that play their own tracks when tapped one after the other,
+/// in either order, such as an animal card and a language card.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Combo {
+    pub cards: [CardUid; 2],
+    pub tracks: Vec<Track>,
+}
+
+/// How soon the second card of a combo must follow the first.
+pub const DEFAULT_COMBO_WINDOW: Duration = Duration::from_secs(3);
+
+/// The idle policy: a quiet playlist started after a stretch of silence.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AmbientPolicy {
+    /// Tracks or folders, looped in order while nothing else plays.
+    pub tracks: Vec<Track>,
+    /// How long nothing must have played before the playlist starts.
+    pub idle_after: Duration,
+    /// Player volume while the playlist runs, from 0.0 to 1.0.
+    pub volume: f32,
+}
+
+/// What tapping the card that is already playing does.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum PlaybackMode {
+    /// Stop playback; the next tap starts the card from the beginning.
+    #[default]
+    StopOnRetap,
+    /// Pause playback; the next tap resumes from the same position.
+    PauseResume,
+    /// Play only while the card rests on the reader; lifting it stops
+    /// playback. Needs a reader that reports removals.
+    LiftToStop,
+}
+
+/// The order a card's playlist plays in.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum PlaylistMode {
+    /// Play the tracks once, in order.
+    #[default]
+    Sequential,
+    /// Play the tracks once, in a random order picked on each tap.
+    Shuffle,
+    /// Start over from the first track after the last one.
+    Repeat,
+    /// Play the current track over and over.
+    RepeatOne,
+    /// Play one track per tap, taking the next one in the list on each tap
+    /// and wrapping around, such as a word read out in two languages.
+    Alternate,
+}
+
+/// What happens once a card's playlist has played to its end.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum PlaylistEnd {
+    #[default]
+    Stop,
+    /// Start over from the first track.
+    Repeat,
+    /// Start over in a fresh random order.
+    ShuffleRepeat,
+    /// Go on to another card's playlist, as if it had been tapped.
+    Continue(CardUid),
+}
+
+/// What a system command card does when tapped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SystemCommand {
+    /// Mutes the box, or unmutes it if it is muted.
+    ToggleMute,
+    /// Switches night mode on, or off if it is on.
+    ToggleNightMode,
+    /// Raises the volume by one volume step.
+    VolumeUp,
+    /// Lowers the volume by one volume step.
+    VolumeDown,
+    /// Moves the playing card on to its next track.
+    Next,
+    /// Moves the playing card back a track, or to the start of this one.
+    Previous,
+    /// Pauses the playing card, or resumes it if it is paused.
+    PlayPause,
+    /// Stops playback and ends the run loop, as if the reader shut down.
+    Shutdown,
+    /// Re-reads the config file and swaps in its cards.
+    ReloadConfig,
+    /// The parent card: lifts the lock on the playing card, so buttons and
+    /// command cards can skip or stop it again.
+    Unlock,
+}
+
+impl SystemCommand {
+    /// Whether the command only works with an audio backend that has a
+    /// volume control.
+    pub fn needs_volume(self) -> bool {
+        matches!(
+            self,
+            SystemCommand::ToggleMute
+                | SystemCommand::ToggleNightMode
+                | SystemCommand::VolumeUp
+                | SystemCommand::VolumeDown
+        )
     }
+}
 
-    pub fn handle_card(&mut self, uid: &CardUid) -> Result<ControllerAction, ControllerError> {
-        if let Some(active) = &self.active {
-            if &active.card == uid {
-                self.player.stop()?;
-                let stopped = ControllerAction::Stopped {
-                    card: active.card.clone(),
-                    track: active.track.clone(),
-                };
-                self.active = None;
-                return Ok(stopped);
-            }
-        }
+/// What tapping a card does, as configured in the library.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CardBinding<'a> {
+    /// Plays the card's playlist.
+    Playlist(&'a [Track]),
+    /// Acts on the cards of a group.
+    Group(&'a GroupCommand),
+    /// Acts on the box itself.
+    System(SystemCommand),
+}
 
-        let track = self
-            .library
-            .lookup(uid)
-            .cloned()
-            .ok_or(ControllerError::TrackNotFound)?;
+/// What a group command card does to the cards in its group.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GroupAction {
+    /// Stop playback if the playing card belongs to the group.
+    Stop,
+    /// Play a random card from the group other than the one playing.
+    Shuffle,
+}
 
-        let action = if let Some(active) = self.active.take() {
-            self.player.stop()?;
-            self.player.play(&track)?;
-            let action = ControllerAction::Switched {
-                from_card: active.card.clone(),
-                from_track: active.track.clone(),
-                to_card: uid.clone(),
-                to_track: track.clone(),
-            };
-            self.active = Some(ActiveTrack {
-                card: uid.clone(),
-                track: track.clone(),
-            });
-            action
-        } else {
-            self.player.play(&track)?;
-            self.active = Some(ActiveTrack {
-                card: uid.clone(),
-                track: track.clone(),
-            });
-            ControllerAction::Started {
-                card: uid.clone(),
-                track: track.clone(),
-            }
-        };
+/// A card that controls a whole group instead of playing a track.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GroupCommand {
+    pub action: GroupAction,
+    pub group: String,
+}
 
-        Ok(action)
+/// How many cards a library maps and how many of their files are missing,
+/// for a status line such as "142 cards, 3 missing".
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LibraryStats {
+    /// Cards that play something or run a command.
+    pub cards: usize,
+    /// Local tracks and folders that are not there, with no copy under
+    /// another case or extension to play instead.
+    pub missing_tracks: usize,
+    /// When the cards were loaded from the config.
+    pub loaded_at: SystemTime,
+}
+
+/// Holds the mapping from card UIDs to playlists. A single-track card is a
+/// playlist of one.
+#[derive(Debug, Clone)]
+pub struct Library {
+    playlists: HashMap<CardUid, Vec<Track>>,
+    names: HashMap<CardUid, String>,
+    colors: HashMap<CardUid, Rgb>,
+    groups: HashMap<CardUid, String>,
+    commands: HashMap<CardUid, GroupCommand>,
+    system_commands: HashMap<CardUid, SystemCommand>,
+    resumable: HashSet<CardUid>,
+    /// Cards whose playback ignores skip and stop commands.
+    locked: HashSet<CardUid>,
+    expiries: HashMap<CardUid, SystemTime>,
+    modes: HashMap<CardUid, PlaylistMode>,
+    ends: HashMap<CardUid, PlaylistEnd>,
+    /// Cards that only answer taps on one labelled reader.
+    readers: HashMap<CardUid, String>,
+    /// Silence between tracks, for cards that want one.
+    gaps: HashMap<CardUid, Duration>,
+    countdowns: HashMap<CardUid, Countdown>,
+    /// How long any card plays before the sleep timer stops it.
+    max_play: Option<Duration>,
+    /// Cards with their own sleep timer; `None` exempts a card from
+    /// `max_play`.
+    max_plays: HashMap<CardUid, Option<Duration>>,
+    sleep_timer: SleepTimer,
+    sounds: SoundCues,
+    effects: Effects,
+    startup: StartupAction,
+    ambient: Option<AmbientPolicy>,
+    night: NightSettings,
+    greetings: Greetings,
+    music_dir: PathBuf,
+    /// Whether symlinks inside `music_dir` may lead out of it.
+    external_paths: bool,
+    playback_mode: PlaybackMode,
+    /// Reader events for the card last acted on are ignored for this long.
+    debounce: Duration,
+    volume_step: f32,
+    combos: Vec<Combo>,
+    combo_window: Duration,
+    api_tokens: ApiTokens,
+    web: WebSettings,
+    /// Built on first use and shared by clones, which see the same cards.
+    search: Arc<OnceLock<SearchIndex>>,
+    loaded_at: SystemTime,
+    /// Counted on first use and shared by clones, like `search`.
+    missing_tracks: Arc<OnceLock<usize>>,
+}
+
+/// How much one press of a volume button changes the volume.
+pub const DEFAULT_VOLUME_STEP: f32 = 0.05;
+
+impl Default for Library {
+    fn default() -> Self {
+        Self::from_playlists(HashMap::new())
     }
+}
 
-    pub fn pause_playback(&mut self) -> Result<Option<ControllerAction>, ControllerError> {
-        if let Some(active) = &self.active {
-            let action = ControllerAction::Stopped {
-                card: active.card.clone(),
-                track: active.track.clone(),
-            };
-            self.player.stop()?;
-            self.active = None;
-            Ok(Some(action))
-        } else {
-            Ok(None)
+impl Library {
+    pub fn new(entries: HashMap<CardUid, Track>) -> Self {
+        Self::from_playlists(
+            entries
+                .into_iter()
+                .map(|(uid, track)| (uid, vec![track]))
+                .collect(),
+        )
+    }
+
+    /// Builds a library where each card plays an ordered list of tracks.
+    /// Empty playlists are dropped.
+    pub fn from_playlists(mut playlists: HashMap<CardUid, Vec<Track>>) -> Self {
+        playlists.retain(|_, tracks| !tracks.is_empty());
+        Self {
+            playlists,
+            names: HashMap::new(),
+            colors: HashMap::new(),
+            groups: HashMap::new(),
+            commands: HashMap::new(),
+            system_commands: HashMap::new(),
+            resumable: HashSet::new(),
+            locked: HashSet::new(),
+            expiries: HashMap::new(),
+            modes: HashMap::new(),
+            ends: HashMap::new(),
+            readers: HashMap::new(),
+            gaps: HashMap::new(),
+            countdowns: HashMap::new(),
+            max_play: None,
+            max_plays: HashMap::new(),
+            sleep_timer: SleepTimer::default(),
+            api_tokens: ApiTokens::default(),
+            web: WebSettings::default(),
+            sounds: SoundCues::default(),
+            effects: Effects::default(),
+            startup: StartupAction::default(),
+            ambient: None,
+            night: NightSettings::default(),
+            greetings: Greetings::default(),
+            music_dir: PathBuf::new(),
+            external_paths: false,
+            playback_mode: PlaybackMode::default(),
+            debounce: Duration::ZERO,
+            volume_step: DEFAULT_VOLUME_STEP,
+            combos: Vec::new(),
+            combo_window: DEFAULT_COMBO_WINDOW,
+            search: Arc::default(),
+            loaded_at: SystemTime::now(),
+            missing_tracks: Arc::default(),
         }
     }
 
-    pub fn active(&self) -> Option<(CardUid, Track)> {
-        self.active
-            .as_ref()
-            .map(|current| (current.card.clone(), current.track.clone()))
+    pub fn with_sounds(mut self, sounds: SoundCues) -> Self {
+        self.sounds = sounds;
+        self
     }
 
-    pub fn replace_library(&mut self, library: Library) {
-        self.library = library;
+    pub fn sounds(&self) -> &SoundCues {
+        &self.sounds
     }
 
-    pub fn library_entries(&self) -> Vec<(CardUid, Track)> {
-        self.library.entries()
+    pub fn with_effects(mut self, effects: Effects) -> Self {
+        self.effects = effects;
+        self
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use std::cell::RefCell;
-    use std::rc::Rc;
+    pub fn effects(&self) -> &Effects {
+        &self.effects
+    }
 
-    #[derive(Debug, Clone, PartialEq, Eq)]
-    enum Call {
-        Play(PathBuf),
-        Stop,
+    /// Sets what [`MusicBoxController::start_up`] does.
+    pub fn with_startup(mut self, startup: StartupAction) -> Self {
+        self.startup = startup;
+        self
     }
 
-    #[derive(Clone)]
-    struct MockPlayer {
-        calls: Rc<RefCell<Vec<Call>>>,
+    pub fn startup(&self) -> StartupAction {
+        self.startup
     }
 
-    impl MockPlayer {
-        fn new() -> Self {
-            Self {
-                calls: Rc::new(RefCell::new(Vec::new())),
-            }
-        }
+    /// Sets night mode's volume cap and schedule.
+    pub fn with_night(mut self, night: NightSettings) -> Self {
+        self.night = night;
+        self
+    }
 
-        fn calls(&self) -> Vec<Call> {
-            self.calls.borrow().clone()
-        }
+    pub fn night(&self) -> &NightSettings {
+        &self.night
     }
 
-    impl AudioPlayer for MockPlayer {
-        fn play(&mut self, track: &Track) -> Result<(), PlayerError> {
-            self.calls.borrow_mut().push(Call::Play(track.path.clone()));
-            Ok(())
-        }
+    /// Sets the clips played before the first card of the morning and of
+    /// the evening.
+    pub fn with_greetings(mut self, greetings: Greetings) -> Self {
+        self.greetings = greetings;
+        self
+    }
 
-        fn stop(&mut self) -> Result<(), PlayerError> {
-            self.calls.borrow_mut().push(Call::Stop);
-            Ok(())
-        }
+    pub fn greetings(&self) -> &Greetings {
+        &self.greetings
     }
 
-    fn library_with(entries: Vec<(CardUid, &str)>) -> Library {
-        let map = entries
-            .into_iter()
-            .map(|(uid, path)| (uid, Track::new(PathBuf::from(path))))
-            .collect();
-        Library::new(map)
+    pub fn with_ambient(mut self, ambient: Option<AmbientPolicy>) -> Self {
+        self.ambient = ambient;
+        self
     }
 
-    fn uid(bytes: &[u8]) -> CardUid {
-        CardUid::new(bytes.to_vec())
+    pub fn ambient(&self) -> Option<&AmbientPolicy> {
+        self.ambient.as_ref()
     }
 
-    #[test]
-    fn card_uid_formats_to_lower_hex() {
-        let uid = uid(&[0x0a, 0x1b, 0xff]);
-        assert_eq!(uid.to_hex_lowercase(), "0a1bff");
-        assert_eq!(uid.to_string(), "0a1bff");
+    pub fn with_playback_mode(mut self, mode: PlaybackMode) -> Self {
+        self.playback_mode = mode;
+        self
     }
 
-    #[test]
-    fn card_uid_from_hex_parses_bytes() {
-        let parsed = CardUid::from_hex("0a0b0c0d").unwrap();
-        assert_eq!(parsed, uid(&[0x0a, 0x0b, 0x0c, 0x0d]));
+    pub fn playback_mode(&self) -> PlaybackMode {
+        self.playback_mode
     }
 
-    #[test]
-    fn card_uid_from_hex_rejects_odd_length() {
-        let err = CardUid::from_hex("abc").unwrap_err();
-        assert_eq!(err, CardUidParseError::OddLength);
+    /// Sets how long after acting on a card further events for it are
+    /// ignored, so a flickering reader or an eager tapper does not toggle
+    /// playback. Zero turns debouncing off.
+    pub fn with_debounce(mut self, window: Duration) -> Self {
+        self.debounce = window;
+        self
     }
 
-    #[test]
-    fn card_uid_from_hex_rejects_invalid_chars() {
-        let err = CardUid::from_hex("zz").unwrap_err();
-        assert_eq!(err, CardUidParseError::InvalidHex('z'));
+    pub fn debounce(&self) -> Duration {
+        self.debounce
+    }
+
+    pub fn with_combos(mut self, combos: Vec<Combo>) -> Self {
+        self.combos = combos;
+        self
+    }
+
+    pub fn with_combo_window(mut self, window: Duration) -> Self {
+        self.combo_window = window;
+        self
+    }
+
+    pub fn combo_window(&self) -> Duration {
+        self.combo_window
+    }
+
+    /// The combo made of `first` and `second`, in either order.
+    pub fn combo(&self, first: &CardUid, second: &CardUid) -> Option<&Combo> {
+        self.combos.iter().find(|combo| {
+            let [a, b] = &combo.cards;
+            (a == first && b == second) || (a == second && b == first)
+        })
+    }
+
+    /// Whether `uid` is one of the cards of any combo.
+    pub fn in_combo(&self, uid: &CardUid) -> bool {
+        self.combos.iter().any(|combo| combo.cards.contains(uid))
+    }
+
+    pub fn with_volume_step(mut self, step: f32) -> Self {
+        self.volume_step = step;
+        self
+    }
+
+    pub fn volume_step(&self) -> f32 {
+        self.volume_step
+    }
+
+    /// Sets the directory that tracks named on NDEF tags are resolved against.
+    pub fn with_music_dir(mut self, music_dir: PathBuf) -> Self {
+        self.music_dir = music_dir;
+        self
+    }
+
+    pub fn music_dir(&self) -> &Path {
+        &self.music_dir
+    }
+
+    /// Lets symlinks inside the music directory lead out of it, for music
+    /// kept on another drive.
+    pub fn with_external_paths(mut self, allowed: bool) -> Self {
+        self.external_paths = allowed;
+        self
+    }
+
+    pub fn allows_external_paths(&self) -> bool {
+        self.external_paths
+    }
+
+    /// Resolves a relative track path from an untrusted source (an NDEF tag,
+    /// another box). Only paths that stay inside the music directory resolve.
+    pub fn confined_track(&self, text: &str) -> Option<Track> {
+        sandbox::confine(&self.music_dir, text, self.external_paths).map(Track::new)
+    }
+
+    /// Attaches human-friendly card nicknames.
+    pub fn with_names(mut self, names: HashMap<CardUid, String>) -> Self {
+        self.names = names;
+        self
+    }
+
+    /// The first track of the card's playlist.
+    pub fn lookup(&self, uid: &CardUid) -> Option<&Track> {
+        self.playlist(uid).and_then(<[Track]>::first)
+    }
+
+    pub fn playlist(&self, uid: &CardUid) -> Option<&[Track]> {
+        self.playlists.get(uid).map(Vec::as_slice)
+    }
+
+    /// Maps `uid` to `tracks`, replacing whatever it played before.
+    pub fn set_playlist(&mut self, uid: CardUid, tracks: Vec<Track>) {
+        if !tracks.is_empty() {
+            self.playlists.insert(uid, tracks);
+        }
+    }
+
+    /// Attaches per-card LED color hints.
+    pub fn with_colors(mut self, colors: HashMap<CardUid, Rgb>) -> Self {
+        self.colors = colors;
+        self
+    }
+
+    pub fn color(&self, uid: &CardUid) -> Option<Rgb> {
+        self.colors.get(uid).copied()
+    }
+
+    /// Attaches group membership for track cards.
+    pub fn with_groups(mut self, groups: HashMap<CardUid, String>) -> Self {
+        self.groups = groups;
+        self
+    }
+
+    /// Attaches group command cards.
+    pub fn with_commands(mut self, commands: HashMap<CardUid, GroupCommand>) -> Self {
+        self.commands = commands;
+        self
+    }
+
+    /// Marks the cards whose playback position is remembered between taps.
+    pub fn with_resumable(mut self, resumable: HashSet<CardUid>) -> Self {
+        self.resumable = resumable;
+        self
+    }
+
+    pub fn resumes(&self, uid: &CardUid) -> bool {
+        self.resumable.contains(uid)
+    }
+
+    /// Marks the cards that cannot be skipped through or stopped by
+    /// buttons and command cards while they play.
+    pub fn with_locked(mut self, locked: HashSet<CardUid>) -> Self {
+        self.locked = locked;
+        self
+    }
+
+    pub fn is_locked(&self, uid: &CardUid) -> bool {
+        self.locked.contains(uid)
+    }
+
+    /// Attaches expiry times of guest cards; they stop playing afterwards.
+    pub fn with_expiries(mut self, expiries: HashMap<CardUid, SystemTime>) -> Self {
+        self.expiries = expiries;
+        self
+    }
+
+    pub fn expires_at(&self, uid: &CardUid) -> Option<SystemTime> {
+        self.expiries.get(uid).copied()
+    }
+
+    pub fn is_expired(&self, uid: &CardUid, now: SystemTime) -> bool {
+        self.expires_at(uid).is_some_and(|expiry| expiry <= now)
+    }
+
+    /// Sets the playlist order of cards that do not play sequentially.
+    pub fn with_modes(mut self, modes: HashMap<CardUid, PlaylistMode>) -> Self {
+        self.modes = modes;
+        self
+    }
+
+    pub fn mode(&self, uid: &CardUid) -> PlaylistMode {
+        self.modes.get(uid).copied().unwrap_or_default()
+    }
+
+    /// Sets what cards do after their last track, for those that do not
+    /// simply stop.
+    pub fn with_ends(mut self, ends: HashMap<CardUid, PlaylistEnd>) -> Self {
+        self.ends = ends;
+        self
+    }
+
+    pub fn end(&self, uid: &CardUid) -> PlaylistEnd {
+        self.ends.get(uid).cloned().unwrap_or_default()
+    }
+
+    /// Sets the silence played between tracks, such as audiobook chapters.
+    pub fn with_gaps(mut self, gaps: HashMap<CardUid, Duration>) -> Self {
+        self.gaps = gaps;
+        self
+    }
+
+    pub fn gap(&self, uid: &CardUid) -> Duration {
+        self.gaps.get(uid).copied().unwrap_or_default()
+    }
+
+    /// Stops any card after it has played for `limit`, fading it out.
+    pub fn with_max_play(mut self, limit: Option<Duration>) -> Self {
+        self.max_play = limit;
+        self
+    }
+
+    /// Gives cards their own sleep timer in place of the one from
+    /// [`Self::with_max_play`]; `None` lets a card play on.
+    pub fn with_max_plays(mut self, limits: HashMap<CardUid, Option<Duration>>) -> Self {
+        self.max_plays = limits;
+        self
+    }
+
+    /// How long `uid` plays before the sleep timer stops it, if at all.
+    pub fn max_play(&self, uid: &CardUid) -> Option<Duration> {
+        self.max_plays.get(uid).copied().unwrap_or(self.max_play)
+    }
+
+    /// Sets how the sleep timer warns and fades out a card.
+    pub fn with_sleep_timer(mut self, sleep_timer: SleepTimer) -> Self {
+        self.sleep_timer = sleep_timer;
+        self
+    }
+
+    /// How long before the sleep timer stops a card its warning plays, and
+    /// how long the fade that follows takes.
+    pub fn sleep_timer(&self) -> SleepTimer {
+        self.sleep_timer
+    }
+
+    /// Requires one of `tokens` on web API requests.
+    pub fn with_api_tokens(mut self, tokens: ApiTokens) -> Self {
+        self.api_tokens = tokens;
+        self
+    }
+
+    /// The tokens the web API accepts; empty leaves it open.
+    pub fn api_tokens(&self) -> &ApiTokens {
+        &self.api_tokens
+    }
+
+    pub fn with_web_settings(mut self, web: WebSettings) -> Self {
+        self.web = web;
+        self
+    }
+
+    /// The `[web]` table, such as whether the web API is read-only.
+    pub fn web_settings(&self) -> WebSettings {
+        self.web
+    }
+
+    /// Turns cards into countdowns that play for a fixed time.
+    pub fn with_countdowns(mut self, countdowns: HashMap<CardUid, Countdown>) -> Self {
+        self.countdowns = countdowns;
+        self
+    }
+
+    pub fn countdown(&self, uid: &CardUid) -> Option<&Countdown> {
+        self.countdowns.get(uid)
+    }
+
+    /// Ties cards to the reader label they must be tapped on.
+    pub fn with_readers(mut self, readers: HashMap<CardUid, String>) -> Self {
+        self.readers = readers;
+        self
+    }
+
+    pub fn reader(&self, uid: &CardUid) -> Option<&str> {
+        self.readers.get(uid).map(String::as_str)
+    }
+
+    pub fn group(&self, uid: &CardUid) -> Option<&str> {
+        self.groups.get(uid).map(String::as_str)
+    }
+
+    /// Track cards in `group`, sorted by UID for a stable order.
+    pub fn group_members(&self, group: &str) -> Vec<CardUid> {
+        let mut members: Vec<CardUid> = self
+            .groups
+            .iter()
+            .filter(|(_, name)| name.as_str() == group)
+            .map(|(uid, _)| uid.clone())
+            .collect();
+        members.sort_by(|a, b| a.as_bytes().cmp(b.as_bytes()));
+        members
+    }
+
+    /// Attaches system command cards, such as mute.
+    pub fn with_system_commands(mut self, commands: HashMap<CardUid, SystemCommand>) -> Self {
+        self.system_commands = commands;
+        self
+    }
+
+    pub fn system_command(&self, uid: &CardUid) -> Option<SystemCommand> {
+        self.system_commands.get(uid).copied()
+    }
+
+    /// What tapping `uid` does, or `None` for cards the library does not
+    /// know.
+    pub fn binding(&self, uid: &CardUid) -> Option<CardBinding<'_>> {
+        if let Some(command) = self.system_command(uid) {
+            return Some(CardBinding::System(command));
+        }
+        if let Some(command) = self.command(uid) {
+            return Some(CardBinding::Group(command));
+        }
+        self.playlist(uid).map(CardBinding::Playlist)
+    }
+
+    pub fn command(&self, uid: &CardUid) -> Option<&GroupCommand> {
+        self.commands.get(uid)
+    }
+
+    pub fn name(&self, uid: &CardUid) -> Option<&str> {
+        self.names.get(uid).map(String::as_str)
+    }
+
+    /// Resolves user input to a card: a nickname (case-insensitive) wins,
+    /// otherwise the input is parsed as a hex UID.
+    pub fn resolve_card(&self, input: &str) -> Result<CardUid, CardUidParseError> {
+        CardUid::resolve(
+            input,
+            self.names.iter().map(|(uid, name)| (uid, name.as_str())),
+        )
+    }
+
+    /// The cards and the files under `music_dir`, indexed for search. The
+    /// first call reads every file's tags, so call it from a clone outside
+    /// the controller lock.
+    pub fn search_index(&self) -> &SearchIndex {
+        self.search.get_or_init(|| SearchIndex::build(self))
+    }
+
+    /// The number of cards and missing files, and when the cards loaded.
+    /// The first call looks for every track, so later files added or
+    /// removed only count once the cards reload.
+    pub fn stats(&self) -> LibraryStats {
+        let cards: HashSet<&CardUid> = self
+            .playlists
+            .keys()
+            .chain(self.commands.keys())
+            .chain(self.system_commands.keys())
+            .collect();
+        let missing_tracks = *self.missing_tracks.get_or_init(|| {
+            let tracks: HashSet<&Path> = self
+                .playlists
+                .values()
+                .flatten()
+                .filter(|track| !track.is_remote())
+                .map(Track::path)
+                .collect();
+            tracks
+                .into_iter()
+                .filter(|path| !path.exists() && find_substitute(path).is_none())
+                .count()
+        });
+        LibraryStats {
+            cards: cards.len(),
+            missing_tracks,
+            loaded_at: self.loaded_at,
+        }
+    }
+
+    /// Each card with the first track of its playlist.
+    pub fn entries(&self) -> Vec<(CardUid, Track)> {
+        self.playlists
+            .iter()
+            .map(|(uid, tracks)| (uid.clone(), tracks[0].clone()))
+            .collect()
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ControllerError {
+    #[error("track not found for card")]
+    TrackNotFound,
+    #[error("card {0} has expired")]
+    CardExpired(CardUid),
+    #[error("card {card} belongs to reader {expected}, not {reader}")]
+    WrongReader {
+        card: CardUid,
+        expected: String,
+        reader: String,
+    },
+    #[error("audio player error: {0}")]
+    Audio(#[from] PlayerError),
+    #[error("the audio backend does not support {0}")]
+    Unsupported(&'static str),
+    #[error("nothing is queued at position {0}")]
+    NotQueued(usize),
+    #[error("queue position {0} is the track playing; skip it instead")]
+    QueuedTrackPlaying(usize),
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum PlayerError {
+    #[error("audio backend failure: {message}")]
+    Backend { message: String },
+}
+
+/// What an audio backend supports beyond playing and stopping tracks, so
+/// callers can skip a feature instead of having it fail mid-playback. The
+/// default claims nothing, matching the trait's default methods.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PlayerCapabilities {
+    /// [`AudioPlayer::position`] and [`AudioPlayer::seek`] work.
+    pub seek: bool,
+    /// [`AudioPlayer::set_volume`] and [`AudioPlayer::set_muted`] work.
+    pub volume: bool,
+    /// [`AudioPlayer::is_finished`] reports track ends.
+    pub track_end: bool,
+    /// `http://` tracks can be played.
+    pub streams: bool,
+}
+
+/// An interface for audio playback.
+pub trait AudioPlayer {
+    fn capabilities(&self) -> PlayerCapabilities {
+        PlayerCapabilities::default()
+    }
+    /// Starts `track` in place of whatever plays, scaled by its
+    /// [`Track::gain`] on top of the volume. Backends without a volume
+    /// control ignore the gain.
+    fn play(&mut self, track: &Track) -> Result<(), PlayerError>;
+    fn stop(&mut self) -> Result<(), PlayerError>;
+    /// Holds the current track at its position until [`AudioPlayer::resume`].
+    fn pause(&mut self) -> Result<(), PlayerError>;
+    fn resume(&mut self) -> Result<(), PlayerError>;
+    fn wait_until_done(&mut self) -> Result<(), PlayerError> {
+        Ok(())
+    }
+    /// Whether the last track played to its end. Backends that cannot tell
+    /// keep the default, which means playlists never auto-advance.
+    fn is_finished(&self) -> bool {
+        false
+    }
+    /// Sets the playback volume, from 0.0 (silent) to 1.0 (full). It applies
+    /// to everything played afterwards until changed again.
+    fn set_volume(&mut self, _volume: f32) -> Result<(), PlayerError> {
+        Ok(())
+    }
+    /// The volume last set, from 0.0 to 1.0.
+    fn volume(&self) -> f32 {
+        1.0
+    }
+    /// Silences output without changing the volume or what plays; unmuting
+    /// restores the volume. Backends without a volume control keep the
+    /// default, which ignores it.
+    fn set_muted(&mut self, _muted: bool) -> Result<(), PlayerError> {
+        Ok(())
+    }
+    /// Switches the backend's night-mode compressor. Backends without one
+    /// keep the default, and night mode then only caps the volume.
+    fn set_night_mode(&mut self, _on: bool) -> Result<(), PlayerError> {
+        Ok(())
+    }
+    /// How far into the current track playback is. Backends that cannot
+    /// tell return `None`, and resumable cards then only remember the track.
+    fn position(&self) -> Option<Duration> {
+        None
+    }
+    /// Jumps to `offset` within the current track. Backends that cannot seek
+    /// keep the default, which plays the track from its beginning.
+    fn seek(&mut self, _offset: Duration) -> Result<(), PlayerError> {
+        Ok(())
+    }
+    /// When the track last started with [`AudioPlayer::play`] first handed
+    /// audio to the output. Backends that cannot tell return `None`, and
+    /// tap latency then stops at the controller's decision.
+    fn first_sound_at(&self) -> Option<Instant> {
+        None
+    }
+
+    /// Lowers the volume of what plays to silence over `over`, then stops
+    /// it, without waiting for the fade. Backends that cannot fade keep
+    /// the default, which stops at once.
+    fn fade_out(&mut self, _over: Duration) -> Result<(), PlayerError> {
+        self.stop()
+    }
+    /// Plays a short sound effect over whatever plays, without interrupting
+    /// it. Backends that cannot mix a second sound keep the default, which
+    /// skips it.
+    fn play_effect(&mut self, _effect: &Track) -> Result<(), PlayerError> {
+        Ok(())
+    }
+    /// Reopens the output if its device appeared, went away, or changed
+    /// since the last call, and says what changed. Backends tied to one
+    /// device for good keep the default, which never changes anything.
+    fn refresh_output(&mut self) -> Option<OutputChange> {
+        None
+    }
+}
+
+/// How a player's output device changed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum OutputChange {
+    /// Playback now goes to the device with this name.
+    Opened(String),
+    /// The device went away; playback carries on silently until it returns.
+    Lost,
+    /// The device was closed after sitting idle and opens again on the
+    /// next play.
+    Released,
+}
+
+/// Represents the actions that can be taken by the `MusicBoxController`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ControllerAction {
+    Started {
+        card: CardUid,
+        track: Track,
+    },
+    Stopped {
+        card: CardUid,
+        track: Track,
+    },
+    Switched {
+        from_card: CardUid,
+        from_track: Track,
+        to_card: CardUid,
+        to_track: Track,
+    },
+    /// The playing card was tapped again in [`PlaybackMode::PauseResume`].
+    Paused {
+        card: CardUid,
+        track: Track,
+    },
+    /// The paused card was tapped again and picked up where it left off.
+    Resumed {
+        card: CardUid,
+        track: Track,
+    },
+    /// The card's playlist moved on to its next track by itself.
+    Advanced {
+        card: CardUid,
+        track: Track,
+    },
+    /// A button moved the card's playlist to another track.
+    Skipped {
+        card: CardUid,
+        track: Track,
+    },
+    /// A command card was tapped but had nothing to act on.
+    Unchanged {
+        card: CardUid,
+    },
+    /// The record card was tapped; the next card tapped gets a voice memo.
+    RecordArmed {
+        card: CardUid,
+    },
+    /// A voice memo for `card` started recording.
+    Recording {
+        card: CardUid,
+    },
+    /// A voice memo was saved and `card` now plays it.
+    MemoRecorded {
+        card: CardUid,
+        track: Track,
+    },
+    /// The box was muted or unmuted; playback carries on either way.
+    MuteToggled {
+        muted: bool,
+    },
+    /// Night mode was switched on or off.
+    NightModeToggled {
+        on: bool,
+    },
+    /// A volume card changed the volume, now `percent` of full.
+    VolumeChanged {
+        percent: u8,
+    },
+    /// The shutdown card was tapped; the run loop stops after reporting it.
+    ShutdownRequested {
+        card: CardUid,
+    },
+    /// The reload card was tapped; the run loop re-reads the config.
+    ReloadRequested {
+        card: CardUid,
+    },
+    /// The parent card lifted the lock on the playing `card`.
+    Unlocked {
+        card: CardUid,
+    },
+    /// A countdown card's time ran out; playback stopped and the chime
+    /// played.
+    CountdownFinished {
+        card: CardUid,
+        track: Track,
+    },
+    /// The sleep timer ran out; the playing card faded out and stopped.
+    TimedOut {
+        card: CardUid,
+        track: Track,
+    },
+    /// A card that is not in the library was tapped; playback carries on.
+    UnknownCard {
+        card: CardUid,
+    },
+    /// An unknown card was tapped in learning mode and noted for
+    /// `tag add --learned`; playback carries on.
+    CardLearned {
+        card: CardUid,
+    },
+    /// The audio output device came, went, or changed, and playback moved
+    /// along with it.
+    OutputChanged {
+        change: OutputChange,
+    },
+}
+
+struct ActiveTrack {
+    card: CardUid,
+    track: Track,
+    /// The card's playlist with folders expanded, captured when it started.
+    queue: Vec<Track>,
+    /// Index of `track` within `queue`.
+    position: usize,
+    paused: bool,
+    /// When the silence between two tracks ends, once the card's gap began.
+    gap_until: Option<Instant>,
+    /// When a countdown card's time is up.
+    ends_at: Option<Instant>,
+    /// When the sleep timer stops the card.
+    stops_at: Option<Instant>,
+    /// Whether the sleep timer's warning has played.
+    warned: bool,
+    /// Whether skip and stop commands are ignored, until the parent card
+    /// unlocks it.
+    locked: bool,
+    /// While a greeting plays ahead of `track`, the offset `track` starts
+    /// at once it is over.
+    greeting: Option<Duration>,
+    /// The cards that played to their end and went on into this one since
+    /// the last tap, oldest first.
+    chain: Vec<CardUid>,
+}
+
+struct AmbientPlayback {
+    queue: Vec<Track>,
+    position: usize,
+}
+
+/// Voice memo recording, set up by [`MusicBoxController::with_recorder`].
+struct MemoRecording {
+    recorder: Box<dyn Recorder>,
+    settings: MemoSettings,
+    state: MemoState,
+}
+
+enum MemoState {
+    Idle,
+    /// The record card was tapped; waiting for the card to record onto.
+    Armed {
+        since: Instant,
+    },
+    Recording {
+        card: CardUid,
+        since: Instant,
+    },
+}
+
+/// How often [`MusicBoxController::tick`] bookmarks a resumable card while
+/// it plays, bounding what a power cut loses.
+const RESUME_SAVE_INTERVAL: Duration = Duration::from_secs(10);
+
+/// How often [`MusicBoxController::poll_output`] looks for the audio
+/// device coming or going.
+pub const OUTPUT_CHECK_INTERVAL: Duration = Duration::from_secs(3);
+
+/// How many learned cards [`MusicBoxController::learned_cards`] keeps.
+const LEARNED_LIMIT: usize = 20;
+
+/// How long playback takes to fade away when the sleep timer runs out,
+/// unless the library says otherwise.
+pub const SLEEP_TIMER_FADE: Duration = Duration::from_secs(5);
+
+/// How long before the sleep timer runs out its warning plays, unless the
+/// library says otherwise.
+pub const SLEEP_TIMER_WARNING: Duration = Duration::from_secs(60);
+
+/// The main controller for the music box.
+pub struct MusicBoxController<P: AudioPlayer> {
+    library: Library,
+    player: P,
+    active: Option<ActiveTrack>,
+    ambient: Option<AmbientPlayback>,
+    /// When the box last went quiet, set on the first tick without playback.
+    silent_since: Option<Instant>,
+    resume: Option<ResumeStore>,
+    last_resume_save: Option<Instant>,
+    last_output_check: Option<Instant>,
+    /// The listener's volume, from 0.0 to 1.0. The ambient playlist plays at
+    /// its own volume scaled by this.
+    volume: f32,
+    rng: Rng,
+    memo: Option<MemoRecording>,
+    muted: bool,
+    night: bool,
+    /// Whether the night schedule's window was open at the last check, so
+    /// only its edges switch night mode and manual toggles stick between.
+    night_window: Option<bool>,
+    /// The card whose reader event was last let through, and when.
+    last_event: Option<(CardUid, Instant)>,
+    /// A combo card tapped recently, waiting for its partner.
+    combo_armed: Option<(CardUid, Instant)>,
+    /// The track each alternating card plays on its next tap.
+    alternations: HashMap<CardUid, usize>,
+    /// Collect unknown cards instead of only ignoring them.
+    learning: bool,
+    /// Unknown cards tapped while learning, oldest first.
+    learned: Vec<CardUid>,
+    /// Minutes since the epoch in local time, for timing greetings.
+    local_clock: fn() -> u64,
+    /// The local day each greeting was last played on.
+    greeted: HashMap<GreetingPeriod, u64>,
+}
+
+impl<P: AudioPlayer> MusicBoxController<P> {
+    pub fn new(library: Library, player: P) -> Self {
+        Self {
+            library,
+            player,
+            active: None,
+            ambient: None,
+            silent_since: None,
+            resume: None,
+            last_resume_save: None,
+            last_output_check: None,
+            volume: 1.0,
+            rng: Rng::from_entropy(),
+            memo: None,
+            muted: false,
+            night: false,
+            night_window: None,
+            last_event: None,
+            combo_armed: None,
+            alternations: HashMap::new(),
+            learning: false,
+            learned: Vec::new(),
+            local_clock: || night::local_minutes(SystemTime::now()),
+            greeted: HashMap::new(),
+        }
+    }
+
+    /// Enables voice memos: tapping `settings.card` records the next card
+    /// tapped with `recorder`.
+    pub fn with_recorder(mut self, recorder: Box<dyn Recorder>, settings: MemoSettings) -> Self {
+        self.memo = Some(MemoRecording {
+            recorder,
+            settings,
+            state: MemoState::Idle,
+        });
+        self
+    }
+
+    /// Seeds the generator behind shuffled playlists and group shuffles, so
+    /// the same seed always picks the same order.
+    pub fn with_rng_seed(mut self, seed: u64) -> Self {
+        self.rng = Rng::new(seed);
+        self
+    }
+
+    /// Remembers where resumable cards leave off in `store` and starts them
+    /// there on their next tap, including after a restart. Alternating
+    /// cards keep their next track there too.
+    pub fn with_resume_store(mut self, store: ResumeStore) -> Self {
+        self.resume = Some(store);
+        self
+    }
+
+    /// Replaces the local clock greetings are timed by, which returns
+    /// minutes since the epoch in local time.
+    pub fn with_local_clock(mut self, clock: fn() -> u64) -> Self {
+        self.local_clock = clock;
+        self
+    }
+
+    pub fn wait_for_player(&mut self) -> Result<(), PlayerError> {
+        self.player.wait_until_done()
+    }
+
+    /// Handles `uid` as if it had been tapped, for callers that name a card
+    /// on purpose: a card that is not in the library is
+    /// [`ControllerError::TrackNotFound`] rather than
+    /// [`ControllerAction::UnknownCard`], and plays no effect.
+    pub fn handle_card(&mut self, uid: &CardUid) -> Result<ControllerAction, ControllerError> {
+        self.tap(uid, None, None, false)
+    }
+
+    /// Handles a tap, falling back to the track named in the tag's NDEF text
+    /// when the UID is not in the library, so pre-written tags play anywhere.
+    pub fn handle_tap(
+        &mut self,
+        uid: &CardUid,
+        ndef_text: Option<&str>,
+    ) -> Result<ControllerAction, ControllerError> {
+        self.handle_tap_at(uid, ndef_text, None)
+    }
+
+    /// Like [`Self::handle_tap`], for a tap seen by the reader labelled
+    /// `reader`. Cards tied to a different reader are rejected; taps from an
+    /// unlabelled reader are accepted everywhere.
+    pub fn handle_tap_at(
+        &mut self,
+        uid: &CardUid,
+        ndef_text: Option<&str>,
+        reader: Option<&str>,
+    ) -> Result<ControllerAction, ControllerError> {
+        self.tap(uid, ndef_text, reader, true)
+    }
+
+    /// Handles a tap. A card that is not in the library plays the
+    /// unknown-card effect and answers [`ControllerAction::UnknownCard`],
+    /// or [`ControllerAction::CardLearned`] while learning, when it came
+    /// from a reader, and is an error otherwise.
+    fn tap(
+        &mut self,
+        uid: &CardUid,
+        ndef_text: Option<&str>,
+        reader: Option<&str>,
+        from_reader: bool,
+    ) -> Result<ControllerAction, ControllerError> {
+        if let (Some(expected), Some(reader)) = (self.library.reader(uid), reader)
+            && expected != reader
+        {
+            return Err(ControllerError::WrongReader {
+                card: uid.clone(),
+                expected: expected.to_string(),
+                reader: reader.to_string(),
+            });
+        }
+        if let Some(action) = self.handle_memo_tap(uid)? {
+            return Ok(action);
+        }
+        if let Some(CardBinding::System(command)) = self.library.binding(uid) {
+            return self.run_system_command(uid, command);
+        }
+        if let Some(action) = self.handle_combo_tap(uid, Instant::now())? {
+            return Ok(action);
+        }
+        // A combo that is playing answers to its second card, which need
+        // not be mapped itself.
+        let playing = self
+            .active
+            .as_ref()
+            .is_some_and(|active| &active.card == uid);
+        if !playing
+            && self.library.binding(uid).is_none()
+            && ndef_text
+                .and_then(|text| self.library.confined_track(text))
+                .is_none()
+        {
+            if !from_reader {
+                return Err(ControllerError::TrackNotFound);
+            }
+            self.play_effect(|effects| &effects.unknown_card)?;
+            if self.learning {
+                tracing::info!(%uid, "learned unknown card");
+                self.learned.retain(|learned| learned != uid);
+                self.learned.push(uid.clone());
+                if self.learned.len() > LEARNED_LIMIT {
+                    self.learned.remove(0);
+                }
+                return Ok(ControllerAction::CardLearned { card: uid.clone() });
+            }
+            tracing::info!(%uid, "ignoring unknown card");
+            return Ok(ControllerAction::UnknownCard { card: uid.clone() });
+        }
+        self.stop_ambient()?;
+        if self
+            .active
+            .as_ref()
+            .is_some_and(|active| &active.card == uid)
+        {
+            if self.library.playback_mode() != PlaybackMode::LiftToStop
+                && let Some(action) = self.skip_greeting()?
+            {
+                return Ok(action);
+            }
+            match self.library.playback_mode() {
+                PlaybackMode::PauseResume => {
+                    let toggled = self.toggle_pause()?;
+                    return Ok(toggled.expect("re-tapped card is active"));
+                }
+                // The card never left, so this is the reader re-reporting it.
+                PlaybackMode::LiftToStop => {
+                    return Ok(ControllerAction::Unchanged { card: uid.clone() });
+                }
+                PlaybackMode::StopOnRetap => {}
+            }
+            self.save_resume_point();
+            let active = self.active.take().expect("re-tapped card is active");
+            self.player.stop()?;
+            return Ok(ControllerAction::Stopped {
+                card: active.card,
+                track: active.track,
+            });
+        }
+
+        if let Some(CardBinding::Group(command)) = self.library.binding(uid) {
+            let command = command.clone();
+            return self.run_group_command(uid, &command);
+        }
+
+        if self.library.binding(uid).is_none()
+            && let Some(track) = ndef_text.and_then(|text| self.library.confined_track(text))
+        {
+            tracing::info!(%uid, track = %track.path().display(), "playing track from NDEF tag");
+            return self.start_queue(uid, track.expand(), ResumePoint::default());
+        }
+
+        self.start_card(uid)
+    }
+
+    /// Plays a combo when `uid` completes the one armed by the tap before,
+    /// within the library's combo window. Otherwise a combo card arms itself
+    /// and carries on with its own mapping; a card that only exists for
+    /// combos answers [`ControllerAction::Unchanged`].
+    fn handle_combo_tap(
+        &mut self,
+        uid: &CardUid,
+        now: Instant,
+    ) -> Result<Option<ControllerAction>, ControllerError> {
+        if let Some((first, at)) = self.combo_armed.take()
+            && &first != uid
+            && now.saturating_duration_since(at) < self.library.combo_window()
+            && let Some(combo) = self.library.combo(&first, uid)
+        {
+            let queue = combo.tracks.iter().flat_map(Track::expand).collect();
+            tracing::info!(%first, second = %uid, "playing combo");
+            self.stop_ambient()?;
+            return self
+                .start_queue(uid, queue, ResumePoint::default())
+                .map(Some);
+        }
+        if !self.library.in_combo(uid) {
+            return Ok(None);
+        }
+        self.combo_armed = Some((uid.clone(), now));
+        let is_active = self
+            .active
+            .as_ref()
+            .is_some_and(|active| &active.card == uid);
+        if self.library.binding(uid).is_none() && !is_active {
+            return Ok(Some(ControllerAction::Unchanged { card: uid.clone() }));
+        }
+        Ok(None)
+    }
+
+    /// Runs a system command card. Commands that change nothing, such as
+    /// next with no card playing, answer [`ControllerAction::Unchanged`];
+    /// shutdown and reload are left to the run loop, which owns the process
+    /// and the config path.
+    fn run_system_command(
+        &mut self,
+        uid: &CardUid,
+        command: SystemCommand,
+    ) -> Result<ControllerAction, ControllerError> {
+        let unchanged = || ControllerAction::Unchanged { card: uid.clone() };
+        if command.needs_volume() && !self.player.capabilities().volume {
+            tracing::debug!(%uid, "audio backend has no volume control; ignoring system card");
+            return Ok(unchanged());
+        }
+        let step = self.library.volume_step();
+        match command {
+            SystemCommand::ToggleMute => self.toggle_mute(),
+            SystemCommand::ToggleNightMode => self.set_night_mode(!self.night),
+            SystemCommand::VolumeUp | SystemCommand::VolumeDown => {
+                let delta = if command == SystemCommand::VolumeUp {
+                    step
+                } else {
+                    -step
+                };
+                let volume = self.adjust_volume(delta)?;
+                Ok(ControllerAction::VolumeChanged {
+                    percent: (volume * 100.0).round() as u8,
+                })
+            }
+            SystemCommand::Next => Ok(self.next_track()?.unwrap_or_else(unchanged)),
+            SystemCommand::Previous => Ok(self.previous_track()?.unwrap_or_else(unchanged)),
+            SystemCommand::PlayPause => Ok(self.toggle_pause()?.unwrap_or_else(unchanged)),
+            SystemCommand::Shutdown => {
+                Ok(ControllerAction::ShutdownRequested { card: uid.clone() })
+            }
+            SystemCommand::ReloadConfig => {
+                Ok(ControllerAction::ReloadRequested { card: uid.clone() })
+            }
+            SystemCommand::Unlock => match &mut self.active {
+                Some(active) if active.locked => {
+                    active.locked = false;
+                    tracing::info!(card = %active.card, "parent card unlocked playback");
+                    Ok(ControllerAction::Unlocked {
+                        card: active.card.clone(),
+                    })
+                }
+                _ => Ok(unchanged()),
+            },
+        }
+    }
+
+    /// Whether a reader event for `uid` at `now` repeats the last one let
+    /// through within the library's debounce window, and should be dropped.
+    /// Events that pass start a new window.
+    pub fn is_bounce(&mut self, uid: &CardUid, now: Instant) -> bool {
+        let window = self.library.debounce();
+        if let Some((last, at)) = &self.last_event
+            && last == uid
+            && now.saturating_duration_since(*at) < window
+        {
+            return true;
+        }
+        self.last_event = Some((uid.clone(), now));
+        false
+    }
+
+    /// Handles a card being lifted off the reader. In
+    /// [`PlaybackMode::LiftToStop`] lifting the playing card stops it, saving
+    /// its resume point; otherwise removals are ignored.
+    pub fn handle_removal(
+        &mut self,
+        uid: &CardUid,
+    ) -> Result<Option<ControllerAction>, ControllerError> {
+        if self.library.playback_mode() != PlaybackMode::LiftToStop
+            || !self
+                .active
+                .as_ref()
+                .is_some_and(|active| &active.card == uid)
+        {
+            return Ok(None);
+        }
+        self.pause_playback()
+    }
+
+    /// Handles taps that belong to voice memo recording, if any.
+    fn handle_memo_tap(
+        &mut self,
+        uid: &CardUid,
+    ) -> Result<Option<ControllerAction>, ControllerError> {
+        let Some(memo) = &mut self.memo else {
+            return Ok(None);
+        };
+        let record_card = &memo.settings.card == uid;
+        match &memo.state {
+            MemoState::Idle if record_card => {
+                memo.state = MemoState::Armed {
+                    since: Instant::now(),
+                };
+                // Keep the music out of the microphone.
+                self.pause_playback()?;
+                Ok(Some(ControllerAction::RecordArmed { card: uid.clone() }))
+            }
+            MemoState::Idle => Ok(None),
+            MemoState::Armed { .. } if record_card => {
+                memo.state = MemoState::Idle;
+                tracing::info!("voice memo cancelled");
+                Ok(Some(ControllerAction::Unchanged { card: uid.clone() }))
+            }
+            MemoState::Armed { .. } => {
+                memo.state = MemoState::Idle;
+                let has_music = self.library.playlist(uid).is_some_and(|tracks| {
+                    tracks
+                        .iter()
+                        .any(|track| !memo.settings.is_memo(track.path()))
+                });
+                if has_music {
+                    tracing::warn!(%uid, "not recording over a card that plays music");
+                    return Ok(Some(ControllerAction::Unchanged { card: uid.clone() }));
+                }
+                if let Err(err) = memo.recorder.start() {
+                    tracing::warn!(%err, %uid, "failed to start recording");
+                    return Ok(Some(ControllerAction::Unchanged { card: uid.clone() }));
+                }
+                memo.state = MemoState::Recording {
+                    card: uid.clone(),
+                    since: Instant::now(),
+                };
+                Ok(Some(ControllerAction::Recording { card: uid.clone() }))
+            }
+            MemoState::Recording { card, .. } if card == uid || record_card => {
+                let card = card.clone();
+                Ok(Some(self.finish_memo(card)))
+            }
+            // Other cards wait until the recording is done.
+            MemoState::Recording { .. } => {
+                Ok(Some(ControllerAction::Unchanged { card: uid.clone() }))
+            }
+        }
+    }
+
+    /// Saves the memo being recorded for `card` and maps the card to it. A
+    /// failure is logged, since a broken microphone should not stop the box.
+    fn finish_memo(&mut self, card: CardUid) -> ControllerAction {
+        let memo = self
+            .memo
+            .as_mut()
+            .expect("finishing a memo needs a recorder");
+        memo.state = MemoState::Idle;
+        let path = memo.settings.path_for(&card);
+        let saved = memo.recorder.finish().and_then(|clip| {
+            if clip.samples.is_empty() {
+                return Err(RecordError::Empty);
+            }
+            clip.write_wav(&path)?;
+            Ok(clip.duration())
+        });
+        match saved {
+            Ok(length) => {
+                tracing::info!(%card, ?length, path = %path.display(), "saved voice memo");
+                let track = Track::new(path);
+                self.library.set_playlist(card.clone(), vec![track.clone()]);
+                ControllerAction::MemoRecorded { card, track }
+            }
+            Err(err) => {
+                tracing::warn!(%err, %card, "failed to save voice memo");
+                ControllerAction::Unchanged { card }
+            }
+        }
+    }
+
+    /// Asks the player whether its output device came or went, at most once
+    /// per [`OUTPUT_CHECK_INTERVAL`]. Call on every reader tick.
+    pub fn poll_output(&mut self, now: Instant) -> Option<ControllerAction> {
+        if self
+            .last_output_check
+            .is_some_and(|checked| now.duration_since(checked) < OUTPUT_CHECK_INTERVAL)
+        {
+            return None;
+        }
+        self.last_output_check = Some(now);
+        let change = self.player.refresh_output()?;
+        Some(ControllerAction::OutputChanged { change })
+    }
+
+    /// Ends a recording that reached its maximum length and disarms the
+    /// record card once nobody picked a card in time. Call on every reader
+    /// tick.
+    pub fn poll_recording(&mut self, now: Instant) -> Option<ControllerAction> {
+        let memo = self.memo.as_mut()?;
+        match &memo.state {
+            MemoState::Armed { since } if now.duration_since(*since) >= ARM_TIMEOUT => {
+                tracing::info!("no card tapped; voice memo cancelled");
+                memo.state = MemoState::Idle;
+                None
+            }
+            MemoState::Recording { card, since }
+                if now.duration_since(*since) >= memo.settings.max_length =>
+            {
+                let card = card.clone();
+                Some(self.finish_memo(card))
+            }
+            _ => None,
+        }
+    }
+
+    fn run_group_command(
+        &mut self,
+        uid: &CardUid,
+        command: &GroupCommand,
+    ) -> Result<ControllerAction, ControllerError> {
+        let unchanged = || ControllerAction::Unchanged { card: uid.clone() };
+        let active_card = self.active.as_ref().map(|active| active.card.clone());
+        match command.action {
+            GroupAction::Stop => {
+                if self.is_locked() {
+                    return Ok(unchanged());
+                }
+                let in_group = active_card
+                    .as_ref()
+                    .is_some_and(|card| self.library.group(card) == Some(&command.group));
+                if !in_group {
+                    return Ok(unchanged());
+                }
+                Ok(self.pause_playback()?.unwrap_or_else(unchanged))
+            }
+            GroupAction::Shuffle => {
+                let now = SystemTime::now();
+                let candidates: Vec<CardUid> = self
+                    .library
+                    .group_members(&command.group)
+                    .into_iter()
+                    .filter(|card| Some(card) != active_card.as_ref())
+                    .filter(|card| !self.library.is_expired(card, now))
+                    .collect();
+                if candidates.is_empty() {
+                    return Ok(unchanged());
+                }
+                let pick = candidates[self.rng.index(candidates.len())].clone();
+                self.start_card(&pick)
+            }
+        }
+    }
+
+    /// Starts `uid`'s track, replacing whatever is playing. Resumable cards
+    /// pick up at their saved point when it still fits the playlist; shuffled
+    /// cards are dealt a new order on every tap and always start fresh.
+    /// Alternating cards play the track after the one they played last.
+    fn start_card(&mut self, uid: &CardUid) -> Result<ControllerAction, ControllerError> {
+        if self.library.is_expired(uid, SystemTime::now()) {
+            return Err(ControllerError::CardExpired(uid.clone()));
+        }
+        let mut queue: Vec<Track> = self
+            .library
+            .playlist(uid)
+            .ok_or(ControllerError::TrackNotFound)?
+            .iter()
+            .flat_map(Track::expand)
+            .collect();
+        if self.library.mode(uid) == PlaylistMode::Alternate {
+            let from = self.next_alternation(uid, queue.len());
+            return self.start_queue(uid, queue, from);
+        }
+        let shuffled = self.library.mode(uid) == PlaylistMode::Shuffle;
+        if shuffled {
+            self.rng.shuffle(&mut queue);
+        }
+        let from = self
+            .resume
+            .as_ref()
+            .filter(|_| self.library.resumes(uid) && !shuffled)
+            .and_then(|store| store.get(uid))
+            .filter(|point| point.track_index < queue.len())
+            .unwrap_or_default();
+        self.start_queue(uid, queue, from)
+    }
+
+    /// Which of its `len` tracks alternating card `uid` plays now, moving
+    /// it on to the next. A failed save is logged rather than returned; the
+    /// card still alternates until the box restarts.
+    fn next_alternation(&mut self, uid: &CardUid, len: usize) -> ResumePoint {
+        let track_index = self
+            .alternations
+            .get(uid)
+            .copied()
+            .or_else(|| {
+                let store = self.resume.as_ref()?;
+                Some(store.get(uid)?.track_index)
+            })
+            .filter(|index| *index < len)
+            .unwrap_or_default();
+        let next = (track_index + 1) % len.max(1);
+        self.alternations.insert(uid.clone(), next);
+        if let Some(store) = &mut self.resume {
+            let point = ResumePoint {
+                track_index: next,
+                offset: Duration::ZERO,
+            };
+            if let Err(err) = store.set(uid, point) {
+                tracing::warn!(%err, card = %uid, "failed to save the next alternating track");
+            }
+        }
+        ResumePoint {
+            track_index,
+            offset: Duration::ZERO,
+        }
+    }
+
+    /// Starts `queue` at `from` on behalf of `uid`.
+    fn start_queue(
+        &mut self,
+        uid: &CardUid,
+        queue: Vec<Track>,
+        from: ResumePoint,
+    ) -> Result<ControllerAction, ControllerError> {
+        let track = queue
+            .get(from.track_index)
+            .cloned()
+            .ok_or(ControllerError::TrackNotFound)?;
+        self.save_resume_point();
+        let ends_at = self
+            .library
+            .countdown(uid)
+            .map(|countdown| Instant::now() + countdown.duration);
+        let stops_at = self
+            .library
+            .max_play(uid)
+            .map(|limit| Instant::now() + limit);
+        let locked = self.library.is_locked(uid);
+        let greeting = self.take_greeting();
+
+        let action = if let Some(active) = self.active.take() {
+            self.player.stop()?;
+            self.play_first(&track, from.offset, greeting.as_ref())?;
+            let action = ControllerAction::Switched {
+                from_card: active.card.clone(),
+                from_track: active.track.clone(),
+                to_card: uid.clone(),
+                to_track: track.clone(),
+            };
+            self.active = Some(ActiveTrack {
+                card: uid.clone(),
+                track: track.clone(),
+                queue,
+                position: from.track_index,
+                paused: false,
+                gap_until: None,
+                ends_at,
+                stops_at,
+                warned: false,
+                locked,
+                greeting: greeting.is_some().then_some(from.offset),
+                chain: Vec::new(),
+            });
+            action
+        } else {
+            self.play_first(&track, from.offset, greeting.as_ref())?;
+            self.active = Some(ActiveTrack {
+                card: uid.clone(),
+                track: track.clone(),
+                queue,
+                position: from.track_index,
+                paused: false,
+                gap_until: None,
+                ends_at,
+                stops_at,
+                warned: false,
+                locked,
+                greeting: greeting.is_some().then_some(from.offset),
+                chain: Vec::new(),
+            });
+            ControllerAction::Started {
+                card: uid.clone(),
+                track: track.clone(),
+            }
+        };
+
+        Ok(action)
+    }
+
+    /// Plays `greeting` when one is due, and `track` from `offset` if not.
+    fn play_first(
+        &mut self,
+        track: &Track,
+        offset: Duration,
+        greeting: Option<&Track>,
+    ) -> Result<(), ControllerError> {
+        match greeting {
+            Some(greeting) => {
+                tracing::info!(greeting = %greeting.path().display(), "playing greeting");
+                Ok(self.player.play(greeting)?)
+            }
+            None => self.play_from(track, offset),
+        }
+    }
+
+    /// The greeting clip due now, if its period has not been greeted yet
+    /// today; it counts as played from here on.
+    fn take_greeting(&mut self) -> Option<Track> {
+        let now = (self.local_clock)();
+        let (period, day, track) = self.library.greetings().due(now)?;
+        if self.greeted.get(&period) == Some(&day) {
+            return None;
+        }
+        let track = track.locate();
+        self.greeted.insert(period, day);
+        Some(track)
+    }
+
+    /// Cuts a playing greeting short and starts the card's track, as
+    /// [`ControllerAction::Skipped`]. `None` when no greeting plays.
+    fn skip_greeting(&mut self) -> Result<Option<ControllerAction>, ControllerError> {
+        let Some(active) = &mut self.active else {
+            return Ok(None);
+        };
+        let Some(offset) = active.greeting.take() else {
+            return Ok(None);
+        };
+        active.paused = false;
+        let (card, track) = (active.card.clone(), active.track.clone());
+        self.play_from(&track, offset)?;
+        Ok(Some(ControllerAction::Skipped { card, track }))
+    }
+
+    /// Plays `track`, skipping `offset` into it. A failed seek only costs the
+    /// listener a replay, so it is logged rather than returned.
+    fn play_from(&mut self, track: &Track, offset: Duration) -> Result<(), ControllerError> {
+        self.player.play(track)?;
+        if !offset.is_zero() && self.player.capabilities().seek {
+            tracing::info!(track = %track.path().display(), ?offset, "resuming track");
+            if let Err(err) = self.player.seek(offset) {
+                tracing::warn!(%err, track = %track.path().display(), "failed to resume track");
+            }
+        }
+        Ok(())
+    }
+
+    /// Bookmarks the active card when it is resumable. A failed save is
+    /// logged rather than returned; it should not interrupt playback.
+    fn save_resume_point(&mut self) {
+        let (Some(store), Some(active)) = (&mut self.resume, &self.active) else {
+            return;
+        };
+        if !self.library.resumes(&active.card)
+            || self.library.mode(&active.card) == PlaylistMode::Alternate
+        {
+            return;
+        }
+        let offset = match active.greeting {
+            Some(offset) => offset,
+            None => self.player.position().unwrap_or_default(),
+        };
+        let point = ResumePoint {
+            track_index: active.position,
+            offset,
+        };
+        if let Err(err) = store.set(&active.card, point) {
+            tracing::warn!(%err, card = %active.card, "failed to save resume point");
+        }
+    }
+
+    /// Handles a press of one of the physical buttons. Presses that have
+    /// nothing to act on, such as next on the last track, return `None`.
+    pub fn handle_button(
+        &mut self,
+        button: ButtonEvent,
+    ) -> Result<Option<ControllerAction>, ControllerError> {
+        let step = self.library.volume_step();
+        let volume_button = matches!(
+            button,
+            ButtonEvent::VolumeUp | ButtonEvent::VolumeDown | ButtonEvent::Mute
+        );
+        if volume_button && !self.player.capabilities().volume {
+            tracing::debug!(
+                ?button,
+                "audio backend has no volume control; ignoring button"
+            );
+            return Ok(None);
+        }
+        match button {
+            ButtonEvent::Next => self.next_track(),
+            ButtonEvent::Previous => self.previous_track(),
+            ButtonEvent::PlayPause => self.toggle_pause(),
+            ButtonEvent::VolumeUp => self.adjust_volume(step).map(|_| None),
+            ButtonEvent::VolumeDown => self.adjust_volume(-step).map(|_| None),
+            ButtonEvent::Mute => self.toggle_mute().map(Some),
+        }
+    }
+
+    pub fn is_muted(&self) -> bool {
+        self.muted
+    }
+
+    /// Mutes or unmutes output. The track keeps playing while muted, and
+    /// volume changes made meanwhile apply once unmuted.
+    pub fn set_muted(&mut self, muted: bool) -> Result<ControllerAction, ControllerError> {
+        if !self.player.capabilities().volume {
+            return Err(ControllerError::Unsupported("muting"));
+        }
+        self.player.set_muted(muted)?;
+        self.muted = muted;
+        Ok(ControllerAction::MuteToggled { muted })
+    }
+
+    pub fn toggle_mute(&mut self) -> Result<ControllerAction, ControllerError> {
+        self.set_muted(!self.muted)
+    }
+
+    pub fn is_night_mode(&self) -> bool {
+        self.night
+    }
+
+    /// Switches night mode, which caps the volume at the library's night
+    /// maximum and turns on the backend's compressor. The listener's own
+    /// volume is kept and comes back once night mode is off.
+    pub fn set_night_mode(&mut self, on: bool) -> Result<ControllerAction, ControllerError> {
+        if !self.player.capabilities().volume {
+            return Err(ControllerError::Unsupported("night mode"));
+        }
+        self.player.set_night_mode(on)?;
+        self.night = on;
+        self.player.set_volume(self.output_volume())?;
+        Ok(ControllerAction::NightModeToggled { on })
+    }
+
+    /// Follows the night schedule, given the local time as minutes after
+    /// midnight: night mode switches on as the window opens and off as it
+    /// closes. Returns the toggle, if one happened.
+    pub fn apply_night_schedule(
+        &mut self,
+        minute_of_day: u16,
+    ) -> Result<Option<ControllerAction>, ControllerError> {
+        let Some(schedule) = self.library.night().schedule else {
+            return Ok(None);
+        };
+        let open = schedule.contains(minute_of_day);
+        // The first check only switches on: starting up outside the window
+        // is not an edge.
+        let edge = match self.night_window.replace(open) {
+            Some(was_open) => was_open != open,
+            None => open,
+        };
+        if !edge || open == self.night || !self.player.capabilities().volume {
+            return Ok(None);
+        }
+        tracing::info!(on = open, "night schedule switching night mode");
+        self.set_night_mode(open).map(Some)
+    }
+
+    /// The volume handed to the player for the listener's volume, capped
+    /// while night mode is on.
+    fn output_volume(&self) -> f32 {
+        let scale = match (&self.ambient, self.library.ambient()) {
+            (Some(_), Some(policy)) => policy.volume,
+            _ => 1.0,
+        };
+        let volume = if self.night {
+            self.volume.min(self.library.night().max_volume)
+        } else {
+            self.volume
+        };
+        volume * scale
+    }
+
+    /// Pauses the playing card, or resumes it if it is paused.
+    pub fn toggle_pause(&mut self) -> Result<Option<ControllerAction>, ControllerError> {
+        self.save_resume_point();
+        let Some(active) = &mut self.active else {
+            return Ok(None);
+        };
+        let (card, track) = (active.card.clone(), active.track.clone());
+        if active.paused {
+            self.player.resume()?;
+            active.paused = false;
+            Ok(Some(ControllerAction::Resumed { card, track }))
+        } else {
+            self.player.pause()?;
+            active.paused = true;
+            Ok(Some(ControllerAction::Paused { card, track }))
+        }
+    }
+
+    /// Whether the playing card is locked against skip and stop commands.
+    fn is_locked(&self) -> bool {
+        let locked = self.active.as_ref().is_some_and(|active| active.locked);
+        if locked {
+            tracing::debug!("playing card is locked; ignoring command");
+        }
+        locked
+    }
+
+    /// Skips to the next track of the playing card's playlist. Locked cards
+    /// do not skip.
+    pub fn next_track(&mut self) -> Result<Option<ControllerAction>, ControllerError> {
+        if self.is_locked() {
+            return Ok(None);
+        }
+        if let Some(action) = self.skip_greeting()? {
+            return Ok(Some(action));
+        }
+        match &self.active {
+            Some(active) if self.library.mode(&active.card) == PlaylistMode::Repeat => {
+                self.skip_to((active.position + 1) % active.queue.len())
+            }
+            Some(active) => self.skip_to(active.position + 1),
+            None => Ok(None),
+        }
+    }
+
+    /// Goes back one track; on the first track, restarts it. Locked cards
+    /// do not skip.
+    pub fn previous_track(&mut self) -> Result<Option<ControllerAction>, ControllerError> {
+        if self.is_locked() {
+            return Ok(None);
+        }
+        if let Some(action) = self.skip_greeting()? {
+            return Ok(Some(action));
+        }
+        match &self.active {
+            Some(active) => self.skip_to(active.position.saturating_sub(1)),
+            None => Ok(None),
+        }
+    }
+
+    /// Takes entry `index` out of the playing card's queue. The edit only
+    /// lasts for this play: the next tap starts from the card's whole
+    /// playlist again. The track playing stays until it is skipped.
+    pub fn remove_queued(&mut self, index: usize) -> Result<Track, ControllerError> {
+        let Some(active) = &mut self.active else {
+            return Err(ControllerError::NotQueued(index));
+        };
+        if index == active.position {
+            return Err(ControllerError::QueuedTrackPlaying(index));
+        }
+        if index >= active.queue.len() {
+            return Err(ControllerError::NotQueued(index));
+        }
+        if index < active.position {
+            active.position -= 1;
+        }
+        Ok(active.queue.remove(index))
+    }
+
+    fn skip_to(&mut self, position: usize) -> Result<Option<ControllerAction>, ControllerError> {
+        let Some(active) = &mut self.active else {
+            return Ok(None);
+        };
+        let Some(track) = active.queue.get(position).cloned() else {
+            return Ok(None);
+        };
+        self.player.play(&track)?;
+        active.track = track.clone();
+        active.position = position;
+        active.paused = false;
+        active.gap_until = None;
+        active.greeting = None;
+        let card = active.card.clone();
+        self.save_resume_point();
+        Ok(Some(ControllerAction::Skipped { card, track }))
+    }
+
+    /// Moves the active playlist on once the player reports the current track
+    /// finished. Returns [`ControllerAction::Advanced`] when the next track
+    /// starts. After the last one, the card's [`PlaylistEnd`] decides: it
+    /// returns [`ControllerAction::Stopped`], starts over, or starts the
+    /// next card. Repeating cards never run out. A card that would continue
+    /// into one already played since the last tap stops instead, so cards
+    /// chained in a circle do not play forever.
+    pub fn poll_playback(&mut self) -> Result<Option<ControllerAction>, ControllerError> {
+        self.poll_playback_at(Instant::now())
+    }
+
+    /// [`Self::poll_playback`] at `now`. Cards with a gap wait that long
+    /// after a track finishes before the next one of theirs starts.
+    pub fn poll_playback_at(
+        &mut self,
+        now: Instant,
+    ) -> Result<Option<ControllerAction>, ControllerError> {
+        self.warn_before_sleep_timer(now)?;
+        let Some(active) = &mut self.active else {
+            return Ok(None);
+        };
+        if active.ends_at.is_some_and(|ends_at| now >= ends_at) {
+            return self.finish_countdown().map(Some);
+        }
+        let fade = self.library.sleep_timer().fade;
+        if active
+            .stops_at
+            .is_some_and(|stops_at| now + fade >= stops_at)
+        {
+            return self.finish_sleep_timer(fade).map(Some);
+        }
+        if active.paused || !self.player.is_finished() {
+            return Ok(None);
+        }
+        if let Some(offset) = active.greeting.take() {
+            let track = active.track.clone();
+            self.play_from(&track, offset)?;
+            return Ok(None);
+        }
+
+        let mut position = match self.library.mode(&active.card) {
+            PlaylistMode::Sequential | PlaylistMode::Shuffle => active.position + 1,
+            PlaylistMode::Repeat => (active.position + 1) % active.queue.len(),
+            PlaylistMode::RepeatOne => active.position,
+            PlaylistMode::Alternate => active.queue.len(),
+        };
+        // Countdowns loop their playlist until the time is up.
+        if active.ends_at.is_some() && position >= active.queue.len() {
+            position = 0;
+        }
+        let another_track = position < active.queue.len()
+            || matches!(
+                self.library.end(&active.card),
+                PlaylistEnd::Repeat | PlaylistEnd::ShuffleRepeat
+            );
+        let gap = self.library.gap(&active.card);
+        if another_track && !gap.is_zero() {
+            match active.gap_until {
+                None => {
+                    active.gap_until = Some(now + gap);
+                    return Ok(None);
+                }
+                Some(until) if now < until => return Ok(None),
+                Some(_) => active.gap_until = None,
+            }
+        }
+        match active.queue.get(position).cloned() {
+            Some(track) => {
+                self.player.play(&track)?;
+                active.track = track.clone();
+                active.position = position;
+                let card = active.card.clone();
+                self.save_resume_point();
+                Ok(Some(ControllerAction::Advanced { card, track }))
+            }
+            None => {
+                let end = self.library.end(&active.card);
+                if matches!(end, PlaylistEnd::Repeat | PlaylistEnd::ShuffleRepeat) {
+                    if end == PlaylistEnd::ShuffleRepeat {
+                        self.rng.shuffle(&mut active.queue);
+                    }
+                    let track = active.queue[0].clone();
+                    self.player.play(&track)?;
+                    active.track = track.clone();
+                    active.position = 0;
+                    let card = active.card.clone();
+                    self.save_resume_point();
+                    return Ok(Some(ControllerAction::Advanced { card, track }));
+                }
+                let finished = self.active.take().expect("active checked above");
+                // Finished cards start over on their next tap; alternating
+                // cards keep their place in the list.
+                if let Some(store) = &mut self.resume
+                    && self.library.mode(&finished.card) != PlaylistMode::Alternate
+                    && let Err(err) = store.clear(&finished.card)
+                {
+                    tracing::warn!(%err, card = %finished.card, "failed to clear resume point");
+                }
+                if let PlaylistEnd::Continue(next) = end {
+                    let mut chain = finished.chain;
+                    chain.push(finished.card.clone());
+                    if chain.contains(&next) {
+                        tracing::warn!(card = %finished.card, %next, "cards continue into each other in a loop; stopping");
+                    } else {
+                        // Playback carries on, so nobody needs greeting.
+                        self.take_greeting();
+                        match self.start_card(&next) {
+                            Ok(action) => {
+                                if let Some(active) = &mut self.active {
+                                    active.chain = chain;
+                                }
+                                return Ok(Some(action));
+                            }
+                            Err(err) => {
+                                tracing::warn!(%err, card = %finished.card, %next, "failed to continue into next card");
+                            }
+                        }
+                    }
+                }
+                Ok(Some(ControllerAction::Stopped {
+                    card: finished.card,
+                    track: finished.track,
+                }))
+            }
+        }
+    }
+
+    /// Stops a countdown card whose time is up and plays its chime.
+    fn finish_countdown(&mut self) -> Result<ControllerAction, ControllerError> {
+        let finished = self.active.take().expect("caller checked the active card");
+        self.player.stop()?;
+        let chime = self
+            .library
+            .countdown(&finished.card)
+            .and_then(|countdown| countdown.chime.as_ref())
+            .or(self.library.sounds().chime.as_ref())
+            .map(Track::locate);
+        if let Some(chime) = chime {
+            self.player.play(&chime)?;
+        }
+        tracing::info!(card = %finished.card, "countdown finished");
+        Ok(ControllerAction::CountdownFinished {
+            card: finished.card,
+            track: finished.track,
+        })
+    }
+
+    /// Plays the sleep timer's warning once the playing card is that close
+    /// to its `max_play`. A paused card is warned once it plays again.
+    fn warn_before_sleep_timer(&mut self, now: Instant) -> Result<(), ControllerError> {
+        let warning = self.library.sleep_timer().warning;
+        let Some(active) = &mut self.active else {
+            return Ok(());
+        };
+        let due = active
+            .stops_at
+            .is_some_and(|stops_at| now + warning >= stops_at);
+        if !due || active.warned || active.paused {
+            return Ok(());
+        }
+        active.warned = true;
+        self.play_effect(|effects| &effects.sleep_warning)?;
+        Ok(())
+    }
+
+    /// Fades out and stops a card that has played for its `max_play`, so
+    /// it is silent once the time is up. Resumable cards remember where
+    /// they faded out.
+    fn finish_sleep_timer(&mut self, fade: Duration) -> Result<ControllerAction, ControllerError> {
+        self.save_resume_point();
+        let finished = self.active.take().expect("caller checked the active card");
+        self.player.fade_out(fade)?;
+        tracing::info!(card = %finished.card, "sleep timer ran out");
+        Ok(ControllerAction::TimedOut {
+            card: finished.card,
+            track: finished.track,
+        })
+    }
+
+    /// The track `uid` would start, without touching playback state. Command
+    /// cards and unknown UIDs return `None`.
+    pub fn peek(&self, uid: &CardUid) -> Option<Track> {
+        self.library.lookup(uid).cloned()
+    }
+
+    /// Applies the library's idle policy; call on every reader tick. Once
+    /// nothing has played for [`AmbientPolicy::idle_after`], the ambient
+    /// playlist starts at its volume and loops until the next card tap.
+    /// While a resumable card plays, its position is saved every
+    /// `RESUME_SAVE_INTERVAL`.
+    pub fn tick(&mut self, now: Instant) -> Result<(), ControllerError> {
+        let playing = self.active.as_ref().is_some_and(|active| !active.paused);
+        let save_due = self
+            .last_resume_save
+            .is_none_or(|saved| now.duration_since(saved) >= RESUME_SAVE_INTERVAL);
+        if playing && save_due {
+            self.save_resume_point();
+            self.last_resume_save = Some(now);
+        }
+        let Some(policy) = self.library.ambient() else {
+            return self.stop_ambient();
+        };
+        if self.active.is_some() {
+            self.silent_since = None;
+            return Ok(());
+        }
+        if let Some(ambient) = &mut self.ambient {
+            if self.player.is_finished() {
+                ambient.position = (ambient.position + 1) % ambient.queue.len();
+                self.player.play(&ambient.queue[ambient.position])?;
+            }
+            return Ok(());
+        }
+
+        let since = *self.silent_since.get_or_insert(now);
+        if now.duration_since(since) < policy.idle_after {
+            return Ok(());
+        }
+        self.start_ambient(now)
+    }
+
+    fn start_ambient(&mut self, now: Instant) -> Result<(), ControllerError> {
+        let Some(policy) = self.library.ambient() else {
+            return Ok(());
+        };
+        let queue: Vec<Track> = policy.tracks.iter().flat_map(Track::expand).collect();
+        let Some(first) = queue.first() else {
+            tracing::warn!("ambient playlist has no playable tracks");
+            self.silent_since = Some(now);
+            return Ok(());
+        };
+        tracing::info!(track = %first.path().display(), "starting ambient playlist");
+        let first = first.clone();
+        self.ambient = Some(AmbientPlayback { queue, position: 0 });
+        self.player.set_volume(self.output_volume())?;
+        self.player.play(&first)?;
+        Ok(())
+    }
+
+    /// Whether the ambient playlist is what is currently playing.
+    pub fn ambient_playing(&self) -> bool {
+        self.ambient.is_some()
+    }
+
+    fn stop_ambient(&mut self) -> Result<(), ControllerError> {
+        self.silent_since = None;
+        if self.ambient.take().is_some() {
+            self.player.stop()?;
+            self.player.set_volume(self.output_volume())?;
+        }
+        Ok(())
+    }
+
+    /// When the player first made sound for the track it last started.
+    pub fn first_sound_at(&self) -> Option<Instant> {
+        self.player.first_sound_at()
+    }
+
+    pub fn player_capabilities(&self) -> PlayerCapabilities {
+        self.player.capabilities()
+    }
+
+    pub fn volume(&self) -> f32 {
+        self.volume
+    }
+
+    /// Sets the listener's volume, clamped to 0.0..=1.0, and returns the
+    /// value applied.
+    pub fn set_volume(&mut self, volume: f32) -> Result<f32, ControllerError> {
+        if !self.player.capabilities().volume {
+            return Err(ControllerError::Unsupported("volume control"));
+        }
+        self.volume = if volume.is_nan() {
+            self.volume
+        } else {
+            volume.clamp(0.0, 1.0)
+        };
+        self.player.set_volume(self.output_volume())?;
+        Ok(self.volume)
+    }
+
+    /// Nudges the volume by `delta`, as a volume knob does.
+    pub fn adjust_volume(&mut self, delta: f32) -> Result<f32, ControllerError> {
+        self.set_volume(self.volume + delta)
+    }
+
+    pub fn pause_playback(&mut self) -> Result<Option<ControllerAction>, ControllerError> {
+        self.stop_ambient()?;
+        self.save_resume_point();
+        if let Some(active) = &self.active {
+            let action = ControllerAction::Stopped {
+                card: active.card.clone(),
+                track: active.track.clone(),
+            };
+            self.player.stop()?;
+            self.active = None;
+            Ok(Some(action))
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Does what the library's [`StartupAction`] asks for, once the run
+    /// loop is about to start. Returns the action when a card was resumed.
+    pub fn start_up(&mut self) -> Result<Option<ControllerAction>, ControllerError> {
+        self.play_effect(|effects| &effects.ready)?;
+        match self.library.startup() {
+            StartupAction::Silent => Ok(None),
+            StartupAction::Chime => {
+                if let Some(chime) = self.library.sounds().startup.as_ref().map(Track::locate) {
+                    self.player.play(&chime)?;
+                }
+                Ok(None)
+            }
+            StartupAction::Resume => {
+                let Some(store) = &self.resume else {
+                    tracing::info!("no resume state to pick up from at startup");
+                    return Ok(None);
+                };
+                let Some(uid) = store.latest(|uid| self.library.resumes(uid)) else {
+                    return Ok(None);
+                };
+                tracing::info!(%uid, "resuming the last card at startup");
+                self.handle_card(&uid).map(Some)
+            }
+            StartupAction::Ambient => {
+                if self.active.is_none() && self.ambient.is_none() {
+                    self.start_ambient(Instant::now())?;
+                }
+                Ok(None)
+            }
+        }
+    }
+
+    /// Stops playback and, when configured, plays the goodnight cue to
+    /// completion so listeners hear that the box is turning off.
+    pub fn shutdown(&mut self) -> Result<Option<ControllerAction>, ControllerError> {
+        let effect = self.play_effect(|effects| &effects.shutdown)?;
+        let stopped = self.pause_playback()?;
+        if let Some(goodnight) = self.library.sounds().goodnight.as_ref().map(Track::locate) {
+            self.player.play(&goodnight)?;
+            self.player.wait_until_done()?;
+        } else if effect {
+            // Let the effect finish before audio is torn down.
+            self.player.stop()?;
+            self.player.wait_until_done()?;
+        }
+        Ok(stopped)
+    }
+
+    /// Plays the effect `pick` chooses, if one is configured. Returns
+    /// whether one was.
+    fn play_effect(
+        &mut self,
+        pick: impl FnOnce(&Effects) -> &Option<Track>,
+    ) -> Result<bool, ControllerError> {
+        let Some(effect) = pick(self.library.effects()).as_ref().map(Track::locate) else {
+            return Ok(false);
+        };
+        self.player.play_effect(&effect)?;
+        Ok(true)
+    }
+
+    /// The LED color hint of the card currently playing, if it has one.
+    pub fn active_color(&self) -> Option<Rgb> {
+        self.active
+            .as_ref()
+            .and_then(|active| self.library.color(&active.card))
+    }
+
+    /// Whether the playing card is paused.
+    pub fn is_paused(&self) -> bool {
+        self.active.as_ref().is_some_and(|active| active.paused)
+    }
+
+    /// The playing card's queue, folders expanded, and the index of the
+    /// track playing in it.
+    pub fn queue(&self) -> Option<(&[Track], usize)> {
+        self.active
+            .as_ref()
+            .map(|active| (active.queue.as_slice(), active.position))
+    }
+
+    pub fn active(&self) -> Option<(CardUid, Track)> {
+        self.active
+            .as_ref()
+            .map(|current| (current.card.clone(), current.track.clone()))
+    }
+
+    pub fn replace_library(&mut self, library: Library) {
+        self.library = library;
+    }
+
+    pub fn library_entries(&self) -> Vec<(CardUid, Track)> {
+        self.library.entries()
+    }
+
+    pub fn library(&self) -> &Library {
+        &self.library
+    }
+
+    /// Starts or stops noting unknown cards as they are tapped.
+    pub fn set_learning(&mut self, on: bool) {
+        self.learning = on;
+    }
+
+    pub fn is_learning(&self) -> bool {
+        self.learning
+    }
+
+    /// Unknown cards tapped while learning, most recent first, leaving out
+    /// those mapped since.
+    pub fn learned_cards(&self) -> Vec<CardUid> {
+        self.learned
+            .iter()
+            .rev()
+            .filter(|uid| self.library.binding(uid).is_none())
+            .cloned()
+            .collect()
+    }
+}
+
+/// A small splitmix64 generator for shuffling. It is seeded from
+/// [`random_u64`] unless a test pins the seed.
+#[derive(Debug, Clone)]
+struct Rng(u64);
+
+impl Rng {
+    fn new(seed: u64) -> Self {
+        Self(seed)
+    }
+
+    fn from_entropy() -> Self {
+        Self(random_u64())
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    fn index(&mut self, len: usize) -> usize {
+        (self.next_u64() % len as u64) as usize
+    }
+
+    /// Fisher-Yates shuffle.
+    fn shuffle<T>(&mut self, items: &mut [T]) {
+        for i in (1..items.len()).rev() {
+            items.swap(i, self.index(i + 1));
+        }
+    }
+}
+
+/// Random bits from the standard library's hash keys, which are seeded from
+/// the OS once per thread and bumped on every `RandomState::new`, so no RNG
+/// dependency is needed.
+fn random_u64() -> u64 {
+    use std::hash::BuildHasher;
+    std::collections::hash_map::RandomState::new().hash_one(0u8)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::night::NightSchedule;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    #[derive(Debug, Clone, PartialEq)]
+    enum Call {
+        Play(PathBuf),
+        Stop,
+        Pause,
+        Resume,
+        Volume(f32),
+        Mute(bool),
+        Night(bool),
+        Seek(Duration),
+        FadeOut(Duration),
+        Effect(PathBuf),
+    }
+
+    #[derive(Clone)]
+    struct MockPlayer {
+        calls: Rc<RefCell<Vec<Call>>>,
+        finished: Rc<std::cell::Cell<bool>>,
+        position: Rc<std::cell::Cell<Duration>>,
+        capabilities: PlayerCapabilities,
+        /// Reported by the next [`AudioPlayer::refresh_output`].
+        output: Rc<RefCell<Option<OutputChange>>>,
+    }
+
+    impl MockPlayer {
+        fn new() -> Self {
+            Self {
+                calls: Rc::new(RefCell::new(Vec::new())),
+                finished: Rc::new(std::cell::Cell::new(false)),
+                position: Rc::new(std::cell::Cell::new(Duration::ZERO)),
+                capabilities: PlayerCapabilities {
+                    seek: true,
+                    volume: true,
+                    track_end: true,
+                    streams: true,
+                },
+                output: Rc::new(RefCell::new(None)),
+            }
+        }
+
+        /// A player that only plays and stops.
+        fn basic() -> Self {
+            Self {
+                capabilities: PlayerCapabilities::default(),
+                ..Self::new()
+            }
+        }
+
+        fn finish_track(&self) {
+            self.finished.set(true);
+        }
+
+        fn play_for(&self, elapsed: Duration) {
+            self.position.set(self.position.get() + elapsed);
+        }
+
+        fn calls(&self) -> Vec<Call> {
+            self.calls.borrow().clone()
+        }
+    }
+
+    impl AudioPlayer for MockPlayer {
+        fn capabilities(&self) -> PlayerCapabilities {
+            self.capabilities
+        }
+
+        fn play(&mut self, track: &Track) -> Result<(), PlayerError> {
+            self.calls.borrow_mut().push(Call::Play(track.path.clone()));
+            self.finished.set(false);
+            self.position.set(Duration::ZERO);
+            Ok(())
+        }
+
+        fn stop(&mut self) -> Result<(), PlayerError> {
+            self.calls.borrow_mut().push(Call::Stop);
+            Ok(())
+        }
+
+        fn pause(&mut self) -> Result<(), PlayerError> {
+            self.calls.borrow_mut().push(Call::Pause);
+            Ok(())
+        }
+
+        fn resume(&mut self) -> Result<(), PlayerError> {
+            self.calls.borrow_mut().push(Call::Resume);
+            Ok(())
+        }
+
+        fn is_finished(&self) -> bool {
+            self.finished.get()
+        }
+
+        fn set_volume(&mut self, volume: f32) -> Result<(), PlayerError> {
+            self.calls.borrow_mut().push(Call::Volume(volume));
+            Ok(())
+        }
+
+        fn set_muted(&mut self, muted: bool) -> Result<(), PlayerError> {
+            self.calls.borrow_mut().push(Call::Mute(muted));
+            Ok(())
+        }
+
+        fn set_night_mode(&mut self, on: bool) -> Result<(), PlayerError> {
+            self.calls.borrow_mut().push(Call::Night(on));
+            Ok(())
+        }
+
+        fn position(&self) -> Option<Duration> {
+            Some(self.position.get())
+        }
+
+        fn seek(&mut self, offset: Duration) -> Result<(), PlayerError> {
+            self.calls.borrow_mut().push(Call::Seek(offset));
+            self.position.set(offset);
+            Ok(())
+        }
+
+        fn fade_out(&mut self, over: Duration) -> Result<(), PlayerError> {
+            self.calls.borrow_mut().push(Call::FadeOut(over));
+            Ok(())
+        }
+
+        fn play_effect(&mut self, effect: &Track) -> Result<(), PlayerError> {
+            self.calls
+                .borrow_mut()
+                .push(Call::Effect(effect.path().to_path_buf()));
+            Ok(())
+        }
+
+        fn refresh_output(&mut self) -> Option<OutputChange> {
+            self.output.borrow_mut().take()
+        }
+    }
+
+    fn library_with(entries: Vec<(CardUid, &str)>) -> Library {
+        let map = entries
+            .into_iter()
+            .map(|(uid, path)| (uid, Track::new(PathBuf::from(path))))
+            .collect();
+        Library::new(map)
+    }
+
+    fn uid(bytes: &[u8]) -> CardUid {
+        CardUid::new(bytes.to_vec())
+    }
+
+    #[test]
+    fn card_uid_formats_to_lower_hex() {
+        let uid = uid(&[0x0a, 0x1b, 0xff]);
+        assert_eq!(uid.to_hex_lowercase(), "0a1bff");
+        assert_eq!(uid.to_string(), "0a1bff");
+    }
+
+    #[test]
+    fn card_uid_from_hex_parses_bytes() {
+        let parsed = CardUid::from_hex("0a0b0c0d").unwrap();
+        assert_eq!(parsed, uid(&[0x0a, 0x0b, 0x0c, 0x0d]));
+    }
+
+    #[test]
+    fn card_uid_from_hex_rejects_odd_length() {
+        let err = CardUid::from_hex("abc").unwrap_err();
+        assert_eq!(err, CardUidParseError::OddLength);
+    }
+
+    #[test]
+    fn card_uid_from_hex_rejects_invalid_chars() {
+        let err = CardUid::from_hex("zz").unwrap_err();
+        assert_eq!(err, CardUidParseError::InvalidHex('z'));
+    }
+
+    #[test]
+    fn card_uid_parse_ignores_case_and_separators() {
+        let expected = uid(&[0xab, 0xcd]);
+        for input in ["abcd", "ABCD", "AB CD", " ab:cd ", "Ab-Cd"] {
+            assert_eq!(CardUid::parse(input).unwrap(), expected, "{input:?}");
+        }
+        assert_eq!(
+            CardUid::parse("ab c").unwrap_err(),
+            CardUidParseError::OddLength
+        );
+    }
+
+    #[test]
+    fn synthetic_random_uids_do_not_collide() {
+        let uids: std::collections::HashSet<CardUid> = (0..10_000)
+            .map(|_| CardUid::synthetic(SyntheticUid::Random))
+            .collect();
+
+        assert_eq!(uids.len(), 10_000);
+        assert!(uids.iter().all(|uid| uid.as_bytes().len() == 7));
+    }
+
+    #[test]
+    fn synthetic_ulid_uids_are_unique_and_time_ordered() {
+        let uids: Vec<CardUid> = (0..10_000)
+            .map(|_| CardUid::synthetic(SyntheticUid::Ulid))
+            .collect();
+
+        let unique: std::collections::HashSet<&CardUid> = uids.iter().collect();
+        assert_eq!(unique.len(), uids.len());
+        assert!(uids.iter().all(|uid| uid.as_bytes().len() == 16));
+        assert!(
+            uids.windows(2)
+                .all(|pair| pair[0].as_bytes()[..6] <= pair[1].as_bytes()[..6])
+        );
+    }
+
+    #[test]
+    fn library_resolves_names_before_hex() {
+        let library = library_with(vec![(uid(&[0xca, 0xfe]), "a.mp3"), (uid(&[1, 2]), "b.mp3")])
+            .with_names(HashMap::from([(uid(&[1, 2]), "Cafe".to_string())]));
+
+        assert_eq!(library.resolve_card("cafe").unwrap(), uid(&[1, 2]));
+        assert_eq!(library.resolve_card(" CA FE ").unwrap(), uid(&[0xca, 0xfe]));
+        assert_eq!(library.name(&uid(&[1, 2])), Some("Cafe"));
+        assert!(library.resolve_card("unknown").is_err());
+    }
+
+    #[test]
+    fn tapping_card_starts_associated_track() {
+        let player = MockPlayer::new();
+        let library = library_with(vec![(uid(&[1, 2]), "song1.mp3")]);
+        let mut controller = MusicBoxController::new(library, player.clone());
+
+        let action = controller.handle_card(&uid(&[1, 2])).unwrap();
+
+        assert_eq!(
+            action,
+            ControllerAction::Started {
+                card: uid(&[1, 2]),
+                track: Track::new(PathBuf::from("song1.mp3"))
+            }
+        );
+        assert_eq!(player.calls(), vec![Call::Play(PathBuf::from("song1.mp3"))]);
+    }
+
+    #[test]
+    fn tapping_same_card_stops_playback() {
+        let player = MockPlayer::new();
+        let library = library_with(vec![(uid(&[1, 2]), "song1.mp3")]);
+        let mut controller = MusicBoxController::new(library, player.clone());
+
+        controller.handle_card(&uid(&[1, 2])).unwrap();
+        let action = controller.handle_card(&uid(&[1, 2])).unwrap();
+
+        assert_eq!(
+            action,
+            ControllerAction::Stopped {
+                card: uid(&[1, 2]),
+                track: Track::new(PathBuf::from("song1.mp3"))
+            }
+        );
+        assert_eq!(
+            player.calls(),
+            vec![Call::Play(PathBuf::from("song1.mp3")), Call::Stop,]
+        );
+    }
+
+    #[test]
+    fn pause_resume_mode_pauses_then_resumes_on_retap() {
+        let player = MockPlayer::new();
+        let library = library_with(vec![(uid(&[1]), "song.mp3")])
+            .with_playback_mode(PlaybackMode::PauseResume);
+        let mut controller = MusicBoxController::new(library, player.clone());
+        let track = Track::new(PathBuf::from("song.mp3"));
+
+        controller.handle_card(&uid(&[1])).unwrap();
+        let paused = controller.handle_card(&uid(&[1])).unwrap();
+        player.finish_track();
+        assert_eq!(controller.poll_playback().unwrap(), None);
+        let resumed = controller.handle_card(&uid(&[1])).unwrap();
+
+        assert_eq!(
+            paused,
+            ControllerAction::Paused {
+                card: uid(&[1]),
+                track: track.clone(),
+            }
+        );
+        assert_eq!(
+            resumed,
+            ControllerAction::Resumed {
+                card: uid(&[1]),
+                track,
+            }
+        );
+        assert_eq!(
+            player.calls(),
+            vec![
+                Call::Play(PathBuf::from("song.mp3")),
+                Call::Pause,
+                Call::Resume,
+            ]
+        );
+    }
+
+    #[test]
+    fn repeated_events_for_a_card_inside_the_debounce_window_are_dropped() {
+        let library = library_with(vec![(uid(&[1]), "a.mp3"), (uid(&[2]), "b.mp3")])
+            .with_debounce(Duration::from_millis(300));
+        let mut controller = MusicBoxController::new(library, MockPlayer::new());
+        let start = Instant::now();
+        let at = |ms| start + Duration::from_millis(ms);
+
+        assert!(!controller.is_bounce(&uid(&[1]), at(0)));
+        assert!(controller.is_bounce(&uid(&[1]), at(50)));
+        assert!(controller.is_bounce(&uid(&[1]), at(299)));
+        // The window runs from the last event let through, not the last seen.
+        assert!(!controller.is_bounce(&uid(&[1]), at(300)));
+        // Another card is never a bounce, and restarts the window.
+        assert!(!controller.is_bounce(&uid(&[2]), at(310)));
+        assert!(!controller.is_bounce(&uid(&[1]), at(320)));
+
+        let mut undebounced =
+            MusicBoxController::new(library_with(vec![(uid(&[1]), "a.mp3")]), MockPlayer::new());
+        assert!(!undebounced.is_bounce(&uid(&[1]), at(0)));
+        assert!(!undebounced.is_bounce(&uid(&[1]), at(0)));
+    }
+
+    #[test]
+    fn lift_to_stop_mode_plays_only_while_the_card_rests() {
+        let player = MockPlayer::new();
+        let library = library_with(vec![(uid(&[1]), "a.mp3"), (uid(&[2]), "b.mp3")])
+            .with_playback_mode(PlaybackMode::LiftToStop);
+        let mut controller = MusicBoxController::new(library, player.clone());
+
+        controller.handle_card(&uid(&[1])).unwrap();
+        assert_eq!(
+            controller.handle_card(&uid(&[1])).unwrap(),
+            ControllerAction::Unchanged { card: uid(&[1]) }
+        );
+        assert_eq!(controller.handle_removal(&uid(&[2])).unwrap(), None);
+        assert_eq!(
+            controller.handle_removal(&uid(&[1])).unwrap(),
+            Some(ControllerAction::Stopped {
+                card: uid(&[1]),
+                track: Track::new(PathBuf::from("a.mp3")),
+            })
+        );
+        assert_eq!(controller.active(), None);
+        assert_eq!(controller.handle_removal(&uid(&[1])).unwrap(), None);
+
+        let mut retap =
+            MusicBoxController::new(library_with(vec![(uid(&[1]), "a.mp3")]), MockPlayer::new());
+        retap.handle_card(&uid(&[1])).unwrap();
+        assert_eq!(retap.handle_removal(&uid(&[1])).unwrap(), None);
+        assert!(retap.active().is_some());
+        assert_eq!(
+            player.calls(),
+            vec![Call::Play(PathBuf::from("a.mp3")), Call::Stop]
+        );
+    }
+
+    #[test]
+    fn tapping_different_card_switches_tracks() {
+        let player = MockPlayer::new();
+        let library = library_with(vec![
+            (uid(&[1, 2]), "song1.mp3"),
+            (uid(&[3, 4]), "song2.mp3"),
+        ]);
+        let mut controller = MusicBoxController::new(library, player.clone());
+
+        controller.handle_card(&uid(&[1, 2])).unwrap();
+        let action = controller.handle_card(&uid(&[3, 4])).unwrap();
+
+        assert_eq!(
+            action,
+            ControllerAction::Switched {
+                from_card: uid(&[1, 2]),
+                from_track: Track::new(PathBuf::from("song1.mp3")),
+                to_card: uid(&[3, 4]),
+                to_track: Track::new(PathBuf::from("song2.mp3")),
+            }
+        );
+        assert_eq!(
+            player.calls(),
+            vec![
+                Call::Play(PathBuf::from("song1.mp3")),
+                Call::Stop,
+                Call::Play(PathBuf::from("song2.mp3")),
+            ]
+        );
+    }
+
+    #[test]
+    fn unknown_card_causes_error_without_audio_calls() {
+        let player = MockPlayer::new();
+        let library = library_with(vec![(uid(&[1, 2]), "song1.mp3")]);
+        let mut controller = MusicBoxController::new(library, player.clone());
+
+        let err = controller.handle_card(&uid(&[9, 9])).unwrap_err();
+
+        assert!(matches!(err, ControllerError::TrackNotFound));
+        assert!(player.calls().is_empty());
+    }
+
+    #[test]
+    fn effects_play_over_playback_at_startup_unknown_taps_and_shutdown() {
+        let player = MockPlayer::new();
+        let effect = |name: &str| Some(Track::new(PathBuf::from(name)));
+        let library = library_with(vec![(uid(&[1, 2]), "song1.mp3")]).with_effects(Effects {
+            ready: effect("ready.ogg"),
+            unknown_card: effect("bonk.ogg"),
+            shutdown: effect("bye.ogg"),
+            ..Effects::default()
+        });
+        let mut controller = MusicBoxController::new(library, player.clone());
+
+        assert_eq!(controller.start_up().unwrap(), None);
+        controller.handle_card(&uid(&[1, 2])).unwrap();
+        assert_eq!(
+            controller.handle_tap(&uid(&[9, 9]), None).unwrap(),
+            ControllerAction::UnknownCard { card: uid(&[9, 9]) }
+        );
+        assert!(controller.active().is_some(), "the song plays on");
+        controller.shutdown().unwrap();
+
+        assert_eq!(
+            player.calls(),
+            vec![
+                Call::Effect(PathBuf::from("ready.ogg")),
+                Call::Play(PathBuf::from("song1.mp3")),
+                Call::Effect(PathBuf::from("bonk.ogg")),
+                Call::Effect(PathBuf::from("bye.ogg")),
+                Call::Stop,
+                Call::Stop,
+            ]
+        );
+    }
+
+    #[test]
+    fn cards_tied_to_a_reader_ignore_taps_on_other_readers() {
+        let player = MockPlayer::new();
+        let library = library_with(vec![(uid(&[1, 2]), "song1.mp3")])
+            .with_readers(HashMap::from([(uid(&[1, 2]), "back".to_string())]));
+        let mut controller = MusicBoxController::new(library, player.clone());
+
+        let err = controller
+            .handle_tap_at(&uid(&[1, 2]), None, Some("front"))
+            .unwrap_err();
+        assert!(matches!(err, ControllerError::WrongReader { expected, .. } if expected == "back"));
+        assert!(player.calls().is_empty());
+
+        controller
+            .handle_tap_at(&uid(&[1, 2]), None, Some("back"))
+            .unwrap();
+        controller.handle_card(&uid(&[1, 2])).unwrap();
+        assert_eq!(player.calls().len(), 2);
+    }
+
+    #[test]
+    fn expired_guest_card_is_rejected() {
+        let player = MockPlayer::new();
+        let now = SystemTime::now();
+        let library = library_with(vec![
+            (uid(&[1, 2]), "song1.mp3"),
+            (uid(&[3, 4]), "song2.mp3"),
+        ])
+        .with_expiries(HashMap::from([
+            (uid(&[1, 2]), now - Duration::from_secs(60)),
+            (uid(&[3, 4]), now + Duration::from_secs(3_600)),
+        ]));
+        let mut controller = MusicBoxController::new(library, player.clone());
+
+        let err = controller.handle_card(&uid(&[1, 2])).unwrap_err();
+        assert!(matches!(err, ControllerError::CardExpired(card) if card == uid(&[1, 2])));
+        assert!(player.calls().is_empty());
+
+        controller.handle_card(&uid(&[3, 4])).unwrap();
+        assert_eq!(player.calls(), vec![Call::Play(PathBuf::from("song2.mp3"))]);
+    }
+
+    #[test]
+    fn pause_playback_stops_active_track() {
+        let player = MockPlayer::new();
+        let library = library_with(vec![(uid(&[1, 2]), "song1.mp3")]);
+        let mut controller = MusicBoxController::new(library, player.clone());
+
+        controller.handle_card(&uid(&[1, 2])).unwrap();
+        let result = controller.pause_playback().unwrap();
+
+        assert_eq!(
+            result,
+            Some(ControllerAction::Stopped {
+                card: uid(&[1, 2]),
+                track: Track::new(PathBuf::from("song1.mp3")),
+            })
+        );
+        assert_eq!(
+            player.calls(),
+            vec![Call::Play(PathBuf::from("song1.mp3")), Call::Stop]
+        );
+        assert!(controller.active().is_none());
+    }
+
+    #[test]
+    fn pause_playback_noops_when_idle() {
+        let player = MockPlayer::new();
+        let library = library_with(vec![(uid(&[1, 2]), "song1.mp3")]);
+        let mut controller = MusicBoxController::new(library, player.clone());
+
+        let result = controller.pause_playback().unwrap();
+
+        assert!(result.is_none());
+        assert!(player.calls().is_empty());
+    }
+
+    #[test]
+    fn locate_prefers_existing_path() {
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join("song.mp3");
+        std::fs::write(&path, b"").unwrap();
+        std::fs::write(tmp.path().join("song.ogg"), b"").unwrap();
+
+        assert_eq!(Track::new(path.clone()).locate().path(), path);
+    }
+
+    #[test]
+    fn stats_count_cards_and_tracks_that_cannot_be_found() {
+        let tmp = tempfile::tempdir().unwrap();
+        std::fs::write(tmp.path().join("here.mp3"), b"").unwrap();
+        std::fs::write(tmp.path().join("reencoded.ogg"), b"").unwrap();
+        let library = Library::from_playlists(HashMap::from([
+            (uid(&[1]), vec![Track::new(tmp.path().join("here.mp3"))]),
+            (
+                uid(&[2]),
+                vec![Track::new(tmp.path().join("reencoded.mp3"))],
+            ),
+            (
+                uid(&[3]),
+                vec![
+                    Track::new(tmp.path().join("gone.mp3")),
+                    Track::new(PathBuf::from("http://radio.example/live")),
+                ],
+            ),
+            (uid(&[4]), vec![Track::new(tmp.path().join("gone.mp3"))]),
+        ]))
+        .with_system_commands(HashMap::from([(uid(&[0xee]), SystemCommand::ToggleMute)]));
+
+        let stats = library.stats();
+
+        assert_eq!(stats.cards, 5);
+        assert_eq!(stats.missing_tracks, 1);
+        // Found once per load, so files added since do not count yet.
+        std::fs::write(tmp.path().join("gone.mp3"), b"").unwrap();
+        assert_eq!(library.stats(), stats);
+    }
+
+    #[test]
+    fn locate_falls_back_to_case_insensitive_match() {
+        let tmp = tempfile::tempdir().unwrap();
+        let actual = tmp.path().join("Song.MP3");
+        std::fs::write(&actual, b"").unwrap();
+        std::fs::write(tmp.path().join("song.ogg"), b"").unwrap();
+
+        let located = Track::new(tmp.path().join("song.mp3")).locate();
+
+        assert_eq!(located.path(), actual);
+    }
+
+    #[test]
+    fn locate_falls_back_to_reencoded_sibling() {
+        let tmp = tempfile::tempdir().unwrap();
+        std::fs::write(tmp.path().join("song.flac"), b"").unwrap();
+        std::fs::write(tmp.path().join("song.ogg"), b"").unwrap();
+        std::fs::write(tmp.path().join("other.mp3"), b"").unwrap();
+
+        let located = Track::new(tmp.path().join("song.mp3")).locate();
+
+        assert_eq!(located.path(), tmp.path().join("song.ogg"));
+    }
+
+    #[test]
+    fn locate_keeps_missing_track_without_substitute() {
+        let tmp = tempfile::tempdir().unwrap();
+        let missing = tmp.path().join("song.mp3");
+
+        assert_eq!(Track::new(missing.clone()).locate().path(), missing);
+    }
+
+    #[test]
+    fn shutdown_stops_playback_then_plays_goodnight() {
+        let player = MockPlayer::new();
+        let library = library_with(vec![(uid(&[1, 2]), "song1.mp3")]).with_sounds(SoundCues {
+            goodnight: Some(Track::new(PathBuf::from("goodnight.mp3"))),
+            ..SoundCues::default()
+        });
+        let mut controller = MusicBoxController::new(library, player.clone());
+
+        controller.handle_card(&uid(&[1, 2])).unwrap();
+        let stopped = controller.shutdown().unwrap();
+
+        assert!(matches!(stopped, Some(ControllerAction::Stopped { .. })));
+        assert_eq!(
+            player.calls(),
+            vec![
+                Call::Play(PathBuf::from("song1.mp3")),
+                Call::Stop,
+                Call::Play(PathBuf::from("goodnight.mp3")),
+            ]
+        );
+        assert!(controller.active().is_none());
+    }
+
+    #[test]
+    fn shutdown_without_goodnight_is_silent() {
+        let player = MockPlayer::new();
+        let mut controller = MusicBoxController::new(Library::default(), player.clone());
+
+        assert!(controller.shutdown().unwrap().is_none());
+        assert!(player.calls().is_empty());
+    }
+
+    #[test]
+    fn active_color_follows_playing_card() {
+        let orange = Rgb::new(0xff, 0x80, 0x00);
+        let library = library_with(vec![(uid(&[1]), "a.mp3"), (uid(&[2]), "b.mp3")])
+            .with_colors(HashMap::from([(uid(&[1]), orange)]));
+        let mut controller = MusicBoxController::new(library, MockPlayer::new());
+
+        controller.handle_card(&uid(&[1])).unwrap();
+        assert_eq!(controller.active_color(), Some(orange));
+        controller.handle_card(&uid(&[2])).unwrap();
+        assert_eq!(controller.active_color(), None);
+    }
+
+    fn grouped_library() -> Library {
+        library_with(vec![
+            (uid(&[1]), "book1.mp3"),
+            (uid(&[2]), "book2.mp3"),
+            (uid(&[3]), "song.mp3"),
+        ])
+        .with_groups(HashMap::from([
+            (uid(&[1]), "audiobooks".to_string()),
+            (uid(&[2]), "audiobooks".to_string()),
+            (uid(&[3]), "music".to_string()),
+        ]))
+        .with_commands(HashMap::from([
+            (
+                uid(&[0xa0]),
+                GroupCommand {
+                    action: GroupAction::Stop,
+                    group: "audiobooks".into(),
+                },
+            ),
+            (
+                uid(&[0xa1]),
+                GroupCommand {
+                    action: GroupAction::Shuffle,
+                    group: "audiobooks".into(),
+                },
+            ),
+        ]))
+    }
+
+    #[test]
+    fn stop_group_only_stops_members() {
+        let mut controller = MusicBoxController::new(grouped_library(), MockPlayer::new());
+
+        controller.handle_card(&uid(&[3])).unwrap();
+        let action = controller.handle_card(&uid(&[0xa0])).unwrap();
+        assert_eq!(action, ControllerAction::Unchanged { card: uid(&[0xa0]) });
+        assert_eq!(controller.active().unwrap().0, uid(&[3]));
+
+        controller.handle_card(&uid(&[1])).unwrap();
+        let action = controller.handle_card(&uid(&[0xa0])).unwrap();
+        assert!(matches!(action, ControllerAction::Stopped { card, .. } if card == uid(&[1])));
+        assert!(controller.active().is_none());
+    }
+
+    #[test]
+    fn shuffle_group_picks_another_member() {
+        let mut controller = MusicBoxController::new(grouped_library(), MockPlayer::new());
+        controller.handle_card(&uid(&[1])).unwrap();
+
+        for _ in 0..10 {
+            let before = controller.active().unwrap().0;
+            let action = controller.handle_card(&uid(&[0xa1])).unwrap();
+            let ControllerAction::Switched { to_card, .. } = action else {
+                panic!("expected switch, got {action:?}");
+            };
+            assert_ne!(to_card, before);
+            assert_eq!(controller.library().group(&to_card), Some("audiobooks"));
+        }
+    }
+
+    #[test]
+    fn playlist_advances_when_track_finishes() {
+        let player = MockPlayer::new();
+        let library = Library::from_playlists(HashMap::from([(
+            uid(&[1]),
+            vec![
+                Track::new(PathBuf::from("01.mp3")),
+                Track::new(PathBuf::from("02.mp3")),
+            ],
+        )]));
+        let mut controller = MusicBoxController::new(library, player.clone());
+
+        controller.handle_card(&uid(&[1])).unwrap();
+        assert!(controller.poll_playback().unwrap().is_none());
+
+        player.finish_track();
+        let advanced = controller.poll_playback().unwrap();
+        assert_eq!(
+            advanced,
+            Some(ControllerAction::Advanced {
+                card: uid(&[1]),
+                track: Track::new(PathBuf::from("02.mp3")),
+            })
+        );
+        assert_eq!(controller.active().unwrap().1.path(), Path::new("02.mp3"));
+
+        player.finish_track();
+        let stopped = controller.poll_playback().unwrap();
+        assert!(matches!(stopped, Some(ControllerAction::Stopped { .. })));
+        assert!(controller.active().is_none());
+        assert_eq!(
+            player.calls(),
+            vec![
+                Call::Play(PathBuf::from("01.mp3")),
+                Call::Play(PathBuf::from("02.mp3")),
+            ]
+        );
+    }
+
+    #[test]
+    fn removed_queue_entries_are_skipped_for_this_play_only() {
+        let player = MockPlayer::new();
+        let mut controller =
+            MusicBoxController::new(moded_library(PlaylistMode::Sequential), player.clone());
+        assert!(controller.queue().is_none());
+        assert!(matches!(
+            controller.remove_queued(0),
+            Err(ControllerError::NotQueued(0))
+        ));
+
+        controller.handle_card(&uid(&[1])).unwrap();
+        controller.next_track().unwrap();
+        assert!(matches!(
+            controller.remove_queued(1),
+            Err(ControllerError::QueuedTrackPlaying(1))
+        ));
+        assert!(matches!(
+            controller.remove_queued(4),
+            Err(ControllerError::NotQueued(4))
+        ));
+        let removed = controller.remove_queued(2).unwrap();
+        assert_eq!(removed.path(), Path::new("03.mp3"));
+        controller.remove_queued(0).unwrap();
+        let (queue, position) = controller.queue().unwrap();
+        let paths: Vec<_> = queue.iter().map(Track::path).collect();
+        assert_eq!(paths, [Path::new("02.mp3"), Path::new("04.mp3")]);
+        assert_eq!(position, 0);
+
+        player.finish_track();
+        controller.poll_playback().unwrap();
+        assert_eq!(controller.active().unwrap().1.path(), Path::new("04.mp3"));
+
+        controller.pause_playback().unwrap();
+        controller.handle_card(&uid(&[1])).unwrap();
+        assert_eq!(controller.queue().unwrap().0.len(), 4);
+    }
+
+    fn moded_library(mode: PlaylistMode) -> Library {
+        let tracks = ["01.mp3", "02.mp3", "03.mp3", "04.mp3"]
+            .into_iter()
+            .map(|name| Track::new(PathBuf::from(name)))
+            .collect();
+        Library::from_playlists(HashMap::from([(uid(&[1]), tracks)]))
+            .with_modes(HashMap::from([(uid(&[1]), mode)]))
+    }
+
+    fn play_through(controller: &mut MusicBoxController<MockPlayer>, player: &MockPlayer) {
+        controller.handle_card(&uid(&[1])).unwrap();
+        for _ in 0..5 {
+            player.finish_track();
+            controller.poll_playback().unwrap();
+        }
+    }
+
+    #[test]
+    fn gaps_hold_the_next_track_back_but_not_the_end() {
+        let player = MockPlayer::new();
+        let tracks = ["01.mp3", "02.mp3"]
+            .into_iter()
+            .map(|name| Track::new(PathBuf::from(name)))
+            .collect();
+        let library = Library::from_playlists(HashMap::from([(uid(&[1]), tracks)]))
+            .with_gaps(HashMap::from([(uid(&[1]), Duration::from_secs(2))]));
+        let mut controller = MusicBoxController::new(library, player.clone());
+        let start = Instant::now();
+        controller.handle_card(&uid(&[1])).unwrap();
+
+        player.finish_track();
+        assert_eq!(controller.poll_playback_at(start).unwrap(), None);
+        let early = start + Duration::from_secs(1);
+        assert_eq!(controller.poll_playback_at(early).unwrap(), None);
+        let due = start + Duration::from_secs(2);
+        assert!(matches!(
+            controller.poll_playback_at(due).unwrap(),
+            Some(ControllerAction::Advanced { .. })
+        ));
+
+        // No gap after the last track: the card stops straight away.
+        player.finish_track();
+        assert!(matches!(
+            controller.poll_playback_at(due).unwrap(),
+            Some(ControllerAction::Stopped { .. })
+        ));
+        assert_eq!(player.calls().len(), 2);
+    }
+
+    #[test]
+    fn countdowns_loop_until_the_time_is_up_then_chime() {
+        let player = MockPlayer::new();
+        let library = library_with(vec![(uid(&[1]), "brush.mp3")])
+            .with_countdowns(HashMap::from([(
+                uid(&[1]),
+                Countdown {
+                    duration: Duration::from_secs(120),
+                    chime: None,
+                },
+            )]))
+            .with_sounds(SoundCues {
+                chime: Some(Track::new(PathBuf::from("chime.mp3"))),
+                ..SoundCues::default()
+            });
+        let mut controller = MusicBoxController::new(library, player.clone());
+        let start = Instant::now();
+        controller.handle_card(&uid(&[1])).unwrap();
+
+        player.finish_track();
+        assert!(matches!(
+            controller.poll_playback_at(start).unwrap(),
+            Some(ControllerAction::Advanced { .. })
+        ));
+        assert_eq!(
+            controller
+                .poll_playback_at(start + Duration::from_secs(121))
+                .unwrap(),
+            Some(ControllerAction::CountdownFinished {
+                card: uid(&[1]),
+                track: Track::new(PathBuf::from("brush.mp3")),
+            })
+        );
+        assert_eq!(controller.active(), None);
+        assert_eq!(
+            player.calls(),
+            vec![
+                Call::Play(PathBuf::from("brush.mp3")),
+                Call::Play(PathBuf::from("brush.mp3")),
+                Call::Stop,
+                Call::Play(PathBuf::from("chime.mp3")),
+            ]
+        );
+    }
+
+    #[test]
+    fn sleep_timer_fades_out_cards_that_play_too_long() {
+        let player = MockPlayer::new();
+        let library = library_with(vec![(uid(&[1]), "lullaby.mp3"), (uid(&[2]), "book.mp3")])
+            .with_max_play(Some(Duration::from_secs(30 * 60)))
+            .with_max_plays(HashMap::from([(uid(&[2]), None)]));
+        let mut controller = MusicBoxController::new(library, player.clone());
+        let start = Instant::now();
+        controller.handle_card(&uid(&[1])).unwrap();
+
+        assert_eq!(
+            controller
+                .poll_playback_at(start + Duration::from_secs(29 * 60))
+                .unwrap(),
+            None
+        );
+        assert_eq!(
+            controller
+                .poll_playback_at(start + Duration::from_secs(31 * 60))
+                .unwrap(),
+            Some(ControllerAction::TimedOut {
+                card: uid(&[1]),
+                track: Track::new(PathBuf::from("lullaby.mp3")),
+            })
+        );
+        assert_eq!(controller.active(), None);
+        assert_eq!(
+            player.calls(),
+            vec![
+                Call::Play(PathBuf::from("lullaby.mp3")),
+                Call::FadeOut(SLEEP_TIMER_FADE),
+            ]
+        );
+
+        controller.handle_card(&uid(&[2])).unwrap();
+        assert_eq!(
+            controller
+                .poll_playback_at(start + Duration::from_secs(24 * 60 * 60))
+                .unwrap(),
+            None,
+            "exempt cards play on"
+        );
+    }
+
+    #[test]
+    fn sleep_timer_warns_then_fades_over_its_last_seconds() {
+        let player = MockPlayer::new();
+        let library = library_with(vec![(uid(&[1]), "lullaby.mp3")])
+            .with_max_play(Some(Duration::from_secs(30 * 60)))
+            .with_sleep_timer(SleepTimer {
+                fade: Duration::from_secs(20),
+                warning: Duration::from_secs(90),
+            })
+            .with_effects(Effects {
+                sleep_warning: Some(Track::new(PathBuf::from("soft-chime.ogg"))),
+                ..Effects::default()
+            });
+        let mut controller = MusicBoxController::new(library, player.clone());
+        let start = Instant::now();
+        controller.handle_card(&uid(&[1])).unwrap();
+        let before_end = |secs: u64| start + Duration::from_secs(30 * 60 - secs);
+
+        assert_eq!(controller.poll_playback_at(before_end(92)).unwrap(), None);
+        assert_eq!(
+            player.calls(),
+            vec![Call::Play(PathBuf::from("lullaby.mp3"))]
+        );
+        assert_eq!(controller.poll_playback_at(before_end(89)).unwrap(), None);
+        assert_eq!(controller.poll_playback_at(before_end(60)).unwrap(), None);
+        assert_eq!(controller.poll_playback_at(before_end(21)).unwrap(), None);
+        assert_eq!(
+            controller.poll_playback_at(before_end(19)).unwrap(),
+            Some(ControllerAction::TimedOut {
+                card: uid(&[1]),
+                track: Track::new(PathBuf::from("lullaby.mp3")),
+            })
+        );
+        assert_eq!(
+            player.calls(),
+            vec![
+                Call::Play(PathBuf::from("lullaby.mp3")),
+                Call::Effect(PathBuf::from("soft-chime.ogg")),
+                Call::FadeOut(Duration::from_secs(20)),
+            ]
+        );
+    }
+
+    #[test]
+    fn repeat_modes_loop_the_playlist_or_the_track() {
+        let played = |mode| {
+            let player = MockPlayer::new();
+            let mut controller = MusicBoxController::new(moded_library(mode), player.clone());
+            play_through(&mut controller, &player);
+            assert!(
+                controller.active().is_some(),
+                "{mode:?} should keep playing"
+            );
+            player.calls()
+        };
+        let play = |name: &str| Call::Play(PathBuf::from(name));
+
+        assert_eq!(
+            played(PlaylistMode::Repeat),
+            ["01.mp3", "02.mp3", "03.mp3", "04.mp3", "01.mp3", "02.mp3"].map(play)
+        );
+        assert_eq!(played(PlaylistMode::RepeatOne), vec![play("01.mp3"); 6]);
+    }
+
+    #[test]
+    fn alternate_mode_plays_the_next_track_on_each_tap_across_restarts() {
+        let tmp = tempfile::tempdir().unwrap();
+        let state = tmp.path().join("resume.json");
+        let player = MockPlayer::new();
+        let mut controller =
+            MusicBoxController::new(moded_library(PlaylistMode::Alternate), player.clone())
+                .with_resume_store(ResumeStore::load(&state).unwrap());
+
+        controller.handle_card(&uid(&[1])).unwrap();
+        player.finish_track();
+        assert!(matches!(
+            controller.poll_playback().unwrap(),
+            Some(ControllerAction::Stopped { .. })
+        ));
+        controller.handle_card(&uid(&[1])).unwrap();
+        // Re-tapping while it plays stops it; the next tap moves on.
+        controller.handle_card(&uid(&[1])).unwrap();
+        controller.handle_card(&uid(&[1])).unwrap();
+        drop(controller);
+
+        let mut controller =
+            MusicBoxController::new(moded_library(PlaylistMode::Alternate), player.clone())
+                .with_resume_store(ResumeStore::load(&state).unwrap());
+        for _ in 0..2 {
+            controller.handle_card(&uid(&[1])).unwrap();
+            player.finish_track();
+            controller.poll_playback().unwrap();
+        }
+
+        let played: Vec<Call> = player
+            .calls()
+            .into_iter()
+            .filter(|call| matches!(call, Call::Play(_)))
+            .collect();
+        assert_eq!(
+            played,
+            ["01.mp3", "02.mp3", "03.mp3", "04.mp3", "01.mp3"]
+                .map(|name| Call::Play(PathBuf::from(name)))
+        );
+    }
+
+    #[test]
+    fn shuffle_mode_plays_every_track_once_in_seeded_order() {
+        let shuffled = |seed| {
+            let player = MockPlayer::new();
+            let mut controller =
+                MusicBoxController::new(moded_library(PlaylistMode::Shuffle), player.clone())
+                    .with_rng_seed(seed);
+            play_through(&mut controller, &player);
+            assert!(controller.active().is_none());
+            player.calls()
+        };
+
+        let order = shuffled(7);
+        assert_eq!(order, shuffled(7));
+        let mut sorted = order.clone();
+        sorted.sort_by_key(|call| format!("{call:?}"));
+        assert_eq!(
+            sorted,
+            ["01.mp3", "02.mp3", "03.mp3", "04.mp3"].map(|name| Call::Play(PathBuf::from(name)))
+        );
+        assert!(
+            (0..20).any(|seed| shuffled(seed) != order),
+            "every seed dealt the same order"
+        );
+    }
+
+    #[test]
+    fn playlist_ends_stop_repeat_or_continue_into_another_card() {
+        let played = |end: PlaylistEnd| {
+            let player = MockPlayer::new();
+            let tracks = ["01.mp3", "02.mp3", "03.mp3", "04.mp3"]
+                .into_iter()
+                .map(|name| Track::new(PathBuf::from(name)))
+                .collect();
+            let library = Library::from_playlists(HashMap::from([
+                (uid(&[1]), tracks),
+                (uid(&[2]), vec![Track::new(PathBuf::from("lullaby.mp3"))]),
+            ]))
+            .with_ends(HashMap::from([(uid(&[1]), end)]));
+            let mut controller = MusicBoxController::new(library, player.clone()).with_rng_seed(3);
+            play_through(&mut controller, &player);
+            (player.calls(), controller.active().map(|(card, _)| card))
+        };
+        let play = |name: &str| Call::Play(PathBuf::from(name));
+
+        assert_eq!(
+            played(PlaylistEnd::Stop),
+            (
+                ["01.mp3", "02.mp3", "03.mp3", "04.mp3"].map(play).to_vec(),
+                None
+            )
+        );
+        assert_eq!(
+            played(PlaylistEnd::Repeat),
+            (
+                ["01.mp3", "02.mp3", "03.mp3", "04.mp3", "01.mp3", "02.mp3"]
+                    .map(play)
+                    .to_vec(),
+                Some(uid(&[1]))
+            )
+        );
+        let (calls, active) = played(PlaylistEnd::Continue(uid(&[2])));
+        assert_eq!(
+            calls,
+            ["01.mp3", "02.mp3", "03.mp3", "04.mp3", "lullaby.mp3"].map(play)
+        );
+        assert_eq!(active, None, "the lullaby card stops after its own track");
+
+        let (calls, active) = played(PlaylistEnd::ShuffleRepeat);
+        assert_eq!(active, Some(uid(&[1])));
+        assert_eq!(calls.len(), 6);
+        assert_ne!(calls[4], calls[5], "a reshuffled round has no repeats");
+    }
+
+    #[test]
+    fn chained_cards_stop_rather_than_loop_back() {
+        let player = MockPlayer::new();
+        let library = Library::from_playlists(
+            [1, 2, 3]
+                .map(|n| {
+                    (
+                        uid(&[n]),
+                        vec![Track::new(PathBuf::from(format!("part{n}.mp3")))],
+                    )
+                })
+                .into(),
+        )
+        .with_ends(HashMap::from([
+            (uid(&[1]), PlaylistEnd::Continue(uid(&[2]))),
+            (uid(&[2]), PlaylistEnd::Continue(uid(&[3]))),
+            (uid(&[3]), PlaylistEnd::Continue(uid(&[1]))),
+        ]));
+        let mut controller = MusicBoxController::new(library, player.clone());
+        play_through(&mut controller, &player);
+        let play = |name: &str| Call::Play(PathBuf::from(name));
+        assert_eq!(
+            player.calls(),
+            ["part1.mp3", "part2.mp3", "part3.mp3"].map(play)
+        );
+        assert_eq!(controller.active(), None);
+
+        // A tap starts a fresh chain.
+        controller.handle_card(&uid(&[2])).unwrap();
+        player.finish_track();
+        controller.poll_playback().unwrap();
+        assert_eq!(controller.active().map(|(card, _)| card), Some(uid(&[3])));
+    }
+
+    struct MockRecorder {
+        recording: bool,
+    }
+
+    impl Recorder for MockRecorder {
+        fn start(&mut self) -> Result<(), RecordError> {
+            self.recording = true;
+            Ok(())
+        }
+
+        fn finish(&mut self) -> Result<crate::memo::Clip, RecordError> {
+            if !std::mem::take(&mut self.recording) {
+                return Err(RecordError::NotRecording);
+            }
+            Ok(crate::memo::Clip {
+                sample_rate: 8_000,
+                channels: 1,
+                samples: vec![0; 8_000],
+            })
+        }
+    }
+
+    fn memo_controller(dir: &Path) -> (MusicBoxController<MockPlayer>, MockPlayer) {
+        let player = MockPlayer::new();
+        let library = library_with(vec![(uid(&[1]), "song.mp3")]);
+        let settings = MemoSettings {
+            card: uid(&[0xb0]),
+            dir: dir.to_path_buf(),
+            max_length: Duration::from_secs(30),
+        };
+        let controller = MusicBoxController::new(library, player.clone())
+            .with_recorder(Box::new(MockRecorder { recording: false }), settings);
+        (controller, player)
+    }
+
+    #[test]
+    fn record_card_saves_a_memo_onto_the_next_card() {
+        let dir = tempfile::tempdir().unwrap();
+        let (mut controller, player) = memo_controller(dir.path());
+        let memo = Track::new(dir.path().join("02.wav"));
+
+        controller.handle_card(&uid(&[1])).unwrap();
+        assert_eq!(
+            controller.handle_card(&uid(&[0xb0])).unwrap(),
+            ControllerAction::RecordArmed { card: uid(&[0xb0]) }
+        );
+        assert!(controller.active().is_none(), "music stops while recording");
+        assert_eq!(
+            controller.handle_card(&uid(&[2])).unwrap(),
+            ControllerAction::Recording { card: uid(&[2]) }
+        );
+        assert_eq!(
+            controller.handle_card(&uid(&[2])).unwrap(),
+            ControllerAction::MemoRecorded {
+                card: uid(&[2]),
+                track: memo.clone(),
+            }
+        );
+        assert_eq!(std::fs::metadata(memo.path()).unwrap().len(), 44 + 16_000);
+
+        assert!(matches!(
+            controller.handle_card(&uid(&[2])).unwrap(),
+            ControllerAction::Started { .. }
+        ));
+        assert_eq!(player.calls().last(), Some(&Call::Play(memo.path().into())));
+    }
+
+    #[test]
+    fn memos_never_replace_music_and_stop_at_their_limit() {
+        let dir = tempfile::tempdir().unwrap();
+        let (mut controller, _) = memo_controller(dir.path());
+
+        controller.handle_card(&uid(&[0xb0])).unwrap();
+        assert_eq!(
+            controller.handle_card(&uid(&[1])).unwrap(),
+            ControllerAction::Unchanged { card: uid(&[1]) }
+        );
+        assert!(!dir.path().join("01.wav").exists());
+
+        controller.handle_card(&uid(&[0xb0])).unwrap();
+        controller.handle_card(&uid(&[3])).unwrap();
+        let now = Instant::now();
+        assert_eq!(controller.poll_recording(now), None);
+        assert!(matches!(
+            controller.poll_recording(now + Duration::from_secs(31)),
+            Some(ControllerAction::MemoRecorded { .. })
+        ));
+        assert!(controller.library().playlist(&uid(&[3])).is_some());
+
+        controller.handle_card(&uid(&[0xb0])).unwrap();
+        assert_eq!(controller.poll_recording(now + ARM_TIMEOUT * 2), None);
+        assert!(matches!(
+            controller.handle_card(&uid(&[1])).unwrap(),
+            ControllerAction::Started { .. }
+        ));
+    }
+
+    #[test]
+    fn mute_card_and_button_silence_without_stopping() {
+        let player = MockPlayer::new();
+        let library = library_with(vec![(uid(&[1]), "song.mp3")])
+            .with_system_commands(HashMap::from([(uid(&[0xee]), SystemCommand::ToggleMute)]));
+        let mut controller = MusicBoxController::new(library, player.clone());
+
+        controller.handle_card(&uid(&[1])).unwrap();
+        assert_eq!(
+            controller.handle_card(&uid(&[0xee])).unwrap(),
+            ControllerAction::MuteToggled { muted: true }
+        );
+        assert!(controller.is_muted());
+        assert_eq!(controller.active().unwrap().0, uid(&[1]));
+        assert_eq!(
+            controller.handle_button(ButtonEvent::Mute).unwrap(),
+            Some(ControllerAction::MuteToggled { muted: false })
+        );
+        assert_eq!(
+            player.calls(),
+            vec![
+                Call::Play(PathBuf::from("song.mp3")),
+                Call::Mute(true),
+                Call::Mute(false),
+            ]
+        );
+    }
+
+    #[test]
+    fn a_second_combo_card_inside_the_window_plays_the_combo() {
+        let combo = Combo {
+            cards: [uid(&[1]), uid(&[0xc1])],
+            tracks: vec![Track::new("lion-de.mp3".into())],
+        };
+        let library = library_with(vec![(uid(&[1]), "lion.mp3"), (uid(&[2]), "other.mp3")])
+            .with_combos(vec![combo]);
+        let player = MockPlayer::new();
+        let mut controller = MusicBoxController::new(library.clone(), player.clone());
+
+        // The first card plays as usual; the language card only exists for
+        // combos, so on its own it does nothing.
+        controller.handle_card(&uid(&[1])).unwrap();
+        assert_eq!(
+            controller.handle_card(&uid(&[0xc1])).unwrap(),
+            ControllerAction::Switched {
+                from_card: uid(&[1]),
+                from_track: Track::new("lion.mp3".into()),
+                to_card: uid(&[0xc1]),
+                to_track: Track::new("lion-de.mp3".into()),
+            }
+        );
+        assert_eq!(
+            controller.handle_card(&uid(&[0xc1])).unwrap(),
+            ControllerAction::Stopped {
+                card: uid(&[0xc1]),
+                track: Track::new("lion-de.mp3".into()),
+            }
+        );
+        // Either order works.
+        assert_eq!(
+            controller.handle_card(&uid(&[1])).unwrap(),
+            ControllerAction::Started {
+                card: uid(&[1]),
+                track: Track::new("lion-de.mp3".into()),
+            }
+        );
+
+        // A card tapped in between breaks the combo.
+        controller.handle_card(&uid(&[2])).unwrap();
+        assert_eq!(
+            controller.handle_card(&uid(&[0xc1])).unwrap(),
+            ControllerAction::Unchanged { card: uid(&[0xc1]) }
+        );
+        controller.handle_card(&uid(&[2])).unwrap();
+        assert_eq!(
+            controller.handle_card(&uid(&[1])).unwrap(),
+            ControllerAction::Started {
+                card: uid(&[1]),
+                track: Track::new("lion.mp3".into()),
+            }
+        );
+
+        let mut slow =
+            MusicBoxController::new(library.with_combo_window(Duration::ZERO), MockPlayer::new());
+        slow.handle_card(&uid(&[1])).unwrap();
+        assert_eq!(
+            slow.handle_card(&uid(&[0xc1])).unwrap(),
+            ControllerAction::Unchanged { card: uid(&[0xc1]) }
+        );
+    }
+
+    #[test]
+    fn command_cards_drive_the_box_like_buttons() {
+        let commands = HashMap::from([
+            (uid(&[0xa1]), SystemCommand::VolumeDown),
+            (uid(&[0xa2]), SystemCommand::Next),
+            (uid(&[0xa3]), SystemCommand::PlayPause),
+            (uid(&[0xa4]), SystemCommand::Shutdown),
+            (uid(&[0xa5]), SystemCommand::ReloadConfig),
+        ]);
+        let library = Library::from_playlists(HashMap::from([(
+            uid(&[1]),
+            vec![Track::new("a.mp3".into()), Track::new("b.mp3".into())],
+        )]))
+        .with_system_commands(commands.clone())
+        .with_volume_step(0.25);
+        assert_eq!(
+            library.binding(&uid(&[0xa2])),
+            Some(CardBinding::System(SystemCommand::Next))
+        );
+        assert!(matches!(
+            library.binding(&uid(&[1])),
+            Some(CardBinding::Playlist([_, _]))
+        ));
+        assert_eq!(library.binding(&uid(&[2])), None);
+        let player = MockPlayer::new();
+        let mut controller = MusicBoxController::new(library, player.clone());
+
+        // Nothing is playing yet, so next has nothing to act on.
+        assert_eq!(
+            controller.handle_card(&uid(&[0xa2])).unwrap(),
+            ControllerAction::Unchanged { card: uid(&[0xa2]) }
+        );
+        controller.handle_card(&uid(&[1])).unwrap();
+        assert_eq!(
+            controller.handle_card(&uid(&[0xa1])).unwrap(),
+            ControllerAction::VolumeChanged { percent: 75 }
+        );
+        assert_eq!(
+            controller.handle_card(&uid(&[0xa2])).unwrap(),
+            ControllerAction::Skipped {
+                card: uid(&[1]),
+                track: Track::new("b.mp3".into()),
+            }
+        );
+        assert!(matches!(
+            controller.handle_card(&uid(&[0xa3])).unwrap(),
+            ControllerAction::Paused { .. }
+        ));
+        assert_eq!(
+            controller.handle_card(&uid(&[0xa4])).unwrap(),
+            ControllerAction::ShutdownRequested { card: uid(&[0xa4]) }
+        );
+        assert_eq!(
+            controller.handle_card(&uid(&[0xa5])).unwrap(),
+            ControllerAction::ReloadRequested { card: uid(&[0xa5]) }
+        );
+
+        // Without a volume control only the volume cards are ignored.
+        let library = library_with(vec![]).with_system_commands(commands);
+        let mut basic = MusicBoxController::new(library, MockPlayer::basic());
+        assert_eq!(
+            basic.handle_card(&uid(&[0xa1])).unwrap(),
+            ControllerAction::Unchanged { card: uid(&[0xa1]) }
+        );
+        assert_eq!(
+            basic.handle_card(&uid(&[0xa4])).unwrap(),
+            ControllerAction::ShutdownRequested { card: uid(&[0xa4]) }
+        );
     }
 
     #[test]
-    fn tapping_card_starts_associated_track() {
+    fn locked_cards_ignore_skips_until_the_parent_card_unlocks_them() {
+        let library = Library::from_playlists(HashMap::from([(
+            uid(&[1]),
+            vec![Track::new("ch1.mp3".into()), Track::new("ch2.mp3".into())],
+        )]))
+        .with_locked(HashSet::from([uid(&[1])]))
+        .with_system_commands(HashMap::from([
+            (uid(&[0xa2]), SystemCommand::Next),
+            (uid(&[0xaf]), SystemCommand::Unlock),
+        ]));
         let player = MockPlayer::new();
-        let library = library_with(vec![(uid(&[1, 2]), "song1.mp3")]);
         let mut controller = MusicBoxController::new(library, player.clone());
+        controller.handle_card(&uid(&[1])).unwrap();
 
-        let action = controller.handle_card(&uid(&[1, 2])).unwrap();
+        assert_eq!(controller.handle_button(ButtonEvent::Next).unwrap(), None);
+        assert_eq!(
+            controller.handle_card(&uid(&[0xa2])).unwrap(),
+            ControllerAction::Unchanged { card: uid(&[0xa2]) }
+        );
+        assert!(matches!(
+            controller.handle_button(ButtonEvent::PlayPause).unwrap(),
+            Some(ControllerAction::Paused { .. })
+        ));
 
         assert_eq!(
-            action,
+            controller.handle_card(&uid(&[0xaf])).unwrap(),
+            ControllerAction::Unlocked { card: uid(&[1]) }
+        );
+        assert_eq!(
+            controller.handle_card(&uid(&[0xaf])).unwrap(),
+            ControllerAction::Unchanged { card: uid(&[0xaf]) }
+        );
+        assert!(matches!(
+            controller.handle_button(ButtonEvent::Next).unwrap(),
+            Some(ControllerAction::Skipped { .. })
+        ));
+
+        // The lock comes back with the next tap of the card.
+        controller.handle_card(&uid(&[1])).unwrap();
+        controller.handle_card(&uid(&[1])).unwrap();
+        assert_eq!(
+            controller.handle_button(ButtonEvent::Previous).unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    fn night_mode_caps_the_volume_and_follows_its_schedule() {
+        let player = MockPlayer::new();
+        let night = NightSettings {
+            max_volume: 0.3,
+            schedule: NightSchedule::from_times((19, 0), (7, 0)),
+        };
+        let library = library_with(vec![(uid(&[1]), "song.mp3")])
+            .with_system_commands(HashMap::from([(
+                uid(&[0xee]),
+                SystemCommand::ToggleNightMode,
+            )]))
+            .with_night(night);
+        let mut controller = MusicBoxController::new(library, player.clone());
+        controller.set_volume(0.8).unwrap();
+
+        assert_eq!(
+            controller.handle_card(&uid(&[0xee])).unwrap(),
+            ControllerAction::NightModeToggled { on: true }
+        );
+        // Quieter requests still apply; louder ones stop at the cap.
+        controller.set_volume(0.2).unwrap();
+        controller.set_volume(0.9).unwrap();
+        assert_eq!(controller.volume(), 0.9);
+
+        // Midday: the first check only notes that the window is closed...
+        assert_eq!(controller.apply_night_schedule(12 * 60).unwrap(), None);
+        assert!(controller.is_night_mode());
+        // ...and the window opening finds night mode already on.
+        assert_eq!(controller.apply_night_schedule(19 * 60).unwrap(), None);
+        assert_eq!(
+            controller.apply_night_schedule(7 * 60).unwrap(),
+            Some(ControllerAction::NightModeToggled { on: false })
+        );
+        assert_eq!(
+            player.calls(),
+            vec![
+                Call::Volume(0.8),
+                Call::Night(true),
+                Call::Volume(0.3),
+                Call::Volume(0.2),
+                Call::Volume(0.3),
+                Call::Night(false),
+                Call::Volume(0.9),
+            ]
+        );
+    }
+
+    #[test]
+    fn first_tap_of_the_morning_plays_the_greeting_first() {
+        use crate::greeting::Greeting;
+
+        let greetings = Greetings {
+            morning: Some(Greeting {
+                track: Track::new(PathBuf::from("morning.mp3")),
+                window: GreetingPeriod::Morning.default_window(),
+            }),
+            evening: None,
+        };
+        let library = library_with(vec![(uid(&[1]), "song.mp3"), (uid(&[2]), "other.mp3")])
+            .with_greetings(greetings);
+        let new_controller = |player: &MockPlayer| {
+            MusicBoxController::new(library.clone(), player.clone())
+                .with_local_clock(|| 20_000 * 24 * 60 + 7 * 60)
+        };
+
+        let player = MockPlayer::new();
+        let mut controller = new_controller(&player);
+        assert_eq!(
+            controller.handle_card(&uid(&[1])).unwrap(),
             ControllerAction::Started {
-                card: uid(&[1, 2]),
-                track: Track::new(PathBuf::from("song1.mp3"))
+                card: uid(&[1]),
+                track: Track::new(PathBuf::from("song.mp3")),
             }
         );
-        assert_eq!(player.calls(), vec![Call::Play(PathBuf::from("song1.mp3"))]);
+        assert_eq!(controller.poll_playback().unwrap(), None);
+        player.finish_track();
+        // The greeting ending leads straight into the card's track.
+        assert_eq!(controller.poll_playback().unwrap(), None);
+        controller.handle_card(&uid(&[2])).unwrap();
+        assert_eq!(
+            player.calls(),
+            vec![
+                Call::Play(PathBuf::from("morning.mp3")),
+                Call::Play(PathBuf::from("song.mp3")),
+                Call::Stop,
+                Call::Play(PathBuf::from("other.mp3")),
+            ]
+        );
+
+        // Skipping or tapping the card again cuts the greeting short.
+        let player = MockPlayer::new();
+        let mut controller = new_controller(&player);
+        controller.handle_card(&uid(&[1])).unwrap();
+        assert_eq!(
+            controller.next_track().unwrap(),
+            Some(ControllerAction::Skipped {
+                card: uid(&[1]),
+                track: Track::new(PathBuf::from("song.mp3")),
+            })
+        );
+        let player = MockPlayer::new();
+        let mut controller = new_controller(&player);
+        controller.handle_card(&uid(&[2])).unwrap();
+        assert_eq!(
+            controller.handle_card(&uid(&[2])).unwrap(),
+            ControllerAction::Skipped {
+                card: uid(&[2]),
+                track: Track::new(PathBuf::from("other.mp3")),
+            }
+        );
+        assert_eq!(
+            player.calls(),
+            vec![
+                Call::Play(PathBuf::from("morning.mp3")),
+                Call::Play(PathBuf::from("other.mp3")),
+            ]
+        );
     }
 
     #[test]
-    fn tapping_same_card_stops_playback() {
-        let player = MockPlayer::new();
-        let library = library_with(vec![(uid(&[1, 2]), "song1.mp3")]);
+    fn missing_backend_features_are_skipped_rather_than_failing() {
+        let player = MockPlayer::basic();
+        let library = library_with(vec![(uid(&[1]), "song.mp3")])
+            .with_system_commands(HashMap::from([(uid(&[0xee]), SystemCommand::ToggleMute)]));
         let mut controller = MusicBoxController::new(library, player.clone());
 
-        controller.handle_card(&uid(&[1, 2])).unwrap();
-        let action = controller.handle_card(&uid(&[1, 2])).unwrap();
+        controller.handle_card(&uid(&[1])).unwrap();
+        assert_eq!(
+            controller.handle_button(ButtonEvent::VolumeUp).unwrap(),
+            None
+        );
+        assert_eq!(
+            controller.handle_card(&uid(&[0xee])).unwrap(),
+            ControllerAction::Unchanged { card: uid(&[0xee]) }
+        );
+        assert!(matches!(
+            controller.set_volume(0.5),
+            Err(ControllerError::Unsupported(_))
+        ));
+        assert!(!controller.is_muted());
+        assert_eq!(player.calls(), vec![Call::Play(PathBuf::from("song.mp3"))]);
+    }
+
+    fn audiobook_library() -> Library {
+        Library::from_playlists(HashMap::from([
+            (
+                uid(&[1]),
+                vec![
+                    Track::new(PathBuf::from("ch1.mp3")),
+                    Track::new(PathBuf::from("ch2.mp3")),
+                ],
+            ),
+            (uid(&[2]), vec![Track::new(PathBuf::from("song.mp3"))]),
+        ]))
+        .with_resumable(HashSet::from([uid(&[1])]))
+    }
+
+    #[test]
+    fn resumable_card_resumes_after_restart() {
+        let tmp = tempfile::tempdir().unwrap();
+        let state = tmp.path().join("resume.json");
+        let player = MockPlayer::new();
+        let mut controller = MusicBoxController::new(audiobook_library(), player.clone())
+            .with_resume_store(ResumeStore::load(&state).unwrap());
+
+        let start = Instant::now();
+        controller.handle_card(&uid(&[1])).unwrap();
+        controller.tick(start).unwrap();
+        player.finish_track();
+        controller.poll_playback().unwrap();
+        player.play_for(Duration::from_secs(42));
+        controller
+            .tick(start + RESUME_SAVE_INTERVAL + Duration::from_secs(42))
+            .unwrap();
+        // Power cut: the controller is dropped without stopping.
+        drop(controller);
+
+        let player = MockPlayer::new();
+        let mut controller = MusicBoxController::new(audiobook_library(), player.clone())
+            .with_resume_store(ResumeStore::load(&state).unwrap());
+        let started = controller.handle_card(&uid(&[1])).unwrap();
 
         assert_eq!(
-            action,
-            ControllerAction::Stopped {
-                card: uid(&[1, 2]),
-                track: Track::new(PathBuf::from("song1.mp3"))
+            started,
+            ControllerAction::Started {
+                card: uid(&[1]),
+                track: Track::new(PathBuf::from("ch2.mp3")),
             }
         );
         assert_eq!(
             player.calls(),
-            vec![Call::Play(PathBuf::from("song1.mp3")), Call::Stop,]
+            vec![
+                Call::Play(PathBuf::from("ch2.mp3")),
+                Call::Seek(Duration::from_secs(42)),
+            ]
         );
     }
 
     #[test]
-    fn tapping_different_card_switches_tracks() {
+    fn startup_resumes_the_last_resumable_card() {
+        let tmp = tempfile::tempdir().unwrap();
+        let state = tmp.path().join("resume.json");
+        let mut store = ResumeStore::load(&state).unwrap();
+        store
+            .set(
+                &uid(&[1]),
+                ResumePoint {
+                    track_index: 1,
+                    offset: Duration::from_secs(42),
+                },
+            )
+            .unwrap();
         let player = MockPlayer::new();
-        let library = library_with(vec![
-            (uid(&[1, 2]), "song1.mp3"),
-            (uid(&[3, 4]), "song2.mp3"),
-        ]);
+        let library = audiobook_library().with_startup(StartupAction::Resume);
+        let mut controller =
+            MusicBoxController::new(library, player.clone()).with_resume_store(store);
+
+        assert_eq!(
+            controller.start_up().unwrap(),
+            Some(ControllerAction::Started {
+                card: uid(&[1]),
+                track: Track::new(PathBuf::from("ch2.mp3")),
+            })
+        );
+        assert_eq!(
+            player.calls(),
+            vec![
+                Call::Play(PathBuf::from("ch2.mp3")),
+                Call::Seek(Duration::from_secs(42)),
+            ]
+        );
+
+        let player = MockPlayer::new();
+        let library = audiobook_library().with_startup(StartupAction::Resume);
         let mut controller = MusicBoxController::new(library, player.clone());
+        assert_eq!(controller.start_up().unwrap(), None, "no resume state");
+        assert!(player.calls().is_empty());
+    }
 
-        controller.handle_card(&uid(&[1, 2])).unwrap();
-        let action = controller.handle_card(&uid(&[3, 4])).unwrap();
+    #[test]
+    fn startup_plays_the_chime_or_starts_ambient_right_away() {
+        let player = MockPlayer::new();
+        let library = library_with(vec![(uid(&[1]), "song.mp3")])
+            .with_sounds(SoundCues {
+                startup: Some(Track::new(PathBuf::from("hello.mp3"))),
+                ..SoundCues::default()
+            })
+            .with_startup(StartupAction::Chime);
+        let mut controller = MusicBoxController::new(library, player.clone());
+        assert_eq!(controller.start_up().unwrap(), None);
+        assert_eq!(player.calls(), vec![Call::Play(PathBuf::from("hello.mp3"))]);
+        assert_eq!(controller.active(), None);
+
+        let player = MockPlayer::new();
+        let library = ambient_library().with_startup(StartupAction::Ambient);
+        let mut controller = MusicBoxController::new(library, player.clone());
+        controller.start_up().unwrap();
+        assert!(controller.ambient_playing());
+        assert!(
+            player
+                .calls()
+                .contains(&Call::Play(PathBuf::from("rain.ogg")))
+        );
+
+        let player = MockPlayer::new();
+        let mut controller = MusicBoxController::new(ambient_library(), player.clone());
+        assert_eq!(controller.start_up().unwrap(), None);
+        assert!(player.calls().is_empty(), "silent by default");
+    }
 
+    #[test]
+    fn resumable_card_saves_when_switched_away_and_restarts_once_finished() {
+        let tmp = tempfile::tempdir().unwrap();
+        let state = tmp.path().join("resume.json");
+        let player = MockPlayer::new();
+        let mut controller = MusicBoxController::new(audiobook_library(), player.clone())
+            .with_resume_store(ResumeStore::load(&state).unwrap());
+
+        controller.handle_card(&uid(&[1])).unwrap();
+        player.play_for(Duration::from_secs(7));
+        controller.handle_card(&uid(&[2])).unwrap();
+        player.play_for(Duration::from_secs(3));
+        controller.handle_card(&uid(&[1])).unwrap();
         assert_eq!(
-            action,
-            ControllerAction::Switched {
-                from_card: uid(&[1, 2]),
-                from_track: Track::new(PathBuf::from("song1.mp3")),
-                to_card: uid(&[3, 4]),
-                to_track: Track::new(PathBuf::from("song2.mp3")),
-            }
+            ResumeStore::load(&state).unwrap().get(&uid(&[2])),
+            None,
+            "cards without resume are not bookmarked"
         );
+
+        player.finish_track();
+        controller.poll_playback().unwrap();
+        player.finish_track();
+        controller.poll_playback().unwrap();
+        controller.handle_card(&uid(&[1])).unwrap();
+
         assert_eq!(
             player.calls(),
             vec![
-                Call::Play(PathBuf::from("song1.mp3")),
+                Call::Play(PathBuf::from("ch1.mp3")),
                 Call::Stop,
-                Call::Play(PathBuf::from("song2.mp3")),
+                Call::Play(PathBuf::from("song.mp3")),
+                Call::Stop,
+                Call::Play(PathBuf::from("ch1.mp3")),
+                Call::Seek(Duration::from_secs(7)),
+                Call::Play(PathBuf::from("ch2.mp3")),
+                Call::Play(PathBuf::from("ch1.mp3")),
             ]
         );
     }
 
     #[test]
-    fn unknown_card_causes_error_without_audio_calls() {
+    fn folder_entries_queue_audio_files_in_natural_order() {
+        let tmp = tempfile::tempdir().unwrap();
+        for name in ["10 Ende.mp3", "2 Mitte.mp3", "1 Anfang.MP3", "cover.jpg"] {
+            std::fs::write(tmp.path().join(name), b"").unwrap();
+        }
+        std::fs::create_dir(tmp.path().join("extras")).unwrap();
         let player = MockPlayer::new();
-        let library = library_with(vec![(uid(&[1, 2]), "song1.mp3")]);
+        let library = library_with(vec![(uid(&[1]), tmp.path().to_str().unwrap())]);
         let mut controller = MusicBoxController::new(library, player.clone());
 
-        let err = controller.handle_card(&uid(&[9, 9])).unwrap_err();
+        controller.handle_card(&uid(&[1])).unwrap();
+        player.finish_track();
+        controller.poll_playback().unwrap();
+        player.finish_track();
+        controller.poll_playback().unwrap();
+
+        assert_eq!(
+            player.calls(),
+            vec![
+                Call::Play(tmp.path().join("1 Anfang.MP3")),
+                Call::Play(tmp.path().join("2 Mitte.mp3")),
+                Call::Play(tmp.path().join("10 Ende.mp3")),
+            ]
+        );
+    }
+
+    #[test]
+    fn empty_folder_reports_missing_track() {
+        let tmp = tempfile::tempdir().unwrap();
+        let library = library_with(vec![(uid(&[1]), tmp.path().to_str().unwrap())]);
+        let mut controller = MusicBoxController::new(library, MockPlayer::new());
+
+        let err = controller.handle_card(&uid(&[1])).unwrap_err();
 
         assert!(matches!(err, ControllerError::TrackNotFound));
+    }
+
+    fn ambient_library() -> Library {
+        library_with(vec![(uid(&[1]), "song.mp3")]).with_ambient(Some(AmbientPolicy {
+            tracks: vec![
+                Track::new(PathBuf::from("rain.ogg")),
+                Track::new(PathBuf::from("waves.ogg")),
+            ],
+            idle_after: Duration::from_secs(600),
+            volume: 0.25,
+        }))
+    }
+
+    #[test]
+    fn ambient_playlist_starts_after_silence_and_loops() {
+        let player = MockPlayer::new();
+        let mut controller = MusicBoxController::new(ambient_library(), player.clone());
+        let start = Instant::now();
+
+        controller.tick(start).unwrap();
+        controller.tick(start + Duration::from_secs(599)).unwrap();
         assert!(player.calls().is_empty());
+
+        controller.tick(start + Duration::from_secs(600)).unwrap();
+        player.finish_track();
+        controller.tick(start + Duration::from_secs(700)).unwrap();
+        player.finish_track();
+        controller.tick(start + Duration::from_secs(800)).unwrap();
+
+        assert!(controller.ambient_playing());
+        assert_eq!(
+            player.calls(),
+            vec![
+                Call::Volume(0.25),
+                Call::Play(PathBuf::from("rain.ogg")),
+                Call::Play(PathBuf::from("waves.ogg")),
+                Call::Play(PathBuf::from("rain.ogg")),
+            ]
+        );
     }
 
     #[test]
-    fn pause_playback_stops_active_track() {
+    fn card_tap_stops_ambient_and_restores_volume() {
         let player = MockPlayer::new();
-        let library = library_with(vec![(uid(&[1, 2]), "song1.mp3")]);
-        let mut controller = MusicBoxController::new(library, player.clone());
+        let mut controller = MusicBoxController::new(ambient_library(), player.clone());
+        let start = Instant::now();
+        controller.tick(start).unwrap();
+        controller.tick(start + Duration::from_secs(600)).unwrap();
 
-        controller.handle_card(&uid(&[1, 2])).unwrap();
-        let result = controller.pause_playback().unwrap();
+        let action = controller.handle_card(&uid(&[1])).unwrap();
 
+        assert!(matches!(action, ControllerAction::Started { .. }));
+        assert!(!controller.ambient_playing());
         assert_eq!(
-            result,
-            Some(ControllerAction::Stopped {
-                card: uid(&[1, 2]),
-                track: Track::new(PathBuf::from("song1.mp3")),
-            })
+            player.calls()[2..],
+            [
+                Call::Stop,
+                Call::Volume(1.0),
+                Call::Play(PathBuf::from("song.mp3")),
+            ]
         );
+    }
+
+    #[test]
+    fn volume_is_clamped_and_scales_ambient_playback() {
+        let player = MockPlayer::new();
+        let mut controller = MusicBoxController::new(ambient_library(), player.clone());
+        let start = Instant::now();
+
+        assert_eq!(controller.set_volume(1.5).unwrap(), 1.0);
+        assert_eq!(controller.adjust_volume(-0.5).unwrap(), 0.5);
+        controller.tick(start).unwrap();
+        controller.tick(start + Duration::from_secs(600)).unwrap();
+        assert_eq!(controller.adjust_volume(-0.75).unwrap(), 0.0);
+        controller.set_volume(0.5).unwrap();
+        controller.handle_card(&uid(&[1])).unwrap();
+
+        assert_eq!(controller.volume(), 0.5);
         assert_eq!(
             player.calls(),
-            vec![Call::Play(PathBuf::from("song1.mp3")), Call::Stop]
+            vec![
+                Call::Volume(1.0),
+                Call::Volume(0.5),
+                Call::Volume(0.125),
+                Call::Play(PathBuf::from("rain.ogg")),
+                Call::Volume(0.0),
+                Call::Volume(0.125),
+                Call::Stop,
+                Call::Volume(0.5),
+                Call::Play(PathBuf::from("song.mp3")),
+            ]
         );
-        assert!(controller.active().is_none());
     }
 
     #[test]
-    fn pause_playback_noops_when_idle() {
+    fn playback_resets_the_silence_timer() {
         let player = MockPlayer::new();
-        let library = library_with(vec![(uid(&[1, 2]), "song1.mp3")]);
+        let mut controller = MusicBoxController::new(ambient_library(), player.clone());
+        let start = Instant::now();
+        controller.tick(start).unwrap();
+        controller.handle_card(&uid(&[1])).unwrap();
+        controller.tick(start + Duration::from_secs(500)).unwrap();
+        controller.handle_card(&uid(&[1])).unwrap();
+
+        controller.tick(start + Duration::from_secs(700)).unwrap();
+        controller.tick(start + Duration::from_secs(1000)).unwrap();
+
+        assert!(!controller.ambient_playing());
+    }
+
+    #[test]
+    fn peek_reports_mapping_without_playing() {
+        let player = MockPlayer::new();
+        let library = library_with(vec![(uid(&[1]), "one.mp3"), (uid(&[2]), "two.mp3")]);
         let mut controller = MusicBoxController::new(library, player.clone());
+        controller.handle_card(&uid(&[1])).unwrap();
 
-        let result = controller.pause_playback().unwrap();
+        assert_eq!(
+            controller.peek(&uid(&[2])),
+            Some(Track::new(PathBuf::from("two.mp3")))
+        );
+        assert_eq!(controller.peek(&uid(&[3])), None);
+        assert_eq!(controller.active().unwrap().0, uid(&[1]));
+        assert_eq!(player.calls(), vec![Call::Play(PathBuf::from("one.mp3"))]);
+    }
 
-        assert!(result.is_none());
-        assert!(player.calls().is_empty());
+    #[test]
+    fn unmapped_card_plays_track_from_ndef_text() {
+        let player = MockPlayer::new();
+        let library =
+            library_with(vec![(uid(&[1]), "/music/one.mp3")]).with_music_dir("/music".into());
+        let mut controller = MusicBoxController::new(library, player.clone());
+
+        let action = controller
+            .handle_tap(&uid(&[9]), Some("tags/lullaby.mp3"))
+            .unwrap();
+        assert_eq!(
+            action,
+            ControllerAction::Started {
+                card: uid(&[9]),
+                track: Track::new(PathBuf::from("/music/tags/lullaby.mp3")),
+            }
+        );
+
+        // Mapped UIDs win over whatever the tag says.
+        controller
+            .handle_tap(&uid(&[1]), Some("tags/lullaby.mp3"))
+            .unwrap();
+        assert_eq!(
+            player.calls().last(),
+            Some(&Call::Play(PathBuf::from("/music/one.mp3")))
+        );
+    }
+
+    #[test]
+    fn ndef_text_cannot_escape_music_dir() {
+        let library = Library::default().with_music_dir("/music".into());
+
+        assert_eq!(library.confined_track("/etc/passwd"), None);
+        assert_eq!(library.confined_track("../secret.mp3"), None);
+        assert_eq!(library.confined_track("  "), None);
+        assert_eq!(
+            library.confined_track("a/b.mp3"),
+            Some(Track::new(PathBuf::from("/music/a/b.mp3")))
+        );
+
+        let mut controller = MusicBoxController::new(library, MockPlayer::new());
+        assert_eq!(
+            controller
+                .handle_tap(&uid(&[9]), Some("../secret.mp3"))
+                .unwrap(),
+            ControllerAction::UnknownCard { card: uid(&[9]) }
+        );
     }
 
     #[test]
@@ -466,4 +4779,67 @@ mod tests {
             ]
         );
     }
+
+    #[test]
+    fn output_changes_are_checked_now_and_then() {
+        let player = MockPlayer::new();
+        let mut controller = MusicBoxController::new(library_with(vec![]), player.clone());
+        let now = Instant::now();
+
+        assert_eq!(controller.poll_output(now), None);
+        *player.output.borrow_mut() = Some(OutputChange::Opened("usb".to_string()));
+        assert_eq!(controller.poll_output(now + Duration::from_secs(1)), None);
+        assert_eq!(
+            controller.poll_output(now + OUTPUT_CHECK_INTERVAL),
+            Some(ControllerAction::OutputChanged {
+                change: OutputChange::Opened("usb".to_string())
+            })
+        );
+        assert_eq!(
+            controller.poll_output(now + OUTPUT_CHECK_INTERVAL * 2),
+            None
+        );
+    }
+
+    #[test]
+    fn learning_mode_notes_unknown_cards_until_they_are_mapped() {
+        let player = MockPlayer::new();
+        let library = library_with(vec![(uid(&[1]), "song.mp3")]).with_effects(Effects {
+            unknown_card: Some(Track::new(PathBuf::from("huh.wav"))),
+            ..Effects::default()
+        });
+        let mut controller = MusicBoxController::new(library, player.clone());
+
+        assert_eq!(
+            controller.handle_tap(&uid(&[7]), None).unwrap(),
+            ControllerAction::UnknownCard { card: uid(&[7]) }
+        );
+        assert!(controller.learned_cards().is_empty());
+
+        controller.set_learning(true);
+        for card in [uid(&[7]), uid(&[8]), uid(&[7])] {
+            assert_eq!(
+                controller.handle_tap(&card, None).unwrap(),
+                ControllerAction::CardLearned { card }
+            );
+        }
+        assert_eq!(controller.learned_cards(), vec![uid(&[7]), uid(&[8])]);
+        assert!(matches!(
+            controller.handle_card(&uid(&[9])),
+            Err(ControllerError::TrackNotFound)
+        ));
+        assert_eq!(
+            player
+                .calls()
+                .iter()
+                .filter(|call| matches!(call, Call::Effect(_)))
+                .count(),
+            4
+        );
+
+        let mut cards = controller.library().entries();
+        cards.push((uid(&[7]), Track::new(PathBuf::from("new.mp3"))));
+        controller.replace_library(Library::new(cards.into_iter().collect()));
+        assert_eq!(controller.learned_cards(), vec![uid(&[8])]);
+    }
 }
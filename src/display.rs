@@ -1,8 +1,11 @@
 use crate::{
     controller::{CardUid, ControllerAction, Track},
-    telemetry::StatusSnapshot,
+    locale::{Locale, Strings},
+    telemetry::{DisplayHealth, SharedStatus, StatusSnapshot},
 };
-use std::time::{Duration, SystemTime};
+use std::cell::Cell;
+use std::panic::{self, AssertUnwindSafe};
+use std::time::{Duration, Instant, SystemTime};
 use thiserror::Error;
 
 /// Errors that can occur while interacting with a status display backend.
@@ -14,6 +17,8 @@ pub enum DisplayError {
     #[cfg(feature = "waveshare-display")]
     #[error(transparent)]
     Waveshare(#[from] waveshare::WaveshareError),
+    #[error(transparent)]
+    Remote(#[from] remote::RemoteDisplayError),
 }
 
 /// Render the latest controller status to an external display.
@@ -23,6 +28,12 @@ pub trait StatusDisplay: Send {
     fn shutdown(&mut self) -> Result<(), DisplayError> {
         Ok(())
     }
+
+    /// Shows a last-words error screen, e.g. from the panic hook right before
+    /// the process exits. Backends without a useful rendering may ignore it.
+    fn show_error(&mut self, _message: &str) -> Result<(), DisplayError> {
+        Ok(())
+    }
 }
 
 /// A no-op display backend used when no hardware is configured.
@@ -35,37 +46,266 @@ impl StatusDisplay for NullDisplay {
     }
 }
 
-/// Returns human-readable status lines describing the current controller state.
-pub fn status_lines(snapshot: &StatusSnapshot) -> Vec<String> {
-    let idle_line = format!("Idle polls: {}", snapshot.idle_events);
+/// Builds a display backend; called again whenever the guard re-initializes.
+pub type DisplayFactory = Box<dyn FnMut() -> Result<Box<dyn StatusDisplay>, DisplayError> + Send>;
 
-    let (state, active_card, active_track) = match snapshot.last_action.as_ref() {
-        Some(ControllerAction::Started { card, track }) => {
-            ("Playing".to_string(), Some(card), Some(track))
+/// How tolerant [`GuardedDisplay`] is of a misbehaving backend.
+#[derive(Debug, Clone, Copy)]
+pub struct DisplayGuardSettings {
+    /// Consecutive update errors tolerated before the display is disabled.
+    pub error_threshold: u32,
+    /// How long a disabled display waits before re-initialization is tried.
+    pub reinit_interval: Duration,
+}
+
+impl Default for DisplayGuardSettings {
+    fn default() -> Self {
+        Self {
+            error_threshold: 3,
+            reinit_interval: Duration::from_secs(60),
         }
+    }
+}
+
+/// Wraps a display backend so rendering trouble never reaches the caller.
+///
+/// Panics are caught (so the surrounding mutex is never poisoned) and disable
+/// the display at once; errors disable it after
+/// [`DisplayGuardSettings::error_threshold`] consecutive failures. While
+/// disabled, updates are dropped silently and the backend is rebuilt through
+/// the factory every [`DisplayGuardSettings::reinit_interval`]. Transitions
+/// are logged once and recorded in [`SharedStatus`].
+pub struct GuardedDisplay {
+    factory: DisplayFactory,
+    device: Option<Box<dyn StatusDisplay>>,
+    settings: DisplayGuardSettings,
+    status: SharedStatus,
+    consecutive_errors: u32,
+    disabled_at: Option<Instant>,
+}
+
+impl GuardedDisplay {
+    /// Initializes the backend right away; if that fails the guard starts
+    /// disabled and retries later.
+    pub fn new(
+        factory: DisplayFactory,
+        settings: DisplayGuardSettings,
+        status: SharedStatus,
+    ) -> Self {
+        let mut guard = Self {
+            factory,
+            device: None,
+            settings,
+            status,
+            consecutive_errors: 0,
+            disabled_at: None,
+        };
+        guard.reinit();
+        guard
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.device.is_some()
+    }
+
+    fn reinit(&mut self) {
+        match run_guarded(|| (self.factory)()) {
+            Ok(device) => {
+                tracing::info!("status display initialized");
+                self.device = Some(device);
+                self.consecutive_errors = 0;
+                self.disabled_at = None;
+                self.status.record_display_health(DisplayHealth::Active);
+            }
+            Err(failure) => self.disable(failure.to_string()),
+        }
+    }
+
+    fn disable(&mut self, reason: String) {
+        if self.device.is_some() || self.disabled_at.is_none() {
+            tracing::warn!(%reason, "status display disabled");
+        }
+        self.device = None;
+        self.disabled_at = Some(Instant::now());
+        self.status
+            .record_display_health(DisplayHealth::Disabled { reason });
+    }
+}
+
+impl StatusDisplay for GuardedDisplay {
+    fn update(&mut self, snapshot: &StatusSnapshot) -> Result<(), DisplayError> {
+        if self.device.is_none() {
+            let due = self
+                .disabled_at
+                .is_none_or(|at| at.elapsed() >= self.settings.reinit_interval);
+            if !due {
+                return Ok(());
+            }
+            self.reinit();
+        }
+        let Some(device) = self.device.as_mut() else {
+            return Ok(());
+        };
+
+        match run_guarded(|| device.update(snapshot)) {
+            Ok(()) => self.consecutive_errors = 0,
+            Err(failure @ BackendFailure::Panicked(_)) => self.disable(failure.to_string()),
+            Err(failure) => {
+                self.consecutive_errors += 1;
+                if self.consecutive_errors >= self.settings.error_threshold {
+                    self.disable(failure.to_string());
+                } else if self.consecutive_errors == 1 {
+                    tracing::warn!(%failure, "status display update failed");
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn shutdown(&mut self) -> Result<(), DisplayError> {
+        if let Some(device) = self.device.as_mut()
+            && let Err(failure) = run_guarded(|| device.shutdown())
+        {
+            tracing::warn!(%failure, "status display shutdown failed");
+        }
+        Ok(())
+    }
+
+    fn show_error(&mut self, message: &str) -> Result<(), DisplayError> {
+        if let Some(device) = self.device.as_mut()
+            && let Err(failure) = run_guarded(|| device.show_error(message))
+        {
+            tracing::warn!(%failure, "status display error screen failed");
+        }
+        Ok(())
+    }
+}
+
+thread_local! {
+    static GUARDED_CALL: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Whether the current thread is inside a [`GuardedDisplay`] backend call,
+/// whose panics are caught and handled. Panic hooks run before unwinding, so
+/// they use this to leave contained display panics alone.
+pub fn panic_is_contained() -> bool {
+    GUARDED_CALL.with(Cell::get)
+}
+
+/// Why a guarded backend call did not succeed.
+#[derive(Debug, Error)]
+enum BackendFailure {
+    #[error(transparent)]
+    Error(#[from] DisplayError),
+    #[error("display backend panicked: {0}")]
+    Panicked(String),
+}
+
+/// Runs a backend call, catching panics so they surface like errors.
+fn run_guarded<T>(call: impl FnOnce() -> Result<T, DisplayError>) -> Result<T, BackendFailure> {
+    let outer = GUARDED_CALL.with(|flag| flag.replace(true));
+    let outcome = panic::catch_unwind(AssertUnwindSafe(call));
+    GUARDED_CALL.with(|flag| flag.set(outer));
+    match outcome {
+        Ok(result) => Ok(result?),
+        Err(payload) => {
+            let message = payload
+                .downcast_ref::<&str>()
+                .map(|msg| msg.to_string())
+                .or_else(|| payload.downcast_ref::<String>().cloned())
+                .unwrap_or_else(|| "unknown panic".to_string());
+            Err(BackendFailure::Panicked(message))
+        }
+    }
+}
+
+/// Lines for the error screen shown when the process is about to restart.
+pub fn error_lines(message: &str, locale: Locale) -> Vec<String> {
+    const WIDTH: usize = 24;
+    let strings = locale.strings();
+    let mut lines = vec![
+        strings.title.to_string(),
+        strings.error_restarting.to_string(),
+    ];
+    let words = message.split_whitespace().collect::<Vec<_>>();
+    let mut current = String::new();
+    for word in words {
+        if !current.is_empty() && current.chars().count() + 1 + word.chars().count() > WIDTH {
+            lines.push(std::mem::take(&mut current));
+        }
+        if !current.is_empty() {
+            current.push(' ');
+        }
+        current.push_str(word);
+    }
+    if !current.is_empty() {
+        lines.push(current);
+    }
+    lines.truncate(6);
+    lines
+}
+
+/// Returns human-readable status lines describing the current controller
+/// state. Once the cards have loaded, their count and the missing files
+/// take the place of the idle poll counter.
+pub fn status_lines(snapshot: &StatusSnapshot, locale: Locale) -> Vec<String> {
+    let strings = locale.strings();
+    let idle_line = match snapshot.library {
+        Some(library) => (strings.library)(library.cards, library.missing_tracks),
+        None => format!("{}: {}", strings.idle_polls, snapshot.idle_events),
+    };
+
+    let (state, active_card, active_track) = match snapshot.last_action.as_ref() {
+        Some(
+            ControllerAction::Started { card, track }
+            | ControllerAction::Advanced { card, track }
+            | ControllerAction::Skipped { card, track }
+            | ControllerAction::Resumed { card, track },
+        ) => (strings.playing, Some(card), Some(track)),
+        Some(ControllerAction::Paused { card, track }) => (strings.paused, Some(card), Some(track)),
         Some(ControllerAction::Switched {
             to_card, to_track, ..
-        }) => ("Switched".to_string(), Some(to_card), Some(to_track)),
-        Some(ControllerAction::Stopped { .. }) => ("Stopped".to_string(), None, None),
-        None => ("Waiting".to_string(), None, None),
+        }) => (strings.switched, Some(to_card), Some(to_track)),
+        Some(ControllerAction::Stopped { .. } | ControllerAction::CountdownFinished { .. }) => {
+            (strings.stopped, None, None)
+        }
+        Some(ControllerAction::TimedOut { .. }) => (strings.timed_out, None, None),
+        Some(ControllerAction::RecordArmed { .. }) => (strings.recording, None, None),
+        Some(ControllerAction::Recording { card }) => (strings.recording, Some(card), None),
+        Some(ControllerAction::CardLearned { card }) => (strings.learned, Some(card), None),
+        Some(ControllerAction::MemoRecorded { card, track }) => {
+            (strings.waiting, Some(card), Some(track))
+        }
+        Some(
+            ControllerAction::Unchanged { .. }
+            | ControllerAction::MuteToggled { .. }
+            | ControllerAction::NightModeToggled { .. }
+            | ControllerAction::VolumeChanged { .. }
+            | ControllerAction::ShutdownRequested { .. }
+            | ControllerAction::ReloadRequested { .. }
+            | ControllerAction::Unlocked { .. }
+            | ControllerAction::UnknownCard { .. }
+            | ControllerAction::OutputChanged { .. },
+        )
+        | None => (strings.waiting, None, None),
     };
 
-    let card_line = format!("Card: {}", format_card(active_card));
-    let track_line = format!("Track: {}", format_track(active_track));
+    let card_line = format!("{}: {}", strings.card, format_card(active_card));
+    let track_line = format!("{}: {}", strings.track, format_track(active_track));
 
-    let updated_line = snapshot
+    let age = snapshot
         .last_update
         .and_then(|instant| SystemTime::now().duration_since(instant).ok())
-        .map(format_update_age)
-        .unwrap_or_else(|| "Updated: –".to_string());
+        .map(|delta| format_update_age(delta, strings))
+        .unwrap_or_else(|| "–".to_string());
 
     vec![
-        "Musicbox".to_string(),
-        format!("State: {state}"),
+        strings.title.to_string(),
+        format!("{}: {state}", strings.state),
         idle_line,
         card_line,
         track_line,
-        updated_line,
+        format!("{}: {age}", strings.updated),
     ]
 }
 
@@ -86,73 +326,113 @@ fn format_track(track: Option<&Track>) -> String {
     }
 }
 
-fn format_update_age(delta: Duration) -> String {
+fn format_update_age(delta: Duration, strings: &Strings) -> String {
     if delta.as_secs() == 0 {
-        "Updated: just now".to_string()
+        strings.just_now.to_string()
     } else {
-        format!("Updated: {}s ago", delta.as_secs())
+        (strings.seconds_ago)(delta.as_secs())
     }
 }
 
-#[cfg(all(feature = "waveshare-display", target_os = "linux"))]
+#[cfg(feature = "waveshare-display")]
 pub mod waveshare {
-    use super::{DisplayError, StatusDisplay, status_lines};
+    use super::{DisplayError, StatusDisplay, error_lines, status_lines};
+    use crate::hal::{self, HalError, InputPin, OutputPin, SpiBus};
+    use crate::locale::Locale;
     use crate::telemetry::StatusSnapshot;
     use embedded_graphics::{
-        mono_font::{MonoTextStyleBuilder, ascii::FONT_9X15_BOLD},
+        mono_font::{MonoTextStyleBuilder, iso_8859_1::FONT_9X15_BOLD},
         prelude::*,
         text::{Baseline, Text},
     };
+    use embedded_hal::{delay::DelayNs, digital, spi};
     use epd_waveshare::{
         epd2in13_v2::{Display2in13, Epd2in13},
         prelude::{Color, DisplayRotation, WaveshareDisplay as EpdDriver},
     };
-    use gpio_cdev::{Chip, LineRequestFlags};
-    use linux_embedded_hal::{
-        CdevPin, Delay, SpidevDevice,
-        spidev::{SpiModeFlags, Spidev, SpidevOptions},
-    };
-    use std::{io, path::Path};
+    use std::{io, path::Path, time::Duration};
     use thiserror::Error;
 
-    type BusyPin = CdevPin;
-    type DcPin = CdevPin;
-    type RstPin = CdevPin;
     const GPIO_CONSUMER_TAG: &str = "musicbox-waveshare";
 
-    fn open_spi(path: &Path, speed_hz: u32) -> Result<SpidevDevice, io::Error> {
-        let mut spi = Spidev::open(path)?;
-        let options = SpidevOptions::new()
-            .bits_per_word(8)
-            .max_speed_hz(speed_hz)
-            .mode(SpiModeFlags::SPI_MODE_0)
-            .build();
-        spi.configure(&options)?;
-        Ok(SpidevDevice(spi))
-    }
-
     fn to_line_offset(pin: u64) -> Result<u32, WaveshareError> {
         u32::try_from(pin).map_err(|_| WaveshareError::PinOutOfRange(pin))
     }
 
-    fn request_input_pin(
-        chip: &mut Chip,
-        offset: u32,
-    ) -> Result<CdevPin, gpio_cdev::errors::Error> {
-        let line = chip.get_line(offset)?;
-        let handle = line.request(LineRequestFlags::INPUT, 0, GPIO_CONSUMER_TAG)?;
-        CdevPin::new(handle)
+    impl spi::Error for HalError {
+        fn kind(&self) -> spi::ErrorKind {
+            spi::ErrorKind::Other
+        }
+    }
+
+    impl digital::Error for HalError {
+        fn kind(&self) -> digital::ErrorKind {
+            digital::ErrorKind::Other
+        }
+    }
+
+    /// The [`hal`] bus as the `embedded-hal` device the driver expects.
+    /// The panel is only ever written to.
+    struct Spi(Box<dyn SpiBus>);
+
+    impl spi::ErrorType for Spi {
+        type Error = HalError;
+    }
+
+    impl spi::SpiDevice for Spi {
+        fn transaction(
+            &mut self,
+            operations: &mut [spi::Operation<'_, u8>],
+        ) -> Result<(), HalError> {
+            for operation in operations {
+                match operation {
+                    spi::Operation::Write(bytes) => self.0.write(bytes)?,
+                    spi::Operation::DelayNs(ns) => Delay.delay_ns(*ns),
+                    _ => return Err(HalError::Unsupported("reading from the display")),
+                }
+            }
+            Ok(())
+        }
+    }
+
+    struct Input(Box<dyn InputPin>);
+
+    impl digital::ErrorType for Input {
+        type Error = HalError;
+    }
+
+    impl digital::InputPin for Input {
+        fn is_high(&mut self) -> Result<bool, HalError> {
+            self.0.is_high()
+        }
+
+        fn is_low(&mut self) -> Result<bool, HalError> {
+            self.0.is_high().map(|high| !high)
+        }
+    }
+
+    struct Output(Box<dyn OutputPin>);
+
+    impl digital::ErrorType for Output {
+        type Error = HalError;
     }
 
-    fn request_output_pin(
-        chip: &mut Chip,
-        offset: u32,
-        initial_high: bool,
-    ) -> Result<CdevPin, gpio_cdev::errors::Error> {
-        let line = chip.get_line(offset)?;
-        let initial_value = if initial_high { 1 } else { 0 };
-        let handle = line.request(LineRequestFlags::OUTPUT, initial_value, GPIO_CONSUMER_TAG)?;
-        CdevPin::new(handle)
+    impl digital::OutputPin for Output {
+        fn set_low(&mut self) -> Result<(), HalError> {
+            self.0.set(false)
+        }
+
+        fn set_high(&mut self) -> Result<(), HalError> {
+            self.0.set(true)
+        }
+    }
+
+    struct Delay;
+
+    impl DelayNs for Delay {
+        fn delay_ns(&mut self, ns: u32) {
+            std::thread::sleep(Duration::from_nanos(u64::from(ns)));
+        }
     }
 
     /// Configuration for the Waveshare E-Ink HAT wiring and SPI bus.
@@ -165,6 +445,7 @@ pub mod waveshare {
         pub spi_speed_hz: u32,
         pub rotation: DisplayRotation,
         pub gpio_chip_path: String,
+        pub locale: Locale,
     }
 
     impl Default for WaveshareConfig {
@@ -177,6 +458,7 @@ pub mod waveshare {
                 spi_speed_hz: 8_000_000,
                 rotation: DisplayRotation::Rotate270,
                 gpio_chip_path: "/dev/gpiochip0".to_string(),
+                locale: Locale::default(),
             }
         }
     }
@@ -184,10 +466,8 @@ pub mod waveshare {
     /// Errors from initializing or updating the Waveshare display.
     #[derive(Debug, Error)]
     pub enum WaveshareError {
-        #[error("SPI error: {0}")]
-        Spi(#[from] io::Error),
-        #[error("GPIO error: {0}")]
-        Gpio(#[from] gpio_cdev::errors::Error),
+        #[error(transparent)]
+        Hal(#[from] HalError),
         #[error("GPIO pin {0} is out of range for this platform")]
         PinOutOfRange(u64),
         #[error("display driver error: {0}")]
@@ -196,44 +476,53 @@ pub mod waveshare {
 
     /// Renderer that targets the Waveshare 2.13\" e-ink HAT.
     pub struct WaveshareDisplay {
-        spi: SpidevDevice,
-        epd: Epd2in13<SpidevDevice, BusyPin, DcPin, RstPin, Delay>,
+        spi: Spi,
+        epd: Epd2in13<Spi, Input, Output, Output, Delay>,
         delay: Delay,
         rotation: DisplayRotation,
         last_lines: Option<Vec<String>>,
+        locale: Locale,
     }
 
     impl WaveshareDisplay {
         pub fn new(config: WaveshareConfig) -> Result<Self, WaveshareError> {
             let spi_path = Path::new(&config.spi_path);
             if !spi_path.exists() {
-                return Err(WaveshareError::Spi(io::Error::new(
+                return Err(HalError::Spi(io::Error::new(
                     io::ErrorKind::NotFound,
                     format!("SPI device {} not found", config.spi_path),
-                )));
+                ))
+                .into());
             }
 
-            let mut spi = open_spi(spi_path, config.spi_speed_hz)?;
-
-            let mut chip =
-                Chip::new(Path::new(&config.gpio_chip_path)).map_err(WaveshareError::Gpio)?;
-            let busy_offset = to_line_offset(config.busy_pin)?;
-            let dc_offset = to_line_offset(config.dc_pin)?;
-            let rst_offset = to_line_offset(config.reset_pin)?;
+            let spi = hal::open_spi(spi_path, config.spi_speed_hz)?;
 
-            let busy = request_input_pin(&mut chip, busy_offset).map_err(WaveshareError::Gpio)?;
-            let dc =
-                request_output_pin(&mut chip, dc_offset, false).map_err(WaveshareError::Gpio)?;
-            let rst =
-                request_output_pin(&mut chip, rst_offset, true).map_err(WaveshareError::Gpio)?;
+            let mut gpio = hal::open_gpio(Path::new(&config.gpio_chip_path))?;
+            let busy = gpio.input(to_line_offset(config.busy_pin)?, GPIO_CONSUMER_TAG)?;
+            let dc = gpio.output(to_line_offset(config.dc_pin)?, false, GPIO_CONSUMER_TAG)?;
+            let rst = gpio.output(to_line_offset(config.reset_pin)?, true, GPIO_CONSUMER_TAG)?;
+            Self::with_pins(spi, busy, dc, rst, config)
+        }
 
+        /// Drives a panel on an SPI bus and pins that are already open,
+        /// such as [`hal::mock`] ones. `config` only supplies the rotation
+        /// and locale.
+        pub fn with_pins(
+            spi: Box<dyn SpiBus>,
+            busy: Box<dyn InputPin>,
+            dc: Box<dyn OutputPin>,
+            rst: Box<dyn OutputPin>,
+            config: WaveshareConfig,
+        ) -> Result<Self, WaveshareError> {
+            let mut spi = Spi(spi);
+            let (busy, dc, rst) = (Input(busy), Output(dc), Output(rst));
             let mut delay = Delay;
-            let mut epd = Epd2in13::new(&mut spi, busy, dc, rst, &mut delay, None)
-                .map_err(|err| driver_error(err))?;
+            let mut epd =
+                Epd2in13::new(&mut spi, busy, dc, rst, &mut delay, None).map_err(driver_error)?;
             epd.clear_frame(&mut spi, &mut delay)
-                .map_err(|err| driver_error(err))?;
+                .map_err(driver_error)?;
             epd.display_frame(&mut spi, &mut delay)
-                .map_err(|err| driver_error(err))?;
+                .map_err(driver_error)?;
 
             Ok(Self {
                 spi,
@@ -241,6 +530,7 @@ pub mod waveshare {
                 delay,
                 rotation: config.rotation,
                 last_lines: None,
+                locale: config.locale,
             })
         }
 
@@ -285,10 +575,10 @@ pub mod waveshare {
 
             self.epd
                 .update_frame(&mut self.spi, frame.buffer(), &mut self.delay)
-                .map_err(|err| driver_error(err))?;
+                .map_err(driver_error)?;
             self.epd
                 .display_frame(&mut self.spi, &mut self.delay)
-                .map_err(|err| driver_error(err))?;
+                .map_err(driver_error)?;
             self.last_lines = Some(lines.to_vec());
             Ok(())
         }
@@ -296,47 +586,476 @@ pub mod waveshare {
 
     impl StatusDisplay for WaveshareDisplay {
         fn update(&mut self, snapshot: &StatusSnapshot) -> Result<(), DisplayError> {
-            let lines = status_lines(snapshot);
+            let lines = status_lines(snapshot, self.locale);
             self.render_lines(&lines).map_err(DisplayError::from)
         }
 
         fn shutdown(&mut self) -> Result<(), DisplayError> {
             self.epd
                 .sleep(&mut self.spi, &mut self.delay)
-                .map_err(|err| driver_error(err))?;
+                .map_err(driver_error)?;
             Ok(())
         }
+
+        fn show_error(&mut self, message: &str) -> Result<(), DisplayError> {
+            self.render_lines(&error_lines(message, self.locale))
+                .map_err(DisplayError::from)
+        }
     }
 
     fn driver_error<E: std::fmt::Display>(err: E) -> WaveshareError {
         WaveshareError::Driver(err.to_string())
     }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use crate::controller::{CardUid, ControllerAction, Track};
+        use crate::hal::Gpio;
+        use crate::hal::mock::{MockGpio, MockSpi};
+
+        #[test]
+        fn draws_on_mock_pins_and_skips_unchanged_status() {
+            let spi = MockSpi::new();
+            let mut gpio = MockGpio::new();
+            let config = WaveshareConfig::default();
+            let mut display = WaveshareDisplay::with_pins(
+                Box::new(spi.clone()),
+                gpio.input(24, "test").unwrap(),
+                gpio.output(25, false, "test").unwrap(),
+                gpio.output(17, true, "test").unwrap(),
+                config,
+            )
+            .unwrap();
+            let after_init = spi.writes().len();
+            assert!(after_init > 0);
+
+            let snapshot = StatusSnapshot {
+                last_action: Some(ControllerAction::Started {
+                    card: CardUid::new(vec![0x0a]),
+                    track: Track::new("lullaby.mp3".into()),
+                }),
+                ..StatusSnapshot::default()
+            };
+            display.update(&snapshot).unwrap();
+            let after_update = spi.writes().len();
+            assert!(after_update > after_init);
+            assert!(gpio.is_high(17), "reset released after init");
+
+            display.update(&snapshot).unwrap();
+            assert_eq!(spi.writes().len(), after_update);
+        }
+    }
 }
 
-#[cfg(all(feature = "waveshare-display", not(target_os = "linux")))]
-pub mod waveshare {
-    use super::{DisplayError, StatusDisplay};
+/// Mirrors the status on a screen elsewhere in the room, such as an ESP32
+/// with a small display, by sending each snapshot's lines as JSON over HTTP
+/// or UDP.
+pub mod remote {
+    use super::{DisplayError, StatusDisplay, error_lines, status_lines};
+    use crate::events::EventStamp;
+    use crate::locale::Locale;
     use crate::telemetry::StatusSnapshot;
-    use thiserror::Error;
+    use crate::webhook::{self, WebhookError, WebhookUrl};
+    use std::net::{ToSocketAddrs, UdpSocket};
+    use std::sync::mpsc::{self, Receiver, Sender};
+    use std::time::{Duration, Instant};
 
-    #[derive(Debug, Error)]
-    #[error("waveshare display is only supported on Linux targets")]
-    pub struct WaveshareError;
+    /// How often an unchanged status is sent again, so a screen that
+    /// restarted catches up.
+    pub const DEFAULT_REFRESH: Duration = Duration::from_secs(30);
 
-    #[derive(Debug, Clone, Default)]
-    pub struct WaveshareConfig;
+    #[derive(Debug, thiserror::Error)]
+    pub enum RemoteDisplayError {
+        #[error(
+            "unsupported remote display url {0:?}; use http[s]://HOST[:PORT]/PATH or udp://HOST:PORT"
+        )]
+        UnsupportedUrl(String),
+        #[error(transparent)]
+        Http(#[from] WebhookError),
+        #[error("failed to send to the remote display: {0}")]
+        Udp(#[from] std::io::Error),
+    }
 
-    pub struct WaveshareDisplay;
+    /// Where the status goes.
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    pub enum RemoteTarget {
+        /// POSTed as a JSON body.
+        Http(WebhookUrl),
+        /// Sent as one JSON datagram to `host:port`.
+        Udp(String),
+    }
 
-    impl WaveshareDisplay {
-        pub fn new(_: WaveshareConfig) -> Result<Self, WaveshareError> {
-            Err(WaveshareError)
+    impl RemoteTarget {
+        pub fn parse(url: &str) -> Result<Self, RemoteDisplayError> {
+            let unsupported = || RemoteDisplayError::UnsupportedUrl(url.to_string());
+            if let Some(addr) = url.strip_prefix("udp://") {
+                let addr = addr.trim_end_matches('/');
+                return match addr.rsplit_once(':') {
+                    Some((host, port)) if !host.is_empty() && port.parse::<u16>().is_ok() => {
+                        Ok(Self::Udp(addr.to_string()))
+                    }
+                    _ => Err(unsupported()),
+                };
+            }
+            WebhookUrl::parse(url)
+                .map(Self::Http)
+                .map_err(|err| match err {
+                    WebhookError::UnsupportedUrl(_) => unsupported(),
+                    err => err.into(),
+                })
+        }
+
+        fn send(&self, payload: &serde_json::Value) -> Result<(), RemoteDisplayError> {
+            match self {
+                Self::Http(url) => Ok(webhook::post_json(url, payload)?),
+                Self::Udp(addr) => {
+                    let addr = addr.to_socket_addrs()?.next().ok_or_else(|| {
+                        std::io::Error::new(std::io::ErrorKind::NotFound, "no address for host")
+                    })?;
+                    let local = if addr.is_ipv4() {
+                        "0.0.0.0:0"
+                    } else {
+                        "[::]:0"
+                    };
+                    let body = serde_json::to_vec(payload).map_err(WebhookError::from)?;
+                    UdpSocket::bind(local)?.send_to(&body, addr)?;
+                    Ok(())
+                }
+            }
         }
     }
 
-    impl StatusDisplay for WaveshareDisplay {
+    /// Settings from the config's `[remote_display]` table.
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    pub struct RemoteDisplaySettings {
+        pub target: RemoteTarget,
+        pub refresh: Duration,
+    }
+
+    /// Sends status from a background thread, so a slow or missing screen
+    /// never holds up the run loop. Only the newest status is sent when
+    /// updates arrive faster than the network takes them.
+    pub struct RemoteDisplay {
+        target: RemoteTarget,
+        refresh: Duration,
+        locale: Locale,
+        outbox: Sender<serde_json::Value>,
+        /// The lines last sent, and when.
+        last: Option<(Vec<String>, Instant)>,
+    }
+
+    impl RemoteDisplay {
+        pub fn new(settings: RemoteDisplaySettings, locale: Locale) -> Self {
+            let (outbox, inbox) = mpsc::channel();
+            let target = settings.target.clone();
+            std::thread::spawn(move || deliver(&target, inbox));
+            Self {
+                target: settings.target,
+                refresh: settings.refresh,
+                locale,
+                outbox,
+                last: None,
+            }
+        }
+    }
+
+    impl StatusDisplay for RemoteDisplay {
+        fn update(&mut self, snapshot: &StatusSnapshot) -> Result<(), DisplayError> {
+            let lines = status_lines(snapshot, self.locale);
+            let now = Instant::now();
+            if self.last.as_ref().is_some_and(|(last, sent)| {
+                *last == lines && now.saturating_duration_since(*sent) < self.refresh
+            }) {
+                return Ok(());
+            }
+            let payload = payload(&lines, snapshot.reader_health.label());
+            self.last = Some((lines, now));
+            // The thread only stops with the process.
+            let _ = self.outbox.send(payload);
+            Ok(())
+        }
+
+        /// Sends the error screen at once: the process exits right after.
+        fn show_error(&mut self, message: &str) -> Result<(), DisplayError> {
+            let mut payload = payload(&error_lines(message, self.locale), "unknown");
+            payload["error"] = message.into();
+            Ok(self.target.send(&payload)?)
+        }
+    }
+
+    fn payload(lines: &[String], reader: &str) -> serde_json::Value {
+        let stamp = EventStamp::next();
+        serde_json::json!({
+            "lines": lines,
+            "reader": reader,
+            "id": stamp.id,
+            "sequence": stamp.sequence,
+            "timestamp_ms": stamp.timestamp_ms,
+        })
+    }
+
+    /// Sends what arrives in `inbox`, warning once when the screen stops
+    /// answering rather than on every update.
+    fn deliver(target: &RemoteTarget, inbox: Receiver<serde_json::Value>) {
+        let mut failing = false;
+        while let Ok(mut payload) = inbox.recv() {
+            while let Ok(newer) = inbox.try_recv() {
+                payload = newer;
+            }
+            match target.send(&payload) {
+                Ok(()) if failing => {
+                    failing = false;
+                    tracing::info!("remote display is answering again");
+                }
+                Ok(()) => {}
+                Err(err) if !failing => {
+                    failing = true;
+                    tracing::warn!(%err, "remote display update failed");
+                }
+                Err(_) => {}
+            }
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use crate::controller::{CardUid, ControllerAction, Track};
+
+        #[test]
+        fn parses_http_and_udp_targets() {
+            assert!(matches!(
+                RemoteTarget::parse("http://esp32.local/status"),
+                Ok(RemoteTarget::Http(_))
+            ));
+            assert_eq!(
+                RemoteTarget::parse("udp://192.168.1.50:4210").unwrap(),
+                RemoteTarget::Udp("192.168.1.50:4210".into())
+            );
+            for url in ["udp://esp32.local", "udp://:4210", "ftp://esp32.local/"] {
+                assert!(matches!(
+                    RemoteTarget::parse(url),
+                    Err(RemoteDisplayError::UnsupportedUrl(_))
+                ));
+            }
+        }
+
+        #[test]
+        fn sends_changed_status_lines_as_datagrams() {
+            let screen = UdpSocket::bind("127.0.0.1:0").unwrap();
+            screen
+                .set_read_timeout(Some(Duration::from_secs(2)))
+                .unwrap();
+            let mut display = RemoteDisplay::new(
+                RemoteDisplaySettings {
+                    target: RemoteTarget::Udp(screen.local_addr().unwrap().to_string()),
+                    refresh: Duration::from_secs(60),
+                },
+                Locale::En,
+            );
+            let receive = || {
+                let mut buffer = [0u8; 2048];
+                let len = screen.recv(&mut buffer).unwrap();
+                serde_json::from_slice::<serde_json::Value>(&buffer[..len]).unwrap()
+            };
+
+            let mut snapshot = StatusSnapshot::default();
+            display.update(&snapshot).unwrap();
+            let idle = receive();
+            assert_eq!(idle["reader"], "healthy");
+            assert!(idle["sequence"].as_u64().is_some());
+
+            display.update(&snapshot).unwrap();
+            snapshot.last_action = Some(ControllerAction::Started {
+                card: CardUid::new(vec![0x0a]),
+                track: Track::new("lullaby.mp3".into()),
+            });
+            display.update(&snapshot).unwrap();
+            let playing = receive();
+            let lines: Vec<&str> = playing["lines"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .filter_map(|line| line.as_str())
+                .collect();
+            assert_ne!(playing["lines"], idle["lines"]);
+            assert!(lines.iter().any(|line| line.contains("lullaby")));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::controller::LibraryStats;
+    use std::sync::{Arc, Mutex};
+
+    #[derive(Clone, Copy)]
+    enum Behavior {
+        Ok,
+        Fail,
+        Panic,
+    }
+
+    struct ScriptedDisplay {
+        behavior: Arc<Mutex<Behavior>>,
+        updates: Arc<Mutex<u32>>,
+    }
+
+    impl StatusDisplay for ScriptedDisplay {
         fn update(&mut self, _snapshot: &StatusSnapshot) -> Result<(), DisplayError> {
-            Err(DisplayError::BackendUnavailable)
+            *self.updates.lock().unwrap() += 1;
+            let behavior = *self.behavior.lock().unwrap();
+            match behavior {
+                Behavior::Ok => Ok(()),
+                Behavior::Fail => Err(DisplayError::BackendUnavailable),
+                Behavior::Panic => panic!("spi bus exploded"),
+            }
+        }
+    }
+
+    struct Harness {
+        behavior: Arc<Mutex<Behavior>>,
+        updates: Arc<Mutex<u32>>,
+        inits: Arc<Mutex<u32>>,
+        status: SharedStatus,
+    }
+
+    impl Harness {
+        fn new() -> Self {
+            Self {
+                behavior: Arc::new(Mutex::new(Behavior::Ok)),
+                updates: Arc::new(Mutex::new(0)),
+                inits: Arc::new(Mutex::new(0)),
+                status: SharedStatus::default(),
+            }
+        }
+
+        fn guard(&self, reinit_interval: Duration) -> GuardedDisplay {
+            let behavior = self.behavior.clone();
+            let updates = self.updates.clone();
+            let inits = self.inits.clone();
+            GuardedDisplay::new(
+                Box::new(move || {
+                    *inits.lock().unwrap() += 1;
+                    Ok(Box::new(ScriptedDisplay {
+                        behavior: behavior.clone(),
+                        updates: updates.clone(),
+                    }) as Box<dyn StatusDisplay>)
+                }),
+                DisplayGuardSettings {
+                    error_threshold: 2,
+                    reinit_interval,
+                },
+                self.status.clone(),
+            )
+        }
+
+        fn set(&self, behavior: Behavior) {
+            *self.behavior.lock().unwrap() = behavior;
         }
     }
+
+    #[test]
+    fn error_lines_wrap_message_under_banner() {
+        let lines = error_lines("controller mutex poisoned while handling card", Locale::En);
+
+        assert_eq!(lines[1], "ERROR - restarting");
+        assert!(lines[2..].iter().all(|line| line.chars().count() <= 24));
+        assert_eq!(
+            lines[2..].join(" "),
+            "controller mutex poisoned while handling card"
+        );
+    }
+
+    #[test]
+    fn status_lines_follow_locale() {
+        let snapshot = StatusSnapshot {
+            last_action: Some(ControllerAction::Started {
+                card: CardUid::new(vec![0xab]),
+                track: Track::new("album/lied.mp3".into()),
+            }),
+            ..StatusSnapshot::default()
+        };
+
+        let english = status_lines(&snapshot, Locale::En);
+        let german = status_lines(&snapshot, Locale::De);
+
+        assert_eq!(english[1], "State: Playing");
+        assert_eq!(german[0], "Musikbox");
+        assert_eq!(german[1], "Status: Spielt");
+        assert_eq!(german[4], "Titel: lied.mp3");
+        assert_eq!(german[5], "Aktualisiert: –");
+    }
+
+    #[test]
+    fn loaded_cards_replace_the_idle_poll_line() {
+        let mut snapshot = StatusSnapshot {
+            idle_events: 7,
+            ..StatusSnapshot::default()
+        };
+        assert_eq!(status_lines(&snapshot, Locale::En)[2], "Idle polls: 7");
+
+        snapshot.library = Some(LibraryStats {
+            cards: 142,
+            missing_tracks: 3,
+            loaded_at: SystemTime::UNIX_EPOCH,
+        });
+        assert_eq!(
+            status_lines(&snapshot, Locale::En)[2],
+            "142 cards, 3 missing"
+        );
+        assert_eq!(
+            status_lines(&snapshot, Locale::De)[2],
+            "142 Karten, 3 fehlen"
+        );
+    }
+
+    #[test]
+    fn panic_disables_display_without_propagating() {
+        let harness = Harness::new();
+        let mut display = harness.guard(Duration::from_secs(60));
+        harness.set(Behavior::Panic);
+
+        display.update(&StatusSnapshot::default()).unwrap();
+        display.update(&StatusSnapshot::default()).unwrap();
+
+        assert!(!display.is_active());
+        assert_eq!(*harness.updates.lock().unwrap(), 1);
+        let health = harness.status.snapshot().display_health;
+        assert_eq!(health.label(), "disabled");
+        assert!(health.reason().unwrap().contains("spi bus exploded"));
+    }
+
+    #[test]
+    fn persistent_errors_disable_after_threshold() {
+        let harness = Harness::new();
+        let mut display = harness.guard(Duration::from_secs(60));
+        harness.set(Behavior::Fail);
+
+        display.update(&StatusSnapshot::default()).unwrap();
+        assert!(display.is_active());
+        display.update(&StatusSnapshot::default()).unwrap();
+        assert!(!display.is_active());
+    }
+
+    #[test]
+    fn disabled_display_reinitializes_after_interval() {
+        let harness = Harness::new();
+        let mut display = harness.guard(Duration::ZERO);
+        harness.set(Behavior::Panic);
+        display.update(&StatusSnapshot::default()).unwrap();
+        assert!(!display.is_active());
+
+        harness.set(Behavior::Ok);
+        display.update(&StatusSnapshot::default()).unwrap();
+
+        assert!(display.is_active());
+        assert_eq!(*harness.inits.lock().unwrap(), 2);
+        assert_eq!(
+            harness.status.snapshot().display_health,
+            DisplayHealth::Active
+        );
+    }
 }
@@ -0,0 +1,253 @@
+//! The whole musicbox behavior as one embeddable type.
+//!
+//! [`Engine`] owns the controller and runs [`run_until_shutdown`] on its own
+//! thread, so a GUI, a test bench, or a kiosk app gets card handling,
+//! playlists, timers, and buttons without copying the glue in `main.rs`.
+//! What happens is published on its [`EventBus`] as [`EngineEvent`]s,
+//! each with an [`EventStamp`](crate::events::EventStamp).
+
+use crate::app::{self, AppError, RunLoopError, ShutdownToken, run_until_shutdown};
+use crate::controller::{
+    AudioPlayer, CardUid, ControllerAction, ControllerError, MusicBoxController,
+};
+pub use crate::events::{EngineEvent, EventBus, Published};
+use crate::input::ButtonSource;
+use crate::reader::NfcReader;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::Receiver;
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+#[derive(Debug, thiserror::Error)]
+pub enum EngineError {
+    #[error("the engine is already running")]
+    AlreadyRunning,
+    #[error(transparent)]
+    RunLoop(#[from] RunLoopError),
+    #[error("controller error: {0}")]
+    Controller(#[from] ControllerError),
+    #[error("the run loop panicked")]
+    Panicked,
+}
+
+/// A controller and the thread that feeds it reader events, button presses,
+/// and timer ticks.
+pub struct Engine<P: AudioPlayer + Send + 'static> {
+    controller: Arc<Mutex<MusicBoxController<P>>>,
+    config_path: Option<PathBuf>,
+    shutdown: ShutdownToken,
+    events: EventBus,
+    run_loop: Option<JoinHandle<Result<(), RunLoopError>>>,
+}
+
+impl<P: AudioPlayer + Send + 'static> Engine<P> {
+    pub fn new(controller: MusicBoxController<P>) -> Self {
+        Self {
+            controller: Arc::new(Mutex::new(controller)),
+            config_path: None,
+            shutdown: ShutdownToken::new(),
+            events: EventBus::new(),
+            run_loop: None,
+        }
+    }
+
+    /// An engine for the config at `path`. Reload cards re-read it.
+    pub fn from_config_path(path: impl AsRef<Path>, player: P) -> Result<Self, AppError> {
+        let controller = app::controller_from_config_path(&path, player)?;
+        Ok(Self {
+            config_path: Some(path.as_ref().to_path_buf()),
+            ..Self::new(controller)
+        })
+    }
+
+    /// Uses `shutdown` to stop the run loop, such as one from
+    /// [`ShutdownToken::from_signals`].
+    pub fn with_shutdown_token(mut self, shutdown: ShutdownToken) -> Self {
+        self.shutdown = shutdown;
+        self
+    }
+
+    /// The controller, for reading state or acting on it directly.
+    pub fn controller(&self) -> Arc<Mutex<MusicBoxController<P>>> {
+        self.controller.clone()
+    }
+
+    /// Events from now on. Each subscriber gets every event.
+    pub fn subscribe(&self) -> Receiver<Published> {
+        self.events.subscribe()
+    }
+
+    /// The bus events are published on, for handlers that should run on
+    /// the engine's thread with [`EventBus::on`].
+    pub fn events(&self) -> &EventBus {
+        &self.events
+    }
+
+    /// Whether the run loop is still going.
+    pub fn is_running(&self) -> bool {
+        self.run_loop
+            .as_ref()
+            .is_some_and(|run_loop| !run_loop.is_finished())
+    }
+
+    /// Starts the run loop on its own thread, reading taps from `reader`
+    /// and presses from `buttons`. Timers advance whenever the reader
+    /// reports that it is idle.
+    pub fn start<R, B>(&mut self, mut reader: R, mut buttons: B) -> Result<(), EngineError>
+    where
+        R: NfcReader + Send + 'static,
+        B: ButtonSource + Send + 'static,
+    {
+        if self.run_loop.is_some() {
+            return Err(EngineError::AlreadyRunning);
+        }
+        if let Some(path) = self.config_path.clone() {
+            let controller = self.controller.clone();
+            self.events.on(move |published| {
+                if matches!(
+                    published.event,
+                    EngineEvent::Action(ControllerAction::ReloadRequested { .. })
+                ) && let Err(err) = app::reload_library(&controller, &path)
+                {
+                    tracing::warn!(%err, "keeping the current cards; config reload failed");
+                }
+            });
+        }
+        let controller = self.controller.clone();
+        let shutdown = self.shutdown.clone();
+        let events = self.events.clone();
+        self.run_loop = Some(std::thread::spawn(move || {
+            run_until_shutdown(
+                controller,
+                &mut reader,
+                &mut buttons,
+                &shutdown,
+                &events,
+                Duration::ZERO,
+            )
+        }));
+        Ok(())
+    }
+
+    /// Handles `uid` as if it had been tapped, whether or not the run loop
+    /// is going, and publishes the action.
+    pub fn handle(&self, uid: &CardUid) -> Result<ControllerAction, ControllerError> {
+        self.events
+            .apply_and_publish(&self.controller, |controller| controller.handle_card(uid))
+    }
+
+    /// Stops the run loop after the event it is handling, then stops
+    /// playback and plays the goodnight cue. Returns what stopped, if
+    /// anything was playing, or the error that ended the run loop.
+    pub fn shutdown(mut self) -> Result<Option<ControllerAction>, EngineError> {
+        self.shutdown.request();
+        if let Some(run_loop) = self.run_loop.take() {
+            run_loop.join().map_err(|_| EngineError::Panicked)??;
+        }
+        let stopped = self
+            .controller
+            .lock()
+            .expect("controller lock")
+            .shutdown()?;
+        Ok(stopped)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::controller::{Library, PlayerError, Track};
+    use crate::input::NoButtons;
+    use crate::reader::{ReplayReader, ReplayStep};
+    use std::collections::HashMap;
+
+    struct SilentPlayer;
+
+    impl AudioPlayer for SilentPlayer {
+        fn play(&mut self, _track: &Track) -> Result<(), PlayerError> {
+            Ok(())
+        }
+
+        fn stop(&mut self) -> Result<(), PlayerError> {
+            Ok(())
+        }
+
+        fn pause(&mut self) -> Result<(), PlayerError> {
+            Ok(())
+        }
+
+        fn resume(&mut self) -> Result<(), PlayerError> {
+            Ok(())
+        }
+
+        fn is_finished(&self) -> bool {
+            false
+        }
+    }
+
+    fn engine() -> Engine<SilentPlayer> {
+        let library = Library::new(HashMap::from([(
+            CardUid::new(vec![1]),
+            Track::new(PathBuf::from("song.mp3")),
+        )]));
+        Engine::new(MusicBoxController::new(library, SilentPlayer))
+    }
+
+    #[test]
+    fn runs_taps_from_the_reader_and_reports_them() {
+        let mut engine = engine();
+        let events = engine.subscribe();
+        let reader = ReplayReader::new(vec![
+            ReplayStep::Tap(CardUid::new(vec![9])),
+            ReplayStep::Tap(CardUid::new(vec![1])),
+        ]);
+        engine.start(reader, NoButtons).unwrap();
+        assert!(matches!(
+            engine.start(ReplayReader::new(Vec::new()), NoButtons),
+            Err(EngineError::AlreadyRunning)
+        ));
+
+        let published: Vec<Published> = events
+            .iter()
+            .take_while(|published| published.event != EngineEvent::Stopped)
+            .collect();
+        assert!(
+            published
+                .windows(2)
+                .all(|pair| pair[0].stamp.sequence < pair[1].stamp.sequence)
+        );
+        let events: Vec<EngineEvent> = published
+            .into_iter()
+            .map(|published| published.event)
+            .filter(|event| !matches!(event, EngineEvent::Latency(_) | EngineEvent::Idle))
+            .collect();
+        assert!(matches!(
+            &events[..],
+            [
+                EngineEvent::Action(ControllerAction::UnknownCard { .. }),
+                EngineEvent::Action(ControllerAction::Started { .. }),
+            ]
+        ));
+        assert!(matches!(
+            engine.shutdown().unwrap(),
+            Some(ControllerAction::Stopped { .. })
+        ));
+    }
+
+    #[test]
+    fn handles_taps_without_a_reader() {
+        let engine = engine();
+        let events = engine.subscribe();
+        let action = engine.handle(&CardUid::new(vec![1])).unwrap();
+        assert!(matches!(action, ControllerAction::Started { .. }));
+        assert_eq!(
+            events.try_recv().unwrap().event,
+            EngineEvent::Action(action)
+        );
+        assert!(!engine.is_running());
+        drop(events);
+        engine.handle(&CardUid::new(vec![1])).unwrap();
+        assert_eq!(engine.events().subscribers(), 0);
+    }
+}
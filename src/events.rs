@@ -0,0 +1,241 @@
+//! What the run loop does, published to whoever listens.
+//!
+//! [`run_until_shutdown`](crate::app::run_until_shutdown) reports every
+//! action, ignored tap, tap latency, and idle tick as an [`EngineEvent`] on
+//! an [`EventBus`]. The status display, telemetry, the LED strip, and
+//! embedders each subscribe on their own instead of sharing one callback.
+//! Plays and pauses from the web API, the control socket, and MQTT are
+//! published the same way through [`EventBus::apply_and_publish`].
+//!
+//! Every event carries an [`EventStamp`], so consumers such as Home
+//! Assistant automations can drop a webhook delivered twice and put events
+//! that arrive out of order back in sequence.
+
+use crate::controller::{CardUid, ControllerAction};
+use crate::telemetry::TapLatency;
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Something the run loop did, as seen by subscribers.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EngineEvent {
+    /// A tap, button press, or timer changed what the controller does.
+    Action(ControllerAction),
+    /// A tap on an expired or misplaced card was ignored. Unknown cards
+    /// arrive as [`ControllerAction::UnknownCard`].
+    Rejected { card: CardUid, reason: String },
+    /// How long an accepted tap took to act on.
+    Latency(TapLatency),
+    /// A tap came with the tag's own read counter. Sent for every tap the
+    /// reader reports, even one ignored as a bounce; the tag also counts
+    /// reads the reader missed, so gaps between counts show taps lost.
+    TagCounter { card: CardUid, count: u32 },
+    /// The reader had nothing to report and timers advanced. Arrives once
+    /// per reader poll, so it doubles as a heartbeat.
+    Idle,
+    /// The run loop ended: the reader shut down, the shutdown card was
+    /// tapped, or shutdown was requested.
+    Stopped,
+}
+
+/// An [`EngineEvent`] as delivered. Every subscriber gets the same stamp
+/// for the same event.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Published {
+    pub stamp: EventStamp,
+    pub event: EngineEvent,
+}
+
+enum Subscriber {
+    Channel(Sender<Published>),
+    Handler(Box<dyn FnMut(&Published) + Send>),
+}
+
+/// Hands each published event to every subscriber, in the order they
+/// subscribed. Clones share the same subscribers.
+#[derive(Clone, Default)]
+pub struct EventBus {
+    subscribers: Arc<Mutex<Vec<Subscriber>>>,
+}
+
+impl EventBus {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Events from now on, to read on any thread. A subscriber whose
+    /// receiver is dropped is forgotten at the next event.
+    pub fn subscribe(&self) -> Receiver<Published> {
+        let (sender, receiver) = mpsc::channel();
+        self.push(Subscriber::Channel(sender));
+        receiver
+    }
+
+    /// Calls `handler` with each event from now on, on the publishing
+    /// thread before [`Self::publish`] returns, so the run loop waits for
+    /// it. Handlers must not publish themselves.
+    pub fn on(&self, handler: impl FnMut(&Published) + Send + 'static) {
+        self.push(Subscriber::Handler(Box::new(handler)));
+    }
+
+    /// Stamps `event` and delivers it to every subscriber.
+    pub fn publish(&self, event: EngineEvent) {
+        let published = Published {
+            stamp: EventStamp::next(),
+            event,
+        };
+        self.subscribers
+            .lock()
+            .expect("subscribers lock")
+            .retain_mut(|subscriber| match subscriber {
+                Subscriber::Channel(sender) => sender.send(published.clone()).is_ok(),
+                Subscriber::Handler(handler) => {
+                    handler(&published);
+                    true
+                }
+            });
+    }
+
+    /// Runs `command` on `controller` and publishes the action it took
+    /// once the lock is released, so handlers can take it again. Commands
+    /// from the web API, the control socket, and MQTT go through here so
+    /// every subscriber sees them the way it sees taps.
+    pub fn apply_and_publish<C, A, E>(
+        &self,
+        controller: &Mutex<C>,
+        command: impl FnOnce(&mut C) -> Result<A, E>,
+    ) -> Result<A, E>
+    where
+        A: Clone + Into<Option<ControllerAction>>,
+    {
+        let result = command(&mut controller.lock().expect("controller lock"))?;
+        if let Some(action) = result.clone().into() {
+            self.publish(EngineEvent::Action(action));
+        }
+        Ok(result)
+    }
+
+    /// How many subscribers are still listening.
+    pub fn subscribers(&self) -> usize {
+        self.subscribers.lock().expect("subscribers lock").len()
+    }
+
+    fn push(&self, subscriber: Subscriber) {
+        self.subscribers
+            .lock()
+            .expect("subscribers lock")
+            .push(subscriber);
+    }
+}
+
+/// Identifies one published event.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct EventStamp {
+    /// Unique across events and restarts: the run this process belongs to,
+    /// then the event's sequence number, such as `18f3a2b4c01-4d2-17`.
+    pub id: String,
+    /// Counts up from 1 for each event this process publishes.
+    pub sequence: u64,
+    /// When the event happened, in milliseconds since the Unix epoch.
+    pub timestamp_ms: u64,
+}
+
+impl EventStamp {
+    /// Stamps an event happening now.
+    pub fn next() -> Self {
+        static SEQUENCE: AtomicU64 = AtomicU64::new(1);
+        static RUN: OnceLock<String> = OnceLock::new();
+        let run = RUN.get_or_init(|| format!("{:x}-{:x}", unix_millis(), std::process::id()));
+        let sequence = SEQUENCE.fetch_add(1, Ordering::Relaxed);
+        Self {
+            id: format!("{run}-{sequence}"),
+            sequence,
+            timestamp_ms: unix_millis(),
+        }
+    }
+}
+
+fn unix_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_millis() as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stamps_are_unique_and_ordered() {
+        let first = EventStamp::next();
+        let second = EventStamp::next();
+        assert_ne!(first.id, second.id);
+        assert!(second.sequence > first.sequence);
+        assert!(second.timestamp_ms >= first.timestamp_ms);
+        assert!(first.id.ends_with(&format!("-{}", first.sequence)));
+        assert_eq!(
+            first.id.rsplit_once('-').unwrap().0,
+            second.id.rsplit_once('-').unwrap().0
+        );
+    }
+
+    #[test]
+    fn every_subscriber_gets_each_event_with_the_same_stamp() {
+        let bus = EventBus::new();
+        let handled = Arc::new(Mutex::new(Vec::new()));
+        bus.on({
+            let handled = handled.clone();
+            move |published| handled.lock().unwrap().push(published.clone())
+        });
+        let first = bus.subscribe();
+        let second = bus.subscribe();
+
+        bus.publish(EngineEvent::Idle);
+        drop(second);
+        bus.publish(EngineEvent::Stopped);
+
+        let received: Vec<Published> = first.try_iter().collect();
+        assert_eq!(*handled.lock().unwrap(), received);
+        assert_eq!(received[0].event, EngineEvent::Idle);
+        assert_eq!(received[1].event, EngineEvent::Stopped);
+        assert!(received[0].stamp.sequence < received[1].stamp.sequence);
+        assert_eq!(bus.subscribers(), 2);
+    }
+
+    #[test]
+    fn applied_commands_are_published_after_the_lock_is_released() {
+        let bus = EventBus::new();
+        let controller = Arc::new(Mutex::new(0));
+        bus.on({
+            let controller = controller.clone();
+            move |_| *controller.try_lock().expect("lock released") += 10
+        });
+        let events = bus.subscribe();
+        let card = CardUid::new(vec![0x04]);
+
+        let action = bus.apply_and_publish(&controller, |count| {
+            *count += 1;
+            Ok::<_, ()>(ControllerAction::UnknownCard { card: card.clone() })
+        });
+        assert_eq!(
+            action,
+            Ok(ControllerAction::UnknownCard { card: card.clone() })
+        );
+        let nothing = bus.apply_and_publish(&controller, |_| Ok::<_, ()>(None));
+        assert_eq!(nothing, Ok(None));
+        assert_eq!(
+            bus.apply_and_publish(&controller, |_| Err::<Option<_>, _>(())),
+            Err(())
+        );
+
+        assert_eq!(*controller.lock().unwrap(), 11);
+        let published: Vec<EngineEvent> = events.try_iter().map(|p| p.event).collect();
+        assert_eq!(
+            published,
+            [EngineEvent::Action(ControllerAction::UnknownCard { card })]
+        );
+    }
+}
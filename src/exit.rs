@@ -0,0 +1,114 @@
+//! The codes `musicbox` exits with, so wrapper scripts and systemd units
+//! can tell a broken config from a reader that is not plugged in.
+//!
+//! Where one fits, a code matches its meaning in BSD's `sysexits.h`, as
+//! shells and service managers already know those.
+
+use std::fmt;
+
+/// Why `musicbox` stopped. Anything without a code of its own exits with
+/// [`ExitCode::Failure`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ExitCode {
+    /// The command did what was asked; the player stopped on request.
+    Success,
+    /// An error without a more specific code.
+    Failure,
+    /// The command line did not parse. clap prints the usage and exits
+    /// with this itself.
+    Usage,
+    /// `config validate`, `verify`, or `library analyze` ran and found
+    /// problems.
+    ValidationFailed,
+    /// No NFC reader could be opened, or the reader failed for good.
+    ReaderUnavailable,
+    /// The player panicked. See the history log for where.
+    Panic,
+    /// Audio output failed in a way playback could not carry on from.
+    AudioUnavailable,
+    /// The config is missing, unreadable, or invalid.
+    Config,
+}
+
+impl ExitCode {
+    /// Every code, in numeric order.
+    pub const ALL: [ExitCode; 8] = [
+        ExitCode::Success,
+        ExitCode::Failure,
+        ExitCode::Usage,
+        ExitCode::ValidationFailed,
+        ExitCode::ReaderUnavailable,
+        ExitCode::Panic,
+        ExitCode::AudioUnavailable,
+        ExitCode::Config,
+    ];
+
+    /// The number the process exits with.
+    pub fn code(self) -> i32 {
+        match self {
+            ExitCode::Success => 0,
+            ExitCode::Failure => 1,
+            ExitCode::Usage => 2,
+            // EX_DATAERR
+            ExitCode::ValidationFailed => 65,
+            // EX_UNAVAILABLE
+            ExitCode::ReaderUnavailable => 69,
+            // EX_SOFTWARE
+            ExitCode::Panic => 70,
+            // EX_IOERR
+            ExitCode::AudioUnavailable => 74,
+            // EX_CONFIG
+            ExitCode::Config => 78,
+        }
+    }
+
+    /// The code a process exited with, if it is one of these.
+    pub fn from_code(code: i32) -> Option<Self> {
+        Self::ALL.into_iter().find(|exit| exit.code() == code)
+    }
+
+    /// Exits the process with this code.
+    pub fn exit(self) -> ! {
+        std::process::exit(self.code())
+    }
+}
+
+impl fmt::Display for ExitCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            ExitCode::Success => "success",
+            ExitCode::Failure => "failure",
+            ExitCode::Usage => "usage",
+            ExitCode::ValidationFailed => "validation failed",
+            ExitCode::ReaderUnavailable => "reader unavailable",
+            ExitCode::Panic => "panic",
+            ExitCode::AudioUnavailable => "audio unavailable",
+            ExitCode::Config => "config error",
+        };
+        write!(f, "{name} ({})", self.code())
+    }
+}
+
+impl From<ExitCode> for std::process::ExitCode {
+    fn from(exit: ExitCode) -> Self {
+        // Every code fits in a byte.
+        std::process::ExitCode::from(exit.code() as u8)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn codes_are_distinct_and_round_trip() {
+        for (i, exit) in ExitCode::ALL.into_iter().enumerate() {
+            assert_eq!(ExitCode::from_code(exit.code()), Some(exit));
+            if let Some(next) = ExitCode::ALL.get(i + 1) {
+                assert!(exit.code() < next.code());
+            }
+        }
+        assert_eq!(ExitCode::from_code(3), None);
+        assert_eq!(ExitCode::Config.to_string(), "config error (78)");
+    }
+}
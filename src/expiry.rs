@@ -0,0 +1,151 @@
+//! Expiry times for guest cards.
+//!
+//! Expiries are stored in the config as TOML offset date-times, such as
+//! `expires = 2026-10-25T18:00:00Z`, so they stay readable when editing the
+//! file by hand. The CLI takes lifetimes like `7d` and writes the resulting
+//! moment in UTC.
+
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use toml::value::{Date, Datetime, Offset, Time};
+
+#[derive(Debug, thiserror::Error, PartialEq, Eq)]
+#[error("invalid lifetime {0:?}: expected a number followed by m, h, d, or w, like `7d`")]
+pub struct LifetimeParseError(String);
+
+/// Parses a lifetime such as `90m`, `12h`, `7d`, or `2w`.
+pub fn parse_lifetime(text: &str) -> Result<Duration, LifetimeParseError> {
+    let invalid = || LifetimeParseError(text.to_string());
+    let trimmed = text.trim();
+    let split = trimmed
+        .find(|c: char| !c.is_ascii_digit())
+        .ok_or_else(invalid)?;
+    let (amount, unit) = trimmed.split_at(split);
+    let amount: u64 = amount.parse().map_err(|_| invalid())?;
+    let unit_secs = match unit {
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        "w" => 7 * 24 * 60 * 60,
+        _ => return Err(invalid()),
+    };
+    if amount == 0 {
+        return Err(invalid());
+    }
+    amount
+        .checked_mul(unit_secs)
+        .map(Duration::from_secs)
+        .ok_or_else(invalid)
+}
+
+/// The moment a TOML date-time names. Only offset date-times name a single
+/// moment; dates and local times return `None`.
+pub fn to_system_time(datetime: &Datetime) -> Option<SystemTime> {
+    let (Some(date), Some(time), Some(offset)) = (datetime.date, datetime.time, datetime.offset)
+    else {
+        return None;
+    };
+    let offset_minutes = match offset {
+        Offset::Z => 0,
+        Offset::Custom { minutes } => i64::from(minutes),
+    };
+    let days = days_from_civil(
+        i64::from(date.year),
+        i64::from(date.month),
+        i64::from(date.day),
+    );
+    let secs = days * 86_400
+        + i64::from(time.hour) * 3_600
+        + i64::from(time.minute) * 60
+        + i64::from(time.second)
+        - offset_minutes * 60;
+    let whole = if secs >= 0 {
+        UNIX_EPOCH.checked_add(Duration::from_secs(secs.unsigned_abs()))
+    } else {
+        UNIX_EPOCH.checked_sub(Duration::from_secs(secs.unsigned_abs()))
+    }?;
+    whole.checked_add(Duration::from_nanos(u64::from(time.nanosecond)))
+}
+
+/// `time` as a UTC offset date-time, to the second.
+pub fn to_datetime(time: SystemTime) -> Datetime {
+    let secs = time
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs())
+        .unwrap_or(0);
+    let (year, month, day) = civil_from_days((secs / 86_400) as i64);
+    let of_day = secs % 86_400;
+    Datetime {
+        date: Some(Date {
+            year: year as u16,
+            month: month as u8,
+            day: day as u8,
+        }),
+        time: Some(Time {
+            hour: (of_day / 3_600) as u8,
+            minute: (of_day % 3_600 / 60) as u8,
+            second: (of_day % 60) as u8,
+            nanosecond: 0,
+        }),
+        offset: Some(Offset::Z),
+    }
+}
+
+/// Days since 1970-01-01 of a proleptic Gregorian date.
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
+/// The inverse of [`days_from_civil`].
+fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let days = days + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days - era * 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1_460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 {
+        shifted_month + 3
+    } else {
+        shifted_month - 9
+    };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lifetimes_parse_with_units() {
+        assert_eq!(parse_lifetime("90m"), Ok(Duration::from_secs(90 * 60)));
+        assert_eq!(parse_lifetime("7d"), Ok(Duration::from_secs(7 * 86_400)));
+        assert_eq!(parse_lifetime("2w"), Ok(Duration::from_secs(14 * 86_400)));
+        for bad in ["", "7", "d", "0d", "7 days", "-1h"] {
+            assert!(parse_lifetime(bad).is_err(), "{bad:?} should not parse");
+        }
+    }
+
+    #[test]
+    fn datetimes_round_trip_through_system_time() {
+        let datetime: Datetime = "2026-10-25T18:30:05Z".parse().unwrap();
+        let time = to_system_time(&datetime).unwrap();
+        assert_eq!(
+            time.duration_since(UNIX_EPOCH).unwrap(),
+            Duration::from_secs(1_792_953_005)
+        );
+        assert_eq!(to_datetime(time).to_string(), "2026-10-25T18:30:05Z");
+
+        let shifted: Datetime = "2026-10-25T20:30:05+02:00".parse().unwrap();
+        assert_eq!(to_system_time(&shifted), Some(time));
+        let local: Datetime = "2026-10-25T18:30:05".parse().unwrap();
+        assert_eq!(to_system_time(&local), None);
+    }
+}
@@ -0,0 +1,118 @@
+//! Greetings: a short "good morning" or "good evening" clip played before
+//! the first card tapped in each part of the day.
+//!
+//! Each greeting has a window in the box's local time. The first card
+//! tapped while a window is open hears the clip, then its own track; later
+//! taps in the same window play straight away. Skipping or re-tapping the
+//! card cuts the clip short.
+
+use crate::controller::Track;
+use crate::night::{MINUTES_PER_DAY, NightSchedule};
+
+/// Which greeting is due.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum GreetingPeriod {
+    Morning,
+    Evening,
+}
+
+impl GreetingPeriod {
+    /// The window used when the config names a clip but no times: 05:00
+    /// to 11:00 for the morning and 17:00 to 22:00 for the evening.
+    pub fn default_window(self) -> NightSchedule {
+        let (from, until) = match self {
+            GreetingPeriod::Morning => ((5, 0), (11, 0)),
+            GreetingPeriod::Evening => ((17, 0), (22, 0)),
+        };
+        NightSchedule::from_times(from, until).expect("default greeting windows are valid")
+    }
+}
+
+/// A clip and the local times it greets between.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Greeting {
+    pub track: Track,
+    pub window: NightSchedule,
+}
+
+/// The `[greetings]` table.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Greetings {
+    pub morning: Option<Greeting>,
+    pub evening: Option<Greeting>,
+}
+
+impl Greetings {
+    /// The greeting whose window is open at `local_minutes` (minutes since
+    /// the epoch in local time, as [`crate::night::local_minutes`] counts
+    /// them), with the local day that window opened on. A window running
+    /// past midnight belongs to the day it opened.
+    pub fn due(&self, local_minutes: u64) -> Option<(GreetingPeriod, u64, &Track)> {
+        let day = local_minutes / u64::from(MINUTES_PER_DAY);
+        let minute = (local_minutes % u64::from(MINUTES_PER_DAY)) as u16;
+        [
+            (GreetingPeriod::Morning, &self.morning),
+            (GreetingPeriod::Evening, &self.evening),
+        ]
+        .into_iter()
+        .find_map(|(period, greeting)| {
+            let greeting = greeting.as_ref().filter(|g| g.window.contains(minute))?;
+            let window = greeting.window;
+            let opened = if window.from > window.until && minute < window.until {
+                day.saturating_sub(1)
+            } else {
+                day
+            };
+            Some((period, opened, &greeting.track))
+        })
+    }
+
+    /// The configured clips.
+    pub fn tracks(&self) -> impl Iterator<Item = &Track> {
+        [&self.morning, &self.evening]
+            .into_iter()
+            .flatten()
+            .map(|greeting| &greeting.track)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.morning.is_none() && self.evening.is_none()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    #[test]
+    fn due_greetings_follow_their_windows() {
+        let greetings = Greetings {
+            morning: Some(Greeting {
+                track: Track::new(PathBuf::from("/music/morning.mp3")),
+                window: GreetingPeriod::Morning.default_window(),
+            }),
+            evening: Some(Greeting {
+                track: Track::new(PathBuf::from("/music/evening.mp3")),
+                window: NightSchedule::from_times((20, 0), (2, 0)).unwrap(),
+            }),
+        };
+        let day = 20_000 * u64::from(MINUTES_PER_DAY);
+        let at = |hour: u64, minute: u64| day + hour * 60 + minute;
+
+        let (period, opened, track) = greetings.due(at(7, 30)).unwrap();
+        assert_eq!((period, opened), (GreetingPeriod::Morning, 20_000));
+        assert_eq!(track.path(), PathBuf::from("/music/morning.mp3"));
+        assert_eq!(greetings.due(at(11, 0)), None);
+        assert_eq!(greetings.due(at(14, 0)), None);
+
+        // After midnight the evening still belongs to the day before.
+        let (period, opened, _) = greetings.due(at(1, 0)).unwrap();
+        assert_eq!((period, opened), (GreetingPeriod::Evening, 19_999));
+        let (_, opened, _) = greetings.due(at(21, 0)).unwrap();
+        assert_eq!(opened, 20_000);
+
+        assert_eq!(greetings.tracks().count(), 2);
+        assert!(Greetings::default().due(at(7, 30)).is_none());
+    }
+}
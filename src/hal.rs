@@ -0,0 +1,282 @@
+//! The few GPIO lines and the write-only SPI bus the hardware add-ons use.
+//!
+//! Buttons, the volume knob, the WS2812 strip, and the e-ink display talk
+//! to their pins through the traits here rather than to `gpio-cdev` and
+//! `spidev` directly. Their logic then builds and is tested on any
+//! development machine against [`mock`] pins, and only the Linux
+//! implementations behind [`open_gpio`] and [`open_spi`] touch hardware.
+
+use std::path::Path;
+
+#[derive(Debug, thiserror::Error)]
+pub enum HalError {
+    #[error("GPIO error: {0}")]
+    Gpio(String),
+    #[error("SPI error: {0}")]
+    Spi(#[from] std::io::Error),
+    #[error("{0} is not available on this platform or in this build")]
+    Unsupported(&'static str),
+}
+
+/// A GPIO line read as an input.
+pub trait InputPin: Send {
+    fn is_high(&mut self) -> Result<bool, HalError>;
+}
+
+/// A GPIO line driven as an output.
+pub trait OutputPin: Send {
+    /// Drives the line high, or low for `false`.
+    fn set(&mut self, high: bool) -> Result<(), HalError>;
+}
+
+/// An SPI device that is only written to.
+pub trait SpiBus: Send {
+    fn write(&mut self, bytes: &[u8]) -> Result<(), HalError>;
+}
+
+/// A GPIO controller with numbered lines, such as `/dev/gpiochip0`.
+/// `consumer` names the program holding a line in `gpioinfo`.
+pub trait Gpio {
+    fn input(&mut self, line: u32, consumer: &str) -> Result<Box<dyn InputPin>, HalError>;
+
+    fn output(
+        &mut self,
+        line: u32,
+        initial_high: bool,
+        consumer: &str,
+    ) -> Result<Box<dyn OutputPin>, HalError>;
+}
+
+/// Opens the GPIO character device at `path`.
+pub fn open_gpio(path: &Path) -> Result<Box<dyn Gpio>, HalError> {
+    #[cfg(all(target_os = "linux", feature = "gpio"))]
+    return Ok(Box::new(cdev::CdevGpio::open(path)?));
+    #[cfg(not(all(target_os = "linux", feature = "gpio")))]
+    {
+        let _ = path;
+        Err(HalError::Unsupported("GPIO"))
+    }
+}
+
+/// Opens the spidev device at `path` in mode 0 with 8-bit words.
+pub fn open_spi(path: &Path, speed_hz: u32) -> Result<Box<dyn SpiBus>, HalError> {
+    #[cfg(all(target_os = "linux", feature = "spi"))]
+    return Ok(Box::new(spi::Spi::open(path, speed_hz)?));
+    #[cfg(not(all(target_os = "linux", feature = "spi")))]
+    {
+        let _ = (path, speed_hz);
+        Err(HalError::Unsupported("SPI"))
+    }
+}
+
+#[cfg(all(target_os = "linux", feature = "gpio"))]
+mod cdev {
+    use super::{Gpio, HalError, InputPin, OutputPin};
+    use gpio_cdev::{Chip, LineHandle, LineRequestFlags};
+    use std::path::Path;
+
+    impl From<gpio_cdev::errors::Error> for HalError {
+        fn from(err: gpio_cdev::errors::Error) -> Self {
+            HalError::Gpio(err.to_string())
+        }
+    }
+
+    pub struct CdevGpio(Chip);
+
+    impl CdevGpio {
+        pub fn open(path: &Path) -> Result<Self, HalError> {
+            Ok(Self(Chip::new(path)?))
+        }
+    }
+
+    impl Gpio for CdevGpio {
+        fn input(&mut self, line: u32, consumer: &str) -> Result<Box<dyn InputPin>, HalError> {
+            let handle = self
+                .0
+                .get_line(line)?
+                .request(LineRequestFlags::INPUT, 0, consumer)?;
+            Ok(Box::new(Line(handle)))
+        }
+
+        fn output(
+            &mut self,
+            line: u32,
+            initial_high: bool,
+            consumer: &str,
+        ) -> Result<Box<dyn OutputPin>, HalError> {
+            let handle = self.0.get_line(line)?.request(
+                LineRequestFlags::OUTPUT,
+                u8::from(initial_high),
+                consumer,
+            )?;
+            Ok(Box::new(Line(handle)))
+        }
+    }
+
+    struct Line(LineHandle);
+
+    impl InputPin for Line {
+        fn is_high(&mut self) -> Result<bool, HalError> {
+            Ok(self.0.get_value()? != 0)
+        }
+    }
+
+    impl OutputPin for Line {
+        fn set(&mut self, high: bool) -> Result<(), HalError> {
+            Ok(self.0.set_value(u8::from(high))?)
+        }
+    }
+}
+
+#[cfg(all(target_os = "linux", feature = "spi"))]
+mod spi {
+    use super::{HalError, SpiBus};
+    use spidev::{SpiModeFlags, Spidev, SpidevOptions};
+    use std::io::Write;
+    use std::path::Path;
+
+    pub struct Spi(Spidev);
+
+    impl Spi {
+        pub fn open(path: &Path, speed_hz: u32) -> Result<Self, HalError> {
+            let mut spi = Spidev::open(path)?;
+            let options = SpidevOptions::new()
+                .bits_per_word(8)
+                .max_speed_hz(speed_hz)
+                .mode(SpiModeFlags::SPI_MODE_0)
+                .build();
+            spi.configure(&options)?;
+            Ok(Self(spi))
+        }
+    }
+
+    impl SpiBus for Spi {
+        fn write(&mut self, bytes: &[u8]) -> Result<(), HalError> {
+            Ok(self.0.write_all(bytes)?)
+        }
+    }
+}
+
+/// Pins and buses that live in memory, for tests and for trying the
+/// hardware code on a machine without any.
+pub mod mock {
+    use super::{Gpio, HalError, InputPin, OutputPin, SpiBus};
+    use std::collections::HashMap;
+    use std::sync::{Arc, Mutex};
+
+    /// A GPIO controller whose line levels the caller sets and reads. Lines
+    /// start low; clones share them.
+    #[derive(Debug, Clone, Default)]
+    pub struct MockGpio {
+        levels: Arc<Mutex<HashMap<u32, bool>>>,
+    }
+
+    impl MockGpio {
+        pub fn new() -> Self {
+            Self::default()
+        }
+
+        /// Drives `line`, as a button or a peripheral would.
+        pub fn set(&self, line: u32, high: bool) {
+            self.levels.lock().expect("gpio lock").insert(line, high);
+        }
+
+        pub fn is_high(&self, line: u32) -> bool {
+            self.levels
+                .lock()
+                .expect("gpio lock")
+                .get(&line)
+                .copied()
+                .unwrap_or(false)
+        }
+
+        fn pin(&self, line: u32) -> MockPin {
+            MockPin {
+                gpio: self.clone(),
+                line,
+            }
+        }
+    }
+
+    impl Gpio for MockGpio {
+        fn input(&mut self, line: u32, _consumer: &str) -> Result<Box<dyn InputPin>, HalError> {
+            Ok(Box::new(self.pin(line)))
+        }
+
+        fn output(
+            &mut self,
+            line: u32,
+            initial_high: bool,
+            _consumer: &str,
+        ) -> Result<Box<dyn OutputPin>, HalError> {
+            self.set(line, initial_high);
+            Ok(Box::new(self.pin(line)))
+        }
+    }
+
+    struct MockPin {
+        gpio: MockGpio,
+        line: u32,
+    }
+
+    impl InputPin for MockPin {
+        fn is_high(&mut self) -> Result<bool, HalError> {
+            Ok(self.gpio.is_high(self.line))
+        }
+    }
+
+    impl OutputPin for MockPin {
+        fn set(&mut self, high: bool) -> Result<(), HalError> {
+            self.gpio.set(self.line, high);
+            Ok(())
+        }
+    }
+
+    /// An SPI bus that keeps everything written to it; clones share it.
+    #[derive(Debug, Clone, Default)]
+    pub struct MockSpi {
+        writes: Arc<Mutex<Vec<Vec<u8>>>>,
+    }
+
+    impl MockSpi {
+        pub fn new() -> Self {
+            Self::default()
+        }
+
+        /// Each write so far, oldest first.
+        pub fn writes(&self) -> Vec<Vec<u8>> {
+            self.writes.lock().expect("spi lock").clone()
+        }
+    }
+
+    impl SpiBus for MockSpi {
+        fn write(&mut self, bytes: &[u8]) -> Result<(), HalError> {
+            self.writes.lock().expect("spi lock").push(bytes.to_vec());
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::mock::{MockGpio, MockSpi};
+    use super::*;
+
+    #[test]
+    fn mock_pins_share_levels_with_their_controller() {
+        let mut gpio = MockGpio::new();
+        let mut button = gpio.input(5, "test").unwrap();
+        assert!(!button.is_high().unwrap());
+        gpio.set(5, true);
+        assert!(button.is_high().unwrap());
+
+        let mut reset = gpio.output(17, true, "test").unwrap();
+        assert!(gpio.is_high(17));
+        reset.set(false).unwrap();
+        assert!(!gpio.is_high(17));
+
+        let mut spi = MockSpi::new();
+        spi.write(&[1, 2]).unwrap();
+        assert_eq!(spi.writes(), vec![vec![1, 2]]);
+    }
+}
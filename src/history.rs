@@ -0,0 +1,349 @@
+//! Append-only JSON Lines log of notable events and of every track played.
+//!
+//! Each line is one self-contained JSON object, so the file survives crashes
+//! mid-write (at worst the last line is truncated) and can be inspected with
+//! `tail` or `jq` after a restart loop.
+
+use crate::controller::{CardUid, ControllerAction, Track};
+use crate::mirror;
+use serde::{Deserialize, Serialize};
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+#[derive(Debug, thiserror::Error)]
+pub enum HistoryError {
+    #[error("failed to write history log {path:?}: {source}")]
+    Io {
+        path: PathBuf,
+        #[source]
+        source: std::io::Error,
+    },
+    #[error("failed to read history log {path:?}: {source}")]
+    Read {
+        path: PathBuf,
+        #[source]
+        source: std::io::Error,
+    },
+    #[error("failed to encode history entry: {0}")]
+    Encode(#[from] serde_json::Error),
+}
+
+/// How many plays `musicbox history` and `/api/history` list unless asked
+/// for another number.
+pub const DEFAULT_LIMIT: usize = 50;
+
+/// Something worth remembering across restarts.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum HistoryEvent {
+    /// The process panicked and is about to exit.
+    Panic {
+        message: String,
+        location: Option<String>,
+    },
+    /// `card`, in lowercase hex, played `track` from `started`, in seconds
+    /// since the Unix epoch, for `played_ms`, not counting time spent
+    /// paused.
+    Played {
+        card: String,
+        track: PathBuf,
+        started: u64,
+        played_ms: u64,
+    },
+    /// `card`, in lowercase hex, reported its NFC read counter on a tap.
+    TagCounter { card: String, count: u32 },
+}
+
+/// A line read back from the log.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HistoryEntry {
+    /// When the line was written, in seconds since the Unix epoch.
+    pub timestamp: u64,
+    #[serde(flatten)]
+    pub event: HistoryEvent,
+}
+
+#[derive(Serialize)]
+struct HistoryLine<'a> {
+    /// Seconds since the Unix epoch.
+    timestamp: u64,
+    #[serde(flatten)]
+    event: &'a HistoryEvent,
+}
+
+/// Handle to a history file; every append opens, writes, and closes it.
+#[derive(Debug, Clone)]
+pub struct HistoryLog {
+    path: PathBuf,
+}
+
+impl HistoryLog {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn append(&self, event: &HistoryEvent) -> Result<(), HistoryError> {
+        let timestamp = unix_secs(SystemTime::now());
+        let mut line = serde_json::to_vec(&HistoryLine { timestamp, event })?;
+        line.push(b'\n');
+
+        let io_err = |source| HistoryError::Io {
+            path: self.path.clone(),
+            source,
+        };
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .map_err(io_err)?;
+        file.write_all(&line).map_err(io_err)?;
+        mirror::update(&self.path);
+        Ok(())
+    }
+
+    /// Every entry, oldest first. A missing file is an empty log. Lines
+    /// that do not parse, such as one cut short by a power cut, are
+    /// skipped.
+    pub fn read(&self) -> Result<Vec<HistoryEntry>, HistoryError> {
+        let contents = match fs::read_to_string(&self.path) {
+            Ok(contents) => contents,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(source) => {
+                return Err(HistoryError::Read {
+                    path: self.path.clone(),
+                    source,
+                });
+            }
+        };
+        Ok(contents
+            .lines()
+            .filter_map(|line| serde_json::from_str(line).ok())
+            .collect())
+    }
+
+    /// The `limit` most recent plays that started at or after `since`,
+    /// newest first.
+    pub fn recent_plays(
+        &self,
+        since: SystemTime,
+        limit: usize,
+    ) -> Result<Vec<HistoryEntry>, HistoryError> {
+        let since = unix_secs(since);
+        let mut plays: Vec<HistoryEntry> = self
+            .read()?
+            .into_iter()
+            .filter(|entry| {
+                matches!(entry.event, HistoryEvent::Played { started, .. } if started >= since)
+            })
+            .collect();
+        plays.reverse();
+        plays.truncate(limit);
+        Ok(plays)
+    }
+}
+
+/// Follows controller actions and turns each track that stops playing
+/// into a [`HistoryEvent::Played`].
+#[derive(Debug, Default)]
+pub struct PlayTracker {
+    current: Option<Play>,
+}
+
+#[derive(Debug)]
+struct Play {
+    card: CardUid,
+    track: PathBuf,
+    started: SystemTime,
+    /// Time played before the last pause.
+    played: Duration,
+    /// When playback last started or resumed; `None` while paused.
+    since: Option<Instant>,
+}
+
+impl PlayTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Notes `action`, taken at `now`, and returns the play it ended, if
+    /// any.
+    pub fn record(&mut self, action: &ControllerAction, now: Instant) -> Option<HistoryEvent> {
+        match action {
+            ControllerAction::Started { card, track }
+            | ControllerAction::Advanced { card, track }
+            | ControllerAction::Skipped { card, track }
+            | ControllerAction::Switched {
+                to_card: card,
+                to_track: track,
+                ..
+            } => {
+                let finished = self.finish(now);
+                self.start(card, track, now);
+                finished
+            }
+            ControllerAction::Paused { .. } => {
+                if let Some(play) = &mut self.current
+                    && let Some(since) = play.since.take()
+                {
+                    play.played += now.saturating_duration_since(since);
+                }
+                None
+            }
+            ControllerAction::Resumed { card, track } => {
+                match &mut self.current {
+                    Some(play) if play.since.is_none() => play.since = Some(now),
+                    Some(_) => {}
+                    // Resumed at startup, with nothing playing before.
+                    None => self.start(card, track, now),
+                }
+                None
+            }
+            ControllerAction::Stopped { .. }
+            | ControllerAction::CountdownFinished { .. }
+            | ControllerAction::TimedOut { .. }
+            | ControllerAction::ShutdownRequested { .. } => self.finish(now),
+            _ => None,
+        }
+    }
+
+    /// Ends the play in progress, such as when the box shuts down.
+    pub fn finish(&mut self, now: Instant) -> Option<HistoryEvent> {
+        let play = self.current.take()?;
+        let played = play.played
+            + play
+                .since
+                .map_or(Duration::ZERO, |since| now.saturating_duration_since(since));
+        Some(HistoryEvent::Played {
+            card: play.card.to_hex_lowercase(),
+            track: play.track,
+            started: unix_secs(play.started),
+            played_ms: u64::try_from(played.as_millis()).unwrap_or(u64::MAX),
+        })
+    }
+
+    fn start(&mut self, card: &CardUid, track: &Track, now: Instant) {
+        self.current = Some(Play {
+            card: card.clone(),
+            track: track.path().to_path_buf(),
+            started: SystemTime::now(),
+            played: Duration::ZERO,
+            since: Some(now),
+        });
+    }
+}
+
+fn unix_secs(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs())
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn appends_one_json_object_per_line() {
+        let tmp = tempdir().unwrap();
+        let log = HistoryLog::new(tmp.path().join("history.jsonl"));
+
+        for message in ["first", "second"] {
+            log.append(&HistoryEvent::Panic {
+                message: message.into(),
+                location: Some("src/main.rs:1:1".into()),
+            })
+            .unwrap();
+        }
+
+        let contents = std::fs::read_to_string(log.path()).unwrap();
+        let lines: Vec<serde_json::Value> = contents
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0]["event"], "panic");
+        assert_eq!(lines[1]["message"], "second");
+        assert!(lines[0]["timestamp"].as_u64().unwrap() > 0);
+    }
+
+    #[test]
+    fn tracks_plays_without_paused_time_and_reads_them_back() {
+        let card = CardUid::new(vec![0x0a, 0x0b]);
+        let track = |name: &str| Track::new(PathBuf::from(name));
+        let start = Instant::now();
+        let at = |secs: u64| start + Duration::from_secs(secs);
+        let mut tracker = PlayTracker::new();
+
+        let started = ControllerAction::Started {
+            card: card.clone(),
+            track: track("one.mp3"),
+        };
+        assert_eq!(tracker.record(&started, at(0)), None);
+        let paused = ControllerAction::Paused {
+            card: card.clone(),
+            track: track("one.mp3"),
+        };
+        tracker.record(&paused, at(30));
+        let resumed = ControllerAction::Resumed {
+            card: card.clone(),
+            track: track("one.mp3"),
+        };
+        tracker.record(&resumed, at(90));
+        let advanced = ControllerAction::Advanced {
+            card: card.clone(),
+            track: track("two.mp3"),
+        };
+        let Some(HistoryEvent::Played {
+            card: played_card,
+            track: played_track,
+            played_ms,
+            ..
+        }) = tracker.record(&advanced, at(100))
+        else {
+            panic!("advancing ends the first play");
+        };
+        assert_eq!(played_card, "0a0b");
+        assert_eq!(played_track, PathBuf::from("one.mp3"));
+        assert_eq!(played_ms, 40_000);
+        let volume = ControllerAction::VolumeChanged { percent: 50 };
+        assert_eq!(tracker.record(&volume, at(110)), None);
+
+        let tmp = tempdir().unwrap();
+        let log = HistoryLog::new(tmp.path().join("history.jsonl"));
+        assert!(log.read().unwrap().is_empty());
+        let second = tracker.finish(at(160)).unwrap();
+        assert!(matches!(
+            second,
+            HistoryEvent::Played {
+                played_ms: 60_000,
+                ..
+            }
+        ));
+        log.append(&HistoryEvent::Panic {
+            message: "boom".into(),
+            location: None,
+        })
+        .unwrap();
+        log.append(&second).unwrap();
+        std::fs::OpenOptions::new()
+            .append(true)
+            .open(log.path())
+            .unwrap()
+            .write_all(b"{\"timestamp\": 1, \"eve")
+            .unwrap();
+
+        assert_eq!(log.read().unwrap().len(), 2);
+        let plays = log.recent_plays(UNIX_EPOCH, 10).unwrap();
+        assert_eq!(plays.len(), 1);
+        assert_eq!(plays[0].event, second);
+        let later = SystemTime::now() + Duration::from_secs(60);
+        assert!(log.recent_plays(later, 10).unwrap().is_empty());
+    }
+}
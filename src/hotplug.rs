@@ -0,0 +1,566 @@
+//! Audio output that follows its device coming and going.
+//!
+//! A USB speaker plugged in after boot, or unplugged mid-song, used to mean
+//! restarting the box. [`HotplugPlayer`] instead plays silently while its
+//! device is missing, keeping time with a [`SimulatedPlayer`] so playlists
+//! and countdowns carry on, and moves the current track over to the device
+//! at the same position once it shows up again.
+//!
+//! Opened lazily, it also leaves the device closed until something plays
+//! and closes it again once the box has sat idle for a while. Opening the
+//! stream takes seconds on some Raspberry Pis, and a closed device is free
+//! for other programs while the box waits for a card.
+
+use crate::audio::{SimulatedPlayer, VirtualClock};
+use crate::controller::{AudioPlayer, OutputChange, PlayerCapabilities, PlayerError, Track};
+use std::time::{Duration, Instant};
+
+/// Names the device playback should use right now, or `None` if there is
+/// none to use.
+type Probe = Box<dyn FnMut() -> Option<String> + Send>;
+
+/// Opens a player on the named device.
+type Opener<P> = Box<dyn FnMut(&str) -> Result<P, PlayerError> + Send>;
+
+enum Output<P> {
+    Device { name: String, player: P },
+    Silent(SimulatedPlayer),
+}
+
+impl<P: AudioPlayer> Output<P> {
+    fn device(&self) -> Option<&str> {
+        match self {
+            Output::Device { name, .. } => Some(name),
+            Output::Silent(_) => None,
+        }
+    }
+
+    fn player(&self) -> &dyn AudioPlayer {
+        match self {
+            Output::Device { player, .. } => player,
+            Output::Silent(player) => player,
+        }
+    }
+
+    fn player_mut(&mut self) -> &mut dyn AudioPlayer {
+        match self {
+            Output::Device { player, .. } => player,
+            Output::Silent(player) => player,
+        }
+    }
+}
+
+/// Plays through `P` on whichever device the probe names, reopening it
+/// whenever [`AudioPlayer::refresh_output`] finds the device appeared,
+/// disappeared, or changed. Volume, mute, night mode, and the current
+/// track with its position move over to each newly opened output.
+pub struct HotplugPlayer<P> {
+    probe: Probe,
+    open: Opener<P>,
+    output: Output<P>,
+    clock: Option<VirtualClock>,
+    /// The device that last failed to open, so retries do not warn again.
+    failed: Option<String>,
+    volume: f32,
+    muted: bool,
+    night: bool,
+    /// The track last started, until it is stopped.
+    current: Option<Track>,
+    paused: bool,
+    /// The track had ended on an output that has since been replaced.
+    ended: bool,
+    /// How long the device may sit idle before it is closed, when it is
+    /// only opened to play.
+    release_after: Option<Duration>,
+    /// When [`AudioPlayer::refresh_output`] first found nothing playing.
+    idle_since: Option<Instant>,
+}
+
+impl<P: AudioPlayer> HotplugPlayer<P> {
+    /// A player that starts silent; call [`AudioPlayer::refresh_output`]
+    /// once to open the device if it is already there.
+    pub fn new(
+        probe: impl FnMut() -> Option<String> + Send + 'static,
+        open: impl FnMut(&str) -> Result<P, PlayerError> + Send + 'static,
+    ) -> Self {
+        Self {
+            probe: Box::new(probe),
+            open: Box::new(open),
+            output: Output::Silent(SimulatedPlayer::new()),
+            clock: None,
+            failed: None,
+            volume: 1.0,
+            muted: false,
+            night: false,
+            current: None,
+            paused: false,
+            ended: false,
+            release_after: None,
+            idle_since: None,
+        }
+    }
+
+    /// Leaves the device closed until a track or an effect plays, and
+    /// closes it once nothing has played for `release_after`.
+    /// [`AudioPlayer::refresh_output`] then leaves a closed device alone
+    /// while the box is idle.
+    pub fn with_lazy_open(mut self, release_after: Duration) -> Self {
+        self.release_after = Some(release_after);
+        self
+    }
+
+    /// Keeps silent playback on `clock` instead of the real one.
+    pub fn with_clock(mut self, clock: VirtualClock) -> Self {
+        if let Output::Silent(player) = &mut self.output {
+            *player = SimulatedPlayer::new().with_clock(clock.clone());
+        }
+        self.clock = Some(clock);
+        self
+    }
+
+    /// The device playback goes to, or `None` while it plays silently.
+    pub fn device(&self) -> Option<&str> {
+        self.output.device()
+    }
+
+    fn now(&self) -> Instant {
+        match &self.clock {
+            Some(clock) => clock.now(),
+            None => Instant::now(),
+        }
+    }
+
+    /// Nothing is playing: no track, or one that is paused or over.
+    fn is_idle(&self) -> bool {
+        self.current.is_none() || self.paused || self.is_finished()
+    }
+
+    /// Opens the device before a lazily opened player makes a sound. It
+    /// stays silent if there is no device or it fails to open.
+    fn wake(&mut self) {
+        self.idle_since = None;
+        if self.release_after.is_none() || self.output.device().is_some() {
+            return;
+        }
+        if let Some(name) = (self.probe)() {
+            self.open_device(name);
+        }
+    }
+
+    fn open_device(&mut self, name: String) -> Option<OutputChange> {
+        match (self.open)(&name) {
+            Ok(player) => {
+                tracing::info!(device = %name, "opened audio output");
+                self.failed = None;
+                self.switch(Output::Device {
+                    name: name.clone(),
+                    player,
+                });
+                Some(OutputChange::Opened(name))
+            }
+            Err(err) => {
+                if self.failed.as_deref() == Some(name.as_str()) {
+                    tracing::debug!(device = %name, %err, "audio output still fails to open");
+                } else {
+                    tracing::warn!(device = %name, %err, "could not open audio output; will retry");
+                    self.failed = Some(name);
+                }
+                None
+            }
+        }
+    }
+
+    /// Closes an idle device once it has been idle for `release_after`.
+    fn release_if_idle(&mut self, release_after: Duration) -> Option<OutputChange> {
+        if !self.is_idle() {
+            self.idle_since = None;
+            return None;
+        }
+        let now = self.now();
+        let since = *self.idle_since.get_or_insert(now);
+        let device = self.output.device()?;
+        if now.duration_since(since) < release_after {
+            return None;
+        }
+        tracing::info!(device, "released idle audio output");
+        let silence = self.silence();
+        self.switch(Output::Silent(silence));
+        Some(OutputChange::Released)
+    }
+
+    fn silence(&self) -> SimulatedPlayer {
+        match &self.clock {
+            Some(clock) => SimulatedPlayer::new().with_clock(clock.clone()),
+            None => SimulatedPlayer::new(),
+        }
+    }
+
+    /// Stops the old output and carries the playback state over to `to`.
+    fn switch(&mut self, mut to: Output<P>) {
+        let from = self.output.player_mut();
+        let position = from.position();
+        let ended = from.is_finished();
+        if let Err(err) = from.stop() {
+            tracing::debug!(%err, "could not stop the old audio output");
+        }
+
+        let player = to.player_mut();
+        let restored = player
+            .set_volume(self.volume)
+            .and_then(|()| player.set_muted(self.muted))
+            .and_then(|()| player.set_night_mode(self.night));
+        if let Err(err) = restored {
+            tracing::warn!(%err, "could not restore volume on the new audio output");
+        }
+        if let Some(track) = &self.current {
+            if ended {
+                self.ended = true;
+            } else if let Err(err) = Self::replay(player, track, position, self.paused) {
+                tracing::warn!(%err, "could not move the track to the new audio output");
+            }
+        }
+        self.output = to;
+    }
+
+    fn replay(
+        player: &mut dyn AudioPlayer,
+        track: &Track,
+        position: Option<Duration>,
+        paused: bool,
+    ) -> Result<(), PlayerError> {
+        player.play(track)?;
+        if let Some(position) = position {
+            player.seek(position)?;
+        }
+        if paused {
+            player.pause()?;
+        }
+        Ok(())
+    }
+}
+
+impl<P: AudioPlayer> AudioPlayer for HotplugPlayer<P> {
+    fn capabilities(&self) -> PlayerCapabilities {
+        self.output.player().capabilities()
+    }
+
+    fn play(&mut self, track: &Track) -> Result<(), PlayerError> {
+        // The old track is not carried over to a device opened to play a
+        // new one.
+        self.current = None;
+        self.wake();
+        self.current = Some(track.clone());
+        self.paused = false;
+        self.ended = false;
+        self.output.player_mut().play(track)
+    }
+
+    fn stop(&mut self) -> Result<(), PlayerError> {
+        self.current = None;
+        self.ended = false;
+        self.output.player_mut().stop()
+    }
+
+    fn pause(&mut self) -> Result<(), PlayerError> {
+        self.paused = true;
+        self.output.player_mut().pause()
+    }
+
+    fn resume(&mut self) -> Result<(), PlayerError> {
+        self.paused = false;
+        self.wake();
+        self.output.player_mut().resume()
+    }
+
+    fn wait_until_done(&mut self) -> Result<(), PlayerError> {
+        self.output.player_mut().wait_until_done()
+    }
+
+    fn is_finished(&self) -> bool {
+        self.ended || self.output.player().is_finished()
+    }
+
+    fn set_volume(&mut self, volume: f32) -> Result<(), PlayerError> {
+        self.volume = volume;
+        self.output.player_mut().set_volume(volume)
+    }
+
+    fn volume(&self) -> f32 {
+        self.volume
+    }
+
+    fn set_muted(&mut self, muted: bool) -> Result<(), PlayerError> {
+        self.muted = muted;
+        self.output.player_mut().set_muted(muted)
+    }
+
+    fn set_night_mode(&mut self, on: bool) -> Result<(), PlayerError> {
+        self.night = on;
+        self.output.player_mut().set_night_mode(on)
+    }
+
+    fn position(&self) -> Option<Duration> {
+        self.output.player().position()
+    }
+
+    fn seek(&mut self, offset: Duration) -> Result<(), PlayerError> {
+        self.ended = false;
+        self.output.player_mut().seek(offset)
+    }
+
+    fn first_sound_at(&self) -> Option<Instant> {
+        self.output.player().first_sound_at()
+    }
+
+    fn fade_out(&mut self, over: Duration) -> Result<(), PlayerError> {
+        self.current = None;
+        self.ended = false;
+        self.output.player_mut().fade_out(over)
+    }
+
+    fn play_effect(&mut self, effect: &Track) -> Result<(), PlayerError> {
+        self.wake();
+        self.output.player_mut().play_effect(effect)
+    }
+
+    fn refresh_output(&mut self) -> Option<OutputChange> {
+        if let Some(release_after) = self.release_after {
+            if let Some(released) = self.release_if_idle(release_after) {
+                return Some(released);
+            }
+            if self.output.device().is_none() && self.is_idle() {
+                return None;
+            }
+        }
+        let found = (self.probe)();
+        if found.as_deref() == self.output.device() {
+            return None;
+        }
+        let Some(name) = found else {
+            tracing::warn!(
+                device = self.output.device(),
+                "audio output went away; playing silently until it returns"
+            );
+            let silence = self.silence();
+            self.switch(Output::Silent(silence));
+            return Some(OutputChange::Lost);
+        };
+        self.open_device(name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audio::SIMULATED_TRACK_LENGTH;
+    use std::collections::HashMap;
+    use std::path::PathBuf;
+    use std::sync::{Arc, Mutex};
+
+    /// A hotplug player over simulated devices, with the device the probe
+    /// reports under the test's control.
+    fn player(
+        clock: &VirtualClock,
+        lengths: &HashMap<PathBuf, Duration>,
+    ) -> (HotplugPlayer<SimulatedPlayer>, Arc<Mutex<Option<String>>>) {
+        let plugged = Arc::new(Mutex::new(None));
+        let probe = {
+            let plugged = plugged.clone();
+            move || plugged.lock().unwrap().clone()
+        };
+        let open = {
+            let clock = clock.clone();
+            let lengths = lengths.clone();
+            move |name: &str| {
+                if name == "broken" {
+                    return Err(PlayerError::Backend {
+                        message: "device busy".to_string(),
+                    });
+                }
+                Ok(SimulatedPlayer::new()
+                    .with_clock(clock.clone())
+                    .with_lengths(lengths.clone()))
+            }
+        };
+        let player = HotplugPlayer::new(probe, open).with_clock(clock.clone());
+        (player, plugged)
+    }
+
+    #[test]
+    fn a_late_device_picks_up_the_track_where_silence_left_it() {
+        let clock = VirtualClock::new();
+        let track = Track::new(PathBuf::from("song.mp3"));
+        // Silence has no file to measure and falls back to the default.
+        let lengths = HashMap::from([(track.path().to_path_buf(), SIMULATED_TRACK_LENGTH)]);
+        let (mut player, plugged) = player(&clock, &lengths);
+
+        assert_eq!(player.refresh_output(), None);
+        assert_eq!(player.device(), None);
+        player.set_volume(0.4).unwrap();
+        player.play(&track).unwrap();
+        clock.advance(Duration::from_secs(20));
+
+        *plugged.lock().unwrap() = Some("usb".to_string());
+        assert_eq!(
+            player.refresh_output(),
+            Some(OutputChange::Opened("usb".to_string()))
+        );
+        assert_eq!(player.device(), Some("usb"));
+        assert_eq!(player.refresh_output(), None);
+        assert_eq!(player.position(), Some(Duration::from_secs(20)));
+        assert_eq!(player.output.player().volume(), 0.4);
+
+        player.pause().unwrap();
+        *plugged.lock().unwrap() = None;
+        assert_eq!(player.refresh_output(), Some(OutputChange::Lost));
+        clock.advance(Duration::from_secs(5));
+        assert_eq!(player.position(), Some(Duration::from_secs(20)));
+
+        player.resume().unwrap();
+        clock.advance(SIMULATED_TRACK_LENGTH);
+        assert!(player.is_finished());
+        *plugged.lock().unwrap() = Some("hdmi".to_string());
+        assert_eq!(
+            player.refresh_output(),
+            Some(OutputChange::Opened("hdmi".to_string()))
+        );
+        assert!(player.is_finished(), "the track end must not get lost");
+    }
+
+    #[test]
+    fn a_device_that_fails_to_open_is_retried() {
+        let clock = VirtualClock::new();
+        let (mut player, plugged) = player(&clock, &HashMap::new());
+
+        *plugged.lock().unwrap() = Some("broken".to_string());
+        assert_eq!(player.refresh_output(), None);
+        assert_eq!(player.refresh_output(), None);
+        assert_eq!(player.device(), None);
+
+        *plugged.lock().unwrap() = Some("usb".to_string());
+        assert_eq!(
+            player.refresh_output(),
+            Some(OutputChange::Opened("usb".to_string()))
+        );
+    }
+
+    #[test]
+    fn a_lazy_device_opens_to_play_and_closes_when_idle() {
+        let clock = VirtualClock::new();
+        let (player, plugged) = player(&clock, &HashMap::new());
+        let mut player = player.with_lazy_open(Duration::from_secs(60));
+        *plugged.lock().unwrap() = Some("usb".to_string());
+
+        assert_eq!(player.refresh_output(), None);
+        assert_eq!(player.device(), None, "idle boxes leave the device closed");
+
+        let track = Track::new(PathBuf::from("song.mp3"));
+        player.play(&track).unwrap();
+        assert_eq!(player.device(), Some("usb"));
+        clock.advance(Duration::from_secs(30));
+        player.pause().unwrap();
+        assert_eq!(player.refresh_output(), None);
+        clock.advance(Duration::from_secs(59));
+        assert_eq!(player.refresh_output(), None);
+        clock.advance(Duration::from_secs(1));
+        assert_eq!(player.refresh_output(), Some(OutputChange::Released));
+        assert_eq!(player.device(), None);
+        assert_eq!(player.refresh_output(), None);
+
+        player.resume().unwrap();
+        assert_eq!(player.device(), Some("usb"));
+        assert_eq!(player.position(), Some(Duration::from_secs(30)));
+        clock.advance(Duration::from_secs(120));
+        assert_eq!(player.refresh_output(), None, "playing keeps it open");
+
+        player.stop().unwrap();
+        player
+            .play_effect(&Track::new(PathBuf::from("chime.wav")))
+            .unwrap();
+        assert_eq!(player.device(), Some("usb"));
+    }
+
+    /// A simulated device that notes every track it is asked to play.
+    struct CountingPlayer {
+        inner: SimulatedPlayer,
+        plays: Arc<Mutex<Vec<PathBuf>>>,
+    }
+
+    impl AudioPlayer for CountingPlayer {
+        fn play(&mut self, track: &Track) -> Result<(), PlayerError> {
+            self.plays.lock().unwrap().push(track.path().to_path_buf());
+            self.inner.play(track)
+        }
+
+        fn stop(&mut self) -> Result<(), PlayerError> {
+            self.inner.stop()
+        }
+
+        fn pause(&mut self) -> Result<(), PlayerError> {
+            self.inner.pause()
+        }
+
+        fn resume(&mut self) -> Result<(), PlayerError> {
+            self.inner.resume()
+        }
+
+        fn is_finished(&self) -> bool {
+            self.inner.is_finished()
+        }
+
+        fn position(&self) -> Option<Duration> {
+            self.inner.position()
+        }
+
+        fn seek(&mut self, offset: Duration) -> Result<(), PlayerError> {
+            self.inner.seek(offset)
+        }
+    }
+
+    #[test]
+    fn a_released_device_reopens_for_just_the_new_track() {
+        let clock = VirtualClock::new();
+        let plays = Arc::new(Mutex::new(Vec::new()));
+        let open = {
+            let clock = clock.clone();
+            let plays = plays.clone();
+            move |_: &str| {
+                Ok(CountingPlayer {
+                    inner: SimulatedPlayer::new().with_clock(clock.clone()),
+                    plays: plays.clone(),
+                })
+            }
+        };
+        let mut player = HotplugPlayer::new(|| Some("usb".to_string()), open)
+            .with_clock(clock.clone())
+            .with_lazy_open(Duration::from_secs(60));
+        let release = |player: &mut HotplugPlayer<CountingPlayer>| {
+            assert_eq!(player.refresh_output(), None);
+            clock.advance(Duration::from_secs(60));
+            assert_eq!(player.refresh_output(), Some(OutputChange::Released));
+        };
+
+        // The last track ran to its end before the device closed.
+        player
+            .play(&Track::new(PathBuf::from("first.mp3")))
+            .unwrap();
+        clock.advance(SIMULATED_TRACK_LENGTH);
+        release(&mut player);
+        plays.lock().unwrap().clear();
+        player
+            .play(&Track::new(PathBuf::from("second.mp3")))
+            .unwrap();
+        assert!(!player.is_finished());
+        assert_eq!(*plays.lock().unwrap(), [PathBuf::from("second.mp3")]);
+
+        // The last track was paused before the device closed.
+        player.pause().unwrap();
+        release(&mut player);
+        plays.lock().unwrap().clear();
+        player
+            .play(&Track::new(PathBuf::from("third.mp3")))
+            .unwrap();
+        assert!(!player.is_finished());
+        assert_eq!(*plays.lock().unwrap(), [PathBuf::from("third.mp3")]);
+    }
+}
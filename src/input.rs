@@ -0,0 +1,198 @@
+//! Physical buttons next to the card reader.
+//!
+//! A [`ButtonSource`] reports presses; the run loop polls it between reader
+//! events and hands each press to the controller. Buttons are wired between
+//! a GPIO pin and ground with a pull-up, so a pressed button reads low.
+
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+/// What a button press asks for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ButtonEvent {
+    Next,
+    Previous,
+    PlayPause,
+    VolumeUp,
+    VolumeDown,
+    Mute,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum InputError {
+    #[error("button input stopped: {0}")]
+    Disconnected(String),
+}
+
+/// Something that reports button presses.
+pub trait ButtonSource {
+    /// Presses since the last call, oldest first. Must not block.
+    fn poll(&mut self) -> Result<Vec<ButtonEvent>, InputError>;
+}
+
+/// Used when no buttons are configured.
+#[derive(Debug, Default)]
+pub struct NoButtons;
+
+impl ButtonSource for NoButtons {
+    fn poll(&mut self) -> Result<Vec<ButtonEvent>, InputError> {
+        Ok(Vec::new())
+    }
+}
+
+/// The `[buttons]` config table: which GPIO line each button is on.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ButtonConfig {
+    pub gpio_chip: PathBuf,
+    pub pins: Vec<(u32, ButtonEvent)>,
+}
+
+/// Presses closer together than this are treated as contact bounce.
+pub const DEBOUNCE: Duration = Duration::from_millis(50);
+
+/// Turns raw level samples of one button into presses.
+#[derive(Debug, Clone, Default)]
+pub struct Debouncer {
+    pressed: bool,
+    last_change: Option<Instant>,
+}
+
+impl Debouncer {
+    /// Feeds one sample. Returns `true` once per press, when the button goes
+    /// down after staying up for at least [`DEBOUNCE`].
+    pub fn update(&mut self, pressed: bool, now: Instant) -> bool {
+        if pressed == self.pressed {
+            return false;
+        }
+        let settled = self
+            .last_change
+            .is_none_or(|changed| now.duration_since(changed) >= DEBOUNCE);
+        if !settled {
+            return false;
+        }
+        self.pressed = pressed;
+        self.last_change = Some(now);
+        pressed
+    }
+}
+
+/// Buttons on GPIO lines, sampled on a background thread so short presses
+/// are not missed between reader polls.
+pub mod gpio {
+    use super::{ButtonConfig, ButtonEvent, ButtonSource, Debouncer, InputError};
+    use crate::hal::{self, Gpio, HalError, InputPin};
+    use std::sync::mpsc::{self, Receiver, TryRecvError};
+    use std::time::{Duration, Instant};
+
+    const GPIO_CONSUMER_TAG: &str = "musicbox-buttons";
+    const SAMPLE_INTERVAL: Duration = Duration::from_millis(5);
+
+    pub struct GpioButtons {
+        presses: Receiver<ButtonEvent>,
+    }
+
+    impl GpioButtons {
+        /// Opens the lines in `config` on its GPIO chip.
+        pub fn open(config: &ButtonConfig) -> Result<Self, HalError> {
+            let mut gpio = hal::open_gpio(&config.gpio_chip)?;
+            Self::with_gpio(gpio.as_mut(), &config.pins)
+        }
+
+        /// Samples `pins`, each a line on `gpio` and the button wired to it.
+        pub fn with_gpio(
+            gpio: &mut dyn Gpio,
+            pins: &[(u32, ButtonEvent)],
+        ) -> Result<Self, HalError> {
+            let mut lines: Vec<(Box<dyn InputPin>, ButtonEvent, Debouncer)> = Vec::new();
+            for &(pin, button) in pins {
+                let line = gpio.input(pin, GPIO_CONSUMER_TAG)?;
+                lines.push((line, button, Debouncer::default()));
+            }
+
+            let (sender, presses) = mpsc::channel();
+            std::thread::spawn(move || {
+                loop {
+                    let now = Instant::now();
+                    for (line, button, debouncer) in &mut lines {
+                        let pressed = match line.is_high() {
+                            Ok(high) => !high,
+                            Err(err) => {
+                                tracing::warn!(%err, ?button, "failed to read button");
+                                return;
+                            }
+                        };
+                        if debouncer.update(pressed, now) && sender.send(*button).is_err() {
+                            return;
+                        }
+                    }
+                    std::thread::sleep(SAMPLE_INTERVAL);
+                }
+            });
+            Ok(Self { presses })
+        }
+    }
+
+    impl ButtonSource for GpioButtons {
+        fn poll(&mut self) -> Result<Vec<ButtonEvent>, InputError> {
+            let mut events = Vec::new();
+            loop {
+                match self.presses.try_recv() {
+                    Ok(event) => events.push(event),
+                    Err(TryRecvError::Empty) => return Ok(events),
+                    Err(TryRecvError::Disconnected) if !events.is_empty() => {
+                        return Ok(events);
+                    }
+                    Err(TryRecvError::Disconnected) => {
+                        return Err(InputError::Disconnected(
+                            "GPIO sampling thread exited".into(),
+                        ));
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn debouncer_reports_each_press_once_and_ignores_bounce() {
+        let start = Instant::now();
+        let at = |millis| start + Duration::from_millis(millis);
+        let mut debouncer = Debouncer::default();
+
+        assert!(debouncer.update(true, at(0)));
+        assert!(!debouncer.update(true, at(5)));
+        // Bounce right after the press settles back to pressed.
+        assert!(!debouncer.update(false, at(10)));
+        assert!(!debouncer.update(true, at(15)));
+        assert!(!debouncer.update(false, at(120)));
+        assert!(!debouncer.update(true, at(130)));
+        assert!(debouncer.update(true, at(200)));
+    }
+
+    #[test]
+    fn gpio_buttons_report_a_line_pulled_low() {
+        let mut gpio = crate::hal::mock::MockGpio::new();
+        gpio.set(5, true);
+        gpio.set(6, true);
+        let mut buttons = gpio::GpioButtons::with_gpio(
+            &mut gpio,
+            &[(5, ButtonEvent::Next), (6, ButtonEvent::Mute)],
+        )
+        .unwrap();
+        std::thread::sleep(Duration::from_millis(20));
+        assert_eq!(buttons.poll().unwrap(), vec![]);
+
+        gpio.set(6, false);
+        let deadline = Instant::now() + Duration::from_secs(2);
+        let mut presses = Vec::new();
+        while presses.is_empty() && Instant::now() < deadline {
+            std::thread::sleep(Duration::from_millis(5));
+            presses = buttons.poll().unwrap();
+        }
+        assert_eq!(presses, vec![ButtonEvent::Mute]);
+    }
+}
@@ -0,0 +1,143 @@
+//! A rotary encoder used as a volume knob.
+//!
+//! Encoders such as the KY-040 pull two pins (A and B) low in an overlapping
+//! pattern as they turn; which pin changes first gives the direction. One
+//! detent walks through all four A/B combinations, so the decoder reports a
+//! step only after a full cycle, which also filters out contact bounce.
+
+/// Turns A/B pin samples into detent steps.
+#[derive(Debug, Clone)]
+pub struct QuadratureDecoder {
+    last: u8,
+    accumulated: i8,
+}
+
+/// Movement for each `(previous << 2) | current` state pair: +1 when A
+/// leads B, -1 when B leads A, 0 for no change or an impossible jump.
+const TRANSITIONS: [i8; 16] = [0, -1, 1, 0, 1, 0, 0, -1, -1, 0, 0, 1, 0, 1, -1, 0];
+const TRANSITIONS_PER_DETENT: i8 = 4;
+
+impl QuadratureDecoder {
+    /// Starts from the pins' current levels.
+    pub fn new(a: bool, b: bool) -> Self {
+        Self {
+            last: encode(a, b),
+            accumulated: 0,
+        }
+    }
+
+    /// Feeds one sample of both pins. Returns +1 for a clockwise detent, -1
+    /// for a counter-clockwise one, and 0 otherwise.
+    pub fn update(&mut self, a: bool, b: bool) -> i8 {
+        let state = encode(a, b);
+        self.accumulated += TRANSITIONS[usize::from((self.last << 2) | state)];
+        self.last = state;
+        if self.accumulated >= TRANSITIONS_PER_DETENT {
+            self.accumulated = 0;
+            1
+        } else if self.accumulated <= -TRANSITIONS_PER_DETENT {
+            self.accumulated = 0;
+            -1
+        } else {
+            0
+        }
+    }
+}
+
+fn encode(a: bool, b: bool) -> u8 {
+    (u8::from(a) << 1) | u8::from(b)
+}
+
+/// An encoder wired to two GPIO inputs. The pins need pull-ups; KY-040
+/// boards have them on board.
+pub mod gpio {
+    use super::QuadratureDecoder;
+    use crate::hal::{self, Gpio, HalError, InputPin};
+    use std::path::Path;
+
+    const GPIO_CONSUMER_TAG: &str = "musicbox-knob";
+
+    #[derive(Debug, thiserror::Error)]
+    #[error("volume knob GPIO error: {0}")]
+    pub struct KnobError(#[from] HalError);
+
+    pub struct RotaryKnob {
+        a: Box<dyn InputPin>,
+        b: Box<dyn InputPin>,
+        decoder: QuadratureDecoder,
+    }
+
+    impl RotaryKnob {
+        pub fn open(chip_path: &Path, pin_a: u32, pin_b: u32) -> Result<Self, KnobError> {
+            let mut gpio = hal::open_gpio(chip_path)?;
+            Self::with_gpio(gpio.as_mut(), pin_a, pin_b)
+        }
+
+        /// An encoder on lines `pin_a` and `pin_b` of `gpio`.
+        pub fn with_gpio(gpio: &mut dyn Gpio, pin_a: u32, pin_b: u32) -> Result<Self, KnobError> {
+            let mut a = gpio.input(pin_a, GPIO_CONSUMER_TAG)?;
+            let mut b = gpio.input(pin_b, GPIO_CONSUMER_TAG)?;
+            let decoder = QuadratureDecoder::new(a.is_high()?, b.is_high()?);
+            Ok(Self { a, b, decoder })
+        }
+
+        /// Samples both pins; call every millisecond or two so fast turns
+        /// do not skip states. Returns the detent step, if any.
+        pub fn poll(&mut self) -> Result<i8, KnobError> {
+            let a = self.a.is_high()?;
+            let b = self.b.is_high()?;
+            Ok(self.decoder.update(a, b))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Feeds `states` as (A, B) pairs and sums the reported steps.
+    fn turn(decoder: &mut QuadratureDecoder, states: &[(bool, bool)]) -> i32 {
+        states
+            .iter()
+            .map(|&(a, b)| i32::from(decoder.update(a, b)))
+            .sum()
+    }
+
+    // One detent each way, starting and ending at rest with both pins high.
+    const CLOCKWISE: [(bool, bool); 4] =
+        [(false, true), (false, false), (true, false), (true, true)];
+    const COUNTER_CLOCKWISE: [(bool, bool); 4] =
+        [(true, false), (false, false), (false, true), (true, true)];
+
+    #[test]
+    fn full_cycles_report_one_step_per_detent() {
+        let mut decoder = QuadratureDecoder::new(true, true);
+        assert_eq!(turn(&mut decoder, &CLOCKWISE), 1);
+        assert_eq!(turn(&mut decoder, &CLOCKWISE), 1);
+        assert_eq!(turn(&mut decoder, &COUNTER_CLOCKWISE), -1);
+    }
+
+    #[test]
+    fn bounce_and_partial_turns_do_not_step() {
+        let mut decoder = QuadratureDecoder::new(true, true);
+        let bounce = [(false, true), (true, true), (false, true), (true, true)];
+        assert_eq!(turn(&mut decoder, &bounce), 0);
+        assert_eq!(turn(&mut decoder, &CLOCKWISE[..2]), 0);
+        assert_eq!(turn(&mut decoder, &CLOCKWISE[2..]), 1);
+    }
+
+    #[test]
+    fn gpio_knob_steps_as_its_pins_change() {
+        let mut gpio = crate::hal::mock::MockGpio::new();
+        gpio.set(22, true);
+        gpio.set(23, true);
+        let mut knob = gpio::RotaryKnob::with_gpio(&mut gpio, 22, 23).unwrap();
+        let mut steps = 0;
+        for (a, b) in COUNTER_CLOCKWISE {
+            gpio.set(22, a);
+            gpio.set(23, b);
+            steps += knob.poll().unwrap();
+        }
+        assert_eq!(steps, -1);
+    }
+}
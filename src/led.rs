@@ -0,0 +1,227 @@
+//! Status LEDs that glow in a card's color while it plays.
+//!
+//! Pre-readers cannot check the display, so a per-card color (set with
+//! `color = "#rrggbb"` in the card table) confirms the right card was
+//! recognized. Backends only see the color to show, or `None` for off.
+
+use crate::hal::HalError;
+use std::fmt;
+use thiserror::Error;
+
+/// A 24-bit color parsed from `#rrggbb` notation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Rgb {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+#[error("invalid color {0:?}; expected #rrggbb")]
+pub struct ColorParseError(pub String);
+
+impl Rgb {
+    pub const fn new(r: u8, g: u8, b: u8) -> Self {
+        Self { r, g, b }
+    }
+
+    /// Parses `#rrggbb` (the `#` is optional, case is ignored).
+    pub fn parse(value: &str) -> Result<Self, ColorParseError> {
+        let trimmed = value.trim();
+        let hex = trimmed.strip_prefix('#').unwrap_or(trimmed);
+        let channel = |range: std::ops::Range<usize>| {
+            hex.get(range)
+                .and_then(|digits| u8::from_str_radix(digits, 16).ok())
+        };
+        match (hex.len(), channel(0..2), channel(2..4), channel(4..6)) {
+            (6, Some(r), Some(g), Some(b)) => Ok(Self { r, g, b }),
+            _ => Err(ColorParseError(value.to_string())),
+        }
+    }
+}
+
+impl fmt::Display for Rgb {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "#{:02x}{:02x}{:02x}", self.r, self.g, self.b)
+    }
+}
+
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum LedError {
+    #[error("LED I/O error: {0}")]
+    Hal(#[from] HalError),
+}
+
+/// A light that shows the color hint of whatever is playing.
+pub trait LedIndicator: Send {
+    /// Lights every LED in `color`, or turns them off for `None`.
+    fn show(&mut self, color: Option<Rgb>) -> Result<(), LedError>;
+}
+
+/// Used when no LED hardware is configured.
+#[derive(Debug, Default)]
+pub struct NullLed;
+
+impl LedIndicator for NullLed {
+    fn show(&mut self, _color: Option<Rgb>) -> Result<(), LedError> {
+        Ok(())
+    }
+}
+
+/// Skips writes when the requested color is already showing, so callers can
+/// sync on every poll without flooding the bus.
+pub struct LedHint {
+    led: Box<dyn LedIndicator>,
+    current: Option<Option<Rgb>>,
+}
+
+impl LedHint {
+    pub fn new(led: Box<dyn LedIndicator>) -> Self {
+        Self { led, current: None }
+    }
+
+    pub fn show(&mut self, color: Option<Rgb>) -> Result<(), LedError> {
+        if self.current == Some(color) {
+            return Ok(());
+        }
+        self.led.show(color)?;
+        self.current = Some(color);
+        Ok(())
+    }
+}
+
+/// WS2812 ("NeoPixel") strips driven from an SPI MOSI pin.
+///
+/// At 2.4 MHz each SPI bit lasts ~417 ns, so every WS2812 data bit is sent
+/// as three SPI bits: `110` for one and `100` for zero.
+pub mod ws2812 {
+    use super::Rgb;
+
+    /// Encodes `colors` (one per LED, in strip order) as an SPI bitstream,
+    /// followed by a low reset period.
+    pub fn encode(colors: &[Rgb]) -> Vec<u8> {
+        const RESET_BYTES: usize = 24;
+        let mut bits: Vec<bool> = Vec::with_capacity(colors.len() * 72);
+        for color in colors {
+            // WS2812 expects green, red, blue, most significant bit first.
+            for byte in [color.g, color.r, color.b] {
+                for shift in (0..8).rev() {
+                    let one = byte & (1 << shift) != 0;
+                    bits.extend([true, one, false]);
+                }
+            }
+        }
+        let mut out: Vec<u8> = bits
+            .chunks(8)
+            .map(|chunk| {
+                chunk.iter().enumerate().fold(0u8, |acc, (index, bit)| {
+                    acc | (u8::from(*bit) << (7 - index))
+                })
+            })
+            .collect();
+        out.resize(out.len() + RESET_BYTES, 0);
+        out
+    }
+
+    pub use spi::Ws2812Led;
+
+    mod spi {
+        use super::super::{LedError, LedIndicator, Rgb};
+        use super::encode;
+        use crate::hal::{self, SpiBus};
+        use std::path::Path;
+
+        const SPI_SPEED_HZ: u32 = 2_400_000;
+
+        /// A WS2812 strip on the MOSI pin of an SPI bus.
+        pub struct Ws2812Led {
+            spi: Box<dyn SpiBus>,
+            count: usize,
+        }
+
+        impl Ws2812Led {
+            /// Opens the strip on the spidev device at `path`.
+            pub fn open(path: &Path, count: usize) -> Result<Self, LedError> {
+                Ok(Self::new(hal::open_spi(path, SPI_SPEED_HZ)?, count))
+            }
+
+            /// A strip of `count` LEDs on `spi`, which must run at 2.4 MHz.
+            pub fn new(spi: Box<dyn SpiBus>, count: usize) -> Self {
+                Self { spi, count }
+            }
+        }
+
+        impl LedIndicator for Ws2812Led {
+            fn show(&mut self, color: Option<Rgb>) -> Result<(), LedError> {
+                let color = color.unwrap_or(Rgb::new(0, 0, 0));
+                self.spi.write(&encode(&vec![color; self.count]))?;
+                Ok(())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+
+    #[test]
+    fn parses_hex_colors() {
+        assert_eq!(Rgb::parse("#FF8000"), Ok(Rgb::new(0xff, 0x80, 0x00)));
+        assert_eq!(Rgb::parse("00ff7f"), Ok(Rgb::new(0x00, 0xff, 0x7f)));
+        assert!(Rgb::parse("#fff").is_err());
+        assert!(Rgb::parse("#gg0000").is_err());
+        assert_eq!(Rgb::new(1, 2, 255).to_string(), "#0102ff");
+    }
+
+    #[test]
+    fn ws2812_encoding_sends_grb_as_three_bit_symbols() {
+        let encoded = ws2812::encode(&[Rgb::new(0x00, 0xff, 0x00)]);
+
+        // Green 0xff: eight `110` symbols = 0b110110110110110110110110 (x3 bytes).
+        assert_eq!(&encoded[..3], &[0xdb, 0x6d, 0xb6]);
+        // Red 0x00: eight `100` symbols.
+        assert_eq!(&encoded[3..6], &[0x92, 0x49, 0x24]);
+        assert_eq!(encoded.len(), 9 + 24);
+        assert!(encoded[9..].iter().all(|byte| *byte == 0));
+    }
+
+    #[test]
+    fn ws2812_strip_writes_one_color_per_led() {
+        let spi = crate::hal::mock::MockSpi::new();
+        let mut strip = ws2812::Ws2812Led::new(Box::new(spi.clone()), 3);
+        strip.show(Some(Rgb::new(0, 0xff, 0))).unwrap();
+        strip.show(None).unwrap();
+
+        let writes = spi.writes();
+        assert_eq!(writes.len(), 2);
+        assert_eq!(writes[0], ws2812::encode(&[Rgb::new(0, 0xff, 0); 3]));
+        assert_eq!(writes[1], ws2812::encode(&[Rgb::new(0, 0, 0); 3]));
+    }
+
+    #[derive(Clone, Default)]
+    struct RecordingLed(Arc<Mutex<Vec<Option<Rgb>>>>);
+
+    impl LedIndicator for RecordingLed {
+        fn show(&mut self, color: Option<Rgb>) -> Result<(), LedError> {
+            self.0.lock().unwrap().push(color);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn hint_only_writes_changes() {
+        let led = RecordingLed::default();
+        let mut hint = LedHint::new(Box::new(led.clone()));
+        let orange = Some(Rgb::new(0xff, 0x80, 0));
+
+        hint.show(None).unwrap();
+        hint.show(orange).unwrap();
+        hint.show(orange).unwrap();
+        hint.show(None).unwrap();
+
+        assert_eq!(*led.0.lock().unwrap(), vec![None, orange, None]);
+    }
+}
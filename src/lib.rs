@@ -1,9 +1,55 @@
+pub mod access;
 pub mod app;
 pub mod audio;
+pub mod backup;
+pub mod checksum;
 pub mod config;
+#[cfg(unix)]
+pub mod control;
 pub mod controller;
 pub mod display;
+pub mod engine;
+pub mod events;
+pub mod exit;
+pub mod expiry;
+pub mod greeting;
+pub mod hal;
+pub mod history;
+pub mod hotplug;
+pub mod input;
+pub mod knob;
+pub mod led;
+pub mod locale;
+pub mod logfile;
+pub mod loudness;
+pub mod memo;
+pub mod metadata;
+pub mod mirror;
+pub mod mqtt;
+pub mod night;
+pub mod notify;
+pub mod pn532;
+pub mod podcast;
 pub mod reader;
+pub mod remote;
+pub mod sandbox;
+pub mod search;
+pub mod secrets;
+pub mod setup;
+pub mod speech;
+pub mod spotify;
+pub mod state;
+pub mod stats;
+pub mod stream;
+pub mod subsonic;
+#[cfg(feature = "systemd")]
+pub mod systemd;
 pub mod telemetry;
+#[cfg(test)]
+mod test_http;
+#[cfg(any(feature = "https", feature = "mqtt"))]
+pub mod tls;
+pub mod update;
 #[cfg(feature = "debug-http")]
 pub mod web;
+pub mod webhook;
@@ -0,0 +1,222 @@
+//! Translations for the child-facing text and sounds musicbox plays.
+//!
+//! Only what a listener sees or hears on the device is translated: status
+//! lines, spoken announcements, and cue files with a variant for the
+//! locale. Logs, CLI output, and the debug dashboard stay in English for
+//! whoever is troubleshooting.
+
+use serde::Deserialize;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+/// The language used for on-device text, set by `locale` in the config.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Locale {
+    #[default]
+    En,
+    De,
+    Fr,
+}
+
+impl Locale {
+    pub const ALL: [Locale; 3] = [Locale::En, Locale::De, Locale::Fr];
+
+    pub fn code(self) -> &'static str {
+        match self {
+            Locale::En => "en",
+            Locale::De => "de",
+            Locale::Fr => "fr",
+        }
+    }
+
+    pub fn strings(self) -> &'static Strings {
+        match self {
+            Locale::En => &EN,
+            Locale::De => &DE,
+            Locale::Fr => &FR,
+        }
+    }
+
+    /// The cue file to play for `path`: its `<stem>.<code>.<ext>` sibling,
+    /// such as `goodnight.de.mp3`, when one exists, and otherwise `path`.
+    pub fn localized(self, path: &Path) -> PathBuf {
+        let (Some(stem), Some(extension)) = (path.file_stem(), path.extension()) else {
+            return path.to_path_buf();
+        };
+        let mut name = stem.to_os_string();
+        name.push(format!(".{}.", self.code()));
+        name.push(extension);
+        let variant = path.with_file_name(name);
+        if variant.is_file() {
+            variant
+        } else {
+            path.to_path_buf()
+        }
+    }
+}
+
+impl fmt::Display for Locale {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.code())
+    }
+}
+
+/// Every translatable phrase. Labels are rendered as `"{label}: {value}"`.
+/// Stick to Latin-1 characters; the e-ink font cannot draw anything else.
+#[derive(Debug)]
+pub struct Strings {
+    pub title: &'static str,
+    pub state: &'static str,
+    pub playing: &'static str,
+    pub paused: &'static str,
+    pub switched: &'static str,
+    pub stopped: &'static str,
+    /// The sleep timer stopped playback.
+    pub timed_out: &'static str,
+    pub recording: &'static str,
+    /// An unknown card was noted in learning mode.
+    pub learned: &'static str,
+    pub waiting: &'static str,
+    pub idle_polls: &'static str,
+    /// Renders the cards loaded and the files missing, e.g. "142 cards,
+    /// 3 missing".
+    pub library: fn(usize, usize) -> String,
+    pub card: &'static str,
+    pub track: &'static str,
+    pub updated: &'static str,
+    pub just_now: &'static str,
+    /// Renders an age in whole seconds, e.g. "5s ago".
+    pub seconds_ago: fn(u64) -> String,
+    pub error_restarting: &'static str,
+    /// What `[speech]` says in place of an effect without a file.
+    pub announcements: Announcements,
+}
+
+/// Spoken stand-ins for the `[effects]` sounds.
+#[derive(Debug)]
+pub struct Announcements {
+    pub ready: &'static str,
+    pub unknown_card: &'static str,
+    pub shutdown: &'static str,
+    pub sleep_warning: &'static str,
+}
+
+static EN: Strings = Strings {
+    title: "Musicbox",
+    state: "State",
+    playing: "Playing",
+    paused: "Paused",
+    switched: "Switched",
+    stopped: "Stopped",
+    timed_out: "Sleep timer",
+    recording: "Recording",
+    learned: "Learned card",
+    waiting: "Waiting",
+    idle_polls: "Idle polls",
+    library: |cards, missing| format!("{cards} cards, {missing} missing"),
+    card: "Card",
+    track: "Track",
+    updated: "Updated",
+    just_now: "just now",
+    seconds_ago: |secs| format!("{secs}s ago"),
+    error_restarting: "ERROR - restarting",
+    announcements: Announcements {
+        ready: "Ready to play.",
+        unknown_card: "I don't know this card yet.",
+        shutdown: "Good night!",
+        sleep_warning: "Time to sleep soon.",
+    },
+};
+
+static DE: Strings = Strings {
+    title: "Musikbox",
+    state: "Status",
+    playing: "Spielt",
+    paused: "Pausiert",
+    switched: "Gewechselt",
+    stopped: "Gestoppt",
+    timed_out: "Schlaftimer",
+    recording: "Aufnahme",
+    learned: "Karte gelernt",
+    waiting: "Wartet",
+    idle_polls: "Leerlauf",
+    library: |cards, missing| format!("{cards} Karten, {missing} fehlen"),
+    card: "Karte",
+    track: "Titel",
+    updated: "Aktualisiert",
+    just_now: "gerade eben",
+    seconds_ago: |secs| format!("vor {secs}s"),
+    error_restarting: "FEHLER - Neustart",
+    announcements: Announcements {
+        ready: "Bereit zum Spielen.",
+        unknown_card: "Diese Karte kenne ich noch nicht.",
+        shutdown: "Gute Nacht!",
+        sleep_warning: "Bald ist Schlafenszeit.",
+    },
+};
+
+static FR: Strings = Strings {
+    title: "Boîte à musique",
+    state: "État",
+    playing: "Lecture",
+    paused: "En pause",
+    switched: "Changé",
+    stopped: "Arrêté",
+    timed_out: "Minuterie",
+    recording: "Enregistrement",
+    learned: "Carte apprise",
+    waiting: "En attente",
+    idle_polls: "Inactif",
+    library: |cards, missing| format!("{cards} cartes, {missing} manquants"),
+    card: "Carte",
+    track: "Piste",
+    updated: "Mis à jour",
+    just_now: "à l'instant",
+    seconds_ago: |secs| format!("il y a {secs}s"),
+    error_restarting: "ERREUR - redémarrage",
+    announcements: Announcements {
+        ready: "Prêt à jouer.",
+        unknown_card: "Je ne connais pas encore cette carte.",
+        shutdown: "Bonne nuit !",
+        sleep_warning: "Bientôt l'heure de dormir.",
+    },
+};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_locale_translates_every_phrase() {
+        for locale in Locale::ALL {
+            let strings = locale.strings();
+            assert!(!strings.title.is_empty(), "{locale} title");
+            assert!((strings.seconds_ago)(5).contains('5'), "{locale} age");
+            assert!((strings.library)(14, 3).contains("14"), "{locale} library");
+        }
+        assert_ne!(Locale::De.strings().playing, Locale::En.strings().playing);
+        assert_ne!(
+            Locale::Fr.strings().announcements.shutdown,
+            Locale::En.strings().announcements.shutdown
+        );
+    }
+
+    #[test]
+    fn cues_prefer_a_variant_for_the_locale() {
+        let dir = tempfile::tempdir().unwrap();
+        let cue = dir.path().join("goodnight.mp3");
+        std::fs::write(&cue, b"").unwrap();
+        std::fs::write(dir.path().join("goodnight.de.mp3"), b"").unwrap();
+
+        assert_eq!(
+            Locale::De.localized(&cue),
+            dir.path().join("goodnight.de.mp3")
+        );
+        assert_eq!(Locale::Fr.localized(&cue), cue);
+        assert_eq!(
+            Locale::De.localized(Path::new("cues/hello")),
+            Path::new("cues/hello")
+        );
+    }
+}
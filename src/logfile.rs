@@ -0,0 +1,143 @@
+//! A log file that rotates by size, for boxes whose logs are shipped off
+//! by a collector tailing the file rather than read from the journal.
+//!
+//! When the next line would take the file past its limit, `musicbox.log`
+//! becomes `musicbox.log.1`, older files move up by one, and the oldest
+//! beyond the number kept is deleted. Lines are never split across files.
+
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// How large a log file grows before it is rotated.
+pub const DEFAULT_MAX_BYTES: u64 = 10 * 1024 * 1024;
+
+/// How many rotated files are kept beside the current one.
+pub const DEFAULT_KEEP: usize = 5;
+
+/// An append-only file that [`tracing_subscriber`] writes log lines to.
+#[derive(Debug)]
+pub struct RotatingFile {
+    path: PathBuf,
+    max_bytes: u64,
+    keep: usize,
+    state: Mutex<Open>,
+}
+
+#[derive(Debug)]
+struct Open {
+    file: File,
+    len: u64,
+}
+
+impl RotatingFile {
+    /// Opens `path` for appending, creating it and its directory if
+    /// needed.
+    pub fn open(path: impl Into<PathBuf>, max_bytes: u64, keep: usize) -> io::Result<Self> {
+        let path = path.into();
+        if let Some(parent) = path
+            .parent()
+            .filter(|parent| !parent.as_os_str().is_empty())
+        {
+            fs::create_dir_all(parent)?;
+        }
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        let len = file.metadata()?.len();
+        Ok(Self {
+            path,
+            max_bytes,
+            keep,
+            state: Mutex::new(Open { file, len }),
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// `musicbox.log.N`, the `n`th most recent rotated file.
+    fn rotated(&self, n: usize) -> PathBuf {
+        let mut name = self.path.clone().into_os_string();
+        name.push(format!(".{n}"));
+        PathBuf::from(name)
+    }
+
+    fn rotate(&self, open: &mut Open) -> io::Result<()> {
+        if self.keep == 0 {
+            fs::remove_file(&self.path)?;
+        } else {
+            match fs::remove_file(self.rotated(self.keep)) {
+                Err(err) if err.kind() != io::ErrorKind::NotFound => return Err(err),
+                _ => {}
+            }
+            for n in (1..self.keep).rev() {
+                match fs::rename(self.rotated(n), self.rotated(n + 1)) {
+                    Err(err) if err.kind() != io::ErrorKind::NotFound => return Err(err),
+                    _ => {}
+                }
+            }
+            fs::rename(&self.path, self.rotated(1))?;
+        }
+        open.file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        open.len = 0;
+        Ok(())
+    }
+}
+
+impl Write for &RotatingFile {
+    /// Writes `buf` whole, rotating first if it would not fit. The fmt
+    /// layer writes each event in one call, so a line stays in one file.
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut open = self.state.lock().expect("log file lock");
+        if open.len > 0 && open.len + buf.len() as u64 > self.max_bytes {
+            self.rotate(&mut open)?;
+        }
+        open.file.write_all(buf)?;
+        open.len += buf.len() as u64;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.state.lock().expect("log file lock").file.flush()
+    }
+}
+
+impl<'a> tracing_subscriber::fmt::MakeWriter<'a> for RotatingFile {
+    type Writer = &'a RotatingFile;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rotates_whole_lines_and_keeps_the_newest_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("logs/musicbox.log");
+        let log = RotatingFile::open(&path, 10, 2).unwrap();
+        for line in ["one\n", "two\n", "three\n", "four\n", "five\n"] {
+            (&log).write_all(line.as_bytes()).unwrap();
+        }
+
+        let read = |path: &Path| fs::read_to_string(path).unwrap();
+        assert_eq!(read(&path), "four\nfive\n");
+        assert_eq!(read(&log.rotated(1)), "three\n");
+        assert_eq!(read(&log.rotated(2)), "one\ntwo\n");
+        assert!(!log.rotated(3).exists());
+
+        // A reopened file carries on from its size.
+        let log = RotatingFile::open(&path, 10, 2).unwrap();
+        (&log).write_all(b"six\n").unwrap();
+        assert_eq!(read(&path), "six\n");
+        assert_eq!(read(&log.rotated(1)), "four\nfive\n");
+        assert_eq!(read(&log.rotated(2)), "three\n");
+    }
+}
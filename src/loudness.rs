@@ -0,0 +1,562 @@
+//! Loudness normalization: measures how loud each track is (EBU R128
+//! integrated loudness) and plays it louder or quieter so every track lands
+//! on the same level.
+//!
+//! Measuring means decoding the whole file, which takes seconds on a Pi, so
+//! each result is cached in a sidecar next to the track, `<file>.loudness`.
+//! `musicbox library analyze` fills them in ahead of time; [`LoudnessPlayer`]
+//! measures tracks it has no sidecar for in the background, so they are
+//! normalized from their next play.
+
+use crate::controller::{
+    AudioPlayer, Gain, OutputChange, PlayerCapabilities, PlayerError, Track, TrackSource,
+};
+use crate::metadata::{self, MetadataError};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Sender};
+use std::time::{Duration, Instant, UNIX_EPOCH};
+use symphonia::core::audio::SampleBuffer;
+use symphonia::core::codecs::DecoderOptions;
+use symphonia::core::errors::Error as DecodeError;
+
+/// The level tracks are brought to unless the config says otherwise, the
+/// ReplayGain 2 reference.
+pub const DEFAULT_TARGET_LUFS: f32 = -18.0;
+
+/// Most a quiet track is boosted, so a near-silent one is not blown up
+/// into clipping.
+const MAX_BOOST_DB: f32 = 12.0;
+
+/// Blocks quieter than this are silence and do not count.
+const ABSOLUTE_GATE_LUFS: f64 = -70.0;
+
+/// Blocks this far below the ungated level are pauses and do not count.
+const RELATIVE_GATE_LU: f64 = 10.0;
+
+#[derive(Debug, thiserror::Error)]
+pub enum LoudnessError {
+    #[error(transparent)]
+    Metadata(#[from] MetadataError),
+    #[error("failed to decode {path:?}: {source}")]
+    Decode {
+        path: PathBuf,
+        #[source]
+        source: DecodeError,
+    },
+    #[error("failed to write loudness sidecar {path:?}: {source}")]
+    Sidecar {
+        path: PathBuf,
+        #[source]
+        source: std::io::Error,
+    },
+}
+
+/// The optional `[loudness]` table: normalize tracks to `target_lufs`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LoudnessSettings {
+    pub target_lufs: f32,
+}
+
+impl Default for LoudnessSettings {
+    fn default() -> Self {
+        Self {
+            target_lufs: DEFAULT_TARGET_LUFS,
+        }
+    }
+}
+
+impl LoudnessSettings {
+    /// The gain that brings a track measured at `loudness_lufs` to the
+    /// target. Silent tracks play as recorded.
+    pub fn gain_for(&self, loudness_lufs: Option<f32>) -> Gain {
+        loudness_lufs
+            .and_then(|lufs| Gain::from_db((self.target_lufs - lufs).min(MAX_BOOST_DB)))
+            .unwrap_or_default()
+    }
+}
+
+/// What a sidecar records: the measurement, and the file it was made from
+/// so an edited track is measured again.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Analysis {
+    /// Integrated loudness in LUFS; `None` for a track that is all silence.
+    pub loudness_lufs: Option<f32>,
+    size: u64,
+    modified_ms: u64,
+}
+
+/// Where the sidecar for the track at `path` lives.
+pub fn sidecar_path(path: &Path) -> PathBuf {
+    let mut sidecar = path.to_path_buf().into_os_string();
+    sidecar.push(".loudness");
+    PathBuf::from(sidecar)
+}
+
+/// The cached analysis of the track at `path`, if there is one and the
+/// track has not changed since.
+pub fn cached(path: &Path) -> Option<Analysis> {
+    let bytes = fs::read(sidecar_path(path)).ok()?;
+    let analysis: Analysis = serde_json::from_slice(&bytes).ok()?;
+    (Some((analysis.size, analysis.modified_ms)) == fingerprint(path)).then_some(analysis)
+}
+
+/// Measures the track at `path` and writes its sidecar.
+pub fn analyze(path: &Path) -> Result<Analysis, LoudnessError> {
+    let (size, modified_ms) = fingerprint(path).ok_or_else(|| {
+        LoudnessError::Metadata(MetadataError::Open {
+            path: path.to_path_buf(),
+            source: std::io::ErrorKind::NotFound.into(),
+        })
+    })?;
+    let analysis = Analysis {
+        loudness_lufs: integrated_loudness(path)?.map(|lufs| lufs as f32),
+        size,
+        modified_ms,
+    };
+    let sidecar = sidecar_path(path);
+    let bytes = serde_json::to_vec(&analysis).expect("analyses always serialize");
+    crate::state::write_atomically(&sidecar, &bytes).map_err(|source| LoudnessError::Sidecar {
+        path: sidecar,
+        source,
+    })?;
+    Ok(analysis)
+}
+
+/// The audio files under `music_dir`, sorted, for `library analyze`.
+pub fn tracks_under(music_dir: &Path) -> std::io::Result<Vec<PathBuf>> {
+    let mut tracks = crate::config::audio_files_under(music_dir)?;
+    tracks.sort();
+    Ok(tracks)
+}
+
+/// The file's size and modification time, which change when it is edited.
+fn fingerprint(path: &Path) -> Option<(u64, u64)> {
+    let metadata = fs::metadata(path).ok()?;
+    let modified = metadata.modified().ok()?.duration_since(UNIX_EPOCH).ok()?;
+    Some((metadata.len(), modified.as_millis() as u64))
+}
+
+/// The EBU R128 integrated loudness of the track at `path`, in LUFS, or
+/// `None` when every block of it is below the silence gate.
+pub fn integrated_loudness(path: &Path) -> Result<Option<f64>, LoudnessError> {
+    let decode_error = |source| LoudnessError::Decode {
+        path: path.to_path_buf(),
+        source,
+    };
+    let mut format = metadata::open(path)?;
+    let track = format
+        .default_track()
+        .ok_or_else(|| MetadataError::NoAudio(path.to_path_buf()))?;
+    let track_id = track.id;
+    let mut decoder = symphonia::default::get_codecs()
+        .make(&track.codec_params, &DecoderOptions::default())
+        .map_err(decode_error)?;
+    let mut meter: Option<Meter> = None;
+    let mut samples: Option<SampleBuffer<f32>> = None;
+    loop {
+        let packet = match format.next_packet() {
+            Ok(packet) => packet,
+            Err(DecodeError::IoError(err)) if err.kind() == std::io::ErrorKind::UnexpectedEof => {
+                break;
+            }
+            Err(DecodeError::ResetRequired) => break,
+            Err(err) => return Err(decode_error(err)),
+        };
+        if packet.track_id() != track_id {
+            continue;
+        }
+        let decoded = match decoder.decode(&packet) {
+            Ok(decoded) => decoded,
+            // A corrupt frame is skipped, as a player would.
+            Err(DecodeError::DecodeError(_)) => continue,
+            Err(err) => return Err(decode_error(err)),
+        };
+        let spec = *decoded.spec();
+        let buffer = match &mut samples {
+            Some(buffer) if buffer.capacity() >= decoded.capacity() * spec.channels.count() => {
+                buffer
+            }
+            _ => samples.insert(SampleBuffer::new(decoded.capacity() as u64, spec)),
+        };
+        buffer.copy_interleaved_ref(decoded);
+        meter
+            .get_or_insert_with(|| Meter::new(spec.rate, spec.channels.count()))
+            .push(buffer.samples());
+    }
+    Ok(meter.and_then(Meter::integrated))
+}
+
+/// A biquad filter section in direct form I.
+#[derive(Debug, Clone, Copy)]
+struct Biquad {
+    b: [f64; 3],
+    a: [f64; 2],
+    x: [f64; 2],
+    y: [f64; 2],
+}
+
+impl Biquad {
+    fn new(b: [f64; 3], a: [f64; 2]) -> Self {
+        Self {
+            b,
+            a,
+            x: [0.0; 2],
+            y: [0.0; 2],
+        }
+    }
+
+    fn process(&mut self, input: f64) -> f64 {
+        let output = self.b[0] * input + self.b[1] * self.x[0] + self.b[2] * self.x[1]
+            - self.a[0] * self.y[0]
+            - self.a[1] * self.y[1];
+        self.x = [input, self.x[0]];
+        self.y = [output, self.y[0]];
+        output
+    }
+}
+
+/// The two stages of the K-weighting filter from ITU-R BS.1770 at
+/// `rate`: a high shelf for the head's effect, then a high pass.
+fn k_weighting(rate: u32) -> [Biquad; 2] {
+    let rate = f64::from(rate);
+    let shelf = {
+        let (f0, gain_db, q) = (1681.974450955533, 3.999843853973347, 0.7071752369554196);
+        let k = (std::f64::consts::PI * f0 / rate).tan();
+        let vh = 10f64.powf(gain_db / 20.0);
+        let vb = vh.powf(0.4996667741545416);
+        let a0 = 1.0 + k / q + k * k;
+        Biquad::new(
+            [
+                (vh + vb * k / q + k * k) / a0,
+                2.0 * (k * k - vh) / a0,
+                (vh - vb * k / q + k * k) / a0,
+            ],
+            [2.0 * (k * k - 1.0) / a0, (1.0 - k / q + k * k) / a0],
+        )
+    };
+    let high_pass = {
+        let (f0, q) = (38.13547087602444, 0.5003270373238773);
+        let k = (std::f64::consts::PI * f0 / rate).tan();
+        let a0 = 1.0 + k / q + k * k;
+        Biquad::new(
+            [1.0, -2.0, 1.0],
+            [2.0 * (k * k - 1.0) / a0, (1.0 - k / q + k * k) / a0],
+        )
+    };
+    [shelf, high_pass]
+}
+
+/// Accumulates K-weighted power over 100 ms steps, the hop between the
+/// overlapping 400 ms gating blocks.
+struct Meter {
+    filters: Vec<[Biquad; 2]>,
+    weights: Vec<f64>,
+    step_len: usize,
+    step_fill: usize,
+    step_power: f64,
+    steps: Vec<f64>,
+}
+
+impl Meter {
+    fn new(rate: u32, channels: usize) -> Self {
+        // Surround channels count extra; the LFE channel of 5.1 not at all.
+        let weights = (0..channels)
+            .map(|channel| match (channels, channel) {
+                (6.., 3) => 0.0,
+                (5.., 3..) => 1.41,
+                _ => 1.0,
+            })
+            .collect();
+        Self {
+            filters: vec![k_weighting(rate); channels],
+            weights,
+            step_len: (rate as usize / 10).max(1),
+            step_fill: 0,
+            step_power: 0.0,
+            steps: Vec::new(),
+        }
+    }
+
+    /// Adds interleaved samples.
+    fn push(&mut self, samples: &[f32]) {
+        for frame in samples.chunks_exact(self.filters.len()) {
+            for ((sample, filters), weight) in
+                frame.iter().zip(&mut self.filters).zip(&self.weights)
+            {
+                let weighted = filters
+                    .iter_mut()
+                    .fold(f64::from(*sample), |value, filter| filter.process(value));
+                self.step_power += weight * weighted * weighted;
+            }
+            self.step_fill += 1;
+            if self.step_fill == self.step_len {
+                self.steps.push(self.step_power / self.step_len as f64);
+                self.step_fill = 0;
+                self.step_power = 0.0;
+            }
+        }
+    }
+
+    /// The gated loudness over every whole block pushed.
+    fn integrated(self) -> Option<f64> {
+        let blocks: Vec<f64> = self
+            .steps
+            .windows(4)
+            .map(|steps| steps.iter().sum::<f64>() / 4.0)
+            .filter(|power| lufs(*power) > ABSOLUTE_GATE_LUFS)
+            .collect();
+        if blocks.is_empty() {
+            return None;
+        }
+        let relative_gate = lufs(mean(&blocks)) - RELATIVE_GATE_LU;
+        let gated: Vec<f64> = blocks
+            .into_iter()
+            .filter(|power| lufs(*power) > relative_gate)
+            .collect();
+        Some(lufs(mean(&gated)))
+    }
+}
+
+fn lufs(power: f64) -> f64 {
+    -0.691 + 10.0 * power.log10()
+}
+
+fn mean(values: &[f64]) -> f64 {
+    values.iter().sum::<f64>() / values.len() as f64
+}
+
+/// Wraps an [`AudioPlayer`] so local tracks play at the target loudness
+/// from their sidecars, on top of their card's gain. Without settings it
+/// passes tracks through unchanged.
+pub struct LoudnessPlayer<P> {
+    inner: P,
+    settings: Option<LoudnessSettings>,
+    analyzer: Option<Sender<PathBuf>>,
+    queued: HashSet<PathBuf>,
+}
+
+impl<P: AudioPlayer> LoudnessPlayer<P> {
+    pub fn new(inner: P, settings: Option<LoudnessSettings>) -> Self {
+        Self {
+            inner,
+            settings,
+            analyzer: None,
+            queued: HashSet::new(),
+        }
+    }
+
+    /// The gain that normalizes `path`, or `None` until it is analyzed.
+    fn normalization(&mut self, settings: LoudnessSettings, path: &Path) -> Option<Gain> {
+        if let Some(analysis) = cached(path) {
+            return Some(settings.gain_for(analysis.loudness_lufs));
+        }
+        if self.queued.insert(path.to_path_buf()) {
+            tracing::debug!(track = %path.display(), "measuring loudness in the background");
+            let analyzer = self.analyzer.get_or_insert_with(spawn_analyzer);
+            if analyzer.send(path.to_path_buf()).is_err() {
+                tracing::warn!("loudness analyzer stopped; tracks play as recorded");
+            }
+        }
+        None
+    }
+}
+
+/// Starts the thread that measures tracks played before they were analyzed.
+fn spawn_analyzer() -> Sender<PathBuf> {
+    let (sender, receiver) = mpsc::channel::<PathBuf>();
+    std::thread::spawn(move || {
+        for path in receiver {
+            if let Err(err) = analyze(&path) {
+                tracing::warn!(%err, "failed to measure track loudness");
+            }
+        }
+    });
+    sender
+}
+
+impl<P: AudioPlayer> AudioPlayer for LoudnessPlayer<P> {
+    fn capabilities(&self) -> PlayerCapabilities {
+        self.inner.capabilities()
+    }
+
+    fn play(&mut self, track: &Track) -> Result<(), PlayerError> {
+        let (Some(settings), TrackSource::File(path)) = (self.settings, track.source()) else {
+            return self.inner.play(track);
+        };
+        let Some(normalization) = self.normalization(settings, path) else {
+            return self.inner.play(track);
+        };
+        let gain = Gain::from_factor(track.gain().factor() * normalization.factor())
+            .unwrap_or(track.gain());
+        self.inner.play(&track.clone().with_gain(gain))
+    }
+
+    fn stop(&mut self) -> Result<(), PlayerError> {
+        self.inner.stop()
+    }
+
+    fn pause(&mut self) -> Result<(), PlayerError> {
+        self.inner.pause()
+    }
+
+    fn resume(&mut self) -> Result<(), PlayerError> {
+        self.inner.resume()
+    }
+
+    fn wait_until_done(&mut self) -> Result<(), PlayerError> {
+        self.inner.wait_until_done()
+    }
+
+    fn is_finished(&self) -> bool {
+        self.inner.is_finished()
+    }
+
+    fn set_volume(&mut self, volume: f32) -> Result<(), PlayerError> {
+        self.inner.set_volume(volume)
+    }
+
+    fn volume(&self) -> f32 {
+        self.inner.volume()
+    }
+
+    fn set_muted(&mut self, muted: bool) -> Result<(), PlayerError> {
+        self.inner.set_muted(muted)
+    }
+
+    fn set_night_mode(&mut self, on: bool) -> Result<(), PlayerError> {
+        self.inner.set_night_mode(on)
+    }
+
+    fn position(&self) -> Option<Duration> {
+        self.inner.position()
+    }
+
+    fn seek(&mut self, offset: Duration) -> Result<(), PlayerError> {
+        self.inner.seek(offset)
+    }
+
+    fn first_sound_at(&self) -> Option<Instant> {
+        self.inner.first_sound_at()
+    }
+
+    fn fade_out(&mut self, over: Duration) -> Result<(), PlayerError> {
+        self.inner.fade_out(over)
+    }
+
+    fn play_effect(&mut self, effect: &Track) -> Result<(), PlayerError> {
+        self.inner.play_effect(effect)
+    }
+
+    fn refresh_output(&mut self) -> Option<OutputChange> {
+        self.inner.refresh_output()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::memo::Clip;
+    use std::sync::{Arc, Mutex};
+
+    /// A stereo 1 kHz sine peaking at `dbfs`, which BS.1770 rates at
+    /// `dbfs` LUFS.
+    fn write_tone(path: &Path, dbfs: f64) {
+        let rate = 48_000;
+        let amplitude = 10f64.powf(dbfs / 20.0) * f64::from(i16::MAX);
+        let samples = (0..rate * 3)
+            .flat_map(|n| {
+                let phase = 2.0 * std::f64::consts::PI * 1_000.0 * f64::from(n) / f64::from(rate);
+                let sample = (amplitude * phase.sin()).round() as i16;
+                [sample, sample]
+            })
+            .collect();
+        Clip {
+            sample_rate: rate,
+            channels: 2,
+            samples,
+        }
+        .write_wav(path)
+        .unwrap();
+    }
+
+    #[test]
+    fn measures_a_reference_tone_and_caches_it_beside_the_track() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("tone.wav");
+        write_tone(&path, -23.0);
+
+        let measured = integrated_loudness(&path).unwrap().unwrap();
+        assert!((measured + 23.0).abs() < 0.1, "measured {measured} LUFS");
+        assert_eq!(cached(&path), None);
+        let analysis = analyze(&path).unwrap();
+        assert!(sidecar_path(&path).ends_with("tone.wav.loudness"));
+        assert_eq!(cached(&path), Some(analysis));
+
+        fs::write(dir.path().join("empty.wav"), b"").unwrap();
+        assert!(analyze(&dir.path().join("empty.wav")).is_err());
+        fs::OpenOptions::new()
+            .write(true)
+            .open(&path)
+            .unwrap()
+            .set_len(1_000)
+            .unwrap();
+        assert_eq!(cached(&path), None, "edited tracks are measured again");
+    }
+
+    #[test]
+    fn gains_bring_tracks_to_the_target_with_a_capped_boost() {
+        let settings = LoudnessSettings::default();
+        let db = |lufs| 20.0 * settings.gain_for(lufs).factor().log10();
+        assert!((db(Some(-13.0)) + 5.0).abs() < 0.01);
+        assert!((db(Some(-60.0)) - MAX_BOOST_DB).abs() < 0.01);
+        assert_eq!(settings.gain_for(None), Gain::UNITY);
+    }
+
+    #[derive(Clone, Default)]
+    struct GainRecorder(Arc<Mutex<Vec<Gain>>>);
+
+    impl AudioPlayer for GainRecorder {
+        fn play(&mut self, track: &Track) -> Result<(), PlayerError> {
+            self.0.lock().unwrap().push(track.gain());
+            Ok(())
+        }
+
+        fn stop(&mut self) -> Result<(), PlayerError> {
+            Ok(())
+        }
+
+        fn pause(&mut self) -> Result<(), PlayerError> {
+            Ok(())
+        }
+
+        fn resume(&mut self) -> Result<(), PlayerError> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn analyzed_tracks_play_normalized_on_top_of_the_card_gain() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("tone.wav");
+        write_tone(&path, -23.0);
+        analyze(&path).unwrap();
+        let recorder = GainRecorder::default();
+        let half = Gain::from_factor(0.5).unwrap();
+        let track = Track::new(path).with_gain(half);
+
+        LoudnessPlayer::new(recorder.clone(), None)
+            .play(&track)
+            .unwrap();
+        LoudnessPlayer::new(recorder.clone(), Some(LoudnessSettings::default()))
+            .play(&track)
+            .unwrap();
+
+        let gains = recorder.0.lock().unwrap().clone();
+        assert_eq!(gains[0], half);
+        // -23 LUFS to -18 LUFS is +5 dB, about 1.78x.
+        assert!((gains[1].factor() - 0.5 * 1.778).abs() < 0.01);
+    }
+}
@@ -1,30 +1,231 @@
 use clap::{Args, Parser, Subcommand, ValueEnum, builder::ValueHint};
-use musicbox::app::{RunLoopError, controller_from_config_path, run_until_shutdown};
-use musicbox::audio::RodioPlayer;
-use musicbox::config::{self, ConfigEditError};
-use musicbox::controller::{AudioPlayer, CardUid, CardUidParseError, PlayerError, Track};
-#[cfg(feature = "waveshare-display")]
-use musicbox::display;
+use indicatif::{ProgressBar, ProgressDrawTarget, ProgressStyle};
+use musicbox::app::{
+    CONFIG_WATCH_INTERVAL, ConfigWatcher, PolicyReader, ProcessOutcome, ReaderErrorPolicy,
+    ReaderErrorSettings, RunLoopError, ShutdownToken, load_config, process_next_event,
+    reload_library, run_until_shutdown,
+};
+use musicbox::audio::{self, RodioPlayer, SimulatedPlayer};
+use musicbox::backup::{self, BackupError, BackupSources, RestoreOptions};
+use musicbox::checksum::{ChecksumError, ChecksumManifest, MANIFEST_NAME, TrackStatus};
+use musicbox::config::{
+    self, ConfigEditError, Diagnostic, MusicBoxConfig, Severity, ValidationReport,
+};
+#[cfg(unix)]
+use musicbox::control::{
+    self, ControlClient, ControlError, ControlRequest, ControlServer, ControlState,
+};
+use musicbox::controller::{
+    AudioPlayer, CardUid, CardUidParseError, ControllerAction, ControllerError, Library,
+    MusicBoxController, OutputChange, PlaybackMode, PlayerCapabilities, PlayerError, SyntheticUid,
+    Track,
+};
 #[cfg(feature = "waveshare-display")]
 use musicbox::display::waveshare::{WaveshareConfig, WaveshareDisplay};
-use musicbox::reader::{NfcReader, ReaderError, ReaderEvent};
-use musicbox::telemetry::{self, SharedStatus};
-#[cfg(feature = "debug-http")]
+use musicbox::display::{self, remote::RemoteDisplay};
+#[cfg(feature = "waveshare-display")]
+use musicbox::display::{DisplayGuardSettings, GuardedDisplay};
+use musicbox::events::{EngineEvent, EventBus, EventStamp};
+use musicbox::exit::ExitCode;
+use musicbox::expiry::{self, parse_lifetime};
+use musicbox::history::{self, HistoryError, HistoryEvent, HistoryLog, PlayTracker};
+use musicbox::hotplug::HotplugPlayer;
+#[cfg(feature = "gpio-buttons")]
+use musicbox::input::gpio::GpioButtons;
+use musicbox::input::{ButtonConfig, ButtonSource, NoButtons};
+#[cfg(feature = "rotary-knob")]
+use musicbox::knob::gpio::RotaryKnob;
+#[cfg(feature = "ws2812-led")]
+use musicbox::led::{LedHint, ws2812::Ws2812Led};
+use musicbox::logfile::{self, RotatingFile};
+use musicbox::loudness::{self, LoudnessPlayer};
+use musicbox::memo::{MemoSettings, Recorder};
+use musicbox::mirror::{self, Mirror};
+use musicbox::mqtt::MqttSettings;
+use musicbox::notify::{BATTERY_POLL_INTERVAL, Notification};
+use musicbox::pn532::Pn532Settings;
+use musicbox::podcast;
+use musicbox::reader::{
+    DEFAULT_POLL_INTERVAL, NfcReader, PcscSettings, ReaderError, ReaderEvent, ReaderZone,
+    ReplayParseError, ReplayReader, ReplayStep, parse_replay_script,
+};
+use musicbox::remote::{RemoteController, RemoteError, RemoteLibrary};
+use musicbox::setup::{self, Prompter, SetupChoices};
+use musicbox::spotify::{self, SpotifySettings};
+use musicbox::state::ResumeStore;
+use musicbox::stats::{self, PeriodTotal, PlayStats};
+use musicbox::stream;
+use musicbox::subsonic::{self, CachingPlayer};
+#[cfg(feature = "systemd")]
+use musicbox::systemd::SystemdNotifier;
+use musicbox::telemetry::{self, LogFormat, ReaderHealth, SharedStatus, StatusSnapshot};
+use musicbox::update::{self, UpdateError};
+use musicbox::webhook::{self, WebhookError, WebhookUrl};
+use std::collections::HashSet;
+use std::io::IsTerminal;
 use std::net::SocketAddr;
 use std::path::{Path, PathBuf};
-use std::sync::{Arc, Mutex};
-use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, Instant, SystemTime};
 use thiserror::Error;
 
-#[cfg(feature = "waveshare-display")]
 type SharedStatusDisplay = Arc<Mutex<Box<dyn display::StatusDisplay>>>;
 
 fn main() {
-    telemetry::init_logging();
+    let cli = Cli::parse();
+    OUTPUT.get_or_init(|| cli.output);
+    QUIET.store(cli.quiet, Ordering::Relaxed);
+    let format = cli.logging.log_format.into();
+    let (file, file_error) = match &cli.logging.log_file {
+        Some(path) => match RotatingFile::open(
+            path,
+            cli.logging.log_max_size_mib << 20,
+            cli.logging.log_keep,
+        ) {
+            Ok(file) => (Some(file), None),
+            Err(err) => (None, Some((path.clone(), err))),
+        },
+        None => (None, None),
+    };
+    if json_output() {
+        telemetry::init_logging_with(std::io::stderr, format, file);
+    } else {
+        telemetry::init_logging_with(std::io::stdout, format, file);
+    }
+    if let Some((path, err)) = file_error {
+        tracing::warn!(%err, path = %path.display(), "cannot open the log file; logging to the console only");
+    }
+
+    if let Err(err) = run(cli) {
+        let exit = err.exit_code();
+        if json_output() && !EMITTED.load(Ordering::Relaxed) {
+            println!(
+                "{}",
+                serde_json::json!({ "ok": false, "error": err.to_string(), "exit_code": exit.code() })
+            );
+        } else {
+            eprintln!("{err}");
+        }
+        exit.exit();
+    }
+}
+
+/// How commands report what they did.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+enum OutputFormat {
+    /// Sentences for people.
+    #[default]
+    Text,
+    /// One JSON object on stdout per command; the sentences go to stderr.
+    Json,
+}
+
+static OUTPUT: OnceLock<OutputFormat> = OnceLock::new();
+
+/// Set once a command has printed its JSON result, so a failure after it
+/// does not print a second object.
+static EMITTED: AtomicBool = AtomicBool::new(false);
+
+/// Set by `--quiet`: long commands show no progress and no per-item lines.
+static QUIET: AtomicBool = AtomicBool::new(false);
+
+fn json_output() -> bool {
+    OUTPUT.get() == Some(&OutputFormat::Json)
+}
+
+fn quiet() -> bool {
+    QUIET.load(Ordering::Relaxed)
+}
+
+/// Prints a line for people: to stdout, or to stderr with `--output json`
+/// so stdout holds nothing but the result.
+macro_rules! say {
+    ($($arg:tt)*) => {
+        if json_output() {
+            eprintln!($($arg)*);
+        } else {
+            println!($($arg)*);
+        }
+    };
+}
+
+/// Prints a command's result as one line of JSON with `--output json`, with
+/// `"ok": true` unless the result says otherwise. Text output has been said
+/// already, so this does nothing without the flag.
+fn emit(mut result: serde_json::Value) {
+    if !json_output() {
+        return;
+    }
+    if let Some(fields) = result.as_object_mut() {
+        fields.entry("ok").or_insert(true.into());
+    }
+    println!("{result}");
+    EMITTED.store(true, Ordering::Relaxed);
+}
+
+/// A progress bar on stderr for a command that works through many files.
+/// It stays hidden with `--quiet` or `--output json`, and when stderr is
+/// not a terminal.
+struct Progress {
+    bar: ProgressBar,
+}
+
+impl Progress {
+    /// A bar counting items; its length is set as work starts.
+    fn bar() -> Self {
+        let bar = Self::hidden_unless_shown(ProgressBar::new(0));
+        bar.set_style(
+            ProgressStyle::with_template("{bar:30} {pos}/{len} {wide_msg}")
+                .expect("valid progress template"),
+        );
+        Self { bar }
+    }
+
+    /// A spinner for work whose size is not known up front.
+    fn spinner(message: &'static str) -> Self {
+        let bar = Self::hidden_unless_shown(ProgressBar::new_spinner());
+        bar.set_message(message);
+        bar.enable_steady_tick(Duration::from_millis(120));
+        Self { bar }
+    }
+
+    fn hidden_unless_shown(bar: ProgressBar) -> ProgressBar {
+        if quiet() || json_output() {
+            bar.set_draw_target(ProgressDrawTarget::hidden());
+        } else {
+            bar.set_draw_target(ProgressDrawTarget::stderr());
+        }
+        bar
+    }
+
+    /// Moves the bar to item `index` of `total`, named `item`.
+    fn working_on(&self, index: usize, total: usize, item: impl std::fmt::Display) {
+        self.bar.set_length(total as u64);
+        self.bar.set_position(index as u64);
+        self.bar.set_message(item.to_string());
+    }
+
+    /// Prints what happened to one item, unless `--quiet`.
+    fn item(&self, line: impl std::fmt::Display) {
+        if !quiet() {
+            self.report(line);
+        }
+    }
+
+    /// Prints a line that matters even with `--quiet`, such as a problem.
+    fn report(&self, line: impl std::fmt::Display) {
+        self.bar.suspend(|| say!("{line}"));
+    }
+
+    /// Clears the bar before the command prints its summary. Dropping it
+    /// does the same, such as when the command fails halfway.
+    fn finish(self) {}
+}
 
-    if let Err(err) = run() {
-        eprintln!("{err}");
-        std::process::exit(1);
+impl Drop for Progress {
+    fn drop(&mut self) {
+        self.bar.finish_and_clear();
     }
 }
 
@@ -33,6 +234,8 @@ enum RunError {
     #[error(transparent)]
     App(#[from] musicbox::app::AppError),
     #[error(transparent)]
+    Config(#[from] config::ConfigError),
+    #[error(transparent)]
     Loop(#[from] RunLoopError),
     #[error(transparent)]
     Reader(#[from] ReaderError),
@@ -40,8 +243,93 @@ enum RunError {
     Tag(#[from] TagError),
     #[error("audio player error: {0}")]
     Player(#[from] PlayerError),
+    #[error(transparent)]
+    Webhook(#[from] WebhookError),
+    #[error(transparent)]
+    Remote(#[from] RemoteError),
+    #[cfg(unix)]
+    #[error(transparent)]
+    Control(#[from] ControlError),
+    #[error("failed to read script {path:?}: {source}")]
+    ReadScript {
+        path: PathBuf,
+        #[source]
+        source: std::io::Error,
+    },
+    #[error("invalid replay script: {0}")]
+    Replay(#[from] ReplayParseError),
     #[error("configuration path required")]
     MissingConfig,
+    #[error("history log path required; pass --log or --history-log")]
+    MissingHistoryLog,
+    #[error(transparent)]
+    History(#[from] HistoryError),
+    #[error(transparent)]
+    ConfigEdit(#[from] ConfigEditError),
+    #[error(transparent)]
+    Backup(#[from] BackupError),
+    #[error(transparent)]
+    Checksum(#[from] ChecksumError),
+    #[error(transparent)]
+    Update(#[from] UpdateError),
+    #[error("setup failed: {0}")]
+    Setup(#[source] std::io::Error),
+    #[error("{0} track(s) failed verification")]
+    VerifyFailed(usize),
+    #[error("failed to list tracks under {path:?}: {source}")]
+    ListTracks {
+        path: PathBuf,
+        #[source]
+        source: std::io::Error,
+    },
+    #[error("{0} track(s) could not be analyzed")]
+    AnalyzeFailed(usize),
+    #[error("config validation found {0} problem(s)")]
+    InvalidConfig(usize),
+    #[error("failed to write track {path:?}: {source}")]
+    WriteTrack {
+        path: PathBuf,
+        #[source]
+        source: std::io::Error,
+    },
+}
+
+impl RunError {
+    /// The code the process exits with after this error.
+    fn exit_code(&self) -> ExitCode {
+        match self {
+            RunError::App(_) | RunError::Config(_) | RunError::MissingConfig => ExitCode::Config,
+            RunError::ConfigEdit(err) | RunError::Tag(TagError::Config(err)) => {
+                config_edit_exit_code(err)
+            }
+            RunError::Reader(_)
+            | RunError::Loop(RunLoopError::Reader(_))
+            | RunError::Tag(TagError::Reader(_) | TagError::ReaderShutdown) => {
+                ExitCode::ReaderUnavailable
+            }
+            RunError::Player(_)
+            | RunError::Loop(RunLoopError::Controller(ControllerError::Audio(_))) => {
+                ExitCode::AudioUnavailable
+            }
+            RunError::Tag(TagError::MissingConfig) => ExitCode::Config,
+            RunError::InvalidConfig(_) | RunError::VerifyFailed(_) | RunError::AnalyzeFailed(_) => {
+                ExitCode::ValidationFailed
+            }
+            _ => ExitCode::Failure,
+        }
+    }
+}
+
+/// Edits fail as config errors when the file itself is the problem, and
+/// as ordinary failures when the edit was refused.
+fn config_edit_exit_code(err: &ConfigEditError) -> ExitCode {
+    match err {
+        ConfigEditError::Read { .. }
+        | ConfigEditError::Parse(_)
+        | ConfigEditError::MissingCards
+        | ConfigEditError::Decode(_) => ExitCode::Config,
+        _ => ExitCode::Failure,
+    }
 }
 
 #[derive(Debug, Parser)]
@@ -56,8 +344,12 @@ struct Cli {
     #[arg(value_name = "CONFIG", value_hint = ValueHint::FilePath)]
     config: Option<PathBuf>,
 
-    #[arg(long, default_value_t = 200, value_name = "MILLIS")]
-    poll_interval_ms: u64,
+    #[arg(
+        long,
+        value_name = "MILLIS",
+        help = "Reader poll interval; overrides the [pcsc] and [pn532] settings [default: 200]"
+    )]
+    poll_interval_ms: Option<u64>,
 
     #[arg(long, value_enum, default_value_t = ReaderKind::Auto)]
     reader: ReaderKind,
@@ -65,13 +357,86 @@ struct Cli {
     #[arg(long, help = "Disable audio playback (use silent mode)")]
     silent: bool,
 
+    #[command(flatten)]
+    reader_errors: ReaderErrorArgs,
+
+    #[command(flatten)]
+    logging: LogArgs,
+
+    #[command(flatten)]
+    mirroring: MirrorArgs,
+
+    #[arg(
+        long,
+        value_name = "PATH",
+        value_hint = ValueHint::FilePath,
+        help = "Append notable events (such as crashes) to this JSON Lines file"
+    )]
+    history_log: Option<PathBuf>,
+
+    #[arg(
+        long,
+        value_name = "PATH",
+        value_hint = ValueHint::FilePath,
+        help = "Remember where cards marked `resume = true` left off in this JSON file"
+    )]
+    resume_state: Option<PathBuf>,
+
+    #[arg(
+        long,
+        help = "Check tracks against their recorded checksums in the background at startup"
+    )]
+    verify_tracks: bool,
+
+    #[arg(long, help = "Do not reload the cards when the config file changes")]
+    no_watch_config: bool,
+
+    #[arg(
+        long,
+        help = "Note unknown cards as they are tapped, for `tag add --learned` to map"
+    )]
+    learn: bool,
+
+    #[arg(
+        long,
+        value_name = "PATH",
+        value_hint = ValueHint::FilePath,
+        help = "Unix socket the player listens on for `ctl` commands [default: $XDG_RUNTIME_DIR/musicbox.sock]"
+    )]
+    control_socket: Option<PathBuf>,
+
+    #[arg(
+        long,
+        value_enum,
+        global = true,
+        default_value_t = OutputFormat::Text,
+        help = "Print each command's result as text, or as one JSON object for scripts"
+    )]
+    output: OutputFormat,
+
+    #[arg(
+        long,
+        short = 'q',
+        global = true,
+        help = "Hide progress bars and per-item lines from long commands"
+    )]
+    quiet: bool,
+
     #[cfg(feature = "waveshare-display")]
     #[command(flatten)]
     waveshare: WaveshareDisplayArgs,
 
+    #[cfg(feature = "ws2812-led")]
+    #[command(flatten)]
+    ws2812: Ws2812Args,
+
+    #[cfg(feature = "rotary-knob")]
+    #[command(flatten)]
+    knob: RotaryKnobArgs,
+
     #[cfg(feature = "debug-http")]
-    #[arg(long, value_name = "ADDR", value_hint = ValueHint::Hostname)]
-    debug_http: Option<SocketAddr>,
+    #[command(flatten)]
+    debug_http: DebugHttpArgs,
 
     #[command(subcommand)]
     command: Option<Command>,
@@ -83,520 +448,3156 @@ enum Command {
     Tag(TagCommand),
     #[command(subcommand)]
     Manual(ManualCommand),
+    #[command(subcommand)]
+    Config(ConfigCommand),
+    #[command(subcommand)]
+    Library(LibraryCommand),
     Add(TagAddArgs),
+    /// Copy cards (and optionally tracks) from another musicbox.
+    Sync(SyncArgs),
+    /// Bundle the config and state files into one .tar.zst archive.
+    Backup(BackupArgs),
+    /// Unpack an archive made by `backup`.
+    Restore(RestoreArgs),
+    /// Check tracks for corruption against their recorded checksums.
+    Verify(VerifyArgs),
+    /// List what was played and for how long, newest first.
+    History(HistoryArgs),
+    /// Show the most played cards and how long was listened each day and
+    /// week.
+    Stats(StatsArgs),
+    /// Check GitHub for a newer release, and optionally install it.
+    UpdateCheck(UpdateCheckArgs),
+    /// Walk through choosing the hardware and write a first config.
+    Setup(SetupArgs),
+    /// Control the running player through its control socket.
+    #[cfg(unix)]
+    #[command(subcommand)]
+    Ctl(CtlCommand),
 }
 
+#[cfg(unix)]
 #[derive(Debug, Subcommand)]
-enum TagCommand {
-    Add(TagAddArgs),
-}
-
-#[derive(Debug, Subcommand)]
-enum ManualCommand {
-    Trigger(ManualTriggerArgs),
-}
-
-#[derive(Debug, Args)]
-struct ManualTriggerArgs {
-    #[arg(long, value_name = "CONFIG", value_hint = ValueHint::FilePath)]
-    config: PathBuf,
-    #[arg(value_name = "UID", help = "Hex-encoded card UID (no spaces)")]
-    card: String,
+enum CtlCommand {
+    /// Play a card, by hex UID or name, as if it had been tapped. Anything
+    /// else is searched for by file name and title.
+    Play {
+        #[arg(value_name = "CARD")]
+        card: String,
+    },
+    /// Stop what is playing.
+    Pause,
+    /// Show what is playing.
+    Status,
+    /// Re-read the config file and pick up its cards.
+    Reload,
+    /// List the playing card's tracks; the one playing is marked.
+    Queue,
+    /// Drop a track from the playing card's queue until the card is tapped
+    /// again.
+    Dequeue {
+        /// Its index, as `ctl queue` lists it.
+        #[arg(value_name = "INDEX")]
+        index: usize,
+    },
 }
 
 #[derive(Debug, Args)]
-struct TagAddArgs {
-    #[arg(long, value_name = "CONFIG", value_hint = ValueHint::FilePath)]
-    config: Option<PathBuf>,
-
-    #[arg(long, value_name = "TRACK", value_hint = ValueHint::FilePath)]
-    track: PathBuf,
-
-    #[arg(long, value_name = "UID", help = "Hex-encoded card UID (no spaces)")]
-    card: Option<String>,
-
+struct HistoryArgs {
     #[arg(
         long,
-        value_enum,
-        value_name = "KIND",
-        help = "Reader backend override"
+        value_name = "PATH",
+        value_hint = ValueHint::FilePath,
+        help = "The player's --history-log file"
     )]
-    reader: Option<ReaderKind>,
+    log: Option<PathBuf>,
 
     #[arg(
         long,
-        value_name = "MILLIS",
-        help = "Override poll interval in milliseconds while waiting for a card"
+        value_name = "CONFIG",
+        value_hint = ValueHint::FilePath,
+        help = "Config to name the cards from"
     )]
-    poll_interval_ms: Option<u64>,
+    config: Option<PathBuf>,
 
-    #[arg(long, help = "Skip writing metadata to the NFC tag")]
-    skip_tag_write: bool,
-}
+    #[arg(long, value_name = "COUNT", default_value_t = history::DEFAULT_LIMIT)]
+    limit: usize,
 
-#[cfg(feature = "waveshare-display")]
-#[derive(Debug, Args, Clone)]
-struct WaveshareDisplayArgs {
-    #[arg(long, help = "Enable status updates on a Waveshare e-ink Pi HAT")]
-    waveshare_display: bool,
+    #[arg(
+        long,
+        value_name = "AGE",
+        value_parser = parse_lifetime,
+        help = "Only plays that started within this long, like 12h or 7d"
+    )]
+    since: Option<Duration>,
+}
 
+#[derive(Debug, Args)]
+struct StatsArgs {
     #[arg(
-        long = "waveshare-spi",
+        long,
         value_name = "PATH",
-        default_value = "/dev/spidev0.0",
         value_hint = ValueHint::FilePath,
-        help = "SPI device path for the display"
+        help = "The player's --history-log file"
     )]
-    spi_path: String,
+    log: Option<PathBuf>,
 
     #[arg(
-        long = "waveshare-busy-pin",
-        value_name = "PIN",
-        default_value_t = 24,
-        help = "GPIO pin wired to the display BUSY line"
+        long,
+        value_name = "CONFIG",
+        value_hint = ValueHint::FilePath,
+        help = "Config to name the cards from"
     )]
-    busy_pin: u64,
+    config: Option<PathBuf>,
 
     #[arg(
-        long = "waveshare-dc-pin",
-        value_name = "PIN",
-        default_value_t = 25,
-        help = "GPIO pin wired to the display D/C line"
+        long,
+        value_name = "COUNT",
+        default_value_t = stats::DEFAULT_TOP,
+        help = "How many of the most played cards to list"
     )]
-    dc_pin: u64,
+    top: usize,
 
     #[arg(
-        long = "waveshare-reset-pin",
-        value_name = "PIN",
-        default_value_t = 17,
-        help = "GPIO pin wired to the display RESET line"
+        long,
+        value_name = "AGE",
+        value_parser = parse_lifetime,
+        help = "Only plays that started within this long, like 12h or 7d"
     )]
-    reset_pin: u64,
+    since: Option<Duration>,
+}
 
+#[derive(Debug, Args)]
+struct SetupArgs {
     #[arg(
-        long = "waveshare-gpio-chip",
-        value_name = "PATH",
-        default_value = "/dev/gpiochip0",
+        long,
+        value_name = "CONFIG",
         value_hint = ValueHint::FilePath,
-        help = "GPIO character device path providing the configured pins"
+        help = "Where to write the config [default: musicbox.toml]"
     )]
-    gpio_chip_path: String,
+    config: Option<PathBuf>,
 }
 
-#[cfg(feature = "waveshare-display")]
-fn waveshare_config_from_args(args: &WaveshareDisplayArgs) -> Option<WaveshareConfig> {
-    if !args.waveshare_display {
-        return None;
-    }
+#[derive(Debug, Args)]
+struct UpdateCheckArgs {
+    #[arg(
+        long,
+        help = "Download the newer release's prebuilt binary and replace this one"
+    )]
+    install: bool,
 
-    let mut config = WaveshareConfig::default();
-    config.spi_path = args.spi_path.clone();
-    config.busy_pin = args.busy_pin;
-    config.dc_pin = args.dc_pin;
-    config.reset_pin = args.reset_pin;
-    config.gpio_chip_path = args.gpio_chip_path.clone();
-    Some(config)
+    #[arg(
+        long,
+        value_name = "OWNER/NAME",
+        default_value = update::DEFAULT_REPOSITORY,
+        help = "GitHub repository to check for releases"
+    )]
+    repository: String,
 }
 
-#[derive(Copy, Clone, Debug, ValueEnum)]
-enum ReaderKind {
-    Auto,
-    Pcsc,
-    Noop,
-}
+#[derive(Debug, Args)]
+struct VerifyArgs {
+    #[arg(long, value_name = "CONFIG", value_hint = ValueHint::FilePath)]
+    config: Option<PathBuf>,
 
-#[derive(Debug, Error)]
-enum TagError {
-    #[error(
-        "configuration path required; pass --config <PATH> or provide CONFIG before the command"
+    #[arg(
+        long,
+        help = "Record checksums for the current files instead of checking them"
     )]
-    MissingConfig,
-    #[error("invalid card uid: {0}")]
-    CardUidParse(#[from] CardUidParseError),
-    #[error("reader error: {0}")]
-    Reader(#[from] ReaderError),
-    #[error("reader shut down before a card was detected")]
-    ReaderShutdown,
-    #[error(transparent)]
-    Config(#[from] ConfigEditError),
-    #[error("track path {0:?} is not valid UTF-8")]
-    InvalidTrackPath(PathBuf),
+    update: bool,
 }
 
-/// Parses command-line arguments and calls the appropriate handler.
-fn run() -> Result<(), RunError> {
-    let cli = Cli::parse();
+#[derive(Debug, Args)]
+struct BackupArgs {
+    #[arg(value_name = "FILE", value_hint = ValueHint::FilePath)]
+    file: PathBuf,
 
-    let Cli {
-        config,
-        poll_interval_ms,
+    #[arg(long, value_name = "CONFIG", value_hint = ValueHint::FilePath)]
+    config: Option<PathBuf>,
+
+    #[arg(
+        long = "state",
+        value_name = "PATH",
+        value_hint = ValueHint::FilePath,
+        help = "Extra state file to include; repeatable. The --history-log and --resume-state files are included automatically"
+    )]
+    state_files: Vec<PathBuf>,
+
+    #[arg(long, help = "Also include the music directory")]
+    include_music: bool,
+}
+
+#[derive(Debug, Args)]
+struct RestoreArgs {
+    #[arg(value_name = "FILE", value_hint = ValueHint::FilePath)]
+    file: PathBuf,
+
+    #[arg(
+        long,
+        value_name = "CONFIG",
+        value_hint = ValueHint::FilePath,
+        help = "Restore the config here instead of its original path"
+    )]
+    config: Option<PathBuf>,
+
+    #[arg(
+        long,
+        value_name = "DIR",
+        value_hint = ValueHint::DirPath,
+        help = "Restore music here instead of its original directory"
+    )]
+    music_dir: Option<PathBuf>,
+
+    #[arg(
+        long = "state",
+        value_name = "PATH",
+        value_hint = ValueHint::FilePath,
+        help = "Extra state file to restore, matched by file name; repeatable. The --history-log and --resume-state files are restored automatically"
+    )]
+    state_files: Vec<PathBuf>,
+
+    #[arg(long, help = "Overwrite files that already exist")]
+    force: bool,
+}
+
+#[derive(Debug, Args)]
+struct SyncArgs {
+    #[arg(long, value_name = "CONFIG", value_hint = ValueHint::FilePath)]
+    config: Option<PathBuf>,
+
+    #[arg(
+        long,
+        value_name = "URL",
+        help = "Debug HTTP address of the box to copy from, e.g. http://otherbox:3000"
+    )]
+    from: String,
+
+    #[arg(long, help = "Also download tracks that are missing under music_dir")]
+    with_tracks: bool,
+}
+
+#[derive(Debug, Subcommand)]
+enum ConfigCommand {
+    /// List audio files under music_dir that no card references.
+    Orphans(ConfigOrphansArgs),
+    /// Remove guest cards whose expiry time has passed.
+    PruneExpired(ConfigPruneArgs),
+    /// Check that every track exists and decodes, and report shared and
+    /// unmapped files.
+    Validate(ConfigValidateArgs),
+}
+
+#[derive(Debug, Subcommand)]
+enum LibraryCommand {
+    /// Find audio files under music_dir that no card plays and add
+    /// placeholder entries for them under [cards].
+    Scan(LibraryScanArgs),
+    /// Measure the loudness of every track under music_dir and cache it
+    /// beside the track, for the [loudness] table.
+    Analyze(LibraryAnalyzeArgs),
+}
+
+#[derive(Debug, Args)]
+struct LibraryAnalyzeArgs {
+    #[arg(long, value_name = "CONFIG", value_hint = ValueHint::FilePath)]
+    config: Option<PathBuf>,
+
+    #[arg(long, help = "Measure tracks again even if they were analyzed")]
+    force: bool,
+}
+
+#[derive(Debug, Args)]
+struct LibraryScanArgs {
+    #[arg(long, value_name = "CONFIG", value_hint = ValueHint::FilePath)]
+    config: Option<PathBuf>,
+
+    #[arg(
+        long,
+        help = "Print the entries that would be added without writing them"
+    )]
+    dry_run: bool,
+}
+
+#[derive(Debug, Args)]
+struct ConfigValidateArgs {
+    #[arg(long, value_name = "CONFIG", value_hint = ValueHint::FilePath)]
+    config: Option<PathBuf>,
+
+    #[arg(long, help = "Print the report as JSON")]
+    json: bool,
+
+    #[arg(long, help = "Fail on warnings as well as errors")]
+    strict: bool,
+}
+
+#[derive(Debug, Args)]
+struct ConfigPruneArgs {
+    #[arg(long, value_name = "CONFIG", value_hint = ValueHint::FilePath)]
+    config: Option<PathBuf>,
+}
+
+#[derive(Debug, Args)]
+struct ConfigOrphansArgs {
+    #[arg(long, value_name = "CONFIG", value_hint = ValueHint::FilePath)]
+    config: Option<PathBuf>,
+
+    #[arg(long, help = "Print commented TOML stubs ready to paste under [cards]")]
+    stubs: bool,
+}
+
+#[derive(Debug, Subcommand)]
+enum TagCommand {
+    Add(TagAddArgs),
+    /// Map every audio file in a folder, one tapped card at a time.
+    Batch(TagBatchArgs),
+    /// Print each card and what it plays.
+    List(TagListArgs),
+    /// Point an existing card at a different track.
+    Update(TagUpdateArgs),
+    /// Delete a card's entry from the config.
+    Remove(TagRemoveArgs),
+}
+
+#[derive(Debug, Args)]
+struct TagListArgs {
+    #[arg(long, value_name = "CONFIG", value_hint = ValueHint::FilePath)]
+    config: Option<PathBuf>,
+
+    #[arg(long, help = "Print the cards as a JSON array")]
+    json: bool,
+}
+
+#[derive(Debug, Args)]
+struct TagBatchArgs {
+    #[arg(long, value_name = "CONFIG", value_hint = ValueHint::FilePath)]
+    config: Option<PathBuf>,
+
+    #[arg(long, value_name = "MUSIC_DIR", value_hint = ValueHint::DirPath)]
+    dir: PathBuf,
+
+    #[arg(
+        long,
+        value_enum,
+        value_name = "KIND",
+        help = "Reader backend override"
+    )]
+    reader: Option<ReaderKind>,
+
+    #[arg(
+        long,
+        value_name = "MILLIS",
+        help = "Override poll interval in milliseconds while waiting for a card"
+    )]
+    poll_interval_ms: Option<u64>,
+
+    #[arg(long, help = "Also write each track to its card as an NDEF record")]
+    write_tags: bool,
+
+    #[arg(
+        long,
+        value_enum,
+        value_name = "STRATEGY",
+        default_value_t = SyntheticUidKind::Random,
+        help = "How to generate UIDs when the reader cannot scan cards"
+    )]
+    synthetic_uid: SyntheticUidKind,
+}
+
+#[derive(Debug, Args)]
+struct TagUpdateArgs {
+    #[arg(long, value_name = "CONFIG", value_hint = ValueHint::FilePath)]
+    config: Option<PathBuf>,
+
+    #[arg(long, value_name = "UID", help = "Hex-encoded card UID or card name")]
+    card: String,
+
+    #[arg(long, value_name = "PATH", value_hint = ValueHint::FilePath)]
+    track: PathBuf,
+
+    #[arg(
+        long,
+        help = "Replace a playlist or command card with the single track"
+    )]
+    force: bool,
+}
+
+#[derive(Debug, Args)]
+struct TagRemoveArgs {
+    #[arg(long, value_name = "CONFIG", value_hint = ValueHint::FilePath)]
+    config: Option<PathBuf>,
+
+    #[arg(long, value_name = "UID", help = "Hex-encoded card UID or card name")]
+    card: String,
+}
+
+#[derive(Debug, Subcommand)]
+enum ManualCommand {
+    Trigger(ManualTriggerArgs),
+    Script(ManualScriptArgs),
+    Mute(ManualMuteArgs),
+}
+
+#[derive(Debug, Args)]
+struct ManualTriggerArgs {
+    #[arg(long, value_name = "CONFIG", value_hint = ValueHint::FilePath)]
+    config: Option<PathBuf>,
+    #[arg(
+        long,
+        value_name = "ADDR",
+        value_hint = ValueHint::Hostname,
+        help = "Debug HTTP address of a running instance to trigger instead of playing locally"
+    )]
+    remote: Option<SocketAddr>,
+    #[arg(value_name = "CARD", help = "Hex-encoded card UID or card name")]
+    card: String,
+}
+
+#[derive(Debug, Args)]
+struct ManualScriptArgs {
+    #[arg(long, value_name = "CONFIG", value_hint = ValueHint::FilePath)]
+    config: Option<PathBuf>,
+    #[arg(
+        long,
+        value_name = "ADDR",
+        value_hint = ValueHint::Hostname,
+        help = "Debug HTTP address of a running instance to drive instead of playing locally"
+    )]
+    remote: Option<SocketAddr>,
+    #[arg(
+        value_name = "FILE",
+        value_hint = ValueHint::FilePath,
+        help = "Replay script with `<uid> [millis]` or `wait <millis>` per line"
+    )]
+    script: PathBuf,
+}
+
+#[derive(Debug, Args)]
+struct ManualMuteArgs {
+    #[arg(
+        long,
+        value_name = "ADDR",
+        value_hint = ValueHint::Hostname,
+        help = "Debug HTTP address of the running instance to mute or unmute"
+    )]
+    remote: SocketAddr,
+    #[arg(value_enum, value_name = "STATE")]
+    state: MuteState,
+}
+
+#[derive(Copy, Clone, Debug, ValueEnum)]
+enum MuteState {
+    On,
+    Off,
+}
+
+#[derive(Debug, Args)]
+struct TagAddArgs {
+    #[arg(long, value_name = "CONFIG", value_hint = ValueHint::FilePath)]
+    config: Option<PathBuf>,
+
+    #[arg(long, value_name = "TRACK", value_hint = ValueHint::FilePath)]
+    track: PathBuf,
+
+    #[arg(long, value_name = "UID", help = "Hex-encoded card UID (no spaces)")]
+    card: Option<String>,
+
+    #[arg(
+        long,
+        conflicts_with = "card",
+        help = "Map the card the running player (started with --learn) noted last"
+    )]
+    learned: bool,
+
+    #[arg(
+        long,
+        value_enum,
+        value_name = "KIND",
+        help = "Reader backend override"
+    )]
+    reader: Option<ReaderKind>,
+
+    #[arg(
+        long,
+        value_name = "MILLIS",
+        help = "Override poll interval in milliseconds while waiting for a card"
+    )]
+    poll_interval_ms: Option<u64>,
+
+    #[arg(long, help = "Skip writing metadata to the NFC tag")]
+    skip_tag_write: bool,
+
+    #[arg(
+        long,
+        value_enum,
+        value_name = "STRATEGY",
+        default_value_t = SyntheticUidKind::Random,
+        help = "How to generate a UID when the reader cannot scan cards"
+    )]
+    synthetic_uid: SyntheticUidKind,
+
+    #[arg(
+        long,
+        value_name = "LIFETIME",
+        value_parser = parse_lifetime,
+        help = "Register a guest card that stops working after this long, like 12h or 7d"
+    )]
+    expires: Option<Duration>,
+}
+
+#[derive(Copy, Clone, Debug, ValueEnum)]
+enum SyntheticUidKind {
+    Random,
+    Ulid,
+}
+
+impl From<SyntheticUidKind> for SyntheticUid {
+    fn from(value: SyntheticUidKind) -> Self {
+        match value {
+            SyntheticUidKind::Random => SyntheticUid::Random,
+            SyntheticUidKind::Ulid => SyntheticUid::Ulid,
+        }
+    }
+}
+
+#[derive(Debug, Args, Clone)]
+struct LogArgs {
+    #[arg(
+        long,
+        value_enum,
+        global = true,
+        env = "MUSICBOX_LOG_FORMAT",
+        default_value_t = LogFormatArg::Text,
+        help = "Write log lines as text, or as one JSON object each for log shippers"
+    )]
+    log_format: LogFormatArg,
+
+    #[arg(
+        long,
+        value_name = "PATH",
+        value_hint = ValueHint::FilePath,
+        global = true,
+        env = "MUSICBOX_LOG_FILE",
+        help = "Also append log lines to this file, rotating it by size"
+    )]
+    log_file: Option<PathBuf>,
+
+    #[arg(
+        long,
+        value_name = "MIB",
+        global = true,
+        default_value_t = logfile::DEFAULT_MAX_BYTES >> 20,
+        value_parser = clap::value_parser!(u64).range(1..),
+        help = "Rotate the log file once it reaches this many MiB"
+    )]
+    log_max_size_mib: u64,
+
+    #[arg(
+        long,
+        value_name = "COUNT",
+        global = true,
+        default_value_t = logfile::DEFAULT_KEEP,
+        help = "Rotated log files to keep beside the current one"
+    )]
+    log_keep: usize,
+}
+
+#[derive(Debug, Args, Clone)]
+struct MirrorArgs {
+    #[arg(
+        long,
+        value_name = "DIR",
+        value_hint = ValueHint::DirPath,
+        global = true,
+        env = "MUSICBOX_MIRROR_DIR",
+        help = "Copy the config and state files here after each write, such as onto a USB stick"
+    )]
+    mirror_dir: Option<PathBuf>,
+
+    #[arg(
+        long,
+        help = "Restore a config or resume state that fails to load from the mirror without asking"
+    )]
+    restore_from_mirror: bool,
+}
+
+#[derive(Copy, Clone, Debug, ValueEnum)]
+enum LogFormatArg {
+    Text,
+    Json,
+}
+
+impl From<LogFormatArg> for LogFormat {
+    fn from(value: LogFormatArg) -> Self {
+        match value {
+            LogFormatArg::Text => LogFormat::Text,
+            LogFormatArg::Json => LogFormat::Json,
+        }
+    }
+}
+
+#[derive(Debug, Args, Clone)]
+struct ReaderErrorArgs {
+    #[arg(
+        long,
+        value_enum,
+        value_name = "POLICY",
+        default_value_t = OnReaderError::Fail,
+        help = "What to do once reader errors persist"
+    )]
+    on_reader_error: OnReaderError,
+
+    #[arg(
+        long,
+        value_name = "COUNT",
+        default_value_t = 3,
+        help = "Consecutive reader errors tolerated before the policy applies"
+    )]
+    reader_error_threshold: u32,
+
+    #[arg(
+        long,
+        value_name = "URL",
+        help = "http:// or https:// endpoint notified when the reader fails or falls back to noop"
+    )]
+    reader_alert_webhook: Option<String>,
+}
+
+#[derive(Copy, Clone, Debug, ValueEnum)]
+enum OnReaderError {
+    Fail,
+    Retry,
+    Fallback,
+}
+
+impl From<OnReaderError> for ReaderErrorPolicy {
+    fn from(value: OnReaderError) -> Self {
+        match value {
+            OnReaderError::Fail => ReaderErrorPolicy::Fail,
+            OnReaderError::Retry => ReaderErrorPolicy::RetryForever,
+            OnReaderError::Fallback => ReaderErrorPolicy::FallbackNoop,
+        }
+    }
+}
+
+fn reader_error_settings_from_args(args: &ReaderErrorArgs, poll: Duration) -> ReaderErrorSettings {
+    ReaderErrorSettings {
+        policy: args.on_reader_error.into(),
+        threshold: args.reader_error_threshold,
+        retry_delay: poll,
+        ..ReaderErrorSettings::default()
+    }
+}
+
+#[cfg(feature = "ws2812-led")]
+#[derive(Debug, Args, Clone)]
+struct Ws2812Args {
+    #[arg(
+        long = "ws2812-spi",
+        value_name = "PATH",
+        value_hint = ValueHint::FilePath,
+        help = "spidev device whose MOSI pin drives a WS2812 strip showing card colors"
+    )]
+    led_spi_path: Option<PathBuf>,
+
+    #[arg(
+        long = "ws2812-count",
+        value_name = "LEDS",
+        default_value_t = 8,
+        help = "Number of LEDs on the WS2812 strip"
+    )]
+    count: usize,
+}
+
+#[cfg(feature = "debug-http")]
+#[derive(Debug, Args, Clone)]
+struct DebugHttpArgs {
+    #[arg(long, value_name = "ADDR", value_hint = ValueHint::Hostname)]
+    debug_http: Option<SocketAddr>,
+
+    #[arg(
+        long = "debug-http-readonly",
+        requires = "debug_http",
+        help = "Refuse web API requests that would change the box, as `read_only` in [web] does"
+    )]
+    debug_http_readonly: bool,
+}
+
+#[cfg(feature = "rotary-knob")]
+#[derive(Debug, Args, Clone)]
+struct RotaryKnobArgs {
+    #[arg(
+        long = "knob-pin-a",
+        value_name = "GPIO",
+        requires = "knob_pin_b",
+        help = "GPIO line of a rotary encoder's A (CLK) pin, used as a volume knob"
+    )]
+    knob_pin_a: Option<u32>,
+
+    #[arg(
+        long = "knob-pin-b",
+        value_name = "GPIO",
+        requires = "knob_pin_a",
+        help = "GPIO line of the rotary encoder's B (DT) pin"
+    )]
+    knob_pin_b: Option<u32>,
+
+    #[arg(
+        long = "knob-gpio-chip",
+        value_name = "PATH",
+        default_value = "/dev/gpiochip0",
+        value_hint = ValueHint::FilePath
+    )]
+    knob_gpio_chip_path: PathBuf,
+
+    #[arg(
+        long = "knob-step",
+        value_name = "VOLUME",
+        default_value_t = 0.05,
+        help = "Volume change per knob detent, from 0.0 to 1.0"
+    )]
+    step: f32,
+}
+
+#[cfg(feature = "waveshare-display")]
+#[derive(Debug, Args, Clone)]
+struct WaveshareDisplayArgs {
+    #[arg(long, help = "Enable status updates on a Waveshare e-ink Pi HAT")]
+    waveshare_display: bool,
+
+    #[arg(
+        long = "waveshare-spi",
+        value_name = "PATH",
+        default_value = "/dev/spidev0.0",
+        value_hint = ValueHint::FilePath,
+        help = "SPI device path for the display"
+    )]
+    spi_path: String,
+
+    #[arg(
+        long = "waveshare-busy-pin",
+        value_name = "PIN",
+        default_value_t = 24,
+        help = "GPIO pin wired to the display BUSY line"
+    )]
+    busy_pin: u64,
+
+    #[arg(
+        long = "waveshare-dc-pin",
+        value_name = "PIN",
+        default_value_t = 25,
+        help = "GPIO pin wired to the display D/C line"
+    )]
+    dc_pin: u64,
+
+    #[arg(
+        long = "waveshare-reset-pin",
+        value_name = "PIN",
+        default_value_t = 17,
+        help = "GPIO pin wired to the display RESET line"
+    )]
+    reset_pin: u64,
+
+    #[arg(
+        long = "waveshare-gpio-chip",
+        value_name = "PATH",
+        default_value = "/dev/gpiochip0",
+        value_hint = ValueHint::FilePath,
+        help = "GPIO character device path providing the configured pins"
+    )]
+    gpio_chip_path: String,
+}
+
+#[cfg(feature = "waveshare-display")]
+fn waveshare_config_from_args(args: &WaveshareDisplayArgs) -> Option<WaveshareConfig> {
+    if !args.waveshare_display {
+        return None;
+    }
+
+    Some(WaveshareConfig {
+        spi_path: args.spi_path.clone(),
+        busy_pin: args.busy_pin,
+        dc_pin: args.dc_pin,
+        reset_pin: args.reset_pin,
+        gpio_chip_path: args.gpio_chip_path.clone(),
+        ..WaveshareConfig::default()
+    })
+}
+
+#[derive(Copy, Clone, Debug, ValueEnum)]
+enum ReaderKind {
+    Auto,
+    Pcsc,
+    Pn532,
+    Noop,
+}
+
+#[derive(Debug, Error)]
+enum TagError {
+    #[error(
+        "configuration path required; pass --config <PATH> or provide CONFIG before the command"
+    )]
+    MissingConfig,
+    #[error("invalid card uid: {0}")]
+    CardUidParse(#[from] CardUidParseError),
+    #[error("reader error: {0}")]
+    Reader(#[from] ReaderError),
+    #[error("reader shut down before a card was detected")]
+    ReaderShutdown,
+    #[error(transparent)]
+    Config(#[from] ConfigEditError),
+    #[error("track path {0:?} is not valid UTF-8")]
+    InvalidTrackPath(PathBuf),
+    #[error("card {0} plays a playlist or runs a command; pass --force to replace it")]
+    NeedsForce(CardUid),
+    #[error("{0:?} is not a directory")]
+    NotADirectory(PathBuf),
+    #[cfg(unix)]
+    #[error(transparent)]
+    Control(#[from] ControlError),
+    #[error(
+        "the running player has not noted any unknown cards; start it with --learn and tap one"
+    )]
+    NothingLearned,
+}
+
+/// Parses command-line arguments and calls the appropriate handler.
+fn run(cli: Cli) -> Result<(), RunError> {
+    let Cli {
+        config,
+        poll_interval_ms,
         reader,
         silent,
+        reader_errors,
+        history_log,
+        resume_state,
+        verify_tracks,
+        no_watch_config,
+        learn,
+        control_socket,
+        output: _,
+        quiet: _,
+        logging: _,
+        mirroring,
         #[cfg(feature = "waveshare-display")]
         waveshare,
+        #[cfg(feature = "ws2812-led")]
+        ws2812,
+        #[cfg(feature = "rotary-knob")]
+        knob,
         #[cfg(feature = "debug-http")]
         debug_http,
         command,
     } = cli;
+    if let Some(dir) = mirroring.mirror_dir {
+        mirror::install(Mirror::new(dir));
+    }
+
+    match command {
+        Some(Command::Tag(tag_command)) => {
+            handle_tag_command(
+                tag_command,
+                config.clone(),
+                reader,
+                poll_interval_ms,
+                control_socket.as_deref(),
+            )?;
+        }
+        Some(Command::Manual(manual_command)) => {
+            handle_manual_command(manual_command, silent, control_socket.as_deref())?;
+        }
+        Some(Command::Config(config_command)) => {
+            handle_config_command(config_command, config.clone())?;
+        }
+        Some(Command::Library(LibraryCommand::Scan(args))) => {
+            handle_library_scan(args, config.clone())?;
+        }
+        Some(Command::Library(LibraryCommand::Analyze(args))) => {
+            handle_library_analyze(args, config.clone())?;
+        }
+        Some(Command::Add(args)) => {
+            handle_tag_add(
+                args,
+                config.clone(),
+                reader,
+                poll_interval_ms,
+                control_socket.as_deref(),
+            )?;
+        }
+        Some(Command::Sync(args)) => {
+            handle_sync(args, config.clone())?;
+        }
+        Some(Command::Backup(args)) => {
+            let state_files = history_log.iter().chain(&resume_state).cloned().collect();
+            handle_backup(args, config.clone(), state_files)?;
+        }
+        Some(Command::Restore(args)) => {
+            let state_files = history_log.iter().chain(&resume_state).cloned().collect();
+            handle_restore(args, state_files)?;
+        }
+        Some(Command::Verify(args)) => {
+            handle_verify(args, config.clone())?;
+        }
+        Some(Command::History(args)) => {
+            handle_history(args, config.clone(), history_log.clone())?;
+        }
+        Some(Command::Stats(args)) => {
+            handle_stats(args, config.clone(), history_log.clone())?;
+        }
+        Some(Command::UpdateCheck(args)) => {
+            handle_update_check(args)?;
+        }
+        Some(Command::Setup(args)) => {
+            handle_setup(args, config.clone(), poll_interval_ms)?;
+        }
+        #[cfg(unix)]
+        Some(Command::Ctl(ctl_command)) => {
+            handle_ctl(ctl_command, control_socket.as_deref())?;
+        }
+        None => {
+            let config_path = config.ok_or(RunError::MissingConfig)?;
+            #[cfg(feature = "waveshare-display")]
+            let waveshare_config = waveshare_config_from_args(&waveshare);
+            run_player_main(
+                config_path,
+                poll_interval_ms,
+                reader,
+                silent,
+                &reader_errors,
+                history_log.map(HistoryLog::new),
+                resume_state,
+                mirroring.restore_from_mirror,
+                verify_tracks,
+                !no_watch_config,
+                learn,
+                control_socket,
+                #[cfg(feature = "waveshare-display")]
+                waveshare_config,
+                #[cfg(feature = "ws2812-led")]
+                &ws2812,
+                #[cfg(feature = "rotary-knob")]
+                &knob,
+                #[cfg(feature = "debug-http")]
+                debug_http,
+            )?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Offers the mirror's copy of `path`, which failed to load with
+/// `problem`, when that copy `loads`. With `--restore-from-mirror` it is
+/// restored straight away; from a terminal, after asking; otherwise the
+/// caller carries on with the error. Returns whether `path` was restored.
+fn offer_mirror_copy(
+    path: &Path,
+    problem: &dyn std::fmt::Display,
+    loads: impl Fn(&Path) -> bool,
+    restore: bool,
+) -> bool {
+    let Some(mirror) = mirror::installed() else {
+        return false;
+    };
+    let Some(copy) = mirror
+        .copy_of(path)
+        .filter(|copy| copy.is_file() && loads(copy))
+    else {
+        return false;
+    };
+    let restore = restore
+        || std::io::stdin().is_terminal() && {
+            eprintln!("{problem}");
+            Prompter::new(std::io::stdin().lock(), std::io::stderr())
+                .confirm(
+                    &format!("The mirror's copy at {} loads. Restore it?", copy.display()),
+                    true,
+                )
+                .unwrap_or(false)
+        };
+    if !restore {
+        eprintln!(
+            "The mirror's copy at {} loads; start with --restore-from-mirror to use it.",
+            copy.display()
+        );
+        return false;
+    }
+    match mirror.restore(path) {
+        Ok(Some(aside)) => eprintln!(
+            "Restored {} from the mirror; the old file is now {}.",
+            path.display(),
+            aside.display()
+        ),
+        Ok(None) => eprintln!("Restored {} from the mirror.", path.display()),
+        Err(err) => {
+            eprintln!("{err}");
+            return false;
+        }
+    }
+    true
+}
+
+/// The main entry point for running the music player.
+#[allow(clippy::too_many_arguments)]
+fn run_player_main(
+    config_path: PathBuf,
+    poll_interval_ms: Option<u64>,
+    reader_kind: ReaderKind,
+    silent: bool,
+    reader_errors: &ReaderErrorArgs,
+    history: Option<HistoryLog>,
+    resume_state: Option<PathBuf>,
+    restore_from_mirror: bool,
+    verify_tracks: bool,
+    watch_config: bool,
+    learn: bool,
+    control_socket: Option<PathBuf>,
+    #[cfg(feature = "waveshare-display")] waveshare_config: Option<WaveshareConfig>,
+    #[cfg(feature = "ws2812-led")] ws2812: &Ws2812Args,
+    #[cfg(feature = "rotary-knob")] knob: &RotaryKnobArgs,
+    #[cfg(feature = "debug-http")] debug_http: DebugHttpArgs,
+) -> Result<(), RunError> {
+    let alert_webhook = reader_errors
+        .reader_alert_webhook
+        .as_deref()
+        .map(WebhookUrl::parse)
+        .transpose()?;
+
+    let config = match load_config(&config_path) {
+        Ok(config) => config,
+        Err(err) => {
+            let config_dir = config_path.parent().unwrap_or(Path::new(""));
+            let loads = |copy: &Path| {
+                std::fs::File::open(copy)
+                    .is_ok_and(|file| MusicBoxConfig::from_reader_in(file, config_dir).is_ok())
+            };
+            if !offer_mirror_copy(&config_path, &err, loads, restore_from_mirror) {
+                return Err(err.into());
+            }
+            load_config(&config_path)?
+        }
+    };
+    // It loaded, so it is the copy to fall back on next time.
+    mirror::update(&config_path);
+    let player = build_player(silent, &config);
+    let locale = config.locale();
+    let remote_display = config.remote_display().cloned();
+    let mqtt = config.mqtt().cloned();
+    if verify_tracks {
+        let music_dir = config.music_dir().to_path_buf();
+        std::thread::spawn(move || verify_tracks_in_background(&music_dir));
+    }
+    let mut buttons = open_buttons(config.buttons());
+    let notifier = config.notifier().cloned();
+    if let Some(notifier) = &notifier
+        && let Some(battery) = notifier.battery()
+    {
+        battery
+            .clone()
+            .spawn(notifier.clone(), BATTERY_POLL_INTERVAL);
+    }
+    let recording = config.recording().cloned();
+    let music_dir = config.music_dir().to_path_buf();
+    let reader_setup = ReaderSetup::from_config(&config);
+    let mut controller = MusicBoxController::new(config.into_library(), player);
+    if let Some(settings) = recording
+        && let Some(recorder) = open_recorder(&settings)
+    {
+        controller = controller.with_recorder(recorder, settings);
+    }
+    if let Some(path) = resume_state {
+        let store = ResumeStore::load(&path)
+            .or_else(|err| {
+                let loads = |copy: &Path| ResumeStore::load(copy).is_ok();
+                if offer_mirror_copy(&path, &err, loads, restore_from_mirror) {
+                    ResumeStore::load(&path)
+                } else {
+                    Err(err)
+                }
+            })
+            .unwrap_or_else(|err| {
+                eprintln!("{err}; starting with no saved positions.");
+                ResumeStore::empty(path)
+            });
+        controller = controller.with_resume_store(store);
+    }
+    if learn {
+        println!("Learning mode: unknown cards are noted for `tag add --learned`.");
+        controller.set_learning(true);
+    }
+    let controller = Arc::new(Mutex::new(controller));
+    let selection = select_reader(
+        reader_kind,
+        poll_interval_ms.map(Duration::from_millis),
+        &reader_setup,
+    )?;
+    let poll_duration = reader_setup.poll_interval(
+        selection.kind(),
+        poll_interval_ms.map(Duration::from_millis),
+    );
+    if watch_config {
+        ConfigWatcher::new(&config_path).spawn(controller.clone(), CONFIG_WATCH_INTERVAL);
+    }
+
+    let status = SharedStatus::default();
+    let health_status_state = status.clone();
+    let health_notifier = notifier.clone();
+
+    let mut reader = PolicyReader::new(
+        selection.into_reader(),
+        reader_error_settings_from_args(reader_errors, poll_duration),
+        move |health: &ReaderHealth| {
+            health_status_state.record_reader_health(health.clone());
+            if let Some(url) = &alert_webhook {
+                send_reader_alert(url, health);
+            }
+            if let Some(notifier) = &health_notifier
+                && let Some(notification) = Notification::from_reader_health(health)
+            {
+                notifier.notify(&notification);
+            }
+        },
+    );
+
+    status.record_reader_capabilities(reader.capabilities());
+    let lift_to_stop = controller
+        .lock()
+        .is_ok_and(|guard| guard.library().playback_mode() == PlaybackMode::LiftToStop);
+    if lift_to_stop && !reader.capabilities().card_removed {
+        tracing::warn!(
+            "playback_mode is lift-to-stop but this reader cannot tell when a card is lifted"
+        );
+    }
+
+    let events = EventBus::new();
+    let control_socket = spawn_control_server(
+        control_socket,
+        controller.clone(),
+        config_path.clone(),
+        events.clone(),
+    );
+
+    #[cfg(feature = "debug-http")]
+    if let Some(addr) = debug_http.debug_http {
+        let server_status = status.clone();
+        let server_controller = controller.clone();
+        let server_config = config_path.clone();
+        let server_history = history.clone();
+        let server_events = events.clone();
+        std::thread::spawn(move || {
+            let state = musicbox::web::DebugState {
+                status: server_status,
+                controller: server_controller,
+                events: server_events,
+                config_path: server_config,
+                history: server_history,
+                read_only: debug_http.debug_http_readonly,
+            };
+            if let Err(err) = musicbox::web::serve(state, addr) {
+                tracing::error!(?err, "debug server terminated");
+            }
+        });
+    }
+
+    let mut displays: Vec<SharedStatusDisplay> = Vec::new();
+    #[cfg(feature = "waveshare-display")]
+    if let Some(config) = waveshare_config {
+        let config = WaveshareConfig { locale, ..config };
+        let guarded = GuardedDisplay::new(
+            Box::new(move || {
+                WaveshareDisplay::new(config.clone())
+                    .map(|device| Box::new(device) as Box<dyn display::StatusDisplay>)
+                    .map_err(display::DisplayError::from)
+            }),
+            DisplayGuardSettings::default(),
+            status.clone(),
+        );
+        if guarded.is_active() {
+            println!("Waveshare display connected; status updates enabled.");
+        } else {
+            eprintln!("Failed to initialize Waveshare display; will retry periodically.");
+        }
+        displays.push(Arc::new(Mutex::new(
+            Box::new(guarded) as Box<dyn display::StatusDisplay>
+        )));
+    }
+    if let Some(settings) = remote_display {
+        println!("Mirroring status to the remote display.");
+        displays.push(Arc::new(Mutex::new(
+            Box::new(RemoteDisplay::new(settings, locale)) as Box<dyn display::StatusDisplay>,
+        )));
+    }
+    if let Ok(guard) = controller.lock() {
+        status.record_library(guard.library().stats());
+    }
+    update_displays(&displays, &status.snapshot());
+
+    install_panic_hook(history.clone(), displays.clone());
+
+    println!("Loaded configuration from {}", config_path.display());
+    println!("Awaiting NFC interactions (reader not connected in this environment).");
+
+    events.on({
+        let memo_config_path = config_path.clone();
+        let reload_controller = controller.clone();
+        let notifier = notifier.clone();
+        move |published| match &published.event {
+            EngineEvent::Action(action) => {
+                println!("Controller action: {:?}", action);
+                tracing::info!(?action, "controller action");
+                match action {
+                    ControllerAction::MemoRecorded { card, track } => {
+                        save_memo_mapping(&memo_config_path, &music_dir, card, track);
+                    }
+                    ControllerAction::CardLearned { card } => {
+                        println!(
+                            "Learned card {card}; map it with `musicbox tag add --learned --track <TRACK>`."
+                        );
+                    }
+                    ControllerAction::UnknownCard { card } => {
+                        if let Some(notifier) = &notifier {
+                            notifier.notify_in_background(Notification::UnknownCard {
+                                card: card.clone(),
+                            });
+                        }
+                    }
+                    ControllerAction::ReloadRequested { .. } => {
+                        match reload_library(&reload_controller, &memo_config_path) {
+                            Ok(()) => tracing::info!("reloaded config"),
+                            Err(err) => {
+                                tracing::warn!(%err, "keeping the current cards; config reload failed");
+                            }
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            EngineEvent::Rejected { card, reason } => {
+                println!("Ignoring card {card}: {reason}");
+                if let Some(notifier) = &notifier {
+                    notifier.notify_in_background(Notification::UnknownCard { card: card.clone() });
+                }
+            }
+            EngineEvent::Latency(latency) => tracing::debug!(?latency, "tap latency"),
+            EngineEvent::TagCounter { card, count } => {
+                tracing::debug!(%card, count, "tag read counter");
+            }
+            EngineEvent::Idle | EngineEvent::Stopped => {}
+        }
+    });
+    events.on({
+        let status = status.clone();
+        let displays = displays.clone();
+        let controller = controller.clone();
+        move |published| {
+            status.record_event(&published.event);
+            let refresh = match published.event {
+                EngineEvent::Action(_) => true,
+                EngineEvent::Idle => status.idle_events() % 100 == 0,
+                _ => false,
+            };
+            if refresh {
+                // The cards may have reloaded from a file watcher, the web
+                // UI, or the control socket since the last refresh. A busy
+                // controller is caught on the next one.
+                if let Ok(guard) = controller.try_lock() {
+                    status.record_library(guard.library().stats());
+                }
+                update_displays(&displays, &status.snapshot());
+            }
+        }
+    });
+
+    if let Some(history) = history {
+        let mut plays = PlayTracker::new();
+        events.on(move |published| {
+            let now = Instant::now();
+            let entry = match &published.event {
+                EngineEvent::Action(action) => plays.record(action, now),
+                EngineEvent::TagCounter { card, count } => Some(HistoryEvent::TagCounter {
+                    card: card.to_hex_lowercase(),
+                    count: *count,
+                }),
+                EngineEvent::Stopped => plays.finish(now),
+                _ => None,
+            };
+            if let Some(entry) = entry
+                && let Err(err) = history.append(&entry)
+            {
+                tracing::warn!(%err, "failed to write to the history log");
+            }
+        });
+    }
+
+    #[cfg(feature = "rotary-knob")]
+    spawn_volume_knob(knob, controller.clone());
+
+    #[cfg(feature = "ws2812-led")]
+    let led = open_ws2812(ws2812);
+    #[cfg(feature = "ws2812-led")]
+    events.on({
+        let led = led.clone();
+        let controller = controller.clone();
+        move |published| {
+            if matches!(published.event, EngineEvent::Action(_) | EngineEvent::Idle) {
+                sync_led(&led, &controller);
+            }
+        }
+    });
+
+    #[cfg(feature = "systemd")]
+    let systemd = SystemdNotifier::from_env().map(Arc::new);
+    #[cfg(feature = "systemd")]
+    if let Some(systemd) = &systemd {
+        let heartbeat = systemd.clone();
+        events.on(move |published| {
+            if matches!(published.event, EngineEvent::Action(_) | EngineEvent::Idle) {
+                heartbeat.heartbeat(Instant::now());
+            }
+        });
+        systemd.ready();
+    }
+
+    if let Some(settings) = &mqtt {
+        start_mqtt(settings, controller.clone(), &events);
+    }
+
+    let shutdown = ShutdownToken::from_signals();
+    run_until_shutdown(
+        controller.clone(),
+        &mut reader,
+        buttons.as_mut(),
+        &shutdown,
+        &events,
+        poll_duration,
+    )?;
+
+    #[cfg(feature = "systemd")]
+    if let Some(systemd) = &systemd {
+        systemd.stopping();
+    }
+
+    match controller.lock() {
+        Ok(mut guard) => {
+            if let Err(err) = guard.shutdown() {
+                tracing::warn!(?err, "failed to play goodnight sound");
+            }
+        }
+        Err(err) => {
+            tracing::warn!(?err, "controller mutex poisoned during shutdown");
+        }
+    }
+
+    #[cfg(feature = "ws2812-led")]
+    if let Some(handle) = &led
+        && let Ok(mut hint) = handle.lock()
+        && let Err(err) = hint.show(None)
+    {
+        tracing::warn!(?err, "failed to switch off WS2812 strip");
+    }
+
+    for handle in &displays {
+        match handle.lock() {
+            Ok(mut device) => {
+                if let Err(err) = device.shutdown() {
+                    tracing::warn!(?err, "failed to put display to sleep");
+                }
+            }
+            Err(err) => {
+                tracing::warn!(?err, "display mutex poisoned during shutdown");
+            }
+        }
+    }
+
+    if let Some(path) = control_socket {
+        let _ = std::fs::remove_file(path);
+    }
+
+    if shutdown.is_requested() {
+        println!("Stop signal received. Exiting.");
+    } else {
+        println!("Reader requested shutdown. Exiting.");
+    }
+    tracing::info!(snapshot = ?status.snapshot(), "final status");
+    let _ = std::io::Write::flush(&mut std::io::stdout());
+
+    Ok(())
+}
+
+/// Listens on the control socket so `ctl` and `manual trigger` drive this
+/// player instead of opening the sound card themselves. Returns the path to
+/// remove on exit, or `None` when the socket could not be bound.
+#[cfg(unix)]
+fn spawn_control_server(
+    path: Option<PathBuf>,
+    controller: Arc<Mutex<MusicBoxController<CachingPlayer<LoudnessPlayer<PlayerBackend>>>>>,
+    config_path: PathBuf,
+    events: EventBus,
+) -> Option<PathBuf> {
+    let path = path.unwrap_or_else(control::default_socket_path);
+    let server = match ControlServer::bind(&path) {
+        Ok(server) => server,
+        Err(err) => {
+            eprintln!("Control socket disabled: {err}");
+            return None;
+        }
+    };
+    let state = ControlState {
+        controller,
+        config_path,
+        events,
+    };
+    std::thread::spawn(move || {
+        if let Err(err) = server.serve(state) {
+            tracing::error!(%err, "control socket stopped");
+        }
+    });
+    println!("Listening for commands on {}", path.display());
+    Some(path)
+}
+
+#[cfg(not(unix))]
+fn spawn_control_server(
+    _path: Option<PathBuf>,
+    _controller: Arc<Mutex<MusicBoxController<CachingPlayer<LoudnessPlayer<PlayerBackend>>>>>,
+    _config_path: PathBuf,
+    _events: EventBus,
+) -> Option<PathBuf> {
+    None
+}
+
+/// Connects to the `[mqtt]` broker and keeps it posted on what the run
+/// loop does.
+#[cfg(feature = "mqtt")]
+fn start_mqtt(
+    settings: &MqttSettings,
+    controller: Arc<Mutex<MusicBoxController<CachingPlayer<LoudnessPlayer<PlayerBackend>>>>>,
+    events: &EventBus,
+) {
+    match musicbox::mqtt::broker::MqttLink::connect(settings, controller, events.clone()) {
+        Ok(link) => {
+            println!(
+                "Publishing status to MQTT broker {}:{} under {}/.",
+                settings.host, settings.port, settings.prefix
+            );
+            events.on(move |published| link.on_event(published));
+        }
+        Err(err) => eprintln!("MQTT disabled: {err}"),
+    }
+}
+
+#[cfg(not(feature = "mqtt"))]
+fn start_mqtt(
+    _settings: &MqttSettings,
+    _controller: Arc<Mutex<MusicBoxController<CachingPlayer<LoudnessPlayer<PlayerBackend>>>>>,
+    _events: &EventBus,
+) {
+    eprintln!("Ignoring the [mqtt] table; rebuild with the `mqtt` feature to talk to the broker.");
+}
+
+/// Opens the buttons from the `[buttons]` table. Without one, or if the
+/// GPIO lines cannot be opened, the box runs on cards alone.
+fn open_buttons(config: Option<&ButtonConfig>) -> Box<dyn ButtonSource> {
+    let Some(config) = config else {
+        return Box::new(NoButtons);
+    };
+    #[cfg(feature = "gpio-buttons")]
+    match GpioButtons::open(config) {
+        Ok(buttons) => {
+            println!("{} button(s) connected.", config.pins.len());
+            return Box::new(buttons);
+        }
+        Err(err) => eprintln!("Failed to open buttons: {err}"),
+    }
+    #[cfg(not(feature = "gpio-buttons"))]
+    eprintln!(
+        "Ignoring {} configured button(s); rebuild with the `gpio-buttons` feature to use them.",
+        config.pins.len()
+    );
+    Box::new(NoButtons)
+}
+
+fn open_recorder(settings: &MemoSettings) -> Option<Box<dyn Recorder>> {
+    #[cfg(feature = "audio-record")]
+    {
+        println!("Voice memos enabled; tap card {} to record.", settings.card);
+        Some(Box::new(musicbox::memo::cpal_input::CpalRecorder::new()))
+    }
+    #[cfg(not(feature = "audio-record"))]
+    {
+        eprintln!(
+            "Ignoring record card {}; rebuild with the `audio-record` feature to record voice memos.",
+            settings.card
+        );
+        None
+    }
+}
+
+/// Maps `card` to its new voice memo in the config file, so the memo keeps
+/// playing after a restart. Re-recorded cards already point at the file.
+fn save_memo_mapping(config_path: &Path, music_dir: &Path, card: &CardUid, track: &Track) {
+    let path = track.path();
+    let entry = path.strip_prefix(music_dir).unwrap_or(path);
+    let Some(entry) = entry.to_str() else {
+        eprintln!("Memo path {} is not valid UTF-8", path.display());
+        return;
+    };
+    match config::add_card_to_config(config_path, card, entry, None) {
+        Ok(()) => println!("Card {card} now plays its voice memo."),
+        Err(ConfigEditError::Duplicate(_)) => {}
+        Err(err) => eprintln!("Failed to save voice memo for card {card}: {err}"),
+    }
+}
+
+#[cfg(feature = "ws2812-led")]
+type SharedLed = Arc<Mutex<LedHint>>;
+
+#[cfg(feature = "ws2812-led")]
+fn open_ws2812(args: &Ws2812Args) -> Option<SharedLed> {
+    let path = args.led_spi_path.as_ref()?;
+    match Ws2812Led::open(path, args.count) {
+        Ok(strip) => {
+            println!("WS2812 strip connected; card colors enabled.");
+            Some(Arc::new(Mutex::new(LedHint::new(Box::new(strip)))))
+        }
+        Err(err) => {
+            eprintln!("Failed to open WS2812 strip on {}: {err}", path.display());
+            None
+        }
+    }
+}
+
+/// Shows the playing card's color hint, or switches the strip off.
+/// Called on every action and idle poll so tracks started over HTTP or
+/// finishing on their own are reflected too.
+#[cfg(feature = "ws2812-led")]
+fn sync_led<P: AudioPlayer>(led: &Option<SharedLed>, controller: &Mutex<MusicBoxController<P>>) {
+    let Some(led) = led else {
+        return;
+    };
+    let color = match controller.lock() {
+        Ok(guard) => guard.active_color(),
+        Err(_) => return,
+    };
+    if let Ok(mut hint) = led.lock()
+        && let Err(err) = hint.show(color)
+    {
+        tracing::warn!(?err, "WS2812 update failed");
+    }
+}
+
+/// How often the volume knob's pins are sampled; fast enough to catch every
+/// state of a quickly turned encoder.
+#[cfg(feature = "rotary-knob")]
+const KNOB_POLL_INTERVAL: Duration = Duration::from_millis(1);
+
+/// Adjusts the volume from a rotary encoder on a background thread.
+#[cfg(feature = "rotary-knob")]
+fn spawn_volume_knob<P: AudioPlayer + Send + 'static>(
+    args: &RotaryKnobArgs,
+    controller: Arc<Mutex<MusicBoxController<P>>>,
+) {
+    let (Some(pin_a), Some(pin_b)) = (args.knob_pin_a, args.knob_pin_b) else {
+        return;
+    };
+    if controller
+        .lock()
+        .is_ok_and(|guard| !guard.player_capabilities().volume)
+    {
+        eprintln!("The audio backend has no volume control; ignoring the volume knob.");
+        return;
+    }
+    let mut knob = match RotaryKnob::open(&args.knob_gpio_chip_path, pin_a, pin_b) {
+        Ok(knob) => knob,
+        Err(err) => {
+            eprintln!("Failed to open volume knob: {err}");
+            return;
+        }
+    };
+    println!("Volume knob connected.");
+    let step = args.step;
+    std::thread::spawn(move || {
+        loop {
+            match knob.poll() {
+                Ok(0) => {}
+                Ok(detent) => {
+                    let Ok(mut guard) = controller.lock() else {
+                        return;
+                    };
+                    match guard.adjust_volume(f32::from(detent) * step) {
+                        Ok(volume) => tracing::debug!(volume, "volume knob turned"),
+                        Err(err) => tracing::warn!(%err, "failed to change volume"),
+                    }
+                }
+                Err(err) => {
+                    tracing::warn!(%err, "volume knob stopped responding");
+                    return;
+                }
+            }
+            std::thread::sleep(KNOB_POLL_INTERVAL);
+        }
+    });
+}
+
+/// Records panics to the history log and display, then exits with
+/// [`ExitCode::Panic`] so the service manager restarts a clean process.
+/// The code differs from ordinary errors and from Rust's default panic
+/// code (101), so restart loops are easy to spot.
+fn install_panic_hook(history: Option<HistoryLog>, displays: Vec<SharedStatusDisplay>) {
+    let default_hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        default_hook(info);
+        if display::panic_is_contained() {
+            return;
+        }
+
+        let message = info
+            .payload()
+            .downcast_ref::<&str>()
+            .map(|msg| msg.to_string())
+            .or_else(|| info.payload().downcast_ref::<String>().cloned())
+            .unwrap_or_else(|| "unknown panic".to_string());
+        let location = info.location().map(|location| location.to_string());
+        tracing::error!(%message, ?location, "musicbox panicked; exiting");
+
+        if let Some(history) = &history
+            && let Err(err) = history.append(&HistoryEvent::Panic {
+                message: message.clone(),
+                location,
+            })
+        {
+            eprintln!("failed to record panic: {err}");
+        }
+
+        // try_lock: the panicking thread may already hold a display.
+        for handle in &displays {
+            if let Ok(mut device) = handle.try_lock() {
+                let _ = device.show_error(&message);
+            }
+        }
+
+        ExitCode::Panic.exit();
+    }));
+}
 
+/// Shows `snapshot` on every display.
+fn update_displays(displays: &[SharedStatusDisplay], snapshot: &StatusSnapshot) {
+    for handle in displays {
+        match handle.lock() {
+            Ok(mut device) => {
+                if let Err(err) = device.update(snapshot) {
+                    tracing::warn!(?err, "display update failed");
+                }
+            }
+            Err(err) => {
+                tracing::warn!(?err, "display mutex poisoned");
+            }
+        }
+    }
+}
+
+/// Notifies the alert webhook when the reader error policy gives up on the reader.
+fn send_reader_alert(url: &WebhookUrl, health: &ReaderHealth) {
+    if !matches!(
+        health,
+        ReaderHealth::FellBack { .. } | ReaderHealth::Failed { .. }
+    ) {
+        return;
+    }
+    let stamp = EventStamp::next();
+    let payload = serde_json::json!({
+        "event": "reader_error",
+        "state": health.label(),
+        "error": health.last_error(),
+        "id": stamp.id,
+        "sequence": stamp.sequence,
+        "timestamp_ms": stamp.timestamp_ms,
+    });
+    if let Err(err) = webhook::post_json(url, &payload) {
+        tracing::warn!(%err, "failed to deliver reader alert webhook");
+    }
+}
+
+/// Handles the `tag` subcommand.
+fn handle_tag_command(
+    command: TagCommand,
+    inherited_config: Option<PathBuf>,
+    default_reader: ReaderKind,
+    default_poll_ms: Option<u64>,
+    control_socket: Option<&Path>,
+) -> Result<(), TagError> {
     match command {
-        Some(Command::Tag(tag_command)) => {
-            handle_tag_command(tag_command, config.clone(), reader, poll_interval_ms)?;
+        TagCommand::Add(args) => handle_tag_add(
+            args,
+            inherited_config,
+            default_reader,
+            default_poll_ms,
+            control_socket,
+        ),
+        TagCommand::Batch(args) => {
+            handle_tag_batch(args, inherited_config, default_reader, default_poll_ms)
         }
-        Some(Command::Manual(manual_command)) => {
-            handle_manual_command(manual_command, silent)?;
+        TagCommand::List(args) => handle_tag_list(args, inherited_config),
+        TagCommand::Update(args) => handle_tag_update(args, inherited_config),
+        TagCommand::Remove(args) => handle_tag_remove(args, inherited_config),
+    }
+}
+
+/// Handles the `tag list` subcommand.
+fn handle_tag_list(args: TagListArgs, inherited_config: Option<PathBuf>) -> Result<(), TagError> {
+    let config_path = args
+        .config
+        .or(inherited_config)
+        .ok_or(TagError::MissingConfig)?;
+    let entries = config::list_cards(&config_path)?;
+    if json_output() {
+        emit(serde_json::json!({ "config": config_path, "cards": entries }));
+        return Ok(());
+    }
+    if args.json {
+        let json = serde_json::to_string_pretty(&entries).expect("card entries serialize");
+        println!("{json}");
+        return Ok(());
+    }
+    for entry in entries {
+        let card = match &entry.name {
+            Some(name) => format!("{} ({name})", entry.uid),
+            None => entry.uid.clone(),
+        };
+        match &entry.action {
+            Some(action) => say!("{card} → action {action}"),
+            None => say!("{card} → {}", entry.tracks.join(", ")),
         }
-        Some(Command::Add(args)) => {
-            handle_tag_add(args, config.clone(), reader, poll_interval_ms)?;
+    }
+    Ok(())
+}
+
+/// Handles the `tag remove` subcommand. The card may be given by name.
+fn handle_tag_remove(
+    args: TagRemoveArgs,
+    inherited_config: Option<PathBuf>,
+) -> Result<(), TagError> {
+    let config_path = args
+        .config
+        .or(inherited_config)
+        .ok_or(TagError::MissingConfig)?;
+    let uid = config_card(&config_path, &args.card)?;
+    config::remove_card_from_config(&config_path, &uid)?;
+    say!("Removed card {uid} from {}", config_path.display());
+    emit(serde_json::json!({
+        "action": "removed",
+        "card": uid.to_string(),
+        "config": config_path,
+    }));
+    Ok(())
+}
+
+/// Handles the `tag update` subcommand.
+fn handle_tag_update(
+    args: TagUpdateArgs,
+    inherited_config: Option<PathBuf>,
+) -> Result<(), TagError> {
+    let config_path = args
+        .config
+        .or(inherited_config)
+        .ok_or(TagError::MissingConfig)?;
+    let uid = config_card(&config_path, &args.card)?;
+    let track = path_to_string(&args.track)?;
+    match config::update_card_in_config(&config_path, &uid, &track, args.force) {
+        Err(ConfigEditError::NotSingleTrack(uid)) => return Err(TagError::NeedsForce(uid)),
+        result => result?,
+    }
+    say!("Mapped card {uid} to {track} in {}", config_path.display());
+    emit(serde_json::json!({
+        "action": "updated",
+        "card": uid.to_string(),
+        "track": track,
+        "config": config_path,
+    }));
+    Ok(())
+}
+
+/// Finds a card of the config at `config_path` by name, or else parses
+/// `wanted` as a hex UID, as [`musicbox::controller::Library::resolve_card`]
+/// does for the loaded cards.
+fn config_card(config_path: &Path, wanted: &str) -> Result<CardUid, TagError> {
+    let names: Vec<(CardUid, String)> = config::list_cards(config_path)?
+        .into_iter()
+        .filter_map(|entry| Some((CardUid::parse(&entry.uid).ok()?, entry.name?)))
+        .collect();
+    Ok(CardUid::resolve(
+        wanted,
+        names.iter().map(|(uid, name)| (uid, name.as_str())),
+    )?)
+}
+
+/// Handles the `tag add` subcommand.
+fn handle_tag_add(
+    args: TagAddArgs,
+    inherited_config: Option<PathBuf>,
+    default_reader: ReaderKind,
+    default_poll_ms: Option<u64>,
+    control_socket: Option<&Path>,
+) -> Result<(), TagError> {
+    emit(add_card(
+        args,
+        inherited_config,
+        default_reader,
+        default_poll_ms,
+        control_socket,
+    )?);
+    Ok(())
+}
+
+/// Maps a card as `tag add` does, returning what was mapped for
+/// `--output json`. `--learned` asks the player on `control_socket`.
+fn add_card(
+    args: TagAddArgs,
+    inherited_config: Option<PathBuf>,
+    default_reader: ReaderKind,
+    default_poll_ms: Option<u64>,
+    control_socket: Option<&Path>,
+) -> Result<serde_json::Value, TagError> {
+    let TagAddArgs {
+        config,
+        track,
+        card,
+        learned,
+        reader,
+        poll_interval_ms,
+        skip_tag_write,
+        synthetic_uid,
+        expires,
+    } = args;
+
+    let config_path = config.or(inherited_config).ok_or(TagError::MissingConfig)?;
+    let reader_setup = load_config(&config_path)
+        .map(|config| ReaderSetup::from_config(&config))
+        .unwrap_or_default();
+    let reader_kind = reader.unwrap_or(default_reader);
+    let poll = poll_interval_ms
+        .or(default_poll_ms)
+        .map(Duration::from_millis);
+
+    let track_str = path_to_string(&track)?;
+
+    let mut effective_reader_kind = reader_kind;
+    let mut auto_generated_uid = false;
+
+    let uid = if let Some(card_hex) = card {
+        CardUid::parse(&card_hex)?
+    } else if learned {
+        let uid = learned_card(control_socket)?;
+        say!("Using card {uid}, which the running player noted last.");
+        uid
+    } else if matches!(reader_kind, ReaderKind::Noop) {
+        auto_generated_uid = true;
+        CardUid::synthetic(synthetic_uid.into())
+    } else {
+        let selection = select_reader(reader_kind, poll, &reader_setup)?;
+        effective_reader_kind = selection.kind();
+        if matches!(effective_reader_kind, ReaderKind::Noop) {
+            auto_generated_uid = true;
+            CardUid::synthetic(synthetic_uid.into())
+        } else {
+            acquire_card_uid(selection.into_reader().as_mut())?
         }
-        None => {
-            let config_path = config.ok_or(RunError::MissingConfig)?;
-            #[cfg(feature = "waveshare-display")]
-            let waveshare_config = waveshare_config_from_args(&waveshare);
-            run_player_main(
-                config_path,
-                poll_interval_ms,
-                reader,
-                silent,
-                #[cfg(feature = "waveshare-display")]
-                waveshare_config,
-                #[cfg(feature = "debug-http")]
-                debug_http,
-            )?;
+    };
+
+    if auto_generated_uid {
+        say!(
+            "Generated synthetic card UID {} because the selected reader cannot scan cards.",
+            uid
+        );
+    }
+
+    let expires = expires.map(|lifetime| SystemTime::now() + lifetime);
+    config::add_card_to_config(&config_path, &uid, &track_str, expires)?;
+
+    say!(
+        "Mapped card {} to {} in {}",
+        uid,
+        track_str,
+        config_path.display()
+    );
+    if let Some(expires) = expires {
+        say!("Card {uid} expires at {}.", expiry::to_datetime(expires));
+    }
+
+    let tag_written = if skip_tag_write {
+        say!("Skipping NFC tag write (per --skip-tag-write).");
+        false
+    } else {
+        attempt_tag_write(
+            effective_reader_kind,
+            reader_setup.poll_interval(effective_reader_kind, poll),
+            &uid,
+            &track_str,
+        )
+        .unwrap_or_else(|err| {
+            tracing::warn!(?err, "failed to write NFC tag; config still updated");
+            false
+        })
+    };
+
+    Ok(serde_json::json!({
+        "action": "mapped",
+        "card": uid.to_string(),
+        "track": track_str,
+        "config": config_path,
+        "synthetic": auto_generated_uid,
+        "expires": expires.map(|expires| expiry::to_datetime(expires).to_string()),
+        "tag_written": tag_written,
+    }))
+}
+
+/// Handles the `tag batch` subcommand: prompts for a card for each audio
+/// file in the folder that no card plays yet, and maps it as soon as it is
+/// tapped, so an interrupted session keeps the cards done so far.
+fn handle_tag_batch(
+    args: TagBatchArgs,
+    inherited_config: Option<PathBuf>,
+    default_reader: ReaderKind,
+    default_poll_ms: Option<u64>,
+) -> Result<(), TagError> {
+    let config_path = args
+        .config
+        .or(inherited_config)
+        .ok_or(TagError::MissingConfig)?;
+    if !args.dir.is_dir() {
+        return Err(TagError::NotADirectory(args.dir));
+    }
+    let loaded = load_config(&config_path).ok();
+    let music_dir = loaded
+        .as_ref()
+        .and_then(|config| config.music_dir().canonicalize().ok());
+    let reader_setup = loaded
+        .as_ref()
+        .map(ReaderSetup::from_config)
+        .unwrap_or_default();
+    let poll = args
+        .poll_interval_ms
+        .or(default_poll_ms)
+        .map(Duration::from_millis);
+
+    let mut mapped_tracks = HashSet::new();
+    let mut mapped_cards = HashSet::new();
+    if config_path.exists() {
+        for entry in config::list_cards(&config_path)? {
+            mapped_tracks.extend(entry.tracks);
+            mapped_cards.insert(CardUid::parse(&entry.uid)?);
         }
     }
+    let pending = Track::new(args.dir.clone())
+        .expand()
+        .into_iter()
+        .map(|track| {
+            let relative = music_dir.as_deref().and_then(|music_dir| {
+                let path = track.path().canonicalize().ok()?;
+                Some(path.strip_prefix(music_dir).ok()?.to_path_buf())
+            });
+            path_to_string(relative.as_deref().unwrap_or(track.path()))
+        })
+        .filter(|entry| {
+            !entry
+                .as_ref()
+                .is_ok_and(|entry| mapped_tracks.contains(entry))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    if pending.is_empty() {
+        say!(
+            "Every audio file in {} already has a card.",
+            args.dir.display()
+        );
+        emit(serde_json::json!({
+            "action": "mapped",
+            "config": config_path,
+            "mapped": [],
+        }));
+        return Ok(());
+    }
 
+    let selection = select_reader(args.reader.unwrap_or(default_reader), poll, &reader_setup)?;
+    let reader_kind = selection.kind();
+    let mut reader = (!matches!(reader_kind, ReaderKind::Noop)).then(|| selection.into_reader());
+    if reader.is_none() {
+        say!("The selected reader cannot scan cards; generating synthetic UIDs instead.");
+    }
+    let mut last_mapped = None;
+    let mut mapped = Vec::new();
+    for (number, track) in pending.iter().enumerate() {
+        say!(
+            "[{}/{}] Place a card for {track}",
+            number + 1,
+            pending.len()
+        );
+        let uid = loop {
+            let uid = match reader.as_mut() {
+                Some(reader) => acquire_card_uid(reader.as_mut())?,
+                None => CardUid::synthetic(args.synthetic_uid.into()),
+            };
+            if !mapped_cards.contains(&uid) {
+                break uid;
+            }
+            // The card just mapped is often still on the reader.
+            if last_mapped.as_ref() != Some(&uid) {
+                say!("Card {uid} already plays something; place another card.");
+            }
+        };
+        config::add_card_to_config(&config_path, &uid, track, None)?;
+        say!("Mapped card {uid} to {track}");
+        if args.write_tags
+            && let Err(err) = attempt_tag_write(
+                reader_kind,
+                reader_setup.poll_interval(reader_kind, poll),
+                &uid,
+                track,
+            )
+        {
+            tracing::warn!(?err, "failed to write NFC tag; config still updated");
+        }
+        mapped.push(serde_json::json!({ "card": uid.to_string(), "track": track }));
+        mapped_cards.insert(uid.clone());
+        last_mapped = Some(uid);
+    }
+    say!(
+        "Mapped {} cards in {}",
+        pending.len(),
+        config_path.display()
+    );
+    emit(serde_json::json!({
+        "action": "mapped",
+        "config": config_path,
+        "mapped": mapped,
+    }));
     Ok(())
 }
 
-/// The main entry point for running the music player.
-fn run_player_main(
-    config_path: PathBuf,
-    poll_interval_ms: u64,
+/// Converts a `Path` to a `String`.
+fn path_to_string(path: &Path) -> Result<String, TagError> {
+    path.to_str()
+        .map(|s| s.to_owned())
+        .ok_or_else(|| TagError::InvalidTrackPath(path.to_path_buf()))
+}
+
+/// Waits for a card to be presented to the reader and returns its UID.
+fn acquire_card_uid(reader: &mut dyn NfcReader) -> Result<CardUid, TagError> {
+    loop {
+        match reader.next_event()? {
+            ReaderEvent::CardPresent { uid, .. } => return Ok(uid),
+            ReaderEvent::CardRemoved { .. } | ReaderEvent::Idle => continue,
+            ReaderEvent::Shutdown => return Err(TagError::ReaderShutdown),
+        }
+    }
+}
+
+/// Writes the track to the NFC tag as an NDEF text record, so other NFC
+/// tools can tell what the card plays. Returns whether a tag was written.
+fn attempt_tag_write(
     reader_kind: ReaderKind,
-    silent: bool,
-    #[cfg(feature = "waveshare-display")] waveshare_config: Option<WaveshareConfig>,
-    #[cfg(feature = "debug-http")] debug_http: Option<SocketAddr>,
+    poll: Duration,
+    uid: &CardUid,
+    track: &str,
+) -> Result<bool, TagError> {
+    if !matches!(reader_kind, ReaderKind::Pcsc) {
+        say!("Tag writing needs a PC/SC reader; config has still been updated.");
+        return Ok(false);
+    }
+    say!("Hold card {uid} on the reader to write its tag...");
+    write_ndef_tag(poll, uid, track)?;
+    say!("Wrote {track} to NFC tag {uid}.");
+    Ok(true)
+}
+
+#[cfg(feature = "nfc-pcsc")]
+fn write_ndef_tag(poll: Duration, uid: &CardUid, track: &str) -> Result<(), ReaderError> {
+    musicbox::reader::pcsc_backend::PcscTagWriter::new(poll)?.write_text(uid, track)
+}
+
+#[cfg(not(feature = "nfc-pcsc"))]
+fn write_ndef_tag(_poll: Duration, _uid: &CardUid, _track: &str) -> Result<(), ReaderError> {
+    Err(ReaderError::backend(
+        "tag writing unavailable without the `nfc-pcsc` feature",
+    ))
+}
+
+/// Handles the `config` subcommand.
+fn handle_config_command(
+    command: ConfigCommand,
+    inherited_config: Option<PathBuf>,
+) -> Result<(), RunError> {
+    match command {
+        ConfigCommand::Orphans(args) => handle_config_orphans(args, inherited_config),
+        ConfigCommand::PruneExpired(args) => handle_config_prune(args, inherited_config),
+        ConfigCommand::Validate(args) => handle_config_validate(args, inherited_config),
+    }
+}
+
+/// Handles the `config validate` subcommand. A config that fails to load
+/// is reported as a single error, so `--json` output always parses.
+fn handle_config_validate(
+    args: ConfigValidateArgs,
+    inherited_config: Option<PathBuf>,
 ) -> Result<(), RunError> {
-    let player = if silent {
-        PlayerBackend::Noop
+    let config_path = args
+        .config
+        .or(inherited_config)
+        .ok_or(RunError::MissingConfig)?;
+    let report = match load_config(&config_path) {
+        Ok(config) => config.validate(),
+        Err(err) => ValidationReport {
+            diagnostics: vec![Diagnostic {
+                severity: Severity::Error,
+                card: None,
+                path: Some(config_path.clone()),
+                message: err.to_string(),
+            }],
+        },
+    };
+
+    let problems = report.errors() + if args.strict { report.warnings() } else { 0 };
+    if json_output() {
+        let mut result = serde_json::to_value(&report).expect("reports serialize");
+        result["ok"] = (problems == 0).into();
+        result["config"] = serde_json::json!(config_path);
+        result["errors"] = report.errors().into();
+        result["warnings"] = report.warnings().into();
+        emit(result);
+    } else if args.json {
+        println!(
+            "{}",
+            serde_json::to_string_pretty(&report).expect("reports serialize")
+        );
     } else {
-        match RodioPlayer::new() {
-            Ok(player) => PlayerBackend::Rodio(player),
-            Err(err) => {
-                eprintln!("Audio backend unavailable ({err}). Falling back to silent playback.");
-                PlayerBackend::Noop
+        for diagnostic in &report.diagnostics {
+            let severity = match diagnostic.severity {
+                Severity::Error => "error",
+                Severity::Warning => "warning",
+            };
+            let mut line = format!("{severity}:");
+            if let Some(card) = &diagnostic.card {
+                line.push_str(&format!(" card {card}:"));
+            }
+            if let Some(path) = &diagnostic.path {
+                line.push_str(&format!(" {}:", path.display()));
             }
+            say!("{line} {}", diagnostic.message);
         }
-    };
+        say!(
+            "{} error(s), {} warning(s) in {}",
+            report.errors(),
+            report.warnings(),
+            config_path.display()
+        );
+    }
 
-    let controller = Arc::new(Mutex::new(controller_from_config_path(
-        &config_path,
-        player,
-    )?));
-    let poll_duration = Duration::from_millis(poll_interval_ms);
-    let mut reader = select_reader(reader_kind, poll_duration)?.into_reader();
+    if problems > 0 {
+        return Err(RunError::InvalidConfig(problems));
+    }
+    Ok(())
+}
 
-    let status = SharedStatus::default();
-    let action_status_state = status.clone();
-    let idle_status_state = status.clone();
+/// Handles the `config prune-expired` subcommand.
+fn handle_config_prune(
+    args: ConfigPruneArgs,
+    inherited_config: Option<PathBuf>,
+) -> Result<(), RunError> {
+    let config_path = args
+        .config
+        .or(inherited_config)
+        .ok_or(RunError::MissingConfig)?;
+    let removed = config::remove_expired_cards(&config_path, SystemTime::now())?;
+    for uid in &removed {
+        say!("Removed expired card {uid}");
+    }
+    say!("{} expired card(s) removed.", removed.len());
+    let removed: Vec<String> = removed.iter().map(ToString::to_string).collect();
+    emit(serde_json::json!({ "action": "removed", "config": config_path, "cards": removed }));
+    Ok(())
+}
 
-    #[cfg(feature = "debug-http")]
-    if let Some(addr) = debug_http {
-        let server_status = status.clone();
-        let server_controller = controller.clone();
-        let server_config = config_path.clone();
-        std::thread::spawn(move || {
-            let state = musicbox::web::DebugState {
-                status: server_status,
-                controller: server_controller,
-                config_path: server_config,
-            };
-            if let Err(err) = musicbox::web::serve(state, addr) {
-                tracing::error!(?err, "debug server terminated");
-            }
-        });
+/// Handles the `config orphans` subcommand.
+fn handle_config_orphans(
+    args: ConfigOrphansArgs,
+    inherited_config: Option<PathBuf>,
+) -> Result<(), RunError> {
+    let config_path = args
+        .config
+        .or(inherited_config)
+        .ok_or(RunError::MissingConfig)?;
+    let orphans = load_config(&config_path)?.orphaned_tracks()?;
+
+    if orphans.is_empty() {
+        say!("Every audio file under music_dir is mapped to a card.");
+        emit(serde_json::json!({ "orphans": [] }));
+        return Ok(());
     }
 
-    #[cfg(feature = "waveshare-display")]
-    let display: Option<SharedStatusDisplay> =
-        waveshare_config.and_then(|config| match WaveshareDisplay::new(config) {
-            Ok(device) => {
-                println!("Waveshare display connected; status updates enabled.");
-                Some(Arc::new(Mutex::new(
-                    Box::new(device) as Box<dyn display::StatusDisplay>
-                )))
-            }
-            Err(err) => {
-                eprintln!("Failed to initialize Waveshare display: {err}");
-                tracing::warn!(?err, "waveshare display initialization failed");
-                None
-            }
-        });
+    for path in &orphans {
+        let display = path.display().to_string();
+        if args.stubs {
+            say!("{}", config::placeholder_entry(&display));
+        } else {
+            say!("{display}");
+        }
+    }
+    eprintln!("{} unmapped audio file(s).", orphans.len());
+    emit(serde_json::json!({ "orphans": orphans }));
+    Ok(())
+}
 
-    #[cfg(feature = "waveshare-display")]
-    if let Some(handle) = &display {
-        match handle.lock() {
-            Ok(mut device) => {
-                if let Err(err) = device.update(&status.snapshot()) {
-                    tracing::warn!(?err, "initial waveshare display update failed");
+/// Handles the `library scan` subcommand: adds a placeholder `[cards]`
+/// entry for each new audio file, or prints them as a diff with
+/// `--dry-run`.
+fn handle_library_scan(
+    args: LibraryScanArgs,
+    inherited_config: Option<PathBuf>,
+) -> Result<(), RunError> {
+    let config_path = args
+        .config
+        .or(inherited_config)
+        .ok_or(RunError::MissingConfig)?;
+    let orphans = load_config(&config_path)?.orphaned_tracks()?;
+    let placeholders: HashSet<String> = config::list_placeholders(&config_path)?
+        .into_iter()
+        .collect();
+    let new: Vec<String> = orphans
+        .iter()
+        .map(|path| path.display().to_string())
+        .filter(|track| !placeholders.contains(track))
+        .collect();
+    if new.is_empty() {
+        say!(
+            "No new audio files; {} unmapped file(s) already have placeholders.",
+            orphans.len()
+        );
+        emit(serde_json::json!({ "dry_run": args.dry_run, "added": [] }));
+        return Ok(());
+    }
+
+    let added = if args.dry_run {
+        new
+    } else {
+        config::add_placeholders_to_config(&config_path, &new)?
+    };
+    for track in &added {
+        say!("+{}", config::placeholder_entry(track));
+    }
+    if args.dry_run {
+        eprintln!("{} new audio file(s); nothing written.", added.len());
+    } else {
+        eprintln!(
+            "Added {} placeholder(s) under [cards]. Fill in each card's UID and uncomment the line, or map them with `musicbox tag batch`.",
+            added.len()
+        );
+    }
+    emit(serde_json::json!({ "dry_run": args.dry_run, "added": added }));
+    Ok(())
+}
+
+/// Handles the `library analyze` subcommand: measures each track under
+/// `music_dir` that has no up-to-date loudness sidecar, or every track with
+/// `--force`.
+fn handle_library_analyze(
+    args: LibraryAnalyzeArgs,
+    inherited_config: Option<PathBuf>,
+) -> Result<(), RunError> {
+    let config_path = args
+        .config
+        .or(inherited_config)
+        .ok_or(RunError::MissingConfig)?;
+    let config = load_config(&config_path)?;
+    let settings = config.loudness().unwrap_or_default();
+    let music_dir = config.music_dir();
+    let tracks = loudness::tracks_under(music_dir).map_err(|source| RunError::ListTracks {
+        path: music_dir.to_path_buf(),
+        source,
+    })?;
+
+    let mut analyzed = Vec::new();
+    let mut skipped = 0;
+    let mut failed = Vec::new();
+    let progress = Progress::bar();
+    for (index, track) in tracks.iter().enumerate() {
+        let shown = track.strip_prefix(music_dir).unwrap_or(track).display();
+        progress.working_on(index, tracks.len(), &shown);
+        if !args.force && loudness::cached(track).is_some() {
+            skipped += 1;
+            continue;
+        }
+        match loudness::analyze(track) {
+            Ok(analysis) => {
+                let gain_db = 20.0 * settings.gain_for(analysis.loudness_lufs).factor().log10();
+                match analysis.loudness_lufs {
+                    Some(lufs) => {
+                        progress.item(format!("{shown}: {lufs:.1} LUFS, {gain_db:+.1} dB"))
+                    }
+                    None => progress.item(format!("{shown}: silent, played as recorded")),
                 }
+                analyzed.push(serde_json::json!({
+                    "track": shown.to_string(),
+                    "loudness_lufs": analysis.loudness_lufs,
+                    "gain_db": gain_db,
+                }));
             }
             Err(err) => {
-                tracing::warn!(?err, "waveshare display mutex poisoned during init");
+                progress.bar.suspend(|| eprintln!("{shown}: {err}"));
+                failed.push(serde_json::json!({
+                    "track": shown.to_string(),
+                    "error": err.to_string(),
+                }));
             }
         }
     }
+    progress.finish();
+    eprintln!(
+        "Analyzed {} track(s); {skipped} already up to date, {} failed.",
+        analyzed.len(),
+        failed.len()
+    );
+    if config.loudness().is_none() && !analyzed.is_empty() {
+        eprintln!("Add a [loudness] table to the config to play tracks at these levels.");
+    }
+    emit(serde_json::json!({
+        "ok": failed.is_empty(),
+        "analyzed": analyzed,
+        "skipped": skipped,
+        "failed": failed,
+    }));
+    if !failed.is_empty() {
+        return Err(RunError::AnalyzeFailed(failed.len()));
+    }
+    Ok(())
+}
 
-    println!("Loaded configuration from {}", config_path.display());
-    println!("Awaiting NFC interactions (reader not connected in this environment).");
-
-    let sleep_duration = Duration::from_millis(poll_interval_ms);
+/// Handles the `sync` subcommand.
+fn handle_sync(args: SyncArgs, inherited_config: Option<PathBuf>) -> Result<(), RunError> {
+    let config_path = args
+        .config
+        .or(inherited_config)
+        .ok_or(RunError::MissingConfig)?;
+    let remote = RemoteLibrary::parse(&args.from)?;
+    let report = config::merge_remote_config(&config_path, &remote.fetch_config()?)?;
 
-    #[cfg(feature = "waveshare-display")]
-    let display_for_actions = display.clone();
-    #[cfg(feature = "waveshare-display")]
-    let display_for_idle = display.clone();
+    for uid in &report.added {
+        say!("Added card {uid}");
+    }
+    for group in &report.groups_added {
+        say!("Added group {group}");
+    }
+    for conflict in &report.conflicts {
+        say!(
+            "Conflict: {} kept local {} (remote has {})",
+            conflict.entry,
+            conflict.local,
+            conflict.remote
+        );
+    }
+    say!(
+        "Synced from {}: {} added, {} unchanged, {} conflict(s).",
+        args.from,
+        report.added.len(),
+        report.unchanged.len(),
+        report.conflicts.len()
+    );
+    let uids = |cards: &[CardUid]| cards.iter().map(ToString::to_string).collect::<Vec<_>>();
+    let mut result = serde_json::json!({
+        "from": args.from,
+        "added": uids(&report.added),
+        "unchanged": uids(&report.unchanged),
+        "groups_added": report.groups_added,
+        "conflicts": report.conflicts.iter().map(|conflict| serde_json::json!({
+            "entry": conflict.entry,
+            "local": conflict.local,
+            "remote": conflict.remote,
+        })).collect::<Vec<_>>(),
+    });
 
-    run_until_shutdown(
-        controller.clone(),
-        &mut reader,
-        {
-            #[cfg(feature = "waveshare-display")]
-            let display_for_actions = display_for_actions;
-            let action_status = action_status_state;
-            move |action| {
-                println!("Controller action: {:?}", action);
-                action_status.record_action(action.clone());
-                tracing::info!(?action, "controller action");
-                #[cfg(feature = "waveshare-display")]
-                {
-                    if let Some(handle) = &display_for_actions {
-                        let snapshot = action_status.snapshot();
-                        match handle.lock() {
-                            Ok(mut device) => {
-                                if let Err(err) = device.update(&snapshot) {
-                                    tracing::warn!(?err, "waveshare display update failed");
-                                }
-                            }
-                            Err(err) => {
-                                tracing::warn!(?err, "waveshare display mutex poisoned");
-                            }
-                        }
-                    }
-                }
+    if args.with_tracks {
+        let library = load_config(&config_path)?.into_library();
+        let mut downloaded = 0usize;
+        let progress = Progress::bar();
+        for (index, entry) in report.tracks.iter().enumerate() {
+            progress.working_on(index, report.tracks.len(), entry);
+            if subsonic::song_id(entry).is_some()
+                || podcast::feed_url(entry).is_some()
+                || spotify::spotify_uri(entry).is_some()
+                || stream::is_stream_url(entry)
+            {
+                continue;
             }
-        },
-        {
-            #[cfg(feature = "waveshare-display")]
-            let display_for_idle = display_for_idle;
-            let idle_status = idle_status_state;
-            move || {
-                idle_status.record_idle();
-                #[cfg(feature = "waveshare-display")]
-                {
-                    if let Some(handle) = &display_for_idle {
-                        let snapshot = idle_status.snapshot();
-                        if snapshot.idle_events % 100 == 0 {
-                            match handle.lock() {
-                                Ok(mut device) => {
-                                    if let Err(err) = device.update(&snapshot) {
-                                        tracing::warn!(?err, "waveshare display update failed");
-                                    }
-                                }
-                                Err(err) => {
-                                    tracing::warn!(?err, "waveshare display mutex poisoned");
-                                }
-                            }
-                        }
+            let Some(track) = library.confined_track(entry) else {
+                progress.report(format!(
+                    "Skipping {entry}: only paths inside music_dir can be copied"
+                ));
+                continue;
+            };
+            if track.path().exists() {
+                continue;
+            }
+            match remote.fetch_track(entry) {
+                Ok(bytes) => {
+                    let write_err = |source| RunError::WriteTrack {
+                        path: track.path().to_path_buf(),
+                        source,
+                    };
+                    if let Some(parent) = track.path().parent() {
+                        std::fs::create_dir_all(parent).map_err(write_err)?;
                     }
+                    std::fs::write(track.path(), bytes).map_err(write_err)?;
+                    progress.item(format!("Downloaded {entry}"));
+                    downloaded += 1;
                 }
-                std::thread::sleep(sleep_duration);
-            }
-        },
-    )?;
-
-    #[cfg(feature = "waveshare-display")]
-    if let Some(handle) = &display {
-        match handle.lock() {
-            Ok(mut device) => {
-                if let Err(err) = device.shutdown() {
-                    tracing::warn!(?err, "failed to put waveshare display to sleep");
+                Err(RemoteError::Rejected { status: 404, .. }) => {
+                    progress.report(format!("Skipping {entry}: not a file on the remote box"));
                 }
-            }
-            Err(err) => {
-                tracing::warn!(?err, "waveshare display mutex poisoned during shutdown");
+                Err(err) => return Err(err.into()),
             }
         }
+        progress.finish();
+        say!("Downloaded {downloaded} track(s).");
+        result["downloaded"] = downloaded.into();
     }
+    emit(result);
+    Ok(())
+}
 
-    println!("Reader requested shutdown. Exiting.");
-    tracing::info!(snapshot = ?status.snapshot(), "final status");
-
+/// Handles the `history` subcommand: lists recent plays, naming cards
+/// from the config when one is given and loads.
+fn handle_history(
+    args: HistoryArgs,
+    inherited_config: Option<PathBuf>,
+    inherited_log: Option<PathBuf>,
+) -> Result<(), RunError> {
+    let history = PlayHistory::open(
+        args.log.or(inherited_log),
+        args.config.or(inherited_config),
+        args.since,
+    )?;
+    let mut plays = Vec::new();
+    for entry in history.log.recent_plays(history.since, args.limit)? {
+        let HistoryEvent::Played {
+            card,
+            track,
+            started,
+            played_ms,
+        } = entry.event
+        else {
+            continue;
+        };
+        let name = history.name(&card);
+        let track = history
+            .library
+            .as_ref()
+            .and_then(|library| track.strip_prefix(library.music_dir()).ok())
+            .map_or_else(|| track.clone(), Path::to_path_buf);
+        let started_at = expiry::to_datetime(SystemTime::UNIX_EPOCH + Duration::from_secs(started));
+        let secs = played_ms / 1000;
+        let label = PlayHistory::label(&card, &name);
+        say!(
+            "{started_at}  {:>3}:{:02}  {label}  {}",
+            secs / 60,
+            secs % 60,
+            track.display()
+        );
+        plays.push(serde_json::json!({
+            "card": card,
+            "name": name,
+            "track": track,
+            "started": started,
+            "played_ms": played_ms,
+        }));
+    }
+    if plays.is_empty() {
+        say!("No plays recorded.");
+    }
+    emit(serde_json::json!({ "plays": plays }));
     Ok(())
 }
 
-/// Handles the `tag` subcommand.
-fn handle_tag_command(
-    command: TagCommand,
+/// The history log the `history` and `stats` subcommands read, with the
+/// library that names its cards.
+struct PlayHistory {
+    log: HistoryLog,
+    /// `None` when no config is given or it fails to load; cards are then
+    /// listed by UID.
+    library: Option<Library>,
+    /// Plays that started before this are left out.
+    since: SystemTime,
+}
+
+impl PlayHistory {
+    fn open(
+        log: Option<PathBuf>,
+        config: Option<PathBuf>,
+        since: Option<Duration>,
+    ) -> Result<Self, RunError> {
+        let log = log
+            .map(HistoryLog::new)
+            .ok_or(RunError::MissingHistoryLog)?;
+        let library = config
+            .and_then(|path| load_config(path).ok())
+            .map(MusicBoxConfig::into_library);
+        let since = since.map_or(SystemTime::UNIX_EPOCH, |age| {
+            SystemTime::now()
+                .checked_sub(age)
+                .unwrap_or(SystemTime::UNIX_EPOCH)
+        });
+        Ok(Self {
+            log,
+            library,
+            since,
+        })
+    }
+
+    /// The library's name for `card`, if it has one.
+    fn name(&self, card: &str) -> Option<String> {
+        let library = self.library.as_ref()?;
+        let uid = CardUid::parse(card).ok()?;
+        library.name(&uid).map(str::to_string)
+    }
+
+    /// `card` as printed: its name with the UID after it, or just the UID.
+    fn label(card: &str, name: &Option<String>) -> String {
+        match name {
+            Some(name) => format!("{name} ({card})"),
+            None => card.to_string(),
+        }
+    }
+}
+
+/// Handles the `stats` subcommand: totals plays per card, day, and week,
+/// naming cards from the config when one is given and loads.
+fn handle_stats(
+    args: StatsArgs,
     inherited_config: Option<PathBuf>,
-    default_reader: ReaderKind,
-    default_poll_ms: u64,
-) -> Result<(), TagError> {
-    match command {
-        TagCommand::Add(args) => {
-            handle_tag_add(args, inherited_config, default_reader, default_poll_ms)
+    inherited_log: Option<PathBuf>,
+) -> Result<(), RunError> {
+    let history = PlayHistory::open(
+        args.log.or(inherited_log),
+        args.config.or(inherited_config),
+        args.since,
+    )?;
+    let stats = PlayStats::from_log(&history.log, history.since)?;
+    if stats.plays == 0 {
+        say!("No plays recorded.");
+    } else {
+        say!("{}, {} in all", plays(stats.plays), clock(stats.played));
+        say!("Most played:");
+    }
+    let mut cards = Vec::new();
+    for total in stats.most_played(args.top) {
+        let name = history.name(&total.card);
+        let label = PlayHistory::label(&total.card, &name);
+        say!("  {:>5}  {:>9}  {label}", total.plays, clock(total.played));
+        cards.push(serde_json::json!({
+            "card": total.card,
+            "name": name,
+            "plays": total.plays,
+            "played_ms": total.played.as_millis() as u64,
+        }));
+    }
+    let periods = |heading: &str, totals: &[PeriodTotal]| {
+        if !totals.is_empty() {
+            say!("{heading}:");
         }
+        totals
+            .iter()
+            .map(|total| {
+                say!(
+                    "  {}  {:>9}  {}",
+                    total.start,
+                    clock(total.played),
+                    plays(total.plays)
+                );
+                serde_json::json!({
+                    "start": total.start.to_string(),
+                    "plays": total.plays,
+                    "played_ms": total.played.as_millis() as u64,
+                })
+            })
+            .collect::<Vec<_>>()
+    };
+    let days = periods("Per day", &stats.days);
+    let weeks = periods("Per week, from Monday", &stats.weeks);
+    if !stats.tags.is_empty() {
+        say!("Tag counters:");
+    }
+    let mut tags = Vec::new();
+    for total in &stats.tags {
+        let name = history.name(&total.card);
+        say!(
+            "  {}: seen {} of {} reads, {} missed",
+            PlayHistory::label(&total.card, &name),
+            total.seen,
+            total.counted,
+            total.missed()
+        );
+        tags.push(serde_json::json!({
+            "card": total.card,
+            "name": name,
+            "seen": total.seen,
+            "counted": total.counted,
+            "missed": total.missed(),
+        }));
     }
+    emit(serde_json::json!({
+        "plays": stats.plays,
+        "played_ms": stats.played.as_millis() as u64,
+        "cards": cards,
+        "days": days,
+        "weeks": weeks,
+        "tags": tags,
+    }));
+    Ok(())
 }
 
-/// Handles the `tag add` subcommand.
-fn handle_tag_add(
-    args: TagAddArgs,
+fn plays(count: u32) -> String {
+    match count {
+        1 => "1 play".to_string(),
+        count => format!("{count} plays"),
+    }
+}
+
+/// `played` as `h:mm:ss`, or `m:ss` under an hour.
+fn clock(played: Duration) -> String {
+    let secs = played.as_secs();
+    if secs >= 3_600 {
+        format!("{}:{:02}:{:02}", secs / 3_600, secs % 3_600 / 60, secs % 60)
+    } else {
+        format!("{}:{:02}", secs / 60, secs % 60)
+    }
+}
+
+/// Handles the `backup` subcommand.
+fn handle_backup(
+    args: BackupArgs,
     inherited_config: Option<PathBuf>,
-    default_reader: ReaderKind,
-    default_poll_ms: u64,
-) -> Result<(), TagError> {
-    let TagAddArgs {
-        config,
-        track,
-        card,
-        reader,
-        poll_interval_ms,
-        skip_tag_write,
-    } = args;
+    inherited_state: Vec<PathBuf>,
+) -> Result<(), RunError> {
+    let config_path = args
+        .config
+        .or(inherited_config)
+        .ok_or(RunError::MissingConfig)?;
+    let music_dir = if args.include_music {
+        Some(load_config(&config_path)?.music_dir().to_path_buf())
+    } else {
+        None
+    };
+    let sources = BackupSources {
+        config: config_path,
+        state_files: inherited_state
+            .into_iter()
+            .chain(args.state_files)
+            .collect(),
+        music_dir,
+    };
+    let progress = Progress::spinner("Backing up");
+    let summary = backup::create_backup(&args.file, &sources);
+    progress.finish();
+    let summary = summary?;
+    say!(
+        "Backed up {} file(s) and {} track(s) to {}",
+        summary.files,
+        summary.music_files,
+        args.file.display()
+    );
+    emit(serde_json::json!({
+        "archive": args.file,
+        "files": summary.files,
+        "music_files": summary.music_files,
+    }));
+    Ok(())
+}
 
-    let config_path = config.or(inherited_config).ok_or(TagError::MissingConfig)?;
-    let reader_kind = reader.unwrap_or(default_reader);
-    let poll_ms = poll_interval_ms.unwrap_or(default_poll_ms);
-    let poll_duration = Duration::from_millis(poll_ms);
+/// Handles the `restore` subcommand.
+fn handle_restore(args: RestoreArgs, inherited_state: Vec<PathBuf>) -> Result<(), RunError> {
+    let options = RestoreOptions {
+        config: args.config,
+        music_dir: args.music_dir,
+        state_files: inherited_state
+            .into_iter()
+            .chain(args.state_files)
+            .collect(),
+        overwrite: args.force,
+    };
+    let progress = Progress::spinner("Restoring");
+    let summary = backup::restore_backup(&args.file, &options);
+    progress.finish();
+    let summary = summary?;
+    say!(
+        "Restored {} file(s) and {} track(s) from {}",
+        summary.files,
+        summary.music_files,
+        args.file.display()
+    );
+    emit(serde_json::json!({
+        "archive": args.file,
+        "files": summary.files,
+        "music_files": summary.music_files,
+    }));
+    Ok(())
+}
+
+/// Handles the `update-check` subcommand.
+fn handle_update_check(args: UpdateCheckArgs) -> Result<(), RunError> {
+    let current = update::Version::parse(update::CURRENT_VERSION)?;
+    let release = update::latest_release(&args.repository)?;
+    let latest = release.version()?;
+    let result = |installed: bool| {
+        serde_json::json!({
+            "current": current.to_string(),
+            "latest": latest.to_string(),
+            "update_available": latest > current,
+            "installed": installed,
+        })
+    };
+    if latest <= current {
+        say!("musicbox {current} is up to date (latest release {latest}).");
+        emit(result(false));
+        return Ok(());
+    }
+    say!("musicbox {latest} is available; this is {current}.");
+    if !args.install {
+        say!("Run `musicbox update-check --install` to install it.");
+        emit(result(false));
+        return Ok(());
+    }
+    let target = std::env::current_exe().map_err(|source| UpdateError::Install {
+        path: PathBuf::from("musicbox"),
+        source,
+    })?;
+    update::install(&release, &target)?;
+    say!(
+        "Installed musicbox {latest} to {}. Restart musicbox to use it.",
+        target.display()
+    );
+    emit(result(true));
+    Ok(())
+}
 
-    let track_str = path_to_string(&track)?;
+/// Handles the `setup` subcommand: asks about the reader, audio output,
+/// music, and display, writes a config, and offers to map a first card.
+fn handle_setup(
+    args: SetupArgs,
+    inherited_config: Option<PathBuf>,
+    poll_interval_ms: Option<u64>,
+) -> Result<(), RunError> {
+    let config_path = args
+        .config
+        .or(inherited_config)
+        .unwrap_or_else(|| PathBuf::from("musicbox.toml"));
+    // Questions go where `say!` does, keeping stdout for the result.
+    let output: Box<dyn std::io::Write> = if json_output() {
+        Box::new(std::io::stderr())
+    } else {
+        Box::new(std::io::stdout())
+    };
+    let mut prompt = Prompter::new(std::io::stdin().lock(), output);
+    say!("Setting up musicbox. Press Enter to take the [default] answer.");
+    if config_path.exists()
+        && !prompt
+            .confirm(
+                &format!("{} already exists. Replace it?", config_path.display()),
+                false,
+            )
+            .map_err(RunError::Setup)?
+    {
+        say!("Left {} unchanged.", config_path.display());
+        emit(serde_json::json!({ "config": config_path, "written": false }));
+        return Ok(());
+    }
 
-    let mut effective_reader_kind = reader_kind;
-    let mut auto_generated_uid = false;
+    let mut readers: Vec<(String, ReaderKind, Option<PathBuf>)> = probe_pcsc_readers()
+        .into_iter()
+        .map(|name| (format!("PC/SC reader {name}"), ReaderKind::Pcsc, None))
+        .collect();
+    readers.extend(setup::serial_ports().into_iter().map(|port| {
+        let label = format!("PN532 board on {}", port.display());
+        (label, ReaderKind::Pn532, Some(port))
+    }));
+    readers.push((
+        "No reader for now; add cards with `tag add --card`".into(),
+        ReaderKind::Noop,
+        None,
+    ));
+    let labels: Vec<String> = readers.iter().map(|(label, ..)| label.clone()).collect();
+    let choice = prompt
+        .choose("Which NFC reader should musicbox use?", &labels)
+        .map_err(RunError::Setup)?;
+    let (_, reader_kind, pn532) = readers.swap_remove(choice);
 
-    let uid = if let Some(card_hex) = card {
-        CardUid::from_hex(card_hex.trim())?
-    } else if matches!(reader_kind, ReaderKind::Noop) {
-        auto_generated_uid = true;
-        generate_synthetic_card_uid()
+    let devices = audio::output_devices();
+    let audio_device = if devices.is_empty() {
+        None
     } else {
-        let selection = select_reader(reader_kind, poll_duration)?;
-        effective_reader_kind = selection.kind();
-        if matches!(effective_reader_kind, ReaderKind::Noop) {
-            auto_generated_uid = true;
-            generate_synthetic_card_uid()
-        } else {
-            acquire_card_uid(selection.into_reader())?
-        }
+        let mut labels = vec!["The system default".to_string()];
+        labels.extend(devices.iter().cloned());
+        let choice = prompt
+            .choose("Which audio output should musicbox play on?", &labels)
+            .map_err(RunError::Setup)?;
+        choice.checked_sub(1).map(|index| devices[index].clone())
     };
 
-    if auto_generated_uid {
-        println!(
-            "Generated synthetic card UID {} because the selected reader cannot scan cards.",
-            uid
-        );
+    let config_dir = match config_path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir.to_path_buf(),
+        _ => std::env::current_dir().map_err(RunError::Setup)?,
+    };
+    let default_music = std::path::absolute(config_dir.join("music")).map_err(RunError::Setup)?;
+    let music_dir = PathBuf::from(
+        prompt
+            .ask("Where is the music kept?", &default_music.to_string_lossy())
+            .map_err(RunError::Setup)?,
+    );
+    if !music_dir.is_dir()
+        && prompt
+            .confirm(
+                &format!("{} does not exist. Create it?", music_dir.display()),
+                true,
+            )
+            .map_err(RunError::Setup)?
+    {
+        std::fs::create_dir_all(&music_dir).map_err(RunError::Setup)?;
     }
 
-    config::add_card_to_config(&config_path, &uid, &track_str)?;
+    let mut run_command = vec!["musicbox".to_string()];
+    if matches!(reader_kind, ReaderKind::Pn532) {
+        run_command.push("--reader pn532".into());
+    }
+    #[cfg(feature = "waveshare-display")]
+    if prompt
+        .confirm("Is a Waveshare e-ink display attached?", false)
+        .map_err(RunError::Setup)?
+    {
+        run_command.push("--waveshare-display".into());
+    }
 
-    println!(
-        "Mapped card {} to {} in {}",
-        uid,
-        track_str,
-        config_path.display()
-    );
+    let choices = SetupChoices {
+        music_dir,
+        pn532,
+        audio_device,
+    };
+    std::fs::write(&config_path, setup::render_config(&choices)).map_err(RunError::Setup)?;
+    mirror::update(&config_path);
+    say!("Wrote {}.", config_path.display());
 
-    if skip_tag_write {
-        println!("Skipping NFC tag write (per --skip-tag-write).");
-    } else if let Err(err) =
-        attempt_tag_write(effective_reader_kind, poll_duration, &uid, &track_str)
+    let mut first_card = None;
+    if prompt
+        .confirm("Map a first card now?", false)
+        .map_err(RunError::Setup)?
     {
-        tracing::warn!(?err, "failed to write NFC tag; config still updated");
+        let track = prompt
+            .ask(
+                &format!("Which track, relative to {}?", choices.music_dir.display()),
+                "",
+            )
+            .map_err(RunError::Setup)?;
+        if track.is_empty() {
+            say!("No track given; skipping the first card.");
+        } else {
+            if !matches!(reader_kind, ReaderKind::Noop) {
+                say!("Hold the card to the reader.");
+            }
+            first_card = Some(add_card(
+                TagAddArgs {
+                    config: Some(config_path.clone()),
+                    track: PathBuf::from(track),
+                    card: None,
+                    learned: false,
+                    reader: Some(reader_kind),
+                    poll_interval_ms: None,
+                    skip_tag_write: false,
+                    synthetic_uid: SyntheticUidKind::Random,
+                    expires: None,
+                },
+                None,
+                reader_kind,
+                poll_interval_ms,
+                None,
+            )?);
+        }
     }
 
+    run_command.push(config_path.display().to_string());
+    say!("Start musicbox with: {}", run_command.join(" "));
+    emit(serde_json::json!({
+        "config": config_path,
+        "written": true,
+        "card": first_card,
+        "command": run_command.join(" "),
+    }));
     Ok(())
 }
 
-/// Converts a `Path` to a `String`.
-fn path_to_string(path: &Path) -> Result<String, TagError> {
-    path.to_str()
-        .map(|s| s.to_owned())
-        .ok_or_else(|| TagError::InvalidTrackPath(path.to_path_buf()))
+/// Names of the PC/SC readers attached now, for `setup` to offer.
+#[cfg(feature = "nfc-pcsc")]
+fn probe_pcsc_readers() -> Vec<String> {
+    musicbox::reader::pcsc_backend::PcscReader::new(Duration::from_millis(200))
+        .and_then(|reader| reader.attached_readers())
+        .unwrap_or_default()
+}
+
+#[cfg(not(feature = "nfc-pcsc"))]
+fn probe_pcsc_readers() -> Vec<String> {
+    Vec::new()
 }
 
-fn generate_synthetic_card_uid() -> CardUid {
-    let now = SystemTime::now()
-        .duration_since(UNIX_EPOCH)
-        .unwrap_or(Duration::ZERO);
+/// Handles the `verify` subcommand.
+fn handle_verify(args: VerifyArgs, inherited_config: Option<PathBuf>) -> Result<(), RunError> {
+    let config_path = args
+        .config
+        .or(inherited_config)
+        .ok_or(RunError::MissingConfig)?;
+    let config = load_config(&config_path)?;
+    let music_dir = config.music_dir();
 
-    let mut bytes = Vec::with_capacity(12);
-    bytes.extend_from_slice(&now.as_secs().to_be_bytes());
-    bytes.extend_from_slice(&now.subsec_nanos().to_be_bytes());
+    if args.update {
+        let progress = Progress::bar();
+        let manifest = ChecksumManifest::compute_with(music_dir, |track| {
+            progress.working_on(track.index, track.total, track.path.display());
+        });
+        progress.finish();
+        let manifest = manifest?;
+        manifest.save(music_dir)?;
+        say!(
+            "Recorded checksums for {} track(s) in {}",
+            manifest.len(),
+            music_dir.join(MANIFEST_NAME).display()
+        );
+        emit(serde_json::json!({ "recorded": manifest.len() }));
+        return Ok(());
+    }
 
-    CardUid::new(bytes)
+    let progress = Progress::bar();
+    let checks = ChecksumManifest::load(music_dir)?.verify_with(music_dir, |track| {
+        progress.working_on(track.index, track.total, track.path.display());
+    });
+    progress.finish();
+    let checks = checks?;
+    let (mut changed, mut missing, mut unrecorded) = (0usize, 0usize, 0usize);
+    let mut problems = Vec::new();
+    for check in &checks {
+        let label = match check.status {
+            TrackStatus::Ok => continue,
+            TrackStatus::Mismatch => {
+                changed += 1;
+                "CHANGED"
+            }
+            TrackStatus::Missing => {
+                missing += 1;
+                "MISSING"
+            }
+            TrackStatus::Unrecorded => {
+                unrecorded += 1;
+                "NEW"
+            }
+        };
+        say!("{label:<8} {}", check.path.display());
+        problems.push(serde_json::json!({ "status": label.to_lowercase(), "path": check.path }));
+    }
+    say!(
+        "Verified {} track(s): {changed} changed, {missing} missing, {unrecorded} without a checksum.",
+        checks.len()
+    );
+    if unrecorded > 0 {
+        say!("Run `musicbox verify --update` to record checksums for new tracks.");
+    }
+    emit(serde_json::json!({
+        "ok": changed + missing == 0,
+        "verified": checks.len(),
+        "changed": changed,
+        "missing": missing,
+        "unrecorded": unrecorded,
+        "tracks": problems,
+    }));
+    match changed + missing {
+        0 => Ok(()),
+        failed => Err(RunError::VerifyFailed(failed)),
+    }
 }
 
-/// Waits for a card to be presented to the reader and returns its UID.
-fn acquire_card_uid(mut reader: Box<dyn NfcReader>) -> Result<CardUid, TagError> {
-    loop {
-        match reader.next_event()? {
-            ReaderEvent::CardPresent { uid } => return Ok(uid),
-            ReaderEvent::Idle => continue,
-            ReaderEvent::Shutdown => return Err(TagError::ReaderShutdown),
+/// Logs tracks that no longer match their checksums; runs off the main loop
+/// because hashing a large library on an SD card takes a while.
+fn verify_tracks_in_background(music_dir: &Path) {
+    let checks = match ChecksumManifest::load(music_dir).and_then(|m| m.verify(music_dir)) {
+        Ok(checks) => checks,
+        Err(err) => {
+            tracing::warn!(%err, "track verification skipped");
+            return;
+        }
+    };
+    let mut failed = 0usize;
+    for check in &checks {
+        if matches!(check.status, TrackStatus::Mismatch | TrackStatus::Missing) {
+            failed += 1;
+            tracing::warn!(track = %check.path.display(), status = ?check.status, "track failed verification");
         }
     }
+    tracing::info!(tracks = checks.len(), failed, "track verification finished");
 }
 
-/// Attempts to write the track metadata to the NFC tag.
-fn attempt_tag_write(
-    reader_kind: ReaderKind,
-    poll: Duration,
-    uid: &CardUid,
-    track: &str,
-) -> Result<(), TagError> {
-    let _ = (reader_kind, poll, uid, track);
-    #[cfg(feature = "nfc-pcsc")]
-    {
-        println!(
-            "Writing track metadata to NFC tag {} via PC/SC is not implemented yet.",
-            uid
-        );
+/// Handles the `ctl` subcommand.
+#[cfg(unix)]
+fn handle_ctl(command: CtlCommand, socket: Option<&Path>) -> Result<(), RunError> {
+    let client =
+        ControlClient::new(socket.map_or_else(control::default_socket_path, Path::to_path_buf));
+    let request = match command {
+        CtlCommand::Play { card } => ControlRequest::Play { card },
+        CtlCommand::Pause => ControlRequest::Pause,
+        CtlCommand::Status => ControlRequest::Status,
+        CtlCommand::Reload => ControlRequest::Reload,
+        CtlCommand::Queue => ControlRequest::Queue,
+        CtlCommand::Dequeue { index } => ControlRequest::Dequeue { index },
+    };
+    let response = client.send(&request)?;
+    if request == ControlRequest::Queue {
+        emit(serde_json::to_value(&response).expect("control responses serialize"));
+        let status = response.status.unwrap_or_default();
+        if status.queue.is_empty() {
+            say!("Queue: empty");
+        }
+        for (index, track) in status.queue.iter().enumerate() {
+            let marker = if status.queue_position == Some(index) {
+                "▶"
+            } else {
+                " "
+            };
+            say!("{marker} {index:>3}  {track}");
+        }
+        return Ok(());
     }
-    #[cfg(not(feature = "nfc-pcsc"))]
-    {
-        println!(
-            "Tag writing unavailable without the `nfc-pcsc` feature; config has still been updated."
-        );
+    if request != ControlRequest::Status {
+        say!("{}", response.message);
+    }
+    emit(serde_json::to_value(&response).expect("control responses serialize"));
+    if let Some(status) = response.status {
+        match (status.card, status.track) {
+            (Some(card), Some(track)) => match status.name {
+                Some(name) => say!("Playing: {track} (card {card}, {name})"),
+                None => say!("Playing: {track} (card {card})"),
+            },
+            _ => say!("Playing: nothing"),
+        }
+        let muted = if status.muted { ", muted" } else { "" };
+        say!("Volume: {:.0}%{muted}", status.volume * 100.0);
+        if status.learning {
+            match status.learned.as_slice() {
+                [] => say!("Learning: no unknown cards yet"),
+                learned => say!("Learning: {}", learned.join(", ")),
+            }
+        }
     }
     Ok(())
 }
 
 /// Handles the `manual` subcommand.
-fn handle_manual_command(command: ManualCommand, silent: bool) -> Result<(), RunError> {
+fn handle_manual_command(
+    command: ManualCommand,
+    silent: bool,
+    control_socket: Option<&Path>,
+) -> Result<(), RunError> {
     match command {
-        ManualCommand::Trigger(args) => handle_manual_trigger(args, silent),
+        ManualCommand::Trigger(args) => handle_manual_trigger(args, silent, control_socket),
+        ManualCommand::Script(args) => handle_manual_script(args, silent),
+        ManualCommand::Mute(args) => handle_manual_mute(args),
     }
 }
 
-/// Handles the `manual trigger` subcommand.
-fn handle_manual_trigger(args: ManualTriggerArgs, silent: bool) -> Result<(), RunError> {
-    let player = if silent {
-        PlayerBackend::Noop
+/// Handles the `manual mute` subcommand. Muting only makes sense for a box
+/// that is already playing, so there is no standalone fallback.
+fn handle_manual_mute(args: ManualMuteArgs) -> Result<(), RunError> {
+    let muted =
+        RemoteController::new(args.remote).set_muted(matches!(args.state, MuteState::On))?;
+    if muted {
+        say!("Running instance at {} is muted.", args.remote);
     } else {
-        match RodioPlayer::new() {
-            Ok(player) => PlayerBackend::Rodio(player),
-            Err(err) => {
-                eprintln!("Audio backend unavailable ({err}). Falling back to silent playback.");
-                PlayerBackend::Noop
+        say!("Running instance at {} is unmuted.", args.remote);
+    }
+    emit(serde_json::json!({ "remote": args.remote, "muted": muted }));
+    Ok(())
+}
+
+/// Asks the player on the control socket to handle `card`. Returns `None`
+/// when no player is listening.
+#[cfg(unix)]
+fn trigger_through_socket(socket: Option<&Path>, card: &str) -> Result<Option<String>, RunError> {
+    let client =
+        ControlClient::new(socket.map_or_else(control::default_socket_path, Path::to_path_buf));
+    let request = ControlRequest::Play {
+        card: card.to_string(),
+    };
+    match client.send(&request) {
+        Ok(response) => Ok(Some(response.message)),
+        Err(ControlError::Unreachable { .. }) => Ok(None),
+        Err(err) => Err(err.into()),
+    }
+}
+
+#[cfg(not(unix))]
+fn trigger_through_socket(_socket: Option<&Path>, _card: &str) -> Result<Option<String>, RunError> {
+    Ok(None)
+}
+
+/// The unknown card the player on the control socket noted last.
+#[cfg(unix)]
+fn learned_card(socket: Option<&Path>) -> Result<CardUid, TagError> {
+    let client =
+        ControlClient::new(socket.map_or_else(control::default_socket_path, Path::to_path_buf));
+    let status = client.send(&ControlRequest::Status)?.status;
+    let learned = status
+        .and_then(|status| status.learned.into_iter().next())
+        .ok_or(TagError::NothingLearned)?;
+    Ok(CardUid::parse(&learned)?)
+}
+
+#[cfg(not(unix))]
+fn learned_card(_socket: Option<&Path>) -> Result<CardUid, TagError> {
+    Err(TagError::NothingLearned)
+}
+
+/// Handles the `manual trigger` subcommand. A running player takes the
+/// trigger, through `--remote` if given and otherwise its control socket;
+/// only with none listening does this play the card itself.
+fn handle_manual_trigger(
+    args: ManualTriggerArgs,
+    silent: bool,
+    control_socket: Option<&Path>,
+) -> Result<(), RunError> {
+    if let Some(addr) = args.remote {
+        match RemoteController::new(addr).trigger_ref(&args.card) {
+            Ok(message) => {
+                say!("Running instance at {addr} handled trigger: {message}");
+                emit(serde_json::json!({
+                    "card": args.card,
+                    "handled_by": addr,
+                    "action": message,
+                }));
+                return Ok(());
+            }
+            Err(RemoteError::Unreachable { source, .. }) => {
+                eprintln!(
+                    "No running instance at {addr} ({source}). Falling back to standalone playback."
+                );
             }
+            Err(err) => return Err(err.into()),
         }
-    };
+    } else if let Some(message) = trigger_through_socket(control_socket, &args.card)? {
+        say!("Running instance handled trigger: {message}");
+        emit(serde_json::json!({
+            "card": args.card,
+            "handled_by": "control-socket",
+            "action": message,
+        }));
+        return Ok(());
+    }
 
-    let mut controller = controller_from_config_path(&args.config, player)?;
+    let config_path = args.config.ok_or(RunError::MissingConfig)?;
+    let config = load_config(&config_path)?;
+    let player = build_player(silent, &config);
 
-    let uid = CardUid::from_hex(args.card.trim())
+    let mut controller = MusicBoxController::new(config.into_library(), player);
+    let uid = controller
+        .library()
+        .resolve_card(&args.card)
         .map_err(TagError::CardUidParse)
         .map_err(RunError::Tag)?;
 
@@ -605,24 +3606,275 @@ fn handle_manual_trigger(args: ManualTriggerArgs, silent: bool) -> Result<(), Ru
         .map_err(RunLoopError::from)
         .map_err(RunError::Loop)?;
 
-    println!("Manual trigger produced action: {:?}", action);
+    say!("Manual trigger produced action: {:?}", action);
+    emit(serde_json::json!({
+        "card": uid.to_string(),
+        "handled_by": "standalone",
+        "action": action_json(&action),
+    }));
     controller.wait_for_player()?;
 
     Ok(())
 }
 
+/// A controller action as `--output json` reports it: its `kind`, with the
+/// card, track, and other details it carries.
+fn action_json(action: &ControllerAction) -> serde_json::Value {
+    let played = |kind: &str, card: &CardUid, track: &Track| {
+        serde_json::json!({
+            "kind": kind,
+            "card": card.to_string(),
+            "track": track.path(),
+        })
+    };
+    let tapped =
+        |kind: &str, card: &CardUid| serde_json::json!({ "kind": kind, "card": card.to_string() });
+    match action {
+        ControllerAction::Started { card, track } => played("started", card, track),
+        ControllerAction::Stopped { card, track } => played("stopped", card, track),
+        ControllerAction::Switched {
+            from_card,
+            from_track,
+            to_card,
+            to_track,
+        } => serde_json::json!({
+            "kind": "switched",
+            "card": to_card.to_string(),
+            "track": to_track.path(),
+            "from_card": from_card.to_string(),
+            "from_track": from_track.path(),
+        }),
+        ControllerAction::Paused { card, track } => played("paused", card, track),
+        ControllerAction::Resumed { card, track } => played("resumed", card, track),
+        ControllerAction::Advanced { card, track } => played("advanced", card, track),
+        ControllerAction::Skipped { card, track } => played("skipped", card, track),
+        ControllerAction::Unchanged { card } => tapped("unchanged", card),
+        ControllerAction::RecordArmed { card } => tapped("record_armed", card),
+        ControllerAction::Recording { card } => tapped("recording", card),
+        ControllerAction::MemoRecorded { card, track } => played("memo_recorded", card, track),
+        ControllerAction::MuteToggled { muted } => {
+            serde_json::json!({ "kind": "mute_toggled", "muted": muted })
+        }
+        ControllerAction::NightModeToggled { on } => {
+            serde_json::json!({ "kind": "night_mode_toggled", "on": on })
+        }
+        ControllerAction::VolumeChanged { percent } => {
+            serde_json::json!({ "kind": "volume_changed", "percent": percent })
+        }
+        ControllerAction::ShutdownRequested { card } => tapped("shutdown_requested", card),
+        ControllerAction::ReloadRequested { card } => tapped("reload_requested", card),
+        ControllerAction::Unlocked { card } => tapped("unlocked", card),
+        ControllerAction::CountdownFinished { card, track } => {
+            played("countdown_finished", card, track)
+        }
+        ControllerAction::TimedOut { card, track } => played("timed_out", card, track),
+        ControllerAction::UnknownCard { card } => tapped("unknown_card", card),
+        ControllerAction::OutputChanged { change } => {
+            let (change, device) = match change {
+                OutputChange::Opened(device) => ("opened", Some(device)),
+                OutputChange::Lost => ("lost", None),
+                OutputChange::Released => ("released", None),
+            };
+            serde_json::json!({ "kind": "output_changed", "change": change, "device": device })
+        }
+        ControllerAction::CardLearned { card } => tapped("card_learned", card),
+    }
+}
+
+/// Handles the `manual script` subcommand.
+fn handle_manual_script(args: ManualScriptArgs, silent: bool) -> Result<(), RunError> {
+    let contents =
+        std::fs::read_to_string(&args.script).map_err(|source| RunError::ReadScript {
+            path: args.script.clone(),
+            source,
+        })?;
+    let steps = parse_replay_script(&contents)?;
+
+    if let Some(addr) = args.remote
+        && replay_against_remote(RemoteController::new(addr), &steps)?
+    {
+        return Ok(());
+    }
+
+    let config_path = args.config.ok_or(RunError::MissingConfig)?;
+    let config = load_config(&config_path)?;
+    let player = build_player(silent, &config);
+    let mut controller = MusicBoxController::new(config.into_library(), player);
+    let mut reader = ReplayReader::new(steps);
+    let mut taps = 0usize;
+    let mut failures = 0usize;
+    let mut results = Vec::new();
+
+    loop {
+        match process_next_event(&mut controller, &mut reader) {
+            Ok(ProcessOutcome::Action(ControllerAction::UnknownCard { card })) => {
+                taps += 1;
+                failures += 1;
+                let err = format!("card {card} is not in the library");
+                eprintln!("Script tap failed: {err}");
+                results.push(serde_json::json!({ "error": err }));
+            }
+            Ok(ProcessOutcome::Action(action)) => {
+                taps += 1;
+                say!("Script action: {:?}", action);
+                results.push(serde_json::json!({ "action": action_json(&action) }));
+            }
+            Ok(ProcessOutcome::NoEvent) => {}
+            Ok(ProcessOutcome::Shutdown) => break,
+            Err(RunLoopError::Controller(err)) => {
+                taps += 1;
+                failures += 1;
+                eprintln!("Script tap failed: {err}");
+                results.push(serde_json::json!({ "error": err.to_string() }));
+            }
+            Err(err) => return Err(err.into()),
+        }
+    }
+
+    say!("Replayed {taps} taps ({failures} failed).");
+    emit(serde_json::json!({
+        "handled_by": "standalone",
+        "taps": taps,
+        "failures": failures,
+        "results": results,
+    }));
+    controller.wait_for_player()?;
+    Ok(())
+}
+
+/// Replays `steps` against a running instance. Returns `false` when nothing
+/// answered the first tap so the caller can fall back to standalone mode.
+fn replay_against_remote(remote: RemoteController, steps: &[ReplayStep]) -> Result<bool, RunError> {
+    let mut taps = 0usize;
+    let mut failures = 0usize;
+    let mut results = Vec::new();
+
+    for step in steps {
+        match step {
+            ReplayStep::Wait(delay) => std::thread::sleep(*delay),
+            ReplayStep::Tap(uid) => {
+                match remote.trigger(uid) {
+                    Ok(message) => {
+                        say!("{uid}: {message}");
+                        results.push(serde_json::json!({
+                            "card": uid.to_string(),
+                            "action": message,
+                        }));
+                    }
+                    Err(RemoteError::Unreachable { source, .. }) if taps == 0 => {
+                        eprintln!(
+                            "No running instance at {} ({source}). Falling back to standalone playback.",
+                            remote.addr()
+                        );
+                        return Ok(false);
+                    }
+                    Err(RemoteError::Rejected { message, .. }) => {
+                        failures += 1;
+                        eprintln!("{uid}: {message}");
+                        results.push(serde_json::json!({
+                            "card": uid.to_string(),
+                            "error": message,
+                        }));
+                    }
+                    Err(err) => return Err(err.into()),
+                }
+                taps += 1;
+            }
+        }
+    }
+
+    say!(
+        "Replayed {taps} taps ({failures} failed) against {}.",
+        remote.addr()
+    );
+    emit(serde_json::json!({
+        "handled_by": remote.addr(),
+        "taps": taps,
+        "failures": failures,
+        "results": results,
+    }));
+    Ok(true)
+}
+
+/// Opens the Rodio backend unless `silent`. While its device is missing the
+/// box plays silently, and it switches to the device once it shows up.
+/// Songs on a configured Subsonic server and podcast episodes are downloaded
+/// before they play, and with a `[loudness]` table every local track plays
+/// at the same level.
+fn build_player(
+    silent: bool,
+    config: &MusicBoxConfig,
+) -> CachingPlayer<LoudnessPlayer<PlayerBackend>> {
+    if silent {
+        let backend = PlayerBackend::Simulated(SimulatedPlayer::new());
+        return CachingPlayer::new(LoudnessPlayer::new(backend, config.loudness()), None);
+    }
+    let wanted = config.audio_device().map(str::to_string);
+    let fades = config.fades();
+    let probe = {
+        let wanted = wanted.clone();
+        move || audio::current_output(wanted.as_deref())
+    };
+    let open = {
+        let wanted = wanted.clone();
+        move |_found: &str| {
+            let opened = match &wanted {
+                Some(device) => RodioPlayer::open_device(device),
+                None => RodioPlayer::new(),
+            };
+            opened.map(|player| player.with_fades(fades))
+        }
+    };
+    let mut player = HotplugPlayer::new(probe, open);
+    if let Some(release_after) = config.audio_release_after() {
+        player = player.with_lazy_open(release_after);
+    } else if player.refresh_output().is_none() {
+        let device = wanted.as_deref().unwrap_or("an output device");
+        eprintln!("Audio output unavailable. Playing silently until {device} appears.");
+    }
+    let backend = PlayerBackend::Rodio(Box::new(player));
+    CachingPlayer::new(
+        LoudnessPlayer::new(backend, config.loudness()),
+        config.subsonic().cloned(),
+    )
+    .with_podcasts(Some(config.podcasts().clone()))
+    .with_spotify(config.spotify().and_then(open_spotify))
+}
+
+/// Builds the Spotify Connect player, or explains why `spotify:` cards will
+/// not play.
+#[cfg(feature = "spotify")]
+fn open_spotify(settings: &SpotifySettings) -> Option<Box<dyn AudioPlayer + Send>> {
+    Some(Box::new(spotify::connect::SpotifyPlayer::new(settings)))
+}
+
+#[cfg(not(feature = "spotify"))]
+fn open_spotify(_settings: &SpotifySettings) -> Option<Box<dyn AudioPlayer + Send>> {
+    eprintln!(
+        "Ignoring the [spotify] table; rebuild with the `spotify` feature to play spotify: cards."
+    );
+    None
+}
+
 enum PlayerBackend {
-    Rodio(RodioPlayer),
-    Noop,
+    Rodio(Box<HotplugPlayer<RodioPlayer>>),
+    Simulated(SimulatedPlayer),
 }
 
 impl AudioPlayer for PlayerBackend {
+    fn capabilities(&self) -> PlayerCapabilities {
+        match self {
+            PlayerBackend::Rodio(player) => player.capabilities(),
+            PlayerBackend::Simulated(player) => player.capabilities(),
+        }
+    }
+
     fn play(&mut self, track: &Track) -> Result<(), PlayerError> {
         match self {
             PlayerBackend::Rodio(player) => player.play(track),
-            PlayerBackend::Noop => {
-                println!("[silent] Would play track: {}", track.path().display());
-                Ok(())
+            PlayerBackend::Simulated(player) => {
+                say!("[silent] Would play track: {}", track.path().display());
+                player.play(track)
             }
         }
     }
@@ -630,9 +3882,29 @@ impl AudioPlayer for PlayerBackend {
     fn stop(&mut self) -> Result<(), PlayerError> {
         match self {
             PlayerBackend::Rodio(player) => player.stop(),
-            PlayerBackend::Noop => {
-                println!("[silent] Would stop playback");
-                Ok(())
+            PlayerBackend::Simulated(player) => {
+                say!("[silent] Would stop playback");
+                player.stop()
+            }
+        }
+    }
+
+    fn pause(&mut self) -> Result<(), PlayerError> {
+        match self {
+            PlayerBackend::Rodio(player) => player.pause(),
+            PlayerBackend::Simulated(player) => {
+                say!("[silent] Would pause playback");
+                player.pause()
+            }
+        }
+    }
+
+    fn resume(&mut self) -> Result<(), PlayerError> {
+        match self {
+            PlayerBackend::Rodio(player) => player.resume(),
+            PlayerBackend::Simulated(player) => {
+                say!("[silent] Would resume playback");
+                player.resume()
             }
         }
     }
@@ -640,7 +3912,93 @@ impl AudioPlayer for PlayerBackend {
     fn wait_until_done(&mut self) -> Result<(), PlayerError> {
         match self {
             PlayerBackend::Rodio(player) => player.wait_until_done(),
-            PlayerBackend::Noop => Ok(()),
+            PlayerBackend::Simulated(player) => player.wait_until_done(),
+        }
+    }
+
+    fn is_finished(&self) -> bool {
+        match self {
+            PlayerBackend::Rodio(player) => player.is_finished(),
+            PlayerBackend::Simulated(player) => player.is_finished(),
+        }
+    }
+
+    fn set_volume(&mut self, volume: f32) -> Result<(), PlayerError> {
+        match self {
+            PlayerBackend::Rodio(player) => player.set_volume(volume),
+            PlayerBackend::Simulated(player) => player.set_volume(volume),
+        }
+    }
+
+    fn volume(&self) -> f32 {
+        match self {
+            PlayerBackend::Rodio(player) => player.volume(),
+            PlayerBackend::Simulated(player) => player.volume(),
+        }
+    }
+
+    fn set_muted(&mut self, muted: bool) -> Result<(), PlayerError> {
+        match self {
+            PlayerBackend::Rodio(player) => player.set_muted(muted),
+            PlayerBackend::Simulated(player) => player.set_muted(muted),
+        }
+    }
+
+    fn set_night_mode(&mut self, on: bool) -> Result<(), PlayerError> {
+        match self {
+            PlayerBackend::Rodio(player) => player.set_night_mode(on),
+            PlayerBackend::Simulated(player) => player.set_night_mode(on),
+        }
+    }
+
+    fn position(&self) -> Option<Duration> {
+        match self {
+            PlayerBackend::Rodio(player) => player.position(),
+            PlayerBackend::Simulated(player) => player.position(),
+        }
+    }
+
+    fn seek(&mut self, offset: Duration) -> Result<(), PlayerError> {
+        match self {
+            PlayerBackend::Rodio(player) => player.seek(offset),
+            PlayerBackend::Simulated(player) => {
+                say!("[silent] Would seek to {offset:?}");
+                player.seek(offset)
+            }
+        }
+    }
+
+    fn first_sound_at(&self) -> Option<Instant> {
+        match self {
+            PlayerBackend::Rodio(player) => player.first_sound_at(),
+            PlayerBackend::Simulated(player) => player.first_sound_at(),
+        }
+    }
+
+    fn fade_out(&mut self, over: Duration) -> Result<(), PlayerError> {
+        match self {
+            PlayerBackend::Rodio(player) => player.fade_out(over),
+            PlayerBackend::Simulated(player) => {
+                say!("[silent] Would fade out playback over {over:?}");
+                player.fade_out(over)
+            }
+        }
+    }
+
+    fn play_effect(&mut self, effect: &Track) -> Result<(), PlayerError> {
+        match self {
+            PlayerBackend::Rodio(player) => player.play_effect(effect),
+            PlayerBackend::Simulated(player) => {
+                say!("[silent] Would play effect {}", effect.path().display());
+                player.play_effect(effect)
+            }
+        }
+    }
+
+    fn refresh_output(&mut self) -> Option<OutputChange> {
+        match self {
+            PlayerBackend::Rodio(player) => player.refresh_output(),
+            PlayerBackend::Simulated(player) => player.refresh_output(),
         }
     }
 }
@@ -698,33 +4056,121 @@ impl ReaderSelection {
     }
 }
 
-fn select_reader(kind: ReaderKind, poll: Duration) -> Result<ReaderSelection, ReaderError> {
+/// Reader settings from the config file.
+#[derive(Debug, Default)]
+struct ReaderSetup {
+    /// The `[readers]` table; empty polls every attached PC/SC reader.
+    zones: Vec<ReaderZone>,
+    pn532: Option<Pn532Settings>,
+    pcsc: PcscSettings,
+}
+
+impl ReaderSetup {
+    fn from_config(config: &MusicBoxConfig) -> Self {
+        Self {
+            zones: config.reader_zones().to_vec(),
+            pn532: config.pn532().cloned(),
+            pcsc: config.pcsc(),
+        }
+    }
+
+    /// How often the `kind` backend is polled: `--poll-interval-ms` when
+    /// given, then the backend's own table, then the default.
+    fn poll_interval(&self, kind: ReaderKind, overridden: Option<Duration>) -> Duration {
+        let configured = match kind {
+            ReaderKind::Pcsc => self.pcsc.poll_interval,
+            ReaderKind::Pn532 => self.pn532.as_ref().and_then(|pn532| pn532.poll_interval),
+            ReaderKind::Auto | ReaderKind::Noop => None,
+        };
+        overridden.or(configured).unwrap_or(DEFAULT_POLL_INTERVAL)
+    }
+}
+
+/// Opens the reader backend. `auto` tries PC/SC, then a configured PN532,
+/// and falls back to the noop reader. `poll` overrides each backend's own
+/// poll interval.
+fn select_reader(
+    kind: ReaderKind,
+    poll: Option<Duration>,
+    setup: &ReaderSetup,
+) -> Result<ReaderSelection, ReaderError> {
+    let pcsc_poll = setup.poll_interval(ReaderKind::Pcsc, poll);
+    let pn532_poll = setup.poll_interval(ReaderKind::Pn532, poll);
     match kind {
         ReaderKind::Noop => Ok(ReaderSelection::noop()),
-        ReaderKind::Pcsc => {
-            build_pcsc_reader(poll).map(|reader| ReaderSelection::new(ReaderKind::Pcsc, reader))
+        ReaderKind::Pcsc => build_pcsc_reader(pcsc_poll, &setup.zones, setup.pcsc.max_backoff)
+            .map(|reader| ReaderSelection::new(ReaderKind::Pcsc, reader)),
+        ReaderKind::Pn532 => {
+            let settings = setup.pn532.as_ref().ok_or_else(|| {
+                ReaderError::backend("the pn532 reader needs a [pn532] table in the config")
+            })?;
+            build_pn532_reader(pn532_poll, settings)
+                .map(|reader| ReaderSelection::new(ReaderKind::Pn532, reader))
         }
-        ReaderKind::Auto => match build_pcsc_reader(poll) {
-            Ok(reader) => Ok(ReaderSelection::new(ReaderKind::Pcsc, reader)),
-            Err(err) => {
-                tracing::warn!(
-                    ?err,
-                    "PC/SC reader unavailable; falling back to noop reader"
-                );
-                Ok(ReaderSelection::noop())
+        ReaderKind::Auto => {
+            match build_pcsc_reader(pcsc_poll, &setup.zones, setup.pcsc.max_backoff) {
+                Ok(reader) => Ok(ReaderSelection::new(ReaderKind::Pcsc, reader)),
+                Err(err) => {
+                    if let Some(settings) = &setup.pn532 {
+                        tracing::warn!(?err, "PC/SC reader unavailable; trying the PN532");
+                        match build_pn532_reader(pn532_poll, settings) {
+                            Ok(reader) => {
+                                return Ok(ReaderSelection::new(ReaderKind::Pn532, reader));
+                            }
+                            Err(err) => tracing::warn!(
+                                ?err,
+                                "PN532 reader unavailable; falling back to noop reader"
+                            ),
+                        }
+                    } else {
+                        tracing::warn!(
+                            ?err,
+                            "PC/SC reader unavailable; falling back to noop reader"
+                        );
+                    }
+                    Ok(ReaderSelection::noop())
+                }
             }
-        },
+        }
     }
 }
 
+#[cfg(feature = "nfc-pn532")]
+fn build_pn532_reader(
+    poll: Duration,
+    settings: &Pn532Settings,
+) -> Result<Box<dyn NfcReader>, ReaderError> {
+    Ok(Box::new(musicbox::pn532::serial::open(settings, poll)?))
+}
+
+#[cfg(not(feature = "nfc-pn532"))]
+fn build_pn532_reader(
+    _poll: Duration,
+    _settings: &Pn532Settings,
+) -> Result<Box<dyn NfcReader>, ReaderError> {
+    Err(ReaderError::backend(
+        "pn532 support not built; recompile with `--features nfc-pn532`",
+    ))
+}
+
 #[cfg(feature = "nfc-pcsc")]
-fn build_pcsc_reader(poll: Duration) -> Result<Box<dyn NfcReader>, ReaderError> {
-    let reader = musicbox::reader::pcsc_backend::PcscReader::new(poll)?;
+fn build_pcsc_reader(
+    poll: Duration,
+    zones: &[ReaderZone],
+    max_backoff: Duration,
+) -> Result<Box<dyn NfcReader>, ReaderError> {
+    let reader = musicbox::reader::pcsc_backend::PcscReader::new(poll)?
+        .with_zones(zones.to_vec())
+        .with_max_backoff(max_backoff);
     Ok(Box::new(reader))
 }
 
 #[cfg(not(feature = "nfc-pcsc"))]
-fn build_pcsc_reader(_poll: Duration) -> Result<Box<dyn NfcReader>, ReaderError> {
+fn build_pcsc_reader(
+    _poll: Duration,
+    _zones: &[ReaderZone],
+    _max_backoff: Duration,
+) -> Result<Box<dyn NfcReader>, ReaderError> {
     Err(ReaderError::backend(
         "pcsc support not built; recompile with `--features nfc-pcsc`",
     ))
@@ -740,7 +4186,12 @@ mod tests {
         unsafe {
             std::env::set_var("MUSICBOX_NOOP_SHUTDOWN", "1");
         }
-        let selection = select_reader(ReaderKind::Noop, Duration::from_millis(1)).unwrap();
+        let selection = select_reader(
+            ReaderKind::Noop,
+            Some(Duration::from_millis(1)),
+            &ReaderSetup::default(),
+        )
+        .unwrap();
         let mut reader = selection.into_reader();
         let event = reader.next_event().unwrap();
         assert!(matches!(event, ReaderEvent::Shutdown));
@@ -752,9 +4203,37 @@ mod tests {
     #[cfg(not(feature = "nfc-pcsc"))]
     #[test]
     fn select_reader_pcsc_without_feature_errors() {
-        match select_reader(ReaderKind::Pcsc, Duration::from_millis(1)) {
+        match select_reader(
+            ReaderKind::Pcsc,
+            Some(Duration::from_millis(1)),
+            &ReaderSetup::default(),
+        ) {
             Ok(_) => panic!("expected pcsc selection to fail"),
             Err(err) => assert!(matches!(err, ReaderError::Backend { .. })),
         }
     }
+
+    #[test]
+    fn poll_interval_prefers_the_flag_then_the_backend_table() {
+        let setup = ReaderSetup {
+            pcsc: PcscSettings {
+                poll_interval: Some(Duration::from_millis(500)),
+                ..PcscSettings::default()
+            },
+            ..ReaderSetup::default()
+        };
+        let flag = Some(Duration::from_millis(50));
+        assert_eq!(
+            setup.poll_interval(ReaderKind::Pcsc, None),
+            Duration::from_millis(500)
+        );
+        assert_eq!(
+            setup.poll_interval(ReaderKind::Pcsc, flag),
+            Duration::from_millis(50)
+        );
+        assert_eq!(
+            setup.poll_interval(ReaderKind::Pn532, None),
+            DEFAULT_POLL_INTERVAL
+        );
+    }
 }
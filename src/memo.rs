@@ -0,0 +1,278 @@
+//! Voice memos recorded onto cards.
+//!
+//! Tapping the record card arms the box; the next card tapped starts a
+//! recording from the microphone, and tapping that card again (or the record
+//! card, or reaching [`MemoSettings::max_length`]) saves it as a WAV file in
+//! the memo directory and maps the card to it. The capture itself sits
+//! behind [`Recorder`], so the flow works the same with any input backend.
+
+use crate::controller::CardUid;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// How long the box waits for a card after the record card is tapped.
+pub const ARM_TIMEOUT: Duration = Duration::from_secs(30);
+
+#[derive(Debug, thiserror::Error)]
+pub enum RecordError {
+    #[error("microphone error: {message}")]
+    Backend { message: String },
+    #[error("no recording is in progress")]
+    NotRecording,
+    #[error("the recording is empty")]
+    Empty,
+    #[error("failed to save memo {path:?}: {source}")]
+    Io {
+        path: PathBuf,
+        #[source]
+        source: std::io::Error,
+    },
+}
+
+/// The `[recording]` table.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MemoSettings {
+    /// The command card that arms recording.
+    pub card: CardUid,
+    /// Where memos are saved, one `<uid>.wav` per card.
+    pub dir: PathBuf,
+    /// Recordings stop by themselves after this long.
+    pub max_length: Duration,
+}
+
+impl MemoSettings {
+    /// The file a memo for `card` is saved to.
+    pub fn path_for(&self, card: &CardUid) -> PathBuf {
+        self.dir.join(format!("{}.wav", card.to_hex_lowercase()))
+    }
+
+    /// Whether `path` is a memo, as opposed to music the card was mapped to.
+    pub fn is_memo(&self, path: &Path) -> bool {
+        path.starts_with(&self.dir)
+    }
+}
+
+/// Captured audio as interleaved 16-bit samples.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Clip {
+    pub sample_rate: u32,
+    pub channels: u16,
+    pub samples: Vec<i16>,
+}
+
+impl Clip {
+    pub fn duration(&self) -> Duration {
+        let frames = self.samples.len() as u64 / u64::from(self.channels.max(1));
+        Duration::from_millis(frames * 1000 / u64::from(self.sample_rate.max(1)))
+    }
+
+    /// Writes the clip as a PCM WAV file, creating its directory if needed.
+    pub fn write_wav(&self, path: &Path) -> Result<(), RecordError> {
+        let io_err = |source| RecordError::Io {
+            path: path.to_path_buf(),
+            source,
+        };
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).map_err(io_err)?;
+        }
+        fs::write(path, self.to_wav()).map_err(io_err)
+    }
+
+    fn to_wav(&self) -> Vec<u8> {
+        let data_len = (self.samples.len() * 2) as u32;
+        let block_align = self.channels * 2;
+        let byte_rate = self.sample_rate * u32::from(block_align);
+        let mut wav = Vec::with_capacity(44 + data_len as usize);
+        wav.extend_from_slice(b"RIFF");
+        wav.extend_from_slice(&(36 + data_len).to_le_bytes());
+        wav.extend_from_slice(b"WAVEfmt ");
+        wav.extend_from_slice(&16u32.to_le_bytes());
+        wav.extend_from_slice(&1u16.to_le_bytes());
+        wav.extend_from_slice(&self.channels.to_le_bytes());
+        wav.extend_from_slice(&self.sample_rate.to_le_bytes());
+        wav.extend_from_slice(&byte_rate.to_le_bytes());
+        wav.extend_from_slice(&block_align.to_le_bytes());
+        wav.extend_from_slice(&16u16.to_le_bytes());
+        wav.extend_from_slice(b"data");
+        wav.extend_from_slice(&data_len.to_le_bytes());
+        for sample in &self.samples {
+            wav.extend_from_slice(&sample.to_le_bytes());
+        }
+        wav
+    }
+}
+
+/// A microphone.
+pub trait Recorder: Send {
+    /// Starts capturing in the background.
+    fn start(&mut self) -> Result<(), RecordError>;
+    /// Stops capturing and returns everything captured since [`start`].
+    ///
+    /// [`start`]: Recorder::start
+    fn finish(&mut self) -> Result<Clip, RecordError>;
+}
+
+#[cfg(feature = "audio-record")]
+pub mod cpal_input {
+    use super::{Clip, RecordError, Recorder};
+    use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+    use std::sync::mpsc;
+    use std::thread::JoinHandle;
+
+    fn backend_err(err: impl std::fmt::Display) -> RecordError {
+        RecordError::Backend {
+            message: err.to_string(),
+        }
+    }
+
+    /// The stop signal and the thread that returns the recorded clip.
+    type RecordingSession = (mpsc::Sender<()>, JoinHandle<Result<Clip, RecordError>>);
+
+    /// Records from the default input device through cpal. The stream lives
+    /// on its own thread, since cpal streams cannot move between threads.
+    #[derive(Default)]
+    pub struct CpalRecorder {
+        session: Option<RecordingSession>,
+    }
+
+    impl CpalRecorder {
+        pub fn new() -> Self {
+            Self::default()
+        }
+    }
+
+    impl Recorder for CpalRecorder {
+        fn start(&mut self) -> Result<(), RecordError> {
+            let (stop_tx, stop_rx) = mpsc::channel();
+            let (ready_tx, ready_rx) = mpsc::channel();
+            let handle = std::thread::spawn(move || capture(stop_rx, ready_tx));
+            match ready_rx.recv() {
+                Ok(Ok(())) => {
+                    self.session = Some((stop_tx, handle));
+                    Ok(())
+                }
+                Ok(Err(err)) => Err(err),
+                Err(_) => Err(backend_err("capture thread exited")),
+            }
+        }
+
+        fn finish(&mut self) -> Result<Clip, RecordError> {
+            let (stop_tx, handle) = self.session.take().ok_or(RecordError::NotRecording)?;
+            let _ = stop_tx.send(());
+            handle
+                .join()
+                .map_err(|_| backend_err("capture thread panicked"))?
+        }
+    }
+
+    /// Opens the default input, reports readiness on `ready`, and records
+    /// until `stop` fires.
+    fn capture(
+        stop: mpsc::Receiver<()>,
+        ready: mpsc::Sender<Result<(), RecordError>>,
+    ) -> Result<Clip, RecordError> {
+        let opened = open_stream();
+        let (stream, samples_rx, sample_rate, channels) = match opened {
+            Ok(opened) => {
+                let _ = ready.send(Ok(()));
+                opened
+            }
+            Err(err) => {
+                let _ = ready.send(Err(backend_err(&err)));
+                return Err(err);
+            }
+        };
+        let _ = stop.recv();
+        drop(stream);
+        let samples: Vec<i16> = samples_rx.try_iter().flatten().collect();
+        Ok(Clip {
+            sample_rate,
+            channels,
+            samples,
+        })
+    }
+
+    type Opened = (cpal::Stream, mpsc::Receiver<Vec<i16>>, u32, u16);
+
+    fn open_stream() -> Result<Opened, RecordError> {
+        let device = cpal::default_host()
+            .default_input_device()
+            .ok_or_else(|| backend_err("no input device"))?;
+        let supported = device.default_input_config().map_err(backend_err)?;
+        let format = supported.sample_format();
+        let config: cpal::StreamConfig = supported.into();
+        let (tx, rx) = mpsc::channel::<Vec<i16>>();
+        let on_error = |err: cpal::StreamError| tracing::warn!(%err, "microphone stream error");
+        let stream = match format {
+            cpal::SampleFormat::I16 => device.build_input_stream(
+                &config,
+                move |data: &[i16], _: &cpal::InputCallbackInfo| {
+                    let _ = tx.send(data.to_vec());
+                },
+                on_error,
+                None,
+            ),
+            cpal::SampleFormat::U16 => device.build_input_stream(
+                &config,
+                move |data: &[u16], _: &cpal::InputCallbackInfo| {
+                    let _ = tx.send(data.iter().map(|&s| (s as i32 - 32_768) as i16).collect());
+                },
+                on_error,
+                None,
+            ),
+            cpal::SampleFormat::F32 => device.build_input_stream(
+                &config,
+                move |data: &[f32], _: &cpal::InputCallbackInfo| {
+                    let _ = tx.send(
+                        data.iter()
+                            .map(|&s| (s.clamp(-1.0, 1.0) * f32::from(i16::MAX)) as i16)
+                            .collect(),
+                    );
+                },
+                on_error,
+                None,
+            ),
+            other => return Err(backend_err(format!("unsupported sample format {other}"))),
+        }
+        .map_err(backend_err)?;
+        stream.play().map_err(backend_err)?;
+        Ok((stream, rx, config.sample_rate.0, config.channels))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn clips_are_written_as_pcm_wav() {
+        let clip = Clip {
+            sample_rate: 8_000,
+            channels: 1,
+            samples: vec![0, 1, -1, i16::MAX],
+        };
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("memos/0a0b.wav");
+        clip.write_wav(&path).unwrap();
+
+        let wav = fs::read(&path).unwrap();
+        assert_eq!(wav.len(), 44 + 8);
+        assert_eq!(&wav[..4], b"RIFF");
+        assert_eq!(u32::from_le_bytes(wav[4..8].try_into().unwrap()), 36 + 8);
+        assert_eq!(&wav[8..16], b"WAVEfmt ");
+        assert_eq!(u32::from_le_bytes(wav[24..28].try_into().unwrap()), 8_000);
+        assert_eq!(&wav[36..40], b"data");
+        assert_eq!(&wav[44..46], &[0, 0]);
+        assert_eq!(&wav[48..50], &[0xff, 0xff]);
+        assert_eq!(
+            Clip {
+                samples: vec![0; 16_000],
+                ..clip
+            }
+            .duration(),
+            Duration::from_secs(2)
+        );
+    }
+}
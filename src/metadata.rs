@@ -0,0 +1,292 @@
+//! What audio files say about themselves, read from their headers without
+//! decoding the audio.
+
+use crate::controller::Track;
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use symphonia::core::codecs::DecoderOptions;
+use symphonia::core::formats::{FormatOptions, FormatReader};
+use symphonia::core::io::MediaSourceStream;
+use symphonia::core::meta::{MetadataOptions, MetadataRevision, StandardTagKey};
+use symphonia::core::probe::{Hint, ProbeResult};
+use symphonia::core::units::TimeBase;
+
+#[derive(Debug, thiserror::Error)]
+pub enum MetadataError {
+    #[error("failed to open {path:?}: {source}")]
+    Open {
+        path: PathBuf,
+        #[source]
+        source: std::io::Error,
+    },
+    #[error("unreadable audio file {path:?}: {source}")]
+    Probe {
+        path: PathBuf,
+        #[source]
+        source: symphonia::core::errors::Error,
+    },
+    #[error("{0:?} has no audio track")]
+    NoAudio(PathBuf),
+    #[error("{0:?} does not say how long it is")]
+    UnknownDuration(PathBuf),
+}
+
+/// Checks that the file at `path` is audio this build can decode: its
+/// container is recognised and a decoder exists for its codec.
+pub fn probe(path: &Path) -> Result<(), MetadataError> {
+    let format = open(path)?;
+    let params = &format
+        .default_track()
+        .ok_or_else(|| MetadataError::NoAudio(path.to_path_buf()))?
+        .codec_params;
+    symphonia::default::get_codecs()
+        .make(params, &DecoderOptions::default())
+        .map_err(|source| MetadataError::Probe {
+            path: path.to_path_buf(),
+            source,
+        })?;
+    Ok(())
+}
+
+/// How long the audio file at `path` plays, from its container headers.
+/// Files that do not record a length, such as MP3s without a Xing header,
+/// give [`MetadataError::UnknownDuration`].
+pub fn duration(path: &Path) -> Result<Duration, MetadataError> {
+    let format = open(path)?;
+    let time = format
+        .default_track()
+        .and_then(|track| {
+            let params = &track.codec_params;
+            let frames = params.n_frames?;
+            let time_base = params
+                .time_base
+                .or_else(|| params.sample_rate.map(|rate| TimeBase::new(1, rate)))?;
+            Some(time_base.calc_time(frames))
+        })
+        .ok_or_else(|| MetadataError::UnknownDuration(path.to_path_buf()))?;
+    Ok(Duration::from_secs(time.seconds) + Duration::from_secs_f64(time.frac))
+}
+
+/// The names an audio file's tags give it.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Tags {
+    pub title: Option<String>,
+    pub artist: Option<String>,
+    pub album: Option<String>,
+}
+
+impl Tags {
+    fn read(&mut self, revision: &MetadataRevision) {
+        for tag in revision.tags() {
+            let field = match tag.std_key {
+                Some(StandardTagKey::TrackTitle) => &mut self.title,
+                Some(StandardTagKey::Artist) => &mut self.artist,
+                Some(StandardTagKey::Album) => &mut self.album,
+                _ => continue,
+            };
+            // RIFF INFO values keep their NUL terminator.
+            let value = tag.value.to_string();
+            let value = value.trim_matches(|c: char| c == '\0' || c.is_whitespace());
+            if field.is_none() && !value.is_empty() {
+                *field = Some(value.to_string());
+            }
+        }
+    }
+}
+
+/// The title, artist, and album tagged in the audio file at `path`, from
+/// ID3 tags ahead of the audio as well as the container's own.
+pub fn tags(path: &Path) -> Result<Tags, MetadataError> {
+    let mut probed = open_probed(path)?;
+    let mut tags = Tags::default();
+    if let Some(revision) = probed.format.metadata().current() {
+        tags.read(revision);
+    }
+    if let Some(revision) = probed
+        .metadata
+        .get()
+        .as_ref()
+        .and_then(|metadata| metadata.current())
+    {
+        tags.read(revision);
+    }
+    Ok(tags)
+}
+
+/// What [`check_track`] found behind a card's track.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TrackCheck {
+    /// Audio files that decode, a folder counting each of its files.
+    pub playable: usize,
+    /// How long those files play together, or `None` when one of them does
+    /// not record its length.
+    pub duration: Option<Duration>,
+    /// One line for each file that is missing or cannot be decoded.
+    pub problems: Vec<String>,
+}
+
+impl TrackCheck {
+    /// True when a tap would play every file of the track.
+    pub fn is_ok(&self) -> bool {
+        self.playable > 0 && self.problems.is_empty()
+    }
+}
+
+/// Checks a track the way a tap would play it: a file, or each audio file
+/// of a folder, must exist and decode. Their lengths are added up.
+pub fn check_track(track: &Track) -> TrackCheck {
+    let files = track.expand();
+    let mut check = TrackCheck {
+        duration: Some(Duration::ZERO),
+        ..TrackCheck::default()
+    };
+    if files.is_empty() {
+        check
+            .problems
+            .push(format!("{:?} holds no audio files", track.path()));
+    }
+    for file in &files {
+        if let Err(err) = probe(file.path()) {
+            check.problems.push(err.to_string());
+            continue;
+        }
+        check.playable += 1;
+        check.duration = check
+            .duration
+            .zip(duration(file.path()).ok())
+            .map(|(total, length)| total + length);
+    }
+    if check.playable == 0 {
+        check.duration = None;
+    }
+    check
+}
+
+/// Opens the file at `path` and reads its container headers.
+pub(crate) fn open(path: &Path) -> Result<Box<dyn FormatReader>, MetadataError> {
+    Ok(open_probed(path)?.format)
+}
+
+fn open_probed(path: &Path) -> Result<ProbeResult, MetadataError> {
+    let file = File::open(path).map_err(|source| MetadataError::Open {
+        path: path.to_path_buf(),
+        source,
+    })?;
+    let mut hint = Hint::new();
+    if let Some(extension) = path.extension().and_then(|extension| extension.to_str()) {
+        hint.with_extension(extension);
+    }
+    symphonia::default::get_probe()
+        .format(
+            &hint,
+            MediaSourceStream::new(Box::new(file), Default::default()),
+            &FormatOptions::default(),
+            &MetadataOptions::default(),
+        )
+        .map_err(|source| MetadataError::Probe {
+            path: path.to_path_buf(),
+            source,
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::memo::Clip;
+
+    #[test]
+    fn probes_wav_files_and_reads_their_length() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("tone.wav");
+        Clip {
+            sample_rate: 8_000,
+            channels: 2,
+            samples: vec![0; 8_000 * 2 * 3 / 2],
+        }
+        .write_wav(&path)
+        .unwrap();
+        assert_eq!(duration(&path).unwrap(), Duration::from_millis(1_500));
+        assert!(probe(&path).is_ok());
+
+        std::fs::write(dir.path().join("noise.mp3"), b"not audio").unwrap();
+        assert!(matches!(
+            duration(&dir.path().join("noise.mp3")),
+            Err(MetadataError::Probe { .. })
+        ));
+        assert!(probe(&dir.path().join("noise.mp3")).is_err());
+        assert!(matches!(
+            duration(&dir.path().join("missing.ogg")),
+            Err(MetadataError::Open { .. })
+        ));
+    }
+
+    #[test]
+    fn reads_title_artist_and_album_tags() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("tagged.wav");
+        Clip {
+            sample_rate: 8_000,
+            channels: 1,
+            samples: vec![0; 800],
+        }
+        .write_wav(&path)
+        .unwrap();
+        // A RIFF INFO list goes ahead of the data chunk, which starts at 36.
+        let mut info = b"INFO".to_vec();
+        for (id, value) in [(b"INAM", "Moon Song "), (b"IART", "Ada"), (b"IPRD", "")] {
+            let mut value = value.as_bytes().to_vec();
+            value.push(0);
+            info.extend_from_slice(id);
+            info.extend_from_slice(&(value.len() as u32).to_le_bytes());
+            if value.len() % 2 == 1 {
+                value.push(0);
+            }
+            info.extend_from_slice(&value);
+        }
+        let mut wav = std::fs::read(&path).unwrap();
+        let mut list = b"LIST".to_vec();
+        list.extend_from_slice(&(info.len() as u32).to_le_bytes());
+        list.extend_from_slice(&info);
+        let riff_len = (wav.len() + list.len() - 8) as u32;
+        wav.splice(36..36, list);
+        wav[4..8].copy_from_slice(&riff_len.to_le_bytes());
+        std::fs::write(&path, wav).unwrap();
+
+        let tags = tags(&path).unwrap();
+        assert_eq!(tags.title.as_deref(), Some("Moon Song"));
+        assert_eq!(tags.artist.as_deref(), Some("Ada"));
+        assert_eq!(tags.album, None);
+        assert!(probe(&path).is_ok());
+    }
+
+    #[test]
+    fn checks_every_file_of_a_folder_track() {
+        let dir = tempfile::tempdir().unwrap();
+        let album = dir.path().join("album");
+        std::fs::create_dir(&album).unwrap();
+        let clip = Clip {
+            sample_rate: 8_000,
+            channels: 1,
+            samples: vec![0; 8_000],
+        };
+        clip.write_wav(&album.join("1.wav")).unwrap();
+        clip.write_wav(&album.join("2.wav")).unwrap();
+
+        let check = check_track(&Track::new(album.clone()));
+        assert!(check.is_ok());
+        assert_eq!(check.playable, 2);
+        assert_eq!(check.duration, Some(Duration::from_secs(2)));
+
+        std::fs::write(album.join("3.mp3"), b"not audio").unwrap();
+        let check = check_track(&Track::new(album));
+        assert!(!check.is_ok());
+        assert_eq!(check.playable, 2);
+        assert_eq!(check.problems.len(), 1);
+
+        let check = check_track(&Track::new(dir.path().join("missing.wav")));
+        assert!(!check.is_ok());
+        assert_eq!(check.duration, None);
+        assert!(check.problems[0].contains("failed to open"));
+    }
+}
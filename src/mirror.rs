@@ -0,0 +1,152 @@
+//! A second copy of the config and state files, kept on a USB stick or a
+//! network mount in case the SD card's copies are lost or corrupted.
+//!
+//! Once a mirror is installed, every successful write of the config, the
+//! resume database, the history log, or podcast progress is followed by a
+//! copy into the mirror directory under the file's own name. Copies go
+//! through a temporary file and a rename, like the writes they follow, so
+//! a stick pulled mid-copy keeps its previous version.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+static INSTALLED: OnceLock<Mirror> = OnceLock::new();
+
+#[derive(Debug, thiserror::Error)]
+pub enum MirrorError {
+    #[error("the mirror has no copy of {0:?}")]
+    Missing(PathBuf),
+    #[error("failed to restore {path:?} from the mirror: {source}")]
+    Io {
+        path: PathBuf,
+        #[source]
+        source: io::Error,
+    },
+}
+
+/// A directory holding the latest good copy of each mirrored file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Mirror {
+    dir: PathBuf,
+}
+
+impl Mirror {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Where the mirror keeps its copy of `path`.
+    pub fn copy_of(&self, path: &Path) -> Option<PathBuf> {
+        Some(self.dir.join(path.file_name()?))
+    }
+
+    /// Copies `path` into the mirror. The directory is not created: a
+    /// missing mount point means the stick is unplugged, and writing there
+    /// would only fill the SD card.
+    pub fn update(&self, path: &Path) -> io::Result<()> {
+        let target = self
+            .copy_of(path)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "path has no file name"))?;
+        copy_atomically(path, &target)
+    }
+
+    /// Replaces `path` with the mirror's copy. The file it replaces is
+    /// moved aside to `<name>.corrupt` rather than deleted; its new path is
+    /// returned when there was one.
+    pub fn restore(&self, path: &Path) -> Result<Option<PathBuf>, MirrorError> {
+        let io_err = |source| MirrorError::Io {
+            path: path.to_path_buf(),
+            source,
+        };
+        let copy = self
+            .copy_of(path)
+            .filter(|copy| copy.is_file())
+            .ok_or_else(|| MirrorError::Missing(path.to_path_buf()))?;
+        let aside = if path.exists() {
+            let mut aside = path.to_path_buf().into_os_string();
+            aside.push(".corrupt");
+            let aside = PathBuf::from(aside);
+            fs::rename(path, &aside).map_err(io_err)?;
+            Some(aside)
+        } else {
+            None
+        };
+        copy_atomically(&copy, path).map_err(io_err)?;
+        tracing::info!(path = %path.display(), copy = %copy.display(), "restored from mirror");
+        Ok(aside)
+    }
+}
+
+/// Makes `mirror` the one [`update`] copies into, for the rest of the
+/// process. Only the first call counts.
+pub fn install(mirror: Mirror) {
+    if INSTALLED.set(mirror).is_err() {
+        tracing::warn!("a mirror is already installed; ignoring another");
+    }
+}
+
+pub fn installed() -> Option<&'static Mirror> {
+    INSTALLED.get()
+}
+
+/// Copies `path` into the installed mirror after a successful write. A
+/// failure is logged rather than returned: the primary copy is safe, and
+/// an unplugged stick should not stop the box.
+pub fn update(path: &Path) {
+    let Some(mirror) = installed() else {
+        return;
+    };
+    if let Err(err) = mirror.update(path) {
+        tracing::warn!(%err, path = %path.display(), mirror = %mirror.dir().display(), "failed to update mirror");
+    }
+}
+
+fn copy_atomically(from: &Path, to: &Path) -> io::Result<()> {
+    let mut temporary = to.to_path_buf().into_os_string();
+    temporary.push(".tmp");
+    fs::copy(from, &temporary)?;
+    fs::rename(&temporary, to)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn updates_copies_and_restores_over_a_corrupt_file() {
+        let primary = tempdir().unwrap();
+        let stick = tempdir().unwrap();
+        let mirror = Mirror::new(stick.path());
+        let config = primary.path().join("musicbox.toml");
+        fs::write(&config, "music_dir = \"/music\"\n").unwrap();
+        mirror.update(&config).unwrap();
+        assert_eq!(
+            fs::read_to_string(stick.path().join("musicbox.toml")).unwrap(),
+            "music_dir = \"/music\"\n"
+        );
+
+        fs::write(&config, "music_dir = \"/mu").unwrap();
+        let aside = mirror.restore(&config).unwrap().unwrap();
+        assert_eq!(
+            fs::read_to_string(&config).unwrap(),
+            "music_dir = \"/music\"\n"
+        );
+        assert_eq!(fs::read_to_string(aside).unwrap(), "music_dir = \"/mu");
+
+        let missing = primary.path().join("resume.json");
+        assert!(matches!(
+            mirror.restore(&missing),
+            Err(MirrorError::Missing(_))
+        ));
+        // An unplugged stick is an error for the caller to log.
+        let unplugged = Mirror::new(stick.path().join("gone"));
+        assert!(unplugged.update(&config).is_err());
+    }
+}
@@ -0,0 +1,116 @@
+//! Night mode: a quieter, compressed profile for late-evening listening.
+//!
+//! While night mode is on, the controller caps the listener's volume at
+//! [`NightSettings::max_volume`] and asks the audio backend to run its
+//! compressor, so sudden loud passages do not wake the household. It is
+//! switched by a command card, or by a schedule in the box's local time.
+
+use std::time::SystemTime;
+
+/// Minutes in a day, the range of [`local_minute_of_day`].
+pub(crate) const MINUTES_PER_DAY: u16 = 24 * 60;
+
+/// The `[night]` table.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct NightSettings {
+    /// Highest volume, from 0.0 to 1.0, while night mode is on.
+    pub max_volume: f32,
+    pub schedule: Option<NightSchedule>,
+}
+
+impl Default for NightSettings {
+    fn default() -> Self {
+        Self {
+            max_volume: 0.5,
+            schedule: None,
+        }
+    }
+}
+
+/// When night mode switches itself on and off, as minutes after local
+/// midnight. A window may run past midnight, such as 19:30 to 07:00.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NightSchedule {
+    pub from: u16,
+    pub until: u16,
+}
+
+impl NightSchedule {
+    /// Builds a schedule from hours and minutes; `None` if either time is
+    /// not a valid time of day or both are the same.
+    pub fn from_times(from: (u8, u8), until: (u8, u8)) -> Option<Self> {
+        let minute = |(hour, minute): (u8, u8)| {
+            (hour < 24 && minute < 60).then(|| u16::from(hour) * 60 + u16::from(minute))
+        };
+        let (from, until) = (minute(from)?, minute(until)?);
+        (from != until).then_some(Self { from, until })
+    }
+
+    /// Whether `minute` (after local midnight) falls inside the window.
+    pub fn contains(&self, minute: u16) -> bool {
+        if self.from < self.until {
+            (self.from..self.until).contains(&minute)
+        } else {
+            minute >= self.from || minute < self.until
+        }
+    }
+}
+
+/// Minutes after local midnight at `at`, honouring the system time zone and
+/// daylight saving. Platforms without `localtime_r` use UTC.
+pub fn local_minute_of_day(at: SystemTime) -> u16 {
+    (local_minutes(at) % u64::from(MINUTES_PER_DAY)) as u16
+}
+
+/// Minutes since the Unix epoch at `at`, counted in local time, so that
+/// dividing by a day's minutes gives the local date.
+pub fn local_minutes(at: SystemTime) -> u64 {
+    let secs = at
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map_or(0, |since| since.as_secs());
+    let offset = utc_offset_secs(secs);
+    secs.saturating_add_signed(offset) / 60
+}
+
+#[cfg(unix)]
+fn utc_offset_secs(secs: u64) -> i64 {
+    let time = secs as libc::time_t;
+    // SAFETY: `localtime_r` only writes to the `tm` we own, and the zeroed
+    // `tm` is a valid value for it to overwrite.
+    let mut tm: libc::tm = unsafe { std::mem::zeroed() };
+    if unsafe { libc::localtime_r(&time, &mut tm) }.is_null() {
+        return 0;
+    }
+    tm.tm_gmtoff
+}
+
+#[cfg(not(unix))]
+fn utc_offset_secs(_secs: u64) -> i64 {
+    0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn schedules_can_run_past_midnight() {
+        let evening = NightSchedule::from_times((19, 30), (7, 0)).unwrap();
+        assert!(evening.contains(19 * 60 + 30));
+        assert!(evening.contains(2 * 60));
+        assert!(!evening.contains(7 * 60));
+        assert!(!evening.contains(12 * 60));
+
+        let nap = NightSchedule::from_times((13, 0), (15, 0)).unwrap();
+        assert!(nap.contains(14 * 60));
+        assert!(!nap.contains(16 * 60));
+
+        assert_eq!(NightSchedule::from_times((24, 0), (7, 0)), None);
+        assert_eq!(NightSchedule::from_times((7, 0), (7, 0)), None);
+    }
+
+    #[test]
+    fn local_minutes_stay_within_a_day() {
+        assert!(local_minute_of_day(SystemTime::now()) < MINUTES_PER_DAY);
+    }
+}
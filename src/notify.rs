@@ -0,0 +1,463 @@
+//! Push notifications to a parent's phone through ntfy, Pushover, or any
+//! JSON webhook.
+//!
+//! Pushes go out over the HTTP client in [`crate::webhook`]. Pushover's API
+//! only speaks HTTPS, so it needs a build with the `https` feature.
+
+use crate::controller::CardUid;
+use crate::events::EventStamp;
+use crate::telemetry::ReaderHealth;
+use crate::webhook::{self, WebhookError, WebhookUrl};
+use serde_json::{Value, json};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Repeats of the same notification within this window are dropped, so a
+/// child tapping an unknown card over and over sends one push.
+pub const NOTIFY_COOLDOWN: Duration = Duration::from_secs(60);
+
+const TITLE: &str = "Musicbox";
+
+/// Pushover's message API.
+pub const PUSHOVER_API: &str = "https://api.pushover.net/1/messages.json";
+
+/// How often [`BatteryWatch::spawn`] reads the battery level.
+pub const BATTERY_POLL_INTERVAL: Duration = Duration::from_secs(60);
+
+/// The level, in percent, at or below which the battery counts as low.
+pub const DEFAULT_LOW_BATTERY_PERCENT: u8 = 15;
+
+/// How far above the low level the battery must charge before it can warn
+/// again, so a level wavering around the threshold sends one push.
+const LOW_BATTERY_RECOVERY: u8 = 5;
+
+/// Where pushes are delivered.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PushService {
+    /// An ntfy server, posted to with ntfy's JSON publishing API.
+    Ntfy { server: WebhookUrl, topic: String },
+    /// Pushover's message API at [`PUSHOVER_API`].
+    Pushover { token: String, user: String },
+    /// Any endpoint that accepts a JSON body.
+    Webhook { url: WebhookUrl },
+}
+
+/// Which events send a push.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NotifyEvents {
+    pub unknown_card: bool,
+    pub reader_offline: bool,
+    /// Only has an effect with a [`BatteryWatch`] to report the level.
+    pub low_battery: bool,
+}
+
+impl Default for NotifyEvents {
+    fn default() -> Self {
+        Self {
+            unknown_card: true,
+            reader_offline: true,
+            low_battery: true,
+        }
+    }
+}
+
+/// Something worth a push.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Notification {
+    /// A card that is not in the library, or has expired, was tapped.
+    UnknownCard { card: CardUid },
+    /// The reader error policy gave up on the reader.
+    ReaderOffline { error: Option<String> },
+    /// The battery ran down to its low level.
+    LowBattery { percent: u8 },
+}
+
+impl Notification {
+    /// The notification for a reader health change, if it is worth one.
+    pub fn from_reader_health(health: &ReaderHealth) -> Option<Self> {
+        match health {
+            ReaderHealth::FellBack { .. } | ReaderHealth::Failed { .. } => {
+                Some(Notification::ReaderOffline {
+                    error: health.last_error().map(str::to_string),
+                })
+            }
+            ReaderHealth::Healthy | ReaderHealth::Retrying { .. } => None,
+        }
+    }
+
+    fn event(&self) -> &'static str {
+        match self {
+            Notification::UnknownCard { .. } => "unknown_card",
+            Notification::ReaderOffline { .. } => "reader_offline",
+            Notification::LowBattery { .. } => "low_battery",
+        }
+    }
+
+    /// Identifies repeats for [`NOTIFY_COOLDOWN`].
+    fn dedup_key(&self) -> String {
+        match self {
+            Notification::UnknownCard { card } => format!("unknown_card:{card}"),
+            Notification::ReaderOffline { .. } => "reader_offline".to_string(),
+            Notification::LowBattery { .. } => "low_battery".to_string(),
+        }
+    }
+
+    fn message(&self) -> String {
+        match self {
+            Notification::UnknownCard { card } => format!("Unknown card {card} was tapped."),
+            Notification::ReaderOffline { error: Some(error) } => {
+                format!("The card reader stopped working: {error}")
+            }
+            Notification::ReaderOffline { error: None } => {
+                "The card reader stopped working.".to_string()
+            }
+            Notification::LowBattery { percent } => {
+                format!("The battery is low: {percent}% left.")
+            }
+        }
+    }
+}
+
+/// Sends pushes for the enabled events.
+#[derive(Debug, Clone)]
+pub struct Notifier {
+    service: PushService,
+    events: NotifyEvents,
+    battery: Option<BatteryWatch>,
+    last_sent: Arc<Mutex<HashMap<String, Instant>>>,
+}
+
+impl Notifier {
+    pub fn new(service: PushService, events: NotifyEvents) -> Self {
+        Self {
+            service,
+            events,
+            battery: None,
+            last_sent: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// Watches `battery` for [`Notification::LowBattery`] pushes.
+    pub fn with_battery(mut self, battery: BatteryWatch) -> Self {
+        self.battery = Some(battery);
+        self
+    }
+
+    pub fn battery(&self) -> Option<&BatteryWatch> {
+        self.battery.as_ref()
+    }
+
+    pub fn service(&self) -> &PushService {
+        &self.service
+    }
+
+    pub fn events(&self) -> NotifyEvents {
+        self.events
+    }
+
+    /// Sends `notification` if its event is enabled and it was not sent
+    /// within [`NOTIFY_COOLDOWN`]. Failures are logged.
+    pub fn notify(&self, notification: &Notification) {
+        if !self.should_send(notification, Instant::now()) {
+            return;
+        }
+        if let Err(err) = self.send(notification) {
+            tracing::warn!(%err, event = notification.event(), "failed to send push");
+        }
+    }
+
+    /// Like [`Notifier::notify`], but on a background thread, so a slow push
+    /// service never delays the caller.
+    pub fn notify_in_background(&self, notification: Notification) {
+        let notifier = self.clone();
+        std::thread::spawn(move || notifier.notify(&notification));
+    }
+
+    /// Delivers `notification` now, regardless of toggles and cooldown.
+    pub fn send(&self, notification: &Notification) -> Result<(), WebhookError> {
+        let (url, payload) = self.request(notification, &EventStamp::next())?;
+        webhook::post_json(&url, &payload)
+    }
+
+    fn should_send(&self, notification: &Notification, now: Instant) -> bool {
+        let enabled = match notification {
+            Notification::UnknownCard { .. } => self.events.unknown_card,
+            Notification::ReaderOffline { .. } => self.events.reader_offline,
+            Notification::LowBattery { .. } => self.events.low_battery,
+        };
+        if !enabled {
+            return false;
+        }
+        let mut last_sent = self.last_sent.lock().expect("notifier lock");
+        let key = notification.dedup_key();
+        if last_sent
+            .get(&key)
+            .is_some_and(|sent| now.duration_since(*sent) < NOTIFY_COOLDOWN)
+        {
+            return false;
+        }
+        last_sent.insert(key, now);
+        true
+    }
+
+    /// The URL and body of the push. Webhook bodies carry `stamp`, so a
+    /// receiver can tell a repeated delivery from a new event.
+    fn request(
+        &self,
+        notification: &Notification,
+        stamp: &EventStamp,
+    ) -> Result<(WebhookUrl, Value), WebhookError> {
+        let message = notification.message();
+        Ok(match &self.service {
+            PushService::Ntfy { server, topic } => (
+                server.clone(),
+                json!({ "topic": topic, "title": TITLE, "message": message }),
+            ),
+            PushService::Pushover { token, user } => (
+                WebhookUrl::parse(PUSHOVER_API)?,
+                json!({ "token": token, "user": user, "title": TITLE, "message": message }),
+            ),
+            PushService::Webhook { url } => {
+                let mut payload = json!({
+                    "event": notification.event(),
+                    "message": message,
+                    "id": stamp.id,
+                    "sequence": stamp.sequence,
+                    "timestamp_ms": stamp.timestamp_ms,
+                });
+                match notification {
+                    Notification::UnknownCard { card } => {
+                        payload["card"] = json!(card.to_string());
+                    }
+                    Notification::LowBattery { percent } => payload["percent"] = json!(percent),
+                    Notification::ReaderOffline { .. } => {}
+                }
+                (url.clone(), payload)
+            }
+        })
+    }
+}
+
+/// Watches the charge of a battery, such as a UPS HAT's, through a file
+/// holding its level in percent like `/sys/class/power_supply/BAT0/capacity`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BatteryWatch {
+    pub path: PathBuf,
+    /// At or below this level the battery counts as low.
+    pub low_percent: u8,
+}
+
+impl BatteryWatch {
+    /// Reads the battery level.
+    pub fn level(&self) -> std::io::Result<u8> {
+        let contents = std::fs::read_to_string(&self.path)?;
+        contents.trim().parse().map_err(|_| {
+            std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!("{:?} is not a battery level", contents.trim()),
+            )
+        })
+    }
+
+    /// Reads the level every `interval` on a background thread and pushes
+    /// through `notifier` once when it falls to the low level. Another push
+    /// waits until the battery has charged back above that. A file that
+    /// cannot be read is logged once until it can be again.
+    pub fn spawn(self, notifier: Notifier, interval: Duration) -> std::thread::JoinHandle<()> {
+        std::thread::spawn(move || {
+            let mut alarm = LowBatteryAlarm::new(self.low_percent);
+            let mut failing = false;
+            loop {
+                match self.level() {
+                    Ok(percent) => {
+                        failing = false;
+                        if let Some(notification) = alarm.check(percent) {
+                            notifier.notify(&notification);
+                        }
+                    }
+                    Err(err) if !failing => {
+                        failing = true;
+                        tracing::warn!(%err, path = ?self.path, "failed to read the battery level");
+                    }
+                    Err(_) => {}
+                }
+                std::thread::sleep(interval);
+            }
+        })
+    }
+}
+
+/// Decides which battery readings are worth a push.
+#[derive(Debug)]
+struct LowBatteryAlarm {
+    low_percent: u8,
+    warned: bool,
+}
+
+impl LowBatteryAlarm {
+    fn new(low_percent: u8) -> Self {
+        Self {
+            low_percent,
+            warned: false,
+        }
+    }
+
+    fn check(&mut self, percent: u8) -> Option<Notification> {
+        if percent <= self.low_percent {
+            if self.warned {
+                return None;
+            }
+            self.warned = true;
+            return Some(Notification::LowBattery { percent });
+        }
+        if percent > self.low_percent.saturating_add(LOW_BATTERY_RECOVERY) {
+            self.warned = false;
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ntfy(events: NotifyEvents) -> Notifier {
+        Notifier::new(
+            PushService::Ntfy {
+                server: WebhookUrl::parse("http://ntfy.sh").unwrap(),
+                topic: "kids-box".into(),
+            },
+            events,
+        )
+    }
+
+    #[test]
+    fn toggles_and_cooldown_limit_pushes() {
+        let notifier = ntfy(NotifyEvents {
+            unknown_card: true,
+            reader_offline: false,
+            low_battery: true,
+        });
+        let card = |hex| Notification::UnknownCard {
+            card: CardUid::from_hex(hex).unwrap(),
+        };
+        let start = Instant::now();
+
+        assert!(notifier.should_send(&card("0a0b"), start));
+        assert!(!notifier.should_send(&card("0a0b"), start + Duration::from_secs(5)));
+        assert!(notifier.should_send(&card("0c0d"), start + Duration::from_secs(5)));
+        assert!(notifier.should_send(&card("0a0b"), start + NOTIFY_COOLDOWN));
+        assert!(!notifier.should_send(&Notification::ReaderOffline { error: None }, start));
+    }
+
+    #[test]
+    fn payloads_match_each_service() {
+        let offline = Notification::ReaderOffline {
+            error: Some("no such device".into()),
+        };
+        let stamp = EventStamp::next();
+        let (_, payload) = ntfy(NotifyEvents::default())
+            .request(&offline, &stamp)
+            .unwrap();
+        assert_eq!(
+            payload,
+            json!({
+                "topic": "kids-box",
+                "title": "Musicbox",
+                "message": "The card reader stopped working: no such device",
+            })
+        );
+
+        let webhook = Notifier::new(
+            PushService::Webhook {
+                url: WebhookUrl::parse("http://hass.local/hook").unwrap(),
+            },
+            NotifyEvents::default(),
+        );
+        let unknown = Notification::UnknownCard {
+            card: CardUid::from_hex("0a0b").unwrap(),
+        };
+        let (_, payload) = webhook.request(&unknown, &stamp).unwrap();
+        assert_eq!(payload["event"], "unknown_card");
+        assert_eq!(payload["card"], "0a0b");
+        assert_eq!(payload["id"], stamp.id.as_str());
+        assert_eq!(payload["sequence"], stamp.sequence);
+        assert_eq!(payload["timestamp_ms"], stamp.timestamp_ms);
+
+        let (_, payload) = webhook
+            .request(&Notification::LowBattery { percent: 12 }, &stamp)
+            .unwrap();
+        assert_eq!(payload["event"], "low_battery");
+        assert_eq!(payload["percent"], 12);
+        assert_eq!(payload["message"], "The battery is low: 12% left.");
+    }
+
+    #[cfg(feature = "https")]
+    #[test]
+    fn pushover_posts_straight_to_its_api() {
+        let pushover = Notifier::new(
+            PushService::Pushover {
+                token: "app".into(),
+                user: "parent".into(),
+            },
+            NotifyEvents::default(),
+        );
+        let (url, payload) = pushover
+            .request(
+                &Notification::LowBattery { percent: 9 },
+                &EventStamp::next(),
+            )
+            .unwrap();
+        assert_eq!(url, WebhookUrl::parse(PUSHOVER_API).unwrap());
+        assert_eq!(payload["token"], "app");
+        assert_eq!(payload["user"], "parent");
+    }
+
+    #[test]
+    fn low_battery_warns_once_until_it_charges_back() {
+        let mut alarm = LowBatteryAlarm::new(15);
+        let low = |percent| Some(Notification::LowBattery { percent });
+        assert_eq!(alarm.check(40), None);
+        assert_eq!(alarm.check(15), low(15));
+        assert_eq!(alarm.check(12), None);
+        assert_eq!(alarm.check(18), None);
+        assert_eq!(alarm.check(14), None);
+        assert_eq!(alarm.check(30), None);
+        assert_eq!(alarm.check(10), low(10));
+    }
+
+    #[test]
+    fn battery_level_reads_a_capacity_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let battery = BatteryWatch {
+            path: dir.path().join("capacity"),
+            low_percent: DEFAULT_LOW_BATTERY_PERCENT,
+        };
+        assert!(battery.level().is_err());
+        std::fs::write(&battery.path, "87\n").unwrap();
+        assert_eq!(battery.level().unwrap(), 87);
+        std::fs::write(&battery.path, "Full\n").unwrap();
+        assert!(battery.level().is_err());
+    }
+
+    #[test]
+    fn only_giving_up_on_the_reader_notifies() {
+        assert_eq!(
+            Notification::from_reader_health(&ReaderHealth::Retrying {
+                consecutive_errors: 1,
+                last_error: "timeout".into(),
+            }),
+            None
+        );
+        assert_eq!(
+            Notification::from_reader_health(&ReaderHealth::Failed {
+                last_error: "gone".into(),
+            }),
+            Some(Notification::ReaderOffline {
+                error: Some("gone".into())
+            })
+        );
+    }
+}
@@ -0,0 +1,465 @@
+//! A reader backend for NXP PN532 boards wired to a serial port.
+//!
+//! Hobbyist builds often use a PN532 breakout on the Raspberry Pi's UART
+//! instead of a USB PC/SC reader. [`Pn532Reader`] speaks the chip's HSU
+//! (high speed UART) framing directly: it wakes the chip, polls for ISO
+//! 14443A tags with `InListPassiveTarget`, and reads NDEF text from Type 2
+//! tags with `InDataExchange`.
+//!
+//! The reader works over any byte stream so the protocol can be exercised
+//! without hardware; opening a real serial port sits behind the `nfc-pn532`
+//! feature.
+
+use crate::controller::CardUid;
+use crate::reader::{NfcReader, ReaderCapabilities, ReaderError, ReaderEvent, ndef};
+use std::io::{Read, Write};
+use std::path::PathBuf;
+use std::time::Duration;
+
+/// Baud rate the PN532 uses after power-up.
+pub const DEFAULT_BAUD: u32 = 115_200;
+
+/// Serial speeds the PN532's HSU interface supports.
+pub const SUPPORTED_BAUDS: &[u32] = &[
+    9_600, 19_200, 38_400, 57_600, 115_200, 230_400, 460_800, 921_600,
+];
+
+/// Frame identifier for host-to-chip frames; answers use `0xD5`.
+const HOST_TO_PN532: u8 = 0xD4;
+const PN532_TO_HOST: u8 = 0xD5;
+
+/// Bytes sent ahead of the first command to wake the chip from power-down.
+const WAKE_UP: [u8; 16] = [0x55, 0x55, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+
+const SAM_CONFIGURATION: u8 = 0x14;
+const RF_CONFIGURATION: u8 = 0x32;
+const IN_LIST_PASSIVE_TARGET: u8 = 0x4A;
+const IN_DATA_EXCHANGE: u8 = 0x40;
+const IN_RELEASE: u8 = 0x52;
+/// MIFARE Ultralight / NTAG READ: four pages starting at the given one.
+const TAG_READ: u8 = 0x30;
+/// NTAG21x `READ_CNT` and the address of its NFC counter.
+const TAG_READ_COUNTER: [u8; 2] = [0x39, 0x02];
+
+/// The `[pn532]` table.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Pn532Settings {
+    /// Serial device the board is wired to, such as `/dev/serial0`.
+    pub device: PathBuf,
+    pub baud: u32,
+    /// Overrides [`DEFAULT_POLL_INTERVAL`](crate::reader::DEFAULT_POLL_INTERVAL)
+    /// for this board.
+    pub poll_interval: Option<Duration>,
+    /// Reads NTAG21x tags' NFC counter on each tap.
+    pub read_counter: bool,
+}
+
+/// Wraps `data` (a command code and its parameters) in a normal information
+/// frame addressed to the chip.
+pub fn encode_command(data: &[u8]) -> Vec<u8> {
+    encode_frame(HOST_TO_PN532, data)
+}
+
+fn encode_frame(tfi: u8, data: &[u8]) -> Vec<u8> {
+    let len = (data.len() + 1) as u8;
+    let sum = data.iter().fold(tfi, |sum, byte| sum.wrapping_add(*byte));
+    let mut frame = Vec::with_capacity(data.len() + 8);
+    frame.extend([0x00, 0x00, 0xFF, len, len.wrapping_neg(), tfi]);
+    frame.extend_from_slice(data);
+    frame.extend([sum.wrapping_neg(), 0x00]);
+    frame
+}
+
+/// A frame received from the chip.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Frame {
+    Ack,
+    Nack,
+    /// The frame's payload after the `0xD5` identifier.
+    Data(Vec<u8>),
+}
+
+/// Reads the next frame, skipping any bytes before its start code.
+fn read_frame(port: &mut impl Read) -> Result<Frame, ReaderError> {
+    let mut previous = 0xFF;
+    loop {
+        let byte = read_byte(port)?;
+        if previous == 0x00 && byte == 0xFF {
+            break;
+        }
+        previous = byte;
+    }
+    let len = read_byte(port)?;
+    let lcs = read_byte(port)?;
+    match (len, lcs) {
+        (0x00, 0xFF) => return Ok(Frame::Ack),
+        (0xFF, 0x00) => return Ok(Frame::Nack),
+        _ if len.wrapping_add(lcs) != 0 => {
+            return Err(ReaderError::backend("PN532 frame length checksum mismatch"));
+        }
+        _ => {}
+    }
+    let mut body = vec![0; usize::from(len) + 1];
+    port.read_exact(&mut body).map_err(io_error)?;
+    let dcs = body.pop().expect("body holds the checksum");
+    if body.iter().fold(dcs, |sum, byte| sum.wrapping_add(*byte)) != 0 {
+        return Err(ReaderError::backend("PN532 frame data checksum mismatch"));
+    }
+    // Postamble.
+    read_byte(port)?;
+    match body.split_first() {
+        Some((&PN532_TO_HOST, data)) => Ok(Frame::Data(data.to_vec())),
+        // A syntax error frame carries the bare error code 0x7F.
+        Some((&0x7F, [])) => Err(ReaderError::backend("PN532 rejected the command frame")),
+        _ => Err(ReaderError::backend(format!(
+            "unexpected PN532 frame {body:02X?}"
+        ))),
+    }
+}
+
+fn read_byte(port: &mut impl Read) -> Result<u8, ReaderError> {
+    let mut byte = [0u8];
+    port.read_exact(&mut byte).map_err(io_error)?;
+    Ok(byte[0])
+}
+
+fn io_error(err: std::io::Error) -> ReaderError {
+    ReaderError::backend(format!("PN532 serial link failed: {err}"))
+}
+
+/// Polls a PN532 for ISO 14443A tags.
+pub struct Pn532Reader<T> {
+    port: T,
+    poll_interval: Duration,
+    /// Whether the chip has been woken and configured since the last error.
+    ready: bool,
+    last_uid: Option<CardUid>,
+    read_counter: bool,
+}
+
+impl<T: Read + Write> Pn532Reader<T> {
+    pub fn new(port: T, poll_interval: Duration) -> Self {
+        Self {
+            port,
+            poll_interval,
+            ready: false,
+            last_uid: None,
+            read_counter: false,
+        }
+    }
+
+    /// Also reads each arriving tag's NFC counter, for tap statistics.
+    pub fn with_read_counter(mut self, read_counter: bool) -> Self {
+        self.read_counter = read_counter;
+        self
+    }
+
+    /// Sends `data` and returns the answer's parameters, after checking the
+    /// chip acknowledged the command and answered that same command.
+    fn command(&mut self, data: &[u8]) -> Result<Vec<u8>, ReaderError> {
+        self.port
+            .write_all(&encode_command(data))
+            .and_then(|()| self.port.flush())
+            .map_err(io_error)?;
+        if read_frame(&mut self.port)? != Frame::Ack {
+            return Err(ReaderError::backend(format!(
+                "PN532 did not acknowledge command {:02X}",
+                data[0]
+            )));
+        }
+        match read_frame(&mut self.port)? {
+            Frame::Data(answer) if answer.first() == Some(&(data[0] + 1)) => {
+                Ok(answer[1..].to_vec())
+            }
+            other => Err(ReaderError::backend(format!(
+                "unexpected PN532 answer to command {:02X}: {other:?}",
+                data[0]
+            ))),
+        }
+    }
+
+    /// Wakes the chip, switches off its secure access module, and limits
+    /// each poll to a couple of attempts so an empty field answers quickly.
+    fn wake(&mut self) -> Result<(), ReaderError> {
+        self.port.write_all(&WAKE_UP).map_err(io_error)?;
+        self.command(&[SAM_CONFIGURATION, 0x01, 0x14, 0x01])?;
+        self.command(&[RF_CONFIGURATION, 0x05, 0xFF, 0x01, 0x02])?;
+        self.ready = true;
+        Ok(())
+    }
+
+    /// The UID of the tag in the field, if any, left selected as target 1.
+    fn find_tag(&mut self) -> Result<Option<CardUid>, ReaderError> {
+        let answer = self.command(&[IN_LIST_PASSIVE_TARGET, 0x01, 0x00])?;
+        match answer.as_slice() {
+            [0, ..] => Ok(None),
+            // Tg, SENS_RES (2), SEL_RES, NFCID length, NFCID.
+            [_, _, _, _, _, len, uid @ ..] if uid.len() >= usize::from(*len) => {
+                Ok(Some(CardUid::new(uid[..usize::from(*len)].to_vec())))
+            }
+            _ => Err(ReaderError::backend(format!(
+                "malformed PN532 target list {answer:02X?}"
+            ))),
+        }
+    }
+
+    fn read_pages(&mut self, page: u8) -> Result<Vec<u8>, ReaderError> {
+        let answer = self.command(&[IN_DATA_EXCHANGE, 0x01, TAG_READ, page])?;
+        match answer.split_first() {
+            Some((status, data)) if status & 0x3F == 0 && data.len() >= 16 => Ok(data.to_vec()),
+            Some((status, _)) if status & 0x3F != 0 => Err(ReaderError::backend(format!(
+                "PN532 read of page {page} failed with status {status:02X}"
+            ))),
+            _ => Err(ReaderError::backend(format!(
+                "short read of {} bytes at page {page}",
+                answer.len().saturating_sub(1)
+            ))),
+        }
+    }
+
+    /// The tag's 24-bit NFC counter, which NTAG21x tags count up on the
+    /// first read after entering a field. Tags without the counter, or
+    /// with it disabled, refuse the command.
+    fn read_tap_count(&mut self) -> Result<u32, ReaderError> {
+        let [command, address] = TAG_READ_COUNTER;
+        let answer = self.command(&[IN_DATA_EXCHANGE, 0x01, command, address])?;
+        match answer.as_slice() {
+            [status, low, middle, high, ..] if status & 0x3F == 0 => {
+                Ok(u32::from_le_bytes([*low, *middle, *high, 0]))
+            }
+            _ => Err(ReaderError::backend(format!(
+                "tag refused to read its counter: {answer:02X?}"
+            ))),
+        }
+    }
+
+    fn poll(&mut self) -> Result<Option<ReaderEvent>, ReaderError> {
+        if !self.ready {
+            self.wake()?;
+        }
+        let Some(uid) = self.find_tag()? else {
+            return Ok(self
+                .last_uid
+                .take()
+                .map(|uid| ReaderEvent::CardRemoved { uid }));
+        };
+        let event = if self.last_uid.as_ref() == Some(&uid) {
+            ReaderEvent::Idle
+        } else {
+            let ndef_text = match ndef::read_tag_text(|page| self.read_pages(page)) {
+                Ok(text) => text,
+                Err(err) => {
+                    tracing::debug!(?err, %uid, "could not read NDEF data from tag");
+                    None
+                }
+            };
+            // After the NDEF read, so the count includes this tap.
+            let tap_count = if self.read_counter {
+                match self.read_tap_count() {
+                    Ok(count) => Some(count),
+                    Err(err) => {
+                        tracing::debug!(?err, %uid, "could not read the tag's counter");
+                        None
+                    }
+                }
+            } else {
+                None
+            };
+            self.last_uid = Some(uid.clone());
+            ReaderEvent::CardPresent {
+                uid,
+                ndef_text,
+                reader: None,
+                tap_count,
+            }
+        };
+        self.command(&[IN_RELEASE, 0x01])?;
+        Ok(Some(event))
+    }
+}
+
+impl<T: Read + Write> NfcReader for Pn532Reader<T> {
+    fn capabilities(&self) -> ReaderCapabilities {
+        ReaderCapabilities {
+            card_removed: true,
+            ndef: true,
+        }
+    }
+
+    fn next_event(&mut self) -> Result<ReaderEvent, ReaderError> {
+        loop {
+            match self.poll() {
+                Ok(Some(event)) => return Ok(event),
+                Ok(None) => std::thread::sleep(self.poll_interval),
+                Err(err) => {
+                    // Start over with a fresh wake-up once the caller retries.
+                    self.ready = false;
+                    return Err(err);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(feature = "nfc-pn532")]
+pub mod serial {
+    use super::{Pn532Reader, Pn532Settings};
+    use crate::reader::ReaderError;
+    use std::time::Duration;
+
+    /// How long to wait for the chip to answer a command.
+    const TIMEOUT: Duration = Duration::from_millis(500);
+
+    /// Opens the serial port in `settings`.
+    pub fn open(
+        settings: &Pn532Settings,
+        poll_interval: Duration,
+    ) -> Result<Pn532Reader<Box<dyn serialport::SerialPort>>, ReaderError> {
+        let port = serialport::new(settings.device.to_string_lossy(), settings.baud)
+            .timeout(TIMEOUT)
+            .open()
+            .map_err(|err| {
+                ReaderError::backend(format!(
+                    "failed to open PN532 serial port {}: {err}",
+                    settings.device.display()
+                ))
+            })?;
+        Ok(Pn532Reader::new(port, poll_interval).with_read_counter(settings.read_counter))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::VecDeque;
+
+    const ACK: [u8; 6] = [0x00, 0x00, 0xFF, 0x00, 0xFF, 0x00];
+
+    /// A serial link that answers with canned bytes and records writes.
+    #[derive(Default)]
+    struct ScriptedPort {
+        input: VecDeque<u8>,
+        written: Vec<u8>,
+    }
+
+    impl ScriptedPort {
+        fn answer(&mut self, command: u8, data: &[u8]) {
+            let mut payload = vec![command + 1];
+            payload.extend_from_slice(data);
+            self.input.extend(ACK);
+            self.input.extend(encode_frame(PN532_TO_HOST, &payload));
+        }
+    }
+
+    impl Read for ScriptedPort {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            self.input.read(buf)
+        }
+    }
+
+    impl Write for ScriptedPort {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.written.extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn commands_are_framed_with_length_and_data_checksums() {
+        assert_eq!(
+            encode_command(&[0x02]),
+            [0x00, 0x00, 0xFF, 0x02, 0xFE, 0xD4, 0x02, 0x2A, 0x00]
+        );
+        let mut input: &[u8] = &[0xAA, 0x00, 0x00, 0xFF, 0x00, 0xFF, 0x00];
+        assert_eq!(read_frame(&mut input).unwrap(), Frame::Ack);
+
+        let mut corrupt = encode_frame(PN532_TO_HOST, &[0x03, 0x32]);
+        corrupt[7] ^= 0x01;
+        assert!(read_frame(&mut corrupt.as_slice()).is_err());
+    }
+
+    #[test]
+    fn reader_wakes_the_chip_and_reports_each_tag_arriving_and_leaving() {
+        let mut port = ScriptedPort::default();
+        port.answer(SAM_CONFIGURATION, &[]);
+        port.answer(RF_CONFIGURATION, &[]);
+        // One target: Tg, SENS_RES, SEL_RES, then a 7-byte NFCID.
+        let listing = [
+            0x01, 0x01, 0x00, 0x44, 0x00, 0x07, 0x04, 0xA1, 0xB2, 0xC3, 0xD4, 0xE5, 0xF6,
+        ];
+        port.answer(IN_LIST_PASSIVE_TARGET, &listing);
+        let mut capability = vec![0x00, 0xE1, 0x10, 0x12, 0x00];
+        capability.resize(17, 0);
+        port.answer(IN_DATA_EXCHANGE, &capability);
+        let mut data = vec![0x00];
+        data.extend(ndef::tlv_pages(&ndef::text_record("a.mp3", "en")).concat());
+        data.resize(17, 0);
+        port.answer(IN_DATA_EXCHANGE, &data);
+        port.answer(IN_RELEASE, &[0x00]);
+        // The same tag again, still resting on the reader.
+        port.answer(IN_LIST_PASSIVE_TARGET, &listing);
+        port.answer(IN_RELEASE, &[0x00]);
+        // Then lifted off.
+        port.answer(IN_LIST_PASSIVE_TARGET, &[0x00]);
+
+        let mut reader = Pn532Reader::new(port, Duration::ZERO);
+        assert_eq!(
+            reader.next_event().unwrap(),
+            ReaderEvent::CardPresent {
+                uid: CardUid::new(vec![0x04, 0xA1, 0xB2, 0xC3, 0xD4, 0xE5, 0xF6]),
+                ndef_text: Some("a.mp3".to_string()),
+                reader: None,
+                tap_count: None,
+            }
+        );
+        assert_eq!(reader.next_event().unwrap(), ReaderEvent::Idle);
+        assert_eq!(
+            reader.next_event().unwrap(),
+            ReaderEvent::CardRemoved {
+                uid: CardUid::new(vec![0x04, 0xA1, 0xB2, 0xC3, 0xD4, 0xE5, 0xF6]),
+            }
+        );
+        assert!(reader.port.written.starts_with(&WAKE_UP));
+
+        // The scripted link has run dry, as a timed-out port would.
+        assert!(reader.next_event().is_err());
+        assert!(!reader.ready);
+    }
+
+    #[test]
+    fn reader_asks_tags_for_their_counter_when_set_to() {
+        let mut port = ScriptedPort::default();
+        port.answer(SAM_CONFIGURATION, &[]);
+        port.answer(RF_CONFIGURATION, &[]);
+        let tag = |last: u8| [0x01, 0x01, 0x00, 0x44, 0x00, 0x04, 0x04, 0xA1, 0xB2, last];
+        for (last, counter) in [(0x01, &[0x00, 0x2A, 0x01, 0x00][..]), (0x02, &[0x01][..])] {
+            port.answer(IN_LIST_PASSIVE_TARGET, &tag(last));
+            // Not NDEF formatted; the read still counts as the tap.
+            port.answer(IN_DATA_EXCHANGE, &[0x00; 17]);
+            port.answer(IN_DATA_EXCHANGE, counter);
+            port.answer(IN_RELEASE, &[0x00]);
+        }
+
+        let mut reader = Pn532Reader::new(port, Duration::ZERO).with_read_counter(true);
+        let counts: Vec<_> = (0..2)
+            .map(|_| match reader.next_event().unwrap() {
+                ReaderEvent::CardPresent { tap_count, .. } => tap_count,
+                other => panic!("expected a tap, got {other:?}"),
+            })
+            .collect();
+        // The second tag has its counter disabled and refuses.
+        assert_eq!(counts, [Some(0x012A), None]);
+        let asked = encode_command(&[IN_DATA_EXCHANGE, 0x01, 0x39, 0x02]);
+        assert!(
+            reader
+                .port
+                .written
+                .windows(asked.len())
+                .any(|window| window == asked)
+        );
+    }
+}
@@ -0,0 +1,479 @@
+//! Podcast cards: a card entry written as `podcast:<feed url>` plays an
+//! episode of that RSS feed.
+//!
+//! On each tap the feed is fetched again and an episode is picked by
+//! [`EpisodeOrder`]. The episode is downloaded into the podcast cache, which
+//! keeps one episode per feed, and which episodes have played is tracked in
+//! `progress.json` beside it. When the feed cannot be fetched, the last
+//! downloaded episode plays instead, so a card still works offline.
+//!
+//! Feeds and episodes are fetched with the HTTP client in [`crate::webhook`],
+//! over `http://`, or `https://` in builds with the `https` feature.
+
+use crate::mirror;
+use crate::state;
+use crate::webhook::{self, WebhookError, WebhookUrl};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Prefix of card entries that name a podcast feed.
+pub const SCHEME: &str = "podcast:";
+
+const PROGRESS_FILE: &str = "progress.json";
+
+#[derive(Debug, thiserror::Error)]
+pub enum PodcastError {
+    #[error("podcast request failed: {0}")]
+    Http(#[from] WebhookError),
+    #[error("podcast server responded with status {0}")]
+    Status(u16),
+    #[error("feed {0} lists no episodes")]
+    NoEpisodes(String),
+    #[error("failed to access podcast cache {path:?}: {source}")]
+    Io {
+        path: PathBuf,
+        #[source]
+        source: std::io::Error,
+    },
+    #[error("invalid podcast progress {path:?}: {source}")]
+    Progress {
+        path: PathBuf,
+        #[source]
+        source: serde_json::Error,
+    },
+}
+
+/// The feed URL of a `podcast:<url>` entry, if `entry` is one.
+pub fn feed_url(entry: &str) -> Option<&str> {
+    entry.strip_prefix(SCHEME)
+}
+
+/// Which episode a tap plays.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum EpisodeOrder {
+    /// Always the newest episode.
+    #[default]
+    Newest,
+    /// The oldest episode that has not played yet, working through the feed
+    /// in order; once every episode has played, the newest.
+    NextUnplayed,
+}
+
+/// One `<item>` of a feed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Episode {
+    /// The item's `<guid>`, or its audio URL when the feed has none.
+    pub guid: String,
+    pub title: String,
+    /// The `<enclosure>` URL of the audio.
+    pub url: String,
+}
+
+/// The episodes of an RSS feed in the order it lists them, which for
+/// podcasts is newest first. Items without audio are skipped.
+pub fn parse_feed(xml: &str) -> Vec<Episode> {
+    elements(xml, "item")
+        .into_iter()
+        .filter_map(|(_, item)| {
+            let (enclosure, _) = elements(item, "enclosure").into_iter().next()?;
+            let url = attribute(enclosure, "url")?;
+            let guid = elements(item, "guid")
+                .into_iter()
+                .next()
+                .map(|(_, guid)| text(guid))
+                .filter(|guid| !guid.is_empty())
+                .unwrap_or_else(|| url.clone());
+            let title = elements(item, "title")
+                .into_iter()
+                .next()
+                .map(|(_, title)| text(title))
+                .unwrap_or_default();
+            Some(Episode { guid, title, url })
+        })
+        .collect()
+}
+
+/// Every `<name>` element in `xml`, as its attribute text and content.
+/// Self-closing elements have empty content. Nested elements of the same
+/// name are not supported, which RSS never needs.
+fn elements<'a>(xml: &'a str, name: &str) -> Vec<(&'a str, &'a str)> {
+    let open = format!("<{name}");
+    let close = format!("</{name}>");
+    let mut found = Vec::new();
+    let mut rest = xml;
+    while let Some(start) = rest.find(&open) {
+        let after = &rest[start + open.len()..];
+        // `<items>` is not an `<item>`.
+        if !after.starts_with(|c: char| c.is_whitespace() || c == '>' || c == '/') {
+            rest = after;
+            continue;
+        }
+        let Some(tag_end) = after.find('>') else {
+            break;
+        };
+        let attributes = &after[..tag_end];
+        let after_tag = &after[tag_end + 1..];
+        if let Some(attributes) = attributes.strip_suffix('/') {
+            found.push((attributes, ""));
+            rest = after_tag;
+            continue;
+        }
+        let Some(end) = after_tag.find(&close) else {
+            break;
+        };
+        found.push((attributes, &after_tag[..end]));
+        rest = &after_tag[end + close.len()..];
+    }
+    found
+}
+
+/// The decoded value of attribute `name` in an element's attribute text.
+fn attribute(attributes: &str, name: &str) -> Option<String> {
+    let mut rest = attributes;
+    loop {
+        let index = rest.find(name)?;
+        let preceded_by_space = rest[..index].ends_with(char::is_whitespace) || index == 0;
+        let after = rest[index + name.len()..].trim_start();
+        if preceded_by_space && let Some(value) = after.strip_prefix('=') {
+            let value = value.trim_start();
+            let quote = value.chars().next().filter(|c| *c == '"' || *c == '\'')?;
+            let value = &value[1..];
+            let end = value.find(quote)?;
+            return Some(decode_entities(&value[..end]));
+        }
+        rest = &rest[index + name.len()..];
+    }
+}
+
+/// Element content as text: CDATA is taken as is, anything else has its
+/// entities decoded.
+fn text(content: &str) -> String {
+    let content = content.trim();
+    match content
+        .strip_prefix("<![CDATA[")
+        .and_then(|inner| inner.strip_suffix("]]>"))
+    {
+        Some(inner) => inner.trim().to_string(),
+        None => decode_entities(content),
+    }
+}
+
+fn decode_entities(text: &str) -> String {
+    let mut decoded = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('&') {
+        decoded.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let entity = rest.find(';').map(|end| (&rest[1..end], end));
+        let character = entity.and_then(|(name, _)| match name {
+            "amp" => Some('&'),
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            _ => {
+                let code = match name.strip_prefix("#x").or_else(|| name.strip_prefix("#X")) {
+                    Some(hex) => u32::from_str_radix(hex, 16).ok(),
+                    None => name.strip_prefix('#').and_then(|dec| dec.parse().ok()),
+                };
+                code.and_then(char::from_u32)
+            }
+        });
+        match (character, entity) {
+            (Some(character), Some((_, end))) => {
+                decoded.push(character);
+                rest = &rest[end + 1..];
+            }
+            _ => {
+                decoded.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    decoded.push_str(rest);
+    decoded
+}
+
+/// What the cache remembers about one feed.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct FeedProgress {
+    /// Guids of the episodes that have started playing.
+    #[serde(default)]
+    played: Vec<String>,
+    /// The cached episode, relative to the cache directory.
+    #[serde(default)]
+    file: Option<String>,
+}
+
+/// Podcast episodes on disk and what has played, keyed by feed URL.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Podcasts {
+    dir: PathBuf,
+    order: EpisodeOrder,
+}
+
+impl Podcasts {
+    pub fn new(dir: impl Into<PathBuf>, order: EpisodeOrder) -> Self {
+        Self {
+            dir: dir.into(),
+            order,
+        }
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    pub fn order(&self) -> EpisodeOrder {
+        self.order
+    }
+
+    /// The file of the episode a tap on the feed at `url` should play,
+    /// downloading it if needed and marking it played.
+    pub fn episode(&self, url: &str) -> Result<PathBuf, PodcastError> {
+        let key = url.to_string();
+        let mut progress = self.load_progress()?;
+        let entry = progress.entry(key.clone()).or_default();
+        let episodes = match fetch_feed(url) {
+            Ok(episodes) => episodes,
+            Err(err) => {
+                let cached = entry.file.as_ref().map(|file| self.dir.join(file));
+                return match cached.filter(|path| path.is_file()) {
+                    Some(path) => {
+                        tracing::warn!(%err, feed = %key, "feed unavailable; replaying cached episode");
+                        Ok(path)
+                    }
+                    None => Err(err),
+                };
+            }
+        };
+        let episode = self
+            .pick(&episodes, &entry.played)
+            .ok_or_else(|| PodcastError::NoEpisodes(key.clone()))?;
+
+        let file = episode_file_name(episode);
+        let path = self.dir.join(&file);
+        if !path.is_file() {
+            self.download(episode, &path)?;
+            tracing::info!(feed = %key, title = %episode.title, "cached podcast episode");
+        }
+        if let Some(previous) = entry.file.replace(file.clone())
+            && previous != file
+            && let Err(err) = fs::remove_file(self.dir.join(&previous))
+        {
+            tracing::warn!(%err, file = previous, "failed to remove old podcast episode");
+        }
+        if !entry.played.contains(&episode.guid) {
+            entry.played.push(episode.guid.clone());
+        }
+        self.save_progress(&progress)?;
+        Ok(path)
+    }
+
+    fn pick<'a>(&self, episodes: &'a [Episode], played: &[String]) -> Option<&'a Episode> {
+        let newest = episodes.first();
+        match self.order {
+            EpisodeOrder::Newest => newest,
+            EpisodeOrder::NextUnplayed => episodes
+                .iter()
+                .rev()
+                .find(|episode| !played.contains(&episode.guid))
+                .or(newest),
+        }
+    }
+
+    fn download(&self, episode: &Episode, path: &Path) -> Result<(), PodcastError> {
+        let response = webhook::get(&WebhookUrl::parse(&episode.url)?)?;
+        if !(200..300).contains(&response.status) {
+            return Err(PodcastError::Status(response.status));
+        }
+        fs::create_dir_all(&self.dir).map_err(io_err(&self.dir))?;
+        // Download under a temporary name so an interrupted fetch is retried.
+        let mut partial = path.to_path_buf().into_os_string();
+        partial.push(".part");
+        let partial = PathBuf::from(partial);
+        fs::write(&partial, response.body).map_err(io_err(&partial))?;
+        fs::rename(&partial, path).map_err(io_err(path))
+    }
+
+    fn progress_path(&self) -> PathBuf {
+        self.dir.join(PROGRESS_FILE)
+    }
+
+    fn load_progress(&self) -> Result<BTreeMap<String, FeedProgress>, PodcastError> {
+        let path = self.progress_path();
+        match fs::read(&path) {
+            Ok(bytes) => serde_json::from_slice(&bytes)
+                .map_err(|source| PodcastError::Progress { path, source }),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(BTreeMap::new()),
+            Err(source) => Err(PodcastError::Io { path, source }),
+        }
+    }
+
+    fn save_progress(&self, progress: &BTreeMap<String, FeedProgress>) -> Result<(), PodcastError> {
+        let path = self.progress_path();
+        let mut bytes =
+            serde_json::to_vec_pretty(progress).map_err(|source| PodcastError::Progress {
+                path: path.clone(),
+                source,
+            })?;
+        bytes.push(b'\n');
+        fs::create_dir_all(&self.dir).map_err(io_err(&self.dir))?;
+        state::write_atomically(&path, &bytes).map_err(io_err(&path))?;
+        mirror::update(&path);
+        Ok(())
+    }
+}
+
+fn fetch_feed(url: &str) -> Result<Vec<Episode>, PodcastError> {
+    let response = webhook::get(&WebhookUrl::parse(url)?)?;
+    if !(200..300).contains(&response.status) {
+        return Err(PodcastError::Status(response.status));
+    }
+    Ok(parse_feed(&String::from_utf8_lossy(&response.body)))
+}
+
+/// A stable cache name for `episode`: a hash of its guid, keeping the audio
+/// URL's extension so decoders can tell the format.
+fn episode_file_name(episode: &Episode) -> String {
+    let digest = Sha256::digest(episode.guid.as_bytes());
+    let stem: String = digest[..8]
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect();
+    let path = episode.url.split(['?', '#']).next().unwrap_or_default();
+    let extension = path
+        .rsplit_once('/')
+        .and_then(|(_, name)| name.rsplit_once('.'))
+        .map(|(_, extension)| extension)
+        .filter(|extension| {
+            (1..=4).contains(&extension.len())
+                && extension.chars().all(|c| c.is_ascii_alphanumeric())
+        });
+    match extension {
+        Some(extension) => format!("{stem}.{}", extension.to_ascii_lowercase()),
+        None => stem,
+    }
+}
+
+fn io_err(path: &Path) -> impl FnOnce(std::io::Error) -> PodcastError {
+    let path = path.to_path_buf();
+    move |source| PodcastError::Io { path, source }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_http::{TestServer, ok};
+    use tempfile::tempdir;
+
+    const FEED: &str = r#"<?xml version="1.0"?>
+<rss version="2.0"><channel><title>Bedtime Stories</title>
+  <item>
+    <title><![CDATA[Episode 3 & more]]></title>
+    <guid isPermaLink="false">ep-3</guid>
+    <enclosure url="http://cdn.example/ep3.MP3?src=rss&amp;x=1" type="audio/mpeg"/>
+  </item>
+  <item>
+    <title>Trailer</title>
+  </item>
+  <item>
+    <title>Episode 2 &#8211; Owls</title>
+    <guid>ep-2</guid>
+    <enclosure length="1" url='http://cdn.example/ep2.mp3'></enclosure>
+  </item>
+  <item>
+    <title>Episode 1</title>
+    <enclosure url="http://cdn.example/ep1.mp3" />
+  </item>
+</channel></rss>"#;
+
+    #[test]
+    fn parses_rss_items_with_audio() {
+        let episodes = parse_feed(FEED);
+        assert_eq!(
+            episodes,
+            vec![
+                Episode {
+                    guid: "ep-3".into(),
+                    title: "Episode 3 & more".into(),
+                    url: "http://cdn.example/ep3.MP3?src=rss&x=1".into(),
+                },
+                Episode {
+                    guid: "ep-2".into(),
+                    title: "Episode 2 \u{2013} Owls".into(),
+                    url: "http://cdn.example/ep2.mp3".into(),
+                },
+                Episode {
+                    guid: "http://cdn.example/ep1.mp3".into(),
+                    title: "Episode 1".into(),
+                    url: "http://cdn.example/ep1.mp3".into(),
+                },
+            ]
+        );
+        assert!(episode_file_name(&episodes[0]).ends_with(".mp3"));
+    }
+
+    #[test]
+    fn next_unplayed_works_through_the_feed_oldest_first() {
+        let episodes = parse_feed(FEED);
+        let podcasts = Podcasts::new("/unused", EpisodeOrder::NextUnplayed);
+        let guid = |played: &[String]| podcasts.pick(&episodes, played).unwrap().guid.clone();
+
+        let mut played = Vec::new();
+        for _ in 0..3 {
+            played.push(guid(&played));
+        }
+        assert_eq!(played, ["http://cdn.example/ep1.mp3", "ep-2", "ep-3"]);
+        assert_eq!(guid(&played), "ep-3");
+
+        let newest = Podcasts::new("/unused", EpisodeOrder::Newest);
+        assert_eq!(newest.pick(&episodes, &played).unwrap().guid, "ep-3");
+    }
+
+    #[test]
+    fn episodes_download_once_and_replay_offline() {
+        let server = TestServer::bind();
+        let base = server.url("");
+        let feed = |guid: &str| {
+            ok(format!(
+                "<rss><item><guid>{guid}</guid><enclosure url=\"{base}/{guid}.mp3\"/></item></rss>"
+            ))
+        };
+        let server = server.respond([
+            feed("ep1"),
+            ok("ONE"),
+            feed("ep1"),
+            b"HTTP/1.1 503 Unavailable\r\nContent-Length: 0\r\n\r\n".to_vec(),
+            feed("ep2"),
+            ok("TWO"),
+        ]);
+
+        let dir = tempdir().unwrap();
+        let podcasts = Podcasts::new(dir.path(), EpisodeOrder::Newest);
+        let url = format!("{base}/feed");
+        let first = podcasts.episode(&url).unwrap();
+        assert_eq!(fs::read(&first).unwrap(), b"ONE");
+        // Already cached, so only the feed is fetched.
+        assert_eq!(podcasts.episode(&url).unwrap(), first);
+        // The feed is down; the cached episode plays.
+        assert_eq!(podcasts.episode(&url).unwrap(), first);
+
+        let second = podcasts.episode(&url).unwrap();
+        assert_eq!(fs::read(&second).unwrap(), b"TWO");
+        assert!(!first.exists(), "the old episode is evicted");
+        let paths: Vec<String> = server
+            .join()
+            .unwrap()
+            .into_iter()
+            .map(|request| request.path)
+            .collect();
+        assert_eq!(
+            paths,
+            ["/feed", "/ep1.mp3", "/feed", "/feed", "/feed", "/ep2.mp3"]
+        );
+    }
+}
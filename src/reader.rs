@@ -1,4 +1,6 @@
-use crate::controller::CardUid;
+use crate::controller::{CardUid, CardUidParseError};
+use std::collections::VecDeque;
+use std::time::Duration;
 
 #[derive(Debug, thiserror::Error, Clone, PartialEq, Eq)]
 pub enum ReaderError {
@@ -6,6 +8,14 @@ pub enum ReaderError {
     Backend { message: String },
     #[error("card response status {sw1:02X}{sw2:02X}")]
     StatusWord { sw1: u8, sw2: u8 },
+    #[error("tag is not NDEF formatted (capability container {0:02X?})")]
+    NotNdefFormatted([u8; 4]),
+    #[error("NDEF message needs {needed} bytes but the tag holds {available}")]
+    TagTooSmall { needed: usize, available: usize },
+    #[error("tag page {page} read back differently than written")]
+    VerifyMismatch { page: u8 },
+    #[error("presented tag {found} is not card {expected}")]
+    WrongTag { expected: CardUid, found: CardUid },
     #[cfg(feature = "nfc-pcsc")]
     #[error("pcsc error: {0}")]
     Pcsc(#[from] pcsc::Error),
@@ -21,33 +31,429 @@ impl ReaderError {
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum ReaderEvent {
-    CardPresent { uid: CardUid },
+    CardPresent {
+        uid: CardUid,
+        /// Text of the tag's NDEF text record, when it has one.
+        ndef_text: Option<String>,
+        /// Which reader saw the tag, for backends that watch several.
+        reader: Option<String>,
+        /// The tag's NFC read counter, for NTAG21x tags with it enabled,
+        /// when the reader is set to ask for it.
+        tap_count: Option<u32>,
+    },
+    /// The card last reported present was lifted off the reader. Only
+    /// readers whose capabilities claim `card_removed` send this.
+    CardRemoved {
+        uid: CardUid,
+    },
     Idle,
     Shutdown,
 }
 
+/// What a reader backend can report beyond card taps. The default claims
+/// nothing, so features built on these stay off for unknown readers.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ReaderCapabilities {
+    /// Reports when a card is lifted off the reader.
+    pub card_removed: bool,
+    /// Reads NDEF text records from tags.
+    pub ndef: bool,
+}
+
 /// An interface for reading events from an NFC reader.
 pub trait NfcReader {
     fn next_event(&mut self) -> Result<ReaderEvent, ReaderError>;
+
+    fn capabilities(&self) -> ReaderCapabilities {
+        ReaderCapabilities::default()
+    }
 }
 
 impl<T: NfcReader + ?Sized> NfcReader for Box<T> {
     fn next_event(&mut self) -> Result<ReaderEvent, ReaderError> {
         (**self).next_event()
     }
+
+    fn capabilities(&self) -> ReaderCapabilities {
+        (**self).capabilities()
+    }
+}
+
+/// How often readers are polled unless the config or `--poll-interval-ms`
+/// says otherwise.
+pub const DEFAULT_POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+/// The optional `[pcsc]` table.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PcscSettings {
+    /// Overrides [`DEFAULT_POLL_INTERVAL`] for PC/SC readers.
+    pub poll_interval: Option<Duration>,
+    /// Longest wait between retries while `pcscd` or the reader keeps
+    /// failing.
+    pub max_backoff: Duration,
+}
+
+impl Default for PcscSettings {
+    fn default() -> Self {
+        Self {
+            poll_interval: None,
+            max_backoff: Duration::from_secs(30),
+        }
+    }
+}
+
+/// Delays between retries of a backend that keeps failing: doubling from
+/// `base` up to `max`, with the upper half of each delay random so boxes
+/// that lost `pcscd` together do not retry in lockstep.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Backoff {
+    base: Duration,
+    max: Duration,
+    failures: u32,
+}
+
+impl Backoff {
+    pub fn new(base: Duration, max: Duration) -> Self {
+        Self {
+            base,
+            max: max.max(base),
+            failures: 0,
+        }
+    }
+
+    /// Failures since the last [`Backoff::reset`].
+    pub fn failures(&self) -> u32 {
+        self.failures
+    }
+
+    /// Records a failure and returns how long to wait before trying again.
+    pub fn next_delay(&mut self) -> Duration {
+        self.failures = self.failures.saturating_add(1);
+        let shift = (self.failures - 1).min(16);
+        let ceiling = self.base.saturating_mul(1 << shift).min(self.max);
+        let half = ceiling / 2;
+        let jitter = ceiling.saturating_sub(half).as_millis() as u64;
+        half + Duration::from_millis(random_u64() % (jitter + 1))
+    }
+
+    /// Starts over after a success.
+    pub fn reset(&mut self) {
+        self.failures = 0;
+    }
+}
+
+/// A random number from the standard library's per-process hash keys, which
+/// is plenty for jitter.
+fn random_u64() -> u64 {
+    use std::hash::BuildHasher;
+    std::collections::hash_map::RandomState::new().hash_one(0u8)
+}
+
+/// A reader named in the `[readers]` table. Devices whose name contains
+/// `device` (ignoring case) report taps under `label`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReaderZone {
+    pub label: String,
+    pub device: String,
+}
+
+/// The label taps on `device` are reported under. With no zones every
+/// attached reader is used under its own device name; otherwise readers no
+/// zone matches are ignored.
+pub fn zone_label(zones: &[ReaderZone], device: &str) -> Option<String> {
+    if zones.is_empty() {
+        return Some(device.to_string());
+    }
+    let device = device.to_lowercase();
+    zones
+        .iter()
+        .find(|zone| device.contains(&zone.device.to_lowercase()))
+        .map(|zone| zone.label.clone())
+}
+
+/// A single instruction in a replay script.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ReplayStep {
+    Tap(CardUid),
+    Wait(Duration),
+}
+
+#[derive(Debug, thiserror::Error, PartialEq, Eq)]
+pub enum ReplayParseError {
+    #[error("line {line}: invalid card uid: {source}")]
+    CardUid {
+        line: usize,
+        #[source]
+        source: CardUidParseError,
+    },
+    #[error("line {line}: invalid delay {value:?}; expected milliseconds")]
+    Delay { line: usize, value: String },
+    #[error("line {line}: unexpected trailing input {rest:?}")]
+    Trailing { line: usize, rest: String },
+}
+
+/// Parses a replay script.
+///
+/// Each line is either `wait <millis>` or `<uid> [<millis>]`, the latter being
+/// shorthand for a tap followed by a wait. Blank lines and `#` comments are
+/// ignored.
+pub fn parse_replay_script(script: &str) -> Result<Vec<ReplayStep>, ReplayParseError> {
+    let mut steps = Vec::new();
+    for (index, raw_line) in script.lines().enumerate() {
+        let line = index + 1;
+        let content = raw_line.split('#').next().unwrap_or_default();
+        let mut words = content.split_whitespace();
+        let Some(first) = words.next() else {
+            continue;
+        };
+        let delay = words
+            .next()
+            .map(|value| {
+                value
+                    .parse::<u64>()
+                    .map(Duration::from_millis)
+                    .map_err(|_| ReplayParseError::Delay {
+                        line,
+                        value: value.to_string(),
+                    })
+            })
+            .transpose()?;
+        let rest: Vec<_> = words.collect();
+        if !rest.is_empty() {
+            return Err(ReplayParseError::Trailing {
+                line,
+                rest: rest.join(" "),
+            });
+        }
+
+        if first.eq_ignore_ascii_case("wait") {
+            let delay = delay.ok_or_else(|| ReplayParseError::Delay {
+                line,
+                value: String::new(),
+            })?;
+            steps.push(ReplayStep::Wait(delay));
+        } else {
+            let uid = CardUid::parse(first)
+                .map_err(|source| ReplayParseError::CardUid { line, source })?;
+            steps.push(ReplayStep::Tap(uid));
+            if let Some(delay) = delay {
+                steps.push(ReplayStep::Wait(delay));
+            }
+        }
+    }
+    Ok(steps)
+}
+
+/// A reader that replays a scripted sequence of taps, then shuts down.
+///
+/// Waits are slept inside `next_event`, so the run loop sees the same timing
+/// it would with a person tapping cards.
+pub struct ReplayReader {
+    steps: VecDeque<ReplayStep>,
+}
+
+impl ReplayReader {
+    pub fn new(steps: Vec<ReplayStep>) -> Self {
+        Self {
+            steps: steps.into(),
+        }
+    }
+}
+
+impl NfcReader for ReplayReader {
+    fn next_event(&mut self) -> Result<ReaderEvent, ReaderError> {
+        while let Some(step) = self.steps.pop_front() {
+            match step {
+                ReplayStep::Tap(uid) => {
+                    return Ok(ReaderEvent::CardPresent {
+                        uid,
+                        ndef_text: None,
+                        reader: None,
+                        tap_count: None,
+                    });
+                }
+                ReplayStep::Wait(delay) => std::thread::sleep(delay),
+            }
+        }
+        Ok(ReaderEvent::Shutdown)
+    }
+}
+
+/// NDEF encoding for NTAG21x / MIFARE Ultralight (NFC Forum Type 2) tags.
+///
+/// Type 2 tags store data in 4-byte pages. Pages 0-2 hold the UID and lock
+/// bits, page 3 the capability container, and user data starts at page 4 as a
+/// sequence of TLV blocks.
+pub mod ndef {
+    /// First page of the user data area.
+    pub const FIRST_DATA_PAGE: u8 = 4;
+    /// Page holding the capability container.
+    pub const CAPABILITY_PAGE: u8 = 3;
+    pub const PAGE_SIZE: usize = 4;
+
+    const CC_MAGIC: u8 = 0xE1;
+    const NDEF_TLV: u8 = 0x03;
+    const TERMINATOR_TLV: u8 = 0xFE;
+
+    /// Size in bytes of the data area described by a capability container, or
+    /// `None` if the tag is not NDEF formatted. Capped at what single-byte
+    /// page addresses can reach.
+    pub fn data_area_size(capability: [u8; 4]) -> Option<usize> {
+        const ADDRESSABLE: usize = (256 - FIRST_DATA_PAGE as usize) * PAGE_SIZE;
+        (capability[0] == CC_MAGIC).then(|| (usize::from(capability[2]) * 8).min(ADDRESSABLE))
+    }
+
+    /// Encodes `text` as a single NDEF well-known text record (`T`), tagged
+    /// with the IANA language code `lang`.
+    pub fn text_record(text: &str, lang: &str) -> Vec<u8> {
+        let mut payload = Vec::with_capacity(1 + lang.len() + text.len());
+        // Status byte: UTF-8 encoding flag clear, low bits give the code length.
+        payload.push(lang.len() as u8 & 0x3F);
+        payload.extend_from_slice(lang.as_bytes());
+        payload.extend_from_slice(text.as_bytes());
+
+        // MB | ME | TNF=well-known, plus SR when the length fits in one byte.
+        let mut record = Vec::with_capacity(payload.len() + 7);
+        match u8::try_from(payload.len()) {
+            Ok(short) => record.extend([0xD1, 0x01, short]),
+            Err(_) => {
+                record.extend([0xC1, 0x01]);
+                record.extend((payload.len() as u32).to_be_bytes());
+            }
+        }
+        record.push(b'T');
+        record.extend(payload);
+        record
+    }
+
+    /// Reads the first NDEF text record from a tag's data area (starting at
+    /// [`FIRST_DATA_PAGE`]). Returns `None` if the data holds no complete text
+    /// record yet.
+    pub fn read_text(data: &[u8]) -> Option<String> {
+        let mut rest = data;
+        let message = loop {
+            match *rest.first()? {
+                // NULL TLVs are single padding bytes.
+                0x00 => rest = &rest[1..],
+                NDEF_TLV => {
+                    let (len, header) = match *rest.get(1)? {
+                        0xFF => {
+                            let len = u16::from_be_bytes([*rest.get(2)?, *rest.get(3)?]);
+                            (usize::from(len), 4)
+                        }
+                        len => (usize::from(len), 2),
+                    };
+                    break rest.get(header..header + len)?;
+                }
+                TERMINATOR_TLV => return None,
+                // Lock and memory control TLVs: skip over their value.
+                _ => rest = rest.get(2 + usize::from(*rest.get(1)?)..)?,
+            }
+        };
+
+        let flags = *message.first()?;
+        let type_len = usize::from(*message.get(1)?);
+        let (payload_len, mut offset) = if flags & 0x10 != 0 {
+            (usize::from(*message.get(2)?), 3)
+        } else {
+            let bytes: [u8; 4] = message.get(2..6)?.try_into().ok()?;
+            (u32::from_be_bytes(bytes) as usize, 6)
+        };
+        let id_len = if flags & 0x08 != 0 {
+            offset += 1;
+            usize::from(*message.get(offset - 1)?)
+        } else {
+            0
+        };
+        if flags & 0x07 != 0x01 || message.get(offset..offset + type_len)? != b"T" {
+            return None;
+        }
+        let start = offset + type_len + id_len;
+        let payload = message.get(start..start + payload_len)?;
+        let lang_len = usize::from(*payload.first()? & 0x3F);
+        String::from_utf8(payload.get(1 + lang_len..)?.to_vec()).ok()
+    }
+
+    /// Reads the NDEF text record from a Type 2 tag through `read_pages`,
+    /// which returns the 16 bytes starting at a page. Pages are fetched
+    /// until the NDEF TLV is complete or the data area ends; tags that are
+    /// not NDEF formatted yield `Ok(None)`.
+    pub fn read_tag_text<E>(
+        mut read_pages: impl FnMut(u8) -> Result<Vec<u8>, E>,
+    ) -> Result<Option<String>, E> {
+        let page = read_pages(CAPABILITY_PAGE)?;
+        let capability = [page[0], page[1], page[2], page[3]];
+        let Some(available) = data_area_size(capability) else {
+            return Ok(None);
+        };
+        let mut data = Vec::with_capacity(available);
+        for page in (FIRST_DATA_PAGE..).step_by(4) {
+            if data.len() >= available {
+                break;
+            }
+            data.extend(read_pages(page)?.into_iter().take(16));
+            if let Some(text) = read_text(&data) {
+                return Ok(Some(text));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Wraps an NDEF message in its TLV block and a terminator, zero-padded
+    /// to whole pages ready to write from [`FIRST_DATA_PAGE`].
+    pub fn tlv_pages(message: &[u8]) -> Vec<[u8; 4]> {
+        let mut bytes = Vec::with_capacity(message.len() + 5);
+        bytes.push(NDEF_TLV);
+        match u8::try_from(message.len()) {
+            Ok(short) if short != 0xFF => bytes.push(short),
+            _ => {
+                bytes.push(0xFF);
+                bytes.extend((message.len() as u16).to_be_bytes());
+            }
+        }
+        bytes.extend_from_slice(message);
+        bytes.push(TERMINATOR_TLV);
+        bytes
+            .chunks(PAGE_SIZE)
+            .map(|chunk| {
+                let mut page = [0u8; 4];
+                page[..chunk.len()].copy_from_slice(chunk);
+                page
+            })
+            .collect()
+    }
 }
 
 #[cfg(feature = "nfc-pcsc")]
 pub mod pcsc_backend {
-    use super::{CardUid, NfcReader, ReaderError, ReaderEvent};
+    use super::{
+        Backoff, CardUid, NfcReader, PcscSettings, ReaderCapabilities, ReaderError, ReaderEvent,
+        ReaderZone, ndef, zone_label,
+    };
     use pcsc::{Card, Context, Error as PcscError, Protocols, Scope, ShareMode, Status};
+    use std::collections::VecDeque;
+    use std::ffi::CString;
     use std::time::Duration;
 
-    /// A `NfcReader` that uses the `pcsc` crate to communicate with a PC/SC reader.
+    /// A `NfcReader` that polls every attached PC/SC reader, or only those
+    /// its zones name, and reports which one saw each tap.
     pub struct PcscReader {
         context: Context,
-        card: Option<Card>,
+        zones: Vec<ReaderZone>,
+        slots: Vec<Slot>,
+        pending: VecDeque<ReaderEvent>,
         poll_interval: Duration,
+        backoff: Backoff,
+        /// Set when `pcscd` went away, so the context is re-established
+        /// before the next poll.
+        stale_context: bool,
+    }
+
+    /// An attached reader and the tag currently on it.
+    struct Slot {
+        device: CString,
+        label: String,
+        card: Option<Card>,
         last_uid: Option<CardUid>,
     }
 
@@ -56,119 +462,169 @@ pub mod pcsc_backend {
             let context = Context::establish(Scope::User).map_err(ReaderError::from)?;
             Ok(Self {
                 context,
-                card: None,
+                zones: Vec::new(),
+                slots: Vec::new(),
+                pending: VecDeque::new(),
                 poll_interval,
-                last_uid: None,
+                backoff: Backoff::new(poll_interval, PcscSettings::default().max_backoff),
+                stale_context: false,
             })
         }
 
-        fn connect_card(&mut self) -> Result<(), ReaderError> {
-            let readers_buf = self
+        /// Waits at most `max_backoff` between retries while `pcscd` or a
+        /// reader keeps failing.
+        pub fn with_max_backoff(mut self, max_backoff: Duration) -> Self {
+            self.backoff = Backoff::new(self.poll_interval, max_backoff);
+            self
+        }
+
+        /// Names of the readers PC/SC can see right now.
+        pub fn attached_readers(&self) -> Result<Vec<String>, ReaderError> {
+            let devices = self
                 .context
                 .list_readers_owned()
                 .map_err(ReaderError::from)?;
-            if readers_buf.is_empty() {
-                return Err(ReaderError::backend("no PC/SC readers available"));
-            }
-            let reader_name = readers_buf
-                .first()
-                .ok_or_else(|| ReaderError::backend("failed to obtain reader name from PC/SC"))?
-                .as_c_str();
-            match self
-                .context
-                .connect(reader_name, ShareMode::Shared, Protocols::ANY)
-            {
-                Ok(card) => {
-                    self.card = Some(card);
-                    self.last_uid = None;
-                }
-                Err(PcscError::NoSmartcard) => {
-                    // Card absent: keep polling until one is presented.
-                    self.card = None;
-                    self.last_uid = None;
-                }
-                Err(err) => return Err(ReaderError::from(err)),
-            }
-            Ok(())
+            Ok(devices
+                .iter()
+                .map(|device| device.to_string_lossy().into_owned())
+                .collect())
+        }
+
+        /// Polls only the readers `zones` match, reporting taps under the
+        /// zone's label.
+        pub fn with_zones(mut self, zones: Vec<ReaderZone>) -> Self {
+            self.zones = zones;
+            self
         }
 
-        fn read_uid(card: &Card) -> Result<CardUid, ReaderError> {
-            const SEND_BUFFER: [u8; 5] = [0xFF, 0xCA, 0x00, 0x00, 0x00];
-            let mut recv_buffer = [0u8; pcsc::MAX_BUFFER_SIZE];
-            let response = card
-                .transmit(&SEND_BUFFER, &mut recv_buffer)
+        /// Matches the slots to the attached readers, keeping the state of
+        /// those still plugged in so a resting card is not reported again.
+        fn refresh_slots(&mut self) -> Result<(), ReaderError> {
+            let devices = self
+                .context
+                .list_readers_owned()
                 .map_err(ReaderError::from)?;
-            if response.len() < 2 {
-                return Err(ReaderError::backend("card UID response too short"));
-            }
-            let (data, status) = response.split_at(response.len() - 2);
-            if status != [0x90, 0x00] {
-                return Err(ReaderError::StatusWord {
-                    sw1: status[0],
-                    sw2: status[1],
+            self.slots.retain(|slot| devices.contains(&slot.device));
+            for device in devices {
+                if self.slots.iter().any(|slot| slot.device == device) {
+                    continue;
+                }
+                let Some(label) = zone_label(&self.zones, &device.to_string_lossy()) else {
+                    continue;
+                };
+                tracing::info!(?device, %label, "polling PC/SC reader");
+                self.slots.push(Slot {
+                    device,
+                    label,
+                    card: None,
+                    last_uid: None,
                 });
             }
-            Ok(CardUid::new(data.to_vec()))
+            if self.slots.is_empty() {
+                return Err(ReaderError::backend(if self.zones.is_empty() {
+                    "no PC/SC readers available"
+                } else {
+                    "no attached PC/SC reader matches the [readers] table"
+                }));
+            }
+            Ok(())
         }
 
         fn poll(&mut self) -> Result<Option<ReaderEvent>, ReaderError> {
-            if self.card.is_none() {
-                self.connect_card()?;
+            if let Some(event) = self.pending.pop_front() {
+                return Ok(Some(event));
+            }
+            if self.stale_context {
+                self.context = Context::establish(Scope::User).map_err(ReaderError::from)?;
+                self.slots.clear();
+                self.stale_context = false;
             }
+            self.refresh_slots()?;
+            let mut idle = false;
+            for slot in &mut self.slots {
+                match slot.poll(&self.context)? {
+                    Some(ReaderEvent::Idle) => idle = true,
+                    Some(event) => self.pending.push_back(event),
+                    None => {}
+                }
+            }
+            Ok(self
+                .pending
+                .pop_front()
+                .or(idle.then_some(ReaderEvent::Idle)))
+        }
+    }
+
+    impl Slot {
+        fn forget_card(&mut self) {
+            self.card = None;
+            self.last_uid = None;
+        }
 
+        /// Forgets the card after it left the reader, reporting the removal
+        /// if it had been announced.
+        fn lift_card(&mut self) -> Option<ReaderEvent> {
+            let uid = self.last_uid.take();
+            self.forget_card();
+            uid.map(|uid| ReaderEvent::CardRemoved { uid })
+        }
+
+        fn poll(&mut self, context: &Context) -> Result<Option<ReaderEvent>, ReaderError> {
             if self.card.is_none() {
-                return Ok(None);
-            }
-
-            let status = {
-                let card = self
-                    .card
-                    .as_ref()
-                    .expect("card present after successful connect");
-                match card.status2_owned().map_err(ReaderError::from) {
-                    Ok(status) => status,
-                    Err(ReaderError::Pcsc(PcscError::RemovedCard | PcscError::ResetCard)) => {
-                        self.card = None;
+                match context.connect(&self.device, ShareMode::Shared, Protocols::ANY) {
+                    Ok(card) => {
+                        self.card = Some(card);
                         self.last_uid = None;
-                        return Ok(None);
                     }
-                    Err(err) => return Err(err),
+                    // Card absent: keep polling until one is presented.
+                    Err(PcscError::NoSmartcard) => return Ok(self.lift_card()),
+                    Err(err) => return Err(ReaderError::from(err)),
                 }
-            };
-
-            if !status.status().contains(Status::PRESENT) {
-                self.card = None;
-                self.last_uid = None;
-                return Ok(None);
             }
+            let card = self.card.as_ref().expect("card present after connect");
 
-            let uid_result = {
-                let card = self.card.as_ref().expect("card present while decoding UID");
-                Self::read_uid(card)
+            let status = match card.status2_owned().map_err(ReaderError::from) {
+                Ok(status) => status,
+                Err(ReaderError::Pcsc(PcscError::RemovedCard)) => return Ok(self.lift_card()),
+                Err(ReaderError::Pcsc(PcscError::ResetCard)) => {
+                    self.forget_card();
+                    return Ok(None);
+                }
+                Err(err) => return Err(err),
             };
+            if !status.status().contains(Status::PRESENT) {
+                return Ok(self.lift_card());
+            }
 
-            match uid_result {
-                Ok(uid) => match self.last_uid.as_ref() {
-                    Some(previous) if previous == &uid => Ok(Some(ReaderEvent::Idle)),
-                    _ => {
-                        self.last_uid = Some(uid.clone());
-                        Ok(Some(ReaderEvent::CardPresent { uid }))
-                    }
-                },
-                Err(ReaderError::StatusWord { sw1: 0x63, sw2: 0x00 }) => {
-                    tracing::debug!("PC/SC reported status 6300; resetting reader state");
-                    self.card = None;
-                    self.last_uid = None;
+            match read_uid(card) {
+                Ok(uid) if self.last_uid.as_ref() == Some(&uid) => Ok(Some(ReaderEvent::Idle)),
+                Ok(uid) => {
+                    let ndef_text = match read_ndef_text(card) {
+                        Ok(text) => text,
+                        Err(err) => {
+                            tracing::debug!(?err, %uid, "could not read NDEF data from tag");
+                            None
+                        }
+                    };
+                    self.last_uid = Some(uid.clone());
+                    Ok(Some(ReaderEvent::CardPresent {
+                        uid,
+                        ndef_text,
+                        reader: Some(self.label.clone()),
+                        tap_count: None,
+                    }))
+                }
+                Err(ReaderError::StatusWord {
+                    sw1: 0x63,
+                    sw2: 0x00,
+                }) => {
+                    tracing::debug!(label = %self.label, "PC/SC reported status 6300; resetting reader state");
+                    self.forget_card();
                     Ok(None)
                 }
-                Err(err)
-                    if matches!(
-                        err,
-                        ReaderError::Pcsc(PcscError::RemovedCard | PcscError::ResetCard)
-                    ) =>
-                {
-                    self.card = None;
-                    self.last_uid = None;
+                Err(ReaderError::Pcsc(PcscError::RemovedCard)) => Ok(self.lift_card()),
+                Err(ReaderError::Pcsc(PcscError::ResetCard)) => {
+                    self.forget_card();
                     Ok(None)
                 }
                 Err(err) => Err(err),
@@ -177,15 +633,190 @@ pub mod pcsc_backend {
     }
 
     impl NfcReader for PcscReader {
+        fn capabilities(&self) -> ReaderCapabilities {
+            ReaderCapabilities {
+                card_removed: true,
+                ndef: true,
+            }
+        }
+
+        /// Transient errors, such as `pcscd` restarting or a reader being
+        /// replugged, are retried with a growing delay instead of being
+        /// returned.
         fn next_event(&mut self) -> Result<ReaderEvent, ReaderError> {
             loop {
-                match self.poll()? {
-                    Some(event) => return Ok(event),
-                    None => std::thread::sleep(self.poll_interval),
+                match self.poll() {
+                    Ok(Some(event)) => {
+                        self.recovered();
+                        return Ok(event);
+                    }
+                    Ok(None) => {
+                        self.recovered();
+                        std::thread::sleep(self.poll_interval);
+                    }
+                    Err(ReaderError::Pcsc(err)) if is_transient(err) => {
+                        self.stale_context |= matches!(
+                            err,
+                            PcscError::NoService
+                                | PcscError::ServiceStopped
+                                | PcscError::InvalidHandle
+                        );
+                        let delay = self.backoff.next_delay();
+                        tracing::warn!(
+                            %err,
+                            failures = self.backoff.failures(),
+                            ?delay,
+                            "PC/SC unavailable; backing off"
+                        );
+                        std::thread::sleep(delay);
+                    }
+                    Err(err) => return Err(err),
                 }
             }
         }
     }
+
+    impl PcscReader {
+        fn recovered(&mut self) {
+            if self.backoff.failures() > 0 {
+                tracing::info!(failures = self.backoff.failures(), "PC/SC available again");
+                self.backoff.reset();
+            }
+        }
+    }
+
+    /// Errors that clear up by themselves: the service restarting, a reader
+    /// unplugged or busy, or a card lifted mid-exchange.
+    fn is_transient(err: PcscError) -> bool {
+        matches!(
+            err,
+            PcscError::NoService
+                | PcscError::ServiceStopped
+                | PcscError::InvalidHandle
+                | PcscError::NoReadersAvailable
+                | PcscError::ReaderUnavailable
+                | PcscError::UnknownReader
+                | PcscError::Timeout
+                | PcscError::CommError
+                | PcscError::SharingViolation
+                | PcscError::UnresponsiveCard
+                | PcscError::UnpoweredCard
+        )
+    }
+
+    /// Writes NDEF text records to NTAG21x / MIFARE Ultralight tags using the
+    /// PC/SC storage-card commands (READ BINARY and UPDATE BINARY).
+    pub struct PcscTagWriter {
+        context: Context,
+        poll_interval: Duration,
+    }
+
+    impl PcscTagWriter {
+        pub fn new(poll_interval: Duration) -> Result<Self, ReaderError> {
+            let context = Context::establish(Scope::User).map_err(ReaderError::from)?;
+            Ok(Self {
+                context,
+                poll_interval,
+            })
+        }
+
+        /// Waits for `expected` to be presented, writes `text` to it as an NDEF
+        /// text record, then reads every written page back to verify it.
+        pub fn write_text(&self, expected: &CardUid, text: &str) -> Result<(), ReaderError> {
+            let card = self.wait_for_card()?;
+            let found = read_uid(&card)?;
+            if &found != expected {
+                return Err(ReaderError::WrongTag {
+                    expected: expected.clone(),
+                    found,
+                });
+            }
+
+            let capability: [u8; 4] = read_pages(&card, ndef::CAPABILITY_PAGE)?[..4]
+                .try_into()
+                .expect("read returns four pages");
+            let available = ndef::data_area_size(capability)
+                .ok_or(ReaderError::NotNdefFormatted(capability))?;
+            let pages = ndef::tlv_pages(&ndef::text_record(text, "en"));
+            let needed = pages.len() * ndef::PAGE_SIZE;
+            if needed > available {
+                return Err(ReaderError::TagTooSmall { needed, available });
+            }
+
+            for (page, data) in (ndef::FIRST_DATA_PAGE..).zip(&pages) {
+                let mut apdu = vec![0xFF, 0xD6, 0x00, page, data.len() as u8];
+                apdu.extend_from_slice(data);
+                transmit(&card, &apdu)?;
+            }
+            for (first, chunk) in (ndef::FIRST_DATA_PAGE..).step_by(4).zip(pages.chunks(4)) {
+                let read = read_pages(&card, first)?;
+                for ((page, expected), actual) in
+                    (first..).zip(chunk).zip(read.chunks(ndef::PAGE_SIZE))
+                {
+                    if actual != expected {
+                        return Err(ReaderError::VerifyMismatch { page });
+                    }
+                }
+            }
+            Ok(())
+        }
+
+        fn wait_for_card(&self) -> Result<Card, ReaderError> {
+            loop {
+                let readers = self.context.list_readers_owned()?;
+                let reader = readers
+                    .first()
+                    .ok_or_else(|| ReaderError::backend("no PC/SC readers available"))?;
+                match self
+                    .context
+                    .connect(reader, ShareMode::Exclusive, Protocols::ANY)
+                {
+                    Ok(card) => return Ok(card),
+                    Err(PcscError::NoSmartcard) => std::thread::sleep(self.poll_interval),
+                    Err(err) => return Err(ReaderError::from(err)),
+                }
+            }
+        }
+    }
+
+    /// Sends an APDU and returns the response data once the card reports 9000.
+    fn transmit(card: &Card, apdu: &[u8]) -> Result<Vec<u8>, ReaderError> {
+        let mut recv_buffer = [0u8; pcsc::MAX_BUFFER_SIZE];
+        let response = card
+            .transmit(apdu, &mut recv_buffer)
+            .map_err(ReaderError::from)?;
+        if response.len() < 2 {
+            return Err(ReaderError::backend("card response too short"));
+        }
+        let (data, status) = response.split_at(response.len() - 2);
+        if status != [0x90, 0x00] {
+            return Err(ReaderError::StatusWord {
+                sw1: status[0],
+                sw2: status[1],
+            });
+        }
+        Ok(data.to_vec())
+    }
+
+    fn read_uid(card: &Card) -> Result<CardUid, ReaderError> {
+        transmit(card, &[0xFF, 0xCA, 0x00, 0x00, 0x00]).map(CardUid::new)
+    }
+
+    fn read_ndef_text(card: &Card) -> Result<Option<String>, ReaderError> {
+        ndef::read_tag_text(|page| read_pages(card, page))
+    }
+
+    /// Reads the four pages (16 bytes) starting at `page`.
+    fn read_pages(card: &Card, page: u8) -> Result<Vec<u8>, ReaderError> {
+        let data = transmit(card, &[0xFF, 0xB0, 0x00, page, 0x10])?;
+        if data.len() < 16 {
+            return Err(ReaderError::backend(format!(
+                "short read of {} bytes at page {page}",
+                data.len()
+            )));
+        }
+        Ok(data)
+    }
 }
 
 #[cfg(test)]
@@ -203,10 +834,177 @@ mod tests {
         );
     }
 
+    #[test]
+    fn ndef_text_record_is_wrapped_in_padded_tlv_pages() {
+        let record = ndef::text_record("a.mp3", "en");
+        assert_eq!(
+            record,
+            [
+                0xD1, 0x01, 0x08, b'T', 0x02, b'e', b'n', b'a', b'.', b'm', b'p', b'3'
+            ]
+        );
+
+        let pages = ndef::tlv_pages(&record);
+        assert_eq!(pages.len(), 4);
+        assert_eq!(pages[0], [0x03, 0x0C, 0xD1, 0x01]);
+        assert_eq!(pages[3], [b'p', b'3', 0xFE, 0x00]);
+    }
+
+    #[test]
+    fn ndef_long_records_use_extended_lengths() {
+        let text = "x".repeat(300);
+        let record = ndef::text_record(&text, "en");
+        assert_eq!(&record[..6], &[0xC1, 0x01, 0x00, 0x00, 0x01, 0x2F]);
+
+        let pages = ndef::tlv_pages(&record);
+        assert_eq!(&pages[0][..4], &[0x03, 0xFF, 0x01, 0x36]);
+    }
+
+    #[test]
+    fn ndef_text_round_trips_through_tag_pages() {
+        for text in ["album/track02.ogg", &"long/".repeat(80)] {
+            let data: Vec<u8> = ndef::tlv_pages(&ndef::text_record(text, "en")).concat();
+            assert_eq!(ndef::read_text(&data).as_deref(), Some(text));
+            assert_eq!(ndef::read_text(&data[..data.len() / 2]), None);
+        }
+    }
+
+    #[test]
+    fn ndef_reader_skips_other_tlvs_and_ignores_non_text_records() {
+        // A lock control TLV, then a URI record ("U") rather than text.
+        let data = [
+            0x01, 0x03, 0xA0, 0x0C, 0x34, 0x03, 0x06, 0xD1, 0x01, 0x02, b'U', 0x04, b'x', 0xFE,
+        ];
+        assert_eq!(ndef::read_text(&data), None);
+
+        let mut padded = vec![0x00, 0x00];
+        padded.extend(ndef::tlv_pages(&ndef::text_record("a.mp3", "de")).concat());
+        assert_eq!(ndef::read_text(&padded).as_deref(), Some("a.mp3"));
+    }
+
+    #[test]
+    fn ndef_capability_container_gives_data_area_size() {
+        // NTAG213: 144 bytes of user memory.
+        assert_eq!(ndef::data_area_size([0xE1, 0x10, 0x12, 0x00]), Some(144));
+        assert_eq!(ndef::data_area_size([0x00, 0x00, 0x00, 0x00]), None);
+    }
+
+    #[test]
+    fn zones_label_matching_readers_and_drop_the_rest() {
+        assert_eq!(
+            zone_label(&[], "ACS ACR122U 00 00").as_deref(),
+            Some("ACS ACR122U 00 00")
+        );
+        let zones = [
+            ReaderZone {
+                label: "front".into(),
+                device: "acr122u 00 00".into(),
+            },
+            ReaderZone {
+                label: "back".into(),
+                device: "ACR122U 01".into(),
+            },
+        ];
+        assert_eq!(
+            zone_label(&zones, "ACS ACR122U 00 00").as_deref(),
+            Some("front")
+        );
+        assert_eq!(
+            zone_label(&zones, "ACS ACR122U 01 00").as_deref(),
+            Some("back")
+        );
+        assert_eq!(zone_label(&zones, "Yubikey 5"), None);
+    }
+
+    #[test]
+    fn parses_replay_script() {
+        let script = "
+# demo
+0a0b 250
+wait 1000
+0C0D   # trailing comment
+";
+        let steps = parse_replay_script(script).unwrap();
+        assert_eq!(
+            steps,
+            vec![
+                ReplayStep::Tap(CardUid::new(vec![0x0a, 0x0b])),
+                ReplayStep::Wait(Duration::from_millis(250)),
+                ReplayStep::Wait(Duration::from_millis(1000)),
+                ReplayStep::Tap(CardUid::new(vec![0x0c, 0x0d])),
+            ]
+        );
+    }
+
+    #[test]
+    fn replay_script_errors_report_line_numbers() {
+        assert!(matches!(
+            parse_replay_script("0a0b\nzz"),
+            Err(ReplayParseError::CardUid { line: 2, .. })
+        ));
+        assert!(matches!(
+            parse_replay_script("wait soon"),
+            Err(ReplayParseError::Delay { line: 1, .. })
+        ));
+        assert!(matches!(
+            parse_replay_script("wait"),
+            Err(ReplayParseError::Delay { line: 1, .. })
+        ));
+        assert!(matches!(
+            parse_replay_script("0a0b 10 20"),
+            Err(ReplayParseError::Trailing { line: 1, .. })
+        ));
+    }
+
+    #[test]
+    fn replay_reader_emits_taps_then_shutdown() {
+        let uid = CardUid::new(vec![1, 2]);
+        let mut reader = ReplayReader::new(vec![
+            ReplayStep::Wait(Duration::ZERO),
+            ReplayStep::Tap(uid.clone()),
+        ]);
+
+        assert_eq!(
+            reader.next_event().unwrap(),
+            ReaderEvent::CardPresent {
+                uid,
+                ndef_text: None,
+                reader: None,
+                tap_count: None,
+            }
+        );
+        assert_eq!(reader.next_event().unwrap(), ReaderEvent::Shutdown);
+    }
+
     #[test]
     fn reader_event_card_present_holds_uid() {
         let uid = CardUid::new(vec![1, 2, 3, 4]);
-        let event = ReaderEvent::CardPresent { uid: uid.clone() };
-        assert_eq!(event, ReaderEvent::CardPresent { uid });
+        let event = ReaderEvent::CardPresent {
+            uid: uid.clone(),
+            ndef_text: None,
+            reader: None,
+            tap_count: None,
+        };
+        assert!(matches!(event, ReaderEvent::CardPresent { uid: found, .. } if found == uid));
+    }
+
+    #[test]
+    fn backoff_doubles_with_jitter_up_to_the_cap_and_resets() {
+        let mut backoff = Backoff::new(Duration::from_millis(200), Duration::from_secs(1));
+        for ceiling in [200, 400, 800, 1_000, 1_000] {
+            let delay = backoff.next_delay();
+            assert!(
+                (Duration::from_millis(ceiling / 2)..=Duration::from_millis(ceiling))
+                    .contains(&delay),
+                "{delay:?} outside the range for {ceiling} ms"
+            );
+        }
+        assert_eq!(backoff.failures(), 5);
+        backoff.reset();
+        assert_eq!(backoff.failures(), 0);
+        assert!(backoff.next_delay() <= Duration::from_millis(200));
+
+        let mut floored = Backoff::new(Duration::from_secs(2), Duration::from_secs(1));
+        assert!(floored.next_delay() >= Duration::from_secs(1));
     }
 }
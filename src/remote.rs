@@ -0,0 +1,220 @@
+//! Client for driving an already-running musicbox through its debug HTTP API.
+//!
+//! The daemon owns the sound card, so one-off CLI commands should ask it to
+//! act instead of opening a second audio stream. Callers decide what to do
+//! when no instance is listening; [`RemoteError::Unreachable`] is kept
+//! distinct so they can fall back to standalone mode.
+//!
+//! [`RemoteLibrary`] reads another box's config and tracks for `musicbox sync`.
+
+use crate::controller::CardUid;
+use crate::webhook::{self, WebhookError, WebhookUrl};
+use std::net::SocketAddr;
+
+#[derive(Debug, thiserror::Error)]
+pub enum RemoteError {
+    #[error("no musicbox instance reachable at {addr}: {source}")]
+    Unreachable {
+        addr: SocketAddr,
+        #[source]
+        source: std::io::Error,
+    },
+    #[error("running instance rejected the request ({status}): {message}")]
+    Rejected { status: u16, message: String },
+    #[error(transparent)]
+    Transport(#[from] WebhookError),
+    #[error("unexpected response from instance: {0}")]
+    Decode(#[from] serde_json::Error),
+}
+
+/// Handle to a musicbox instance serving the debug HTTP API.
+#[derive(Debug, Clone, Copy)]
+pub struct RemoteController {
+    addr: SocketAddr,
+}
+
+impl RemoteController {
+    pub fn new(addr: SocketAddr) -> Self {
+        Self { addr }
+    }
+
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// Asks the running instance to handle `uid` as if it had been tapped.
+    /// Returns the human-readable message the instance reported.
+    pub fn trigger(&self, uid: &CardUid) -> Result<String, RemoteError> {
+        self.trigger_ref(&uid.to_hex_lowercase())
+    }
+
+    /// Like [`RemoteController::trigger`], but lets the instance resolve a
+    /// card nickname or hex UID against its own library.
+    pub fn trigger_ref(&self, card: &str) -> Result<String, RemoteError> {
+        let payload = serde_json::json!({ "card_hex": card });
+        let body = self.post("/api/play", &payload)?;
+        Ok(body["message"].as_str().unwrap_or_default().to_string())
+    }
+
+    /// Mutes or unmutes the running instance, returning the state it reports.
+    pub fn set_muted(&self, muted: bool) -> Result<bool, RemoteError> {
+        let payload = serde_json::json!({ "muted": muted });
+        let body = self.post("/api/mute", &payload)?;
+        Ok(body["muted"].as_bool().unwrap_or(muted))
+    }
+
+    fn post(
+        &self,
+        path: &str,
+        payload: &serde_json::Value,
+    ) -> Result<serde_json::Value, RemoteError> {
+        let url = WebhookUrl::from_socket_addr(self.addr, path);
+        let response = webhook::send_json(&url, payload).map_err(|err| match err {
+            WebhookError::Io(source) => RemoteError::Unreachable {
+                addr: self.addr,
+                source,
+            },
+            other => RemoteError::Transport(other),
+        })?;
+
+        let body: serde_json::Value = serde_json::from_str(&response.body).unwrap_or_default();
+        if response.is_success() {
+            Ok(body)
+        } else {
+            Err(RemoteError::Rejected {
+                status: response.status,
+                message: body["error"]
+                    .as_str()
+                    .map(str::to_string)
+                    .unwrap_or(response.body),
+            })
+        }
+    }
+}
+
+/// Another musicbox whose library can be copied, addressed by base URL.
+#[derive(Debug, Clone)]
+pub struct RemoteLibrary {
+    base: WebhookUrl,
+}
+
+impl RemoteLibrary {
+    /// Accepts `http://host:port`, optionally with a path prefix.
+    pub fn parse(url: &str) -> Result<Self, RemoteError> {
+        Ok(Self {
+            base: WebhookUrl::parse(url)?,
+        })
+    }
+
+    /// The remote config file, as TOML text.
+    pub fn fetch_config(&self) -> Result<String, RemoteError> {
+        let body = self.get("/api/config")?;
+        let response: serde_json::Value = serde_json::from_slice(&body)?;
+        Ok(response["contents"]
+            .as_str()
+            .unwrap_or_default()
+            .to_string())
+    }
+
+    /// Downloads a track by its path relative to the remote `music_dir`.
+    pub fn fetch_track(&self, entry: &str) -> Result<Vec<u8>, RemoteError> {
+        self.get(&format!("/api/tracks/{}", percent_encode_path(entry)))
+    }
+
+    fn get(&self, path: &str) -> Result<Vec<u8>, RemoteError> {
+        let response = webhook::get(&self.base.join(path))?;
+        if (200..300).contains(&response.status) {
+            return Ok(response.body);
+        }
+        let body: serde_json::Value = serde_json::from_slice(&response.body).unwrap_or_default();
+        Err(RemoteError::Rejected {
+            status: response.status,
+            message: body["error"]
+                .as_str()
+                .map(str::to_string)
+                .unwrap_or_else(|| String::from_utf8_lossy(&response.body).into_owned()),
+        })
+    }
+}
+
+/// Percent-encodes everything but unreserved characters and `/`.
+fn percent_encode_path(path: &str) -> String {
+    path.bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b'/' => {
+                (byte as char).to_string()
+            }
+            other => format!("%{other:02X}"),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_http::{serve_once, unused_addr};
+
+    #[test]
+    fn trigger_returns_instance_message() {
+        let addr = serve_once(
+            "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\n\r\n{\"message\":\"Started\"}",
+        );
+
+        let message = RemoteController::new(addr)
+            .trigger(&CardUid::new(vec![0xde, 0xad]))
+            .unwrap();
+
+        assert_eq!(message, "Started");
+    }
+
+    #[test]
+    fn set_muted_reports_the_instance_state() {
+        let addr = serve_once("HTTP/1.1 200 OK\r\n\r\n{\"muted\":true}");
+
+        assert!(RemoteController::new(addr).set_muted(true).unwrap());
+    }
+
+    #[test]
+    fn trigger_surfaces_rejections() {
+        let addr = serve_once(
+            "HTTP/1.1 404 Not Found\r\n\r\n{\"error\":\"controller error: track not found for card\"}",
+        );
+
+        let err = RemoteController::new(addr)
+            .trigger(&CardUid::new(vec![0xde, 0xad]))
+            .unwrap_err();
+
+        assert!(
+            matches!(err, RemoteError::Rejected { status: 404, ref message } if message.contains("track not found"))
+        );
+    }
+
+    #[test]
+    fn remote_library_fetches_config_contents() {
+        let addr = serve_once(
+            "HTTP/1.1 200 OK\r\n\r\n{\"path\":\"/etc/musicbox.toml\",\"contents\":\"music_dir = \\\"/m\\\"\"}",
+        );
+
+        let remote = RemoteLibrary::parse(&format!("http://{addr}")).unwrap();
+
+        assert_eq!(remote.fetch_config().unwrap(), "music_dir = \"/m\"");
+    }
+
+    #[test]
+    fn track_paths_are_percent_encoded() {
+        assert_eq!(
+            percent_encode_path("Kinderlieder/Alle meine Entchen.mp3"),
+            "Kinderlieder/Alle%20meine%20Entchen.mp3"
+        );
+        assert_eq!(percent_encode_path("café#1.ogg"), "caf%C3%A9%231.ogg");
+    }
+
+    #[test]
+    fn trigger_reports_unreachable_instance() {
+        let err = RemoteController::new(unused_addr())
+            .trigger(&CardUid::new(vec![0xde, 0xad]))
+            .unwrap_err();
+
+        assert!(matches!(err, RemoteError::Unreachable { .. }));
+    }
+}
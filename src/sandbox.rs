@@ -0,0 +1,123 @@
+//! Keeping track paths inside `music_dir`.
+//!
+//! A track named by an NDEF tag, the web API, or another box could point
+//! anywhere: `../../etc/shadow`, an absolute path, or a symlink someone
+//! dropped into the music folder. These checks catch all three.
+
+use std::path::{Component, Path, PathBuf};
+
+/// Whether `path` stays inside `dir` once `.` and `..` are applied and the
+/// symlinks along the parts of it that exist are followed.
+pub fn is_inside(dir: &Path, path: &Path) -> bool {
+    let (dir, path) = (normalize(dir), normalize(path));
+    path.starts_with(&dir) && resolve(&path).starts_with(resolve(&dir))
+}
+
+/// `dir` joined with `entry`, an untrusted relative path, or `None` if
+/// `entry` is empty, absolute, climbs out with `..`, or leads out through a
+/// symlink. With `follow_symlinks_out`, symlinks may lead anywhere.
+pub fn confine(dir: &Path, entry: &str, follow_symlinks_out: bool) -> Option<PathBuf> {
+    let relative = Path::new(entry.trim());
+    let plain = relative
+        .components()
+        .all(|component| matches!(component, Component::Normal(_)));
+    if entry.trim().is_empty() || !plain {
+        return None;
+    }
+    let path = dir.join(relative);
+    (follow_symlinks_out || is_inside(dir, &path)).then_some(path)
+}
+
+/// Applies `.` and `..` without looking at the filesystem. A `..` above
+/// the start of a relative path is kept.
+fn normalize(path: &Path) -> PathBuf {
+    let mut normal = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                if !matches!(normal.components().next_back(), Some(Component::Normal(_)))
+                    && !normal.has_root()
+                {
+                    normal.push(component);
+                } else {
+                    normal.pop();
+                }
+            }
+            component => normal.push(component),
+        }
+    }
+    normal
+}
+
+/// Follows the symlinks in the longest part of `path` that exists. The
+/// rest, which may be a file about to be written, is appended unchanged.
+fn resolve(path: &Path) -> PathBuf {
+    let mut existing = path;
+    let mut rest = Vec::new();
+    loop {
+        if let Ok(resolved) = existing.canonicalize() {
+            return rest
+                .iter()
+                .rev()
+                .fold(resolved, |path, part| path.join(part));
+        }
+        match (existing.parent(), existing.file_name()) {
+            (Some(parent), Some(name)) => {
+                rest.push(name);
+                existing = parent;
+            }
+            _ => return path.to_path_buf(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dot_dot_and_absolute_entries_stay_out() {
+        let dir = Path::new("/music");
+        assert!(is_inside(dir, Path::new("/music/a/../b.mp3")));
+        assert!(!is_inside(dir, Path::new("/music/../etc/passwd")));
+        assert!(!is_inside(dir, Path::new("/srv/b.mp3")));
+        assert!(is_inside(Path::new("music"), Path::new("./music/b.mp3")));
+
+        assert_eq!(
+            confine(dir, " a/b.mp3 ", false),
+            Some(PathBuf::from("/music/a/b.mp3"))
+        );
+        for entry in [
+            "",
+            "/etc/passwd",
+            "../secret.mp3",
+            "a/../../b.mp3",
+            "./a.mp3",
+        ] {
+            assert_eq!(confine(dir, entry, true), None, "{entry}");
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn symlinks_out_of_the_folder_are_caught_unless_allowed() {
+        let root = tempfile::tempdir().unwrap();
+        let music = root.path().join("music");
+        let outside = root.path().join("outside");
+        std::fs::create_dir_all(music.join("albums")).unwrap();
+        std::fs::create_dir_all(&outside).unwrap();
+        std::fs::write(outside.join("secret.mp3"), b"").unwrap();
+        std::os::unix::fs::symlink(&outside, music.join("nas")).unwrap();
+        std::os::unix::fs::symlink(music.join("albums"), music.join("shortcut")).unwrap();
+
+        assert!(!is_inside(&music, &music.join("nas/secret.mp3")));
+        assert!(!is_inside(&music, &music.join("nas/new/track.mp3")));
+        assert!(is_inside(&music, &music.join("shortcut/new.mp3")));
+        assert_eq!(confine(&music, "nas/secret.mp3", false), None);
+        assert_eq!(
+            confine(&music, "nas/secret.mp3", true),
+            Some(music.join("nas/secret.mp3"))
+        );
+    }
+}
@@ -0,0 +1,278 @@
+//! Finds cards and tracks by what a person remembers about them: a card's
+//! name, a file name, or the title, artist, or album its tags record.
+//!
+//! The index is held in memory. [`Library::search_index`] builds it the
+//! first time it is asked for after the cards load, so a reload starts
+//! over with the new cards.
+
+use crate::config::audio_files_under;
+use crate::controller::{CardUid, Library};
+use crate::metadata::{self, Tags};
+use std::path::{Path, PathBuf};
+
+/// How many hits a search returns unless asked for another number.
+pub const DEFAULT_LIMIT: usize = 20;
+
+/// What a hit refers to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HitKind {
+    /// A mapped card, found by its name or its tracks' file names.
+    Card,
+    /// An audio file under `music_dir`, found by its name or its tags.
+    File,
+}
+
+/// One result of [`SearchIndex::search`], best first.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SearchHit {
+    pub kind: HitKind,
+    /// The card's first track, or the file; relative to `music_dir` when
+    /// it lies inside it.
+    pub track: PathBuf,
+    /// The card, or the card whose playlist includes the file.
+    pub card: Option<CardUid>,
+    pub name: Option<String>,
+    pub tags: Tags,
+    pub score: u32,
+}
+
+#[derive(Debug)]
+struct Entry {
+    hit: SearchHit,
+    words: Vec<String>,
+}
+
+/// Cards and audio files with the words they can be found by.
+#[derive(Debug)]
+pub struct SearchIndex {
+    entries: Vec<Entry>,
+}
+
+impl SearchIndex {
+    /// Indexes `library`'s cards and every audio file under its
+    /// `music_dir`, reading each file's tags.
+    pub fn build(library: &Library) -> Self {
+        let music_dir = library.music_dir();
+        let relative = |path: &Path| {
+            path.strip_prefix(music_dir)
+                .map_or_else(|_| path.to_path_buf(), Path::to_path_buf)
+        };
+        let mut cards = library.entries();
+        cards.sort_by(|(a, _), (b, _)| a.0.cmp(&b.0));
+
+        let mut entries = Vec::new();
+        let mut mapped = Vec::new();
+        for (card, first) in &cards {
+            let name = library.name(card).map(str::to_string);
+            let mut words = name.as_deref().map(normalize).unwrap_or_default();
+            for track in library.playlist(card).unwrap_or_default() {
+                words.extend(path_words(&relative(track.path())));
+                mapped.push((track.path().to_path_buf(), card.clone(), name.clone()));
+            }
+            entries.push(Entry {
+                hit: SearchHit {
+                    kind: HitKind::Card,
+                    track: relative(first.path()),
+                    card: Some(card.clone()),
+                    name,
+                    tags: Tags::default(),
+                    score: 0,
+                },
+                words,
+            });
+        }
+
+        let mut files = audio_files_under(music_dir).unwrap_or_else(|err| {
+            tracing::debug!(%err, dir = %music_dir.display(), "no music to index");
+            Vec::new()
+        });
+        files.sort();
+        for file in files {
+            let tags = metadata::tags(&file).unwrap_or_default();
+            let track = relative(&file);
+            let mut words = path_words(&track);
+            for tag in [&tags.title, &tags.artist, &tags.album]
+                .into_iter()
+                .flatten()
+            {
+                words.extend(normalize(tag));
+            }
+            let owner = mapped.iter().find(|(path, _, _)| file.starts_with(path));
+            entries.push(Entry {
+                hit: SearchHit {
+                    kind: HitKind::File,
+                    track,
+                    card: owner.map(|(_, card, _)| card.clone()),
+                    name: owner.and_then(|(_, _, name)| name.clone()),
+                    tags,
+                    score: 0,
+                },
+                words,
+            });
+        }
+        Self { entries }
+    }
+
+    /// The entries matching every word of `query`, best first, at most
+    /// `limit` of them. A word matches a whole word, the start or middle
+    /// of one, or one a typo or two away, in that order of preference.
+    pub fn search(&self, query: &str, limit: usize) -> Vec<SearchHit> {
+        let terms = normalize(query);
+        if terms.is_empty() {
+            return Vec::new();
+        }
+        let mut hits: Vec<SearchHit> = self
+            .entries
+            .iter()
+            .filter_map(|entry| {
+                let score = terms.iter().try_fold(0, |total, term| {
+                    let best = entry
+                        .words
+                        .iter()
+                        .map(|word| word_score(term, word))
+                        .max()
+                        .unwrap_or(0);
+                    (best > 0).then_some(total + best)
+                })?;
+                Some(SearchHit {
+                    score,
+                    ..entry.hit.clone()
+                })
+            })
+            .collect();
+        hits.sort_by(|a, b| {
+            b.score
+                .cmp(&a.score)
+                .then_with(|| (a.kind == HitKind::File).cmp(&(b.kind == HitKind::File)))
+                .then_with(|| a.track.cmp(&b.track))
+        });
+        hits.truncate(limit);
+        hits
+    }
+
+    /// The card the best hit for `query` plays, for commands that take a
+    /// card and were given a title instead.
+    pub fn best_card(&self, query: &str) -> Option<CardUid> {
+        self.search(query, usize::MAX)
+            .into_iter()
+            .find_map(|hit| hit.card)
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+/// Lowercased words, split at anything that is not a letter or digit.
+fn normalize(text: &str) -> Vec<String> {
+    text.to_lowercase()
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+/// The words of a path's folders and file stem, without its extension.
+fn path_words(path: &Path) -> Vec<String> {
+    let mut words: Vec<String> = path
+        .parent()
+        .into_iter()
+        .flat_map(|parent| parent.iter())
+        .flat_map(|part| normalize(&part.to_string_lossy()))
+        .collect();
+    if let Some(stem) = path.file_stem() {
+        words.extend(normalize(&stem.to_string_lossy()));
+    }
+    words
+}
+
+fn word_score(term: &str, word: &str) -> u32 {
+    if word == term {
+        4
+    } else if word.starts_with(term) {
+        3
+    } else if word.contains(term) {
+        2
+    } else {
+        let allowed = match term.chars().count() {
+            0..=3 => 0,
+            4..=7 => 1,
+            _ => 2,
+        };
+        u32::from(allowed > 0 && edit_distance(term, word) <= allowed)
+    }
+}
+
+/// Levenshtein distance between `a` and `b`, by characters.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = vec![i + 1; b.len() + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitute = previous[j] + usize::from(ca != *cb);
+            current[j + 1] = substitute.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::controller::Track;
+    use std::collections::HashMap;
+
+    #[test]
+    fn finds_cards_and_files_by_name_title_and_near_misses() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir(dir.path().join("stories")).unwrap();
+        for name in ["stories/the-gruffalo.mp3", "stories/room_on_the_broom.mp3"] {
+            std::fs::write(dir.path().join(name), b"not audio").unwrap();
+        }
+        let card = CardUid::new(vec![0x0a]);
+        let library = Library::new(HashMap::from([(
+            card.clone(),
+            Track::new(dir.path().join("stories/the-gruffalo.mp3")),
+        )]))
+        .with_names(HashMap::from([(card.clone(), "Bedtime".to_string())]))
+        .with_music_dir(dir.path().into());
+        let index = SearchIndex::build(&library);
+        assert_eq!(index.len(), 3);
+
+        let hits = index.search("gruffalo", DEFAULT_LIMIT);
+        assert_eq!(hits.len(), 2);
+        assert_eq!(hits[0].kind, HitKind::Card);
+        assert_eq!(hits[1].track, Path::new("stories/the-gruffalo.mp3"));
+        assert_eq!(hits[1].card, Some(card.clone()));
+        assert_eq!(hits[1].name.as_deref(), Some("Bedtime"));
+
+        let hits = index.search("Broom", DEFAULT_LIMIT);
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].card, None);
+        assert_eq!(index.search("grufalo", DEFAULT_LIMIT).len(), 2);
+        assert_eq!(index.search("bedtme", DEFAULT_LIMIT)[0].kind, HitKind::Card);
+        assert!(index.search("gruffalo broom", DEFAULT_LIMIT).is_empty());
+        assert!(index.search("  ", DEFAULT_LIMIT).is_empty());
+        assert_eq!(index.best_card("the gruffalo"), Some(card));
+        assert_eq!(index.best_card("broom"), None);
+    }
+
+    #[test]
+    fn closer_matches_rank_first() {
+        assert_eq!(
+            normalize("Der Grüffelo (Hörspiel)"),
+            ["der", "grüffelo", "hörspiel"]
+        );
+        assert!(word_score("moon", "moon") > word_score("moon", "moonlight"));
+        assert!(word_score("moon", "moonlight") > word_score("moon", "honeymoon"));
+        assert!(word_score("moon", "honeymoon") > word_score("moon", "mood"));
+        assert_eq!(word_score("moon", "sun"), 0);
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+    }
+}
@@ -0,0 +1,99 @@
+//! Credentials kept out of the main config.
+//!
+//! The config is edited from the debug dashboard, copied between boxes by
+//! `musicbox sync`, and pasted into bug reports, so passwords and tokens
+//! are better kept in a separate file only the musicbox user can read. The
+//! config names that file with `secrets = "..."` and refers to its entries
+//! as `{ secret = "<key>" }` wherever a credential is expected.
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, thiserror::Error)]
+pub enum SecretsError {
+    #[error("failed to read secrets file {path:?}: {source}")]
+    Read {
+        path: PathBuf,
+        #[source]
+        source: std::io::Error,
+    },
+    #[error("invalid secrets file {path:?}: {source}")]
+    Parse {
+        path: PathBuf,
+        #[source]
+        source: toml::de::Error,
+    },
+}
+
+/// Named credentials from a flat TOML file of `key = "value"` lines.
+#[derive(Debug, Clone, Default)]
+pub struct Secrets {
+    values: HashMap<String, String>,
+}
+
+impl Secrets {
+    pub fn load(path: &Path) -> Result<Self, SecretsError> {
+        let contents = fs::read_to_string(path).map_err(|source| SecretsError::Read {
+            path: path.to_path_buf(),
+            source,
+        })?;
+        warn_if_shared(path);
+        let values = toml::from_str(&contents).map_err(|source| SecretsError::Parse {
+            path: path.to_path_buf(),
+            source,
+        })?;
+        Ok(Self { values })
+    }
+
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.values.get(key).map(String::as_str)
+    }
+}
+
+/// Logs a warning when other users on the box can read the file.
+#[cfg(unix)]
+fn warn_if_shared(path: &Path) {
+    use std::os::unix::fs::PermissionsExt;
+    if let Ok(metadata) = fs::metadata(path)
+        && metadata.permissions().mode() & 0o077 != 0
+    {
+        tracing::warn!(
+            ?path,
+            "secrets file is readable by other users; run `chmod 600` on it"
+        );
+    }
+}
+
+#[cfg(not(unix))]
+fn warn_if_shared(_path: &Path) {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn loads_string_entries_and_rejects_other_values() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("secrets.toml");
+        fs::write(
+            &path,
+            "subsonic = \"hunter2\"\n\"pushover-token\" = \"abc\"\n",
+        )
+        .unwrap();
+        let secrets = Secrets::load(&path).unwrap();
+        assert_eq!(secrets.get("subsonic"), Some("hunter2"));
+        assert_eq!(secrets.get("pushover-token"), Some("abc"));
+        assert_eq!(secrets.get("missing"), None);
+
+        fs::write(&path, "port = 8080\n").unwrap();
+        assert!(matches!(
+            Secrets::load(&path),
+            Err(SecretsError::Parse { .. })
+        ));
+        assert!(matches!(
+            Secrets::load(&dir.path().join("absent.toml")),
+            Err(SecretsError::Read { .. })
+        ));
+    }
+}
@@ -0,0 +1,197 @@
+//! Building blocks for the `musicbox setup` wizard: asking questions on a
+//! terminal, finding hardware, and writing the first config.
+
+use std::fs;
+use std::io::{self, BufRead, Write};
+use std::path::{Path, PathBuf};
+use toml_edit::{DocumentMut, table, value};
+
+/// Serial ports a PN532 board is commonly wired to: the Pi's UART, and
+/// USB serial adapters.
+const SERIAL_PORT_PREFIXES: [&str; 4] = ["serial", "ttyAMA", "ttyUSB", "ttyACM"];
+
+/// Asks questions on a terminal. Every question has a default, taken when
+/// the answer is left empty.
+pub struct Prompter<R, W> {
+    input: R,
+    output: W,
+}
+
+impl<R: BufRead, W: Write> Prompter<R, W> {
+    pub fn new(input: R, output: W) -> Self {
+        Self { input, output }
+    }
+
+    /// Asks for free text.
+    pub fn ask(&mut self, question: &str, default: &str) -> io::Result<String> {
+        write!(self.output, "{question} [{default}]: ")?;
+        let answer = self.answer()?;
+        Ok(if answer.is_empty() {
+            default.to_string()
+        } else {
+            answer
+        })
+    }
+
+    /// Asks a yes-or-no question.
+    pub fn confirm(&mut self, question: &str, default: bool) -> io::Result<bool> {
+        let hint = if default { "Y/n" } else { "y/N" };
+        loop {
+            write!(self.output, "{question} [{hint}]: ")?;
+            match self.answer()?.to_ascii_lowercase().as_str() {
+                "" => return Ok(default),
+                "y" | "yes" => return Ok(true),
+                "n" | "no" => return Ok(false),
+                _ => writeln!(self.output, "Please answer y or n.")?,
+            }
+        }
+    }
+
+    /// Asks for one of `options` by number, returning its index. The first
+    /// option is the default.
+    pub fn choose(&mut self, question: &str, options: &[String]) -> io::Result<usize> {
+        writeln!(self.output, "{question}")?;
+        for (number, option) in options.iter().enumerate() {
+            writeln!(self.output, "  {}) {option}", number + 1)?;
+        }
+        loop {
+            write!(self.output, "Choice [1]: ")?;
+            let answer = self.answer()?;
+            if answer.is_empty() {
+                return Ok(0);
+            }
+            match answer.parse::<usize>() {
+                Ok(number) if (1..=options.len()).contains(&number) => return Ok(number - 1),
+                _ => writeln!(
+                    self.output,
+                    "Please enter a number from 1 to {}.",
+                    options.len()
+                )?,
+            }
+        }
+    }
+
+    fn answer(&mut self) -> io::Result<String> {
+        self.output.flush()?;
+        let mut line = String::new();
+        if self.input.read_line(&mut line)? == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "input ended before setup finished",
+            ));
+        }
+        Ok(line.trim().to_string())
+    }
+}
+
+/// Serial ports under `/dev` that a PN532 board might be on, sorted.
+pub fn serial_ports() -> Vec<PathBuf> {
+    serial_ports_in(Path::new("/dev"))
+}
+
+fn serial_ports_in(dev: &Path) -> Vec<PathBuf> {
+    let Ok(entries) = fs::read_dir(dev) else {
+        return Vec::new();
+    };
+    let mut ports: Vec<PathBuf> = entries
+        .filter_map(Result::ok)
+        .filter(|entry| {
+            let name = entry.file_name();
+            let name = name.to_string_lossy();
+            SERIAL_PORT_PREFIXES.iter().any(|prefix| {
+                name.strip_prefix(prefix)
+                    .is_some_and(|rest| !rest.is_empty())
+            })
+        })
+        .map(|entry| entry.path())
+        .collect();
+    ports.sort();
+    ports
+}
+
+/// What the wizard learned, enough for a complete config.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SetupChoices {
+    pub music_dir: PathBuf,
+    /// Serial port of a PN532 board, if that is the reader.
+    pub pn532: Option<PathBuf>,
+    /// Output device name; `None` keeps the system default.
+    pub audio_device: Option<String>,
+}
+
+/// Renders a config for `choices` with an empty `[cards]` table, ready for
+/// `musicbox tag add`.
+pub fn render_config(choices: &SetupChoices) -> String {
+    let mut doc = DocumentMut::new();
+    doc["music_dir"] = value(choices.music_dir.to_string_lossy().as_ref());
+    if let Some(device) = &choices.audio_device {
+        doc["audio"] = table();
+        doc["audio"]["device"] = value(device.as_str());
+    }
+    if let Some(port) = &choices.pn532 {
+        doc["pn532"] = table();
+        doc["pn532"]["device"] = value(port.to_string_lossy().as_ref());
+    }
+    doc["cards"] = table();
+    doc.to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::MusicBoxConfig;
+    use std::io::Cursor;
+
+    #[test]
+    fn prompts_fall_back_to_defaults_and_retry_bad_answers() {
+        let input = Cursor::new("\nmaybe\ny\n7\n2\n/srv/music\n");
+        let mut output = Vec::new();
+        let mut prompt = Prompter::new(input, &mut output);
+        let options = ["a".to_string(), "b".to_string()];
+        assert!(!prompt.confirm("Replace?", false).unwrap());
+        assert!(prompt.confirm("Replace?", false).unwrap());
+        assert_eq!(prompt.choose("Which?", &options).unwrap(), 1);
+        assert_eq!(prompt.ask("Music?", "/music").unwrap(), "/srv/music");
+        let err = prompt.ask("More?", "x").unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.contains("Please answer y or n."));
+        assert!(output.contains("  2) b"));
+        assert!(output.contains("Please enter a number from 1 to 2."));
+    }
+
+    #[test]
+    fn finds_serial_ports_by_name() {
+        let dev = tempfile::tempdir().unwrap();
+        for name in ["ttyUSB0", "serial0", "ttyS0", "ttyAMA", "null"] {
+            fs::write(dev.path().join(name), "").unwrap();
+        }
+        assert_eq!(
+            serial_ports_in(dev.path()),
+            vec![dev.path().join("serial0"), dev.path().join("ttyUSB0")]
+        );
+    }
+
+    #[test]
+    fn rendered_configs_load() {
+        let choices = SetupChoices {
+            music_dir: PathBuf::from("/home/pi/My \"Music\""),
+            pn532: Some(PathBuf::from("/dev/serial0")),
+            audio_device: Some("USB Audio".into()),
+        };
+        let config = MusicBoxConfig::from_reader(render_config(&choices).as_bytes()).unwrap();
+        assert_eq!(config.music_dir(), choices.music_dir);
+        assert_eq!(config.audio_device(), Some("USB Audio"));
+        assert_eq!(
+            config.pn532().unwrap().device,
+            PathBuf::from("/dev/serial0")
+        );
+
+        let minimal = render_config(&SetupChoices {
+            music_dir: PathBuf::from("/music"),
+            ..SetupChoices::default()
+        });
+        assert_eq!(minimal, "music_dir = \"/music\"\n\n[cards]\n");
+    }
+}
@@ -0,0 +1,197 @@
+//! Spoken announcements in the box's locale, for effects without a sound
+//! file.
+//!
+//! Musicbox does not synthesize speech itself. The `[speech]` table names a
+//! text-to-speech command, such as `espeak-ng` or `pico2wave`, that writes
+//! one phrase to a WAV file. Phrases are rendered once into a cache
+//! directory and then played like any other effect.
+
+use crate::controller::{Effects, Track};
+use crate::locale::Locale;
+use sha2::{Digest, Sha256};
+use std::path::PathBuf;
+use std::process::Command;
+
+/// Replaced by the phrase in the command's arguments.
+pub const TEXT: &str = "{text}";
+/// Replaced by the locale code, such as `de`.
+pub const LOCALE: &str = "{locale}";
+/// Replaced by the WAV file the command should write.
+pub const OUTPUT: &str = "{output}";
+
+#[derive(Debug, thiserror::Error)]
+pub enum SpeechError {
+    #[error("failed to run {program:?}: {source}")]
+    Spawn {
+        program: String,
+        #[source]
+        source: std::io::Error,
+    },
+    #[error("{program:?} failed ({status}): {stderr}")]
+    Failed {
+        program: String,
+        status: std::process::ExitStatus,
+        stderr: String,
+    },
+    #[error("{program:?} did not write {path:?}")]
+    NoOutput { program: String, path: PathBuf },
+    #[error("{path:?}: {source}")]
+    Cache {
+        path: PathBuf,
+        #[source]
+        source: std::io::Error,
+    },
+}
+
+/// The `[speech]` table.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Speech {
+    /// The program and its arguments, with [`TEXT`], [`LOCALE`], and
+    /// [`OUTPUT`] filled in for each phrase.
+    pub command: Vec<String>,
+    /// Where rendered phrases are kept between runs.
+    pub cache_dir: PathBuf,
+}
+
+impl Speech {
+    /// The WAV file saying `text` in `locale`, rendered now unless an
+    /// earlier run already did.
+    pub fn render(&self, text: &str, locale: Locale) -> Result<PathBuf, SpeechError> {
+        let digest = Sha256::digest(format!("{}\n{}\n{text}", self.command.join(" "), locale));
+        let name: String = digest[..8]
+            .iter()
+            .map(|byte| format!("{byte:02x}"))
+            .collect();
+        let path = self.cache_dir.join(format!("{locale}-{name}.wav"));
+        if path.is_file() {
+            return Ok(path);
+        }
+        std::fs::create_dir_all(&self.cache_dir).map_err(|source| SpeechError::Cache {
+            path: self.cache_dir.clone(),
+            source,
+        })?;
+        let args: Vec<String> = self
+            .command
+            .iter()
+            .map(|arg| {
+                arg.replace(TEXT, text)
+                    .replace(LOCALE, locale.code())
+                    .replace(OUTPUT, &path.to_string_lossy())
+            })
+            .collect();
+        let program = args[0].clone();
+        let output = Command::new(&program)
+            .args(&args[1..])
+            .output()
+            .map_err(|source| SpeechError::Spawn {
+                program: program.clone(),
+                source,
+            })?;
+        if !output.status.success() {
+            let _ = std::fs::remove_file(&path);
+            return Err(SpeechError::Failed {
+                program,
+                status: output.status,
+                stderr: String::from_utf8_lossy(&output.stderr).trim().to_string(),
+            });
+        }
+        if !path.is_file() {
+            return Err(SpeechError::NoOutput { program, path });
+        }
+        Ok(path)
+    }
+
+    /// `effects` with each slot that has no file filled by its announcement
+    /// in `locale`. A phrase that fails to render is logged and left out.
+    pub fn announce(&self, mut effects: Effects, locale: Locale) -> Effects {
+        let phrases = &locale.strings().announcements;
+        for (slot, text) in [
+            (&mut effects.ready, phrases.ready),
+            (&mut effects.unknown_card, phrases.unknown_card),
+            (&mut effects.shutdown, phrases.shutdown),
+            (&mut effects.sleep_warning, phrases.sleep_warning),
+        ] {
+            if slot.is_some() {
+                continue;
+            }
+            match self.render(text, locale) {
+                Ok(path) => *slot = Some(Track::new(path)),
+                Err(err) => tracing::warn!(%err, text, "could not render announcement"),
+            }
+        }
+        effects
+    }
+
+    /// The default cache directory: `musicbox-speech` in the system's
+    /// temporary directory.
+    pub fn default_cache_dir() -> PathBuf {
+        std::env::temp_dir().join("musicbox-speech")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    /// A "speech engine" that writes the phrase and locale as text.
+    fn echo(cache_dir: &Path) -> Speech {
+        Speech {
+            command: ["sh", "-c", "printf '%s:%s' \"$1\" \"$2\" > \"$3\"", "sh"]
+                .into_iter()
+                .chain([LOCALE, TEXT, OUTPUT])
+                .map(String::from)
+                .collect(),
+            cache_dir: cache_dir.to_path_buf(),
+        }
+    }
+
+    #[test]
+    fn phrases_render_once_per_locale() {
+        let dir = tempfile::tempdir().unwrap();
+        let speech = echo(&dir.path().join("cache"));
+
+        let path = speech.render("Gute Nacht!", Locale::De).unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "de:Gute Nacht!");
+        std::fs::write(&path, "cached").unwrap();
+        assert_eq!(speech.render("Gute Nacht!", Locale::De).unwrap(), path);
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "cached");
+        assert_ne!(speech.render("Gute Nacht!", Locale::Fr).unwrap(), path);
+    }
+
+    #[test]
+    fn announcements_fill_only_empty_effects() {
+        let dir = tempfile::tempdir().unwrap();
+        let speech = echo(dir.path());
+        let effects = speech.announce(
+            Effects {
+                ready: Some(Track::new(PathBuf::from("fx/ready.ogg"))),
+                ..Effects::default()
+            },
+            Locale::Fr,
+        );
+
+        assert_eq!(
+            effects.ready,
+            Some(Track::new(PathBuf::from("fx/ready.ogg")))
+        );
+        let shutdown = effects.shutdown.unwrap();
+        assert_eq!(
+            std::fs::read_to_string(shutdown.path()).unwrap(),
+            "fr:Bonne nuit !"
+        );
+
+        let broken = Speech {
+            command: vec!["false".into(), OUTPUT.into()],
+            cache_dir: dir.path().join("broken"),
+        };
+        assert!(matches!(
+            broken.render("Ready", Locale::En),
+            Err(SpeechError::Failed { .. })
+        ));
+        assert_eq!(
+            broken.announce(Effects::default(), Locale::En),
+            Effects::default()
+        );
+    }
+}
@@ -0,0 +1,304 @@
+//! Spotify playback through a local Spotify Connect daemon.
+//!
+//! Card entries such as `spotify:album:4aawyAB9vmqN3uQ7FjRGTy` are handed to
+//! a [go-librespot] daemon running next to musicbox. The daemon logs in to
+//! Spotify, keeps the credentials and audio cache, and plays through the same
+//! sound card; musicbox only drives its local HTTP API. That keeps Spotify
+//! credentials and a TLS stack out of this process.
+//!
+//! The player itself sits behind the `spotify` feature. Entry parsing and
+//! settings are always available so configs can be validated on any build.
+//!
+//! [go-librespot]: https://github.com/devgianlu/go-librespot
+
+use crate::webhook::WebhookUrl;
+
+/// Prefix of card entries played through Spotify.
+pub const SCHEME: &str = "spotify:";
+
+/// Kinds of Spotify URI a card can start.
+const KINDS: &[&str] = &["track", "album", "playlist", "artist", "episode", "show"];
+
+/// The URI of a `spotify:<kind>:<id>` entry, or `None` if `entry` is not a
+/// well-formed Spotify URI.
+pub fn spotify_uri(entry: &str) -> Option<&str> {
+    let rest = entry.strip_prefix(SCHEME)?;
+    let (kind, id) = rest.split_once(':')?;
+    let valid_id = !id.is_empty() && id.bytes().all(|byte| byte.is_ascii_alphanumeric());
+    (KINDS.contains(&kind) && valid_id).then_some(entry)
+}
+
+/// The `[spotify]` table.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SpotifySettings {
+    /// Base URL of the daemon's HTTP API.
+    pub api: WebhookUrl,
+}
+
+#[cfg(feature = "spotify")]
+pub mod connect {
+    use super::{SpotifySettings, spotify_uri};
+    use crate::controller::{AudioPlayer, PlayerCapabilities, PlayerError, Track};
+    use crate::webhook::{self, WebhookError, WebhookUrl};
+    use serde::Deserialize;
+    use std::cell::Cell;
+    use std::time::{Duration, Instant};
+
+    /// How long a status answer is reused, since the controller asks
+    /// whether the track finished on every poll.
+    const STATUS_TTL: Duration = Duration::from_secs(1);
+
+    #[derive(Debug, thiserror::Error)]
+    pub enum SpotifyError {
+        #[error("Spotify Connect daemon unreachable: {0}")]
+        Http(#[from] WebhookError),
+        #[error("Spotify Connect daemon answered {status}: {message}")]
+        Status { status: u16, message: String },
+        #[error("unexpected answer from Spotify Connect daemon: {0}")]
+        Decode(#[from] serde_json::Error),
+        #[error("{0:?} is not a Spotify URI")]
+        NotSpotify(String),
+    }
+
+    impl From<SpotifyError> for PlayerError {
+        fn from(err: SpotifyError) -> Self {
+            PlayerError::Backend {
+                message: err.to_string(),
+            }
+        }
+    }
+
+    #[derive(Debug, Clone, Copy, Default, Deserialize)]
+    struct Status {
+        #[serde(default)]
+        stopped: bool,
+        track: Option<StatusTrack>,
+    }
+
+    #[derive(Debug, Clone, Copy, Deserialize)]
+    struct StatusTrack {
+        /// Milliseconds into the track.
+        position: u64,
+    }
+
+    #[derive(Debug, Deserialize)]
+    struct VolumeRange {
+        max: u32,
+    }
+
+    /// Plays `spotify:` entries on a go-librespot daemon.
+    #[derive(Debug)]
+    pub struct SpotifyPlayer {
+        api: WebhookUrl,
+        volume: f32,
+        muted: bool,
+        status: Cell<Option<(Instant, Status)>>,
+        /// Whether the daemon reported playback since the last `play`. It
+        /// takes a moment to load a context, and until then reports stopped.
+        started: Cell<bool>,
+    }
+
+    impl SpotifyPlayer {
+        pub fn new(settings: &SpotifySettings) -> Self {
+            Self {
+                api: settings.api.clone(),
+                volume: 1.0,
+                muted: false,
+                status: Cell::new(None),
+                started: Cell::new(false),
+            }
+        }
+
+        fn post(&self, path: &str, payload: serde_json::Value) -> Result<(), SpotifyError> {
+            let response = webhook::send_json(&self.api.join(path), &payload)?;
+            if response.is_success() {
+                Ok(())
+            } else {
+                Err(SpotifyError::Status {
+                    status: response.status,
+                    message: response.body,
+                })
+            }
+        }
+
+        fn get<T: serde::de::DeserializeOwned>(&self, path: &str) -> Result<T, SpotifyError> {
+            let response = webhook::get(&self.api.join(path))?;
+            if !(200..300).contains(&response.status) {
+                return Err(SpotifyError::Status {
+                    status: response.status,
+                    message: String::from_utf8_lossy(&response.body).into_owned(),
+                });
+            }
+            Ok(serde_json::from_slice(&response.body)?)
+        }
+
+        /// The daemon's status, at most [`STATUS_TTL`] old.
+        fn status(&self) -> Result<Status, SpotifyError> {
+            if let Some((at, status)) = self.status.get()
+                && at.elapsed() < STATUS_TTL
+            {
+                return Ok(status);
+            }
+            let status: Status = self.get("/status")?;
+            self.status.set(Some((Instant::now(), status)));
+            if !status.stopped {
+                self.started.set(true);
+            }
+            Ok(status)
+        }
+
+        fn apply_volume(&self) -> Result<(), SpotifyError> {
+            let range: VolumeRange = self.get("/player/volume")?;
+            let level = if self.muted { 0.0 } else { self.volume };
+            let steps = (level * range.max as f32).round() as u32;
+            self.post("/player/volume", serde_json::json!({ "volume": steps }))
+        }
+    }
+
+    impl AudioPlayer for SpotifyPlayer {
+        fn capabilities(&self) -> PlayerCapabilities {
+            PlayerCapabilities {
+                seek: true,
+                volume: true,
+                track_end: true,
+                streams: false,
+            }
+        }
+
+        fn play(&mut self, track: &Track) -> Result<(), PlayerError> {
+            let entry = track.path().to_string_lossy();
+            let uri =
+                spotify_uri(&entry).ok_or_else(|| SpotifyError::NotSpotify(entry.to_string()))?;
+            self.post("/player/play", serde_json::json!({ "uri": uri }))?;
+            self.status.set(None);
+            self.started.set(false);
+            Ok(())
+        }
+
+        /// Spotify Connect has no stop, so this pauses.
+        fn stop(&mut self) -> Result<(), PlayerError> {
+            self.post("/player/pause", serde_json::json!({}))?;
+            self.status.set(None);
+            Ok(())
+        }
+
+        fn pause(&mut self) -> Result<(), PlayerError> {
+            Ok(self.post("/player/pause", serde_json::json!({}))?)
+        }
+
+        fn resume(&mut self) -> Result<(), PlayerError> {
+            Ok(self.post("/player/resume", serde_json::json!({}))?)
+        }
+
+        /// The daemon stops once the album or playlist runs out. A daemon
+        /// that cannot be reached is logged and treated as still playing.
+        fn is_finished(&self) -> bool {
+            match self.status() {
+                Ok(status) => self.started.get() && status.stopped,
+                Err(err) => {
+                    tracing::debug!(%err, "failed to query Spotify Connect status");
+                    false
+                }
+            }
+        }
+
+        fn set_volume(&mut self, volume: f32) -> Result<(), PlayerError> {
+            self.volume = volume.clamp(0.0, 1.0);
+            Ok(self.apply_volume()?)
+        }
+
+        fn volume(&self) -> f32 {
+            self.volume
+        }
+
+        fn set_muted(&mut self, muted: bool) -> Result<(), PlayerError> {
+            self.muted = muted;
+            Ok(self.apply_volume()?)
+        }
+
+        fn position(&self) -> Option<Duration> {
+            let track = self.status().ok()?.track?;
+            Some(Duration::from_millis(track.position))
+        }
+
+        fn seek(&mut self, offset: Duration) -> Result<(), PlayerError> {
+            let position = offset.as_millis() as u64;
+            Ok(self.post("/player/seek", serde_json::json!({ "position": position }))?)
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use crate::test_http::{TestServer, ok, unused_addr};
+        use std::path::PathBuf;
+
+        #[test]
+        fn plays_uris_and_waits_for_playback_before_reporting_the_end() {
+            let server = TestServer::bind();
+            let api = WebhookUrl::from_socket_addr(server.addr(), "");
+            let server =
+                server.respond(["", r#"{"stopped":true}"#, r#"{"max":64}"#, "", ""].map(ok));
+            let mut player = SpotifyPlayer::new(&SpotifySettings { api });
+            player
+                .play(&Track::new(PathBuf::from(
+                    "spotify:album:4aawyAB9vmqN3uQ7FjRGTy",
+                )))
+                .unwrap();
+            // Still loading: stopped, but never seen playing.
+            assert!(!player.is_finished());
+            player.set_volume(0.5).unwrap();
+            player.pause().unwrap();
+
+            let requests: Vec<String> = server
+                .join()
+                .unwrap()
+                .into_iter()
+                .map(|request| format!("{} {} {}", request.method, request.path, request.body))
+                .collect();
+            assert_eq!(
+                requests,
+                [
+                    r#"POST /player/play {"uri":"spotify:album:4aawyAB9vmqN3uQ7FjRGTy"}"#,
+                    "GET /status ",
+                    "GET /player/volume ",
+                    r#"POST /player/volume {"volume":32}"#,
+                    "POST /player/pause {}",
+                ]
+            );
+        }
+
+        #[test]
+        fn unreachable_daemons_surface_as_player_errors() {
+            let mut player = SpotifyPlayer::new(&SpotifySettings {
+                api: WebhookUrl::from_socket_addr(unused_addr(), ""),
+            });
+            let err = player
+                .play(&Track::new(PathBuf::from("spotify:track:abc")))
+                .unwrap_err();
+            assert!(
+                err.to_string()
+                    .contains("Spotify Connect daemon unreachable")
+            );
+            assert!(player.play(&Track::new(PathBuf::from("song.mp3"))).is_err());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn spotify_uris_need_a_known_kind_and_an_id() {
+        assert_eq!(
+            spotify_uri("spotify:album:4aawyAB9vmqN3uQ7FjRGTy"),
+            Some("spotify:album:4aawyAB9vmqN3uQ7FjRGTy")
+        );
+        assert!(spotify_uri("spotify:playlist:37i9dQZF1DX0").is_some());
+        assert_eq!(spotify_uri("spotify:album:"), None);
+        assert_eq!(spotify_uri("spotify:user:bob"), None);
+        assert_eq!(spotify_uri("spotify:track:../x"), None);
+        assert_eq!(spotify_uri("songs/a.mp3"), None);
+    }
+}
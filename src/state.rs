@@ -0,0 +1,217 @@
+//! Resume database: where each resumable card left off.
+//!
+//! The whole database is one small JSON object keyed by card UID. Every save
+//! writes a sibling temporary file and renames it over the old one, so a
+//! power cut mid-write leaves either the previous or the new contents, never
+//! a torn file.
+
+use crate::controller::CardUid;
+use crate::mirror;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+#[derive(Debug, thiserror::Error)]
+pub enum StateError {
+    #[error("failed to access resume state {path:?}: {source}")]
+    Io {
+        path: PathBuf,
+        #[source]
+        source: std::io::Error,
+    },
+    #[error("invalid resume state {path:?}: {source}")]
+    Decode {
+        path: PathBuf,
+        #[source]
+        source: serde_json::Error,
+    },
+    #[error("failed to encode resume state: {0}")]
+    Encode(#[from] serde_json::Error),
+}
+
+/// Where a card's playback should pick up.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ResumePoint {
+    /// Index into the card's playlist, with folders expanded.
+    pub track_index: usize,
+    /// Offset into that track.
+    pub offset: Duration,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct RawResumePoint {
+    track_index: usize,
+    offset_ms: u64,
+    /// When the point was saved, in milliseconds since the Unix epoch; 0
+    /// in files written before it was recorded.
+    #[serde(default)]
+    saved_ms: u64,
+}
+
+/// The resume database, loaded into memory and written back on every change.
+#[derive(Debug, Clone)]
+pub struct ResumeStore {
+    path: PathBuf,
+    /// Keyed by lowercase hex UID; sorted so the file diffs cleanly.
+    points: BTreeMap<String, RawResumePoint>,
+}
+
+impl ResumeStore {
+    /// Loads the database at `path`. A missing file is an empty database.
+    pub fn load(path: impl Into<PathBuf>) -> Result<Self, StateError> {
+        let path = path.into();
+        let points = match fs::read(&path) {
+            Ok(bytes) => serde_json::from_slice(&bytes).map_err(|source| StateError::Decode {
+                path: path.clone(),
+                source,
+            })?,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => BTreeMap::new(),
+            Err(source) => return Err(StateError::Io { path, source }),
+        };
+        Ok(Self { path, points })
+    }
+
+    /// An empty database that will be written to `path`, replacing whatever
+    /// is there on the first save.
+    pub fn empty(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            points: BTreeMap::new(),
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn get(&self, uid: &CardUid) -> Option<ResumePoint> {
+        self.points
+            .get(&uid.to_hex_lowercase())
+            .map(|raw| ResumePoint {
+                track_index: raw.track_index,
+                offset: Duration::from_millis(raw.offset_ms),
+            })
+    }
+
+    /// Records `point` for `uid` and saves the database.
+    pub fn set(&mut self, uid: &CardUid, point: ResumePoint) -> Result<(), StateError> {
+        let saved_ms = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |since| {
+                u64::try_from(since.as_millis()).unwrap_or(u64::MAX)
+            });
+        let raw = RawResumePoint {
+            track_index: point.track_index,
+            offset_ms: u64::try_from(point.offset.as_millis()).unwrap_or(u64::MAX),
+            saved_ms,
+        };
+        self.points.insert(uid.to_hex_lowercase(), raw);
+        self.save()
+    }
+
+    /// The card among those `wanted` whose point was saved most recently.
+    pub fn latest(&self, wanted: impl Fn(&CardUid) -> bool) -> Option<CardUid> {
+        self.points
+            .iter()
+            .filter_map(|(hex, raw)| Some((CardUid::parse(hex).ok()?, raw.saved_ms)))
+            .filter(|(uid, _)| wanted(uid))
+            .max_by_key(|(_, saved_ms)| *saved_ms)
+            .map(|(uid, _)| uid)
+    }
+
+    /// Forgets `uid` so its next tap starts from the beginning.
+    pub fn clear(&mut self, uid: &CardUid) -> Result<(), StateError> {
+        if self.points.remove(&uid.to_hex_lowercase()).is_some() {
+            self.save()?;
+        }
+        Ok(())
+    }
+
+    fn save(&self) -> Result<(), StateError> {
+        let io_err = |source| StateError::Io {
+            path: self.path.clone(),
+            source,
+        };
+        let mut bytes = serde_json::to_vec_pretty(&self.points)?;
+        bytes.push(b'\n');
+        write_atomically(&self.path, &bytes).map_err(io_err)?;
+        mirror::update(&self.path);
+        Ok(())
+    }
+}
+
+/// Writes `bytes` to a sibling temporary file and renames it over `path`.
+pub(crate) fn write_atomically(path: &Path, bytes: &[u8]) -> std::io::Result<()> {
+    let mut temporary = path.to_path_buf().into_os_string();
+    temporary.push(".tmp");
+    fs::write(&temporary, bytes)?;
+    fs::rename(&temporary, path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    fn uid(bytes: &[u8]) -> CardUid {
+        CardUid::new(bytes.to_vec())
+    }
+
+    #[test]
+    fn missing_file_loads_empty() {
+        let dir = tempdir().unwrap();
+        let store = ResumeStore::load(dir.path().join("resume.json")).unwrap();
+        assert_eq!(store.get(&uid(&[1])), None);
+    }
+
+    #[test]
+    fn points_survive_reload() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("resume.json");
+        let point = ResumePoint {
+            track_index: 3,
+            offset: Duration::from_millis(95_250),
+        };
+        let mut store = ResumeStore::load(&path).unwrap();
+        store.set(&uid(&[0xab, 0xcd]), point).unwrap();
+        store.set(&uid(&[0x01]), point).unwrap();
+        store.clear(&uid(&[0x01])).unwrap();
+
+        let reloaded = ResumeStore::load(&path).unwrap();
+        assert_eq!(reloaded.get(&uid(&[0xab, 0xcd])), Some(point));
+        assert_eq!(reloaded.get(&uid(&[0x01])), None);
+        assert!(!dir.path().join("resume.json.tmp").exists());
+    }
+
+    #[test]
+    fn latest_picks_the_most_recently_saved_wanted_card() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("resume.json");
+        fs::write(
+            &path,
+            r#"{
+                "01": { "track_index": 0, "offset_ms": 0 },
+                "02": { "track_index": 1, "offset_ms": 500, "saved_ms": 2000 },
+                "03": { "track_index": 2, "offset_ms": 900, "saved_ms": 3000 }
+            }"#,
+        )
+        .unwrap();
+        let store = ResumeStore::load(&path).unwrap();
+        assert_eq!(store.latest(|_| true), Some(uid(&[3])));
+        assert_eq!(store.latest(|card| *card != uid(&[3])), Some(uid(&[2])));
+        assert_eq!(store.latest(|_| false), None);
+    }
+
+    #[test]
+    fn corrupt_file_is_reported() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("resume.json");
+        fs::write(&path, "{ not json").unwrap();
+        assert!(matches!(
+            ResumeStore::load(&path),
+            Err(StateError::Decode { .. })
+        ));
+    }
+}
@@ -0,0 +1,316 @@
+//! Totals over the plays in the history log: which cards are played most,
+//! and how long the box is listened to each day and each week.
+//!
+//! Where the reader records tags' own read counters, the stats also set
+//! the reads each tag counted against the taps the box saw, so a reader
+//! that misses taps shows up.
+//!
+//! Days and weeks follow the local time zone, and weeks start on Monday.
+//! Both run without gaps from the first play to the last, so a chart of
+//! them shows the quiet days too.
+
+use crate::expiry;
+use crate::history::{HistoryEntry, HistoryError, HistoryEvent, HistoryLog};
+use crate::night;
+use std::collections::{BTreeMap, HashMap};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use toml::value::Date;
+
+/// How many cards `musicbox stats` and `/api/stats` rank unless asked for
+/// another number.
+pub const DEFAULT_TOP: usize = 10;
+
+/// How often one card played, and for how long in all.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CardTotal {
+    /// Lowercase hex, as the history log records it.
+    pub card: String,
+    pub plays: u32,
+    pub played: Duration,
+}
+
+/// Listening in one day or week.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PeriodTotal {
+    /// The day, or the Monday the week starts on.
+    pub start: Date,
+    pub plays: u32,
+    pub played: Duration,
+}
+
+/// A tag's own read counter against the taps the box saw it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TagTotal {
+    /// Lowercase hex, as the history log records it.
+    pub card: String,
+    /// Taps that reported the counter.
+    pub seen: u32,
+    /// Reads the tag counted from the first of those taps to the last.
+    /// Phones and other readers count too.
+    pub counted: u32,
+}
+
+impl TagTotal {
+    /// Reads the tag counted that the box never saw.
+    pub fn missed(&self) -> u32 {
+        self.counted.saturating_sub(self.seen)
+    }
+}
+
+/// Totals over every play in a stretch of the history log.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PlayStats {
+    pub plays: u32,
+    pub played: Duration,
+    /// Most played first: by plays, then by time played, then by card.
+    pub cards: Vec<CardTotal>,
+    /// Oldest first.
+    pub days: Vec<PeriodTotal>,
+    /// Oldest first.
+    pub weeks: Vec<PeriodTotal>,
+    /// Tags that reported their read counter, most missed first.
+    pub tags: Vec<TagTotal>,
+}
+
+impl PlayStats {
+    /// Totals the plays in `entries` that started at or after `since`, and
+    /// the tag counters read since then. Other events are ignored.
+    pub fn from_entries<'a>(
+        entries: impl IntoIterator<Item = &'a HistoryEntry>,
+        since: SystemTime,
+    ) -> Self {
+        let since = since
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |elapsed| elapsed.as_secs());
+        let mut stats = Self::default();
+        let mut cards: HashMap<&str, (u32, Duration)> = HashMap::new();
+        let mut days: BTreeMap<u64, (u32, Duration)> = BTreeMap::new();
+        // First count, last count, and taps seen, per tag.
+        let mut counters: HashMap<&str, (u32, u32, u32)> = HashMap::new();
+        for entry in entries {
+            let (card, started, played_ms) = match &entry.event {
+                HistoryEvent::Played {
+                    card,
+                    started,
+                    played_ms,
+                    ..
+                } if *started >= since => (card, started, played_ms),
+                HistoryEvent::TagCounter { card, count } if entry.timestamp >= since => {
+                    let counter = counters.entry(card).or_insert((*count, *count, 0));
+                    counter.1 = *count;
+                    counter.2 += 1;
+                    continue;
+                }
+                _ => continue,
+            };
+            let played = Duration::from_millis(*played_ms);
+            stats.plays += 1;
+            stats.played += played;
+            add(cards.entry(card).or_default(), played);
+            add(days.entry(local_day(*started)).or_default(), played);
+        }
+
+        stats.cards = cards
+            .into_iter()
+            .map(|(card, (plays, played))| CardTotal {
+                card: card.to_string(),
+                plays,
+                played,
+            })
+            .collect();
+        stats.cards.sort_by(|a, b| {
+            b.plays
+                .cmp(&a.plays)
+                .then(b.played.cmp(&a.played))
+                .then_with(|| a.card.cmp(&b.card))
+        });
+
+        let mut weeks: BTreeMap<u64, (u32, Duration)> = BTreeMap::new();
+        for (&day, &(plays, played)) in &days {
+            let week = weeks.entry(monday_of(day)).or_default();
+            week.0 += plays;
+            week.1 += played;
+        }
+        stats.days = without_gaps(&days, 1);
+        stats.weeks = without_gaps(&weeks, 7);
+
+        stats.tags = counters
+            .into_iter()
+            .map(|(card, (first, last, seen))| TagTotal {
+                card: card.to_string(),
+                seen,
+                // A count that went backwards belongs to a replaced tag.
+                counted: if last >= first {
+                    last - first + 1
+                } else {
+                    seen
+                },
+            })
+            .collect();
+        stats.tags.sort_by(|a, b| {
+            b.missed()
+                .cmp(&a.missed())
+                .then_with(|| a.card.cmp(&b.card))
+        });
+        stats
+    }
+
+    /// Totals the plays `log` records since `since`.
+    pub fn from_log(log: &HistoryLog, since: SystemTime) -> Result<Self, HistoryError> {
+        Ok(Self::from_entries(&log.read()?, since))
+    }
+
+    /// The `limit` most played cards.
+    pub fn most_played(&self, limit: usize) -> &[CardTotal] {
+        &self.cards[..self.cards.len().min(limit)]
+    }
+}
+
+fn add(total: &mut (u32, Duration), played: Duration) {
+    total.0 += 1;
+    total.1 += played;
+}
+
+/// Days since 1970-01-01 in local time at `started`, in seconds since the
+/// Unix epoch.
+fn local_day(started: u64) -> u64 {
+    let minutes = night::local_minutes(UNIX_EPOCH + Duration::from_secs(started));
+    minutes / u64::from(night::MINUTES_PER_DAY)
+}
+
+/// The Monday on or before `day`. 1970-01-01 was a Thursday.
+fn monday_of(day: u64) -> u64 {
+    day - (day + 3) % 7
+}
+
+/// One total per period of `step` days from the first key to the last,
+/// with zero for the periods `totals` lacks.
+fn without_gaps(totals: &BTreeMap<u64, (u32, Duration)>, step: u64) -> Vec<PeriodTotal> {
+    let (Some(&first), Some(&last)) = (totals.keys().next(), totals.keys().next_back()) else {
+        return Vec::new();
+    };
+    (first..=last)
+        .step_by(step as usize)
+        .map(|day| {
+            let (plays, played) = totals.get(&day).copied().unwrap_or_default();
+            PeriodTotal {
+                start: date_of(day),
+                plays,
+                played,
+            }
+        })
+        .collect()
+}
+
+fn date_of(day: u64) -> Date {
+    expiry::to_datetime(UNIX_EPOCH + Duration::from_secs(day * 86_400))
+        .date
+        .expect("to_datetime sets a date")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn played(card: &str, started: u64, played_ms: u64) -> HistoryEntry {
+        HistoryEntry {
+            timestamp: started,
+            event: HistoryEvent::Played {
+                card: card.to_string(),
+                track: PathBuf::from("/music/a.mp3"),
+                started,
+                played_ms,
+            },
+        }
+    }
+
+    fn counter(card: &str, timestamp: u64, count: u32) -> HistoryEntry {
+        HistoryEntry {
+            timestamp,
+            event: HistoryEvent::TagCounter {
+                card: card.to_string(),
+                count,
+            },
+        }
+    }
+
+    #[test]
+    fn totals_cards_days_weeks_and_tag_counters() {
+        // Noon UTC, so the local day is the same in any time zone within
+        // eleven hours of it. 2023-11-14 was a Tuesday.
+        let tuesday = 1_699_963_200;
+        let day = 86_400;
+        let entries = vec![
+            played("0a", tuesday - 30 * day, 60_000),
+            played("0a", tuesday, 60_000),
+            played("0b", tuesday + 10, 120_000),
+            played("0a", tuesday + 2 * day, 30_000),
+            played("0b", tuesday + 7 * day, 600_000),
+            counter("0a", tuesday - 30 * day, 3),
+            counter("0a", tuesday, 10),
+            counter("0a", tuesday + 2 * day, 14),
+            counter("0b", tuesday + 10, 7),
+            HistoryEntry {
+                timestamp: tuesday,
+                event: HistoryEvent::Panic {
+                    message: "boom".to_string(),
+                    location: None,
+                },
+            },
+        ];
+        let since = UNIX_EPOCH + Duration::from_secs(tuesday - day);
+        let stats = PlayStats::from_entries(&entries, since);
+
+        assert_eq!(stats.plays, 4);
+        assert_eq!(stats.played, Duration::from_secs(810));
+        let cards: Vec<_> = stats
+            .cards
+            .iter()
+            .map(|total| (total.card.as_str(), total.plays, total.played.as_secs()))
+            .collect();
+        assert_eq!(cards, [("0b", 2, 720), ("0a", 2, 90)]);
+        assert_eq!(stats.most_played(1).len(), 1);
+        assert_eq!(stats.most_played(5).len(), 2);
+
+        let days: Vec<_> = stats
+            .days
+            .iter()
+            .map(|total| (total.start.to_string(), total.played.as_secs()))
+            .collect();
+        assert_eq!(days.len(), 8);
+        assert_eq!(days[0], ("2023-11-14".to_string(), 180));
+        assert_eq!(days[1], ("2023-11-15".to_string(), 0));
+        assert_eq!(days[2], ("2023-11-16".to_string(), 30));
+        assert_eq!(days[7], ("2023-11-21".to_string(), 600));
+
+        let weeks: Vec<_> = stats
+            .weeks
+            .iter()
+            .map(|total| (total.start.to_string(), total.plays))
+            .collect();
+        assert_eq!(
+            weeks,
+            [("2023-11-13".to_string(), 3), ("2023-11-20".to_string(), 1)]
+        );
+
+        let tags: Vec<_> = stats
+            .tags
+            .iter()
+            .map(|total| {
+                (
+                    total.card.as_str(),
+                    total.seen,
+                    total.counted,
+                    total.missed(),
+                )
+            })
+            .collect();
+        assert_eq!(tags, [("0a", 2, 5, 3), ("0b", 1, 1, 0)]);
+
+        assert_eq!(
+            PlayStats::from_entries(&[], UNIX_EPOCH),
+            PlayStats::default()
+        );
+    }
+}
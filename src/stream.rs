@@ -0,0 +1,184 @@
+//! Tracks played straight from the network, such as a web radio station.
+//!
+//! A card entry that is an `http://` or `https://` URL is streamed instead
+//! of read from `music_dir`. A background thread keeps reading from the
+//! socket into a bounded buffer so short network hiccups do not starve the
+//! decoder, and playback only starts once [`PREBUFFER_BYTES`] have arrived.
+
+use crate::webhook::{self, WebhookError, WebhookUrl};
+use std::io::{self, Read, Seek, SeekFrom};
+use std::sync::Mutex;
+use std::sync::mpsc::{self, Receiver, SyncSender};
+use std::time::Duration;
+
+/// Bytes buffered before [`HttpStream::open`] returns.
+pub const PREBUFFER_BYTES: usize = 64 * 1024;
+
+/// Size of each read from the socket.
+const CHUNK_BYTES: usize = 16 * 1024;
+
+/// Chunks held ahead of the decoder, about 1 MiB or a minute of a 128 kbit/s
+/// stream.
+const BUFFER_CHUNKS: usize = 64;
+
+/// A stream that delivers nothing for this long is treated as dropped.
+const STALL_TIMEOUT: Duration = Duration::from_secs(15);
+
+#[derive(Debug, thiserror::Error)]
+pub enum StreamError {
+    #[error(transparent)]
+    Http(#[from] WebhookError),
+    #[error("stream answered with status {0}")]
+    Status(u16),
+    #[error("stream failed: {0}")]
+    Io(#[from] io::Error),
+}
+
+/// Whether a card entry names a network stream rather than a file.
+pub fn is_stream_url(entry: &str) -> bool {
+    entry.starts_with("http://") || entry.starts_with("https://")
+}
+
+/// An open HTTP stream, read in order. Seeking is not supported beyond
+/// asking for the current position.
+pub struct HttpStream {
+    chunks: Mutex<Receiver<io::Result<Vec<u8>>>>,
+    pending: Vec<u8>,
+    offset: usize,
+    position: u64,
+    content_type: Option<String>,
+}
+
+impl std::fmt::Debug for HttpStream {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("HttpStream")
+            .field("position", &self.position)
+            .field("content_type", &self.content_type)
+            .finish_non_exhaustive()
+    }
+}
+
+impl HttpStream {
+    /// Connects to `url`, following redirects, and waits until the buffer
+    /// holds [`PREBUFFER_BYTES`] or the body ends.
+    pub fn open(url: &str) -> Result<Self, StreamError> {
+        let response = webhook::open(&WebhookUrl::parse(url)?, STALL_TIMEOUT)?;
+        if !(200..300).contains(&response.status) {
+            return Err(StreamError::Status(response.status));
+        }
+        let (tx, rx) = mpsc::sync_channel(BUFFER_CHUNKS);
+        let body = response.body;
+        std::thread::spawn(move || fill(body, tx));
+
+        let mut pending = Vec::new();
+        while pending.len() < PREBUFFER_BYTES {
+            match rx.recv() {
+                Ok(chunk) => pending.extend_from_slice(&chunk?),
+                Err(_) => break,
+            }
+        }
+        Ok(Self {
+            chunks: Mutex::new(rx),
+            pending,
+            offset: 0,
+            position: 0,
+            content_type: response.content_type,
+        })
+    }
+
+    /// The `Content-Type` the server reported, which helps pick a decoder.
+    pub fn content_type(&self) -> Option<&str> {
+        self.content_type.as_deref()
+    }
+}
+
+/// Copies `body` into `tx` until the body ends, the socket fails, or the
+/// stream is dropped.
+fn fill(mut body: impl Read, tx: SyncSender<io::Result<Vec<u8>>>) {
+    let mut buf = vec![0u8; CHUNK_BYTES];
+    loop {
+        let chunk = match body.read(&mut buf) {
+            Ok(0) => return,
+            Ok(read) => Ok(buf[..read].to_vec()),
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+            Err(err) => Err(err),
+        };
+        let failed = chunk.is_err();
+        if tx.send(chunk).is_err() || failed {
+            return;
+        }
+    }
+}
+
+impl Read for HttpStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.offset == self.pending.len() {
+            let next = self.chunks.get_mut().expect("stream lock").recv();
+            match next {
+                Ok(chunk) => {
+                    self.pending = chunk?;
+                    self.offset = 0;
+                }
+                Err(_) => return Ok(0),
+            }
+        }
+        let available = &self.pending[self.offset..];
+        let read = available.len().min(buf.len());
+        buf[..read].copy_from_slice(&available[..read]);
+        self.offset += read;
+        self.position += read as u64;
+        Ok(read)
+    }
+}
+
+impl Seek for HttpStream {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        match pos {
+            SeekFrom::Current(0) => Ok(self.position),
+            _ => Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "network streams cannot seek",
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_http::serve_once;
+
+    fn serve(response: Vec<u8>) -> String {
+        format!("http://{}/radio", serve_once(response))
+    }
+
+    #[test]
+    fn streams_read_the_whole_body_in_order() {
+        let audio: Vec<u8> = (0..200_000u32).map(|i| i as u8).collect();
+        let mut response = b"ICY 200 OK\r\ncontent-type: audio/mpeg\r\n\r\n".to_vec();
+        response.extend_from_slice(&audio);
+        let mut stream = HttpStream::open(&serve(response)).unwrap();
+
+        assert_eq!(stream.content_type(), Some("audio/mpeg"));
+        let mut received = Vec::new();
+        stream.read_to_end(&mut received).unwrap();
+        assert_eq!(received, audio);
+        assert_eq!(stream.stream_position().unwrap(), 200_000);
+        assert!(stream.seek(SeekFrom::Start(0)).is_err());
+    }
+
+    #[test]
+    fn streams_report_failed_requests() {
+        let url = serve(b"HTTP/1.0 404 Not Found\r\n\r\n".to_vec());
+        assert!(matches!(
+            HttpStream::open(&url),
+            Err(StreamError::Status(404))
+        ));
+        assert!(matches!(
+            HttpStream::open("ftp://radio.example/live"),
+            Err(StreamError::Http(WebhookError::UnsupportedUrl(_)))
+        ));
+        assert!(is_stream_url("https://radio.example/live"));
+        assert!(!is_stream_url("songs/a.mp3"));
+    }
+}
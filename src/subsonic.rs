@@ -0,0 +1,432 @@
+//! Tracks served by a Subsonic-compatible server, such as Navidrome, or
+//! Jellyfin with its Subsonic plugin.
+//!
+//! A card entry written as `subsonic:<id>` names a song on the server.
+//! Before such a track plays, [`CachingPlayer`] downloads it through the
+//! Subsonic `stream` endpoint into a local cache and hands the cached file to
+//! the real backend, so later plays work offline. It resolves `podcast:`
+//! feeds through [`crate::podcast`] the same way, and hands `spotify:`
+//! entries to a separate player, as described in [`crate::spotify`].
+
+use crate::controller::{AudioPlayer, OutputChange, PlayerCapabilities, PlayerError, Track};
+use crate::podcast::{self, Podcasts};
+use crate::spotify;
+use crate::webhook::{self, WebhookError, WebhookUrl};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+/// Prefix of card entries that name a song on the Subsonic server.
+pub const SCHEME: &str = "subsonic:";
+
+/// Subsonic REST API version sent with each request; 1.13 is the oldest that
+/// Navidrome and Jellyfin's plugin both accept.
+const API_VERSION: &str = "1.13.0";
+const CLIENT_NAME: &str = "musicbox";
+
+#[derive(Debug, thiserror::Error)]
+pub enum SubsonicError {
+    #[error("subsonic request failed: {0}")]
+    Http(#[from] WebhookError),
+    #[error("subsonic server responded with status {0}")]
+    Status(u16),
+    #[error("subsonic server rejected the request: {0}")]
+    Rejected(String),
+    #[error("failed to write subsonic cache {path:?}: {source}")]
+    Io {
+        path: PathBuf,
+        #[source]
+        source: std::io::Error,
+    },
+}
+
+/// The song id of a `subsonic:<id>` entry, if `entry` is one. Ids are
+/// limited to characters that are safe as a cache file name.
+pub fn song_id(entry: &str) -> Option<&str> {
+    let id = entry.strip_prefix(SCHEME)?;
+    let safe = !id.is_empty()
+        && id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    safe.then_some(id)
+}
+
+/// Connection details for a Subsonic server.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SubsonicServer {
+    base: WebhookUrl,
+    user: String,
+    password: String,
+}
+
+impl SubsonicServer {
+    pub fn new(base: WebhookUrl, user: impl Into<String>, password: impl Into<String>) -> Self {
+        Self {
+            base,
+            user: user.into(),
+            password: password.into(),
+        }
+    }
+
+    /// Downloads song `id` in its original format.
+    pub fn download(&self, id: &str) -> Result<Vec<u8>, SubsonicError> {
+        let response = webhook::get(&self.endpoint("stream", &[("id", id), ("format", "raw")]))?;
+        if !(200..300).contains(&response.status) {
+            return Err(SubsonicError::Status(response.status));
+        }
+        // Errors come back as a 200 with an XML or JSON body instead of audio.
+        let head = String::from_utf8_lossy(&response.body[..response.body.len().min(512)]);
+        if head.trim_start().starts_with("<?xml") || head.contains("\"subsonic-response\"") {
+            return Err(SubsonicError::Rejected(head.trim().to_string()));
+        }
+        Ok(response.body)
+    }
+
+    /// The URL of REST `method` with authentication and `params` attached.
+    /// The password is sent hex-encoded, which every Subsonic server accepts.
+    fn endpoint(&self, method: &str, params: &[(&str, &str)]) -> WebhookUrl {
+        let password: String = self
+            .password
+            .bytes()
+            .map(|byte| format!("{byte:02x}"))
+            .collect();
+        let mut query = format!(
+            "u={}&p=enc:{password}&v={API_VERSION}&c={CLIENT_NAME}",
+            percent_encode(&self.user)
+        );
+        for (key, value) in params {
+            query.push_str(&format!("&{key}={}", percent_encode(value)));
+        }
+        self.base.join(&format!("/rest/{method}?{query}"))
+    }
+}
+
+fn percent_encode(text: &str) -> String {
+    text.bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                (byte as char).to_string()
+            }
+            _ => format!("%{byte:02X}"),
+        })
+        .collect()
+}
+
+/// Downloaded songs, one file per song id.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SubsonicCache {
+    server: SubsonicServer,
+    dir: PathBuf,
+}
+
+impl SubsonicCache {
+    pub fn new(server: SubsonicServer, dir: impl Into<PathBuf>) -> Self {
+        Self {
+            server,
+            dir: dir.into(),
+        }
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// The cached file for song `id`, downloading it first if needed.
+    pub fn fetch(&self, id: &str) -> Result<PathBuf, SubsonicError> {
+        let path = self.dir.join(id);
+        if path.is_file() {
+            return Ok(path);
+        }
+        let io_err = |path: &Path| {
+            let path = path.to_path_buf();
+            move |source| SubsonicError::Io { path, source }
+        };
+        let bytes = self.server.download(id)?;
+        fs::create_dir_all(&self.dir).map_err(io_err(&self.dir))?;
+        // Download under a temporary name so an interrupted fetch is retried.
+        let partial = self.dir.join(format!("{id}.part"));
+        fs::write(&partial, bytes).map_err(io_err(&partial))?;
+        fs::rename(&partial, &path).map_err(io_err(&path))?;
+        tracing::info!(id, path = %path.display(), "cached subsonic track");
+        Ok(path)
+    }
+}
+
+/// Wraps an [`AudioPlayer`] so `subsonic:` tracks and `podcast:` feeds play
+/// from their caches. Without a cache they are passed through and fail like
+/// a missing file. `spotify:` entries go to the Spotify player instead, which
+/// then receives the transport controls until a local track plays again.
+pub struct CachingPlayer<P> {
+    inner: P,
+    cache: Option<SubsonicCache>,
+    podcasts: Option<Podcasts>,
+    spotify: Option<Box<dyn AudioPlayer + Send>>,
+    on_spotify: bool,
+}
+
+impl<P: AudioPlayer> CachingPlayer<P> {
+    pub fn new(inner: P, cache: Option<SubsonicCache>) -> Self {
+        Self {
+            inner,
+            cache,
+            podcasts: None,
+            spotify: None,
+            on_spotify: false,
+        }
+    }
+
+    pub fn with_podcasts(mut self, podcasts: Option<Podcasts>) -> Self {
+        self.podcasts = podcasts;
+        self
+    }
+
+    pub fn with_spotify(mut self, spotify: Option<Box<dyn AudioPlayer + Send>>) -> Self {
+        self.spotify = spotify;
+        self
+    }
+
+    /// The player the current track runs on.
+    fn active(&mut self) -> &mut dyn AudioPlayer {
+        match &mut self.spotify {
+            Some(spotify) if self.on_spotify => spotify.as_mut(),
+            _ => &mut self.inner,
+        }
+    }
+
+    fn active_ref(&self) -> &dyn AudioPlayer {
+        match &self.spotify {
+            Some(spotify) if self.on_spotify => spotify.as_ref(),
+            _ => &self.inner,
+        }
+    }
+
+    fn play_spotify(&mut self, track: &Track) -> Result<(), PlayerError> {
+        let Some(spotify) = &mut self.spotify else {
+            return Err(PlayerError::Backend {
+                message: format!(
+                    "cannot play {}: Spotify needs a build with the `spotify` feature and a [spotify] table",
+                    track.path().display()
+                ),
+            });
+        };
+        if !self.on_spotify {
+            self.inner.stop()?;
+        }
+        spotify.play(track)?;
+        self.on_spotify = true;
+        Ok(())
+    }
+
+    /// Applies a volume change to both players, so switching between them
+    /// keeps the level. Only the active player's failure is returned.
+    fn on_both(
+        &mut self,
+        apply: impl Fn(&mut dyn AudioPlayer) -> Result<(), PlayerError>,
+    ) -> Result<(), PlayerError> {
+        let local = apply(&mut self.inner);
+        let Some(spotify) = &mut self.spotify else {
+            return local;
+        };
+        let remote = apply(spotify.as_mut());
+        if self.on_spotify {
+            if let Err(err) = local {
+                tracing::debug!(%err, "local player rejected volume change");
+            }
+            remote
+        } else {
+            if let Err(err) = remote {
+                tracing::debug!(%err, "Spotify player rejected volume change");
+            }
+            local
+        }
+    }
+}
+
+impl<P: AudioPlayer> AudioPlayer for CachingPlayer<P> {
+    fn capabilities(&self) -> PlayerCapabilities {
+        self.inner.capabilities()
+    }
+
+    fn play(&mut self, track: &Track) -> Result<(), PlayerError> {
+        let entry = track.path().to_str().unwrap_or_default();
+        if entry.starts_with(spotify::SCHEME) {
+            return self.play_spotify(track);
+        }
+        if self.on_spotify {
+            self.on_spotify = false;
+            if let Some(Err(err)) = self.spotify.as_mut().map(|spotify| spotify.stop()) {
+                tracing::warn!(%err, "failed to stop Spotify playback");
+            }
+        }
+        let feed = podcast::feed_url(entry);
+        if let (Some(feed), Some(podcasts)) = (feed, &self.podcasts) {
+            let path = podcasts.episode(feed).map_err(|err| PlayerError::Backend {
+                message: err.to_string(),
+            })?;
+            return self.inner.play(&Track::new(path).with_gain(track.gain()));
+        }
+        let id = song_id(entry);
+        let (Some(id), Some(cache)) = (id, &self.cache) else {
+            return self.inner.play(track);
+        };
+        let path = cache.fetch(id).map_err(|err| PlayerError::Backend {
+            message: err.to_string(),
+        })?;
+        self.inner.play(&Track::new(path).with_gain(track.gain()))
+    }
+
+    fn stop(&mut self) -> Result<(), PlayerError> {
+        self.active().stop()
+    }
+
+    fn pause(&mut self) -> Result<(), PlayerError> {
+        self.active().pause()
+    }
+
+    fn resume(&mut self) -> Result<(), PlayerError> {
+        self.active().resume()
+    }
+
+    fn wait_until_done(&mut self) -> Result<(), PlayerError> {
+        self.active().wait_until_done()
+    }
+
+    fn is_finished(&self) -> bool {
+        self.active_ref().is_finished()
+    }
+
+    fn set_volume(&mut self, volume: f32) -> Result<(), PlayerError> {
+        self.on_both(|player| player.set_volume(volume))
+    }
+
+    fn volume(&self) -> f32 {
+        self.inner.volume()
+    }
+
+    fn set_muted(&mut self, muted: bool) -> Result<(), PlayerError> {
+        self.on_both(|player| player.set_muted(muted))
+    }
+
+    fn set_night_mode(&mut self, on: bool) -> Result<(), PlayerError> {
+        self.on_both(|player| player.set_night_mode(on))
+    }
+
+    fn position(&self) -> Option<Duration> {
+        self.active_ref().position()
+    }
+
+    fn seek(&mut self, offset: Duration) -> Result<(), PlayerError> {
+        self.active().seek(offset)
+    }
+
+    fn first_sound_at(&self) -> Option<Instant> {
+        self.active_ref().first_sound_at()
+    }
+
+    fn fade_out(&mut self, over: Duration) -> Result<(), PlayerError> {
+        self.active().fade_out(over)
+    }
+
+    /// Effects are local files, so they play on the local output even while
+    /// Spotify plays.
+    fn play_effect(&mut self, effect: &Track) -> Result<(), PlayerError> {
+        self.inner.play_effect(effect)
+    }
+
+    /// Spotify plays on its own device, so only the local output is
+    /// watched.
+    fn refresh_output(&mut self) -> Option<OutputChange> {
+        self.inner.refresh_output()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_http::{TestServer, ok};
+    use tempfile::tempdir;
+
+    #[derive(Default)]
+    struct RecordingPlayer {
+        played: Vec<PathBuf>,
+    }
+
+    impl AudioPlayer for RecordingPlayer {
+        fn play(&mut self, track: &Track) -> Result<(), PlayerError> {
+            self.played.push(track.path().to_path_buf());
+            Ok(())
+        }
+
+        fn stop(&mut self) -> Result<(), PlayerError> {
+            Ok(())
+        }
+
+        fn pause(&mut self) -> Result<(), PlayerError> {
+            Ok(())
+        }
+
+        fn resume(&mut self) -> Result<(), PlayerError> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn song_ids_must_be_safe_file_names() {
+        assert_eq!(song_id("subsonic:al-42_x"), Some("al-42_x"));
+        assert_eq!(song_id("subsonic:"), None);
+        assert_eq!(song_id("subsonic:../etc/passwd"), None);
+        assert_eq!(song_id("songs/a.mp3"), None);
+    }
+
+    #[test]
+    fn endpoint_encodes_credentials_and_params() {
+        let server = SubsonicServer::new(
+            WebhookUrl::parse("http://navidrome.local:4533").unwrap(),
+            "kid box",
+            "pw",
+        );
+        let url = server.endpoint("stream", &[("id", "7")]);
+        assert_eq!(
+            url,
+            WebhookUrl::parse(
+                "http://navidrome.local:4533/rest/stream?u=kid%20box&p=enc:7077&v=1.13.0&c=musicbox&id=7"
+            )
+            .unwrap()
+        );
+    }
+
+    #[test]
+    fn caching_player_downloads_once_and_plays_the_cached_file() {
+        let server = TestServer::bind();
+        let base = WebhookUrl::parse(&server.url("")).unwrap();
+        let server = server.respond([ok("ID3!")]);
+
+        let dir = tempdir().unwrap();
+        let cache = SubsonicCache::new(SubsonicServer::new(base, "kid", "pw"), dir.path());
+        let mut player = CachingPlayer::new(RecordingPlayer::default(), Some(cache));
+        let track = Track::new(PathBuf::from("subsonic:song1"));
+
+        player.play(&track).unwrap();
+        // The listener only answers once, so a second download would fail.
+        player.play(&track).unwrap();
+        player
+            .play(&Track::new(PathBuf::from("/music/local.mp3")))
+            .unwrap();
+
+        let cached = dir.path().join("song1");
+        assert_eq!(
+            player.inner.played,
+            vec![
+                cached.clone(),
+                cached.clone(),
+                PathBuf::from("/music/local.mp3")
+            ]
+        );
+        assert_eq!(fs::read(&cached).unwrap(), b"ID3!");
+        assert!(
+            server.join().unwrap()[0]
+                .path
+                .starts_with("/rest/stream?u=kid&p=enc:7077&")
+        );
+    }
+}
@@ -0,0 +1,159 @@
+//! systemd's notify protocol, so a `Type=notify` service knows when the box
+//! is ready and restarts it when the run loop stops answering its watchdog.
+//!
+//! The protocol is a datagram of `KEY=value` lines sent to the socket named
+//! by `$NOTIFY_SOCKET`; it is small enough not to need libsystemd.
+
+use std::io;
+use std::os::unix::net::UnixDatagram;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Sends state changes and watchdog heartbeats to systemd.
+#[derive(Debug)]
+pub struct SystemdNotifier {
+    socket: UnixDatagram,
+    path: String,
+    /// How often to send `WATCHDOG=1`: half the service's `WatchdogSec`,
+    /// as systemd recommends.
+    watchdog: Option<Duration>,
+    last_heartbeat: Mutex<Option<Instant>>,
+}
+
+impl SystemdNotifier {
+    /// Connects to the socket systemd passed in the environment. `None`
+    /// when not started by systemd, or when the service is not
+    /// `Type=notify`.
+    pub fn from_env() -> Option<Self> {
+        let path = std::env::var("NOTIFY_SOCKET").ok()?;
+        let watchdog = watchdog_interval(
+            std::env::var("WATCHDOG_USEC").ok().as_deref(),
+            std::env::var("WATCHDOG_PID").ok().as_deref(),
+            std::process::id(),
+        );
+        match Self::connect(&path, watchdog) {
+            Ok(notifier) => Some(notifier),
+            Err(err) => {
+                tracing::warn!(%err, path, "failed to open the systemd notify socket");
+                None
+            }
+        }
+    }
+
+    fn connect(path: &str, watchdog: Option<Duration>) -> io::Result<Self> {
+        Ok(Self {
+            socket: UnixDatagram::unbound()?,
+            path: path.to_string(),
+            watchdog,
+            last_heartbeat: Mutex::new(None),
+        })
+    }
+
+    /// Tells systemd that startup finished.
+    pub fn ready(&self) {
+        self.send("READY=1\nSTATUS=Waiting for cards");
+    }
+
+    /// Tells systemd that shutdown has begun, so a slow goodnight cue is not
+    /// mistaken for a hang.
+    pub fn stopping(&self) {
+        self.send("STOPPING=1");
+    }
+
+    /// Sends `WATCHDOG=1` if the watchdog is on and half its timeout has
+    /// passed since the last one. Call it from every turn of the run loop.
+    pub fn heartbeat(&self, now: Instant) {
+        let Some(interval) = self.watchdog else {
+            return;
+        };
+        let Ok(mut last) = self.last_heartbeat.lock() else {
+            return;
+        };
+        if last.is_some_and(|last| now.saturating_duration_since(last) < interval) {
+            return;
+        }
+        *last = Some(now);
+        self.send("WATCHDOG=1");
+    }
+
+    fn send(&self, state: &str) {
+        if let Err(err) = self.send_to(state.as_bytes()) {
+            tracing::warn!(%err, state, "failed to notify systemd");
+        }
+    }
+
+    #[cfg(target_os = "linux")]
+    fn send_to(&self, message: &[u8]) -> io::Result<usize> {
+        use std::os::linux::net::SocketAddrExt;
+        use std::os::unix::net::SocketAddr;
+        // A leading `@` names a socket in Linux's abstract namespace.
+        if let Some(name) = self.path.strip_prefix('@') {
+            let addr = SocketAddr::from_abstract_name(name.as_bytes())?;
+            return self.socket.send_to_addr(message, &addr);
+        }
+        self.socket.send_to(message, &self.path)
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    fn send_to(&self, message: &[u8]) -> io::Result<usize> {
+        self.socket.send_to(message, &self.path)
+    }
+}
+
+/// The heartbeat interval from `$WATCHDOG_USEC`, or `None` when the
+/// watchdog is off or meant for another process.
+fn watchdog_interval(usec: Option<&str>, pid: Option<&str>, own_pid: u32) -> Option<Duration> {
+    if pid.is_some_and(|pid| pid.parse::<u32>().ok() != Some(own_pid)) {
+        return None;
+    }
+    let usec: u64 = usec?.parse().ok().filter(|usec| *usec > 0)?;
+    Some(Duration::from_micros(usec) / 2)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn watchdog_interval_is_half_the_timeout_for_this_process() {
+        assert_eq!(
+            watchdog_interval(Some("10000000"), None, 7),
+            Some(Duration::from_secs(5))
+        );
+        assert_eq!(
+            watchdog_interval(Some("10000000"), Some("7"), 7),
+            Some(Duration::from_secs(5))
+        );
+        assert_eq!(watchdog_interval(Some("10000000"), Some("8"), 7), None);
+        assert_eq!(watchdog_interval(Some("0"), None, 7), None);
+        assert_eq!(watchdog_interval(None, None, 7), None);
+    }
+
+    #[test]
+    fn sends_states_and_spaced_out_heartbeats() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("notify.sock");
+        let systemd = UnixDatagram::bind(&path).unwrap();
+        systemd
+            .set_read_timeout(Some(Duration::from_secs(1)))
+            .unwrap();
+        let notifier =
+            SystemdNotifier::connect(path.to_str().unwrap(), Some(Duration::from_secs(5))).unwrap();
+        let receive = || {
+            let mut buffer = [0u8; 256];
+            let len = systemd.recv(&mut buffer).unwrap();
+            String::from_utf8(buffer[..len].to_vec()).unwrap()
+        };
+
+        notifier.ready();
+        assert!(receive().starts_with("READY=1\n"));
+        let start = Instant::now();
+        notifier.heartbeat(start);
+        notifier.heartbeat(start + Duration::from_secs(1));
+        notifier.heartbeat(start + Duration::from_secs(5));
+        notifier.stopping();
+        assert_eq!(receive(), "WATCHDOG=1");
+        assert_eq!(receive(), "WATCHDOG=1");
+        assert_eq!(receive(), "STOPPING=1");
+    }
+}
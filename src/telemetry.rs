@@ -1,56 +1,365 @@
 //! Lightweight in-memory metrics used by the CLI and debug HTTP server.
 //!
-//! We intentionally keep this module dumb and lock-based instead of
-//! introducing an async runtime or channel fan-out. The controller loop is
-//! synchronous and invoked from a single thread, so a small `RwLock` keeps the
-//! implementation easy to reason about while still allowing the debug server
-//! to read a snapshot without blocking the event loop for long.
+//! We intentionally keep this module dumb instead of introducing an async
+//! runtime or channel fan-out. Counters bumped on every poll (idle events,
+//! reader errors, the last-update timestamp) are atomics so the idle loop never
+//! takes a lock; only the action and reader health fields, which change on
+//! card taps and reader trouble, sit behind a small `RwLock`.
 
-use crate::controller::ControllerAction;
+use crate::controller::{ControllerAction, LibraryStats};
+use crate::events::EngineEvent;
+use crate::logfile::RotatingFile;
+use crate::reader::ReaderCapabilities;
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::{Arc, RwLock};
-use std::time::SystemTime;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 #[derive(Debug, Clone, Default)]
 pub struct StatusSnapshot {
     pub last_action: Option<ControllerAction>,
     pub last_update: Option<SystemTime>,
     pub idle_events: u64,
+    pub reader_errors: u64,
+    pub reader_health: ReaderHealth,
+    pub reader_capabilities: ReaderCapabilities,
+    pub display_health: DisplayHealth,
+    pub tap_latency: LatencySummary,
+    /// The cards loaded, once the player has reported them.
+    pub library: Option<LibraryStats>,
+}
+
+/// How many recent taps the latency percentiles cover.
+const LATENCY_SAMPLES: usize = 200;
+
+/// How long one tap took to act on, measured from when the reader reported
+/// the card.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TapLatency {
+    /// Until the controller had decided what to do and told the player.
+    pub decision: Duration,
+    /// Until the player handed the first audio to the output; `None` when
+    /// the tap started nothing or the backend cannot tell.
+    pub sound: Option<Duration>,
+}
+
+/// Percentiles over the most recent taps.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct LatencySummary {
+    pub samples: usize,
+    pub decision_p50: Option<Duration>,
+    pub decision_p95: Option<Duration>,
+    pub sound_p50: Option<Duration>,
+    pub sound_p95: Option<Duration>,
+}
+
+impl LatencySummary {
+    fn from_samples<'a>(samples: impl Iterator<Item = &'a TapLatency> + Clone) -> Self {
+        let decisions: Vec<Duration> = samples.clone().map(|tap| tap.decision).collect();
+        let sounds: Vec<Duration> = samples.filter_map(|tap| tap.sound).collect();
+        Self {
+            samples: decisions.len(),
+            decision_p50: percentile(decisions.clone(), 50),
+            decision_p95: percentile(decisions, 95),
+            sound_p50: percentile(sounds.clone(), 50),
+            sound_p95: percentile(sounds, 95),
+        }
+    }
+}
+
+/// The nearest-rank percentile of `values`.
+fn percentile(mut values: Vec<Duration>, percent: usize) -> Option<Duration> {
+    if values.is_empty() {
+        return None;
+    }
+    values.sort_unstable();
+    let rank = (values.len() * percent).div_ceil(100).max(1);
+    Some(values[rank - 1])
+}
+
+/// How the reader is coping, as decided by the app's reader error policy.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum ReaderHealth {
+    #[default]
+    Healthy,
+    /// Errors are being retried; the reader may still recover.
+    Retrying {
+        consecutive_errors: u32,
+        last_error: String,
+    },
+    /// The reader was abandoned and the loop now idles like the noop reader.
+    FellBack { last_error: String },
+    /// The policy gave up and the run loop is about to exit.
+    Failed { last_error: String },
+}
+
+impl ReaderHealth {
+    pub fn label(&self) -> &'static str {
+        match self {
+            ReaderHealth::Healthy => "healthy",
+            ReaderHealth::Retrying { .. } => "retrying",
+            ReaderHealth::FellBack { .. } => "fallback",
+            ReaderHealth::Failed { .. } => "failed",
+        }
+    }
+
+    pub fn last_error(&self) -> Option<&str> {
+        match self {
+            ReaderHealth::Healthy => None,
+            ReaderHealth::Retrying { last_error, .. }
+            | ReaderHealth::FellBack { last_error }
+            | ReaderHealth::Failed { last_error } => Some(last_error),
+        }
+    }
+}
+
+/// Whether the status display is rendering, as reported by the display guard.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum DisplayHealth {
+    /// No display backend is configured.
+    #[default]
+    Absent,
+    Active,
+    /// Rendering failed or panicked; the guard retries initialization later.
+    Disabled {
+        reason: String,
+    },
+}
+
+impl DisplayHealth {
+    pub fn label(&self) -> &'static str {
+        match self {
+            DisplayHealth::Absent => "absent",
+            DisplayHealth::Active => "active",
+            DisplayHealth::Disabled { .. } => "disabled",
+        }
+    }
+
+    pub fn reason(&self) -> Option<&str> {
+        match self {
+            DisplayHealth::Disabled { reason } => Some(reason),
+            _ => None,
+        }
+    }
 }
 
 #[derive(Clone, Default)]
 pub struct SharedStatus {
-    inner: Arc<RwLock<StatusSnapshot>>,
+    inner: Arc<StatusCells>,
+}
+
+#[derive(Default)]
+struct StatusCells {
+    idle_events: AtomicU64,
+    reader_errors: AtomicU64,
+    /// Nanoseconds since the Unix epoch; zero means "never updated".
+    last_update_nanos: AtomicU64,
+    details: RwLock<StatusDetails>,
+}
+
+/// The fields that are too large for an atomic and change rarely.
+#[derive(Default)]
+struct StatusDetails {
+    last_action: Option<ControllerAction>,
+    reader_health: ReaderHealth,
+    reader_capabilities: ReaderCapabilities,
+    display_health: DisplayHealth,
+    latencies: VecDeque<TapLatency>,
+    library: Option<LibraryStats>,
 }
 
 impl SharedStatus {
     /// Record the latest controller action so UIs can see what triggered
     /// playback changes. We store the clone rather than the original reference
     /// because Axum serves JSON snapshots without holding the controller lock.
+    /// [`ControllerAction::Unchanged`], the mute and night mode toggles,
+    /// volume cards, config reloads, unlocks, unknown cards, and output
+    /// device changes leave playback alone, so they only refresh the
+    /// timestamp and the previous action stays visible.
     pub fn record_action(&self, action: ControllerAction) {
-        let mut guard = self.inner.write().expect("status write lock");
-        guard.last_update = Some(SystemTime::now());
-        guard.last_action = Some(action);
+        if !matches!(
+            action,
+            ControllerAction::Unchanged { .. }
+                | ControllerAction::MuteToggled { .. }
+                | ControllerAction::NightModeToggled { .. }
+                | ControllerAction::VolumeChanged { .. }
+                | ControllerAction::ReloadRequested { .. }
+                | ControllerAction::Unlocked { .. }
+                | ControllerAction::UnknownCard { .. }
+                | ControllerAction::OutputChanged { .. }
+        ) {
+            self.inner
+                .details
+                .write()
+                .expect("status write lock")
+                .last_action = Some(action);
+        }
+        self.touch();
+    }
+
+    /// Records what a published event says about the box: actions, idle
+    /// polls, and tap latencies.
+    pub fn record_event(&self, event: &EngineEvent) {
+        match event {
+            EngineEvent::Action(action) => self.record_action(action.clone()),
+            EngineEvent::Idle => {
+                self.record_idle();
+            }
+            EngineEvent::Latency(latency) => self.record_tap_latency(*latency),
+            EngineEvent::Rejected { .. }
+            | EngineEvent::TagCounter { .. }
+            | EngineEvent::Stopped => {}
+        }
     }
 
     /// Count idle polls so we can detect when the reader is connected but no
     /// cards are present. This is helpful for debugging hardware issues.
-    pub fn record_idle(&self) {
-        let mut guard = self.inner.write().expect("status write lock");
-        guard.last_update = Some(SystemTime::now());
-        guard.idle_events += 1;
+    /// Returns the updated count so callers can throttle work without taking
+    /// a full snapshot.
+    pub fn record_idle(&self) -> u64 {
+        let count = self.inner.idle_events.fetch_add(1, Ordering::Relaxed) + 1;
+        self.touch();
+        count
+    }
+
+    /// Track reader health transitions reported by the reader error policy.
+    /// Every non-healthy report corresponds to one reader error, so the error
+    /// counter is bumped alongside the state change.
+    pub fn record_reader_health(&self, health: ReaderHealth) {
+        if health != ReaderHealth::Healthy {
+            self.inner.reader_errors.fetch_add(1, Ordering::Relaxed);
+        }
+        self.inner
+            .details
+            .write()
+            .expect("status write lock")
+            .reader_health = health;
+        self.touch();
+    }
+
+    /// Remember what the reader backend supports so UIs can hide the rest.
+    pub fn record_reader_capabilities(&self, capabilities: ReaderCapabilities) {
+        self.inner
+            .details
+            .write()
+            .expect("status write lock")
+            .reader_capabilities = capabilities;
+    }
+
+    /// Track whether the status display is rendering or has been disabled.
+    pub fn record_display_health(&self, health: DisplayHealth) {
+        self.inner
+            .details
+            .write()
+            .expect("status write lock")
+            .display_health = health;
+    }
+
+    /// Remember the counts for the cards loaded last. Cheap to call with
+    /// the same stats again, as it only writes when they changed.
+    pub fn record_library(&self, stats: LibraryStats) {
+        if self.inner.details.read().expect("status read lock").library == Some(stats) {
+            return;
+        }
+        self.inner
+            .details
+            .write()
+            .expect("status write lock")
+            .library = Some(stats);
+    }
+
+    /// Keep a tap's latency for the percentiles, dropping the oldest once
+    /// `LATENCY_SAMPLES` are held.
+    pub fn record_tap_latency(&self, latency: TapLatency) {
+        let mut details = self.inner.details.write().expect("status write lock");
+        if details.latencies.len() == LATENCY_SAMPLES {
+            details.latencies.pop_front();
+        }
+        details.latencies.push_back(latency);
+    }
+
+    pub fn idle_events(&self) -> u64 {
+        self.inner.idle_events.load(Ordering::Relaxed)
     }
 
     pub fn snapshot(&self) -> StatusSnapshot {
-        self.inner.read().expect("status read lock").clone()
+        let details = self.inner.details.read().expect("status read lock");
+        StatusSnapshot {
+            last_action: details.last_action.clone(),
+            last_update: self.last_update(),
+            idle_events: self.inner.idle_events.load(Ordering::Relaxed),
+            reader_errors: self.inner.reader_errors.load(Ordering::Relaxed),
+            reader_health: details.reader_health.clone(),
+            reader_capabilities: details.reader_capabilities,
+            display_health: details.display_health.clone(),
+            tap_latency: LatencySummary::from_samples(details.latencies.iter()),
+            library: details.library,
+        }
+    }
+
+    fn touch(&self) {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|elapsed| elapsed.as_nanos() as u64)
+            .unwrap_or(0)
+            .max(1);
+        self.inner.last_update_nanos.store(nanos, Ordering::Relaxed);
+    }
+
+    fn last_update(&self) -> Option<SystemTime> {
+        match self.inner.last_update_nanos.load(Ordering::Relaxed) {
+            0 => None,
+            nanos => Some(UNIX_EPOCH + Duration::from_nanos(nanos)),
+        }
     }
 }
 
 pub fn init_logging() {
-    use tracing_subscriber::{EnvFilter, fmt};
+    init_logging_to(std::io::stdout);
+}
 
-    let env_filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info"));
+/// Like [`init_logging`], but writes to `writer`, such as stderr when
+/// stdout carries machine-readable output.
+pub fn init_logging_to<W>(writer: W)
+where
+    W: for<'a> tracing_subscriber::fmt::MakeWriter<'a> + Send + Sync + 'static,
+{
+    init_logging_with(writer, LogFormat::Text, None);
+}
 
-    let _ = fmt().with_env_filter(env_filter).try_init();
+/// How log lines are written.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum LogFormat {
+    /// Readable lines, colored on a terminal.
+    #[default]
+    Text,
+    /// One JSON object per line, for Loki, Elasticsearch, and the like.
+    Json,
+}
+
+/// Like [`init_logging_to`], with lines in `format`, and copies them to
+/// `file` too when one is given.
+pub fn init_logging_with<W>(writer: W, format: LogFormat, file: Option<RotatingFile>)
+where
+    W: for<'a> tracing_subscriber::fmt::MakeWriter<'a> + Send + Sync + 'static,
+{
+    use tracing_subscriber::{EnvFilter, Layer, Registry, fmt, prelude::*};
+
+    let env_filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info"));
+    let mut layers: Vec<Box<dyn Layer<Registry> + Send + Sync>> = vec![match format {
+        LogFormat::Text => fmt::layer().with_writer(writer).boxed(),
+        LogFormat::Json => fmt::layer().json().with_writer(writer).boxed(),
+    }];
+    if let Some(file) = file {
+        layers.push(match format {
+            LogFormat::Text => fmt::layer().with_ansi(false).with_writer(file).boxed(),
+            LogFormat::Json => fmt::layer().json().with_writer(file).boxed(),
+        });
+    }
+    let _ = tracing_subscriber::registry()
+        .with(layers)
+        .with(env_filter)
+        .try_init();
 }
 
 #[cfg(test)]
@@ -78,4 +387,77 @@ mod tests {
         assert_eq!(snapshot.last_action, Some(action));
         assert!(snapshot.last_update.is_some());
     }
+
+    #[test]
+    fn records_reader_health_transitions() {
+        let status = SharedStatus::default();
+        status.record_reader_health(ReaderHealth::Retrying {
+            consecutive_errors: 1,
+            last_error: "boom".into(),
+        });
+        status.record_reader_health(ReaderHealth::FellBack {
+            last_error: "boom".into(),
+        });
+
+        let snapshot = status.snapshot();
+        assert_eq!(snapshot.reader_errors, 2);
+        assert_eq!(snapshot.reader_health.label(), "fallback");
+        assert_eq!(snapshot.reader_health.last_error(), Some("boom"));
+
+        status.record_reader_health(ReaderHealth::Healthy);
+        let snapshot = status.snapshot();
+        assert_eq!(snapshot.reader_errors, 2);
+        assert_eq!(snapshot.reader_health, ReaderHealth::Healthy);
+    }
+
+    #[test]
+    fn tap_latency_percentiles_cover_recent_taps() {
+        let status = SharedStatus::default();
+        assert_eq!(status.snapshot().tap_latency, LatencySummary::default());
+
+        for ms in 1..=20 {
+            status.record_tap_latency(TapLatency {
+                decision: Duration::from_millis(ms),
+                sound: (ms % 2 == 0).then(|| Duration::from_millis(ms * 10)),
+            });
+        }
+        let summary = status.snapshot().tap_latency;
+        assert_eq!(summary.samples, 20);
+        assert_eq!(summary.decision_p50, Some(Duration::from_millis(10)));
+        assert_eq!(summary.decision_p95, Some(Duration::from_millis(19)));
+        assert_eq!(summary.sound_p50, Some(Duration::from_millis(100)));
+        assert_eq!(summary.sound_p95, Some(Duration::from_millis(200)));
+
+        for _ in 0..LATENCY_SAMPLES {
+            status.record_tap_latency(TapLatency {
+                decision: Duration::from_millis(1),
+                sound: None,
+            });
+        }
+        let summary = status.snapshot().tap_latency;
+        assert_eq!(summary.samples, LATENCY_SAMPLES);
+        assert_eq!(summary.decision_p95, Some(Duration::from_millis(1)));
+        assert_eq!(summary.sound_p50, None);
+    }
+
+    #[test]
+    fn idle_counts_are_exact_across_threads() {
+        let status = SharedStatus::default();
+        let workers: Vec<_> = (0..4)
+            .map(|_| {
+                let status = status.clone();
+                std::thread::spawn(move || {
+                    for _ in 0..1_000 {
+                        status.record_idle();
+                    }
+                })
+            })
+            .collect();
+        for worker in workers {
+            worker.join().unwrap();
+        }
+
+        assert_eq!(status.idle_events(), 4_000);
+        assert_eq!(status.snapshot().idle_events, 4_000);
+    }
 }
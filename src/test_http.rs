@@ -0,0 +1,103 @@
+//! A canned HTTP server for tests of the modules that fetch over HTTP.
+
+use std::io::{BufRead, BufReader, Write};
+use std::net::{SocketAddr, TcpListener};
+use std::thread::JoinHandle;
+
+/// A request the server answered.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Request {
+    pub method: String,
+    /// The request target, query included.
+    pub path: String,
+    pub body: String,
+}
+
+/// A listener on a free local port, bound before it answers so a test can
+/// build responses that point back at it.
+pub struct TestServer {
+    listener: TcpListener,
+}
+
+impl TestServer {
+    pub fn bind() -> Self {
+        Self {
+            listener: TcpListener::bind("127.0.0.1:0").unwrap(),
+        }
+    }
+
+    pub fn addr(&self) -> SocketAddr {
+        self.listener.local_addr().unwrap()
+    }
+
+    /// `path` on this server, as an `http://` URL.
+    pub fn url(&self, path: &str) -> String {
+        format!("http://{}{path}", self.addr())
+    }
+
+    /// Answers one connection per response, in order, on a background
+    /// thread, and hands back the requests once every response is sent.
+    pub fn respond<R: Into<Vec<u8>>>(
+        self,
+        responses: impl IntoIterator<Item = R>,
+    ) -> JoinHandle<Vec<Request>> {
+        let responses: Vec<Vec<u8>> = responses.into_iter().map(Into::into).collect();
+        std::thread::spawn(move || {
+            responses
+                .into_iter()
+                .map(|response| {
+                    let (mut socket, _) = self.listener.accept().unwrap();
+                    let request = read_request(&mut BufReader::new(&socket));
+                    socket.write_all(&response).unwrap();
+                    request
+                })
+                .collect()
+        })
+    }
+}
+
+/// Answers a single request with `response`.
+pub fn serve_once(response: impl Into<Vec<u8>>) -> SocketAddr {
+    let server = TestServer::bind();
+    let addr = server.addr();
+    server.respond([response]);
+    addr
+}
+
+/// A `200 OK` response carrying `body`.
+pub fn ok(body: impl AsRef<[u8]>) -> Vec<u8> {
+    let body = body.as_ref();
+    let mut response =
+        format!("HTTP/1.1 200 OK\r\nContent-Length: {}\r\n\r\n", body.len()).into_bytes();
+    response.extend_from_slice(body);
+    response
+}
+
+/// An address nothing listens on.
+pub fn unused_addr() -> SocketAddr {
+    TestServer::bind().addr()
+}
+
+fn read_request(reader: &mut impl BufRead) -> Request {
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line).unwrap();
+    let mut length = 0;
+    loop {
+        let mut line = String::new();
+        reader.read_line(&mut line).unwrap();
+        if line.trim().is_empty() {
+            break;
+        }
+        if let Some(value) = line.to_ascii_lowercase().strip_prefix("content-length:") {
+            length = value.trim().parse().unwrap();
+        }
+    }
+    let mut body = vec![0; length];
+    reader.read_exact(&mut body).unwrap();
+    let mut parts = request_line.split_whitespace();
+    Request {
+        method: parts.next().unwrap_or_default().to_string(),
+        path: parts.next().unwrap_or_default().to_string(),
+        body: String::from_utf8_lossy(&body).into_owned(),
+    }
+}
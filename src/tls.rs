@@ -0,0 +1,18 @@
+//! Certificates for TLS connections.
+
+use rustls::RootCertStore;
+
+/// The certificate authorities the system trusts. Certificates in the
+/// platform store that rustls cannot parse are skipped, and a store that
+/// cannot be read leaves the set empty so connections fail to verify
+/// rather than the box failing to start.
+pub fn native_roots() -> RootCertStore {
+    let mut roots = RootCertStore::empty();
+    match rustls_native_certs::load_native_certs() {
+        Ok(certs) => {
+            roots.add_parsable_certificates(certs);
+        }
+        Err(err) => tracing::warn!(%err, "failed to load the system's root certificates"),
+    }
+    roots
+}
@@ -0,0 +1,307 @@
+//! Checking GitHub for newer releases and installing their prebuilt binary,
+//! since building on a Pi Zero takes the better part of an hour.
+//!
+//! GitHub only serves HTTPS and [`crate::webhook`] only speaks it in builds
+//! with the `https` feature, so requests go through the system `curl`
+//! rather than depending on how the binary was built.
+
+use crate::checksum::sha256_file;
+use serde::Deserialize;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Where releases are published, as `owner/name` on GitHub.
+pub const DEFAULT_REPOSITORY: &str = "sholiday/musicbox";
+
+/// The version of this binary.
+pub const CURRENT_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+#[derive(Debug, thiserror::Error)]
+pub enum UpdateError {
+    #[error("failed to run curl: {0}; install curl to check for updates")]
+    Curl(#[source] io::Error),
+    #[error("fetching {url} failed: {message}")]
+    Fetch { url: String, message: String },
+    #[error("unexpected release data: {0}")]
+    Release(#[from] serde_json::Error),
+    #[error("release tag {0:?} is not a version like v1.2.3")]
+    Version(String),
+    #[error("release {tag} has no prebuilt binary named {asset}")]
+    NoAsset { tag: String, asset: String },
+    #[error("prebuilt binaries are only published for Linux on ARM and x86_64")]
+    UnsupportedPlatform,
+    #[error("release {tag} has no {asset} to check its binary against")]
+    NoChecksum { tag: String, asset: String },
+    #[error("downloaded binary does not match its checksum (expected {expected}, got {actual})")]
+    Checksum { expected: String, actual: String },
+    #[error("failed to install to {path:?}: {source}")]
+    Install {
+        path: PathBuf,
+        #[source]
+        source: io::Error,
+    },
+}
+
+/// A `major.minor.patch` version. Tags may carry a leading `v`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Version {
+    pub major: u64,
+    pub minor: u64,
+    pub patch: u64,
+}
+
+impl Version {
+    pub fn parse(text: &str) -> Result<Self, UpdateError> {
+        let invalid = || UpdateError::Version(text.to_string());
+        let mut parts = text.trim().trim_start_matches('v').split('.');
+        let mut next = || -> Result<u64, UpdateError> {
+            parts
+                .next()
+                .and_then(|part| part.parse().ok())
+                .ok_or_else(invalid)
+        };
+        let version = Self {
+            major: next()?,
+            minor: next()?,
+            patch: next()?,
+        };
+        if parts.next().is_some() {
+            return Err(invalid());
+        }
+        Ok(version)
+    }
+}
+
+impl fmt::Display for Version {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)
+    }
+}
+
+/// The parts of a GitHub release the check needs.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Release {
+    pub tag_name: String,
+    #[serde(default)]
+    pub assets: Vec<Asset>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Asset {
+    pub name: String,
+    pub browser_download_url: String,
+}
+
+impl Release {
+    pub fn version(&self) -> Result<Version, UpdateError> {
+        Version::parse(&self.tag_name)
+    }
+
+    fn asset(&self, name: &str) -> Option<&Asset> {
+        self.assets.iter().find(|asset| asset.name == name)
+    }
+}
+
+/// The name of the prebuilt binary for the platform this build runs on,
+/// such as `musicbox-arm-unknown-linux-gnueabihf` for a Pi Zero.
+pub fn asset_name() -> Result<String, UpdateError> {
+    let target = if cfg!(not(target_os = "linux")) {
+        return Err(UpdateError::UnsupportedPlatform);
+    } else if cfg!(target_arch = "aarch64") {
+        "aarch64-unknown-linux-gnu"
+    } else if cfg!(all(target_arch = "arm", target_feature = "v7")) {
+        "armv7-unknown-linux-gnueabihf"
+    } else if cfg!(target_arch = "arm") {
+        "arm-unknown-linux-gnueabihf"
+    } else if cfg!(target_arch = "x86_64") {
+        "x86_64-unknown-linux-gnu"
+    } else {
+        return Err(UpdateError::UnsupportedPlatform);
+    };
+    Ok(format!("musicbox-{target}"))
+}
+
+/// Fetches the latest release of `repository` (`owner/name`).
+pub fn latest_release(repository: &str) -> Result<Release, UpdateError> {
+    let url = format!("https://api.github.com/repos/{repository}/releases/latest");
+    let body = fetch(&url, None)?;
+    Ok(serde_json::from_slice(&body)?)
+}
+
+/// Downloads the platform's binary from `release` and swaps it in for
+/// `target` once it matches the release's `<asset>.sha256` file. A release
+/// without one is refused. The old binary keeps running until the process
+/// restarts.
+pub fn install(release: &Release, target: &Path) -> Result<(), UpdateError> {
+    install_asset(release, &asset_name()?, target)
+}
+
+fn install_asset(release: &Release, name: &str, target: &Path) -> Result<(), UpdateError> {
+    let asset = release.asset(name).ok_or_else(|| UpdateError::NoAsset {
+        tag: release.tag_name.clone(),
+        asset: name.to_string(),
+    })?;
+    let sums_name = format!("{name}.sha256");
+    let sums = release
+        .asset(&sums_name)
+        .ok_or_else(|| UpdateError::NoChecksum {
+            tag: release.tag_name.clone(),
+            asset: sums_name,
+        })?;
+    // Download next to the target so the final rename stays on one
+    // filesystem and cannot leave a half-written binary behind.
+    let staged = target.with_file_name(format!(".{name}.download"));
+    let install_error = |source| UpdateError::Install {
+        path: target.to_path_buf(),
+        source,
+    };
+    let result =
+        (|| {
+            fetch(&asset.browser_download_url, Some(&staged))?;
+            let expected = expected_checksum(&fetch(&sums.browser_download_url, None)?)
+                .ok_or_else(|| UpdateError::Fetch {
+                    url: sums.browser_download_url.clone(),
+                    message: "no checksum in file".into(),
+                })?;
+            let actual = sha256_file(&staged).map_err(install_error)?;
+            if actual != expected {
+                return Err(UpdateError::Checksum { expected, actual });
+            }
+            make_executable(&staged).map_err(install_error)?;
+            fs::rename(&staged, target).map_err(install_error)
+        })();
+    if result.is_err() {
+        // curl can leave a partial download behind when it fails.
+        let _ = fs::remove_file(&staged);
+    }
+    result
+}
+
+/// The hex digest at the start of a `sha256sum`-style checksum file.
+fn expected_checksum(contents: &[u8]) -> Option<String> {
+    let text = String::from_utf8_lossy(contents);
+    let digest = text.split_whitespace().next()?.to_ascii_lowercase();
+    (digest.len() == 64 && digest.chars().all(|c| c.is_ascii_hexdigit())).then_some(digest)
+}
+
+#[cfg(unix)]
+fn make_executable(path: &Path) -> io::Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+    fs::set_permissions(path, fs::Permissions::from_mode(0o755))
+}
+
+#[cfg(not(unix))]
+fn make_executable(_path: &Path) -> io::Result<()> {
+    Ok(())
+}
+
+/// Fetches `url` with curl, into `output` if given and otherwise returning
+/// the body.
+fn fetch(url: &str, output: Option<&Path>) -> Result<Vec<u8>, UpdateError> {
+    let mut command = Command::new("curl");
+    command
+        .args(["--fail", "--silent", "--show-error", "--location"])
+        .args(["--max-time", "300"])
+        .args([
+            "--user-agent",
+            concat!("musicbox/", env!("CARGO_PKG_VERSION")),
+        ]);
+    if url.starts_with("https://api.github.com/") {
+        command.args(["--header", "Accept: application/vnd.github+json"]);
+    }
+    if let Some(output) = output {
+        command.arg("--output").arg(output);
+    }
+    let result = command.arg(url).output().map_err(UpdateError::Curl)?;
+    if !result.status.success() {
+        return Err(UpdateError::Fetch {
+            url: url.to_string(),
+            message: String::from_utf8_lossy(&result.stderr).trim().to_string(),
+        });
+    }
+    Ok(result.stdout)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn versions_compare_numerically_and_reject_other_tags() {
+        let parse = |text| Version::parse(text).unwrap();
+        assert_eq!(
+            parse("v1.2.3"),
+            Version {
+                major: 1,
+                minor: 2,
+                patch: 3
+            }
+        );
+        assert!(parse("0.10.0") > parse("v0.9.9"));
+        assert_eq!(parse(CURRENT_VERSION).to_string(), CURRENT_VERSION);
+        for tag in ["nightly", "v1.2", "1.2.3.4", "v1.2.3-rc1"] {
+            assert!(Version::parse(tag).is_err(), "{tag}");
+        }
+    }
+
+    #[test]
+    fn releases_parse_from_the_github_api_and_checksums_from_sha256sum() {
+        let release: Release = serde_json::from_str(
+            r#"{
+                "tag_name": "v0.2.0",
+                "name": "0.2.0",
+                "assets": [{
+                    "name": "musicbox-arm-unknown-linux-gnueabihf",
+                    "size": 1234,
+                    "browser_download_url": "https://example.com/musicbox-arm"
+                }]
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(release.version().unwrap().to_string(), "0.2.0");
+        assert!(
+            release
+                .asset("musicbox-arm-unknown-linux-gnueabihf")
+                .is_some()
+        );
+        assert!(release.asset("musicbox-x86_64-unknown-linux-gnu").is_none());
+
+        let digest = "ab".repeat(32);
+        assert_eq!(
+            expected_checksum(format!("{}  musicbox-arm\n", digest.to_uppercase()).as_bytes()),
+            Some(digest)
+        );
+        assert_eq!(expected_checksum(b"not a checksum"), None);
+    }
+
+    #[test]
+    fn installs_need_a_checksum_and_leave_nothing_behind_when_they_fail() {
+        let dir = tempfile::tempdir().unwrap();
+        let target = dir.path().join("musicbox");
+        fs::write(&target, "old").unwrap();
+        let asset = |name: &str, url: &str| Asset {
+            name: name.to_string(),
+            browser_download_url: url.to_string(),
+        };
+        let missing = format!("file://{}", dir.path().join("missing").display());
+        let mut release = Release {
+            tag_name: "v0.2.0".to_string(),
+            assets: vec![asset("musicbox-test", &missing)],
+        };
+        assert!(matches!(
+            install_asset(&release, "musicbox-test", &target),
+            Err(UpdateError::NoChecksum { asset, .. }) if asset == "musicbox-test.sha256"
+        ));
+
+        // A download that fails takes its partial file with it.
+        release.assets.push(asset("musicbox-test.sha256", &missing));
+        let staged = dir.path().join(".musicbox-test.download");
+        fs::write(&staged, "partial").unwrap();
+        assert!(install_asset(&release, "musicbox-test", &target).is_err());
+        assert!(!staged.exists());
+        assert_eq!(fs::read_to_string(&target).unwrap(), "old");
+    }
+}
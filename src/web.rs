@@ -1,27 +1,39 @@
 //! Debug web server exposing controller state, configuration, and manual
 //! playback controls for development builds.
 
-use crate::config::MusicBoxConfig;
+use crate::access::Grant;
+use crate::config::{self, ConfigEditError, MusicBoxConfig};
 use crate::controller::{
-    AudioPlayer, CardUid, CardUidParseError, ControllerError, MusicBoxController, Track,
+    AudioPlayer, CardUid, CardUidParseError, ControllerError, Library, LibraryStats,
+    MusicBoxController, PlayerCapabilities, Track,
 };
-use crate::telemetry::{SharedStatus, StatusSnapshot};
+use crate::events::EventBus;
+use crate::expiry::{LifetimeParseError, parse_lifetime};
+use crate::history::{self, HistoryEntry, HistoryError, HistoryEvent, HistoryLog};
+use crate::mirror;
+use crate::sandbox;
+use crate::search::{self, HitKind, SearchHit};
+use crate::stats::{self, PeriodTotal, PlayStats};
+use crate::telemetry::{LatencySummary, SharedStatus, StatusSnapshot};
 use axum::{
-    Json, Router,
-    extract::State,
-    http::StatusCode,
+    Extension, Json, Router,
+    extract::{
+        DefaultBodyLimit, Multipart, Path, Query, Request, State, multipart::MultipartError,
+    },
+    http::{Method, StatusCode, header},
+    middleware::{self, Next},
     response::{Html, IntoResponse, Response},
-    routing::{get, post},
+    routing::{delete, get, post},
 };
 use serde::{Deserialize, Serialize};
 use std::{
     net::SocketAddr,
     path::PathBuf,
     sync::{Arc, Mutex},
-    time::UNIX_EPOCH,
+    time::{Duration, SystemTime, UNIX_EPOCH},
 };
 use thiserror::Error;
-use tracing::info;
+use tracing::{debug, info};
 
 const INDEX_HTML: &str = r#"<!DOCTYPE html>
 <html lang="en">
@@ -54,6 +66,22 @@ const INDEX_HTML: &str = r#"<!DOCTYPE html>
             <dt class="text-slate-400">Idle events</dt>
             <dd id="idleCount" class="font-mono">0</dd>
           </div>
+          <div class="flex justify-between">
+            <dt class="text-slate-400">Reader</dt>
+            <dd id="readerHealth" class="font-mono">–</dd>
+          </div>
+          <div class="flex justify-between">
+            <dt class="text-slate-400">Display</dt>
+            <dd id="displayHealth" class="font-mono">–</dd>
+          </div>
+          <div class="flex justify-between">
+            <dt class="text-slate-400">Tap to sound</dt>
+            <dd id="tapLatency" class="font-mono">–</dd>
+          </div>
+          <div class="flex justify-between gap-2">
+            <dt class="text-slate-400">Library</dt>
+            <dd id="librarySummary" class="text-right font-mono">–</dd>
+          </div>
           <div class="flex justify-between">
             <dt class="text-slate-400">Last update</dt>
             <dd id="lastUpdate" class="font-mono">–</dd>
@@ -70,6 +98,10 @@ const INDEX_HTML: &str = r#"<!DOCTYPE html>
             <dt class="text-slate-400">Active track</dt>
             <dd id="activeTrack" class="mt-1 truncate text-sm">–</dd>
           </div>
+          <div>
+            <dt class="text-slate-400">Queue</dt>
+            <dd><ol id="queueList" class="mt-1 space-y-1 text-xs"></ol></dd>
+          </div>
         </dl>
       </div>
 
@@ -93,6 +125,43 @@ const INDEX_HTML: &str = r#"<!DOCTYPE html>
         <p id="libraryEmpty" class="mt-4 hidden rounded-md border border-slate-800 bg-slate-900/60 px-4 py-3 text-sm text-slate-300">
           No card mappings found. Update the config to add UIDs and tracks.
         </p>
+        <div class="mt-4 flex flex-wrap items-center gap-3 text-sm">
+          <label class="flex items-center gap-2">
+            <input id="learnToggle" type="checkbox" class="rounded border-slate-600 bg-slate-800">
+            Learn unknown cards
+          </label>
+          <span id="learnedCards" class="font-mono text-xs text-slate-400"></span>
+        </div>
+        <div id="mappingForm" class="mt-4 hidden flex-wrap items-center gap-3 text-sm">
+          <input id="mapCard" type="text" list="cardSuggestions" placeholder="Card UID or name" class="w-44 rounded-md border border-slate-700 bg-slate-950/70 px-3 py-2 font-mono text-sm focus:outline-none focus:ring-2 focus:ring-slate-500">
+          <select id="filePicker" class="w-72 rounded-md border border-slate-700 bg-slate-950/70 px-3 py-2 text-sm focus:outline-none focus:ring-2 focus:ring-slate-500"></select>
+          <button id="mapCardBtn" class="rounded-md bg-indigo-500 px-4 py-2 text-sm font-medium text-slate-900 hover:bg-indigo-400 focus:outline-none focus:ring-2 focus:ring-indigo-300">Save Mapping</button>
+          <datalist id="cardSuggestions"></datalist>
+        </div>
+        <div id="uploadForm" class="mt-4 hidden flex-wrap items-center gap-3 text-sm">
+          <input id="uploadFiles" type="file" multiple accept="audio/*,.mp3,.ogg,.oga,.flac,.wav,.m4a,.aac,.opus" class="w-64 text-xs text-slate-300 file:mr-3 file:rounded-md file:border-0 file:bg-slate-700 file:px-3 file:py-2 file:text-slate-100">
+          <input id="uploadDir" type="text" placeholder="Folder (optional)" class="w-44 rounded-md border border-slate-700 bg-slate-950/70 px-3 py-2 font-mono text-sm focus:outline-none focus:ring-2 focus:ring-slate-500">
+          <button id="uploadBtn" class="rounded-md border border-slate-600 px-4 py-2 text-sm font-medium hover:bg-slate-800 focus:outline-none focus:ring-2 focus:ring-slate-500">Upload</button>
+          <progress id="uploadProgress" class="hidden w-40" max="100" value="0"></progress>
+          <span id="uploadStatus" class="text-xs text-slate-400"></span>
+        </div>
+      </div>
+    </section>
+
+    <section id="statsSection" class="hidden rounded-xl border border-slate-800 bg-slate-900/70 p-6 backdrop-blur">
+      <div class="flex items-center justify-between">
+        <h2 class="text-lg font-medium">Listening</h2>
+        <span id="statsTotal" class="text-xs text-slate-400"></span>
+      </div>
+      <div class="mt-4 grid gap-6 lg:grid-cols-3">
+        <div class="lg:col-span-2">
+          <h3 class="text-xs uppercase tracking-wide text-slate-400">Last 14 days</h3>
+          <div id="statsDays" class="mt-2 flex h-32 items-end gap-1 border-b border-slate-800"></div>
+        </div>
+        <div>
+          <h3 class="text-xs uppercase tracking-wide text-slate-400">Most played</h3>
+          <ol id="statsCards" class="mt-2 space-y-1 text-sm"></ol>
+        </div>
       </div>
     </section>
 
@@ -109,22 +178,53 @@ const INDEX_HTML: &str = r#"<!DOCTYPE html>
         <button id="saveConfigBtn" class="rounded-md bg-indigo-500 px-4 py-2 text-sm font-medium text-slate-900 hover:bg-indigo-400 focus:outline-none focus:ring-2 focus:ring-indigo-300">Save Config</button>
         <button id="reloadConfigBtn" class="rounded-md border border-slate-600 px-4 py-2 text-sm font-medium hover:bg-slate-800 focus:outline-none focus:ring-2 focus:ring-slate-500">Reload from Disk</button>
       </div>
+      <div class="mt-4 flex flex-wrap items-center gap-3 text-sm">
+        <input id="trackPath" type="text" list="trackSuggestions" placeholder="stories/gruffalo.mp3" class="w-72 rounded-md border border-slate-700 bg-slate-950/70 px-3 py-2 font-mono text-sm focus:outline-none focus:ring-2 focus:ring-slate-500">
+        <button id="checkTrackBtn" class="rounded-md border border-slate-600 px-4 py-2 text-sm font-medium hover:bg-slate-800 focus:outline-none focus:ring-2 focus:ring-slate-500">Check Track</button>
+        <span id="trackCheck" class="text-xs text-slate-400"></span>
+        <datalist id="trackSuggestions"></datalist>
+      </div>
     </section>
   </div>
 
   <script>
     const toastEl = document.getElementById('toast');
     const idleCountEl = document.getElementById('idleCount');
+    const readerHealthEl = document.getElementById('readerHealth');
+    const displayHealthEl = document.getElementById('displayHealth');
+    const tapLatencyEl = document.getElementById('tapLatency');
     const lastUpdateEl = document.getElementById('lastUpdate');
+    const librarySummaryEl = document.getElementById('librarySummary');
     const lastActionEl = document.getElementById('lastAction');
     const activeCardEl = document.getElementById('activeCard');
     const activeTrackEl = document.getElementById('activeTrack');
+    const queueListEl = document.getElementById('queueList');
     const libraryRowsEl = document.getElementById('libraryRows');
     const libraryEmptyEl = document.getElementById('libraryEmpty');
     const libraryCountEl = document.getElementById('libraryCount');
     const configEditorEl = document.getElementById('configEditor');
     const configPathEl = document.getElementById('configPath');
     const configDirtyEl = document.getElementById('configDirty');
+    const learnToggleEl = document.getElementById('learnToggle');
+    const learnedCardsEl = document.getElementById('learnedCards');
+    const trackPathEl = document.getElementById('trackPath');
+    const trackCheckEl = document.getElementById('trackCheck');
+    const trackSuggestionsEl = document.getElementById('trackSuggestions');
+    const statsSectionEl = document.getElementById('statsSection');
+    const statsTotalEl = document.getElementById('statsTotal');
+    const statsDaysEl = document.getElementById('statsDays');
+    const statsCardsEl = document.getElementById('statsCards');
+    const mappingFormEl = document.getElementById('mappingForm');
+    const mapCardEl = document.getElementById('mapCard');
+    const filePickerEl = document.getElementById('filePicker');
+    const cardSuggestionsEl = document.getElementById('cardSuggestions');
+    const uploadFormEl = document.getElementById('uploadForm');
+    const uploadFilesEl = document.getElementById('uploadFiles');
+    const uploadDirEl = document.getElementById('uploadDir');
+    const uploadProgressEl = document.getElementById('uploadProgress');
+    const uploadStatusEl = document.getElementById('uploadStatus');
+    let musicFiles = [];
+    let suggestTimer = null;
     let configDirty = false;
 
     function showToast(message, isError = false) {
@@ -142,10 +242,17 @@ const INDEX_HTML: &str = r#"<!DOCTYPE html>
       configDirtyEl.classList.toggle('hidden', !isDirty);
     }
 
+    // A token shared as /#token=... is sent with every request.
+    const apiToken = new URLSearchParams(location.hash.slice(1)).get('token');
+
     async function fetchJson(url, options = {}) {
       const response = await fetch(url, {
-        headers: { 'Content-Type': 'application/json', ...(options.headers || {}) },
         ...options,
+        headers: {
+          'Content-Type': 'application/json',
+          ...(apiToken ? { Authorization: `Bearer ${apiToken}` } : {}),
+          ...(options.headers || {}),
+        },
       });
 
       if (!response.ok) {
@@ -169,12 +276,68 @@ const INDEX_HTML: &str = r#"<!DOCTYPE html>
 
     function updateStatus(status) {
       idleCountEl.textContent = status.idle_events;
+      readerHealthEl.textContent = status.reader_health + ' (' + status.reader_errors + ' errors)';
+      readerHealthEl.title = status.reader_last_error || '';
+      displayHealthEl.textContent = status.display_health;
+      displayHealthEl.title = status.display_error || '';
+      const latency = status.tap_latency;
+      tapLatencyEl.textContent = latency.sound_p50_ms === null
+        ? '–'
+        : 'p50 ' + latency.sound_p50_ms + ' ms, p95 ' + latency.sound_p95_ms + ' ms';
+      tapLatencyEl.title = latency.samples + ' taps; decision p50 '
+        + latency.decision_p50_ms + ' ms, p95 ' + latency.decision_p95_ms + ' ms';
       lastUpdateEl.textContent = status.last_update || '–';
+      librarySummaryEl.textContent = status.library ? librarySummary(status.library) : '–';
+      librarySummaryEl.classList.toggle('text-red-300', Boolean(status.library && status.library.missing_tracks > 0));
       lastActionEl.textContent = status.last_action || '–';
       activeCardEl.textContent = status.active_card || '–';
       activeTrackEl.textContent = status.active_track || '–';
     }
 
+    // "142 cards, 3 missing files, reloaded 2m ago"
+    function librarySummary(library) {
+      const ago = library.reloaded_secs_ago;
+      const age = ago < 60 ? ago + 's'
+        : ago < 3600 ? Math.floor(ago / 60) + 'm'
+        : ago < 86400 ? Math.floor(ago / 3600) + 'h'
+        : Math.floor(ago / 86400) + 'd';
+      return library.cards + (library.cards === 1 ? ' card, ' : ' cards, ')
+        + library.missing_tracks + (library.missing_tracks === 1 ? ' missing file' : ' missing files')
+        + ', reloaded ' + age + ' ago';
+    }
+
+    function renderQueue(queue) {
+      queueListEl.innerHTML = '';
+      queue.tracks.forEach((track, index) => {
+        const item = document.createElement('li');
+        item.className = 'flex items-center justify-between gap-2';
+        const name = document.createElement('span');
+        name.className = 'truncate';
+        name.textContent = track;
+        name.title = track;
+        item.appendChild(name);
+        if (index === queue.position) {
+          name.classList.add('font-semibold', 'text-emerald-300');
+        } else {
+          const removeBtn = document.createElement('button');
+          removeBtn.className = 'rounded px-1 text-slate-400 hover:text-red-300';
+          removeBtn.textContent = '✕';
+          removeBtn.title = 'Remove from queue';
+          removeBtn.addEventListener('click', () => removeQueued(index));
+          item.appendChild(removeBtn);
+        }
+        queueListEl.appendChild(item);
+      });
+    }
+
+    async function removeQueued(index) {
+      try {
+        renderQueue(await fetchJson('/api/queue/' + index, { method: 'DELETE' }));
+      } catch (err) {
+        showToast(err.message, true);
+      }
+    }
+
     function renderLibrary(entries, activeCard) {
       libraryRowsEl.innerHTML = '';
       if (!entries || entries.length === 0) {
@@ -194,6 +357,12 @@ const INDEX_HTML: &str = r#"<!DOCTYPE html>
         const cardCell = document.createElement('td');
         cardCell.className = 'px-4 py-3 font-mono text-xs';
         cardCell.textContent = entry.card;
+        if (entry.name) {
+          const nameEl = document.createElement('div');
+          nameEl.className = 'font-sans text-sm text-slate-200';
+          nameEl.textContent = entry.name;
+          cardCell.prepend(nameEl);
+        }
 
         const trackCell = document.createElement('td');
         trackCell.className = 'px-4 py-3 text-sm';
@@ -206,8 +375,15 @@ const INDEX_HTML: &str = r#"<!DOCTYPE html>
         playBtn.className = 'rounded-md bg-emerald-500 px-3 py-1 text-xs font-semibold text-slate-900 hover:bg-emerald-400 focus:outline-none focus:ring-2 focus:ring-emerald-300';
         playBtn.textContent = 'Play';
         playBtn.addEventListener('click', () => triggerPlay(entry.card));
+        const editBtn = document.createElement('button');
+        editBtn.className = 'ml-2 rounded-md border border-slate-600 px-3 py-1 text-xs font-medium hover:bg-slate-800 focus:outline-none focus:ring-2 focus:ring-slate-500';
+        editBtn.textContent = 'Edit';
+        editBtn.addEventListener('click', () => editMapping(entry.card));
 
         actionsCell.appendChild(playBtn);
+        if (musicFiles.length > 0) {
+          actionsCell.appendChild(editBtn);
+        }
         row.appendChild(cardCell);
         row.appendChild(trackCell);
         row.appendChild(actionsCell);
@@ -221,6 +397,177 @@ const INDEX_HTML: &str = r#"<!DOCTYPE html>
         updateStatus(status);
         const library = await fetchJson('/api/library');
         renderLibrary(library.entries, status.active_card);
+        renderQueue(await fetchJson('/api/queue'));
+      } catch (err) {
+        showToast(err.message, true);
+      }
+      try {
+        renderLearning(await fetchJson('/api/learn'));
+      } catch (_) {
+        // Guest tokens cannot see learning mode.
+      }
+    }
+
+    async function loadStats() {
+      try {
+        renderStats(await fetchJson('/api/stats?since=14d&top=5'));
+      } catch (_) {
+        // No history log, or a guest token; the section stays hidden.
+      }
+    }
+
+    function minutes(playedMs) {
+      return Math.round(playedMs / 60000) + ' min';
+    }
+
+    function renderStats(stats) {
+      statsSectionEl.classList.remove('hidden');
+      statsTotalEl.textContent = stats.plays + ' plays, ' + minutes(stats.played_ms);
+      const longest = Math.max(1, ...stats.days.map((day) => day.played_ms));
+      statsDaysEl.innerHTML = '';
+      stats.days.forEach((day) => {
+        const bar = document.createElement('div');
+        bar.className = 'flex-1 rounded-t bg-indigo-500/70';
+        bar.style.height = (100 * day.played_ms / longest) + '%';
+        bar.title = day.start + ': ' + minutes(day.played_ms) + ', ' + day.plays + ' plays';
+        statsDaysEl.appendChild(bar);
+      });
+      statsCardsEl.innerHTML = '';
+      stats.cards.forEach((card) => {
+        const item = document.createElement('li');
+        item.className = 'flex justify-between gap-2';
+        const label = document.createElement('span');
+        label.className = 'truncate';
+        label.textContent = card.name || card.card;
+        const count = document.createElement('span');
+        count.className = 'font-mono text-xs text-slate-400';
+        count.textContent = card.plays + '× · ' + minutes(card.played_ms);
+        item.appendChild(label);
+        item.appendChild(count);
+        statsCardsEl.appendChild(item);
+      });
+    }
+
+    function renderLearning(learning) {
+      learnToggleEl.checked = learning.learning;
+      learnedCardsEl.textContent = learning.cards.length === 0
+        ? ''
+        : 'Learned: ' + learning.cards.join(', ');
+      cardSuggestionsEl.innerHTML = '';
+      learning.cards.forEach((card) => {
+        const option = document.createElement('option');
+        option.value = card;
+        cardSuggestionsEl.appendChild(option);
+      });
+    }
+
+    async function loadFiles() {
+      try {
+        musicFiles = (await fetchJson('/api/files')).files;
+      } catch (_) {
+        // Guest tokens cannot map cards; the picker stays hidden.
+        return;
+      }
+      filePickerEl.innerHTML = '';
+      musicFiles.forEach((file) => {
+        const option = document.createElement('option');
+        option.value = file.path;
+        option.textContent = file.card ? file.path + ' (' + file.card + ')' : file.path;
+        filePickerEl.appendChild(option);
+      });
+      mappingFormEl.classList.toggle('hidden', musicFiles.length === 0);
+      mappingFormEl.classList.toggle('flex', musicFiles.length > 0);
+      uploadFormEl.classList.remove('hidden');
+      uploadFormEl.classList.add('flex');
+    }
+
+    // fetch() cannot report upload progress, so this uses XMLHttpRequest.
+    function uploadTracks() {
+      const files = Array.from(uploadFilesEl.files);
+      if (files.length === 0) {
+        showToast('Choose audio files to upload first', true);
+        return;
+      }
+      const form = new FormData();
+      files.forEach((file) => form.append('file', file, file.name));
+      const dir = uploadDirEl.value.trim();
+      const request = new XMLHttpRequest();
+      request.open('POST', '/api/upload' + (dir ? '?dir=' + encodeURIComponent(dir) : ''));
+      if (apiToken) {
+        request.setRequestHeader('Authorization', `Bearer ${apiToken}`);
+      }
+      uploadProgressEl.value = 0;
+      uploadProgressEl.classList.remove('hidden');
+      uploadStatusEl.textContent = 'Uploading…';
+      request.upload.addEventListener('progress', (event) => {
+        if (event.lengthComputable) {
+          const percent = Math.round(100 * event.loaded / event.total);
+          uploadProgressEl.value = percent;
+          uploadStatusEl.textContent = 'Uploading… ' + percent + '%';
+        }
+      });
+      request.addEventListener('load', async () => {
+        uploadProgressEl.classList.add('hidden');
+        let body = null;
+        try {
+          body = JSON.parse(request.responseText);
+        } catch (_) {
+          // Not JSON; fall back to the status text below.
+        }
+        if (request.status !== 200) {
+          uploadStatusEl.textContent = '';
+          showToast((body && body.error) || request.statusText || 'Upload failed', true);
+          return;
+        }
+        uploadStatusEl.textContent = 'Uploaded ' + body.files.map((file) => file.path).join(', ');
+        uploadFilesEl.value = '';
+        await loadFiles();
+        if (body.files.length > 0) {
+          filePickerEl.value = body.files[0].path;
+        }
+      });
+      request.addEventListener('error', () => {
+        uploadProgressEl.classList.add('hidden');
+        uploadStatusEl.textContent = '';
+        showToast('Upload failed; check the connection to the box', true);
+      });
+      request.send(form);
+    }
+
+    function editMapping(card) {
+      mapCardEl.value = card;
+      const file = musicFiles.find((candidate) => candidate.card === card);
+      if (file) {
+        filePickerEl.value = file.path;
+      }
+      mapCardEl.focus();
+    }
+
+    async function saveMapping() {
+      try {
+        const entry = await fetchJson('/api/mappings', {
+          method: 'POST',
+          body: JSON.stringify({ card: mapCardEl.value.trim(), track: filePickerEl.value }),
+        });
+        showToast('Card ' + entry.card + ' now plays ' + filePickerEl.value);
+        await loadFiles();
+        await refreshStatusAndLibrary();
+        if (configDirty) {
+          showToast('Mapping saved; reload the config to see it in the editor');
+        } else {
+          await loadConfig();
+        }
+      } catch (err) {
+        showToast(err.message, true);
+      }
+    }
+
+    async function setLearning() {
+      try {
+        renderLearning(await fetchJson('/api/learn', {
+          method: 'POST',
+          body: JSON.stringify({ learning: learnToggleEl.checked }),
+        }));
       } catch (err) {
         showToast(err.message, true);
       }
@@ -279,22 +626,72 @@ const INDEX_HTML: &str = r#"<!DOCTYPE html>
         configPathEl.textContent = result.path;
         setConfigDirty(false);
         showToast('Configuration saved');
+        await loadFiles();
         await refreshStatusAndLibrary();
       } catch (err) {
         showToast(err.message, true);
       }
     }
 
+    async function checkTrack() {
+      try {
+        const check = await fetchJson('/api/validate-track', {
+          method: 'POST',
+          body: JSON.stringify({ path: trackPathEl.value }),
+        });
+        const length = check.duration_ms === null
+          ? 'unknown length'
+          : Math.round(check.duration_ms / 1000) + ' s';
+        trackCheckEl.textContent = check.ok
+          ? check.files + (check.files === 1 ? ' file, ' : ' files, ') + length
+          : check.problems.join('; ');
+        trackCheckEl.classList.toggle('text-red-300', !check.ok);
+      } catch (err) {
+        showToast(err.message, true);
+      }
+    }
+
+    // Offers files whose name or tags match what was typed so far.
+    function suggestTracks() {
+      clearTimeout(suggestTimer);
+      suggestTimer = setTimeout(async () => {
+        const query = trackPathEl.value.trim();
+        if (query.length < 2) {
+          return;
+        }
+        try {
+          const result = await fetchJson('/api/search?q=' + encodeURIComponent(query));
+          trackSuggestionsEl.innerHTML = '';
+          result.hits.filter((hit) => hit.kind === 'file').forEach((hit) => {
+            const option = document.createElement('option');
+            option.value = hit.track;
+            option.label = [hit.title, hit.artist].filter(Boolean).join(' – ');
+            trackSuggestionsEl.appendChild(option);
+          });
+        } catch (_) {
+          // Suggestions are a convenience; typing the path still works.
+        }
+      }, 250);
+    }
+
     document.addEventListener('DOMContentLoaded', async () => {
       document.getElementById('pauseBtn').addEventListener('click', pausePlayback);
       document.getElementById('refreshBtn').addEventListener('click', refreshStatusAndLibrary);
       document.getElementById('saveConfigBtn').addEventListener('click', saveConfig);
       document.getElementById('reloadConfigBtn').addEventListener('click', loadConfig);
+      learnToggleEl.addEventListener('change', setLearning);
+      document.getElementById('checkTrackBtn').addEventListener('click', checkTrack);
+      trackPathEl.addEventListener('input', suggestTracks);
       configEditorEl.addEventListener('input', () => setConfigDirty(true));
+      document.getElementById('mapCardBtn').addEventListener('click', saveMapping);
+      document.getElementById('uploadBtn').addEventListener('click', uploadTracks);
 
       await loadConfig();
+      await loadFiles();
       await refreshStatusAndLibrary();
       setInterval(refreshStatusAndLibrary, 4000);
+      await loadStats();
+      setInterval(loadStats, 60000);
     });
   </script>
 </body>
@@ -318,7 +715,15 @@ pub enum WebError {
 pub struct DebugState<P: AudioPlayer + Send + 'static> {
     pub status: SharedStatus,
     pub controller: Arc<Mutex<MusicBoxController<P>>>,
+    /// Where plays and pauses from the API are published.
+    pub events: EventBus,
     pub config_path: PathBuf,
+    /// The log `--history-log` records plays in, for `/api/history`.
+    pub history: Option<HistoryLog>,
+    /// Refuses requests that would change the box, as
+    /// `--debug-http-readonly` asks, whatever the config's `[web]` table
+    /// says.
+    pub read_only: bool,
 }
 
 impl<P: AudioPlayer + Send + 'static> Clone for DebugState<P> {
@@ -326,7 +731,10 @@ impl<P: AudioPlayer + Send + 'static> Clone for DebugState<P> {
         Self {
             status: self.status.clone(),
             controller: Arc::clone(&self.controller),
+            events: self.events.clone(),
             config_path: self.config_path.clone(),
+            history: self.history.clone(),
+            read_only: self.read_only,
         }
     }
 }
@@ -355,16 +763,86 @@ pub fn serve<P: AudioPlayer + Send + 'static>(
 
 /// Creates the Axum router and defines the routes.
 fn build_router<P: AudioPlayer + Send + 'static>(state: DebugState<P>) -> Router {
+    let full_only = Router::new()
+        .route("/api/tracks/*path", get(get_track::<P>))
+        .route("/api/config", get(get_config::<P>).put(update_config::<P>))
+        .route("/api/learn", get(get_learning::<P>).post(set_learning::<P>))
+        .route("/api/validate-track", post(validate_track::<P>))
+        .route("/api/files", get(list_files::<P>))
+        .route("/api/mappings", post(map_card::<P>))
+        // Uploads check `[web] max_upload_mb` themselves as they stream in.
+        .route(
+            "/api/upload",
+            post(upload_files::<P>).layer(DefaultBodyLimit::disable()),
+        )
+        .route("/api/history", get(get_history::<P>))
+        .route("/api/stats", get(get_stats::<P>))
+        .route_layer(middleware::from_fn(require_full));
     Router::new()
-        .route("/", get(index::<P>))
         .route("/api/status", get(get_status::<P>))
         .route("/api/library", get(get_library::<P>))
-        .route("/api/config", get(get_config::<P>).put(update_config::<P>))
+        .route("/api/cards/:card", get(get_card::<P>))
         .route("/api/play", post(play_card::<P>))
         .route("/api/pause", post(pause::<P>))
+        .route("/api/search", get(search_library::<P>))
+        .route("/api/queue", get(get_queue::<P>))
+        .route("/api/queue/:index", delete(remove_queued::<P>))
+        .route("/api/volume", get(get_volume::<P>).post(set_volume::<P>))
+        .route("/api/mute", get(get_mute::<P>).post(set_mute::<P>))
+        .merge(full_only)
+        .route_layer(middleware::from_fn_with_state(
+            state.clone(),
+            authenticate::<P>,
+        ))
+        // The dashboard page holds no data, so it loads without a token.
+        .route("/", get(index::<P>))
         .with_state(state)
 }
 
+/// Checks the bearer token or basic auth password, when the config lists
+/// any tokens, and records what the request may do for the handlers. A
+/// read-only API turns away everything but reads.
+async fn authenticate<P: AudioPlayer + Send + 'static>(
+    State(state): State<DebugState<P>>,
+    mut request: Request,
+    next: Next,
+) -> Result<Response, ApiError> {
+    let header = request
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok());
+    let (grant, read_only) = {
+        let guard = state.controller.lock().expect("controller lock");
+        let library = guard.library();
+        let grant = library.api_tokens().authorize(header).map(|(grant, name)| {
+            if let Some(name) = name {
+                debug!(token = name, uri = %request.uri(), "api request");
+            }
+            grant
+        });
+        (grant, state.read_only || library.web_settings().read_only)
+    };
+    let grant = grant.ok_or(ApiError::Unauthorized)?;
+    if read_only && !matches!(*request.method(), Method::GET | Method::HEAD) {
+        return Err(ApiError::ReadOnly);
+    }
+    request.extensions_mut().insert(grant);
+    Ok(next.run(request).await)
+}
+
+/// Turns guest tokens away from routes that read or change the box's
+/// setup.
+async fn require_full(
+    Extension(grant): Extension<Grant>,
+    request: Request,
+    next: Next,
+) -> Result<Response, ApiError> {
+    if !grant.is_full() {
+        return Err(ApiError::Forbidden);
+    }
+    Ok(next.run(request).await)
+}
+
 /// Serves the HTML for the debug dashboard.
 async fn index<P: AudioPlayer + Send + 'static>(
     State(_): State<DebugState<P>>,
@@ -383,20 +861,281 @@ async fn get_status<P: AudioPlayer + Send + 'static>(
 async fn get_library<P: AudioPlayer + Send + 'static>(
     State(state): State<DebugState<P>>,
 ) -> Json<LibraryResponse> {
-    let mut entries = {
+    let mut entries: Vec<LibraryEntry> = {
+        let guard = state.controller.lock().expect("controller lock");
+        let library = guard.library();
+        guard
+            .library_entries()
+            .into_iter()
+            .map(|(card, track)| LibraryEntry {
+                name: library.name(&card).map(str::to_string),
+                card: card.to_hex_lowercase(),
+                track: track.path().display().to_string(),
+            })
+            .collect()
+    };
+    entries.sort_by(|a, b| a.card.cmp(&b.card));
+
+    Json(LibraryResponse { entries })
+}
+
+/// Reports what a card (hex UID or nickname) is mapped to without playing it.
+async fn get_card<P: AudioPlayer + Send + 'static>(
+    State(state): State<DebugState<P>>,
+    Path(card): Path<String>,
+) -> Result<Json<LibraryEntry>, ApiError> {
+    let guard = state.controller.lock().expect("controller lock");
+    let uid = guard.library().resolve_card(&card)?;
+    let track = guard
+        .peek(&uid)
+        .ok_or_else(|| ApiError::UnmappedCard(uid.clone()))?;
+
+    Ok(Json(LibraryEntry {
+        name: guard.library().name(&uid).map(str::to_string),
+        card: uid.to_hex_lowercase(),
+        track: track.path().display().to_string(),
+    }))
+}
+
+/// Serves a track file by its path relative to `music_dir`, so another box
+/// can copy it with `musicbox sync --with-tracks`.
+async fn get_track<P: AudioPlayer + Send + 'static>(
+    State(state): State<DebugState<P>>,
+    Path(path): Path<String>,
+) -> Result<Vec<u8>, ApiError> {
+    let track = {
+        let guard = state.controller.lock().expect("controller lock");
+        guard.library().confined_track(&path)
+    }
+    .ok_or_else(|| ApiError::TrackFile(path.clone()))?;
+    let file = track.path().to_path_buf();
+    if !file.is_file() {
+        return Err(ApiError::TrackFile(path));
+    }
+    tokio::task::spawn_blocking(move || std::fs::read(file))
+        .await
+        .map_err(ApiError::Join)?
+        .map_err(ApiError::Io)
+}
+
+/// Checks a track path, relative to `music_dir`, before the web editor maps
+/// a card to it, so a typo or a file the box cannot decode shows up now
+/// rather than at the next tap.
+async fn validate_track<P: AudioPlayer + Send + 'static>(
+    State(state): State<DebugState<P>>,
+    Json(request): Json<ValidateTrackRequest>,
+) -> Result<Json<TrackCheckPayload>, ApiError> {
+    let track = {
+        let guard = state.controller.lock().expect("controller lock");
+        guard.library().confined_track(&request.path)
+    }
+    .ok_or_else(|| ApiError::TrackPath(request.path.clone()))?;
+    let check = tokio::task::spawn_blocking(move || crate::metadata::check_track(&track))
+        .await
+        .map_err(ApiError::Join)?;
+    Ok(Json(TrackCheckPayload {
+        path: request.path,
+        ok: check.is_ok(),
+        files: check.playable,
+        duration_ms: check
+            .duration
+            .map(|duration| duration.as_millis().try_into().unwrap_or(u64::MAX)),
+        problems: check.problems,
+    }))
+}
+
+/// Lists the audio files under `music_dir`, relative to it and in name
+/// order, so the dashboard can offer them for a card to play.
+async fn list_files<P: AudioPlayer + Send + 'static>(
+    State(state): State<DebugState<P>>,
+) -> Result<Json<FilesResponse>, ApiError> {
+    let (music_dir, mapped) = {
         let guard = state.controller.lock().expect("controller lock");
-        guard.library_entries()
+        let library = guard.library();
+        let mut cards = library.entries();
+        cards.sort_by(|(a, _), (b, _)| a.0.cmp(&b.0));
+        let mapped: Vec<(PathBuf, String)> = cards
+            .iter()
+            .flat_map(|(card, _)| {
+                library
+                    .playlist(card)
+                    .unwrap_or_default()
+                    .iter()
+                    .map(|track| (track.path().to_path_buf(), card.to_hex_lowercase()))
+            })
+            .collect();
+        (library.music_dir().to_path_buf(), mapped)
+    };
+    let listed = {
+        let music_dir = music_dir.clone();
+        tokio::task::spawn_blocking(move || config::audio_files_under(&music_dir))
+            .await
+            .map_err(ApiError::Join)?
+    };
+    let mut files = match listed {
+        Ok(files) => files,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+        Err(err) => return Err(ApiError::Io(err)),
     };
-    entries.sort_by(|a, b| a.0.as_bytes().cmp(b.0.as_bytes()));
-    let entries = entries
+    files.sort();
+    let files = files
         .into_iter()
-        .map(|(card, track)| LibraryEntry {
-            card: card.to_hex_lowercase(),
-            track: track.path().display().to_string(),
+        .map(|file| FilePayload {
+            card: mapped
+                .iter()
+                .find(|(path, _)| file.starts_with(path))
+                .map(|(_, card)| card.clone()),
+            path: file
+                .strip_prefix(&music_dir)
+                .unwrap_or(&file)
+                .display()
+                .to_string(),
         })
         .collect();
+    Ok(Json(FilesResponse {
+        music_dir: music_dir.display().to_string(),
+        files,
+    }))
+}
 
-    Json(LibraryResponse { entries })
+/// Points a card at a file under `music_dir`, adding the card to the
+/// config if it is new, and reloads the cards. A card that plays a
+/// playlist or runs a command is left alone.
+async fn map_card<P: AudioPlayer + Send + 'static>(
+    State(state): State<DebugState<P>>,
+    Json(request): Json<MappingRequest>,
+) -> Result<Json<LibraryEntry>, ApiError> {
+    let (uid, track) = {
+        let guard = state.controller.lock().expect("controller lock");
+        let library = guard.library();
+        let uid = library.resolve_card(&request.card)?;
+        let track = library
+            .confined_track(&request.track)
+            .ok_or_else(|| ApiError::TrackPath(request.track.clone()))?;
+        (uid, track)
+    };
+    if !track.path().exists() {
+        return Err(ApiError::TrackFile(request.track));
+    }
+
+    let path = state.config_path.clone();
+    let edit_uid = uid.clone();
+    let parsed = tokio::task::spawn_blocking(move || {
+        match config::add_card_to_config(&path, &edit_uid, &request.track, None) {
+            Err(ConfigEditError::Duplicate(_)) => {
+                config::update_card_in_config(&path, &edit_uid, &request.track, false)
+            }
+            added => added,
+        }?;
+        let contents = std::fs::read_to_string(&path)?;
+        let config_dir = path.parent().unwrap_or(std::path::Path::new(""));
+        MusicBoxConfig::from_reader_in(contents.as_bytes(), config_dir)
+            .map_err(|err| ApiError::InvalidConfig(err.to_string()))
+    })
+    .await
+    .map_err(ApiError::Join)??;
+
+    let entry = {
+        let mut guard = state.controller.lock().expect("controller lock");
+        guard.replace_library(parsed.into_library());
+        LibraryEntry {
+            name: guard.library().name(&uid).map(str::to_string),
+            card: uid.to_hex_lowercase(),
+            track: track.path().display().to_string(),
+        }
+    };
+    info!(
+        card = %entry.card,
+        track = %entry.track,
+        "debug UI mapped card"
+    );
+    Ok(Json(entry))
+}
+
+/// Saves the audio files of a multipart upload under `music_dir`, in the
+/// folder `dir` names if any, so tracks can be added from a phone. Each
+/// file streams to a hidden temporary file that is renamed into place once
+/// complete, so a dropped connection leaves nothing half written behind.
+async fn upload_files<P: AudioPlayer + Send + 'static>(
+    State(state): State<DebugState<P>>,
+    Query(request): Query<UploadRequest>,
+    mut multipart: Multipart,
+) -> Result<Json<UploadResponse>, ApiError> {
+    use tokio::io::AsyncWriteExt;
+
+    let (music_dir, external, limit) = {
+        let guard = state.controller.lock().expect("controller lock");
+        let library = guard.library();
+        (
+            library.music_dir().to_path_buf(),
+            library.allows_external_paths(),
+            library.web_settings().max_upload_bytes,
+        )
+    };
+    if limit == 0 {
+        return Err(ApiError::UploadsOff);
+    }
+    let dir = request.dir.unwrap_or_default();
+    let dir = dir.trim().trim_matches('/');
+
+    let mut files = Vec::new();
+    while let Some(mut field) = multipart.next_field().await? {
+        let Some(name) = field.file_name().map(str::to_string) else {
+            continue;
+        };
+        let plain = std::path::Path::new(&name)
+            .file_name()
+            .is_some_and(|file_name| file_name == name.as_str());
+        if !plain {
+            return Err(ApiError::TrackPath(name));
+        }
+        if !config::is_audio_file(std::path::Path::new(&name)) {
+            return Err(ApiError::NotAudio(name));
+        }
+        let relative = if dir.is_empty() {
+            name.clone()
+        } else {
+            format!("{dir}/{name}")
+        };
+        let target = sandbox::confine(&music_dir, &relative, external)
+            .ok_or_else(|| ApiError::TrackPath(relative.clone()))?;
+        if tokio::fs::try_exists(&target).await? {
+            return Err(ApiError::UploadExists(relative));
+        }
+        let folder = target.parent().unwrap_or(&music_dir);
+        tokio::fs::create_dir_all(folder).await?;
+        let partial = folder.join(format!(".{name}.upload"));
+
+        let mut file = tokio::fs::File::create(&partial).await?;
+        let mut bytes = 0u64;
+        let written: Result<(), ApiError> = async {
+            while let Some(chunk) = field.chunk().await? {
+                bytes += chunk.len() as u64;
+                if bytes > limit {
+                    return Err(ApiError::UploadTooLarge {
+                        name: name.clone(),
+                        limit,
+                    });
+                }
+                file.write_all(&chunk).await?;
+            }
+            file.flush().await?;
+            Ok(())
+        }
+        .await;
+        drop(file);
+        if let Err(err) = written {
+            let _ = tokio::fs::remove_file(&partial).await;
+            return Err(err);
+        }
+        tokio::fs::rename(&partial, &target).await?;
+        info!(path = %target.display(), bytes, "debug UI uploaded track");
+        files.push(UploadedPayload {
+            path: relative,
+            bytes,
+        });
+    }
+    Ok(Json(UploadResponse { files }))
 }
 
 /// Returns the current configuration.
@@ -421,8 +1160,32 @@ async fn update_config<P: AudioPlayer + Send + 'static>(
     Json(request): Json<UpdateConfigRequest>,
 ) -> Result<Json<ConfigResponse>, ApiError> {
     let contents = request.contents;
-    let parsed = MusicBoxConfig::from_reader(contents.as_bytes())
+    let config_dir = state
+        .config_path
+        .parent()
+        .unwrap_or(std::path::Path::new(""));
+    let parsed = MusicBoxConfig::from_reader_in(contents.as_bytes(), config_dir)
         .map_err(|err| ApiError::InvalidConfig(err.to_string()))?;
+    // A config sent over HTTP may not reach outside music_dir unless the
+    // one on the box already allows it.
+    let allowed = {
+        let guard = state.controller.lock().expect("controller lock");
+        guard.library().allows_external_paths()
+    };
+    if !allowed {
+        if parsed.allow_external_paths() {
+            return Err(ApiError::InvalidConfig(
+                "allow_external_paths can only be turned on in the config file on the box"
+                    .to_string(),
+            ));
+        }
+        if let Some(path) = parsed.external_tracks().first() {
+            return Err(ApiError::InvalidConfig(format!(
+                "track {} is outside music_dir",
+                path.display()
+            )));
+        }
+    }
     let library = parsed.clone().into_library();
 
     let path = state.config_path.clone();
@@ -431,6 +1194,7 @@ async fn update_config<P: AudioPlayer + Send + 'static>(
         .await
         .map_err(ApiError::Join)?
         .map_err(ApiError::Io)?;
+    mirror::update(&state.config_path);
 
     {
         let mut guard = state.controller.lock().expect("controller lock");
@@ -453,13 +1217,16 @@ async fn play_card<P: AudioPlayer + Send + 'static>(
     State(state): State<DebugState<P>>,
     Json(request): Json<PlayRequest>,
 ) -> Result<Json<CommandResponse>, ApiError> {
-    let uid = CardUid::from_hex(request.card_hex.trim()).map_err(ApiError::CardUid)?;
-    let action = {
-        let mut guard = state.controller.lock().expect("controller lock");
-        guard.handle_card(&uid)
-    }?;
+    let action = state
+        .events
+        .apply_and_publish(&state.controller, |controller| {
+            let uid = controller
+                .library()
+                .resolve_card(&request.card_hex)
+                .map_err(ApiError::CardUid)?;
+            Ok::<_, ApiError>(controller.handle_card(&uid)?)
+        })?;
 
-    state.status.record_action(action.clone());
     let message = format!("{action:?}");
     let status = build_status(&state);
 
@@ -473,17 +1240,13 @@ async fn play_card<P: AudioPlayer + Send + 'static>(
 async fn pause<P: AudioPlayer + Send + 'static>(
     State(state): State<DebugState<P>>,
 ) -> Result<Json<CommandResponse>, ApiError> {
-    let maybe_action = {
-        let mut guard = state.controller.lock().expect("controller lock");
-        guard.pause_playback()
-    }?;
+    let maybe_action = state
+        .events
+        .apply_and_publish(&state.controller, |controller| controller.pause_playback())?;
 
     let status = build_status(&state);
     let message = match maybe_action {
-        Some(action) => {
-            state.status.record_action(action.clone());
-            format!("{action:?}")
-        }
+        Some(action) => format!("{action:?}"),
         None => "No active playback to pause".to_string(),
     };
 
@@ -493,26 +1256,297 @@ async fn pause<P: AudioPlayer + Send + 'static>(
     }))
 }
 
+/// Finds cards and files by card name, file name, or tags. The first
+/// search after the cards load builds the index.
+async fn search_library<P: AudioPlayer + Send + 'static>(
+    State(state): State<DebugState<P>>,
+    Query(request): Query<SearchRequest>,
+) -> Result<Json<SearchResponse>, ApiError> {
+    let library = state
+        .controller
+        .lock()
+        .expect("controller lock")
+        .library()
+        .clone();
+    let limit = request.limit.unwrap_or(search::DEFAULT_LIMIT);
+    let query = request.q.clone();
+    let hits = tokio::task::spawn_blocking(move || library.search_index().search(&query, limit))
+        .await
+        .map_err(ApiError::Join)?;
+    Ok(Json(SearchResponse {
+        query: request.q,
+        hits: hits.into_iter().map(SearchHitPayload::from).collect(),
+    }))
+}
+
+/// Lists recent plays from the history log, newest first.
+async fn get_history<P: AudioPlayer + Send + 'static>(
+    State(state): State<DebugState<P>>,
+    Query(request): Query<HistoryRequest>,
+) -> Result<Json<HistoryResponse>, ApiError> {
+    let log = state.history.clone().ok_or(ApiError::NoHistory)?;
+    let since = match request.since.as_deref() {
+        Some(age) => SystemTime::now()
+            .checked_sub(parse_lifetime(age)?)
+            .unwrap_or(UNIX_EPOCH),
+        None => UNIX_EPOCH,
+    };
+    let limit = request.limit.unwrap_or(history::DEFAULT_LIMIT);
+    let plays = tokio::task::spawn_blocking(move || log.recent_plays(since, limit))
+        .await
+        .map_err(ApiError::Join)??;
+    let guard = state.controller.lock().expect("controller lock");
+    let library = guard.library();
+    let plays = plays
+        .into_iter()
+        .filter_map(|entry| PlayPayload::new(entry, library))
+        .collect();
+    Ok(Json(HistoryResponse { plays }))
+}
+
+/// Totals plays from the history log: the most played cards and the time
+/// listened each day and week.
+async fn get_stats<P: AudioPlayer + Send + 'static>(
+    State(state): State<DebugState<P>>,
+    Query(request): Query<StatsRequest>,
+) -> Result<Json<StatsResponse>, ApiError> {
+    let log = state.history.clone().ok_or(ApiError::NoHistory)?;
+    let since = match request.since.as_deref() {
+        Some(age) => SystemTime::now()
+            .checked_sub(parse_lifetime(age)?)
+            .unwrap_or(UNIX_EPOCH),
+        None => UNIX_EPOCH,
+    };
+    let stats = tokio::task::spawn_blocking(move || PlayStats::from_log(&log, since))
+        .await
+        .map_err(ApiError::Join)??;
+    let guard = state.controller.lock().expect("controller lock");
+    let library = guard.library();
+    let cards = stats
+        .most_played(request.top.unwrap_or(stats::DEFAULT_TOP))
+        .iter()
+        .map(|total| CardTotalPayload {
+            card: total.card.clone(),
+            name: CardUid::parse(&total.card)
+                .ok()
+                .and_then(|uid| library.name(&uid).map(str::to_string)),
+            plays: total.plays,
+            played_ms: total.played.as_millis() as u64,
+        })
+        .collect();
+    Ok(Json(StatsResponse {
+        plays: stats.plays,
+        played_ms: stats.played.as_millis() as u64,
+        cards,
+        days: stats.days.iter().map(PeriodPayload::from).collect(),
+        weeks: stats.weeks.iter().map(PeriodPayload::from).collect(),
+        tags: stats
+            .tags
+            .iter()
+            .map(|total| TagTotalPayload {
+                card: total.card.clone(),
+                name: CardUid::parse(&total.card)
+                    .ok()
+                    .and_then(|uid| library.name(&uid).map(str::to_string)),
+                seen: total.seen,
+                counted: total.counted,
+                missed: total.missed(),
+            })
+            .collect(),
+    }))
+}
+
+/// Lists the playing card's tracks, folders expanded, and which one is
+/// playing.
+async fn get_queue<P: AudioPlayer + Send + 'static>(
+    State(state): State<DebugState<P>>,
+) -> Json<QueuePayload> {
+    Json(QueuePayload::from_controller(
+        &state.controller.lock().expect("controller lock"),
+    ))
+}
+
+/// Drops entry `index` from the playing card's queue until the card is
+/// tapped again.
+async fn remove_queued<P: AudioPlayer + Send + 'static>(
+    State(state): State<DebugState<P>>,
+    Path(index): Path<usize>,
+) -> Result<Json<QueuePayload>, ApiError> {
+    let mut guard = state.controller.lock().expect("controller lock");
+    let track = guard.remove_queued(index)?;
+    debug!(index, track = %track.path().display(), "removed queued track");
+    Ok(Json(QueuePayload::from_controller(&guard)))
+}
+
+/// Reports the listener's volume.
+async fn get_volume<P: AudioPlayer + Send + 'static>(
+    State(state): State<DebugState<P>>,
+) -> Json<VolumePayload> {
+    let volume = state.controller.lock().expect("controller lock").volume();
+    Json(VolumePayload { volume })
+}
+
+/// Sets the listener's volume; values outside 0.0..=1.0 are clamped, and
+/// guest tokens cannot go past their `max_volume`.
+async fn set_volume<P: AudioPlayer + Send + 'static>(
+    State(state): State<DebugState<P>>,
+    Extension(grant): Extension<Grant>,
+    Json(request): Json<VolumePayload>,
+) -> Result<Json<VolumePayload>, ApiError> {
+    let volume = state
+        .controller
+        .lock()
+        .expect("controller lock")
+        .set_volume(grant.cap_volume(request.volume))?;
+    Ok(Json(VolumePayload { volume }))
+}
+
+/// Reports whether unknown cards are being learned, and the cards noted so
+/// far, most recent first.
+async fn get_learning<P: AudioPlayer + Send + 'static>(
+    State(state): State<DebugState<P>>,
+) -> Json<LearningPayload> {
+    Json(LearningPayload::from_controller(
+        &state.controller.lock().expect("controller lock"),
+    ))
+}
+
+/// Starts or stops learning mode.
+async fn set_learning<P: AudioPlayer + Send + 'static>(
+    State(state): State<DebugState<P>>,
+    Json(request): Json<LearningRequest>,
+) -> Json<LearningPayload> {
+    let mut guard = state.controller.lock().expect("controller lock");
+    guard.set_learning(request.learning);
+    Json(LearningPayload::from_controller(&guard))
+}
+
+/// Reports whether output is muted.
+async fn get_mute<P: AudioPlayer + Send + 'static>(
+    State(state): State<DebugState<P>>,
+) -> Json<MutePayload> {
+    let muted = state.controller.lock().expect("controller lock").is_muted();
+    Json(MutePayload { muted })
+}
+
+/// Mutes or unmutes output; playback keeps its position either way.
+async fn set_mute<P: AudioPlayer + Send + 'static>(
+    State(state): State<DebugState<P>>,
+    Json(request): Json<MutePayload>,
+) -> Result<Json<MutePayload>, ApiError> {
+    state
+        .controller
+        .lock()
+        .expect("controller lock")
+        .set_muted(request.muted)?;
+    Ok(Json(MutePayload {
+        muted: request.muted,
+    }))
+}
+
 fn build_status<P: AudioPlayer + Send + 'static>(state: &DebugState<P>) -> StatusPayload {
-    let snapshot = state.status.snapshot();
-    let active = {
+    let (active, player) = {
         let guard = state.controller.lock().expect("controller lock");
-        guard.active()
+        state.status.record_library(guard.library().stats());
+        (guard.active(), guard.player_capabilities())
     };
-    StatusPayload::from_snapshot(snapshot, active)
+    StatusPayload::from_snapshot(state.status.snapshot(), active, player)
 }
 
 #[derive(Debug, Serialize)]
 struct StatusPayload {
     idle_events: u64,
+    reader_errors: u64,
+    reader_health: &'static str,
+    reader_last_error: Option<String>,
+    display_health: &'static str,
+    display_error: Option<String>,
     last_action: Option<String>,
     last_update: Option<String>,
     active_card: Option<String>,
     active_track: Option<String>,
+    capabilities: CapabilitiesPayload,
+    tap_latency: LatencyPayload,
+    library: Option<LibraryStatsPayload>,
+}
+
+/// How many cards are loaded and how many of their files are missing.
+#[derive(Debug, Serialize)]
+struct LibraryStatsPayload {
+    cards: usize,
+    missing_tracks: usize,
+    /// Seconds since the Unix epoch.
+    reloaded_at: u64,
+    reloaded_secs_ago: u64,
+}
+
+impl From<LibraryStats> for LibraryStatsPayload {
+    fn from(stats: LibraryStats) -> Self {
+        let secs = |time: SystemTime| {
+            time.duration_since(UNIX_EPOCH)
+                .map_or(0, |elapsed| elapsed.as_secs())
+        };
+        Self {
+            cards: stats.cards,
+            missing_tracks: stats.missing_tracks,
+            reloaded_at: secs(stats.loaded_at),
+            reloaded_secs_ago: SystemTime::now()
+                .duration_since(stats.loaded_at)
+                .map_or(0, |age| age.as_secs()),
+        }
+    }
+}
+
+/// Tap-to-sound latency percentiles over recent taps, in milliseconds.
+#[derive(Debug, Serialize)]
+struct LatencyPayload {
+    samples: usize,
+    decision_p50_ms: Option<u128>,
+    decision_p95_ms: Option<u128>,
+    sound_p50_ms: Option<u128>,
+    sound_p95_ms: Option<u128>,
+}
+
+impl From<LatencySummary> for LatencyPayload {
+    fn from(summary: LatencySummary) -> Self {
+        let ms = |duration: Option<Duration>| duration.map(|duration| duration.as_millis());
+        Self {
+            samples: summary.samples,
+            decision_p50_ms: ms(summary.decision_p50),
+            decision_p95_ms: ms(summary.decision_p95),
+            sound_p50_ms: ms(summary.sound_p50),
+            sound_p95_ms: ms(summary.sound_p95),
+        }
+    }
+}
+
+/// Which optional features the audio and reader backends support, so the
+/// dashboard can hide controls that would only fail.
+#[derive(Debug, Serialize)]
+struct CapabilitiesPayload {
+    seek: bool,
+    volume: bool,
+    track_end: bool,
+    streams: bool,
+    card_removed: bool,
+    ndef: bool,
 }
 
 impl StatusPayload {
-    fn from_snapshot(snapshot: StatusSnapshot, active: Option<(CardUid, Track)>) -> StatusPayload {
+    fn from_snapshot(
+        snapshot: StatusSnapshot,
+        active: Option<(CardUid, Track)>,
+        player: PlayerCapabilities,
+    ) -> StatusPayload {
+        let reader = snapshot.reader_capabilities;
+        let capabilities = CapabilitiesPayload {
+            seek: player.seek,
+            volume: player.volume,
+            track_end: player.track_end,
+            streams: player.streams,
+            card_removed: reader.card_removed,
+            ndef: reader.ndef,
+        };
         let last_action = snapshot.last_action.map(|action| format!("{action:?}"));
         let last_update = snapshot
             .last_update
@@ -530,10 +1564,18 @@ impl StatusPayload {
 
         StatusPayload {
             idle_events: snapshot.idle_events,
+            reader_errors: snapshot.reader_errors,
+            reader_health: snapshot.reader_health.label(),
+            reader_last_error: snapshot.reader_health.last_error().map(str::to_string),
+            display_health: snapshot.display_health.label(),
+            display_error: snapshot.display_health.reason().map(str::to_string),
             last_action,
             last_update,
             active_card,
             active_track,
+            capabilities,
+            tap_latency: snapshot.tap_latency.into(),
+            library: snapshot.library.map(LibraryStatsPayload::from),
         }
     }
 }
@@ -546,6 +1588,7 @@ struct LibraryResponse {
 #[derive(Debug, Serialize)]
 struct LibraryEntry {
     card: String,
+    name: Option<String>,
     track: String,
 }
 
@@ -565,6 +1608,269 @@ struct PlayRequest {
     card_hex: String,
 }
 
+#[derive(Debug, Serialize, Deserialize)]
+struct VolumePayload {
+    volume: f32,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct MutePayload {
+    muted: bool,
+}
+
+#[derive(Debug, Deserialize)]
+struct LearningRequest {
+    learning: bool,
+}
+
+#[derive(Debug, Serialize)]
+struct LearningPayload {
+    learning: bool,
+    /// Hex UIDs, most recent first.
+    cards: Vec<String>,
+}
+
+impl LearningPayload {
+    fn from_controller<P: AudioPlayer>(controller: &MusicBoxController<P>) -> Self {
+        Self {
+            learning: controller.is_learning(),
+            cards: controller
+                .learned_cards()
+                .iter()
+                .map(CardUid::to_hex_lowercase)
+                .collect(),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct SearchRequest {
+    q: String,
+    limit: Option<usize>,
+}
+
+#[derive(Debug, Serialize)]
+struct SearchResponse {
+    query: String,
+    hits: Vec<SearchHitPayload>,
+}
+
+#[derive(Debug, Serialize)]
+struct SearchHitPayload {
+    /// `card` or `file`.
+    kind: &'static str,
+    track: String,
+    card: Option<String>,
+    name: Option<String>,
+    title: Option<String>,
+    artist: Option<String>,
+    album: Option<String>,
+    score: u32,
+}
+
+impl From<SearchHit> for SearchHitPayload {
+    fn from(hit: SearchHit) -> Self {
+        Self {
+            kind: match hit.kind {
+                HitKind::Card => "card",
+                HitKind::File => "file",
+            },
+            track: hit.track.display().to_string(),
+            card: hit.card.as_ref().map(CardUid::to_hex_lowercase),
+            name: hit.name,
+            title: hit.tags.title,
+            artist: hit.tags.artist,
+            album: hit.tags.album,
+            score: hit.score,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct HistoryRequest {
+    limit: Option<usize>,
+    /// Only plays this recent, as a lifetime like `7d`.
+    since: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct HistoryResponse {
+    plays: Vec<PlayPayload>,
+}
+
+#[derive(Debug, Serialize)]
+struct PlayPayload {
+    card: String,
+    name: Option<String>,
+    /// Relative to `music_dir` when it lies inside it.
+    track: String,
+    /// Seconds since the Unix epoch.
+    started: u64,
+    played_ms: u64,
+}
+
+impl PlayPayload {
+    fn new(entry: HistoryEntry, library: &Library) -> Option<Self> {
+        let HistoryEvent::Played {
+            card,
+            track,
+            started,
+            played_ms,
+        } = entry.event
+        else {
+            return None;
+        };
+        let name = CardUid::parse(&card)
+            .ok()
+            .and_then(|uid| library.name(&uid).map(str::to_string));
+        let track = track
+            .strip_prefix(library.music_dir())
+            .unwrap_or(&track)
+            .display()
+            .to_string();
+        Some(Self {
+            card,
+            name,
+            track,
+            started,
+            played_ms,
+        })
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct StatsRequest {
+    /// How many of the most played cards to list.
+    top: Option<usize>,
+    /// Only plays this recent, as a lifetime like `7d`.
+    since: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct StatsResponse {
+    plays: u32,
+    played_ms: u64,
+    cards: Vec<CardTotalPayload>,
+    days: Vec<PeriodPayload>,
+    weeks: Vec<PeriodPayload>,
+    tags: Vec<TagTotalPayload>,
+}
+
+#[derive(Debug, Serialize)]
+struct CardTotalPayload {
+    card: String,
+    name: Option<String>,
+    plays: u32,
+    played_ms: u64,
+}
+
+#[derive(Debug, Serialize)]
+struct TagTotalPayload {
+    card: String,
+    name: Option<String>,
+    seen: u32,
+    counted: u32,
+    missed: u32,
+}
+
+#[derive(Debug, Serialize)]
+struct PeriodPayload {
+    /// The day, or the Monday the week starts on, as `YYYY-MM-DD`.
+    start: String,
+    plays: u32,
+    played_ms: u64,
+}
+
+impl From<&PeriodTotal> for PeriodPayload {
+    fn from(total: &PeriodTotal) -> Self {
+        Self {
+            start: total.start.to_string(),
+            plays: total.plays,
+            played_ms: total.played.as_millis() as u64,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct QueuePayload {
+    /// Hex UID of the card playing.
+    card: Option<String>,
+    /// Index in `tracks` of the track playing.
+    position: Option<usize>,
+    tracks: Vec<String>,
+}
+
+impl QueuePayload {
+    fn from_controller<P: AudioPlayer>(controller: &MusicBoxController<P>) -> Self {
+        let (tracks, position) = controller.queue().unzip();
+        Self {
+            card: controller.active().map(|(card, _)| card.to_hex_lowercase()),
+            position,
+            tracks: tracks
+                .unwrap_or_default()
+                .iter()
+                .map(|track| track.path().display().to_string())
+                .collect(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct FilesResponse {
+    music_dir: String,
+    files: Vec<FilePayload>,
+}
+
+#[derive(Debug, Serialize)]
+struct FilePayload {
+    /// Relative to `music_dir`.
+    path: String,
+    /// The card whose playlist includes the file, if any.
+    card: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct UploadRequest {
+    /// The folder under `music_dir` to save into, created if missing.
+    dir: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct UploadResponse {
+    files: Vec<UploadedPayload>,
+}
+
+#[derive(Debug, Serialize)]
+struct UploadedPayload {
+    /// Relative to `music_dir`, ready to map a card to.
+    path: String,
+    bytes: u64,
+}
+
+#[derive(Debug, Deserialize)]
+struct MappingRequest {
+    /// Hex UID, or the nickname of a mapped card.
+    card: String,
+    /// Relative to `music_dir`.
+    track: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct ValidateTrackRequest {
+    path: String,
+}
+
+#[derive(Debug, Serialize)]
+struct TrackCheckPayload {
+    path: String,
+    ok: bool,
+    /// Audio files that decode.
+    files: usize,
+    /// Total playing time, absent when a file does not record its length.
+    duration_ms: Option<u64>,
+    problems: Vec<String>,
+}
+
 #[derive(Debug, Serialize)]
 struct CommandResponse {
     status: StatusPayload,
@@ -577,26 +1883,82 @@ enum ApiError {
     CardUid(#[from] CardUidParseError),
     #[error("controller error: {0}")]
     Controller(#[from] ControllerError),
+    #[error("card {0} is not mapped to a track")]
+    UnmappedCard(CardUid),
+    #[error("no track file {0:?} under music_dir")]
+    TrackFile(String),
+    #[error("track path {0:?} leads outside music_dir")]
+    TrackPath(String),
     #[error("config validation failed: {0}")]
     InvalidConfig(String),
+    #[error(transparent)]
+    ConfigEdit(#[from] ConfigEditError),
+    #[error("{0:?} is not an audio file musicbox plays")]
+    NotAudio(String),
+    #[error("{0} already exists under music_dir")]
+    UploadExists(String),
+    #[error("{name} is larger than the {limit}-byte upload limit")]
+    UploadTooLarge { name: String, limit: u64 },
+    #[error("uploads are turned off; set [web] max_upload_mb to allow them")]
+    UploadsOff,
+    #[error("upload failed: {0}")]
+    Multipart(#[from] MultipartError),
+    #[error("a valid bearer token or password is required")]
+    Unauthorized,
+    #[error("this token may only control playback")]
+    Forbidden,
+    #[error("the web API is read-only")]
+    ReadOnly,
     #[error("filesystem error: {0}")]
     Io(#[from] std::io::Error),
     #[error("background task failed: {0}")]
     Join(#[from] tokio::task::JoinError),
+    #[error("no history log; start musicbox with --history-log")]
+    NoHistory,
+    #[error(transparent)]
+    History(#[from] HistoryError),
+    #[error(transparent)]
+    Lifetime(#[from] LifetimeParseError),
 }
 
 impl IntoResponse for ApiError {
     fn into_response(self) -> Response {
         let status = match self {
-            ApiError::CardUid(_) | ApiError::InvalidConfig(_) => StatusCode::BAD_REQUEST,
-            ApiError::Controller(ControllerError::TrackNotFound) => StatusCode::NOT_FOUND,
+            ApiError::CardUid(_)
+            | ApiError::InvalidConfig(_)
+            | ApiError::TrackPath(_)
+            | ApiError::Lifetime(_)
+            | ApiError::Multipart(_) => StatusCode::BAD_REQUEST,
+            ApiError::NotAudio(_) => StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            ApiError::UploadTooLarge { .. } => StatusCode::PAYLOAD_TOO_LARGE,
+            ApiError::UploadsOff => StatusCode::FORBIDDEN,
+            ApiError::Unauthorized => StatusCode::UNAUTHORIZED,
+            ApiError::Forbidden | ApiError::ReadOnly => StatusCode::FORBIDDEN,
+            ApiError::Controller(ControllerError::TrackNotFound)
+            | ApiError::UnmappedCard(_)
+            | ApiError::Controller(ControllerError::NotQueued(_))
+            | ApiError::TrackFile(_)
+            | ApiError::NoHistory => StatusCode::NOT_FOUND,
+            ApiError::Controller(ControllerError::QueuedTrackPlaying(_))
+            | ApiError::ConfigEdit(ConfigEditError::NotSingleTrack(_))
+            | ApiError::UploadExists(_) => StatusCode::CONFLICT,
+            ApiError::Controller(ControllerError::CardExpired(_)) => StatusCode::GONE,
+            ApiError::Controller(ControllerError::Unsupported(_)) => StatusCode::NOT_IMPLEMENTED,
             ApiError::Controller(_) => StatusCode::BAD_REQUEST,
-            ApiError::Io(_) | ApiError::Join(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            ApiError::Io(_)
+            | ApiError::Join(_)
+            | ApiError::History(_)
+            | ApiError::ConfigEdit(_) => StatusCode::INTERNAL_SERVER_ERROR,
         };
 
         let body = Json(ErrorResponse {
             error: self.to_string(),
         });
+        if status == StatusCode::UNAUTHORIZED {
+            // Lets a browser opened on the dashboard ask for a password.
+            let challenge = [(header::WWW_AUTHENTICATE, "Basic realm=\"musicbox\"")];
+            return (status, challenge, body).into_response();
+        }
         (status, body).into_response()
     }
 }
@@ -609,7 +1971,518 @@ struct ErrorResponse {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::controller::ControllerAction;
+    use crate::access::{ApiToken, ApiTokens, TokenScope, WebSettings};
+    use crate::controller::{ControllerAction, Library, PlayerError};
+    use std::collections::HashMap;
+    use std::io::{Read, Write};
+
+    #[derive(Default)]
+    struct VolumePlayer {
+        volume: f32,
+    }
+
+    impl AudioPlayer for VolumePlayer {
+        fn capabilities(&self) -> PlayerCapabilities {
+            PlayerCapabilities {
+                volume: true,
+                ..PlayerCapabilities::default()
+            }
+        }
+        fn play(&mut self, _track: &Track) -> Result<(), PlayerError> {
+            Ok(())
+        }
+        fn stop(&mut self) -> Result<(), PlayerError> {
+            Ok(())
+        }
+        fn pause(&mut self) -> Result<(), PlayerError> {
+            Ok(())
+        }
+        fn resume(&mut self) -> Result<(), PlayerError> {
+            Ok(())
+        }
+        fn set_volume(&mut self, volume: f32) -> Result<(), PlayerError> {
+            self.volume = volume;
+            Ok(())
+        }
+        fn volume(&self) -> f32 {
+            self.volume
+        }
+    }
+
+    /// Serves the router on a loopback port for the rest of the test run.
+    fn serve_in_background(library: Library) -> SocketAddr {
+        serve_with_history(library, None)
+    }
+
+    fn serve_with_history(library: Library, history: Option<HistoryLog>) -> SocketAddr {
+        let state = DebugState {
+            status: SharedStatus::default(),
+            events: EventBus::new(),
+            controller: Arc::new(Mutex::new(MusicBoxController::new(
+                library,
+                VolumePlayer::default(),
+            ))),
+            config_path: PathBuf::from("/nonexistent/musicbox.toml"),
+            history,
+            read_only: false,
+        };
+        serve_state(state)
+    }
+
+    fn serve_state(state: DebugState<VolumePlayer>) -> SocketAddr {
+        let rt = tokio::runtime::Builder::new_multi_thread()
+            .worker_threads(1)
+            .enable_all()
+            .build()
+            .unwrap();
+        let listener = rt
+            .block_on(tokio::net::TcpListener::bind("127.0.0.1:0"))
+            .unwrap();
+        let addr = listener.local_addr().unwrap();
+        std::thread::spawn(move || {
+            rt.block_on(async move {
+                axum::serve(listener, build_router(state).into_make_service()).await
+            })
+        });
+        addr
+    }
+
+    /// Sends one request and returns the status code and body.
+    fn request(
+        addr: SocketAddr,
+        method: &str,
+        path: &str,
+        token: Option<&str>,
+        body: &str,
+    ) -> (u16, String) {
+        send(
+            addr,
+            method,
+            path,
+            token,
+            "application/json",
+            body.as_bytes(),
+        )
+    }
+
+    /// Sends one request with a body of any type.
+    fn send(
+        addr: SocketAddr,
+        method: &str,
+        path: &str,
+        token: Option<&str>,
+        content_type: &str,
+        body: &[u8],
+    ) -> (u16, String) {
+        let mut stream = std::net::TcpStream::connect(addr).unwrap();
+        let auth = token.map_or(String::new(), |token| {
+            format!("Authorization: Bearer {token}\r\n")
+        });
+        write!(
+            stream,
+            "{method} {path} HTTP/1.1\r\nHost: test\r\n{auth}Content-Type: {content_type}\r\n\
+             Content-Length: {}\r\nConnection: close\r\n\r\n",
+            body.len()
+        )
+        .unwrap();
+        stream.write_all(body).unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        let status = response[9..12].parse().unwrap();
+        let body = response
+            .split_once("\r\n\r\n")
+            .map_or(String::new(), |(_, body)| body.to_string());
+        (status, body)
+    }
+
+    #[test]
+    fn read_only_api_answers_reads_and_refuses_changes() {
+        let library = Library::new(HashMap::from([(
+            CardUid::new(vec![0x0a]),
+            Track::new(PathBuf::from("/music/a.mp3")),
+        )]));
+
+        // Set in the config's [web] table.
+        let addr = serve_in_background(library.clone().with_web_settings(WebSettings {
+            read_only: true,
+            ..WebSettings::default()
+        }));
+        let (status, body) = request(addr, "GET", "/api/status", None, "");
+        assert_eq!(status, 200);
+        assert!(body.contains(r#""library":{"cards":1,"missing_tracks":1,"#));
+        assert_eq!(request(addr, "GET", "/api/library", None, "").0, 200);
+        let (status, body) = request(addr, "POST", "/api/play", None, r#"{"card_hex": "0a"}"#);
+        assert_eq!(
+            (status, body.as_str()),
+            (403, r#"{"error":"the web API is read-only"}"#)
+        );
+        assert_eq!(request(addr, "DELETE", "/api/queue/0", None, "").0, 403);
+
+        // Set with --debug-http-readonly.
+        let addr = serve_state(DebugState {
+            status: SharedStatus::default(),
+            events: EventBus::new(),
+            controller: Arc::new(Mutex::new(MusicBoxController::new(
+                library,
+                VolumePlayer::default(),
+            ))),
+            config_path: PathBuf::from("/nonexistent/musicbox.toml"),
+            history: None,
+            read_only: true,
+        });
+        assert_eq!(request(addr, "PUT", "/api/config", None, "").0, 403);
+        assert_eq!(request(addr, "POST", "/api/volume", None, "{}").0, 403);
+    }
+
+    #[test]
+    fn guest_tokens_only_control_playback_within_their_volume_cap() {
+        let library = Library::default().with_api_tokens(ApiTokens::new(vec![
+            ApiToken {
+                name: "parents".to_string(),
+                token: "parents-0123456789".to_string(),
+                scope: TokenScope::Full,
+                max_volume: None,
+            },
+            ApiToken {
+                name: "babysitter".to_string(),
+                token: "sitter-0123456789".to_string(),
+                scope: TokenScope::Guest,
+                max_volume: Some(0.4),
+            },
+        ]));
+        let addr = serve_in_background(library);
+        let guest = Some("sitter-0123456789");
+        let full = Some("parents-0123456789");
+
+        assert_eq!(request(addr, "GET", "/api/status", None, "").0, 401);
+        assert_eq!(
+            request(addr, "GET", "/api/status", Some("wrong"), "").0,
+            401
+        );
+        assert_eq!(request(addr, "GET", "/", None, "").0, 200);
+        assert_eq!(request(addr, "GET", "/api/status", guest, "").0, 200);
+        assert_eq!(request(addr, "POST", "/api/pause", guest, "").0, 200);
+
+        let (status, body) = request(addr, "POST", "/api/volume", guest, r#"{"volume": 0.9}"#);
+        assert_eq!(status, 200);
+        assert_eq!(body, r#"{"volume":0.4}"#);
+        let (_, body) = request(addr, "POST", "/api/volume", full, r#"{"volume": 0.9}"#);
+        assert_eq!(body, r#"{"volume":0.9}"#);
+
+        assert_eq!(request(addr, "GET", "/api/config", guest, "").0, 403);
+        assert_eq!(
+            request(addr, "PUT", "/api/config", guest, r#"{"contents": ""}"#).0,
+            403
+        );
+        assert_eq!(request(addr, "GET", "/api/tracks/a.mp3", guest, "").0, 403);
+        assert_eq!(request(addr, "GET", "/api/tracks/a.mp3", full, "").0, 404);
+    }
+
+    #[test]
+    fn validate_track_reports_problems_before_a_card_is_mapped() {
+        let dir = tempfile::tempdir().unwrap();
+        crate::memo::Clip {
+            sample_rate: 8_000,
+            channels: 1,
+            samples: vec![0; 8_000 * 2],
+        }
+        .write_wav(&dir.path().join("tone.wav"))
+        .unwrap();
+        std::fs::write(dir.path().join("noise.mp3"), b"not audio").unwrap();
+        let addr = serve_in_background(Library::default().with_music_dir(dir.path().into()));
+        let validate = |path: &str| {
+            let body = format!(r#"{{"path": "{path}"}}"#);
+            request(addr, "POST", "/api/validate-track", None, &body)
+        };
+
+        let (status, body) = validate("tone.wav");
+        assert_eq!(status, 200);
+        assert_eq!(
+            body,
+            r#"{"path":"tone.wav","ok":true,"files":1,"duration_ms":2000,"problems":[]}"#
+        );
+        let (status, body) = validate("noise.mp3");
+        assert_eq!(status, 200);
+        assert!(body.contains(r#""ok":false"#), "{body}");
+        assert!(body.contains("unreadable audio file"), "{body}");
+        assert!(validate("missing.wav").1.contains(r#""ok":false"#));
+        assert_eq!(validate("../etc/passwd").0, 400);
+    }
+
+    #[test]
+    fn files_list_music_and_mappings_point_cards_at_them() {
+        let dir = tempfile::tempdir().unwrap();
+        let music = dir.path().join("music");
+        std::fs::create_dir_all(music.join("stories")).unwrap();
+        for name in ["a.mp3", "stories/b.ogg", "notes.txt"] {
+            std::fs::write(music.join(name), b"not audio").unwrap();
+        }
+        let config_path = dir.path().join("musicbox.toml");
+        let contents = format!(
+            "music_dir = {:?}\n\n[cards]\n\"0a\" = \"a.mp3\" # kitchen\n\"0c\" = [\"a.mp3\", \"stories/b.ogg\"]\n",
+            music.display().to_string()
+        );
+        std::fs::write(&config_path, contents).unwrap();
+        let contents = std::fs::read(&config_path).unwrap();
+        let library = MusicBoxConfig::from_reader_in(contents.as_slice(), dir.path())
+            .unwrap()
+            .into_library();
+        let addr = serve_state(DebugState {
+            status: SharedStatus::default(),
+            events: EventBus::new(),
+            controller: Arc::new(Mutex::new(MusicBoxController::new(
+                library,
+                VolumePlayer::default(),
+            ))),
+            config_path: config_path.clone(),
+            history: None,
+            read_only: false,
+        });
+        let map = |card: &str, track: &str| {
+            let body = format!(r#"{{"card": "{card}", "track": "{track}"}}"#);
+            request(addr, "POST", "/api/mappings", None, &body)
+        };
+
+        let (status, body) = request(addr, "GET", "/api/files", None, "");
+        assert_eq!(status, 200);
+        assert!(
+            body.ends_with(
+                r#""files":[{"path":"a.mp3","card":"0a"},{"path":"stories/b.ogg","card":"0c"}]}"#
+            ),
+            "{body}"
+        );
+
+        let (status, body) = map("0B", "stories/b.ogg");
+        assert_eq!(status, 200, "{body}");
+        assert!(
+            body.starts_with(r#"{"card":"0b","name":null,"track":""#),
+            "{body}"
+        );
+        let (status, _) = map("0a", "stories/b.ogg");
+        assert_eq!(status, 200);
+        let written = std::fs::read_to_string(&config_path).unwrap();
+        assert!(
+            written.contains(r#""0a" = "stories/b.ogg" # kitchen"#),
+            "{written}"
+        );
+        assert!(written.contains(r#"0b = "stories/b.ogg""#), "{written}");
+        let (_, body) = request(addr, "GET", "/api/cards/0b", None, "");
+        assert!(body.ends_with(r#"b.ogg"}"#), "{body}");
+
+        assert_eq!(map("0c", "a.mp3").0, 409);
+        assert_eq!(map("0d", "missing.mp3").0, 404);
+        assert_eq!(map("0d", "../musicbox.toml").0, 400);
+        assert_eq!(map("not a card", "a.mp3").0, 400);
+    }
+
+    #[test]
+    fn queue_lists_the_playing_card_and_drops_entries() {
+        let card = CardUid::new(vec![0x0a]);
+        let tracks = ["01.mp3", "02.mp3", "03.mp3"]
+            .into_iter()
+            .map(|name| Track::new(PathBuf::from(name)))
+            .collect();
+        let addr = serve_in_background(Library::from_playlists(HashMap::from([(card, tracks)])));
+
+        let (status, body) = request(addr, "GET", "/api/queue", None, "");
+        assert_eq!(status, 200);
+        assert_eq!(body, r#"{"card":null,"position":null,"tracks":[]}"#);
+        assert_eq!(request(addr, "DELETE", "/api/queue/1", None, "").0, 404);
+
+        request(addr, "POST", "/api/play", None, r#"{"card_hex": "0a"}"#);
+        assert_eq!(request(addr, "DELETE", "/api/queue/0", None, "").0, 409);
+        assert_eq!(request(addr, "DELETE", "/api/queue/3", None, "").0, 404);
+        let (status, body) = request(addr, "DELETE", "/api/queue/1", None, "");
+        assert_eq!(status, 200);
+        assert_eq!(
+            body,
+            r#"{"card":"0a","position":0,"tracks":["01.mp3","03.mp3"]}"#
+        );
+    }
+
+    #[test]
+    fn history_lists_recent_plays_with_card_names() {
+        let dir = tempfile::tempdir().unwrap();
+        let log = HistoryLog::new(dir.path().join("history.jsonl"));
+        for (track, played_ms) in [("/music/a.mp3", 1_000), ("/music/b.mp3", 2_000)] {
+            log.append(&HistoryEvent::Played {
+                card: "0a".to_string(),
+                track: PathBuf::from(track),
+                started: 1_700_000_000,
+                played_ms,
+            })
+            .unwrap();
+        }
+        let card = CardUid::new(vec![0x0a]);
+        let library = Library::new(HashMap::from([(
+            card.clone(),
+            Track::new(PathBuf::from("/music/a.mp3")),
+        )]))
+        .with_names(HashMap::from([(card, "Bedtime".to_string())]))
+        .with_music_dir(PathBuf::from("/music"));
+
+        let addr = serve_with_history(library.clone(), Some(log));
+        let (status, body) = request(addr, "GET", "/api/history?limit=1", None, "");
+        assert_eq!(status, 200);
+        assert_eq!(
+            body,
+            r#"{"plays":[{"card":"0a","name":"Bedtime","track":"b.mp3","started":1700000000,"played_ms":2000}]}"#
+        );
+        let (status, body) = request(addr, "GET", "/api/history?since=1d", None, "");
+        assert_eq!((status, body.as_str()), (200, r#"{"plays":[]}"#));
+        assert_eq!(
+            request(addr, "GET", "/api/history?since=soon", None, "").0,
+            400
+        );
+
+        let addr = serve_in_background(library);
+        assert_eq!(request(addr, "GET", "/api/history", None, "").0, 404);
+    }
+
+    /// Posts `files`, as names and contents, to `/api/upload` as a browser
+    /// form would.
+    fn upload(addr: SocketAddr, path: &str, files: &[(&str, &[u8])]) -> (u16, String) {
+        let mut body = Vec::new();
+        for (name, contents) in files {
+            write!(
+                body,
+                "--b0undary\r\nContent-Disposition: form-data; name=\"file\"; \
+                 filename=\"{name}\"\r\nContent-Type: audio/mpeg\r\n\r\n"
+            )
+            .unwrap();
+            body.extend_from_slice(contents);
+            body.extend_from_slice(b"\r\n");
+        }
+        body.extend_from_slice(b"--b0undary--\r\n");
+        let content_type = "multipart/form-data; boundary=b0undary";
+        send(addr, "POST", path, None, content_type, &body)
+    }
+
+    #[test]
+    fn uploads_save_audio_files_under_music_dir() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("taken.mp3"), b"old").unwrap();
+        let library = Library::default()
+            .with_music_dir(dir.path().into())
+            .with_web_settings(WebSettings {
+                max_upload_bytes: 8,
+                ..WebSettings::default()
+            });
+        let addr = serve_in_background(library);
+
+        let (status, body) = upload(
+            addr,
+            "/api/upload?dir=stories/new",
+            &[("one.mp3", b"12345"), ("Two.OGG", b"678")],
+        );
+        assert_eq!(status, 200, "{body}");
+        assert_eq!(
+            body,
+            r#"{"files":[{"path":"stories/new/one.mp3","bytes":5},{"path":"stories/new/Two.OGG","bytes":3}]}"#
+        );
+        let saved = dir.path().join("stories/new");
+        assert_eq!(std::fs::read(saved.join("one.mp3")).unwrap(), b"12345");
+        assert_eq!(std::fs::read_dir(&saved).unwrap().count(), 2);
+
+        let (status, body) = upload(addr, "/api/upload", &[("big.mp3", b"123456789")]);
+        assert_eq!(status, 413, "{body}");
+        assert_eq!(
+            std::fs::read_dir(dir.path()).unwrap().count(),
+            2,
+            "no leftovers"
+        );
+        assert_eq!(upload(addr, "/api/upload", &[("taken.mp3", b"new")]).0, 409);
+        assert_eq!(std::fs::read(dir.path().join("taken.mp3")).unwrap(), b"old");
+        assert_eq!(upload(addr, "/api/upload", &[("notes.txt", b"hi")]).0, 415);
+        assert_eq!(upload(addr, "/api/upload", &[("../up.mp3", b"hi")]).0, 400);
+        assert_eq!(
+            upload(addr, "/api/upload?dir=..", &[("up.mp3", b"hi")]).0,
+            400
+        );
+    }
+
+    #[test]
+    fn stats_rank_cards_and_total_each_day() {
+        let dir = tempfile::tempdir().unwrap();
+        let log = HistoryLog::new(dir.path().join("history.jsonl"));
+        // Noon UTC on 2023-11-14, a Tuesday.
+        for (card, played_ms) in [("0a", 60_000), ("0b", 30_000), ("0a", 60_000)] {
+            log.append(&HistoryEvent::Played {
+                card: card.to_string(),
+                track: PathBuf::from("/music/a.mp3"),
+                started: 1_699_963_200,
+                played_ms,
+            })
+            .unwrap();
+        }
+        for count in [5, 8] {
+            log.append(&HistoryEvent::TagCounter {
+                card: "0a".to_string(),
+                count,
+            })
+            .unwrap();
+        }
+        let card = CardUid::new(vec![0x0a]);
+        let library = Library::new(HashMap::from([(
+            card.clone(),
+            Track::new(PathBuf::from("/music/a.mp3")),
+        )]))
+        .with_names(HashMap::from([(card, "Bedtime".to_string())]));
+
+        let addr = serve_with_history(library, Some(log));
+        let (status, body) = request(addr, "GET", "/api/stats?top=1", None, "");
+        assert_eq!(status, 200);
+        assert_eq!(
+            body,
+            r#"{"plays":3,"played_ms":150000,"cards":[{"card":"0a","name":"Bedtime","plays":2,"played_ms":120000}],"days":[{"start":"2023-11-14","plays":3,"played_ms":150000}],"weeks":[{"start":"2023-11-13","plays":3,"played_ms":150000}],"tags":[{"card":"0a","name":"Bedtime","seen":2,"counted":4,"missed":2}]}"#
+        );
+        let (status, body) = request(addr, "GET", "/api/stats?since=1d", None, "");
+        assert_eq!(
+            (status, body.as_str()),
+            (
+                200,
+                r#"{"plays":0,"played_ms":0,"cards":[],"days":[],"weeks":[],"tags":[{"card":"0a","name":"Bedtime","seen":2,"counted":4,"missed":2}]}"#
+            )
+        );
+    }
+
+    #[test]
+    fn search_finds_cards_and_files_by_fuzzy_title() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("gruffalo.mp3"), b"not audio").unwrap();
+        std::fs::write(dir.path().join("lullaby.mp3"), b"not audio").unwrap();
+        let card = CardUid::new(vec![0x0a]);
+        let library = Library::new(HashMap::from([(
+            card.clone(),
+            Track::new(dir.path().join("gruffalo.mp3")),
+        )]))
+        .with_names(HashMap::from([(card, "Forest".to_string())]))
+        .with_music_dir(dir.path().into());
+        let addr = serve_in_background(library);
+
+        let (status, body) = request(addr, "GET", "/api/search?q=grufalo", None, "");
+        assert_eq!(status, 200);
+        let response: serde_json::Value = serde_json::from_str(&body).unwrap();
+        let hits = response["hits"].as_array().unwrap();
+        assert_eq!(hits.len(), 2);
+        assert_eq!(hits[0]["kind"], "card");
+        assert_eq!(hits[0]["name"], "Forest");
+        assert_eq!(hits[1]["kind"], "file");
+        assert_eq!(hits[1]["track"], "gruffalo.mp3");
+        assert_eq!(hits[1]["card"], "0a");
+
+        let (_, body) = request(addr, "GET", "/api/search?q=lullaby&limit=5", None, "");
+        assert!(body.contains(r#""card":null"#), "{body}");
+        assert_eq!(request(addr, "GET", "/api/search", None, "").0, 400);
+    }
+
+    #[test]
+    fn api_without_tokens_stays_open() {
+        let addr = serve_in_background(Library::default());
+        assert_eq!(request(addr, "GET", "/api/status", None, "").0, 200);
+        let (_, body) = request(addr, "POST", "/api/volume", None, r#"{"volume": 0.9}"#);
+        assert_eq!(body, r#"{"volume":0.9}"#);
+    }
 
     #[test]
     fn status_payload_formats_fields() {
@@ -620,6 +2493,12 @@ mod tests {
                 track: Track::new("track.mp3".into()),
             }),
             last_update: Some(UNIX_EPOCH + std::time::Duration::from_secs(42)),
+            library: Some(LibraryStats {
+                cards: 142,
+                missing_tracks: 3,
+                loaded_at: SystemTime::now() - std::time::Duration::from_secs(120),
+            }),
+            ..StatusSnapshot::default()
         };
 
         let payload = StatusPayload::from_snapshot(
@@ -628,12 +2507,25 @@ mod tests {
                 CardUid::new(vec![0xca, 0xfe]),
                 Track::new("other.mp3".into()),
             )),
+            PlayerCapabilities {
+                volume: true,
+                ..PlayerCapabilities::default()
+            },
         );
 
         assert_eq!(payload.idle_events, 5);
+        assert_eq!(payload.reader_health, "healthy");
+        assert_eq!(payload.display_health, "absent");
         assert!(payload.last_action.as_ref().unwrap().contains("Started"));
         assert_eq!(payload.last_update.as_deref(), Some("42"));
         assert_eq!(payload.active_card.as_deref(), Some("cafe"));
         assert_eq!(payload.active_track.as_deref(), Some("other.mp3"));
+        assert!(payload.capabilities.volume);
+        assert!(!payload.capabilities.seek && !payload.capabilities.ndef);
+        assert_eq!(payload.tap_latency.samples, 0);
+        assert_eq!(payload.tap_latency.sound_p95_ms, None);
+        let library = payload.library.unwrap();
+        assert_eq!((library.cards, library.missing_tracks), (142, 3));
+        assert!((120..125).contains(&library.reloaded_secs_ago));
     }
 }
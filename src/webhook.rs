@@ -0,0 +1,521 @@
+//! Minimal JSON webhooks for operator alerts and for talking to a running
+//! instance's HTTP API.
+//!
+//! Requests are a hand-rolled HTTP/1.1 exchange over `std::net`, which keeps
+//! us from pulling in an async client just to send a few bytes. `https://`
+//! URLs go through rustls behind the `https` feature, trusting the system's
+//! root certificates; without it they are rejected when the config loads.
+
+use serde::Serialize;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{SocketAddr, TcpStream, ToSocketAddrs};
+use std::time::Duration;
+
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Redirects [`get`] follows before giving up; feed and download links often
+/// bounce through a tracking host or two.
+const MAX_REDIRECTS: usize = 5;
+
+#[derive(Debug, thiserror::Error)]
+pub enum WebhookError {
+    #[error("unsupported url {0:?}; use http:// or https://")]
+    UnsupportedUrl(String),
+    #[error("{0:?} needs https support; build with the https feature")]
+    HttpsUnavailable(String),
+    #[error("failed to encode webhook payload: {0}")]
+    Encode(#[from] serde_json::Error),
+    #[error("webhook request failed: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("webhook responded with status {0}")]
+    Status(u16),
+    #[error("gave up after {MAX_REDIRECTS} redirects")]
+    TooManyRedirects,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WebhookUrl {
+    host: String,
+    port: u16,
+    path: String,
+    /// Whether the URL is `https://`.
+    tls: bool,
+}
+
+impl WebhookUrl {
+    pub fn parse(url: &str) -> Result<Self, WebhookError> {
+        let unsupported = || WebhookError::UnsupportedUrl(url.to_string());
+        let (rest, tls, default_port) = match url.strip_prefix("https://") {
+            Some(rest) if cfg!(feature = "https") => (rest, true, 443),
+            Some(_) => return Err(WebhookError::HttpsUnavailable(url.to_string())),
+            None => (
+                url.strip_prefix("http://").ok_or_else(unsupported)?,
+                false,
+                80,
+            ),
+        };
+        let (authority, path) = match rest.find('/') {
+            Some(index) => (&rest[..index], &rest[index..]),
+            None => (rest, "/"),
+        };
+        let (host, port) = match authority.rsplit_once(':') {
+            Some((host, port)) => (host, port.parse().map_err(|_| unsupported())?),
+            None => (authority, default_port),
+        };
+        if host.is_empty() {
+            return Err(unsupported());
+        }
+        Ok(Self {
+            host: host.to_string(),
+            port,
+            path: path.to_string(),
+            tls,
+        })
+    }
+
+    /// The same host and port with `path` appended to this URL's path.
+    pub fn join(&self, path: &str) -> Self {
+        Self {
+            path: format!("{}{path}", self.path.trim_end_matches('/')),
+            ..self.clone()
+        }
+    }
+
+    /// Where a `Location` header points: an absolute URL, or a path on this
+    /// host.
+    fn redirect(&self, location: &str) -> Result<Self, WebhookError> {
+        if location.starts_with('/') {
+            Ok(Self {
+                path: location.to_string(),
+                ..self.clone()
+            })
+        } else {
+            Self::parse(location)
+        }
+    }
+
+    pub fn from_socket_addr(addr: SocketAddr, path: &str) -> Self {
+        Self {
+            host: addr.ip().to_string(),
+            port: addr.port(),
+            path: path.to_string(),
+            tls: false,
+        }
+    }
+}
+
+/// Status and body read back from an endpoint.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WebhookResponse {
+    pub status: u16,
+    pub body: String,
+}
+
+impl WebhookResponse {
+    pub fn is_success(&self) -> bool {
+        (200..300).contains(&self.status)
+    }
+}
+
+/// Posts `payload` as JSON and fails unless the endpoint answers with 2xx.
+pub fn post_json<T: Serialize>(url: &WebhookUrl, payload: &T) -> Result<(), WebhookError> {
+    let response = send_json(url, payload)?;
+    if response.is_success() {
+        Ok(())
+    } else {
+        Err(WebhookError::Status(response.status))
+    }
+}
+
+/// Posts `payload` as JSON and returns whatever the endpoint answered.
+pub fn send_json<T: Serialize>(
+    url: &WebhookUrl,
+    payload: &T,
+) -> Result<WebhookResponse, WebhookError> {
+    let body = serde_json::to_vec(payload)?;
+    let (response, _) = exchange(url, "POST", Some(("application/json", &body)))?;
+    Ok(WebhookResponse {
+        status: response.status,
+        body: String::from_utf8_lossy(&response.body).into_owned(),
+    })
+}
+
+/// Status and raw body bytes of a `GET`, for downloads that are not text.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RawResponse {
+    pub status: u16,
+    pub body: Vec<u8>,
+}
+
+/// Fetches `url`, following redirects, and returns whatever the final
+/// endpoint answered.
+pub fn get(url: &WebhookUrl) -> Result<RawResponse, WebhookError> {
+    let mut url = url.clone();
+    for _ in 0..=MAX_REDIRECTS {
+        let (response, location) = exchange(&url, "GET", None)?;
+        match location.filter(|_| (300..400).contains(&response.status)) {
+            Some(location) => url = url.redirect(&location)?,
+            None => return Ok(response),
+        }
+    }
+    Err(WebhookError::TooManyRedirects)
+}
+
+/// A `GET` whose body is still arriving: a radio stream that never ends, or
+/// a download too large to hold in memory.
+#[derive(Debug)]
+pub struct OpenResponse {
+    pub status: u16,
+    pub content_type: Option<String>,
+    pub body: BufReader<Connection>,
+}
+
+/// Like [`get`], but returns once the headers are in and leaves the body on
+/// the socket. Reads time out after `timeout`, so a stalled stream surfaces
+/// as an error rather than blocking forever.
+pub fn open(url: &WebhookUrl, timeout: Duration) -> Result<OpenResponse, WebhookError> {
+    let mut url = url.clone();
+    for _ in 0..=MAX_REDIRECTS {
+        // HTTP/1.0 keeps servers from answering with a chunked body.
+        let stream = send_request(&url, "GET", "HTTP/1.0", None)?;
+        stream.socket().set_read_timeout(Some(timeout))?;
+        let mut body = BufReader::new(stream);
+        let mut head = String::new();
+        loop {
+            let mut line = String::new();
+            if body.read_line(&mut line)? == 0 || line.trim_end().is_empty() {
+                break;
+            }
+            head.push_str(&line);
+        }
+        let status = parse_status(&head).unwrap_or(0);
+        match header(&head, "location").filter(|_| (300..400).contains(&status)) {
+            Some(location) => url = url.redirect(location)?,
+            None => {
+                return Ok(OpenResponse {
+                    status,
+                    content_type: header(&head, "content-type").map(str::to_string),
+                    body,
+                });
+            }
+        }
+    }
+    Err(WebhookError::TooManyRedirects)
+}
+
+/// A connection to an endpoint, encrypted when its URL is `https://`.
+#[derive(Debug)]
+pub enum Connection {
+    Plain(TcpStream),
+    #[cfg(feature = "https")]
+    Tls(Box<rustls::StreamOwned<rustls::ClientConnection, TcpStream>>),
+}
+
+impl Connection {
+    /// Connects to `url`'s host, completing the TLS handshake on first use.
+    fn open(url: &WebhookUrl) -> Result<Self, WebhookError> {
+        let addr = (url.host.as_str(), url.port)
+            .to_socket_addrs()?
+            .next()
+            .ok_or_else(|| io::Error::other(format!("no address for {}", url.host)))?;
+        let socket = TcpStream::connect_timeout(&addr, TIMEOUT)?;
+        socket.set_read_timeout(Some(TIMEOUT))?;
+        socket.set_write_timeout(Some(TIMEOUT))?;
+        #[cfg(feature = "https")]
+        if url.tls {
+            let name = rustls::pki_types::ServerName::try_from(url.host.clone())
+                .map_err(|_| WebhookError::UnsupportedUrl(url.host.clone()))?;
+            let session =
+                rustls::ClientConnection::new(tls_config(), name).map_err(io::Error::other)?;
+            return Ok(Self::Tls(Box::new(rustls::StreamOwned::new(
+                session, socket,
+            ))));
+        }
+        Ok(Self::Plain(socket))
+    }
+
+    /// The TCP socket underneath, for setting timeouts.
+    pub fn socket(&self) -> &TcpStream {
+        match self {
+            Self::Plain(socket) => socket,
+            #[cfg(feature = "https")]
+            Self::Tls(stream) => stream.get_ref(),
+        }
+    }
+}
+
+impl Read for Connection {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            Self::Plain(socket) => socket.read(buf),
+            // Plenty of servers hang up without a TLS close_notify once
+            // `Connection: close` has done its job; that is the end of the
+            // body, not an error.
+            #[cfg(feature = "https")]
+            Self::Tls(stream) => match stream.read(buf) {
+                Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => Ok(0),
+                read => read,
+            },
+        }
+    }
+}
+
+impl Write for Connection {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Self::Plain(socket) => socket.write(buf),
+            #[cfg(feature = "https")]
+            Self::Tls(stream) => stream.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Self::Plain(socket) => socket.flush(),
+            #[cfg(feature = "https")]
+            Self::Tls(stream) => stream.flush(),
+        }
+    }
+}
+
+/// The client settings every `https://` request shares, trusting the
+/// system's root certificates.
+#[cfg(feature = "https")]
+fn tls_config() -> std::sync::Arc<rustls::ClientConfig> {
+    static CONFIG: std::sync::OnceLock<std::sync::Arc<rustls::ClientConfig>> =
+        std::sync::OnceLock::new();
+    CONFIG
+        .get_or_init(|| {
+            std::sync::Arc::new(
+                rustls::ClientConfig::builder()
+                    .with_root_certificates(crate::tls::native_roots())
+                    .with_no_client_auth(),
+            )
+        })
+        .clone()
+}
+
+/// Connects to `url` and writes one request, leaving the response unread.
+fn send_request(
+    url: &WebhookUrl,
+    method: &str,
+    version: &str,
+    body: Option<(&str, &[u8])>,
+) -> Result<Connection, WebhookError> {
+    let mut request = format!("{method} {} {version}\r\nHost: {}\r\n", url.path, url.host);
+    if let Some((content_type, body)) = body {
+        request.push_str(&format!(
+            "Content-Type: {content_type}\r\nContent-Length: {}\r\n",
+            body.len()
+        ));
+    }
+    request.push_str("Connection: close\r\n\r\n");
+    let mut request = request.into_bytes();
+    if let Some((_, body)) = body {
+        request.extend_from_slice(body);
+    }
+
+    let mut stream = Connection::open(url)?;
+    stream.write_all(&request)?;
+    stream.flush()?;
+    Ok(stream)
+}
+
+/// Sends one request and returns the response along with its `Location`
+/// header, if any.
+fn exchange(
+    url: &WebhookUrl,
+    method: &str,
+    body: Option<(&str, &[u8])>,
+) -> Result<(RawResponse, Option<String>), WebhookError> {
+    let mut stream = send_request(url, method, "HTTP/1.1", body)?;
+    let mut raw = Vec::new();
+    stream.read_to_end(&mut raw)?;
+    let split = raw
+        .windows(4)
+        .position(|window| window == b"\r\n\r\n")
+        .unwrap_or(raw.len());
+    let head = String::from_utf8_lossy(&raw[..split]);
+    let mut body = raw.get(split + 4..).unwrap_or_default().to_vec();
+    if header(&head, "transfer-encoding").is_some_and(|value| value.contains("chunked")) {
+        body = decode_chunked(&body)?;
+    }
+    let response = RawResponse {
+        status: parse_status(&head).unwrap_or(0),
+        body,
+    };
+    Ok((response, header(&head, "location").map(str::to_string)))
+}
+
+fn parse_status(head: &str) -> Option<u16> {
+    head.split_whitespace().nth(1)?.parse().ok()
+}
+
+/// The value of header `name` in a response head.
+fn header<'a>(head: &'a str, name: &str) -> Option<&'a str> {
+    head.lines().skip(1).find_map(|line| {
+        let (key, value) = line.split_once(':')?;
+        key.trim()
+            .eq_ignore_ascii_case(name)
+            .then_some(value.trim())
+    })
+}
+
+/// Reassembles a `Transfer-Encoding: chunked` body.
+fn decode_chunked(mut raw: &[u8]) -> Result<Vec<u8>, WebhookError> {
+    let malformed = || io::Error::new(io::ErrorKind::InvalidData, "malformed chunked body");
+    let mut body = Vec::new();
+    loop {
+        let line_end = raw
+            .windows(2)
+            .position(|window| window == b"\r\n")
+            .ok_or_else(malformed)?;
+        let line = String::from_utf8_lossy(&raw[..line_end]);
+        let size = line.split(';').next().unwrap_or_default().trim();
+        let size = usize::from_str_radix(size, 16).map_err(|_| malformed())?;
+        if size == 0 {
+            return Ok(body);
+        }
+        let chunk = raw
+            .get(line_end + 2..line_end + 2 + size)
+            .ok_or_else(malformed)?;
+        body.extend_from_slice(chunk);
+        raw = raw.get(line_end + 4 + size..).unwrap_or_default();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_http::{Request, TestServer, ok};
+
+    #[test]
+    fn parses_http_urls() {
+        let url = WebhookUrl::parse("http://hass.local:8123/api/webhook/musicbox").unwrap();
+        assert_eq!(url.host, "hass.local");
+        assert_eq!(url.port, 8123);
+        assert_eq!(url.path, "/api/webhook/musicbox");
+
+        let url = WebhookUrl::parse("http://example").unwrap();
+        assert_eq!(url.port, 80);
+        assert_eq!(url.path, "/");
+    }
+
+    #[test]
+    fn rejects_unsupported_urls() {
+        assert!(WebhookUrl::parse("ftp://example.com/").is_err());
+        assert!(WebhookUrl::parse("http://:80/").is_err());
+        assert!(WebhookUrl::parse("http://host:port/").is_err());
+    }
+
+    #[cfg(feature = "https")]
+    #[test]
+    fn parses_https_urls_and_keeps_the_scheme_across_redirects() {
+        let url = WebhookUrl::parse("https://api.pushover.net/1/messages.json").unwrap();
+        assert!(url.tls);
+        assert_eq!(url.port, 443);
+        assert!(url.redirect("/elsewhere").unwrap().tls);
+        assert!(url.join("/more").tls);
+        assert!(!url.redirect("http://plain.example/").unwrap().tls);
+    }
+
+    #[cfg(feature = "https")]
+    #[test]
+    fn https_requests_speak_tls() {
+        // A plain HTTP server cannot answer a TLS handshake, so the request
+        // fails rather than going out in the clear.
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let server = std::thread::spawn(move || {
+            let (mut socket, _) = listener.accept().unwrap();
+            let mut hello = [0u8; 1];
+            socket.read_exact(&mut hello).unwrap();
+            socket
+                .write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n")
+                .unwrap();
+            hello[0]
+        });
+
+        let url = WebhookUrl::parse(&format!("https://localhost:{port}/hook")).unwrap();
+        assert!(matches!(get(&url), Err(WebhookError::Io(_))));
+        // 0x16 opens a TLS handshake record.
+        assert_eq!(server.join().unwrap(), 0x16);
+    }
+
+    #[cfg(not(feature = "https"))]
+    #[test]
+    fn https_urls_need_the_feature() {
+        assert!(matches!(
+            WebhookUrl::parse("https://example.com/"),
+            Err(WebhookError::HttpsUnavailable(_))
+        ));
+    }
+
+    #[test]
+    fn join_appends_to_base_path() {
+        let base = WebhookUrl::parse("http://otherbox:3000/").unwrap();
+        assert_eq!(base.join("/api/config").path, "/api/config");
+
+        let prefixed = WebhookUrl::parse("http://proxy/musicbox").unwrap();
+        assert_eq!(prefixed.join("/api/config").path, "/musicbox/api/config");
+    }
+
+    #[test]
+    fn get_returns_binary_body() {
+        let server = TestServer::bind();
+        let url = WebhookUrl::parse(&server.url("/track")).unwrap();
+        let server = server.respond([ok([0xff, 0x00, 0xfe])]);
+
+        let response = get(&url).unwrap();
+
+        assert_eq!(response.status, 200);
+        assert_eq!(response.body, vec![0xff, 0x00, 0xfe]);
+        let request = &server.join().unwrap()[0];
+        assert_eq!(
+            (request.method.as_str(), request.path.as_str()),
+            ("GET", "/track")
+        );
+    }
+
+    #[test]
+    fn get_follows_redirects_and_decodes_chunked_bodies() {
+        let server = TestServer::bind();
+        let url = WebhookUrl::parse(&server.url("/podcast")).unwrap();
+        let server = server.respond([
+            &b"HTTP/1.1 302 Found\r\nLocation: /feed.xml\r\nContent-Length: 0\r\n\r\n"[..],
+            b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n4\r\n<rss\r\n2;x=y\r\n/>\r\n0\r\n\r\n",
+        ]);
+
+        let response = get(&url).unwrap();
+
+        assert_eq!(response.status, 200);
+        assert_eq!(response.body, b"<rss/>");
+        let paths: Vec<String> = server
+            .join()
+            .unwrap()
+            .into_iter()
+            .map(|request| request.path)
+            .collect();
+        assert_eq!(paths, ["/podcast", "/feed.xml"]);
+    }
+
+    #[test]
+    fn posts_json_body() {
+        let server = TestServer::bind();
+        let url = WebhookUrl::parse(&server.url("/hook")).unwrap();
+        let server = server.respond([ok("ok")]);
+
+        let response = send_json(&url, &serde_json::json!({ "event": "test" })).unwrap();
+
+        assert_eq!(response.status, 200);
+        assert_eq!(response.body, "ok");
+        assert_eq!(
+            server.join().unwrap(),
+            [Request {
+                method: "POST".into(),
+                path: "/hook".into(),
+                body: r#"{"event":"test"}"#.into(),
+            }]
+        );
+    }
+}
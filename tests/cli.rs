@@ -26,6 +26,209 @@ fn cli_runs_with_noop_reader() {
         .stdout(predicate::str::contains("Reader requested shutdown"));
 }
 
+/// Tests that the LED strip flags parse alongside the default display flags.
+#[cfg(feature = "ws2812-led")]
+#[test]
+fn cli_runs_with_ws2812_flags() {
+    let mut cmd = Command::cargo_bin("musicbox").expect("binary");
+    cmd.arg("examples/config.example.toml")
+        .arg("--reader")
+        .arg("noop")
+        .arg("--poll-interval-ms")
+        .arg("10")
+        .arg("--silent")
+        .arg("--ws2812-count")
+        .arg("12")
+        .env("MUSICBOX_NOOP_SHUTDOWN", "1");
+
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("Reader requested shutdown"));
+}
+
+/// Tests that the volume knob flags parse alongside the default display flags.
+#[cfg(feature = "rotary-knob")]
+#[test]
+fn cli_runs_with_knob_flags() {
+    let mut cmd = Command::cargo_bin("musicbox").expect("binary");
+    cmd.arg("examples/config.example.toml")
+        .arg("--reader")
+        .arg("noop")
+        .arg("--poll-interval-ms")
+        .arg("10")
+        .arg("--silent")
+        .arg("--knob-gpio-chip")
+        .arg("/dev/gpiochip1")
+        .env("MUSICBOX_NOOP_SHUTDOWN", "1");
+
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("Reader requested shutdown"));
+}
+
+/// Tests that `--log-format json` writes one JSON object per line, to the
+/// console and to the log file.
+#[test]
+fn cli_logs_json_lines_to_a_file() {
+    let dir = tempdir().expect("tempdir");
+    let log = dir.path().join("logs/musicbox.log");
+
+    let mut cmd = Command::cargo_bin("musicbox").expect("binary");
+    cmd.arg("examples/config.example.toml")
+        .arg("--reader")
+        .arg("noop")
+        .arg("--poll-interval-ms")
+        .arg("10")
+        .arg("--silent")
+        .arg("--log-file")
+        .arg(&log)
+        .env("MUSICBOX_LOG_FORMAT", "json")
+        .env("MUSICBOX_NOOP_SHUTDOWN", "1");
+    let output = cmd.assert().success().get_output().stdout.clone();
+    let console = String::from_utf8(output).expect("utf-8 stdout");
+    assert!(console.contains(r#""message":"final status""#), "{console}");
+
+    let contents = fs::read_to_string(&log).expect("log file");
+    assert!(contents.contains("final status"));
+    for line in contents.lines() {
+        let entry: serde_json::Value = serde_json::from_str(line).expect("json log line");
+        assert!(entry["level"].is_string(), "{line}");
+        assert!(entry["fields"]["message"].is_string(), "{line}");
+    }
+}
+
+#[test]
+fn cli_history_lists_plays_newest_first() {
+    let dir = tempdir().expect("tempdir");
+    let config = dir.path().join("musicbox.toml");
+    fs::write(
+        &config,
+        format!(
+            "music_dir = {:?}\n[cards]\n\"0a\" = {{ track = \"a.mp3\", name = \"Bedtime\" }}\n",
+            dir.path()
+        ),
+    )
+    .expect("write config");
+    let log = dir.path().join("history.jsonl");
+    let track = dir.path().join("a.mp3");
+    fs::write(
+        &log,
+        format!(
+            "{{\"timestamp\":1,\"event\":\"panic\",\"message\":\"boom\",\"location\":null}}\n\
+             {{\"timestamp\":2,\"event\":\"played\",\"card\":\"0a\",\"track\":{track:?},\"started\":1700000000,\"played_ms\":754000}}\n\
+             {{\"timestamp\":3,\"event\":\"played\",\"card\":\"0b\",\"track\":\"/elsewhere/b.mp3\",\"started\":1700000800,\"played_ms\":5000}}\n"
+        ),
+    )
+    .expect("write history");
+
+    let mut cmd = Command::cargo_bin("musicbox").expect("binary");
+    cmd.arg(&config)
+        .arg("--history-log")
+        .arg(&log)
+        .arg("history");
+    let output = cmd.assert().success().get_output().stdout.clone();
+    let lines: Vec<String> = String::from_utf8(output)
+        .expect("utf-8 stdout")
+        .lines()
+        .map(str::to_string)
+        .collect();
+    assert_eq!(
+        lines,
+        [
+            "2023-11-14T22:26:40Z    0:05  0b  /elsewhere/b.mp3",
+            "2023-11-14T22:13:20Z   12:34  Bedtime (0a)  a.mp3",
+        ]
+    );
+}
+
+#[test]
+fn cli_stats_ranks_cards_and_totals_each_day() {
+    let dir = tempdir().expect("tempdir");
+    let config = dir.path().join("musicbox.toml");
+    fs::write(
+        &config,
+        format!(
+            "music_dir = {:?}\n[cards]\n\"0a\" = {{ track = \"a.mp3\", name = \"Bedtime\" }}\n",
+            dir.path()
+        ),
+    )
+    .expect("write config");
+    let log = dir.path().join("history.jsonl");
+    fs::write(
+        &log,
+        "{\"timestamp\":2,\"event\":\"played\",\"card\":\"0b\",\"track\":\"/b.mp3\",\"started\":1700000000,\"played_ms\":5000}\n\
+         {\"timestamp\":3,\"event\":\"played\",\"card\":\"0a\",\"track\":\"/a.mp3\",\"started\":1700000800,\"played_ms\":754000}\n\
+         {\"timestamp\":4,\"event\":\"played\",\"card\":\"0a\",\"track\":\"/a.mp3\",\"started\":1700172800,\"played_ms\":3600000}\n",
+    )
+    .expect("write history");
+
+    let mut cmd = Command::cargo_bin("musicbox").expect("binary");
+    cmd.arg(&config)
+        .arg("--history-log")
+        .arg(&log)
+        .args(["stats", "--top", "1"])
+        .env("TZ", "UTC");
+    let output = cmd.assert().success().get_output().stdout.clone();
+    let lines: Vec<String> = String::from_utf8(output)
+        .expect("utf-8 stdout")
+        .lines()
+        .map(str::to_string)
+        .collect();
+    assert_eq!(
+        lines,
+        [
+            "3 plays, 1:12:39 in all",
+            "Most played:",
+            "      2    1:12:34  Bedtime (0a)",
+            "Per day:",
+            "  2023-11-14      12:39  2 plays",
+            "  2023-11-15       0:00  0 plays",
+            "  2023-11-16    1:00:00  1 play",
+            "Per week, from Monday:",
+            "  2023-11-13    1:12:39  3 plays",
+        ]
+    );
+}
+
+#[test]
+fn cli_mirrors_the_config_and_restores_it_when_corrupt() {
+    let dir = tempdir().expect("tempdir");
+    let stick = dir.path().join("stick");
+    fs::create_dir(&stick).expect("mirror dir");
+    let config = dir.path().join("musicbox.toml");
+    let contents = format!("music_dir = {:?}\n[cards]\n", dir.path());
+    fs::write(&config, &contents).expect("write config");
+
+    let run = |extra: &[&str]| {
+        let mut cmd = Command::cargo_bin("musicbox").expect("binary");
+        cmd.arg(&config)
+            .args(["--reader", "noop", "--poll-interval-ms", "10", "--silent"])
+            .arg("--mirror-dir")
+            .arg(&stick)
+            .args(extra)
+            .env("MUSICBOX_NOOP_SHUTDOWN", "1");
+        cmd.assert()
+    };
+    run(&[]).success();
+    assert_eq!(
+        fs::read_to_string(stick.join("musicbox.toml")).expect("mirror copy"),
+        contents
+    );
+
+    fs::write(&config, "music_dir = [").expect("corrupt config");
+    run(&[])
+        .failure()
+        .stderr(predicate::str::contains("--restore-from-mirror"));
+    run(&["--restore-from-mirror"])
+        .success()
+        .stderr(predicate::str::contains("Restored"));
+    assert_eq!(fs::read_to_string(&config).expect("restored"), contents);
+    assert_eq!(
+        fs::read_to_string(dir.path().join("musicbox.toml.corrupt")).expect("set aside"),
+        "music_dir = ["
+    );
+}
+
 /// Tests that the CLI falls back to the noop reader when the PC/SC reader is not available.
 #[test]
 fn cli_auto_reader_falls_back_when_pcsc_missing() {
@@ -77,6 +280,246 @@ fn cli_add_command_writes_config() {
     );
 }
 
+/// Tests that `tag list` prints the cards and `tag remove` deletes one by name.
+#[test]
+fn cli_tag_list_and_remove() {
+    let tmp = tempdir().expect("temp dir");
+    let config_path = tmp.path().join("musicbox.toml");
+    fs::write(
+        &config_path,
+        "music_dir = \"/music\"\n\n[cards]\n# Shared cards\n\"0a0b\" = { track = \"lullaby.mp3\", name = \"Lullaby\" }\n\"0c\" = { action = \"next\" }\n",
+    )
+    .expect("write config");
+
+    let mut list = Command::cargo_bin("musicbox").expect("binary");
+    list.arg("tag")
+        .arg("list")
+        .arg("--config")
+        .arg(&config_path);
+    list.assert()
+        .success()
+        .stdout(predicate::str::contains("0a0b (Lullaby) → lullaby.mp3"))
+        .stdout(predicate::str::contains("0c → action next"));
+
+    let mut remove = Command::cargo_bin("musicbox").expect("binary");
+    remove
+        .arg("tag")
+        .arg("remove")
+        .arg("--config")
+        .arg(&config_path)
+        .arg("--card")
+        .arg("lullaby");
+    remove
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Removed card 0a0b"));
+
+    let mut json = Command::cargo_bin("musicbox").expect("binary");
+    json.arg(&config_path).arg("tag").arg("list").arg("--json");
+    let output = json.output().expect("run tag list");
+    let entries: serde_json::Value = serde_json::from_slice(&output.stdout).expect("json");
+    assert_eq!(entries.as_array().map(Vec::len), Some(1));
+    assert_eq!(entries[0]["action"], "next");
+}
+
+/// Tests that `config validate` fails on errors, and on warnings with --strict.
+#[test]
+fn cli_config_validate_reports_problems() {
+    let tmp = tempdir().expect("temp dir");
+    let music = tmp.path().join("music");
+    fs::create_dir_all(&music).expect("music dir");
+    fs::write(music.join("broken.mp3"), b"not audio").expect("write track");
+    let config_path = tmp.path().join("musicbox.toml");
+    fs::write(
+        &config_path,
+        format!(
+            "music_dir = {:?}\n\n[cards]\n\"0a\" = \"gone.mp3\"\n",
+            music.display().to_string()
+        ),
+    )
+    .expect("write config");
+    let validate = |extra: &[&str]| {
+        let mut cmd = Command::cargo_bin("musicbox").expect("binary");
+        cmd.arg(&config_path)
+            .arg("config")
+            .arg("validate")
+            .args(extra);
+        cmd.output().expect("run validate")
+    };
+
+    let output = validate(&[]);
+    assert_eq!(output.status.code(), Some(65));
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("error: card 0a:"));
+    assert!(stdout.contains("track does not exist"));
+    assert!(stdout.contains("warning: broken.mp3: no card plays this file"));
+
+    let output = validate(&["--json"]);
+    let report: serde_json::Value = serde_json::from_slice(&output.stdout).expect("json");
+    assert_eq!(report["diagnostics"][0]["severity"], "error");
+    assert_eq!(report["diagnostics"][0]["card"], "0a");
+
+    fs::write(
+        &config_path,
+        format!("music_dir = {:?}\n\n[cards]\n", music.display().to_string()),
+    )
+    .expect("write config");
+    assert!(validate(&[]).status.success());
+    assert!(!validate(&["--strict"]).status.success());
+}
+
+/// Tests that config and reader failures exit with their own codes.
+#[test]
+fn cli_exit_codes_tell_config_errors_from_missing_readers() {
+    let tmp = tempdir().expect("temp dir");
+    let run = |config: &Path, reader: &str| {
+        let mut cmd = Command::cargo_bin("musicbox").expect("binary");
+        cmd.arg(config).args(["--reader", reader, "--silent"]);
+        cmd.output().expect("run player").status.code()
+    };
+    assert_eq!(run(&tmp.path().join("missing.toml"), "noop"), Some(78));
+
+    let config_path = tmp.path().join("musicbox.toml");
+    fs::write(&config_path, "music_dir = \"/music\"\n[cards\n").expect("write config");
+    assert_eq!(run(&config_path, "noop"), Some(78));
+
+    fs::write(&config_path, "music_dir = \"/music\"\n[cards]\n").expect("write config");
+    // No [pn532] table to open the board from.
+    assert_eq!(run(&config_path, "pn532"), Some(69));
+}
+
+/// Tests that `tag batch` maps each unmapped file in a folder to a card.
+#[test]
+fn cli_tag_batch_maps_every_unmapped_file() {
+    let tmp = tempdir().expect("temp dir");
+    let music = tmp.path().join("music");
+    fs::create_dir_all(music.join("stories")).expect("music dir");
+    for name in ["10 End.mp3", "2 Middle.mp3", "1 Start.mp3", "cover.jpg"] {
+        fs::write(music.join("stories").join(name), b"").expect("write file");
+    }
+    let config_path = tmp.path().join("musicbox.toml");
+    fs::write(
+        &config_path,
+        format!(
+            "music_dir = {:?}\n\n[cards]\n\"0a\" = \"stories/1 Start.mp3\"\n",
+            music.display().to_string()
+        ),
+    )
+    .expect("write config");
+
+    let mut cmd = Command::cargo_bin("musicbox").expect("binary");
+    cmd.arg("tag")
+        .arg("batch")
+        .arg("--config")
+        .arg(&config_path)
+        .arg("--dir")
+        .arg(music.join("stories"))
+        .arg("--reader")
+        .arg("noop");
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "[1/2] Place a card for stories/2 Middle.mp3",
+        ))
+        .stdout(predicate::str::contains(
+            "[2/2] Place a card for stories/10 End.mp3",
+        ))
+        .stdout(predicate::str::contains("Mapped 2 cards"));
+
+    let contents = fs::read_to_string(&config_path).expect("read config");
+    assert_eq!(contents.matches("stories/").count(), 3);
+    assert!(!contents.contains("cover.jpg"));
+}
+
+/// Tests that `tag update` remaps a card and needs --force for playlists.
+#[test]
+fn cli_tag_update_remaps_cards() {
+    let tmp = tempdir().expect("temp dir");
+    let config_path = tmp.path().join("musicbox.toml");
+    fs::write(
+        &config_path,
+        "music_dir = \"/music\"\n\n[cards]\n\"0a0b\" = { track = \"lullaby.mp3\", name = \"Lullaby\" }\n\"0c\" = [\"a.mp3\", \"b.mp3\"]\n\"0d0e\" = { track = \"march.mp3\", name = \"Cafe\" }\n\"cafe\" = \"jazz.mp3\"\n",
+    )
+    .expect("write config");
+    let update = |card: &str, force: bool| {
+        let mut cmd = Command::cargo_bin("musicbox").expect("binary");
+        cmd.arg(&config_path)
+            .arg("tag")
+            .arg("update")
+            .arg("--card")
+            .arg(card)
+            .arg("--track")
+            .arg("waltz.mp3");
+        if force {
+            cmd.arg("--force");
+        }
+        cmd.assert()
+    };
+
+    update("lullaby", false)
+        .success()
+        .stdout(predicate::str::contains("Mapped card 0a0b to waltz.mp3"));
+    update("0c", false)
+        .failure()
+        .stderr(predicate::str::contains("--force"));
+    update("0c", true).success();
+    update("0d", true)
+        .failure()
+        .stderr(predicate::str::contains("not mapped"));
+    // Names win over UIDs, as they do for the player.
+    update(" CAFE ", false)
+        .success()
+        .stdout(predicate::str::contains("Mapped card 0d0e to waltz.mp3"));
+
+    let contents = fs::read_to_string(&config_path).expect("read config");
+    assert!(contents.contains("\"0a0b\" = { track = \"waltz.mp3\", name = \"Lullaby\" }"));
+    assert!(contents.contains("\"0c\" = \"waltz.mp3\""));
+    assert!(contents.contains("\"cafe\" = \"jazz.mp3\""));
+}
+
+/// Tests that guest cards are written with an expiry and pruned once it passes.
+#[test]
+fn cli_guest_cards_expire_and_get_pruned() {
+    let tmp = tempdir().expect("temp dir");
+    let config_path = tmp.path().join("musicbox.toml");
+    fs::write(
+        &config_path,
+        "music_dir = \"/music\"\n\n[cards]\n\"0a0b\" = { track = \"old.mp3\", expires = 2020-01-01T00:00:00Z }\n",
+    )
+    .expect("write config");
+
+    let mut cmd = Command::cargo_bin("musicbox").expect("binary");
+    cmd.arg("add")
+        .arg("--config")
+        .arg(&config_path)
+        .arg("--track")
+        .arg("party.mp3")
+        .arg("--card")
+        .arg("deadbeef")
+        .arg("--reader")
+        .arg("noop")
+        .arg("--skip-tag-write")
+        .arg("--expires")
+        .arg("7d");
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("Card deadbeef expires at "));
+
+    let mut cmd = Command::cargo_bin("musicbox").expect("binary");
+    cmd.arg(&config_path).arg("config").arg("prune-expired");
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("Removed expired card 0a0b"))
+        .stdout(predicate::str::contains("1 expired card(s) removed."));
+
+    let contents = fs::read_to_string(&config_path).expect("read config");
+    assert!(!contents.contains("old.mp3"), "expired card should be gone");
+    assert!(
+        contents.contains("deadbeef = { track = \"party.mp3\", expires = "),
+        "guest card should remain: {contents}"
+    );
+}
+
 /// Tests that `musicbox <CONFIG> tag add` falls back to the positional config path.
 #[test]
 fn cli_tag_add_uses_positional_config() {
@@ -156,3 +599,579 @@ fn cli_add_command_generates_uid_for_noop_reader() {
         "config should reference the requested track"
     );
 }
+
+#[test]
+fn cli_setup_writes_a_loadable_config() {
+    let tmp = tempdir().expect("temp dir");
+    let config_path = tmp.path().join("musicbox.toml");
+
+    let mut cmd = assert_cmd::Command::cargo_bin("musicbox").expect("binary");
+    cmd.arg("setup")
+        .arg("--config")
+        .arg(&config_path)
+        .write_stdin("\n\n\n\n\n\n");
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("Which NFC reader"))
+        .stdout(predicate::str::contains("Start musicbox with: musicbox"));
+
+    let contents = fs::read_to_string(&config_path).expect("setup should write a config");
+    let doc: toml::Value = toml::from_str(&contents).expect("config should be valid TOML");
+    let music_dir = doc["music_dir"].as_str().expect("music_dir");
+    assert!(
+        Path::new(music_dir).is_dir(),
+        "setup should create the music dir"
+    );
+    assert!(doc.get("cards").is_some_and(toml::Value::is_table));
+
+    let mut again = assert_cmd::Command::cargo_bin("musicbox").expect("binary");
+    again
+        .arg("setup")
+        .arg("--config")
+        .arg(&config_path)
+        .write_stdin("\n");
+    again
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Left"));
+}
+
+/// Tests that scripted `add` calls with ULID UIDs never collide.
+#[test]
+fn cli_add_command_generates_distinct_ulid_uids() {
+    let tmp = tempdir().expect("temp dir");
+    let config_path = tmp.path().join("musicbox.toml");
+
+    for index in 0..3 {
+        Command::cargo_bin("musicbox")
+            .expect("binary")
+            .arg("add")
+            .arg("--config")
+            .arg(&config_path)
+            .arg("--track")
+            .arg(format!("songs/{index}.mp3"))
+            .arg("--reader")
+            .arg("noop")
+            .arg("--synthetic-uid")
+            .arg("ulid")
+            .arg("--skip-tag-write")
+            .assert()
+            .success();
+    }
+
+    let contents = fs::read_to_string(&config_path).expect("config written");
+    let doc: toml::Value = toml::from_str(&contents).expect("config should be valid TOML");
+    let cards = doc["cards"].as_table().expect("cards table");
+    assert_eq!(cards.len(), 3);
+    assert!(cards.keys().all(|uid| uid.len() == 32));
+}
+
+/// Tests that `manual trigger --remote` hands the tap to a running instance.
+#[test]
+fn cli_manual_trigger_uses_running_instance() {
+    use std::io::{Read, Write};
+
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").expect("bind fake instance");
+    let addr = listener.local_addr().expect("local addr");
+    let server = std::thread::spawn(move || {
+        let (mut socket, _) = listener.accept().expect("accept");
+        let mut request = Vec::new();
+        let mut buf = [0u8; 1024];
+        while !request.ends_with(b"}") {
+            let read = socket.read(&mut buf).expect("read request");
+            request.extend_from_slice(&buf[..read]);
+        }
+        socket
+            .write_all(b"HTTP/1.1 200 OK\r\n\r\n{\"message\":\"Started deadbeef\"}")
+            .expect("write response");
+        String::from_utf8_lossy(&request).into_owned()
+    });
+
+    let mut cmd = Command::cargo_bin("musicbox").expect("binary");
+    cmd.arg("manual")
+        .arg("trigger")
+        .arg("--remote")
+        .arg(addr.to_string())
+        .arg("deadbeef");
+
+    cmd.assert().success().stdout(predicate::str::contains(
+        "handled trigger: Started deadbeef",
+    ));
+
+    let request = server.join().expect("fake instance");
+    assert!(request.starts_with("POST /api/play"));
+    assert!(request.contains("deadbeef"));
+}
+
+/// Tests that `sync` merges another box's cards and downloads missing tracks.
+#[test]
+fn cli_sync_copies_cards_and_tracks_from_other_box() {
+    use std::io::{Read, Write};
+
+    let tmp = tempdir().expect("temp dir");
+    let music_dir = tmp.path().join("music");
+    let config_path = tmp.path().join("musicbox.toml");
+    fs::write(
+        &config_path,
+        format!(
+            "music_dir = {:?}\n\n[cards]\n\"0a0b\" = \"mine.mp3\"\n",
+            music_dir.display().to_string()
+        ),
+    )
+    .expect("write config");
+
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").expect("bind fake box");
+    let addr = listener.local_addr().expect("local addr");
+    let server = std::thread::spawn(move || {
+        let mut paths = Vec::new();
+        for _ in 0..2 {
+            let (mut socket, _) = listener.accept().expect("accept");
+            let mut request = Vec::new();
+            let mut buf = [0u8; 1024];
+            while !request.ends_with(b"\r\n\r\n") {
+                let read = socket.read(&mut buf).expect("read request");
+                request.extend_from_slice(&buf[..read]);
+            }
+            let request = String::from_utf8_lossy(&request).into_owned();
+            let response: &[u8] = if request.starts_with("GET /api/config ") {
+                b"HTTP/1.1 200 OK\r\n\r\n{\"path\":\"x\",\"contents\":\"music_dir = \\\"/srv\\\"\\n[cards]\\n\\\"0a0b\\\" = \\\"theirs.mp3\\\"\\n\\\"0c0d\\\" = \\\"Lieder/Eins.mp3\\\"\\n\"}"
+            } else {
+                b"HTTP/1.1 200 OK\r\nContent-Length: 4\r\n\r\nOggS"
+            };
+            socket.write_all(response).expect("write response");
+            paths.push(request.lines().next().unwrap_or_default().to_string());
+        }
+        paths
+    });
+
+    let mut cmd = Command::cargo_bin("musicbox").expect("binary");
+    cmd.arg("sync")
+        .arg("--config")
+        .arg(&config_path)
+        .arg("--from")
+        .arg(format!("http://{addr}"))
+        .arg("--with-tracks");
+
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("Added card 0c0d"))
+        .stdout(predicate::str::contains("Conflict: card 0a0b"))
+        .stdout(predicate::str::contains("Downloaded 1 track(s)."));
+
+    let requests = server.join().expect("fake box");
+    assert_eq!(requests[1], "GET /api/tracks/Lieder/Eins.mp3 HTTP/1.1");
+    assert_eq!(
+        fs::read(music_dir.join("Lieder/Eins.mp3")).expect("track downloaded"),
+        b"OggS"
+    );
+    let contents = fs::read_to_string(&config_path).expect("config");
+    assert!(contents.contains("\"0a0b\" = \"mine.mp3\""));
+    assert!(contents.contains("\"0c0d\" = \"Lieder/Eins.mp3\""));
+}
+
+/// Tests that `manual trigger` plays locally when no instance is listening.
+#[test]
+fn cli_manual_trigger_falls_back_to_standalone() {
+    let addr = {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").expect("bind");
+        listener.local_addr().expect("local addr")
+    };
+
+    let mut cmd = Command::cargo_bin("musicbox").expect("binary");
+    cmd.arg("--silent")
+        .arg("manual")
+        .arg("trigger")
+        .arg("--config")
+        .arg("examples/config.example.toml")
+        .arg("--remote")
+        .arg(addr.to_string())
+        .arg("04a0b1c2d3");
+
+    cmd.assert()
+        .success()
+        .stderr(predicate::str::contains("Falling back to standalone"))
+        .stdout(predicate::str::contains("Manual trigger produced action"));
+}
+
+/// Tests that the player listens on its control socket and removes it on
+/// exit, after which `ctl` reports that nothing is listening.
+#[test]
+fn cli_ctl_talks_to_the_control_socket() {
+    let tmp = tempdir().expect("temp dir");
+    let socket = tmp.path().join("musicbox.sock");
+
+    let mut player = Command::cargo_bin("musicbox").expect("binary");
+    player
+        .arg("examples/config.example.toml")
+        .arg("--reader")
+        .arg("noop")
+        .arg("--silent")
+        .arg("--control-socket")
+        .arg(&socket)
+        .env("MUSICBOX_NOOP_SHUTDOWN", "1");
+    player
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Listening for commands on"));
+    assert!(!socket.exists());
+
+    let mut ctl = Command::cargo_bin("musicbox").expect("binary");
+    ctl.arg("--control-socket")
+        .arg(&socket)
+        .arg("ctl")
+        .arg("status");
+    ctl.assert().failure().stderr(predicate::str::contains(
+        "no musicbox instance is listening",
+    ));
+}
+
+/// Tests that `manual script` replays every tap and keeps going past unknown cards.
+#[test]
+fn cli_manual_script_replays_taps() {
+    let tmp = tempdir().expect("temp dir");
+    let script_path = tmp.path().join("demo.txt");
+    fs::write(
+        &script_path,
+        "# demo run\n04a0b1c2d3 10\nwait 5\nabcd1234\nffff\n",
+    )
+    .expect("write script");
+
+    let mut cmd = Command::cargo_bin("musicbox").expect("binary");
+    cmd.arg("--silent")
+        .arg("manual")
+        .arg("script")
+        .arg("--config")
+        .arg("examples/config.example.toml")
+        .arg(&script_path);
+
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("Switched"))
+        .stderr(predicate::str::contains("Script tap failed"))
+        .stdout(predicate::str::contains("Replayed 3 taps (1 failed)."));
+}
+
+/// Tests that `config orphans --stubs` lists unmapped audio files as TOML stubs.
+#[test]
+fn cli_config_orphans_prints_stubs() {
+    let tmp = tempdir().expect("temp dir");
+    let music = tmp.path().join("music");
+    fs::create_dir_all(music.join("album")).expect("music dir");
+    fs::write(music.join("mapped.mp3"), b"").expect("mapped track");
+    fs::write(music.join("album/forgotten.ogg"), b"").expect("orphan track");
+    let config_path = tmp.path().join("musicbox.toml");
+    fs::write(
+        &config_path,
+        format!(
+            "music_dir = {:?}\n\n[cards]\n\"deadbeef\" = \"mapped.mp3\"\n",
+            music.display().to_string()
+        ),
+    )
+    .expect("write config");
+
+    let mut cmd = Command::cargo_bin("musicbox").expect("binary");
+    cmd.arg(&config_path)
+        .arg("config")
+        .arg("orphans")
+        .arg("--stubs");
+
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "# \"<uid>\" = \"album/forgotten.ogg\"",
+        ))
+        .stdout(predicate::str::contains("mapped.mp3").not());
+}
+
+/// Tests that `library scan` adds each new file's placeholder to the config
+/// once, and only prints them with `--dry-run`.
+#[test]
+fn cli_library_scan_adds_placeholders() {
+    let tmp = tempdir().expect("temp dir");
+    let music = tmp.path().join("music");
+    fs::create_dir_all(music.join("album")).expect("music dir");
+    fs::write(music.join("mapped.mp3"), b"").expect("mapped track");
+    fs::write(music.join("album/01.ogg"), b"").expect("new track");
+    let config_path = tmp.path().join("musicbox.toml");
+    let original = format!(
+        "music_dir = {:?}\n\n[cards]\n\"deadbeef\" = \"mapped.mp3\" # favourite\n",
+        music.display().to_string()
+    );
+    fs::write(&config_path, &original).expect("write config");
+    let scan = |extra: &[&str]| {
+        let mut cmd = Command::cargo_bin("musicbox").expect("binary");
+        cmd.arg("library")
+            .arg("scan")
+            .arg("--config")
+            .arg(&config_path)
+            .args(extra);
+        cmd.assert().success()
+    };
+
+    scan(&["--dry-run"]).stdout(predicate::str::contains("+# \"<uid>\" = \"album/01.ogg\""));
+    assert_eq!(fs::read_to_string(&config_path).unwrap(), original);
+
+    scan(&[]).stderr(predicate::str::contains("Added 1 placeholder"));
+    assert_eq!(
+        fs::read_to_string(&config_path).unwrap(),
+        format!("{original}# \"<uid>\" = \"album/01.ogg\"\n")
+    );
+    scan(&[]).stdout(predicate::str::contains("No new audio files"));
+}
+
+/// Tests that `library analyze` measures new tracks once and reports
+/// files it cannot decode.
+#[test]
+fn cli_library_analyze_caches_loudness_beside_tracks() {
+    let tmp = tempdir().expect("temp dir");
+    let music = tmp.path().join("music");
+    fs::create_dir_all(&music).expect("music dir");
+    musicbox::memo::Clip {
+        sample_rate: 8_000,
+        channels: 1,
+        samples: (0..8_000)
+            .map(|n| if n % 8 < 4 { 3_000 } else { -3_000 })
+            .collect(),
+    }
+    .write_wav(&music.join("tone.wav"))
+    .expect("write tone");
+    let config_path = tmp.path().join("musicbox.toml");
+    fs::write(
+        &config_path,
+        format!(
+            "music_dir = {:?}\n[loudness]\n[cards]\n",
+            music.display().to_string()
+        ),
+    )
+    .expect("write config");
+    let analyze = |extra: &[&str]| {
+        let mut cmd = Command::cargo_bin("musicbox").expect("binary");
+        cmd.arg("library")
+            .arg("analyze")
+            .arg("--config")
+            .arg(&config_path)
+            .args(extra);
+        cmd.assert()
+    };
+
+    analyze(&[])
+        .success()
+        .stdout(predicate::str::contains("tone.wav:").and(predicate::str::contains("LUFS")));
+    assert!(music.join("tone.wav.loudness").exists());
+    analyze(&[])
+        .success()
+        .stderr(predicate::str::contains("1 already up to date"));
+    analyze(&["--force", "--quiet"])
+        .success()
+        .stdout(predicate::str::is_empty())
+        .stderr(predicate::str::contains("Analyzed 1 track(s)"));
+
+    fs::write(music.join("broken.mp3"), b"not audio").expect("broken track");
+    analyze(&[])
+        .failure()
+        .stderr(predicate::str::contains("1 track(s) could not be analyzed"));
+}
+
+/// Tests that `manual trigger` accepts a card nickname instead of a UID.
+#[test]
+fn cli_manual_trigger_accepts_card_name() {
+    let mut cmd = Command::cargo_bin("musicbox").expect("binary");
+    cmd.arg("--silent")
+        .arg("manual")
+        .arg("trigger")
+        .arg("--config")
+        .arg("examples/config.example.toml")
+        .arg("album");
+
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("album/track02.ogg"));
+}
+
+/// Tests that `backup` and `restore` carry the config and history log to a new box.
+#[test]
+fn cli_backup_and_restore_round_trip() {
+    let old = tempdir().expect("tempdir");
+    let music_dir = old.path().join("music");
+    fs::create_dir_all(&music_dir).expect("music dir");
+    fs::write(music_dir.join("song.mp3"), b"ID3").expect("track");
+    let config_path = old.path().join("musicbox.toml");
+    fs::write(
+        &config_path,
+        format!(
+            "music_dir = {:?}\n[cards]\n\"04AABBCCDD\" = \"song.mp3\"\n",
+            music_dir
+        ),
+    )
+    .expect("config");
+    let history_path = old.path().join("history.jsonl");
+    fs::write(&history_path, "{\"event\":\"crash\"}\n").expect("history");
+    let archive = old.path().join("box.tar.zst");
+
+    Command::cargo_bin("musicbox")
+        .expect("binary")
+        .arg("--history-log")
+        .arg(&history_path)
+        .arg("backup")
+        .arg(&archive)
+        .arg("--config")
+        .arg(&config_path)
+        .arg("--include-music")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "Backed up 2 file(s) and 1 track(s)",
+        ));
+
+    // A fresh SD card: the history log is gone.
+    fs::remove_file(&history_path).expect("remove history");
+    let new = tempdir().expect("tempdir");
+    let restored_config = new.path().join("musicbox.toml");
+    let restored_history = new.path().join("state/history.jsonl");
+    Command::cargo_bin("musicbox")
+        .expect("binary")
+        .arg("--history-log")
+        .arg(&restored_history)
+        .arg("restore")
+        .arg(&archive)
+        .arg("--config")
+        .arg(&restored_config)
+        .arg("--music-dir")
+        .arg(new.path().join("music"))
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "Restored 2 file(s) and 1 track(s)",
+        ));
+
+    assert_eq!(
+        fs::read_to_string(&restored_config).expect("restored config"),
+        fs::read_to_string(&config_path).expect("original config")
+    );
+    assert_eq!(
+        fs::read(new.path().join("music/song.mp3")).expect("restored track"),
+        b"ID3"
+    );
+    assert!(
+        fs::read_to_string(&restored_history)
+            .expect("restored history")
+            .contains("crash")
+    );
+    assert!(!history_path.exists());
+
+    // The music is back at its original path, so restoring it again needs
+    // --force.
+    Command::cargo_bin("musicbox")
+        .expect("binary")
+        .arg("restore")
+        .arg(&archive)
+        .arg("--config")
+        .arg(new.path().join("other.toml"))
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("--force"));
+}
+
+/// Tests that `verify` flags a track whose contents changed after `--update`.
+#[test]
+fn cli_verify_flags_changed_tracks() {
+    let dir = tempdir().expect("tempdir");
+    let music_dir = dir.path().join("music");
+    fs::create_dir_all(&music_dir).expect("music dir");
+    fs::write(music_dir.join("a.mp3"), b"first").expect("track a");
+    fs::write(music_dir.join("b.mp3"), b"second").expect("track b");
+    let config_path = dir.path().join("musicbox.toml");
+    fs::write(
+        &config_path,
+        format!("music_dir = {:?}\n[cards]\n\"01\" = \"a.mp3\"\n", music_dir),
+    )
+    .expect("config");
+
+    let verify = |extra: &[&str]| {
+        let mut cmd = Command::cargo_bin("musicbox").expect("binary");
+        cmd.arg("verify")
+            .arg("--config")
+            .arg(&config_path)
+            .args(extra);
+        cmd.assert()
+    };
+
+    verify(&["--update"])
+        .success()
+        .stdout(predicate::str::contains(
+            "Recorded checksums for 2 track(s)",
+        ));
+    verify(&[])
+        .success()
+        .stdout(predicate::str::contains("0 changed, 0 missing"));
+
+    fs::write(music_dir.join("b.mp3"), b"secomd").expect("corrupt track");
+    verify(&[])
+        .failure()
+        .stdout(predicate::str::contains("CHANGED  b.mp3"))
+        .stderr(predicate::str::contains("1 track(s) failed verification"));
+}
+
+/// Tests that `--output json` prints one JSON object per command on stdout,
+/// including for failures.
+#[test]
+fn cli_output_json_prints_results_for_scripts() {
+    let tmp = tempdir().expect("temp dir");
+    let config_path = tmp.path().join("musicbox.toml");
+    let run = |args: &[&str]| {
+        let output = Command::cargo_bin("musicbox")
+            .expect("binary")
+            .args(args)
+            .arg("--config")
+            .arg(&config_path)
+            .arg("--output")
+            .arg("json")
+            .output()
+            .expect("run musicbox");
+        let stdout = String::from_utf8(output.stdout).expect("utf-8 stdout");
+        assert_eq!(stdout.lines().count(), 1, "one JSON line: {stdout}");
+        let result: serde_json::Value = serde_json::from_str(&stdout).expect("JSON result");
+        (output.status.success(), result)
+    };
+
+    let (success, added) = run(&[
+        "tag",
+        "add",
+        "--track",
+        "songs/a.mp3",
+        "--card",
+        "0A0B",
+        "--reader",
+        "noop",
+    ]);
+    assert!(success);
+    assert_eq!(added["ok"], true);
+    assert_eq!(added["action"], "mapped");
+    assert_eq!(added["card"], "0a0b");
+    assert_eq!(added["track"], "songs/a.mp3");
+    assert_eq!(added["tag_written"], false);
+
+    let (_, listed) = run(&["tag", "list"]);
+    assert_eq!(listed["cards"][0]["uid"], "0a0b");
+
+    // The silent backend's notes go to stderr, leaving stdout to the result.
+    let (success, triggered) = run(&["--silent", "manual", "trigger", "0a0b"]);
+    assert!(success, "{triggered}");
+    assert_eq!(triggered["handled_by"], "standalone");
+    assert_eq!(triggered["action"]["kind"], "started");
+    assert_eq!(triggered["action"]["card"], "0a0b");
+    assert!(
+        triggered["action"]["track"]
+            .as_str()
+            .is_some_and(|track| track.ends_with("songs/a.mp3"))
+    );
+
+    let (success, failed) = run(&["tag", "remove", "--card", "0c"]);
+    assert!(!success);
+    assert_eq!(failed["ok"], false);
+    assert!(
+        failed["error"]
+            .as_str()
+            .is_some_and(|error| !error.is_empty())
+    );
+}